@@ -0,0 +1,14 @@
+use lb::blocking::Lb;
+
+use crate::Res;
+
+pub fn migrate(lb: &Lb) -> Res<()> {
+    println!("Copying documents from disk to object storage...");
+
+    let migration = lb.admin_migrate_documents()?;
+    println!("copied: {}", migration.copied);
+    println!("already present: {}", migration.already_present);
+    println!("not referenced, so not copied: {}", migration.unreferenced);
+
+    Ok(())
+}
@@ -0,0 +1,83 @@
+use clap::Subcommand;
+use lb::blocking::Lb;
+use lb::model::api::{AccountIdentifier, InviteInfo};
+use lb::model::clock::get_time;
+
+use crate::Res;
+
+const DAY_MILLIS: u64 = 24 * 60 * 60 * 1000;
+
+#[derive(Debug, PartialEq, Eq, Subcommand)]
+pub enum Invite {
+    /// Make a code someone can create an account with
+    Create {
+        /// The code stops working this many days from now
+        #[structopt(short, long)]
+        expires_in_days: Option<u64>,
+
+        /// The code stops working after this many accounts are created with it
+        #[structopt(short, long)]
+        max_uses: Option<u32>,
+
+        /// The code stops working after one account is created with it; overrides --max-uses
+        #[structopt(short, long)]
+        single_use: bool,
+    },
+
+    /// Stop a code from working; accounts already created with it are unaffected
+    Revoke { code: String },
+
+    /// List the codes admins have made, including ones that have expired or been used up
+    List,
+}
+
+pub fn invite(lb: &Lb, invite: Invite) -> Res<()> {
+    match invite {
+        Invite::Create { expires_in_days, max_uses, single_use } => {
+            let expires_at = expires_in_days.map(|days| get_time().0 as u64 + days * DAY_MILLIS);
+            let max_uses = if single_use { Some(1) } else { max_uses };
+
+            let invite = lb.admin_create_invite(expires_at, max_uses)?;
+            println!("{}", invite.code);
+        }
+        Invite::Revoke { code } => lb.admin_revoke_invite(&code)?,
+        Invite::List => {
+            let invites = lb.admin_list_invites()?;
+            if invites.is_empty() {
+                println!("There are no invite codes.");
+            }
+            for invite in invites {
+                print_invite(lb, &invite)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn print_invite(lb: &Lb, invite: &InviteInfo) -> Res<()> {
+    let now = get_time().0 as u64;
+    let created_by = lb
+        .admin_get_account_info(AccountIdentifier::PublicKey(invite.created_by.0))?
+        .username;
+    let uses = match invite.max_uses {
+        Some(max_uses) => format!("{}/{}", invite.uses, max_uses),
+        None => invite.uses.to_string(),
+    };
+    let expires = match invite.expires_at {
+        Some(expires_at) if expires_at <= now => "expired".to_string(),
+        Some(expires_at) => format!("in {} days", (expires_at - now).div_ceil(DAY_MILLIS)),
+        None => "never".to_string(),
+    };
+
+    println!(
+        "{}\tby: {}\tuses: {}\texpires: {}\tusable: {}",
+        invite.code,
+        created_by,
+        uses,
+        expires,
+        invite.is_usable(now)
+    );
+
+    Ok(())
+}
@@ -1,8 +1,10 @@
 mod account;
 mod disappear;
+mod documents;
 mod error;
 mod indexes;
 mod info;
+mod invites;
 mod validate;
 
 use clap::{Parser, Subcommand};
@@ -13,6 +15,7 @@ use lb::model::core_config::Config;
 
 use crate::error::Error;
 use crate::indexes::CliIndex;
+use crate::invites::Invite;
 
 #[derive(Debug, PartialEq, Eq, Parser)]
 pub enum Admin {
@@ -68,6 +71,18 @@ pub enum Admin {
     /// Manually set a user's tier and their subscription information
     #[command(subcommand)]
     SetUserTier(SetUserTier),
+
+    /// Copy document contents from the server's disk to its object storage
+    ///
+    /// Run after switching the server's FILES_BACKEND to s3. Contents already in the bucket are
+    /// skipped, so this can be re-run if it's interrupted.
+    MigrateDocuments,
+
+    /// Manage the invite codes accounts can be created with
+    ///
+    /// Codes from the server's INVITE_CODES also work, but aren't listed or revocable here.
+    #[command(subcommand)]
+    Invite(Invite),
 }
 
 #[derive(Debug, PartialEq, Eq, Subcommand)]
@@ -120,6 +135,8 @@ pub fn main() {
         Admin::FileInfo { id } => info::file(&core, id),
         Admin::RebuildIndex(index) => indexes::rebuild(&core, index),
         Admin::SetUserTier(info) => account::set_user_tier(&core, info),
+        Admin::MigrateDocuments => documents::migrate(&core),
+        Admin::Invite(invite) => invites::invite(&core, invite),
     };
 
     if result.is_err() {
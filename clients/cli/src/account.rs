@@ -13,10 +13,12 @@ use lb_rs::model::api::{PaymentMethod, PaymentPlatform, StripeAccountTier};
 use crate::{core, ensure_account, input};
 
 #[tokio::main]
-pub async fn new(username: String, api_url: ApiUrl) -> CliResult<()> {
+pub async fn new(username: String, api_url: ApiUrl, invite: String) -> CliResult<()> {
     let lb = core().await?;
     println!("generating keys and checking for username availability...");
-    lb.create_account(&username, &api_url.0, true).await?;
+    let invite = Some(invite.as_str()).filter(|invite| !invite.is_empty());
+    lb.create_account_with_invite(&username, &api_url.0, invite, true)
+        .await?;
     println!("account created!");
 
     Ok(())
@@ -2,9 +2,12 @@ use std::cell::Cell;
 use std::fs;
 use std::io::{self, Write};
 use std::path::PathBuf;
+use std::str::FromStr;
 
 use cli_rs::cli_error::{CliError, CliResult};
+use cli_rs::flag::Flag;
 use lb_rs::service::import_export::ImportStatus;
+use lb_rs::service::render::RenderFormat;
 
 use crate::input::find_file;
 use crate::{core, ensure_account_and_root};
@@ -34,7 +37,9 @@ pub async fn import(disk: PathBuf, parent: String) -> CliResult<()> {
 }
 
 #[tokio::main]
-pub async fn export(target: String, dest: PathBuf, force: bool, contents: bool) -> CliResult<()> {
+pub async fn export(
+    target: String, dest: PathBuf, force: bool, contents: bool, format: ExportFormat,
+) -> CliResult<()> {
     let lb = &core().await?;
     ensure_account_and_root(lb).await?;
 
@@ -42,6 +47,10 @@ pub async fn export(target: String, dest: PathBuf, force: bool, contents: bool)
 
     println!("exporting '{}'...", target_file.name);
 
+    if let Some(format) = format.rendered() {
+        return export_rendered(lb, &target_file, dest, force, format).await;
+    }
+
     // Document → explicit file path (dest is not an existing directory): write bytes directly.
     if target_file.is_document() && !dest_is_directory(&dest) {
         return export_document_to_path(lb, target_file.id, dest, force).await;
@@ -92,6 +101,82 @@ pub async fn export(target: String, dest: PathBuf, force: bool, contents: bool)
     Ok(())
 }
 
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// the file's bytes, as they're stored
+    #[default]
+    Raw,
+    Html,
+    Pdf,
+}
+
+impl ExportFormat {
+    fn rendered(self) -> Option<RenderFormat> {
+        match self {
+            ExportFormat::Raw => None,
+            ExportFormat::Html => Some(RenderFormat::Html),
+            ExportFormat::Pdf => Some(RenderFormat::Pdf),
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "raw" => Ok(ExportFormat::Raw),
+            "html" => Ok(ExportFormat::Html),
+            "pdf" => Ok(ExportFormat::Pdf),
+            unsupported => {
+                Err(format!("unsupported export format: {unsupported} (expected html or pdf)"))
+            }
+        }
+    }
+}
+
+pub fn format_flag() -> Flag<'static, ExportFormat> {
+    Flag::new("format")
+        .description("render a markdown document as html or pdf, with its images embedded, instead of exporting it as is")
+        .completor(|prompt| {
+            Ok(["html", "pdf"]
+                .into_iter()
+                .filter(|entry| entry.starts_with(prompt))
+                .map(|s| s.to_string())
+                .collect())
+        })
+}
+
+/// Renders a document into `dest`, or into `dest/<name>.<format>` when dest is a directory.
+async fn export_rendered(
+    lb: &lb_rs::Lb, file: &lb_rs::model::file::File, dest: PathBuf, force: bool,
+    format: RenderFormat,
+) -> CliResult<()> {
+    if !file.is_document() {
+        return Err(CliError::from("--format only applies when exporting a document"));
+    }
+
+    let dest = if dest_is_directory(&dest) {
+        let stem = match file.name.rfind('.') {
+            Some(i) if i > 0 => &file.name[..i],
+            _ => &file.name,
+        };
+        let extension = match format {
+            RenderFormat::Html => "html",
+            RenderFormat::Pdf => "pdf",
+        };
+        dest.join(format!("{stem}.{extension}"))
+    } else {
+        dest
+    };
+    prepare_dest(&dest, force)?;
+
+    let content = lb.export_rendered(file.id, format).await?;
+    fs::write(&dest, content)?;
+    println!("wrote {}", dest.display());
+    Ok(())
+}
+
 fn dest_is_directory(dest: &std::path::Path) -> bool {
     dest.is_dir()
         || dest
@@ -103,6 +188,16 @@ fn dest_is_directory(dest: &std::path::Path) -> bool {
 async fn export_document_to_path(
     lb: &lb_rs::Lb, id: lb_rs::Uuid, dest: PathBuf, force: bool,
 ) -> CliResult<()> {
+    prepare_dest(&dest, force)?;
+
+    let content = lb.read_document(id, true).await?;
+    fs::write(&dest, content)?;
+    println!("wrote {}", dest.display());
+    Ok(())
+}
+
+/// Creates the directory a file is written into and refuses to overwrite it without `--force`.
+fn prepare_dest(dest: &std::path::Path, force: bool) -> CliResult<()> {
     if let Some(parent) = dest.parent() {
         if !parent.as_os_str().is_empty() && !parent.exists() {
             fs::create_dir_all(parent)?;
@@ -115,9 +210,5 @@ async fn export_document_to_path(
             dest.display()
         )));
     }
-
-    let content = lb.read_document(id, true).await?;
-    fs::write(&dest, content)?;
-    println!("wrote {}", dest.display());
     Ok(())
 }
@@ -1,11 +1,13 @@
 use crate::{ensure_account, input};
 use cli_rs::cli_error::{CliError, CliResult};
 use fs_extra::dir::CopyOptions;
+use lb_fs::Backend;
 use lb_fs::fs_impl::Drive;
 use lb_rs::{Lb, model::core_config::Config};
+use std::path::PathBuf;
 
 #[tokio::main]
-pub async fn mount() -> CliResult<()> {
+pub async fn mount(backend: Backend, mountpoint: PathBuf) -> CliResult<()> {
     let mut cfg = Config::cli_config("cli");
     cfg.logs = false;
     let lb = Lb::init(cfg)
@@ -16,7 +18,8 @@ pub async fn mount() -> CliResult<()> {
 
     warning()?;
     copy_data()?;
-    Drive::mount().await?;
+    let mountpoint = Some(mountpoint).filter(|path| !path.as_os_str().is_empty());
+    Drive::mount_with(backend, mountpoint).await?;
     Ok(())
 }
 
@@ -51,8 +54,9 @@ iterations will be more tightly integrated into host programs. lb-fs will sync c
 on startup and then every 30 seconds.
 
 This command will not return and print out logs from the NFS server. Once the server starts it will
-mount a virtual file system to /tmp/lockbook. Ctrl-C'ing this process will shut down the server and
-unmount the file system. Each Ctrl-C will attempt to unmount, you must close all apps that are using
+mount a virtual file system to /tmp/lockbook. With --backend fuse no server is started, and the file
+system is mounted to --mountpoint without needing root. Ctrl-C'ing this process will shut down the
+server and unmount the file system. Each Ctrl-C will attempt to unmount, you must close all apps that are using
 the mount before an unmount will succeed.
 
 Programs that depend on file watching for updates may not see the latest changes from syncs. See #2783
@@ -6,6 +6,7 @@ mod input;
 mod lb_fs;
 mod list;
 mod migrate;
+mod mirror;
 mod share;
 mod stream;
 
@@ -39,8 +40,10 @@ fn run() -> CliResult<()> {
                         .input(Arg::str("username").description("your desired username."))
                         .input(Flag::<ApiUrl>::new("api_url")
                             .description("location of the lockbook server you're trying to use. If not provided will check the API_URL env var, and then fall back to https://app.lockbook.net"))
-                        .handler(|username, api_url| {
-                            account::new(username.get(), api_url.get())
+                        .input(Flag::<String>::new("invite")
+                            .description("an invite code, for servers that only accept accounts by invitation"))
+                        .handler(|username, api_url, invite| {
+                            account::new(username.get(), api_url.get(), invite.get())
                         })
                 )
                 .subcommand(
@@ -131,14 +134,18 @@ fn run() -> CliResult<()> {
                 .input(Arg::<PathBuf>::name("dest").description("directory, or file path when exporting a single document"))
                 .input(Flag::bool("force").description("overwrite existing files on disk"))
                 .input(Flag::bool("contents").description("when exporting a folder, place its children in dest (rsync src/ semantics) instead of dest/<folder-name>"))
-                .handler(|target, dest, force, contents| {
-                    imex::export(target.get(), dest.get(), force.get(), contents.get())
+                .input(imex::format_flag())
+                .handler(|target, dest, force, contents, format| {
+                    imex::export(target.get(), dest.get(), force.get(), contents.get(), format.get())
                 })
         )
         .subcommand(
             Command::name("fs")
                 .description("use your lockbook files with your local filesystem by mounting an NFS drive to /tmp/lockbook")
-                .handler(lb_fs::mount)
+                .input(Flag::<::lb_fs::Backend>::new("backend").description("nfs (the default), or fuse, which doesn't need root")
+                    .completor(|prompt| Ok(["nfs", "fuse"].into_iter().filter(|entry| entry.starts_with(prompt)).map(|s| s.to_string()).collect())))
+                .input(Flag::<PathBuf>::new("mountpoint").description("where to mount with fuse, /tmp/lockbook by default"))
+                .handler(|backend, mountpoint| lb_fs::mount(backend.get(), mountpoint.get()))
         )
         .subcommand(
             Command::name("list").description("list files and file information")
@@ -156,7 +163,8 @@ fn run() -> CliResult<()> {
                             .completor(|prompt| input::file_completor(prompt, None)))
                 .input(Arg::str("dest").description("lockbook file path or ID of the new parent folder")
                             .completor(|prompt| input::file_completor(prompt, Some(Filter::FoldersOnly))))
-                .handler(|src, dst| move_file(src.get(), dst.get()))
+                .input(Flag::bool("update-links").description("rewrite links in other documents so they still point at the file"))
+                .handler(|src, dst, update_links| move_file(src.get(), dst.get(), update_links.get()))
         )
         .subcommand(
             Command::name("new").description("create a new file at the given path or do nothing if it exists")
@@ -184,7 +192,8 @@ fn run() -> CliResult<()> {
                 .input(Arg::str("target").description("lockbook file path or ID of file to rename")
                             .completor(|prompt| input::file_completor(prompt, None)))
                 .input(Arg::str("new_name"))
-                .handler(|target, new_name| rename(target.get(), new_name.get()))
+                .input(Flag::bool("update-links").description("rewrite links in other documents so they still point at the file"))
+                .handler(|target, new_name, update_links| rename(target.get(), new_name.get(), update_links.get()))
         )
         .subcommand(
             Command::name("share").description("sharing related commands")
@@ -218,7 +227,7 @@ fn run() -> CliResult<()> {
         )
         .subcommand(
             Command::name("search").description("search file paths and document contents")
-                .input(Arg::str("query"))
+                .input(Arg::str("query").description("text to search for, narrowed with in:/path, type:md|svg, modified:>2026-01-01, owner:alice, by:bob, shared:yes, #tag, AND, OR and NOT"))
                 .handler(|query| search(&query.get()))
         )
         .subcommand(
@@ -228,6 +237,40 @@ fn run() -> CliResult<()> {
                         .input(Arg::<PathBuf>::name("disk-path").description("location of a bear export of files."))
                         .handler(|path| migrate::bear(path.get()))
                 )
+                .subcommand(
+                    Command::name("obsidian").description("migrate an obsidian vault, keeping its folders and turning ![[embeds]] into links")
+                        .input(Arg::<PathBuf>::name("disk-path").description("location of the vault's folder."))
+                        .input(Arg::str("dest").description("lockbook folder to import into")
+                            .completor(|prompt| input::file_completor(prompt, Some(Filter::FoldersOnly)))
+                            .default("/".to_string()))
+                        .input(Flag::bool("dry-run").description("show what would be imported without importing it"))
+                        .handler(|path, dest, dry_run| migrate::obsidian(path.get(), dest.get(), dry_run.get()))
+                )
+                .subcommand(
+                    Command::name("notion").description("migrate a notion workspace. Export as 'Markdown & CSV' and unzip the export.")
+                        .input(Arg::<PathBuf>::name("disk-path").description("location of the unzipped export."))
+                        .input(Arg::str("dest").description("lockbook folder to import into")
+                            .completor(|prompt| input::file_completor(prompt, Some(Filter::FoldersOnly)))
+                            .default("/".to_string()))
+                        .input(Flag::bool("dry-run").description("show what would be imported without importing it"))
+                        .handler(|path, dest, dry_run| migrate::notion(path.get(), dest.get(), dry_run.get()))
+                )
+                .subcommand(
+                    Command::name("evernote").description("migrate evernote notebooks exported as .enex files")
+                        .input(Arg::<PathBuf>::name("disk-path").description("location of an .enex file, or a folder of them."))
+                        .input(Arg::str("dest").description("lockbook folder to import into")
+                            .completor(|prompt| input::file_completor(prompt, Some(Filter::FoldersOnly)))
+                            .default("/".to_string()))
+                        .input(Flag::bool("dry-run").description("show what would be imported without importing it"))
+                        .handler(|path, dest, dry_run| migrate::evernote(path.get(), dest.get(), dry_run.get()))
+                )
+        )
+        .subcommand(
+            Command::name("mirror").description("keep a folder on disk and a lockbook folder the same, both ways, until stopped")
+                .input(Arg::<PathBuf>::name("local-dir").description("folder on disk, created if it doesn't exist"))
+                .input(Arg::str("lb-folder").description("path or id of the lockbook folder")
+                    .completor(|prompt| input::file_completor(prompt, Some(Filter::FoldersOnly))))
+                .handler(|local, folder| mirror::mirror(local.get(), folder.get()))
         )
         .subcommand(
             Command::name("sync").description("sync your local changes back to lockbook servers") // todo also back
@@ -398,13 +441,25 @@ async fn delete(force: bool, target: String) -> Result<(), CliError> {
 }
 
 #[tokio::main]
-async fn move_file(src: String, dest: String) -> CliResult<()> {
+async fn move_file(src: String, dest: String, update_links: bool) -> CliResult<()> {
     let lb = &core().await?;
     ensure_account_and_root(lb).await?;
 
     let src = find_file(lb, &src).await?;
     let dest = find_file(lb, &dest).await?;
-    lb.move_file(&src.id, &dest.id).await?;
+    if update_links {
+        let updated = lb.move_file_with_link_update(&src.id, &dest.id).await?;
+        print_updated_links(lb, updated).await?;
+    } else {
+        lb.move_file(&src.id, &dest.id).await?;
+    }
+    Ok(())
+}
+
+async fn print_updated_links(lb: &Lb, updated: Vec<Uuid>) -> CliResult<()> {
+    for id in updated {
+        println!("updated links in {}", lb.get_path_by_id(id).await?);
+    }
     Ok(())
 }
 
@@ -430,12 +485,17 @@ async fn create_file(path: String) -> CliResult<()> {
 }
 
 #[tokio::main]
-async fn rename(target: String, new_name: String) -> Result<(), CliError> {
+async fn rename(target: String, new_name: String, update_links: bool) -> Result<(), CliError> {
     let lb = &core().await?;
     ensure_account_and_root(lb).await?;
 
     let id = find_file(lb, &target).await?.id;
-    lb.rename_file(&id, &new_name).await?;
+    if update_links {
+        let updated = lb.rename_file_with_link_update(&id, &new_name).await?;
+        print_updated_links(lb, updated).await?;
+    } else {
+        lb.rename_file(&id, &new_name).await?;
+    }
     Ok(())
 }
 
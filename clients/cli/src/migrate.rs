@@ -1,12 +1,20 @@
+mod evernote;
+mod notion;
+mod obsidian;
+
 use std::{
-    path::PathBuf,
-    sync::atomic::{AtomicU16, Ordering},
+    collections::BTreeMap,
+    env, io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU16, AtomicUsize, Ordering},
 };
 
-use cli_rs::cli_error::CliResult;
+use cli_rs::cli_error::{CliError, CliResult};
 use lb_rs::service::import_export::ImportStatus;
+use lb_rs::{Lb, Uuid};
 use tokio::fs;
 
+use crate::input::find_file;
 use crate::{core, ensure_account_and_root};
 
 use colored::Colorize;
@@ -95,6 +103,273 @@ pub async fn bear(path: PathBuf) -> CliResult<()> {
     Ok(())
 }
 
+#[tokio::main]
+pub async fn obsidian(path: PathBuf, dest: String, dry_run: bool) -> CliResult<()> {
+    import(obsidian::convert(&path)?, dest, dry_run).await
+}
+
+#[tokio::main]
+pub async fn notion(path: PathBuf, dest: String, dry_run: bool) -> CliResult<()> {
+    import(notion::convert(&path)?, dest, dry_run).await
+}
+
+#[tokio::main]
+pub async fn evernote(path: PathBuf, dest: String, dry_run: bool) -> CliResult<()> {
+    import(evernote::convert(&path)?, dest, dry_run).await
+}
+
+/// An export converted for lockbook: the files to import, by their path in the folder that's
+/// created for them, and what had to change on the way.
+#[derive(Default)]
+pub struct Conversion {
+    /// the name of the folder everything is imported into
+    pub name: String,
+    pub files: BTreeMap<String, Vec<u8>>,
+    pub links_rewritten: usize,
+    pub warnings: Vec<String>,
+}
+
+impl Conversion {
+    fn new(name: impl Into<String>) -> Self {
+        Self { name: name.into(), ..Default::default() }
+    }
+
+    /// Adds a file, numbering its name if the path is taken, and returns the path it was added at.
+    fn add(&mut self, path: String, content: Vec<u8>) -> String {
+        let mut candidate = path.clone();
+        let mut n = 1;
+        while self.files.contains_key(&candidate)
+            || self
+                .files
+                .keys()
+                .any(|existing| existing.starts_with(&format!("{candidate}/")))
+        {
+            n += 1;
+            candidate = numbered(&path, n);
+        }
+        self.files.insert(candidate.clone(), content);
+        candidate
+    }
+
+    fn print_summary(&self) {
+        let documents = self
+            .files
+            .keys()
+            .filter(|path| path.ends_with(".md"))
+            .count();
+        let attachments = self.files.len() - documents;
+        let mut folders: Vec<&str> = self.files.keys().filter_map(|path| parent(path)).collect();
+        folders.sort();
+        folders.dedup();
+
+        println!("{}", self.name.green());
+        println!(
+            "  {} documents and {} attachments in {} folders",
+            documents.to_string().blue(),
+            attachments.to_string().blue(),
+            (folders.len() + 1).to_string().blue()
+        );
+        println!("  {} links rewritten", self.links_rewritten.to_string().blue());
+        for warning in &self.warnings {
+            println!("  {}", warning.yellow());
+        }
+    }
+}
+
+/// Imports a conversion into `dest`, unless it's a dry run.
+async fn import(conversion: Conversion, dest: String, dry_run: bool) -> CliResult<()> {
+    conversion.print_summary();
+    if dry_run {
+        println!("dry run, nothing was imported");
+        return Ok(());
+    }
+
+    let lb = core().await?;
+    ensure_account_and_root(&lb).await?;
+    let dest_folder = find_file(&lb, &dest).await?;
+    if !dest_folder.is_folder() {
+        return Err(CliError::from(format!("'{dest}' is not a folder")));
+    }
+
+    let imported = import_into(&lb, &conversion, dest_folder.id).await?;
+    println!("{} files imported", imported.to_string().blue());
+    Ok(())
+}
+
+/// Writes a conversion out to a temporary folder and imports it into the folder `dest`, returning
+/// how many documents were imported.
+async fn import_into(lb: &Lb, conversion: &Conversion, dest: Uuid) -> CliResult<usize> {
+    let staging = env::temp_dir().join(format!("lockbook-migrate-{}", Uuid::new_v4()));
+    let root = staging.join(&conversion.name);
+    let result = async {
+        for (path, content) in &conversion.files {
+            let path = root.join(path);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).await?;
+            }
+            fs::write(path, content).await?;
+        }
+        fs::create_dir_all(&root).await?;
+
+        let imported = AtomicUsize::new(0);
+        let f = |status: ImportStatus| {
+            if let ImportStatus::FinishedItem(file) = status {
+                if file.is_document() {
+                    imported.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        };
+        lb.import_files(std::slice::from_ref(&root), dest, &f)
+            .await?;
+        Ok::<usize, CliError>(imported.load(Ordering::Relaxed))
+    }
+    .await;
+
+    let _ = fs::remove_dir_all(&staging).await;
+    result
+}
+
+/// The paths of the files in a folder relative to it, skipping hidden files and folders like
+/// `.obsidian` and `.git`.
+fn walk(root: &Path) -> io::Result<Vec<String>> {
+    let mut paths = vec![];
+    let mut folders = vec![root.to_path_buf()];
+    while let Some(folder) = folders.pop() {
+        for entry in std::fs::read_dir(&folder)? {
+            let path = entry?.path();
+            let Some(name) = path.file_name().and_then(|name| name.to_str()) else { continue };
+            if name.starts_with('.') {
+                continue;
+            }
+            if path.is_dir() {
+                folders.push(path);
+            } else if let Ok(relative) = path.strip_prefix(root) {
+                let relative: Vec<&str> = relative
+                    .components()
+                    .filter_map(|c| c.as_os_str().to_str())
+                    .collect();
+                paths.push(relative.join("/"));
+            }
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// The name of the folder an export is in, used as the name of the folder it's imported into.
+fn folder_name(path: &Path) -> String {
+    path.canonicalize()
+        .ok()
+        .and_then(|path| {
+            path.file_stem()
+                .map(|name| name.to_string_lossy().into_owned())
+        })
+        .unwrap_or_else(|| "Imported".into())
+}
+
+fn parent(path: &str) -> Option<&str> {
+    path.rfind('/').map(|i| &path[..i])
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// `a/b.md` numbered 2 is `a/b 2.md`
+fn numbered(path: &str, n: usize) -> String {
+    let name_start = path.rfind('/').map_or(0, |i| i + 1);
+    match path[name_start..].rfind('.') {
+        Some(dot) if dot > 0 => {
+            let dot = name_start + dot;
+            format!("{} {n}{}", &path[..dot], &path[dot..])
+        }
+        _ => format!("{path} {n}"),
+    }
+}
+
+/// `to` as a path relative to the folder `from`, both relative to the root of the export.
+fn relative(from: Option<&str>, to: &str) -> String {
+    let from: Vec<&str> = from
+        .map(|from| from.split('/').collect())
+        .unwrap_or_default();
+    let to: Vec<&str> = to.split('/').collect();
+    let common = from
+        .iter()
+        .zip(&to)
+        .take_while(|(a, b)| a == b)
+        .count()
+        .min(to.len() - 1);
+    let mut path: Vec<&str> = vec![".."; from.len() - common];
+    path.extend(&to[common..]);
+    path.join("/")
+}
+
+/// `path`, relative to the folder `from`, as a path relative to the root of the export. `None`
+/// if it climbs out of the export.
+fn resolve(from: Option<&str>, path: &str) -> Option<String> {
+    let mut components: Vec<&str> = from
+        .map(|from| from.split('/').collect())
+        .unwrap_or_default();
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop()?;
+            }
+            component => components.push(component),
+        }
+    }
+    Some(components.join("/"))
+}
+
+/// A path as a markdown link destination, in angle brackets if it has characters that would end
+/// it otherwise.
+fn destination(path: &str) -> String {
+    if path.contains([' ', '(', ')']) { format!("<{path}>") } else { path.to_string() }
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            if let Some(byte) = text
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Whether a link points outside of the export, like a web page, or within the same document.
+fn is_external(target: &str) -> bool {
+    target.starts_with('#')
+        || target.starts_with('/')
+        || target.contains("://")
+        || target.starts_with("mailto:")
+}
+
+/// Replaces ranges of `text`, which must be sorted and not overlap.
+fn replace_ranges(text: &str, replacements: Vec<(std::ops::Range<usize>, String)>) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut last = 0;
+    for (range, replacement) in replacements {
+        result.push_str(&text[last..range.start]);
+        result.push_str(&replacement);
+        last = range.end;
+    }
+    result.push_str(&text[last..]);
+    result
+}
+
 fn candidate_locations_from_content(contents: &str) -> Vec<String> {
     let mut prev_char: Option<char> = None;
     let mut in_tag = false;
@@ -144,6 +419,54 @@ fn candidate_locations_from_content(contents: &str) -> Vec<String> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use test_utils::test_core_with_account;
+
+    #[test]
+    fn relative_paths() {
+        assert_eq!(relative(None, "a/b.png"), "a/b.png");
+        assert_eq!(relative(Some("a"), "a/b.png"), "b.png");
+        assert_eq!(relative(Some("a/c"), "a/b.png"), "../b.png");
+        assert_eq!(relative(Some("x"), "b.png"), "../b.png");
+        assert_eq!(resolve(Some("a/c"), "../b.png"), Some("a/b.png".into()));
+        assert_eq!(resolve(None, "../b.png"), None);
+    }
+
+    #[tokio::test]
+    async fn imports_read_back_as_converted() {
+        let vault = env::temp_dir().join(format!("vault-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(vault.join("attachments")).unwrap();
+        std::fs::create_dir_all(vault.join(".obsidian")).unwrap();
+        std::fs::write(vault.join("readme.md"), "![[photo.jpg]]").unwrap();
+        std::fs::write(vault.join("attachments/photo.jpg"), [1, 2, 3]).unwrap();
+        std::fs::write(vault.join(".obsidian/app.json"), "{}").unwrap();
+
+        let conversion = obsidian::convert(&vault).unwrap();
+        std::fs::remove_dir_all(&vault).unwrap();
+        assert_eq!(conversion.files["readme.md"], b"![photo.jpg](attachments/photo.jpg)");
+
+        let lb = test_core_with_account().await;
+        let root = lb.root().await.unwrap();
+        assert_eq!(import_into(&lb, &conversion, root.id).await.unwrap(), 2);
+
+        for (path, content) in &conversion.files {
+            let file = lb
+                .get_by_path(&format!("{}/{path}", conversion.name))
+                .await
+                .unwrap();
+            assert_eq!(&lb.read_document(file.id, false).await.unwrap(), content);
+        }
+        assert!(
+            lb.get_by_path(&format!("{}/.obsidian/app.json", conversion.name))
+                .await
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn numbered_paths() {
+        assert_eq!(numbered("a/b.md", 2), "a/b 2.md");
+        assert_eq!(numbered("a.b/c", 3), "a.b/c 3");
+    }
 
     #[test]
     fn get_candidate_locations_from_empty_content() {
@@ -0,0 +1,579 @@
+//! Evernote exports notebooks as `.enex` files: xml with each note's content in ENML, Evernote's
+//! subset of xhtml, and its attachments ("resources") inline in base64. Notes become markdown
+//! with their creation date and tags in front matter, and attachments become files in an
+//! `attachments` folder beside them, which the notes link to where ENML placed them with
+//! `<en-media>`.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use cli_rs::cli_error::{CliError, CliResult};
+use roxmltree::{Document, Node, ParsingOptions};
+
+use super::{Conversion, destination, folder_name, parent, relative};
+
+/// An `.enex` file is imported as a folder named after it; a folder of them as a folder with one
+/// for each.
+pub fn convert(path: &Path) -> CliResult<Conversion> {
+    if path.is_file() {
+        let mut conversion = Conversion::new(folder_name(path));
+        notebook(&fs::read_to_string(path)?, "", &mut conversion)?;
+        return Ok(conversion);
+    }
+
+    let mut notebooks: Vec<_> = fs::read_dir(path)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "enex")
+        })
+        .collect();
+    if notebooks.is_empty() {
+        return Err(CliError::from("expected an .enex file, or a folder of them"));
+    }
+    notebooks.sort();
+
+    let mut conversion = Conversion::new(folder_name(path));
+    for notebook_path in notebooks {
+        let prefix = format!("{}/", folder_name(&notebook_path));
+        notebook(&fs::read_to_string(notebook_path)?, &prefix, &mut conversion)?;
+    }
+    Ok(conversion)
+}
+
+fn notebook(enex: &str, prefix: &str, conversion: &mut Conversion) -> CliResult<()> {
+    let enex = Document::parse_with_options(enex, xml_options())
+        .map_err(|err| CliError::from(format!("couldn't read evernote export: {err}")))?;
+    for node in enex
+        .root_element()
+        .children()
+        .filter(|node| node.has_tag_name("note"))
+    {
+        note(node, prefix, conversion);
+    }
+    Ok(())
+}
+
+fn note(note: Node, prefix: &str, conversion: &mut Conversion) {
+    let title = child_text(note, "title").trim().replace(['/', '\\'], "-");
+    let title = if title.is_empty() { "Untitled".to_string() } else { title };
+    let path = conversion.add(format!("{prefix}{title}.md"), vec![]);
+
+    // attachments, by the md5 of their contents, which is how ENML refers to them
+    let mut attachments = HashMap::new();
+    for resource in note.children().filter(|node| node.has_tag_name("resource")) {
+        let data: String = child_text(resource, "data")
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect();
+        let Ok(data) = base64::decode(data) else {
+            conversion
+                .warnings
+                .push(format!("{path}: skipped an attachment that couldn't be decoded"));
+            continue;
+        };
+        let hash = format!("{:x}", md5::compute(&data));
+        let mime = child_text(resource, "mime");
+        let name = resource
+            .children()
+            .find(|node| node.has_tag_name("resource-attributes"))
+            .map(|attributes| {
+                child_text(attributes, "file-name")
+                    .trim()
+                    .replace(['/', '\\'], "-")
+            })
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| format!("{}.{}", &hash[..8], extension(&mime)));
+        let attachment = conversion.add(format!("{prefix}attachments/{name}"), data);
+        attachments
+            .insert(hash, Attachment { path: attachment, image: mime.starts_with("image/") });
+    }
+
+    let content = child_text(note, "content");
+    let mut md = front_matter(note);
+    match Document::parse_with_options(&named_entities(&content), xml_options()) {
+        Ok(enml) => {
+            let mut converter =
+                Enml { attachments: &attachments, folder: parent(&path), in_item: false };
+            md.push_str(&converter.markdown(enml.root_element()));
+        }
+        Err(err) => {
+            conversion
+                .warnings
+                .push(format!("{path}: couldn't read the note's content ({err}), kept it as is"));
+            md.push_str(&content);
+        }
+    }
+    if content.contains("<en-crypt") {
+        conversion
+            .warnings
+            .push(format!("{path}: encrypted text can't be imported and was left out"));
+    }
+    conversion.files.insert(path, md.into_bytes());
+}
+
+fn xml_options() -> ParsingOptions {
+    ParsingOptions { allow_dtd: true, ..ParsingOptions::default() }
+}
+
+fn child_text(node: Node, name: &str) -> String {
+    node.children()
+        .find(|child| child.has_tag_name(name))
+        .and_then(|child| child.text())
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// ENML can use html's named entities, which an xml parser only knows from a dtd it won't
+/// download.
+fn named_entities(enml: &str) -> String {
+    const ENTITIES: [(&str, &str); 13] = [
+        ("&nbsp;", "&#160;"),
+        ("&ndash;", "&#8211;"),
+        ("&mdash;", "&#8212;"),
+        ("&hellip;", "&#8230;"),
+        ("&lsquo;", "&#8216;"),
+        ("&rsquo;", "&#8217;"),
+        ("&ldquo;", "&#8220;"),
+        ("&rdquo;", "&#8221;"),
+        ("&bull;", "&#8226;"),
+        ("&middot;", "&#183;"),
+        ("&copy;", "&#169;"),
+        ("&reg;", "&#174;"),
+        ("&trade;", "&#8482;"),
+    ];
+    let mut enml = enml.to_string();
+    for (name, number) in ENTITIES {
+        enml = enml.replace(name, number);
+    }
+    enml
+}
+
+fn extension(mime: &str) -> &str {
+    match mime {
+        "image/jpeg" => "jpg",
+        "image/svg+xml" => "svg",
+        "application/pdf" => "pdf",
+        "text/plain" => "txt",
+        mime => mime
+            .rsplit('/')
+            .next()
+            .filter(|subtype| subtype.chars().all(|c| c.is_ascii_alphanumeric()))
+            .unwrap_or("bin"),
+    }
+}
+
+/// when a note was created and its tags, which lockbook has nowhere else to keep
+fn front_matter(note: Node) -> String {
+    let created = child_text(note, "created");
+    let tags: Vec<String> = note
+        .children()
+        .filter(|node| node.has_tag_name("tag"))
+        .filter_map(|tag| tag.text())
+        .map(|tag| {
+            if tag
+                .chars()
+                .all(|c| c.is_alphanumeric() || matches!(c, ' ' | '-' | '_'))
+            {
+                tag.to_string()
+            } else {
+                format!("{tag:?}")
+            }
+        })
+        .collect();
+    if created.is_empty() && tags.is_empty() {
+        return String::new();
+    }
+
+    let mut front_matter = String::from("---\n");
+    if created.len() == 16 && created.is_ascii() {
+        // 20240131T235959Z
+        front_matter.push_str(&format!(
+            "created: {}-{}-{}T{}:{}:{}Z\n",
+            &created[0..4],
+            &created[4..6],
+            &created[6..8],
+            &created[9..11],
+            &created[11..13],
+            &created[13..15]
+        ));
+    }
+    if !tags.is_empty() {
+        front_matter.push_str(&format!("tags: [{}]\n", tags.join(", ")));
+    }
+    front_matter.push_str("---\n\n");
+    front_matter
+}
+
+struct Attachment {
+    path: String,
+    image: bool,
+}
+
+struct Enml<'a> {
+    attachments: &'a HashMap<String, Attachment>,
+    /// the folder of the note, which links to attachments are relative to
+    folder: Option<&'a str>,
+    in_item: bool,
+}
+
+impl Enml<'_> {
+    fn markdown(&mut self, note: Node) -> String {
+        let md = self.children(note);
+        let mut tidy = String::with_capacity(md.len());
+        let mut blank_lines = 0;
+        for line in md.trim().lines() {
+            let line = line.trim_end_matches(' ');
+            blank_lines = if line.is_empty() { blank_lines + 1 } else { 0 };
+            if blank_lines < 2 {
+                tidy.push_str(line);
+                tidy.push('\n');
+            }
+        }
+        tidy
+    }
+
+    fn children(&mut self, node: Node) -> String {
+        let mut md = String::new();
+        for child in node.children() {
+            self.node(child, &mut md);
+        }
+        md
+    }
+
+    fn node(&mut self, node: Node, md: &mut String) {
+        if node.is_text() {
+            let text = collapse_whitespace(node.text().unwrap_or_default());
+            // like a browser, nothing shows whitespace at the start of a line
+            let text = if md.is_empty() || md.ends_with('\n') { text.trim_start() } else { &text };
+            md.push_str(&escape(text));
+            return;
+        }
+        if !node.is_element() {
+            return;
+        }
+
+        let style = node.attribute("style").unwrap_or_default();
+        match node.tag_name().name() {
+            _ if style.contains("-en-codeblock:true") => code_block(&plain_text(node), md),
+            "pre" => code_block(&plain_text(node), md),
+            "div" | "p" | "en-note" | "center" | "section" | "article" | "header" | "footer" => {
+                let mut inner = self.children(node);
+                // a trailing break ends the line rather than adding one
+                while inner.trim_end_matches(' ').ends_with("\\\n") {
+                    inner.truncate(inner.trim_end_matches(' ').len() - 2);
+                }
+                separate(md);
+                md.push_str(inner.trim());
+                separate(md);
+            }
+            heading @ ("h1" | "h2" | "h3" | "h4" | "h5" | "h6") => {
+                let level = heading[1..].parse().unwrap_or(1);
+                let inner = self.children(node).replace("\\\n", " ").replace('\n', " ");
+                separate(md);
+                md.push_str(&format!("{} {}", "#".repeat(level), inner.trim()));
+                separate(md);
+            }
+            "br" => md.push_str("\\\n"),
+            "hr" => {
+                separate(md);
+                md.push_str("---");
+                separate(md);
+            }
+            "b" | "strong" => self.wrap(node, "**", md),
+            "i" | "em" => self.wrap(node, "*", md),
+            "u" => self.wrap(node, "__", md),
+            "s" | "strike" | "del" => self.wrap(node, "~~", md),
+            "code" => {
+                let code = plain_text(node);
+                if !code.is_empty() {
+                    let fence = if code.contains('`') { "``" } else { "`" };
+                    md.push_str(&format!("{fence}{code}{fence}"));
+                }
+            }
+            "sup" => self.wrap(node, "^", md),
+            "sub" => self.wrap(node, "~", md),
+            "a" => {
+                let text = self.children(node);
+                match node.attribute("href").filter(|href| !href.is_empty()) {
+                    Some(href) if !text.trim().is_empty() => {
+                        md.push_str(&format!("[{}]({})", text.trim(), destination(href)))
+                    }
+                    _ => md.push_str(&text),
+                }
+            }
+            "ul" => self.list(node, false, md),
+            "ol" => self.list(node, true, md),
+            "blockquote" => {
+                let inner = self.children(node);
+                separate(md);
+                for line in inner.trim().lines() {
+                    md.push_str(if line.is_empty() { ">" } else { "> " });
+                    md.push_str(line);
+                    md.push('\n');
+                }
+                separate(md);
+            }
+            "table" => self.table(node, md),
+            "en-todo" => {
+                let checkbox =
+                    if node.attribute("checked") == Some("true") { "[x] " } else { "[ ] " };
+                if !self.in_item {
+                    md.push_str("- ");
+                }
+                md.push_str(checkbox);
+            }
+            "en-media" => {
+                let Some(attachment) = node
+                    .attribute("hash")
+                    .and_then(|hash| self.attachments.get(hash))
+                else {
+                    return;
+                };
+                let name = attachment.path.rsplit('/').next().unwrap_or_default();
+                let to = destination(&relative(self.folder, &attachment.path));
+                let bang = if attachment.image { "!" } else { "" };
+                md.push_str(&format!("{bang}[{}]({to})", escape(name)));
+            }
+            "img" => {
+                if let Some(src) = node.attribute("src").filter(|src| src.starts_with("http")) {
+                    let alt = node.attribute("alt").unwrap_or_default();
+                    md.push_str(&format!("![{}]({})", escape(alt), destination(src)));
+                }
+            }
+            "en-crypt" | "style" | "script" | "head" | "title" => {}
+            _ => {
+                let inner = self.children(node);
+                md.push_str(&inner);
+            }
+        }
+    }
+
+    /// Surrounds formatted text with its markers, which can't have spaces on their inside.
+    fn wrap(&mut self, node: Node, marker: &str, md: &mut String) {
+        let inner = self.children(node);
+        let trimmed = inner.trim();
+        if trimmed.is_empty() {
+            md.push_str(&inner);
+            return;
+        }
+        let start = inner.len() - inner.trim_start().len();
+        let end = inner.trim_end().len();
+        md.push_str(&inner[..start]);
+        md.push_str(&format!("{marker}{trimmed}{marker}"));
+        md.push_str(&inner[end..]);
+    }
+
+    fn list(&mut self, node: Node, ordered: bool, md: &mut String) {
+        separate(md);
+        let mut number = 1;
+        for item in node.children().filter(|child| child.is_element()) {
+            let inner = if item.has_tag_name("li") {
+                let was_in_item = std::mem::replace(&mut self.in_item, true);
+                let inner = self.children(item);
+                self.in_item = was_in_item;
+                inner
+            } else {
+                // a list nested directly in a list belongs to the item before it
+                let mut inner = String::new();
+                self.node(item, &mut inner);
+                let indent = if ordered { "   " } else { "  " };
+                for line in inner.trim().lines().filter(|line| !line.trim().is_empty()) {
+                    md.push_str(indent);
+                    md.push_str(line);
+                    md.push('\n');
+                }
+                continue;
+            };
+
+            let marker = if ordered { format!("{number}. ") } else { "- ".to_string() };
+            number += 1;
+            let mut lines = inner.trim().lines().filter(|line| !line.trim().is_empty());
+            md.push_str(&marker);
+            md.push_str(lines.next().unwrap_or_default().trim_end_matches('\\'));
+            md.push('\n');
+            for line in lines {
+                md.push_str(&" ".repeat(marker.len()));
+                md.push_str(line);
+                md.push('\n');
+            }
+        }
+        separate(md);
+    }
+
+    fn table(&mut self, node: Node, md: &mut String) {
+        let mut rows = vec![];
+        for row in node.descendants().filter(|node| node.has_tag_name("tr")) {
+            let cells: Vec<String> = row
+                .children()
+                .filter(|cell| cell.has_tag_name("td") || cell.has_tag_name("th"))
+                .map(|cell| {
+                    let text = self.children(cell).replace("\\\n", " ").replace('\n', " ");
+                    text.trim().replace('|', "\\|")
+                })
+                .collect();
+            rows.push(cells);
+        }
+        let columns = rows.iter().map(Vec::len).max().unwrap_or_default();
+        if columns == 0 {
+            return;
+        }
+
+        separate(md);
+        for (i, row) in rows.iter().enumerate() {
+            md.push('|');
+            for column in 0..columns {
+                md.push_str(&format!(" {} |", row.get(column).map_or("", String::as_str)));
+            }
+            md.push('\n');
+            if i == 0 {
+                md.push('|');
+                md.push_str(&" --- |".repeat(columns));
+                md.push('\n');
+            }
+        }
+        separate(md);
+    }
+}
+
+/// Ends what's been written with a blank line, so that what's written next is a new block.
+fn separate(md: &mut String) {
+    if md.trim().is_empty() {
+        md.clear();
+        return;
+    }
+    while !md.ends_with("\n\n") {
+        md.push('\n');
+    }
+}
+
+fn code_block(code: &str, md: &mut String) {
+    let code = code.trim_matches('\n');
+    let fence = if code.contains("```") { "~~~" } else { "```" };
+    separate(md);
+    md.push_str(&format!("{fence}\n{code}\n{fence}"));
+    separate(md);
+}
+
+/// the text of a node as it's laid out, for code, where each line is a `<div>`
+fn plain_text(node: Node) -> String {
+    let mut text = String::new();
+    for child in node.children() {
+        if child.is_text() {
+            text.push_str(child.text().unwrap_or_default());
+        } else if child.has_tag_name("br") {
+            text.push('\n');
+        } else if child.has_tag_name("div") || child.has_tag_name("p") {
+            let inner = plain_text(child);
+            if !text.is_empty() && !text.ends_with('\n') {
+                text.push('\n');
+            }
+            text.push_str(inner.trim_end_matches('\n'));
+            text.push('\n');
+        } else {
+            text.push_str(&plain_text(child));
+        }
+    }
+    text.replace('\u{a0}', " ")
+}
+
+/// Whitespace in html is a single space, wherever it is and however much there is of it.
+fn collapse_whitespace(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut in_whitespace = false;
+    for c in text.chars() {
+        if c.is_whitespace() && c != '\u{a0}' {
+            if !in_whitespace {
+                collapsed.push(' ');
+            }
+            in_whitespace = true;
+        } else {
+            collapsed.push(if c == '\u{a0}' { ' ' } else { c });
+            in_whitespace = false;
+        }
+    }
+    collapsed
+}
+
+/// Escapes what would be read as markdown that isn't there in the note.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '*' | '`' | '[' | ']' | '<' | '>') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn markdown(enml: &str, attachments: &HashMap<String, Attachment>) -> String {
+        let enml = Document::parse_with_options(enml, xml_options()).unwrap();
+        Enml { attachments, folder: Some("Notebook"), in_item: false }.markdown(enml.root_element())
+    }
+
+    #[test]
+    fn enml_to_markdown() {
+        let enml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE en-note SYSTEM "http://xml.evernote.com/pub/enml2.dtd">
+<en-note><h1>Plans</h1><div>Some <b>bold</b> and <i>italic </i>text [1]</div><div><br/></div>
+<div><en-todo checked="true"/>done</div><div><en-todo/>not yet</div>
+<ul><li>one</li><li><div>two</div><ul><li>nested</li></ul></li></ul>
+<ol><li>first</li><li>second</li></ol>
+<div>see <a href="https://lockbook.net">lockbook</a><br/>line two</div>
+<div style="box-sizing: border-box; -en-codeblock:true;"><div>fn main() {</div><div>}</div></div>
+<table><tr><td>a</td><td>b|c</td></tr><tr><td>1</td><td>2</td></tr></table>
+<en-media hash="abc" type="image/png"/></en-note>"#;
+        let attachments = HashMap::from([(
+            "abc".to_string(),
+            Attachment { path: "Notebook/attachments/my image.png".into(), image: true },
+        )]);
+        assert_eq!(
+            markdown(enml, &attachments),
+            "# Plans\n\n\
+             Some **bold** and *italic* text \\[1\\]\n\n\
+             - [x] done\n\n\
+             - [ ] not yet\n\n\
+             - one\n- two\n  - nested\n\n\
+             1. first\n2. second\n\n\
+             see [lockbook](https://lockbook.net)\\\nline two\n\n\
+             ```\nfn main() {\n}\n```\n\n\
+             | a | b\\|c |\n| --- | --- |\n| 1 | 2 |\n\n\
+             ![my image.png](<attachments/my image.png>)\n"
+        );
+    }
+
+    #[test]
+    fn notes_and_attachments() {
+        let data = b"not really a png";
+        let enex = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE en-export SYSTEM "http://xml.evernote.com/pub/evernote-export4.dtd">
+<en-export><note><title>Trip / Plans</title><created>20240131T235959Z</created><tag>travel</tag>
+<content><![CDATA[<?xml version="1.0" encoding="UTF-8"?><en-note><div>hi&nbsp;there</div><en-media hash="{:x}" type="image/png"/></en-note>]]></content>
+<resource><data encoding="base64">{}</data><mime>image/png</mime></resource></note>
+<note><title>Trip / Plans</title><content><![CDATA[<en-note/>]]></content></note></en-export>"#,
+            md5::compute(data),
+            base64::encode(data)
+        );
+        let mut conversion = Conversion::default();
+        notebook(&enex, "", &mut conversion).unwrap();
+
+        let hash = format!("{:x}", md5::compute(data));
+        let attachment = format!("attachments/{}.png", &hash[..8]);
+        assert_eq!(conversion.files[&attachment], data);
+        assert_eq!(
+            String::from_utf8_lossy(&conversion.files["Trip - Plans.md"]),
+            format!(
+                "---\ncreated: 2024-01-31T23:59:59Z\ntags: [travel]\n---\n\nhi there\n\n![{}.png]({attachment})\n",
+                &hash[..8]
+            )
+        );
+        assert!(conversion.files.contains_key("Trip - Plans 2.md"));
+    }
+}
@@ -0,0 +1,282 @@
+//! Notion's markdown & CSV export names every page and folder after the page with its id on the
+//! end, like `Plans 0123456789abcdef0123456789abcdef.md`, and links to pages by those names. Ids
+//! are dropped from names and links are pointed at the renamed files. Databases are exported as
+//! CSV, which become markdown tables linking to the pages of their rows.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use cli_rs::cli_error::{CliError, CliResult};
+use lb_rs::service::links::{LinkKind, parse_links};
+
+use super::{
+    Conversion, destination, file_name, folder_name, is_external, numbered, parent, percent_decode,
+    relative, replace_ranges, resolve, walk,
+};
+
+pub fn convert(export: &Path) -> CliResult<Conversion> {
+    if !export.is_dir() {
+        return Err(CliError::from("expected the folder of an unzipped notion export"));
+    }
+
+    let paths = walk(export)?;
+    let renames = renames(&paths);
+    let new_paths: HashSet<&str> = renames.values().map(String::as_str).collect();
+
+    let mut conversion = Conversion::new(folder_name(export));
+    for path in &paths {
+        if superseded(path, &paths) {
+            continue;
+        }
+        let new_path = &renames[path];
+        let mut content = fs::read(export.join(path))?;
+        if path.ends_with(".md") {
+            let md = String::from_utf8_lossy(&content).into_owned();
+            content = fix_links(path, new_path, &md, &renames, &mut conversion).into_bytes();
+        } else if path.ends_with(".csv") {
+            let csv = String::from_utf8_lossy(&content).into_owned();
+            content = table(new_path, &csv, &new_paths).into_bytes();
+        }
+        conversion.files.insert(new_path.clone(), content);
+    }
+    Ok(conversion)
+}
+
+/// Databases are exported twice, as `<name>.csv` with the columns that were visible and as
+/// `<name>_all.csv` with all of them; only the second is kept.
+fn superseded(path: &str, paths: &[String]) -> bool {
+    let Some(stem) = path.strip_suffix(".csv") else { return false };
+    !stem.ends_with("_all") && paths.binary_search(&format!("{stem}_all.csv")).is_ok()
+}
+
+/// Where each file in the export ends up. Pages whose names are the same without their ids are
+/// numbered, along with their folders.
+fn renames(paths: &[String]) -> HashMap<String, String> {
+    let mut ids: HashMap<(String, String), Vec<String>> = HashMap::new();
+    let mut name = |parent: &str, stem: &str| -> String {
+        let (base, Some(id)) = strip_id(stem) else { return stem.to_string() };
+        let seen = ids
+            .entry((parent.to_string(), base.to_lowercase()))
+            .or_default();
+        let n = match seen.iter().position(|seen| seen == id) {
+            Some(n) => n,
+            None => {
+                seen.push(id.to_string());
+                seen.len() - 1
+            }
+        };
+        if n == 0 { base.to_string() } else { format!("{base} {}", n + 1) }
+    };
+
+    let mut renames = HashMap::new();
+    let mut taken = HashSet::new();
+    for path in paths {
+        if superseded(path, paths) {
+            continue;
+        }
+        let components: Vec<&str> = path.split('/').collect();
+        let mut renamed: Vec<String> = vec![];
+        for (i, component) in components.iter().enumerate() {
+            let (stem, extension) = match component.rfind('.') {
+                Some(dot) if dot > 0 && i + 1 == components.len() => component.split_at(dot),
+                _ => (*component, ""),
+            };
+            let (stem, extension) = match extension {
+                ".csv" => (stem.strip_suffix("_all").unwrap_or(stem), ".md"),
+                _ => (stem, extension),
+            };
+            renamed.push(format!("{}{extension}", name(&renamed.join("/"), stem)));
+        }
+
+        let renamed = renamed.join("/");
+        let mut candidate = renamed.clone();
+        let mut n = 1;
+        while !taken.insert(candidate.clone()) {
+            n += 1;
+            candidate = numbered(&renamed, n);
+        }
+        renames.insert(path.clone(), candidate);
+    }
+
+    // links to either export of a database lead to its table
+    for path in paths {
+        if superseded(path, paths) {
+            let all = format!("{}_all.csv", path.trim_end_matches(".csv"));
+            if let Some(table) = renames.get(&all).cloned() {
+                renames.insert(path.clone(), table);
+            }
+        }
+    }
+    renames
+}
+
+/// `Plans 0123456789abcdef0123456789abcdef` is `Plans` with the id `0123456789abcdef0123456789abcdef`
+fn strip_id(stem: &str) -> (&str, Option<&str>) {
+    match stem.rsplit_once(' ') {
+        Some((base, id))
+            if !base.is_empty() && id.len() == 32 && id.bytes().all(|b| b.is_ascii_hexdigit()) =>
+        {
+            (base, Some(id))
+        }
+        _ => (stem, None),
+    }
+}
+
+fn fix_links(
+    path: &str, new_path: &str, md: &str, renames: &HashMap<String, String>,
+    conversion: &mut Conversion,
+) -> String {
+    let mut replacements = vec![];
+    for link in parse_links(md) {
+        if link.kind != LinkKind::Markdown || is_external(&link.target) {
+            continue;
+        }
+        let target = percent_decode(&link.target);
+        let (target, fragment) = match target.find('#') {
+            Some(i) => target.split_at(i),
+            None => (target.as_str(), ""),
+        };
+        let Some(renamed) = resolve(parent(path), target).and_then(|old| renames.get(&old)) else {
+            continue;
+        };
+
+        let to = format!("{}{fragment}", relative(parent(new_path), renamed));
+        let bracketed = md[..link.range.start].ends_with('<');
+        replacements.push((link.range, if bracketed { to } else { destination(&to) }));
+        conversion.links_rewritten += 1;
+    }
+    replace_ranges(md, replacements)
+}
+
+/// A database as a markdown table, titled after it. The first column names the page of each
+/// row, which is linked to when it was exported.
+fn table(path: &str, csv: &str, paths: &HashSet<&str>) -> String {
+    let title = file_name(path).trim_end_matches(".md");
+    let rows = parse_csv(csv.trim_start_matches('\u{feff}'));
+    let mut md = format!("# {title}\n");
+    let Some(header) = rows.first() else { return md };
+    let columns = rows.iter().map(Vec::len).max().unwrap_or_default();
+
+    let cell = |text: &str| text.replace('|', "\\|").replace(['\r', '\n'], " ");
+    let rows_folder = path.trim_end_matches(".md");
+    let row_page = |name: &str| {
+        let page = format!("{rows_folder}/{name}.md");
+        (!name.contains('/') && paths.contains(page.as_str()))
+            .then(|| destination(&relative(parent(path), &page)))
+    };
+
+    md.push_str("\n|");
+    for column in 0..columns {
+        md.push_str(&format!(" {} |", cell(header.get(column).map_or("", String::as_str))));
+    }
+    md.push_str("\n|");
+    md.push_str(&" --- |".repeat(columns));
+    md.push('\n');
+    for row in &rows[1..] {
+        md.push('|');
+        for column in 0..columns {
+            let value = row.get(column).map_or("", String::as_str);
+            let value = match row_page(value) {
+                Some(page) if column == 0 => format!("[{}]({page})", cell(value)),
+                _ => cell(value),
+            };
+            md.push_str(&format!(" {value} |"));
+        }
+        md.push('\n');
+    }
+    md
+}
+
+fn parse_csv(text: &str) -> Vec<Vec<String>> {
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    quoted = false;
+                }
+            }
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => row.push(std::mem::take(&mut field)),
+            '\n' if !quoted => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            '\r' if !quoted => {}
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const A: &str = "0123456789abcdef0123456789abcdef";
+    const B: &str = "fedcba9876543210fedcba9876543210";
+
+    fn paths(paths: &[String]) -> Vec<String> {
+        let mut paths = paths.to_vec();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn ids_are_stripped() {
+        let paths = paths(&[
+            format!("Plans {A}.md"),
+            format!("Plans {A}/Q3 {B}.md"),
+            format!("Plans {A}/image.png"),
+            format!("Plans {B}.md"),
+            format!("Tasks {A}.csv"),
+            format!("Tasks {A}_all.csv"),
+        ]);
+        let renames = renames(&paths);
+        assert_eq!(renames[&format!("Plans {A}.md")], "Plans.md");
+        assert_eq!(renames[&format!("Plans {A}/Q3 {B}.md")], "Plans/Q3.md");
+        assert_eq!(renames[&format!("Plans {A}/image.png")], "Plans/image.png");
+        assert_eq!(renames[&format!("Plans {B}.md")], "Plans 2.md");
+        assert_eq!(renames[&format!("Tasks {A}.csv")], "Tasks.md");
+        assert_eq!(renames[&format!("Tasks {A}_all.csv")], "Tasks.md");
+    }
+
+    #[test]
+    fn links_follow_renames() {
+        let paths = paths(&[format!("Plans {A}.md"), format!("Plans {A}/Q3 {B}.md")]);
+        let renames = renames(&paths);
+        let mut conversion = Conversion::default();
+        let md = fix_links(
+            &format!("Plans {A}.md"),
+            "Plans.md",
+            &format!("[Q3](Plans%20{A}/Q3%20{B}.md) [web](https://notion.so)"),
+            &renames,
+            &mut conversion,
+        );
+        assert_eq!(md, "[Q3](Plans/Q3.md) [web](https://notion.so)");
+        assert_eq!(conversion.links_rewritten, 1);
+    }
+
+    #[test]
+    fn databases_become_tables() {
+        let csv = "\u{feff}Name,Notes\n\"Write, edit\",\"say \"\"hi\"\"\"\nOther,a|b\n";
+        let paths = HashSet::from(["Tasks.md", "Tasks/Write, edit.md"]);
+        assert_eq!(
+            table("Tasks.md", csv, &paths),
+            "# Tasks\n\n| Name | Notes |\n| --- | --- |\n\
+             | [Write, edit](<Tasks/Write, edit.md>) | say \"hi\" |\n| Other | a\\|b |\n"
+        );
+    }
+}
@@ -0,0 +1,199 @@
+//! Obsidian vaults are already folders of markdown, so they're imported as they are, except for
+//! what lockbook reads differently: `![[embeds]]`, and links Obsidian resolves by file name
+//! wherever the attachment was filed. Both become relative markdown links.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use cli_rs::cli_error::{CliError, CliResult};
+use lb_rs::service::links::{LinkKind, parse_links};
+
+use super::{
+    Conversion, destination, file_name, folder_name, is_external, parent, percent_decode, relative,
+    replace_ranges, resolve, walk,
+};
+
+pub fn convert(vault: &Path) -> CliResult<Conversion> {
+    if !vault.is_dir() {
+        return Err(CliError::from("expected the folder of an obsidian vault"));
+    }
+
+    let paths = walk(vault)?;
+    let index = Index::new(&paths);
+    let mut conversion = Conversion::new(folder_name(vault));
+    for path in &paths {
+        let mut content = fs::read(vault.join(path))?;
+        if path.ends_with(".md") {
+            let md = String::from_utf8_lossy(&content).into_owned();
+            content = convert_note(path, &md, &index, &mut conversion).into_bytes();
+        }
+        conversion.files.insert(path.clone(), content);
+    }
+    Ok(conversion)
+}
+
+struct Index<'a> {
+    /// sorted
+    paths: &'a [String],
+    by_name: HashMap<String, Vec<&'a str>>,
+}
+
+impl<'a> Index<'a> {
+    fn new(paths: &'a [String]) -> Self {
+        let mut by_name: HashMap<String, Vec<&str>> = HashMap::new();
+        for path in paths {
+            by_name
+                .entry(file_name(path).to_lowercase())
+                .or_default()
+                .push(path);
+        }
+        Self { paths, by_name }
+    }
+
+    fn get(&self, path: &str) -> Option<&'a str> {
+        self.paths
+            .binary_search_by(|p| p.as_str().cmp(path))
+            .ok()
+            .map(|i| self.paths[i].as_str())
+    }
+
+    /// Finds what a link points at the way Obsidian does: relative to the note, from the root of
+    /// the vault, then by name anywhere in it, preferring the shortest path. Notes can be linked
+    /// without their extension.
+    fn resolve(&self, folder: Option<&str>, target: &str) -> Option<&'a str> {
+        let candidates = [target.to_string(), format!("{target}.md")];
+        for candidate in &candidates {
+            for base in [folder, None] {
+                if let Some(found) = resolve(base, candidate).and_then(|path| self.get(&path)) {
+                    return Some(found);
+                }
+            }
+        }
+        for candidate in &candidates {
+            let candidate = candidate.to_lowercase();
+            let Some(matches) = self.by_name.get(file_name(&candidate)) else { continue };
+            if let Some(found) = matches
+                .iter()
+                .filter(|path| path.to_lowercase().ends_with(&candidate))
+                .min_by_key(|path| path.len())
+            {
+                return Some(found);
+            }
+        }
+        None
+    }
+}
+
+fn convert_note(path: &str, md: &str, index: &Index, conversion: &mut Conversion) -> String {
+    let folder = parent(path);
+    let mut replacements = vec![];
+    for link in parse_links(md) {
+        match link.kind {
+            LinkKind::Wiki => {
+                // wikilinks work as they are; embeds are replaced whole, `!` to `]]`
+                let Some(open) = md[..link.range.start].rfind("[[") else { continue };
+                let Some(len) = md[link.range.end..].find("]]") else { continue };
+                let close = link.range.end + len + 2;
+                if !md[..open].ends_with('!') {
+                    continue;
+                }
+
+                let Some(found) = index.resolve(folder, &link.target) else {
+                    conversion
+                        .warnings
+                        .push(format!("{path}: couldn't find embedded '{}'", link.target));
+                    continue;
+                };
+                let alias = md[open + 2..close - 2]
+                    .split_once('|')
+                    .map(|(_, alias)| alias.trim());
+                let name = file_name(found);
+                let to = destination(&relative(folder, found));
+                let replacement = if let Some(title) = name.strip_suffix(".md") {
+                    // lockbook doesn't embed notes in notes, so they're linked instead
+                    format!("[{}]({to})", alias.unwrap_or(title))
+                } else {
+                    // `![[image.png|300]]` sets a size rather than a caption
+                    let alt = alias
+                        .filter(|alias| !alias.chars().all(|c| c.is_ascii_digit() || c == 'x'))
+                        .unwrap_or(name);
+                    format!("![{alt}]({to})")
+                };
+                replacements.push((open - 1..close, replacement));
+                conversion.links_rewritten += 1;
+            }
+            LinkKind::Markdown => {
+                if is_external(&link.target) {
+                    continue;
+                }
+                let target = percent_decode(&link.target);
+                let (target, fragment) = match target.find('#') {
+                    Some(i) => target.split_at(i),
+                    None => (target.as_str(), ""),
+                };
+                if resolve(folder, target).is_some_and(|path| index.get(&path).is_some()) {
+                    continue;
+                }
+                let Some(found) = index.resolve(folder, target) else { continue };
+
+                let to = format!("{}{fragment}", relative(folder, found));
+                let bracketed = md[..link.range.start].ends_with('<');
+                replacements.push((link.range, if bracketed { to } else { destination(&to) }));
+                conversion.links_rewritten += 1;
+            }
+            LinkKind::Url => {}
+        }
+    }
+    replace_ranges(md, replacements)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert(path: &str, md: &str) -> (String, Conversion) {
+        let paths: Vec<String> = [
+            "attachments/diagram one.png",
+            "attachments/photo.jpg",
+            "daily/2024-01-01.md",
+            "projects/plan.md",
+            "readme.md",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+        let index = Index::new(&paths);
+        let mut conversion = Conversion::default();
+        let md = convert_note(path, md, &index, &mut conversion);
+        (md, conversion)
+    }
+
+    #[test]
+    fn embeds_become_links() {
+        let (md, conversion) = convert(
+            "daily/2024-01-01.md",
+            "![[diagram one.png]] ![[photo.jpg|300]] ![[photo.jpg|a photo]] ![[plan]] [[plan]]",
+        );
+        assert_eq!(
+            md,
+            "![diagram one.png](<../attachments/diagram one.png>) ![photo.jpg](../attachments/photo.jpg) \
+             ![a photo](../attachments/photo.jpg) [plan](../projects/plan.md) [[plan]]"
+        );
+        assert_eq!(conversion.links_rewritten, 4);
+    }
+
+    #[test]
+    fn attachments_are_found_by_name() {
+        let (md, _) =
+            convert("projects/plan.md", "![](photo.jpg) [home](../readme.md) [web](https://x.y)");
+        assert_eq!(md, "![](../attachments/photo.jpg) [home](../readme.md) [web](https://x.y)");
+    }
+
+    #[test]
+    fn missing_embeds_are_reported() {
+        let (md, conversion) = convert("readme.md", "![[gone.png]]");
+        assert_eq!(md, "![[gone.png]]");
+        assert_eq!(conversion.warnings.len(), 1);
+    }
+}
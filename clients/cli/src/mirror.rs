@@ -0,0 +1,637 @@
+//! `lockbook mirror` keeps a folder on disk and a folder in lockbook the same, both ways, so that
+//! editors, git and anything else that works on ordinary files can work on lockbook files.
+//!
+//! What both sides looked like when they last agreed is kept in a state file for each mirror, in
+//! the cli's data directory, which maps paths to the ids of the documents at them. A document
+//! that changed on one side since then is copied to the other. One that changed on both keeps
+//! lockbook's version at its path, and the version on disk is saved beside it as a conflict copy.
+//! Hidden files and folders, like `.git`, aren't mirrored.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+use std::{fs, io};
+
+use cli_rs::cli_error::{CliError, CliResult};
+use hotwatch::{Event as DiskEvent, Hotwatch};
+use lb_rs::model::core_config::Config;
+use lb_rs::model::errors::LbErrKind;
+use lb_rs::model::file::File;
+use lb_rs::model::file_metadata::{DocumentHmac, FileType};
+use lb_rs::service::events::{Actor, Event};
+use lb_rs::{Lb, Uuid};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
+
+use crate::input::find_file;
+use crate::{core, ensure_account_and_root};
+
+const SYNC_INTERVAL: Duration = Duration::from_secs(30);
+/// how long the disk has to be quiet before its changes are mirrored, so that a save that's
+/// several writes is pushed once
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+#[tokio::main]
+pub async fn mirror(local: PathBuf, folder: String) -> CliResult<()> {
+    let lb = core().await?;
+    ensure_account_and_root(&lb).await?;
+
+    let folder = find_file(&lb, &folder).await?;
+    if !folder.is_folder() {
+        return Err(CliError::from(format!("'{}' is not a folder", folder.name)));
+    }
+    fs::create_dir_all(&local)?;
+    let local = local.canonicalize()?;
+
+    let mut mirror = Mirror::load(lb.clone(), local.clone(), folder.id)?;
+    println!("mirroring {} with {}", local.display(), lb.get_path_by_id(folder.id).await?);
+    println!("press ctrl-c to stop");
+
+    sync(&lb).await;
+    mirror.reconcile().await?;
+
+    let (disk_tx, mut disk_rx) = mpsc::unbounded_channel();
+    let mut watcher = Hotwatch::new_with_custom_delay(DEBOUNCE)
+        .map_err(|err| CliError::from(format!("file watcher failed to initialize: {err:#?}")))?;
+    watcher
+        .watch(&local, move |event: DiskEvent| {
+            let _ = disk_tx.send(event.paths);
+        })
+        .map_err(|err| CliError::from(format!("file watcher failed to watch: {err:#?}")))?;
+
+    let mut events = lb.subscribe();
+    let mut sync_interval = tokio::time::interval(SYNC_INTERVAL);
+    sync_interval.tick().await;
+
+    loop {
+        tokio::select! {
+            Some(paths) = disk_rx.recv() => {
+                // git and editors write hidden files constantly
+                if paths.iter().all(|path| is_hidden(&local, path)) {
+                    continue;
+                }
+                while disk_rx.try_recv().is_ok() {}
+                mirror.reconcile_or_report().await;
+                sync(&lb).await;
+            }
+            event = events.recv() => match event {
+                Ok(Event::DocumentWritten(_, Actor::Sync) | Event::MetadataChanged(Actor::Sync))
+                | Err(RecvError::Lagged(_)) => {
+                    // a sync writes documents one at a time; they're mirrored together
+                    while events.try_recv().is_ok() {}
+                    mirror.reconcile_or_report().await;
+                }
+                Ok(_) => {}
+                Err(RecvError::Closed) => break,
+            },
+            _ = sync_interval.tick() => sync(&lb).await,
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+
+    let _ = watcher.unwatch(&local);
+    mirror.save()
+}
+
+async fn sync(lb: &Lb) {
+    if let Err(err) = lb.sync().await {
+        eprintln!("sync failed: {err:?}");
+    }
+}
+
+struct Mirror {
+    lb: Lb,
+    local: PathBuf,
+    folder: Uuid,
+    state_path: PathBuf,
+    state: State,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct State {
+    /// by their path relative to both folders
+    documents: BTreeMap<String, Entry>,
+}
+
+/// A document as it was when both sides last agreed on it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Entry {
+    id: Uuid,
+    hmac: Option<DocumentHmac>,
+    /// lockbook's, so that documents that haven't been modified since aren't read
+    last_modified: u64,
+    /// the md5 of what's on disk
+    digest: String,
+    /// the disk's, so that files that haven't been modified since aren't read
+    disk_modified: u128,
+    disk_len: u64,
+}
+
+#[derive(Debug, PartialEq)]
+enum Action {
+    Nothing,
+    Pull,
+    Push,
+    DeleteOnDisk,
+    DeleteInLockbook,
+    Forget,
+    Conflict,
+}
+
+/// What to do about a path, given what it was when both sides last agreed, the digest of what's
+/// on disk and the id and hmac of what's in lockbook. An edit on one side wins over a delete on
+/// the other.
+fn decide(
+    known: Option<&Entry>, disk: Option<&str>, remote: Option<(Uuid, Option<DocumentHmac>)>,
+) -> Action {
+    let disk_changed = |known: &Entry| disk != Some(known.digest.as_str());
+    let remote_changed = |known: &Entry| remote != Some((known.id, known.hmac));
+    match (known, disk, remote) {
+        (None, None, None) => Action::Nothing,
+        (None, Some(_), None) => Action::Push,
+        (None, None, Some(_)) => Action::Pull,
+        (None, Some(_), Some(_)) => Action::Conflict,
+        (Some(_), None, None) => Action::Forget,
+        (Some(known), Some(_), None) => {
+            if disk_changed(known) {
+                Action::Push
+            } else {
+                Action::DeleteOnDisk
+            }
+        }
+        (Some(known), None, Some(_)) => {
+            if remote_changed(known) {
+                Action::Pull
+            } else {
+                Action::DeleteInLockbook
+            }
+        }
+        (Some(known), Some(_), Some(_)) => match (disk_changed(known), remote_changed(known)) {
+            (false, false) => Action::Nothing,
+            (true, false) => Action::Push,
+            (false, true) => Action::Pull,
+            (true, true) => Action::Conflict,
+        },
+    }
+}
+
+impl Mirror {
+    fn load(lb: Lb, local: PathBuf, folder: Uuid) -> CliResult<Self> {
+        let key = md5::compute(format!("{}\n{folder}", local.display()));
+        let state_path = Path::new(&Config::writeable_path("cli"))
+            .join("mirrors")
+            .join(format!("{key:x}.json"));
+        let state = match fs::read(&state_path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).map_err(|err| {
+                CliError::from(format!("couldn't read {}: {err}", state_path.display()))
+            })?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => State::default(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Self { lb, local, folder, state_path, state })
+    }
+
+    fn save(&self) -> CliResult<()> {
+        if let Some(parent) = self.state_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let state = serde_json::to_vec(&self.state)
+            .map_err(|err| CliError::from(format!("couldn't save mirror state: {err}")))?;
+        fs::write(&self.state_path, state)?;
+        Ok(())
+    }
+
+    /// Mirrors in the background report what went wrong and carry on; what failed is tried again
+    /// the next time either side changes.
+    async fn reconcile_or_report(&mut self) {
+        if let Err(err) = self.reconcile().await {
+            eprintln!("mirror failed: {err:?}");
+        }
+    }
+
+    /// Compares both sides with each other and with how they last agreed, and copies changes.
+    async fn reconcile(&mut self) -> CliResult<()> {
+        let remote = self.remote_documents().await?;
+        let disk = self.disk_files()?;
+        let paths: BTreeSet<String> = self
+            .state
+            .documents
+            .keys()
+            .chain(remote.keys())
+            .chain(disk.keys())
+            .cloned()
+            .collect();
+
+        for path in paths {
+            let known = self.state.documents.get(&path).cloned();
+            let remote = remote.get(&path);
+            let digest = match disk.get(&path) {
+                Some(metadata) => Some(self.digest(&path, metadata, known.as_ref())?),
+                None => None,
+            };
+            let remote_version = match remote {
+                Some(file) => Some(self.remote_version(file, known.as_ref()).await?),
+                None => None,
+            };
+
+            let action = decide(known.as_ref(), digest.as_deref(), remote_version);
+            let result = match (&action, remote, &known) {
+                (Action::Nothing, _, _) => Ok(()),
+                (Action::Pull, Some(file), _) => self.pull(&path, file).await,
+                (Action::Push, remote, _) => self.push(&path, remote).await,
+                (Action::DeleteOnDisk, _, _) => self.delete_on_disk(&path),
+                (Action::DeleteInLockbook, _, Some(known)) => {
+                    self.delete_in_lockbook(&path, known.id).await
+                }
+                (Action::Forget, _, _) => {
+                    self.state.documents.remove(&path);
+                    Ok(())
+                }
+                (Action::Conflict, Some(file), _) => self.conflict(&path, file).await,
+                // decide only pulls from, and conflicts with, what's in lockbook, and only
+                // deletes what it knew about
+                (Action::Pull | Action::Conflict, None, _)
+                | (Action::DeleteInLockbook, _, None) => {
+                    Err(CliError::from(format!("decided to {action:?} {path}, which isn't there")))
+                }
+            };
+            if let Err(err) = result {
+                eprintln!("couldn't mirror {path}: {err:?}");
+            } else if action != Action::Nothing {
+                println!("{action:?} {path}");
+            }
+        }
+
+        self.save()
+    }
+
+    /// the documents in the mirrored folder by their path in it
+    async fn remote_documents(&self) -> CliResult<HashMap<String, File>> {
+        let files = self
+            .lb
+            .get_and_get_children_recursively(&self.folder)
+            .await?;
+        let by_id: HashMap<Uuid, &File> = files.iter().map(|file| (file.id, file)).collect();
+
+        let mut documents = HashMap::new();
+        for file in files.iter().filter(|file| file.is_document()) {
+            let mut components = vec![file.name.as_str()];
+            let mut parent = file.parent;
+            while parent != self.folder {
+                let Some(folder) = by_id.get(&parent) else { break };
+                components.push(folder.name.as_str());
+                parent = folder.parent;
+            }
+            if components.iter().any(|name| name.starts_with('.')) {
+                continue;
+            }
+            components.reverse();
+            documents.insert(components.join("/"), file.clone());
+        }
+        Ok(documents)
+    }
+
+    /// the files in the mirrored folder on disk by their path in it
+    fn disk_files(&self) -> CliResult<HashMap<String, fs::Metadata>> {
+        let mut files = HashMap::new();
+        let mut folders = vec![self.local.clone()];
+        while let Some(folder) = folders.pop() {
+            for entry in fs::read_dir(&folder)? {
+                let entry = entry?;
+                let path = entry.path();
+                if is_hidden(&self.local, &path) {
+                    continue;
+                }
+                let metadata = entry.metadata()?;
+                if metadata.is_dir() {
+                    folders.push(path);
+                } else if metadata.is_file() {
+                    files.insert(relative(&self.local, &path), metadata);
+                }
+            }
+        }
+        Ok(files)
+    }
+
+    fn digest(
+        &self, path: &str, metadata: &fs::Metadata, known: Option<&Entry>,
+    ) -> CliResult<String> {
+        if let Some(known) = known {
+            if known.disk_len == metadata.len() && known.disk_modified == modified(metadata) {
+                return Ok(known.digest.clone());
+            }
+        }
+        let content = fs::read(self.local.join(path))?;
+        Ok(format!("{:x}", md5::compute(content)))
+    }
+
+    async fn remote_version(
+        &self, file: &File, known: Option<&Entry>,
+    ) -> CliResult<(Uuid, Option<DocumentHmac>)> {
+        if let Some(known) = known {
+            if known.id == file.id && known.last_modified == file.last_modified {
+                return Ok((known.id, known.hmac));
+            }
+        }
+        let (hmac, _) = self.lb.read_document_with_hmac(file.id, false).await?;
+        Ok((file.id, hmac))
+    }
+
+    /// Writes lockbook's version of a document to disk.
+    async fn pull(&mut self, path: &str, file: &File) -> CliResult<()> {
+        let (hmac, content) = self.lb.read_document_with_hmac(file.id, false).await?;
+        let metadata = self.write_to_disk(path, &content)?;
+        self.remember(path, file.id, hmac, file.last_modified, &content, &metadata);
+        Ok(())
+    }
+
+    /// Writes the version of a document on disk to lockbook, creating it if it isn't there.
+    async fn push(&mut self, path: &str, file: Option<&File>) -> CliResult<()> {
+        let content = fs::read(self.local.join(path))?;
+        let metadata = fs::metadata(self.local.join(path))?;
+        let (id, hmac) = match file {
+            Some(file) => {
+                let known = self.state.documents.get(path).and_then(|known| known.hmac);
+                match self
+                    .lb
+                    .safe_write(file.id, known, content.clone(), None)
+                    .await
+                {
+                    Ok(hmac) => (file.id, Some(hmac)),
+                    // lockbook's changed since it was last looked at
+                    Err(err) if err.kind == LbErrKind::ReReadRequired => {
+                        return Box::pin(self.conflict(path, file)).await;
+                    }
+                    Err(err) => return Err(err.into()),
+                }
+            }
+            None => {
+                let file = self.create_in_lockbook(path).await?;
+                let hmac = self
+                    .lb
+                    .safe_write(file.id, None, content.clone(), None)
+                    .await?;
+                (file.id, Some(hmac))
+            }
+        };
+        let last_modified = self.lb.get_file_by_id(id).await?.last_modified;
+        self.remember(path, id, hmac, last_modified, &content, &metadata);
+        Ok(())
+    }
+
+    /// Keeps lockbook's version of a document at its path, and saves the version on disk beside
+    /// it on both sides, unless they're the same.
+    async fn conflict(&mut self, path: &str, file: &File) -> CliResult<()> {
+        let disk_content = fs::read(self.local.join(path))?;
+        let (hmac, content) = self.lb.read_document_with_hmac(file.id, false).await?;
+        if disk_content != content {
+            let copy =
+                conflict_path(path, &chrono::Local::now().format("%Y-%m-%d %H.%M.%S").to_string());
+            self.write_to_disk(&copy, &disk_content)?;
+            self.push(&copy, None).await?;
+            eprintln!("both sides changed {path}; kept lockbook's and saved the disk's as {copy}");
+        }
+        let metadata = self.write_to_disk(path, &content)?;
+        self.remember(path, file.id, hmac, file.last_modified, &content, &metadata);
+        Ok(())
+    }
+
+    fn delete_on_disk(&mut self, path: &str) -> CliResult<()> {
+        fs::remove_file(self.local.join(path))?;
+        self.state.documents.remove(path);
+
+        // and the folders that only held it
+        let mut folder = self.local.join(path);
+        while folder.pop() && folder != self.local {
+            if fs::remove_dir(&folder).is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    async fn delete_in_lockbook(&mut self, path: &str, id: Uuid) -> CliResult<()> {
+        self.lb.delete(&id).await?;
+        self.state.documents.remove(path);
+        Ok(())
+    }
+
+    async fn create_in_lockbook(&self, path: &str) -> CliResult<File> {
+        let folder = self.lb.get_path_by_id(self.folder).await?;
+        let file = self
+            .lb
+            .create_at_path(&format!("{}/{path}", folder.trim_end_matches('/')))
+            .await?;
+        if file.file_type != FileType::Document {
+            return Err(CliError::from(format!("{path} is a folder in lockbook")));
+        }
+        Ok(file)
+    }
+
+    /// Writes a file whole, so that nothing reading it sees half of it.
+    fn write_to_disk(&self, path: &str, content: &[u8]) -> CliResult<fs::Metadata> {
+        let destination = self.local.join(path);
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let name = destination
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let temporary = destination.with_file_name(format!(".{name}.lockbook"));
+        fs::write(&temporary, content)?;
+        fs::rename(&temporary, &destination)?;
+        Ok(fs::metadata(&destination)?)
+    }
+
+    fn remember(
+        &mut self, path: &str, id: Uuid, hmac: Option<DocumentHmac>, last_modified: u64,
+        content: &[u8], metadata: &fs::Metadata,
+    ) {
+        let entry = Entry {
+            id,
+            hmac,
+            last_modified,
+            digest: format!("{:x}", md5::compute(content)),
+            disk_modified: modified(metadata),
+            disk_len: metadata.len(),
+        };
+        self.state.documents.insert(path.to_string(), entry);
+    }
+}
+
+fn modified(metadata: &fs::Metadata) -> u128 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|since| since.as_nanos())
+        .unwrap_or_default()
+}
+
+fn relative(root: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    let components: Vec<String> = relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    components.join("/")
+}
+
+fn is_hidden(root: &Path, path: &Path) -> bool {
+    relative(root, path)
+        .split('/')
+        .any(|name| name.starts_with('.'))
+}
+
+/// `notes/plan.md` saved at a time is `notes/plan (conflict <time>).md`
+fn conflict_path(path: &str, time: &str) -> String {
+    let name_start = path.rfind('/').map_or(0, |i| i + 1);
+    let (stem, extension) = match path[name_start..].rfind('.') {
+        Some(dot) if dot > 0 => path.split_at(name_start + dot),
+        _ => (path, ""),
+    };
+    format!("{stem} (conflict {time}){extension}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use test_utils::test_core_with_account;
+
+    fn entry(id: Uuid, hmac: u8, digest: &str) -> Entry {
+        Entry {
+            id,
+            hmac: Some([hmac; 32]),
+            last_modified: 0,
+            digest: digest.into(),
+            disk_modified: 0,
+            disk_len: 0,
+        }
+    }
+
+    #[test]
+    fn new_files_are_copied() {
+        let id = Uuid::new_v4();
+        assert_eq!(decide(None, Some("a"), None), Action::Push);
+        assert_eq!(decide(None, None, Some((id, None))), Action::Pull);
+        assert_eq!(decide(None, Some("a"), Some((id, None))), Action::Conflict);
+    }
+
+    #[test]
+    fn changes_are_copied() {
+        let id = Uuid::new_v4();
+        let known = entry(id, 1, "a");
+        assert_eq!(decide(Some(&known), Some("a"), Some((id, Some([1; 32])))), Action::Nothing);
+        assert_eq!(decide(Some(&known), Some("b"), Some((id, Some([1; 32])))), Action::Push);
+        assert_eq!(decide(Some(&known), Some("a"), Some((id, Some([2; 32])))), Action::Pull);
+        assert_eq!(decide(Some(&known), Some("b"), Some((id, Some([2; 32])))), Action::Conflict);
+    }
+
+    #[test]
+    fn edits_win_over_deletes() {
+        let id = Uuid::new_v4();
+        let known = entry(id, 1, "a");
+        assert_eq!(decide(Some(&known), Some("a"), None), Action::DeleteOnDisk);
+        assert_eq!(decide(Some(&known), Some("b"), None), Action::Push);
+        assert_eq!(decide(Some(&known), None, Some((id, Some([1; 32])))), Action::DeleteInLockbook);
+        assert_eq!(decide(Some(&known), None, Some((id, Some([2; 32])))), Action::Pull);
+        assert_eq!(decide(Some(&known), None, None), Action::Forget);
+    }
+
+    /// mirrors a new folder in lockbook with a new folder on disk
+    async fn mirror() -> Mirror {
+        let lb = test_core_with_account().await;
+        let folder = lb.create_at_path("mirrored/").await.unwrap();
+        let local = env::temp_dir().join(format!("mirror-{}", Uuid::new_v4()));
+        fs::create_dir_all(&local).unwrap();
+        let state_path = local.with_extension("json");
+        Mirror { lb, local, folder: folder.id, state_path, state: State::default() }
+    }
+
+    #[tokio::test]
+    async fn conflicting_edits_keep_both() {
+        let mut mirror = mirror().await;
+        let doc = mirror
+            .lb
+            .create_at_path("mirrored/notes/plan.md")
+            .await
+            .unwrap();
+        mirror.lb.write_document(doc.id, b"lockbook").await.unwrap();
+        mirror.reconcile().await.unwrap();
+        assert_eq!(fs::read(mirror.local.join("notes/plan.md")).unwrap(), b"lockbook");
+
+        mirror
+            .lb
+            .write_document(doc.id, b"lockbook's edit")
+            .await
+            .unwrap();
+        fs::write(mirror.local.join("notes/plan.md"), b"disk's edit").unwrap();
+        mirror.reconcile().await.unwrap();
+
+        assert_eq!(fs::read(mirror.local.join("notes/plan.md")).unwrap(), b"lockbook's edit");
+        let remote = mirror.remote_documents().await.unwrap();
+        let copies: Vec<&String> = remote
+            .keys()
+            .filter(|path| path.starts_with("notes/plan (conflict "))
+            .collect();
+        assert_eq!(copies.len(), 1);
+        let copy = copies[0];
+        assert_eq!(fs::read(mirror.local.join(copy)).unwrap(), b"disk's edit");
+        assert_eq!(
+            mirror
+                .lb
+                .read_document(remote[copy].id, false)
+                .await
+                .unwrap(),
+            b"disk's edit"
+        );
+        assert_eq!(mirror.state.documents.len(), 2);
+
+        fs::remove_dir_all(&mirror.local).unwrap();
+    }
+
+    #[tokio::test]
+    async fn deletes_are_mirrored() {
+        let mut mirror = mirror().await;
+        for (path, content) in [("a.md", "a"), ("b/b.md", "b"), ("c.md", "c")] {
+            let doc = mirror
+                .lb
+                .create_at_path(&format!("mirrored/{path}"))
+                .await
+                .unwrap();
+            mirror
+                .lb
+                .write_document(doc.id, content.as_bytes())
+                .await
+                .unwrap();
+        }
+        mirror.reconcile().await.unwrap();
+        assert!(mirror.local.join("b/b.md").exists());
+
+        // deleted on disk, deleted in lockbook, and deleted on disk but edited in lockbook
+        fs::remove_file(mirror.local.join("a.md")).unwrap();
+        let b = mirror.lb.get_by_path("mirrored/b/b.md").await.unwrap();
+        mirror.lb.delete(&b.id).await.unwrap();
+        fs::remove_file(mirror.local.join("c.md")).unwrap();
+        let c = mirror.lb.get_by_path("mirrored/c.md").await.unwrap();
+        mirror.lb.write_document(c.id, b"c edit").await.unwrap();
+        mirror.reconcile().await.unwrap();
+
+        let remote = mirror.remote_documents().await.unwrap();
+        assert_eq!(remote.keys().collect::<Vec<_>>(), vec!["c.md"]);
+        assert!(!mirror.local.join("b").exists());
+        assert_eq!(fs::read(mirror.local.join("c.md")).unwrap(), b"c edit");
+        assert_eq!(mirror.state.documents.keys().collect::<Vec<_>>(), vec!["c.md"]);
+
+        fs::remove_dir_all(&mirror.local).unwrap();
+    }
+
+    #[test]
+    fn conflict_paths() {
+        assert_eq!(conflict_path("notes/plan.md", "t"), "notes/plan (conflict t).md");
+        assert_eq!(conflict_path("a.b/README", "t"), "a.b/README (conflict t)");
+    }
+}
@@ -47,6 +47,7 @@ public enum EC: Int {
     case insufficientPermission
     case invalidPurchaseToken
     case invalidAuthDetails
+    case inviteRequired
     case keyPhraseInvalid
     case linkInSharedFolder
     case linkTargetIsOwned
@@ -32,8 +32,10 @@ use syntect::parsing::SyntaxSet;
 use syntect_assets::assets::HighlightingAssets;
 use widget::block::LayoutCache;
 use widget::block::leaf::code_block::SyntaxHighlightCache;
+use widget::block::leaf::diagram::DiagramCache;
 use widget::emoji_completions::EmojiCompletions;
 use widget::find::Find;
+use widget::inline::math::MathCache;
 use widget::link_completions::LinkCompletions;
 use widget::toolbar::{MOBILE_TOOL_BAR_SIZE, Toolbar};
 
@@ -186,6 +188,8 @@ pub struct MdRender {
     // caches
     pub layout_cache: LayoutCache,
     pub syntax: SyntaxHighlightCache,
+    pub math: MathCache,
+    pub diagrams: DiagramCache,
 
     // viewport
     pub width: f32,
@@ -511,6 +515,8 @@ impl MdRender {
             ws_seq,
             layout_cache: Default::default(),
             syntax: Default::default(),
+            math: Default::default(),
+            diagrams: Default::default(),
             width: Default::default(),
             width_seq: 0,
             viewport_height: Default::default(),
@@ -583,6 +589,8 @@ impl MdRender {
             files: Arc::new(RwLock::new(FileCache::empty())),
             layout_cache: Default::default(),
             syntax: Default::default(),
+            math: Default::default(),
+            diagrams: Default::default(),
             width: Default::default(),
             width_seq: 0,
             viewport_height: Default::default(),
@@ -654,33 +662,7 @@ impl MdRender {
     }
 
     pub fn comrak_options() -> Options<'static> {
-        let mut options = Options::default();
-        options.parse.smart = true;
-        options.parse.ignore_setext = true;
-        options.extension.alerts = true;
-        options.extension.autolink = true;
-        options.extension.description_lists = false; // todo: is this a good way to power workspace-wide term definitions?
-        options.extension.footnotes = false;
-        options.extension.front_matter_delimiter = Some("---".to_string());
-        options.extension.greentext = false;
-        options.extension.header_ids = None; // intended for HTML renderers
-        options.extension.highlight = true;
-        options.extension.math_code = true; // rendered as code for now
-        options.extension.math_dollars = true; // rendered as code for now
-        options.extension.multiline_block_quotes = false; // todo
-        options.extension.shortcodes = true;
-        options.extension.spoiler = true;
-        options.extension.strikethrough = true;
-        options.extension.subscript = true;
-        options.extension.superscript = true;
-        options.extension.table = true;
-        options.extension.tagfilter = false; // intended for HTML renderers
-        options.extension.tasklist = true;
-        options.extension.underline = true;
-        options.extension.wikilinks_title_after_pipe = true; // matches obsidian
-        options.extension.wikilinks_title_before_pipe = false; // would not match obsidian
-        options.render.escaped_char_spans = true;
-        options
+        lb_rs::service::render::comrak_options()
     }
 }
 
@@ -744,6 +726,8 @@ impl Editor {
 
             layout_cache: Default::default(),
             syntax: Default::default(),
+            math: Default::default(),
+            diagrams: Default::default(),
 
             width: Default::default(),
             width_seq: 0,
@@ -1160,6 +1144,8 @@ impl Editor {
         self.edit.show_completions(ui);
 
         self.edit.renderer.syntax.garbage_collect();
+        self.edit.renderer.math.garbage_collect();
+        self.edit.renderer.diagrams.garbage_collect();
 
         let render_elapsed = start.elapsed();
 
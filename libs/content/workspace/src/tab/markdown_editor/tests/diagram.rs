@@ -0,0 +1,71 @@
+//! Tests for diagram code blocks ([`MdRender::layout_diagram_block`]): a
+//! collapsed `mermaid` or `dot` block renders as a single `Diagram` embed in
+//! place of its code lines, and falls back to code when the cursor enters it
+//! or its source doesn't parse.
+
+use lb_rs::model::text::offset_types::Grapheme;
+
+use super::super::input::Event;
+use super::harness::TestEditor;
+use crate::tab::markdown_editor::widget::block::leaf::diagram::DiagramLang;
+use crate::tab::markdown_editor::widget::utils::wrap_layout::{EmbedKind, FragmentContent};
+
+/// The language of each `Diagram` embed fragment painted this frame.
+fn diagram_fragments(ws: &TestEditor) -> Vec<DiagramLang> {
+    ws.editor
+        .edit
+        .renderer
+        .fragments
+        .iter()
+        .filter_map(|f| match f.content {
+            FragmentContent::Embed { kind: EmbedKind::Diagram { lang }, .. } => Some(lang),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn mermaid_flowchart_is_drawn() {
+    let mut ws =
+        TestEditor::new("intro\n\n```mermaid\nflowchart LR\n  A[Start] --> B{Done?}\n```\n");
+    ws.enter_frame();
+    assert_eq!(diagram_fragments(&ws), vec![DiagramLang::Mermaid]);
+}
+
+#[test]
+fn mermaid_sequence_is_drawn() {
+    let mut ws = TestEditor::new(
+        "intro\n\n```mermaid\nsequenceDiagram\n  Alice->>Bob: Hello\n  Bob-->>Alice: Hi\n```\n",
+    );
+    ws.enter_frame();
+    assert_eq!(diagram_fragments(&ws), vec![DiagramLang::Mermaid]);
+}
+
+#[test]
+fn dot_graph_is_drawn() {
+    let mut ws = TestEditor::new("intro\n\n```dot\ndigraph { a -> b -> c; a -> c }\n```\n");
+    ws.enter_frame();
+    assert_eq!(diagram_fragments(&ws), vec![DiagramLang::Dot]);
+}
+
+#[test]
+fn invalid_diagram_stays_code() {
+    let mut ws = TestEditor::new("intro\n\n```mermaid\nflowchart TD\n  A -->\n```\n");
+    ws.enter_frame();
+    assert!(diagram_fragments(&ws).is_empty());
+}
+
+#[test]
+fn cursor_in_diagram_reveals_source() {
+    let md = "intro\n\n```dot\ndigraph { a -> b }\n```\n";
+    let mut ws = TestEditor::new(md);
+    let inside = Grapheme("intro\n\n```dot\ndig".len());
+    ws.push(Event::Select { region: (inside, inside).into() });
+    ws.enter_frame();
+    assert!(diagram_fragments(&ws).is_empty(), "entered: code replaces the diagram");
+
+    // leaving the block draws it again
+    ws.push(Event::Select { region: (Grapheme(2), Grapheme(2)).into() });
+    ws.enter_frame();
+    assert_eq!(diagram_fragments(&ws), vec![DiagramLang::Dot]);
+}
@@ -0,0 +1,71 @@
+//! Tests for typeset math ([`MdRender::layout_math`]): collapsed math renders
+//! as a single `Math` embed in place of its source, and the source comes back
+//! for editing when the cursor enters the node.
+
+use comrak::Arena;
+use comrak::nodes::{AstNode, NodeValue};
+use lb_rs::model::text::offset_types::{Grapheme, RangeExt as _};
+
+use super::super::input::Event;
+use super::harness::TestEditor;
+use crate::tab::markdown_editor::widget::utils::wrap_layout::{EmbedKind, FragmentContent};
+
+/// The `display` flag of each `Math` embed fragment painted this frame.
+fn math_fragments(ws: &TestEditor) -> Vec<bool> {
+    ws.editor
+        .edit
+        .renderer
+        .fragments
+        .iter()
+        .filter_map(|f| match f.content {
+            FragmentContent::Embed { kind: EmbedKind::Math { display }, .. } => Some(display),
+            _ => None,
+        })
+        .collect()
+}
+
+fn math_range(ws: &mut TestEditor) -> (Grapheme, Grapheme) {
+    let arena = Arena::new();
+    let root: &AstNode = ws.editor.edit.renderer.reparse(&arena);
+    let node = root
+        .descendants()
+        .find(|n| matches!(n.data.borrow().value, NodeValue::Math(_)))
+        .expect("a math node");
+    ws.editor.edit.renderer.node_range(node)
+}
+
+#[test]
+fn inline_math_is_typeset() {
+    let mut ws = TestEditor::new("energy $E=mc^2$ is conserved\n");
+    ws.enter_frame();
+    assert_eq!(math_fragments(&ws), vec![false]);
+}
+
+#[test]
+fn display_math_is_typeset() {
+    let mut ws = TestEditor::new("text\n\n$$\\sum_{i=0}^n i = \\frac{n(n+1)}{2}$$\n\nafter\n");
+    ws.enter_frame();
+    assert_eq!(math_fragments(&ws), vec![true]);
+}
+
+#[test]
+fn cursor_in_math_reveals_source() {
+    let mut ws = TestEditor::new("energy $E=mc^2$ is conserved\n");
+    let range = math_range(&mut ws);
+    ws.push(Event::Select { region: (range.start() + 2, range.start() + 2).into() });
+    ws.enter_frame();
+    assert!(math_fragments(&ws).is_empty(), "entered: raw source replaces the math");
+
+    // leaving the node typesets it again
+    let after = (range.end() + 3, range.end() + 3);
+    ws.push(Event::Select { region: after.into() });
+    ws.enter_frame();
+    assert_eq!(math_fragments(&ws), vec![false]);
+}
+
+#[test]
+fn multi_line_math_stays_source() {
+    let mut ws = TestEditor::new("text\n\n$$\nx^2\n$$\n\nafter\n");
+    ws.enter_frame();
+    assert!(math_fragments(&ws).is_empty());
+}
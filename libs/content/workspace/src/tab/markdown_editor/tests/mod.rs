@@ -23,8 +23,10 @@ mod harness;
 
 mod benches;
 mod block_drag;
+mod diagram;
 mod edit_props;
 mod folding;
 mod link_card;
+mod math;
 mod regressions;
 mod render_props;
@@ -99,10 +99,16 @@ impl<'ast> MdRender {
         let width = self.width(node) - 2. * self.layout.block_padding;
         let row_height = self.layout.row_height;
 
+        let reveal = self.reveal_fenced_code_block(node, node_code_block);
+        if !reveal {
+            if let Some(diagram) = self.layout_diagram_block(node, node_code_block, width) {
+                return diagram.height + 2. * self.layout.block_padding;
+            }
+        }
+
         let mut result = self.layout.block_padding;
         result -= self.layout.row_spacing;
 
-        let reveal = self.reveal_fenced_code_block(node, node_code_block);
         let first_line_idx = self.node_first_line_idx(node);
         let last_line_idx = self.node_last_line_idx(node);
         for line_idx in first_line_idx..=last_line_idx {
@@ -161,9 +167,25 @@ impl<'ast> MdRender {
         let content_left = top_left.x;
         top_left.x += self.layout.block_padding;
         top_left.y += self.layout.block_padding;
-        top_left.y -= self.layout.row_spacing; // makes spacing logic simpler
 
         let reveal = self.reveal_fenced_code_block(node, node_code_block);
+        if !reveal {
+            if let Some(diagram) = self.layout_diagram_block(node, node_code_block, width) {
+                self.show_wrap_layout(ui, top_left, &diagram);
+                let line = self.bounds.source_lines[self.node_first_line_idx(node) + 1];
+                self.show_block_line_prefixes(
+                    ui,
+                    node,
+                    line,
+                    Pos2::new(content_left, top_left.y),
+                    row_height,
+                );
+                return;
+            }
+        }
+
+        top_left.y -= self.layout.row_spacing; // makes spacing logic simpler
+
         let first_line_idx = self.node_first_line_idx(node);
         let last_line_idx = self.node_last_line_idx(node);
         for line_idx in first_line_idx..=last_line_idx {
@@ -497,7 +519,7 @@ impl<'ast> MdRender {
     // followed only by spaces, which are ignored."
     // https://github.github.com/gfm/#fenced-code-blocks
 
-    fn is_closing_fence(
+    pub fn is_closing_fence(
         &self, node: &'ast AstNode<'ast>, node_code_block: &NodeCodeBlock,
         line: (Grapheme, Grapheme),
     ) -> bool {
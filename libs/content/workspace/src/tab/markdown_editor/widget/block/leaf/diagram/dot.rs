@@ -0,0 +1,485 @@
+//! Graphviz DOT: `graph`/`digraph` with node, edge and subgraph statements,
+//! attribute defaults, and the attributes that change what we draw (`label`,
+//! `shape`, `style`, `dir`, `arrowhead`, `arrowtail`, `rankdir`). Other
+//! attributes are accepted and ignored; ports are dropped.
+
+use super::Diagram;
+use super::graph::{Direction, Edge, Graph};
+use super::scene::{Head, Shape, Stroke};
+
+pub fn parse(source: &str) -> Result<Diagram, String> {
+    let tokens = tokenize(source)?;
+    let mut parser = Parser { tokens, pos: 0, directed: false, graph: Graph::default() };
+    parser.file()?;
+    if parser.graph.nodes.is_empty() {
+        return Err("no nodes".into());
+    }
+    Ok(Diagram::Graph(parser.graph))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    /// An identifier, numeral, or quoted or HTML string (`quoted`, so never a
+    /// keyword).
+    Id {
+        text: String,
+        quoted: bool,
+    },
+    /// `->` (directed) or `--`.
+    EdgeOp {
+        directed: bool,
+    },
+    Punct(char),
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    let mut line_start = true;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\n' {
+            line_start = true;
+            i += 1;
+            continue;
+        }
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        // `#` lines are C preprocessor output
+        if (c == '#' && line_start) || (c == '/' && chars.get(i + 1) == Some(&'/')) {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            continue;
+        }
+        line_start = false;
+        if c == '/' && chars.get(i + 1) == Some(&'*') {
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                i += 1;
+            }
+            if i >= chars.len() {
+                return Err("unterminated comment".into());
+            }
+            i += 2;
+            continue;
+        }
+        match c {
+            '{' | '}' | '[' | ']' | ';' | ',' | '=' | ':' => {
+                tokens.push(Token::Punct(c));
+                i += 1;
+            }
+            '-' if matches!(chars.get(i + 1), Some('>' | '-')) => {
+                tokens.push(Token::EdgeOp { directed: chars[i + 1] == '>' });
+                i += 2;
+            }
+            '"' => {
+                let mut text = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => return Err("unterminated string".into()),
+                        Some('"') => break,
+                        Some('\\') if chars.get(i + 1) == Some(&'"') => {
+                            text.push('"');
+                            i += 2;
+                        }
+                        // a backslash-newline continues the string
+                        Some('\\') if chars.get(i + 1) == Some(&'\n') => i += 2,
+                        Some(&c) => {
+                            text.push(c);
+                            i += 1;
+                        }
+                    }
+                }
+                i += 1;
+                tokens.push(Token::Id { text, quoted: true });
+            }
+            '<' => {
+                let (text, end) = html(&chars, i)?;
+                tokens.push(Token::Id { text, quoted: true });
+                i = end;
+            }
+            c if c.is_alphanumeric() || c == '_' || c == '-' || c == '.' => {
+                let start = i;
+                i += 1;
+                while chars
+                    .get(i)
+                    .is_some_and(|&c| c.is_alphanumeric() || c == '_' || c == '.')
+                {
+                    i += 1;
+                }
+                tokens.push(Token::Id { text: chars[start..i].iter().collect(), quoted: false });
+            }
+            _ => return Err(format!("unexpected {c:?}")),
+        }
+    }
+    Ok(tokens)
+}
+
+/// An HTML-like label starting at `chars[start]` (the `<`): its text with the
+/// markup dropped and `<br/>` as a line break, and the index past its end.
+fn html(chars: &[char], start: usize) -> Result<(String, usize), String> {
+    let mut text = String::new();
+    let mut i = start + 1;
+    while i < chars.len() {
+        match chars[i] {
+            '<' => {
+                let end = (i..chars.len())
+                    .find(|&j| chars[j] == '>')
+                    .ok_or("unterminated HTML label")?;
+                let tag: String = chars[i + 1..end].iter().collect();
+                if tag.trim().to_ascii_lowercase().starts_with("br") {
+                    text.push('\n');
+                }
+                i = end;
+            }
+            '>' => return Ok((text, i + 1)),
+            c => text.push(c),
+        }
+        i += 1;
+    }
+    Err("unterminated HTML label".into())
+}
+
+/// Attribute defaults set by `node [...]` and `edge [...]`, scoped to the
+/// graph or subgraph that sets them.
+#[derive(Clone, Default)]
+struct Defaults {
+    node: Vec<(String, String)>,
+    edge: Vec<(String, String)>,
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    directed: bool,
+    graph: Graph,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn punct(&mut self, c: char) -> bool {
+        let at = self.peek() == Some(&Token::Punct(c));
+        if at {
+            self.pos += 1;
+        }
+        at
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        if self.punct(c) { Ok(()) } else { Err(format!("expected {c:?}")) }
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        let at = matches!(
+            self.peek(),
+            Some(Token::Id { text, quoted: false }) if text.eq_ignore_ascii_case(keyword)
+        );
+        if at {
+            self.pos += 1;
+        }
+        at
+    }
+
+    fn id(&mut self) -> Option<String> {
+        match self.peek() {
+            Some(Token::Id { text, .. }) => {
+                let text = text.clone();
+                self.pos += 1;
+                Some(text)
+            }
+            _ => None,
+        }
+    }
+
+    /// `[strict] (graph | digraph) [id] { ... }`
+    fn file(&mut self) -> Result<(), String> {
+        self.keyword("strict");
+        if self.keyword("digraph") {
+            self.directed = true;
+        } else if !self.keyword("graph") {
+            return Err("expected graph or digraph".into());
+        }
+        if !matches!(self.peek(), Some(Token::Punct('{'))) {
+            self.id();
+        }
+        self.expect('{')?;
+        self.statements(&mut Defaults::default())?;
+        self.expect('}')?;
+        if self.peek().is_some() {
+            return Err("unexpected input after the graph".into());
+        }
+        Ok(())
+    }
+
+    /// Statements up to a closing `}`; the nodes they mention.
+    fn statements(&mut self, defaults: &mut Defaults) -> Result<Vec<usize>, String> {
+        let mut nodes = Vec::new();
+        while !matches!(self.peek(), Some(Token::Punct('}')) | None) {
+            nodes.extend(self.statement(defaults)?);
+            self.punct(';');
+        }
+        Ok(nodes)
+    }
+
+    fn statement(&mut self, defaults: &mut Defaults) -> Result<Vec<usize>, String> {
+        for (keyword, target) in [("node", 0), ("edge", 1), ("graph", 2)] {
+            if matches!(self.tokens.get(self.pos + 1), Some(Token::Punct('[')))
+                && self.keyword(keyword)
+            {
+                let attrs = self.attributes()?;
+                match target {
+                    0 => defaults.node.extend(attrs),
+                    1 => defaults.edge.extend(attrs),
+                    _ => self.graph_attributes(&attrs),
+                }
+                return Ok(Vec::new());
+            }
+        }
+        if matches!(self.tokens.get(self.pos + 1), Some(Token::Punct('='))) {
+            let key = self.id().ok_or("expected an attribute")?;
+            self.pos += 1;
+            let value = self.id().ok_or("expected a value")?;
+            self.graph_attributes(&[(key, value)]);
+            return Ok(Vec::new());
+        }
+
+        let (first, single) = self.endpoint(defaults)?;
+        let mut ends = vec![first];
+        while let Some(Token::EdgeOp { directed }) = self.peek() {
+            if *directed != self.directed {
+                return Err(if self.directed {
+                    "use -> in a digraph".into()
+                } else {
+                    "use -- in a graph".into()
+                });
+            }
+            self.pos += 1;
+            ends.push(self.endpoint(defaults)?.0);
+        }
+        let attrs = if matches!(self.peek(), Some(Token::Punct('['))) {
+            self.attributes()?
+        } else {
+            Vec::new()
+        };
+        if ends.len() == 1 {
+            if let Some(node) = single {
+                self.node_attributes(node, &attrs);
+            }
+        } else {
+            for pair in ends.windows(2) {
+                for &from in &pair[0] {
+                    for &to in &pair[1] {
+                        self.edge(from, to, defaults.edge.iter().chain(&attrs));
+                    }
+                }
+            }
+        }
+        Ok(ends.concat())
+    }
+
+    /// A node or a subgraph: the nodes it stands for, and the node if it's a
+    /// single one.
+    fn endpoint(&mut self, defaults: &Defaults) -> Result<(Vec<usize>, Option<usize>), String> {
+        if self.keyword("subgraph") && !matches!(self.peek(), Some(Token::Punct('{'))) {
+            self.id().ok_or("expected a subgraph")?;
+        }
+        if self.punct('{') {
+            let nodes = self.statements(&mut defaults.clone())?;
+            self.expect('}')?;
+            return Ok((nodes, None));
+        }
+        let id = self.id().ok_or("expected a node")?;
+        // ports (`node:port:compass`) don't change the drawing
+        while self.punct(':') {
+            self.id().ok_or("expected a port")?;
+        }
+        let count = self.graph.nodes.len();
+        let node = self.graph.node(&id, Shape::Ellipse);
+        if self.graph.nodes.len() > count {
+            self.node_attributes(node, &defaults.node);
+        }
+        Ok((vec![node], Some(node)))
+    }
+
+    /// `[a=b, c=d][e=f]`
+    fn attributes(&mut self) -> Result<Vec<(String, String)>, String> {
+        let mut attrs = Vec::new();
+        while self.punct('[') {
+            while !self.punct(']') {
+                let key = self.id().ok_or("expected an attribute")?;
+                self.expect('=')?;
+                let value = self.id().ok_or("expected a value")?;
+                attrs.push((key, value));
+                if !self.punct(',') {
+                    self.punct(';');
+                }
+            }
+        }
+        Ok(attrs)
+    }
+
+    fn graph_attributes(&mut self, attrs: &[(String, String)]) {
+        for (key, value) in attrs {
+            if key == "rankdir" {
+                if let Some(direction) = Direction::parse(value) {
+                    self.graph.direction = direction;
+                }
+            }
+        }
+    }
+
+    fn node_attributes(&mut self, node: usize, attrs: &[(String, String)]) {
+        let node = &mut self.graph.nodes[node];
+        for (key, value) in attrs {
+            match key.as_str() {
+                "label" => node.label = label(value, &node.id),
+                "shape" => node.shape = shape(value),
+                _ => {}
+            }
+        }
+    }
+
+    fn edge<'a>(
+        &mut self, from: usize, to: usize, attrs: impl Iterator<Item = &'a (String, String)>,
+    ) {
+        let mut edge = Edge {
+            from,
+            to,
+            label: String::new(),
+            stroke: Stroke::Solid,
+            tail: Head::None,
+            head: if self.directed { Head::Arrow } else { Head::None },
+        };
+        for (key, value) in attrs {
+            match (key.as_str(), value.as_str()) {
+                ("label", value) => edge.label = label(value, ""),
+                ("style", "dashed") => edge.stroke = Stroke::Dashed,
+                ("style", "dotted") => edge.stroke = Stroke::Dotted,
+                ("style", "bold") => edge.stroke = Stroke::Thick,
+                ("dir", "forward") => (edge.tail, edge.head) = (Head::None, Head::Arrow),
+                ("dir", "back") => (edge.tail, edge.head) = (Head::Arrow, Head::None),
+                ("dir", "both") => (edge.tail, edge.head) = (Head::Arrow, Head::Arrow),
+                ("dir", "none") => (edge.tail, edge.head) = (Head::None, Head::None),
+                ("arrowhead", value) if edge.head != Head::None => edge.head = head(value),
+                ("arrowtail", value) if edge.tail != Head::None => edge.tail = head(value),
+                _ => {}
+            }
+        }
+        self.graph.edges.push(edge);
+    }
+}
+
+/// A label with DOT's escapes: `\N` for the node's id and `\n`, `\l`, `\r`
+/// for line breaks.
+fn label(value: &str, id: &str) -> String {
+    value
+        .replace("\\N", id)
+        .replace("\\n", "\n")
+        .replace("\\l", "\n")
+        .replace("\\r", "\n")
+        .trim_end_matches('\n')
+        .to_string()
+}
+
+fn shape(value: &str) -> Shape {
+    match value {
+        "box" | "rect" | "rectangle" | "square" | "record" | "component" | "folder" | "tab" => {
+            Shape::Rect
+        }
+        "Mrecord" | "cylinder" => Shape::Round,
+        "circle" | "doublecircle" | "point" => Shape::Circle,
+        "diamond" | "Mdiamond" => Shape::Diamond,
+        "hexagon" => Shape::Hexagon,
+        "plaintext" | "plain" | "none" | "underline" => Shape::Plain,
+        "note" => Shape::Note,
+        _ => Shape::Ellipse,
+    }
+}
+
+fn head(value: &str) -> Head {
+    match value {
+        "none" => Head::None,
+        "dot" | "odot" => Head::Circle,
+        "tee" | "box" | "obox" => Head::Cross,
+        "vee" | "open" => Head::Open,
+        _ => Head::Arrow,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(source: &str) -> Graph {
+        match parse(source) {
+            Ok(Diagram::Graph(graph)) => graph,
+            other => panic!("expected a graph, got {other:?}"),
+        }
+    }
+
+    fn edges(graph: &Graph) -> Vec<(&str, &str)> {
+        graph
+            .edges
+            .iter()
+            .map(|e| (graph.nodes[e.from].id.as_str(), graph.nodes[e.to].id.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn digraph_with_attributes() {
+        let graph = graph(
+            r#"digraph G {
+                rankdir=LR;
+                node [shape=box];
+                a [label="Start\nhere"];
+                a -> b -> c [label="next", style=dashed];
+                b -> a [dir=back]; // comment
+                /* block
+                   comment */
+            }"#,
+        );
+        assert_eq!(graph.direction, Direction::LeftRight);
+        assert_eq!(graph.nodes[0].label, "Start\nhere");
+        assert!(graph.nodes.iter().all(|n| n.shape == Shape::Rect));
+        assert_eq!(edges(&graph), vec![("a", "b"), ("b", "c"), ("b", "a")]);
+        assert_eq!(graph.edges[0].label, "next");
+        assert_eq!(graph.edges[1].stroke, Stroke::Dashed);
+        assert_eq!((graph.edges[2].tail, graph.edges[2].head), (Head::Arrow, Head::None));
+    }
+
+    #[test]
+    fn undirected_graph_and_subgraphs() {
+        let graph = graph(
+            "strict graph { a -- {b c}; subgraph cluster_x { node [shape=circle]; d } c -- d }",
+        );
+        assert_eq!(edges(&graph), vec![("a", "b"), ("a", "c"), ("c", "d")]);
+        assert!(graph.edges.iter().all(|e| e.head == Head::None));
+        assert_eq!(graph.nodes[0].shape, Shape::Ellipse);
+        assert_eq!(graph.nodes[3].shape, Shape::Circle);
+    }
+
+    #[test]
+    fn html_labels_and_ports() {
+        let graph = graph("digraph { a [label=<<b>bold</b><br/>plain>]; a:n -> b:s:w }");
+        assert_eq!(graph.nodes[0].label, "bold\nplain");
+        assert_eq!(edges(&graph), vec![("a", "b")]);
+    }
+
+    #[test]
+    fn errors() {
+        assert!(parse("digraph { a -- b }").is_err());
+        assert!(parse("graph { a -> b }").is_err());
+        assert!(parse("digraph { a -> }").is_err());
+        assert!(parse("digraph { \"unterminated }").is_err());
+        assert!(parse("flowchart TD").is_err());
+        assert!(parse("digraph {}").is_err());
+    }
+}
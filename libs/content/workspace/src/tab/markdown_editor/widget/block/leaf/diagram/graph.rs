@@ -0,0 +1,605 @@
+//! Node-and-edge diagrams (mermaid flowcharts, DOT graphs) and their layered
+//! layout: break cycles, rank nodes by longest path, thread long edges
+//! through one placeholder per rank they cross, order each rank by
+//! barycenter to reduce crossings, then pull every node toward the nodes it
+//! connects to while keeping ranks ordered and spaced. Ranks run down the
+//! page and are rotated at the end for left-to-right graphs.
+
+use egui::{Pos2, Rect, Vec2};
+
+use super::scene::{Head, Item, LABEL_PADDING, Label, MARGIN, Measure, Scene, Shape, Stroke};
+
+/// Rounds of crossing reduction and of coordinate refinement.
+const SWEEPS: usize = 8;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Direction {
+    #[default]
+    TopDown,
+    BottomUp,
+    LeftRight,
+    RightLeft,
+}
+
+impl Direction {
+    /// Parses mermaid's and DOT's spellings (`TD`, `TB`, `BT`, `LR`, `RL`).
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_uppercase().as_str() {
+            "TD" | "TB" => Some(Self::TopDown),
+            "BT" => Some(Self::BottomUp),
+            "LR" => Some(Self::LeftRight),
+            "RL" => Some(Self::RightLeft),
+            _ => None,
+        }
+    }
+
+    fn horizontal(self) -> bool {
+        matches!(self, Self::LeftRight | Self::RightLeft)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Node {
+    pub id: String,
+    pub label: String,
+    pub shape: Shape,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub label: String,
+    pub stroke: Stroke,
+    pub tail: Head,
+    pub head: Head,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Graph {
+    pub direction: Direction,
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+impl Graph {
+    /// The index of the node with `id`, added with `shape` and its id for a
+    /// label if it's new.
+    pub fn node(&mut self, id: &str, shape: Shape) -> usize {
+        if let Some(idx) = self.nodes.iter().position(|node| node.id == id) {
+            return idx;
+        }
+        self.nodes
+            .push(Node { id: id.into(), label: id.into(), shape });
+        self.nodes.len() - 1
+    }
+}
+
+/// A node or a placeholder on a long edge, in the layout's own frame where
+/// ranks run down `y`.
+struct Vertex {
+    rank: usize,
+    size: Vec2,
+    real: bool,
+}
+
+pub fn layout(graph: &Graph, font_size: f32, measure: &mut dyn Measure) -> Scene {
+    let horizontal = graph.direction.horizontal();
+    // sizes and offsets in the layout frame
+    let frame = |v: Vec2| if horizontal { Vec2::new(v.y, v.x) } else { v };
+
+    let labels: Vec<Label> = graph
+        .nodes
+        .iter()
+        .map(|node| Label::new(&node.label, font_size, measure))
+        .collect();
+    let sizes: Vec<Vec2> = graph
+        .nodes
+        .iter()
+        .zip(&labels)
+        .map(|(node, label)| node_size(node.shape, label.size, font_size))
+        .collect();
+    let edge_labels: Vec<Label> = graph
+        .edges
+        .iter()
+        .map(|edge| Label::new(&edge.label, font_size, measure))
+        .collect();
+
+    // self-loops bulge out the side of their node; reserve the room
+    let loop_size = font_size;
+    let mut loop_room = vec![0.0f32; graph.nodes.len()];
+    for (edge, label) in graph.edges.iter().zip(&edge_labels) {
+        if edge.from == edge.to {
+            let room = loop_size + frame(label.size).x + LABEL_PADDING.x / 2.0;
+            loop_room[edge.from] = loop_room[edge.from].max(room);
+        }
+    }
+
+    let reversed = acyclic(graph);
+    let oriented = |i: usize| {
+        let edge = &graph.edges[i];
+        if reversed[i] { (edge.to, edge.from) } else { (edge.from, edge.to) }
+    };
+    let ranks = rank(graph, &oriented);
+
+    let mut vertices: Vec<Vertex> = sizes
+        .iter()
+        .zip(&ranks)
+        .zip(&loop_room)
+        .map(|((&size, &rank), &room)| {
+            let size = frame(size);
+            Vertex { rank, size: Vec2::new(size.x + 2.0 * room, size.y), real: true }
+        })
+        .collect();
+    let mut chains: Vec<Vec<usize>> = Vec::with_capacity(graph.edges.len());
+    for i in 0..graph.edges.len() {
+        let (from, to) = oriented(i);
+        if from == to {
+            chains.push(Vec::new());
+            continue;
+        }
+        let mut chain = vec![from];
+        for rank in ranks[from] + 1..ranks[to] {
+            vertices.push(Vertex { rank, size: Vec2::new(font_size / 2.0, 0.0), real: false });
+            chain.push(vertices.len() - 1);
+        }
+        chain.push(to);
+        chains.push(chain);
+    }
+
+    let mut above = vec![Vec::new(); vertices.len()];
+    let mut below = vec![Vec::new(); vertices.len()];
+    for chain in &chains {
+        for pair in chain.windows(2) {
+            below[pair[0]].push(pair[1]);
+            above[pair[1]].push(pair[0]);
+        }
+    }
+
+    let rank_count = ranks.iter().max().map_or(0, |max| max + 1);
+    let mut layers: Vec<Vec<usize>> = vec![Vec::new(); rank_count];
+    for (v, vertex) in vertices.iter().enumerate() {
+        layers[vertex.rank].push(v);
+    }
+    order(&mut layers, &above, &below, vertices.len());
+
+    // coordinates across ranks
+    let node_gap = font_size * 1.5;
+    let gap = |a: &Vertex, b: &Vertex| {
+        let gap = if a.real && b.real { node_gap } else { font_size / 2.0 };
+        (a.size.x + b.size.x) / 2.0 + gap
+    };
+    let mut x = vec![0.0f32; vertices.len()];
+    for layer in &layers {
+        let mut left = 0.0;
+        for (i, &v) in layer.iter().enumerate() {
+            if i > 0 {
+                left += gap(&vertices[layer[i - 1]], &vertices[v]);
+            }
+            x[v] = left;
+        }
+    }
+    for sweep in 0..SWEEPS {
+        let (rs, neighbors): (Vec<usize>, _) = if sweep % 2 == 0 {
+            ((1..rank_count).collect(), &above)
+        } else {
+            ((0..rank_count.saturating_sub(1)).rev().collect(), &below)
+        };
+        for r in rs {
+            let layer = &layers[r];
+            let targets: Vec<f32> = layer
+                .iter()
+                .map(|&v| mean(neighbors[v].iter().map(|&n| x[n])).unwrap_or(x[v]))
+                .collect();
+            let gaps: Vec<f32> = layer
+                .windows(2)
+                .map(|pair| gap(&vertices[pair[0]], &vertices[pair[1]]))
+                .collect();
+            for (&v, placed) in layer.iter().zip(place(&targets, &gaps)) {
+                x[v] = placed;
+            }
+        }
+    }
+    let left = vertices
+        .iter()
+        .zip(&x)
+        .map(|(vertex, x)| x - vertex.size.x / 2.0)
+        .fold(f32::INFINITY, f32::min);
+    for x in &mut x {
+        *x -= left;
+    }
+    let extent = vertices
+        .iter()
+        .zip(&x)
+        .map(|(vertex, x)| x + vertex.size.x / 2.0)
+        .fold(0.0, f32::max);
+
+    // coordinates along ranks; the gap leaves room for edge labels
+    let edge_label_room = graph
+        .edges
+        .iter()
+        .zip(&edge_labels)
+        .filter(|(edge, _)| edge.from != edge.to)
+        .map(|(_, label)| frame(label.size).y)
+        .fold(0.0, f32::max);
+    let rank_gap = (font_size * 2.5).max(edge_label_room + font_size * 1.5);
+    let mut rank_center = Vec::with_capacity(rank_count);
+    let mut top = 0.0;
+    for layer in &layers {
+        let height = layer
+            .iter()
+            .map(|&v| vertices[v].size.y)
+            .fold(0.0, f32::max);
+        rank_center.push(top + height / 2.0);
+        top += height + rank_gap;
+    }
+    let depth = (top - rank_gap).max(0.0);
+
+    // into the drawing's frame
+    let origin = Vec2::splat(MARGIN);
+    let position = |v: usize| {
+        let (x, y) = (x[v], rank_center[vertices[v].rank]);
+        let pos = match graph.direction {
+            Direction::TopDown => Pos2::new(x, y),
+            Direction::BottomUp => Pos2::new(x, depth - y),
+            Direction::LeftRight => Pos2::new(y, x),
+            Direction::RightLeft => Pos2::new(depth - y, x),
+        };
+        pos + origin
+    };
+    let rects: Vec<Rect> = sizes
+        .iter()
+        .enumerate()
+        .map(|(v, &size)| Rect::from_center_size(position(v), size))
+        .collect();
+
+    let mut scene = Scene::default();
+    scene.include(Rect::from_min_size(
+        origin.to_pos2(),
+        if horizontal { Vec2::new(depth, extent) } else { Vec2::new(extent, depth) },
+    ));
+    let mut edge_label_items = Vec::new();
+    for (i, edge) in graph.edges.iter().enumerate() {
+        let (from, to) = (edge.from, edge.to);
+        let (points, label_center) = if from == to {
+            self_loop(graph.nodes[from].shape, rects[from], horizontal, loop_size, &edge_labels[i])
+        } else {
+            let mut points: Vec<Pos2> = chains[i].iter().map(|&v| position(v)).collect();
+            if reversed[i] {
+                points.reverse();
+            }
+            let last = points.len() - 1;
+            points[0] = clip(graph.nodes[from].shape, rects[from], points[1]);
+            points[last] = clip(graph.nodes[to].shape, rects[to], points[last - 1]);
+            let center = midpoint(&points);
+            (points, center)
+        };
+        if !edge_labels[i].is_empty() {
+            let rect =
+                Rect::from_center_size(label_center, edge_labels[i].size + LABEL_PADDING / 2.0);
+            scene.include(rect);
+            edge_label_items.push(Item::Shape { shape: Shape::Plain, rect });
+            edge_labels[i].place(label_center, &mut edge_label_items);
+        }
+        for &point in &points {
+            scene.include(Rect::from_center_size(point, Vec2::ZERO));
+        }
+        scene.items.push(Item::Path {
+            points,
+            stroke: edge.stroke,
+            tail: edge.tail,
+            head: edge.head,
+        });
+    }
+    for ((node, label), &rect) in graph.nodes.iter().zip(&labels).zip(&rects) {
+        scene.include(rect);
+        scene.items.push(Item::Shape { shape: node.shape, rect });
+        label.place(rect.center(), &mut scene.items);
+    }
+    scene.items.extend(edge_label_items);
+    scene
+}
+
+/// The outline that fits a label of `label` size.
+fn node_size(shape: Shape, label: Vec2, font_size: f32) -> Vec2 {
+    let label = label.max(Vec2::new(font_size, font_size * 1.25));
+    let padded = label + 2.0 * LABEL_PADDING;
+    match shape {
+        Shape::Rect | Shape::Round | Shape::Note => padded,
+        Shape::Stadium | Shape::Hexagon => Vec2::new(padded.x + label.y, padded.y),
+        // corners of the label touch the sides at twice its size
+        Shape::Diamond => 2.0 * label + LABEL_PADDING,
+        Shape::Circle => Vec2::splat(label.length() + LABEL_PADDING.y),
+        Shape::Ellipse => label * std::f32::consts::SQRT_2 + LABEL_PADDING,
+        Shape::Plain => label + LABEL_PADDING,
+    }
+}
+
+/// Flags the edges to flip so the graph has no cycles: the edges a
+/// depth-first search finds pointing back into its own path.
+fn acyclic(graph: &Graph) -> Vec<bool> {
+    let mut out = vec![Vec::new(); graph.nodes.len()];
+    for (i, edge) in graph.edges.iter().enumerate() {
+        out[edge.from].push(i);
+    }
+    #[derive(Clone, Copy, PartialEq)]
+    enum Visit {
+        New,
+        Open,
+        Done,
+    }
+    let mut visit = vec![Visit::New; graph.nodes.len()];
+    let mut reversed = vec![false; graph.edges.len()];
+    for root in 0..graph.nodes.len() {
+        if visit[root] != Visit::New {
+            continue;
+        }
+        visit[root] = Visit::Open;
+        let mut stack = vec![(root, 0)];
+        while let Some((node, next)) = stack.last_mut() {
+            let node = *node;
+            if let Some(&edge) = out[node].get(*next) {
+                *next += 1;
+                let to = graph.edges[edge].to;
+                match visit[to] {
+                    Visit::New => {
+                        visit[to] = Visit::Open;
+                        stack.push((to, 0));
+                    }
+                    Visit::Open if to != node => reversed[edge] = true,
+                    _ => {}
+                }
+            } else {
+                visit[node] = Visit::Done;
+                stack.pop();
+            }
+        }
+    }
+    reversed
+}
+
+/// Longest-path ranks: every edge points at least one rank down.
+fn rank(graph: &Graph, oriented: &dyn Fn(usize) -> (usize, usize)) -> Vec<usize> {
+    let n = graph.nodes.len();
+    let mut out = vec![Vec::new(); n];
+    let mut incoming = vec![0; n];
+    for i in 0..graph.edges.len() {
+        let (from, to) = oriented(i);
+        if from != to {
+            out[from].push(to);
+            incoming[to] += 1;
+        }
+    }
+    let mut ranks = vec![0; n];
+    let mut ready: Vec<usize> = (0..n).filter(|&v| incoming[v] == 0).rev().collect();
+    while let Some(v) = ready.pop() {
+        for &to in &out[v] {
+            ranks[to] = ranks[to].max(ranks[v] + 1);
+            incoming[to] -= 1;
+            if incoming[to] == 0 {
+                ready.push(to);
+            }
+        }
+    }
+    ranks
+}
+
+/// Reorders each rank by the mean position of its neighbors in the rank
+/// before it, sweeping down and up alternately.
+fn order(layers: &mut [Vec<usize>], above: &[Vec<usize>], below: &[Vec<usize>], count: usize) {
+    let mut position = vec![0.0f32; count];
+    for layer in layers.iter() {
+        for (i, &v) in layer.iter().enumerate() {
+            position[v] = i as f32;
+        }
+    }
+    for sweep in 0..SWEEPS {
+        let (rs, neighbors): (Vec<usize>, _) = if sweep % 2 == 0 {
+            ((1..layers.len()).collect(), above)
+        } else {
+            ((0..layers.len().saturating_sub(1)).rev().collect(), below)
+        };
+        for r in rs {
+            let mut keyed: Vec<(f32, usize)> = layers[r]
+                .iter()
+                .map(|&v| {
+                    let key = mean(neighbors[v].iter().map(|&n| position[n]));
+                    (key.unwrap_or(position[v]), v)
+                })
+                .collect();
+            keyed.sort_by(|a, b| a.0.total_cmp(&b.0));
+            layers[r] = keyed.into_iter().map(|(_, v)| v).collect();
+            for (i, &v) in layers[r].iter().enumerate() {
+                position[v] = i as f32;
+            }
+        }
+    }
+}
+
+/// Positions as close as possible to `targets` (least squares) with the
+/// `i`th and `i + 1`th at least `gaps[i]` apart: pool-adjacent-violators
+/// over the targets with the gaps taken out.
+fn place(targets: &[f32], gaps: &[f32]) -> Vec<f32> {
+    let mut offsets = Vec::with_capacity(targets.len());
+    let mut offset = 0.0;
+    for i in 0..targets.len() {
+        if i > 0 {
+            offset += gaps[i - 1];
+        }
+        offsets.push(offset);
+    }
+
+    // (sum, count) blocks with non-decreasing means
+    let mut blocks: Vec<(f32, usize)> = Vec::new();
+    for (target, offset) in targets.iter().zip(&offsets) {
+        blocks.push((target - offset, 1));
+        while blocks.len() > 1 {
+            let (sum, count) = blocks[blocks.len() - 1];
+            let (prev_sum, prev_count) = blocks[blocks.len() - 2];
+            if prev_sum / prev_count as f32 <= sum / count as f32 {
+                break;
+            }
+            blocks.pop();
+            *blocks.last_mut().unwrap() = (prev_sum + sum, prev_count + count);
+        }
+    }
+    let mut result = Vec::with_capacity(targets.len());
+    for (sum, count) in blocks {
+        for _ in 0..count {
+            result.push(sum / count as f32 + offsets[result.len()]);
+        }
+    }
+    result
+}
+
+fn mean(values: impl Iterator<Item = f32>) -> Option<f32> {
+    let (sum, count) = values.fold((0.0, 0), |(sum, count), v| (sum + v, count + 1));
+    (count > 0).then(|| sum / count as f32)
+}
+
+/// Where the segment from the center of `rect` toward `toward` crosses the
+/// outline.
+fn clip(shape: Shape, rect: Rect, toward: Pos2) -> Pos2 {
+    let center = rect.center();
+    let d = toward - center;
+    let (a, b) = (rect.width() / 2.0, rect.height() / 2.0);
+    if d.length() < f32::EPSILON || a <= 0.0 || b <= 0.0 {
+        return center;
+    }
+    let t = match shape {
+        Shape::Diamond => 1.0 / (d.x.abs() / a + d.y.abs() / b),
+        Shape::Circle | Shape::Ellipse => 1.0 / ((d.x / a).powi(2) + (d.y / b).powi(2)).sqrt(),
+        _ => (a / d.x.abs()).min(b / d.y.abs()),
+    };
+    center + d * t.min(1.0)
+}
+
+/// The point halfway along a polyline.
+fn midpoint(points: &[Pos2]) -> Pos2 {
+    let length: f32 = points.windows(2).map(|p| p[0].distance(p[1])).sum();
+    let mut remaining = length / 2.0;
+    for p in points.windows(2) {
+        let segment = p[0].distance(p[1]);
+        if segment >= remaining && segment > 0.0 {
+            return p[0] + (p[1] - p[0]) * (remaining / segment);
+        }
+        remaining -= segment;
+    }
+    points[0]
+}
+
+/// A loop out of the side of a node, across the flow: right of it in
+/// vertical graphs and below it in horizontal ones. Returns the path and
+/// where its label goes.
+fn self_loop(
+    shape: Shape, rect: Rect, horizontal: bool, size: f32, label: &Label,
+) -> (Vec<Pos2>, Pos2) {
+    let c = rect.center();
+    let (w, h) = (rect.width(), rect.height());
+    if horizontal {
+        let a = clip(shape, rect, c + Vec2::new(-w / 4.0, h));
+        let b = clip(shape, rect, c + Vec2::new(w / 4.0, h));
+        let y = rect.bottom() + size;
+        let label = Pos2::new(c.x, y + LABEL_PADDING.y / 2.0 + label.size.y / 2.0);
+        (vec![a, Pos2::new(a.x, y), Pos2::new(b.x, y), b], label)
+    } else {
+        let a = clip(shape, rect, c + Vec2::new(w, -h / 4.0));
+        let b = clip(shape, rect, c + Vec2::new(w, h / 4.0));
+        let x = rect.right() + size;
+        let label = Pos2::new(x + LABEL_PADDING.x / 2.0 + label.size.x / 2.0, c.y);
+        (vec![a, Pos2::new(x, a.y), Pos2::new(x, b.y), b], label)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mono(text: &str, size: f32) -> f32 {
+        text.chars().count() as f32 * size * 0.5
+    }
+
+    fn graph(direction: Direction, edges: &[(&str, &str)]) -> Graph {
+        let mut graph = Graph { direction, ..Default::default() };
+        for (from, to) in edges {
+            let from = graph.node(from, Shape::Rect);
+            let to = graph.node(to, Shape::Rect);
+            graph.edges.push(Edge {
+                from,
+                to,
+                label: String::new(),
+                stroke: Stroke::Solid,
+                tail: Head::None,
+                head: Head::Arrow,
+            });
+        }
+        graph
+    }
+
+    fn node_rects(scene: &Scene) -> Vec<Rect> {
+        scene
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Shape { shape: Shape::Rect, rect } => Some(*rect),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn place_keeps_gaps_and_order() {
+        let placed = place(&[5.0, 5.0, 5.0], &[2.0, 2.0]);
+        assert_eq!(placed, vec![3.0, 5.0, 7.0]);
+
+        let placed = place(&[0.0, 10.0], &[2.0]);
+        assert_eq!(placed, vec![0.0, 10.0]);
+    }
+
+    #[test]
+    fn edges_point_down_the_ranks() {
+        let scene = layout(&graph(Direction::TopDown, &[("a", "b"), ("b", "c")]), 10.0, &mut mono);
+        let rects = node_rects(&scene);
+        assert!(rects[0].bottom() < rects[1].top());
+        assert!(rects[1].bottom() < rects[2].top());
+    }
+
+    #[test]
+    fn left_right_rotates() {
+        let scene =
+            layout(&graph(Direction::LeftRight, &[("a", "b"), ("b", "c")]), 10.0, &mut mono);
+        let rects = node_rects(&scene);
+        assert!(rects[0].right() < rects[1].left());
+        assert!(rects[1].right() < rects[2].left());
+    }
+
+    #[test]
+    fn cycles_and_loops_lay_out() {
+        let scene = layout(
+            &graph(Direction::TopDown, &[("a", "b"), ("b", "c"), ("c", "a"), ("c", "c")]),
+            10.0,
+            &mut mono,
+        );
+        let rects = node_rects(&scene);
+        assert_eq!(rects.len(), 3);
+        for (i, a) in rects.iter().enumerate() {
+            for b in &rects[i + 1..] {
+                assert!(!a.intersects(*b), "{a:?} overlaps {b:?}");
+            }
+        }
+        for rect in &rects {
+            assert!(Rect::from_min_size(Pos2::ZERO, scene.size).contains_rect(*rect));
+        }
+    }
+
+    #[test]
+    fn siblings_share_a_rank() {
+        let scene = layout(&graph(Direction::TopDown, &[("a", "b"), ("a", "c")]), 10.0, &mut mono);
+        let rects = node_rects(&scene);
+        assert_eq!(rects[1].center().y, rects[2].center().y);
+        assert!(rects[1].right() < rects[2].left() || rects[2].right() < rects[1].left());
+        // the parent centers over its children
+        let between = (rects[1].center().x + rects[2].center().x) / 2.0;
+        assert!((rects[0].center().x - between).abs() < 0.01);
+    }
+}
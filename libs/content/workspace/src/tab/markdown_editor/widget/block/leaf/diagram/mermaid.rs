@@ -0,0 +1,624 @@
+//! The parts of mermaid people write in notes: `flowchart`/`graph` and
+//! `sequenceDiagram`. Styling statements (`style`, `classDef`, `click`, ...)
+//! and subgraph grouping are accepted and ignored; anything else we can't
+//! read is an error so the block falls back to its source.
+
+use super::Diagram;
+use super::graph::{Direction, Edge, Graph};
+use super::scene::{Head, Shape, Stroke};
+use super::sequence::{Event, Placement, Sequence};
+
+pub fn parse(source: &str) -> Result<Diagram, String> {
+    let mut lines = source
+        .lines()
+        .map(|line| match line.find("%%") {
+            Some(comment) => &line[..comment],
+            None => line,
+        })
+        .map(str::trim)
+        .filter(|line| !line.is_empty());
+    let header = lines.next().ok_or("empty diagram")?;
+    let mut words = header.split_whitespace();
+    match words.next() {
+        Some("flowchart" | "graph") => {
+            let direction = match words.next() {
+                Some(direction) => Direction::parse(direction.trim_end_matches(';'))
+                    .ok_or_else(|| format!("unknown direction {direction:?}"))?,
+                None => Direction::TopDown,
+            };
+            flowchart(direction, lines).map(Diagram::Graph)
+        }
+        Some("sequenceDiagram") => sequence(lines).map(Diagram::Sequence),
+        _ => Err(format!("unsupported diagram {header:?}")),
+    }
+}
+
+fn flowchart<'a>(
+    direction: Direction, lines: impl Iterator<Item = &'a str>,
+) -> Result<Graph, String> {
+    let mut graph = Graph { direction, ..Default::default() };
+    for line in lines {
+        let keyword = line.split_whitespace().next().unwrap_or_default();
+        if matches!(
+            keyword,
+            "subgraph"
+                | "end"
+                | "direction"
+                | "style"
+                | "classDef"
+                | "class"
+                | "click"
+                | "linkStyle"
+        ) {
+            continue;
+        }
+        let mut parser = Parser { chars: line.chars().collect(), pos: 0 };
+        while parser.skip_space() {
+            if parser.eat(";") {
+                continue;
+            }
+            parser.statement(&mut graph)?;
+        }
+    }
+    if graph.nodes.is_empty() {
+        return Err("no nodes".into());
+    }
+    Ok(graph)
+}
+
+/// A link's look, before its ends are known.
+struct Link {
+    label: String,
+    stroke: Stroke,
+    tail: Head,
+    head: Head,
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    /// Skips whitespace; false at the end of the line.
+    fn skip_space(&mut self) -> bool {
+        while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+        self.pos < self.chars.len()
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn at(&self, s: &str) -> bool {
+        let mut pos = self.pos;
+        for c in s.chars() {
+            if self.chars.get(pos) != Some(&c) {
+                return false;
+            }
+            pos += 1;
+        }
+        true
+    }
+
+    fn eat(&mut self, s: &str) -> bool {
+        let at = self.at(s);
+        if at {
+            self.pos += s.chars().count();
+        }
+        at
+    }
+
+    /// Whether the character `offset` ahead can't continue an id, so an `o`
+    /// or `x` before it is an arrow head rather than the start of a node.
+    fn ends_word(&self, offset: usize) -> bool {
+        self.chars
+            .get(self.pos + offset)
+            .is_none_or(|c| !(c.is_alphanumeric() || *c == '_'))
+    }
+
+    /// `A & B --> C -- label --> D`: every node of a group links to every
+    /// node of the next.
+    fn statement(&mut self, graph: &mut Graph) -> Result<(), String> {
+        let mut group = self.group(graph)?;
+        loop {
+            self.skip_space();
+            if self.peek().is_none() || self.at(";") {
+                return Ok(());
+            }
+            let link = self.link()?;
+            self.skip_space();
+            let next = self.group(graph)?;
+            for &from in &group {
+                for &to in &next {
+                    graph.edges.push(Edge {
+                        from,
+                        to,
+                        label: link.label.clone(),
+                        stroke: link.stroke,
+                        tail: link.tail,
+                        head: link.head,
+                    });
+                }
+            }
+            group = next;
+        }
+    }
+
+    fn group(&mut self, graph: &mut Graph) -> Result<Vec<usize>, String> {
+        let mut group = vec![self.node(graph)?];
+        loop {
+            let pos = self.pos;
+            self.skip_space();
+            if self.eat("&") {
+                self.skip_space();
+                group.push(self.node(graph)?);
+            } else {
+                self.pos = pos;
+                return Ok(group);
+            }
+        }
+    }
+
+    /// `id`, optionally with a shape and label: `id[label]`, `id(label)`,
+    /// `id{label}`, `id((label))`, ...
+    fn node(&mut self, graph: &mut Graph) -> Result<usize, String> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_alphanumeric() || c == '_') {
+            self.pos += 1;
+        }
+        if self.pos == start {
+            return Err(format!("expected a node at column {}", self.pos + 1));
+        }
+        let id: String = self.chars[start..self.pos].iter().collect();
+        let idx = graph.node(&id, Shape::Rect);
+
+        // longest openers first
+        const SHAPES: &[(&str, &str, Shape)] = &[
+            ("(((", ")))", Shape::Circle),
+            ("((", "))", Shape::Circle),
+            ("([", "])", Shape::Stadium),
+            ("[[", "]]", Shape::Rect),
+            ("[(", ")]", Shape::Round),
+            ("[/", "/]", Shape::Rect),
+            ("[\\", "\\]", Shape::Rect),
+            ("{{", "}}", Shape::Hexagon),
+            ("(", ")", Shape::Round),
+            ("[", "]", Shape::Rect),
+            ("{", "}", Shape::Diamond),
+            (">", "]", Shape::Rect),
+        ];
+        if let Some(&(open, close, shape)) = SHAPES.iter().find(|(open, ..)| self.at(open)) {
+            self.eat(open);
+            let label = self.label(close)?;
+            let node = &mut graph.nodes[idx];
+            node.label = label;
+            node.shape = shape;
+        }
+        // `:::className`
+        if self.eat(":::") {
+            while self
+                .peek()
+                .is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '-')
+            {
+                self.pos += 1;
+            }
+        }
+        Ok(idx)
+    }
+
+    /// Label text up to `close`, quoted or bare.
+    fn label(&mut self, close: &str) -> Result<String, String> {
+        self.skip_space();
+        let text = if self.eat("\"") {
+            let start = self.pos;
+            while self.peek().is_some_and(|c| c != '"') {
+                self.pos += 1;
+            }
+            let text: String = self.chars[start..self.pos].iter().collect();
+            if !self.eat("\"") {
+                return Err("unterminated string".into());
+            }
+            self.skip_space();
+            if !self.eat(close) {
+                return Err(format!("expected {close:?}"));
+            }
+            text
+        } else {
+            let start = self.pos;
+            while !self.at(close) {
+                if self.peek().is_none() {
+                    return Err(format!("expected {close:?}"));
+                }
+                self.pos += 1;
+            }
+            let text: String = self.chars[start..self.pos].iter().collect();
+            self.eat(close);
+            text
+        };
+        Ok(text_with_breaks(text.trim()))
+    }
+
+    /// `-->`, `---`, `-.->`, `==>`, `--o`, `<-->`, ... with a label as
+    /// `-->|label|` or `-- label -->`.
+    fn link(&mut self) -> Result<Link, String> {
+        let (tail, stroke, head, open) = self.arrow()?;
+        let mut label = String::new();
+        if head == Head::None && open && self.peek().is_some_and(char::is_whitespace) {
+            // `-- label -->`: the label runs to the closing arrow
+            let start = self.pos;
+            let closer = loop {
+                if self.peek().is_none() {
+                    return Err("unterminated link label".into());
+                }
+                let pos = self.pos;
+                if self.peek().is_some_and(|c| matches!(c, '-' | '=' | '.')) {
+                    if let Ok(closer) = self.arrow() {
+                        break closer;
+                    }
+                }
+                self.pos = pos + 1;
+            };
+            let end = self.pos - arrow_len(&self.chars[start..self.pos]);
+            label = self.chars[start..end].iter().collect::<String>();
+            return Ok(Link {
+                label: text_with_breaks(label.trim()),
+                stroke,
+                tail,
+                head: closer.2,
+            });
+        }
+        self.skip_space();
+        if self.eat("|") {
+            let start = self.pos;
+            while self.peek().is_some_and(|c| c != '|') {
+                self.pos += 1;
+            }
+            label = self.chars[start..self.pos].iter().collect();
+            if !self.eat("|") {
+                return Err("unterminated link label".into());
+            }
+            label = text_with_breaks(label.trim().trim_matches('"'));
+        }
+        Ok(Link { label, stroke, tail, head })
+    }
+
+    /// One arrow: its tail, stroke and head, and whether it could open a
+    /// `-- label -->` (a bare `--`, `==` or `-.`).
+    fn arrow(&mut self) -> Result<(Head, Stroke, Head, bool), String> {
+        let start = self.pos;
+        let tail = if self.eat("<") {
+            Head::Arrow
+        } else if (self.at("o") || self.at("x"))
+            && self
+                .chars
+                .get(self.pos + 1)
+                .is_some_and(|&c| matches!(c, '-' | '='))
+        {
+            let tail = if self.at("o") { Head::Circle } else { Head::Cross };
+            self.pos += 1;
+            tail
+        } else {
+            Head::None
+        };
+        let body_start = self.pos;
+        while self.peek().is_some_and(|c| matches!(c, '-' | '=' | '.')) {
+            self.pos += 1;
+        }
+        let body: String = self.chars[body_start..self.pos].iter().collect();
+        if body.len() < 2 {
+            self.pos = start;
+            return Err(format!("expected a link at column {}", start + 1));
+        }
+        let stroke = if body.contains('=') {
+            Stroke::Thick
+        } else if body.contains('.') {
+            Stroke::Dotted
+        } else {
+            Stroke::Solid
+        };
+        let head = if self.at(">") {
+            self.pos += 1;
+            Head::Arrow
+        } else if self.at("o") && self.ends_word(1) {
+            self.pos += 1;
+            Head::Circle
+        } else if self.at("x") && self.ends_word(1) {
+            self.pos += 1;
+            Head::Cross
+        } else {
+            Head::None
+        };
+        let open = tail == Head::None && matches!(body.as_str(), "--" | "==" | "-.");
+        Ok((tail, stroke, head, open))
+    }
+}
+
+/// How many of the trailing `chars` are the arrow that closed a
+/// `-- label -->`.
+fn arrow_len(chars: &[char]) -> usize {
+    let mut len = 0;
+    if matches!(chars.last(), Some('>' | 'o' | 'x')) {
+        len += 1;
+    }
+    len + chars[..chars.len() - len]
+        .iter()
+        .rev()
+        .take_while(|&&c| matches!(c, '-' | '=' | '.'))
+        .count()
+}
+
+fn text_with_breaks(text: &str) -> String {
+    text.replace("<br/>", "\n")
+        .replace("<br />", "\n")
+        .replace("<br>", "\n")
+}
+
+fn sequence<'a>(lines: impl Iterator<Item = &'a str>) -> Result<Sequence, String> {
+    let mut sequence = Sequence::default();
+    // frames, and the `rect`/`box` groupings that also close with `end` but
+    // draw nothing here
+    let mut open: Vec<bool> = Vec::new();
+    for line in lines {
+        let (keyword, rest) = match line.split_once(char::is_whitespace) {
+            Some((keyword, rest)) => (keyword, rest.trim()),
+            None => (line, ""),
+        };
+        match keyword {
+            "participant" | "actor" => {
+                let (id, label) = match rest.split_once(" as ") {
+                    Some((id, label)) => (id.trim(), Some(label.trim())),
+                    None => (rest, None),
+                };
+                if id.is_empty() {
+                    return Err(format!("{keyword} needs a name"));
+                }
+                let idx = sequence.participant(id);
+                let participant = &mut sequence.participants[idx];
+                participant.actor = keyword == "actor";
+                if let Some(label) = label {
+                    participant.label = text_with_breaks(label);
+                }
+            }
+            "autonumber" => sequence.autonumber = true,
+            "loop" | "alt" | "opt" | "par" | "critical" | "break" => {
+                open.push(true);
+                sequence
+                    .events
+                    .push(Event::Open { keyword: keyword.into(), text: text_with_breaks(rest) });
+            }
+            "rect" | "box" => open.push(false),
+            "else" | "and" | "option" => {
+                if open.last() != Some(&true) {
+                    return Err(format!("{keyword} outside a block"));
+                }
+                sequence
+                    .events
+                    .push(Event::Divide { text: text_with_breaks(rest) });
+            }
+            "end" => match open.pop() {
+                Some(true) => sequence.events.push(Event::Close),
+                Some(false) => {}
+                None => return Err("end without a block".into()),
+            },
+            "activate" | "deactivate" | "destroy" | "create" | "title" | "link" | "links" => {}
+            _ if keyword.eq_ignore_ascii_case("note") => {
+                let (placement, text) = rest
+                    .split_once(':')
+                    .ok_or("a note needs a colon before its text")?;
+                let placement = note_placement(&mut sequence, placement.trim())?;
+                sequence
+                    .events
+                    .push(Event::Note { placement, text: text_with_breaks(text.trim()) });
+            }
+            _ => message(&mut sequence, line)?,
+        }
+    }
+    if sequence.participants.is_empty() {
+        return Err("no participants".into());
+    }
+    Ok(sequence)
+}
+
+fn note_placement(sequence: &mut Sequence, placement: &str) -> Result<Placement, String> {
+    let lower = placement.to_ascii_lowercase();
+    if let Some(id) = lower.strip_prefix("left of ") {
+        let id = &placement[placement.len() - id.len()..];
+        Ok(Placement::LeftOf(sequence.participant(id.trim())))
+    } else if let Some(id) = lower.strip_prefix("right of ") {
+        let id = &placement[placement.len() - id.len()..];
+        Ok(Placement::RightOf(sequence.participant(id.trim())))
+    } else if let Some(ids) = lower.strip_prefix("over ") {
+        let ids = &placement[placement.len() - ids.len()..];
+        let (a, b) = ids.split_once(',').unwrap_or((ids, ids));
+        let a = sequence.participant(a.trim());
+        let b = sequence.participant(b.trim());
+        Ok(Placement::Over(a, b))
+    } else {
+        Err(format!("unknown note placement {placement:?}"))
+    }
+}
+
+/// `A->>B: text`, with any of mermaid's arrows.
+fn message(sequence: &mut Sequence, line: &str) -> Result<(), String> {
+    // longest first, so `-->>` isn't read as `-->`
+    const ARROWS: &[(&str, Stroke, Head)] = &[
+        ("-->>", Stroke::Dashed, Head::Arrow),
+        ("->>", Stroke::Solid, Head::Arrow),
+        ("-->", Stroke::Dashed, Head::None),
+        ("->", Stroke::Solid, Head::None),
+        ("--x", Stroke::Dashed, Head::Cross),
+        ("-x", Stroke::Solid, Head::Cross),
+        ("--)", Stroke::Dashed, Head::Open),
+        ("-)", Stroke::Solid, Head::Open),
+    ];
+    let (head_part, text) = match line.split_once(':') {
+        Some((head_part, text)) => (head_part, text.trim()),
+        None => (line, ""),
+    };
+    let (at, &(arrow, stroke, head)) = head_part
+        .char_indices()
+        .filter(|&(_, c)| c == '-')
+        .find_map(|(at, _)| {
+            ARROWS
+                .iter()
+                .find(|(arrow, ..)| head_part[at..].starts_with(arrow))
+                .map(|arrow| (at, arrow))
+        })
+        .ok_or_else(|| format!("unrecognized statement {line:?}"))?;
+    let from = head_part[..at].trim();
+    // activation shorthand: `A->>+B`, `B-->>-A`
+    let to = head_part[at + arrow.len()..]
+        .trim_start_matches(['+', '-'])
+        .trim();
+    if from.is_empty() || to.is_empty() {
+        return Err(format!("a message needs two participants: {line:?}"));
+    }
+    let from = sequence.participant(from);
+    let to = sequence.participant(to);
+    sequence
+        .events
+        .push(Event::Message { from, to, text: text_with_breaks(text), stroke, head });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(source: &str) -> Graph {
+        match parse(source) {
+            Ok(Diagram::Graph(graph)) => graph,
+            other => panic!("expected a graph, got {other:?}"),
+        }
+    }
+
+    fn sequence(source: &str) -> Sequence {
+        match parse(source) {
+            Ok(Diagram::Sequence(sequence)) => sequence,
+            other => panic!("expected a sequence, got {other:?}"),
+        }
+    }
+
+    fn edges(graph: &Graph) -> Vec<(&str, &str, &str)> {
+        graph
+            .edges
+            .iter()
+            .map(|e| {
+                (graph.nodes[e.from].id.as_str(), graph.nodes[e.to].id.as_str(), e.label.as_str())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn flowchart_shapes_and_labels() {
+        let graph = graph("flowchart LR\n  A[Start] --> B{Is it?}\n  B -->|Yes| C((Done))\n");
+        assert_eq!(graph.direction, Direction::LeftRight);
+        let shapes: Vec<_> = graph
+            .nodes
+            .iter()
+            .map(|n| (n.label.as_str(), n.shape))
+            .collect();
+        assert_eq!(
+            shapes,
+            vec![("Start", Shape::Rect), ("Is it?", Shape::Diamond), ("Done", Shape::Circle)]
+        );
+        assert_eq!(edges(&graph), vec![("A", "B", ""), ("B", "C", "Yes")]);
+    }
+
+    #[test]
+    fn flowchart_link_styles() {
+        let graph =
+            graph("graph TD\nA -.-> B\nB ==> C\nC --- D\nD -- text --> E\nE --o F; F <--> A");
+        let styles: Vec<_> = graph
+            .edges
+            .iter()
+            .map(|e| (e.stroke, e.tail, e.head))
+            .collect();
+        assert_eq!(
+            styles,
+            vec![
+                (Stroke::Dotted, Head::None, Head::Arrow),
+                (Stroke::Thick, Head::None, Head::Arrow),
+                (Stroke::Solid, Head::None, Head::None),
+                (Stroke::Solid, Head::None, Head::Arrow),
+                (Stroke::Solid, Head::None, Head::Circle),
+                (Stroke::Solid, Head::Arrow, Head::Arrow),
+            ]
+        );
+        assert_eq!(graph.edges[3].label, "text");
+    }
+
+    #[test]
+    fn flowchart_chains_and_groups() {
+        let graph = graph("flowchart TD\nA & B --> C --> D\n%% a comment\nstyle A fill:#f9f\n");
+        assert_eq!(edges(&graph), vec![("A", "C", ""), ("B", "C", ""), ("C", "D", "")]);
+    }
+
+    #[test]
+    fn flowchart_errors() {
+        assert!(parse("flowchart TD\nA -->").is_err());
+        assert!(parse("flowchart TD\nA[unclosed --> B").is_err());
+        assert!(parse("pie title Pets\n\"Dogs\" : 386").is_err());
+        assert!(parse("").is_err());
+    }
+
+    #[test]
+    fn sequence_messages_and_notes() {
+        let sequence = sequence(
+            "sequenceDiagram\n\
+             participant A as Alice\n\
+             actor B\n\
+             A->>B: Hello\n\
+             B-->>-A: Hi\n\
+             A-)B: async\n\
+             Note over A,B: both\n\
+             loop Every minute\n\
+             A->B: ping\n\
+             end\n",
+        );
+        assert_eq!(sequence.participants[0].label, "Alice");
+        assert!(sequence.participants[1].actor);
+        assert_eq!(
+            sequence.events[..3],
+            [
+                Event::Message {
+                    from: 0,
+                    to: 1,
+                    text: "Hello".into(),
+                    stroke: Stroke::Solid,
+                    head: Head::Arrow
+                },
+                Event::Message {
+                    from: 1,
+                    to: 0,
+                    text: "Hi".into(),
+                    stroke: Stroke::Dashed,
+                    head: Head::Arrow
+                },
+                Event::Message {
+                    from: 0,
+                    to: 1,
+                    text: "async".into(),
+                    stroke: Stroke::Solid,
+                    head: Head::Open
+                },
+            ]
+        );
+        assert_eq!(
+            sequence.events[3],
+            Event::Note { placement: Placement::Over(0, 1), text: "both".into() }
+        );
+        assert!(matches!(&sequence.events[4], Event::Open { keyword, .. } if keyword == "loop"));
+        assert_eq!(sequence.events.last(), Some(&Event::Close));
+    }
+
+    #[test]
+    fn sequence_errors() {
+        assert!(parse("sequenceDiagram\nend").is_err());
+        assert!(parse("sequenceDiagram\nA talks to B").is_err());
+        assert!(parse("sequenceDiagram\n").is_err());
+    }
+}
@@ -0,0 +1,351 @@
+//! Diagram code blocks: fenced blocks tagged `mermaid` (flowcharts and
+//! sequence diagrams) or `dot` are laid out and drawn natively while the
+//! block isn't revealed, as a single atomic embed ([`EmbedKind::Diagram`])
+//! in place of the code lines. Source that doesn't parse stays highlighted
+//! code, so a diagram being typed reads as code until it's valid.
+
+mod dot;
+mod graph;
+mod mermaid;
+pub(crate) mod scene;
+mod sequence;
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use comrak::nodes::{AstNode, NodeCodeBlock};
+use egui::{Color32, Pos2, Rect, Vec2};
+use lb_rs::model::text::offset_types::{Grapheme, RangeExt as _};
+
+use crate::TextBufferArea;
+use crate::tab::markdown_editor::MdRender;
+use crate::tab::markdown_editor::widget::utils::wrap_layout::{
+    BufferExt as _, EmbedKind, EmbedSpec, Layout, WrapUnitLayout,
+};
+use crate::theme::palette_v2::{ThemeExt as _, translucent_over};
+use scene::{Head, Item, Scene, Shape, Stroke};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DiagramLang {
+    Mermaid,
+    Dot,
+}
+
+impl DiagramLang {
+    /// The language a fenced block's info string names, if we draw it.
+    pub fn from_info(info: &str) -> Option<Self> {
+        let lang = info.split_whitespace().next()?;
+        if lang.eq_ignore_ascii_case("mermaid") {
+            Some(Self::Mermaid)
+        } else if lang.eq_ignore_ascii_case("dot") || lang.eq_ignore_ascii_case("graphviz") {
+            Some(Self::Dot)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Diagram {
+    Graph(graph::Graph),
+    Sequence(sequence::Sequence),
+}
+
+pub fn parse(source: &str, lang: DiagramLang) -> Result<Diagram, String> {
+    match lang {
+        DiagramLang::Mermaid => mermaid::parse(source),
+        DiagramLang::Dot => dot::parse(source),
+    }
+}
+
+pub fn layout(diagram: &Diagram, font_size: f32, measure: &mut dyn scene::Measure) -> Scene {
+    match diagram {
+        Diagram::Graph(graph) => graph::layout(graph, font_size, measure),
+        Diagram::Sequence(sequence) => sequence::layout(sequence, font_size, measure),
+    }
+}
+
+/// Laid-out diagrams by source, language and size, so each is parsed and
+/// laid out once rather than every frame; `None` records source that
+/// doesn't parse. Entries not used during a frame are dropped at the end of
+/// it.
+#[derive(Default)]
+pub struct DiagramCache {
+    map: RefCell<HashMap<DiagramCacheKey, Option<Arc<Scene>>>>,
+    used_this_frame: RefCell<HashSet<DiagramCacheKey>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct DiagramCacheKey {
+    source: String,
+    lang: DiagramLang,
+    size_bits: u32,
+    ppi_bits: u32,
+}
+
+impl DiagramCache {
+    fn get_or_layout(
+        &self, key: DiagramCacheKey, layout: impl FnOnce() -> Option<Scene>,
+    ) -> Option<Arc<Scene>> {
+        self.used_this_frame.borrow_mut().insert(key.clone());
+        if let Some(scene) = self.map.borrow().get(&key) {
+            return scene.clone();
+        }
+        let scene = layout().map(Arc::new);
+        self.map.borrow_mut().insert(key, scene.clone());
+        scene
+    }
+
+    pub fn garbage_collect(&self) {
+        let used = std::mem::take(&mut *self.used_this_frame.borrow_mut());
+        self.map.borrow_mut().retain(|key, _| used.contains(key));
+    }
+}
+
+impl<'ast> MdRender {
+    /// The laid-out diagram for `source` at body text size, or `None` if it
+    /// doesn't parse.
+    pub fn diagram(&self, source: &str, lang: DiagramLang) -> Option<Arc<Scene>> {
+        let size = self.layout.row_height;
+        let ppi = self.ctx.pixels_per_point();
+        let key = DiagramCacheKey {
+            source: source.into(),
+            lang,
+            size_bits: size.to_bits(),
+            ppi_bits: ppi.to_bits(),
+        };
+        self.diagrams.get_or_layout(key, || {
+            let diagram = parse(source, lang).ok()?;
+            let format = self.text_format_document();
+            let mut measure = |text: &str, size: f32| {
+                self.upsert_glyphon_buffer_unwrapped(text, size, size, f32::MAX, &format)
+                    .read()
+                    .unwrap()
+                    .shaped_size(ppi)
+                    .x
+            };
+            let scene = layout(&diagram, size, &mut measure);
+            (scene.size.x > 0.0 && scene.size.y > 0.0).then_some(scene)
+        })
+    }
+
+    /// A collapsed diagram block's content: one embed covering the code
+    /// lines, scaled down to `width` if the diagram is wider. `None` if the
+    /// block isn't a diagram or doesn't parse, so it renders as code.
+    pub fn layout_diagram_block(
+        &self, node: &'ast AstNode<'ast>, node_code_block: &NodeCodeBlock, width: f32,
+    ) -> Option<WrapUnitLayout> {
+        let lang = DiagramLang::from_info(&node_code_block.info)?;
+        let scene = self.diagram(&node_code_block.literal, lang)?;
+        let range = self.diagram_block_range(node, node_code_block)?;
+        let scale = (width / scene.size.x).min(1.0);
+
+        let mut layout = Layout::new(range);
+        layout.push_embed(EmbedSpec {
+            advance: scene.size.x * scale,
+            ascent: scene.size.y * scale,
+            descent: 0.,
+            source_range: range,
+            url: node_code_block.literal.clone(),
+            kind: EmbedKind::Diagram { lang },
+        });
+        Some(self.compute_layout_from(layout, width, self.layout.row_height))
+    }
+
+    /// The code lines of a fenced block, between its fences.
+    fn diagram_block_range(
+        &self, node: &'ast AstNode<'ast>, node_code_block: &NodeCodeBlock,
+    ) -> Option<(Grapheme, Grapheme)> {
+        let opening_line_idx = self.node_first_line_idx(node);
+        let mut last_line_idx = self.node_last_line_idx(node);
+        if last_line_idx > opening_line_idx
+            && self.is_closing_fence(node, node_code_block, self.bounds.source_lines[last_line_idx])
+        {
+            last_line_idx -= 1;
+        }
+        if last_line_idx <= opening_line_idx {
+            return None;
+        }
+        let first = self.node_line(node, self.bounds.source_lines[opening_line_idx + 1]);
+        let last = self.node_line(node, self.bounds.source_lines[last_line_idx]);
+        Some((first.start(), last.end()))
+    }
+
+    /// Paint a laid-out diagram into `rect` (the slot the layout reserved),
+    /// scaled to its width.
+    pub fn paint_diagram(
+        &mut self, ui: &mut egui::Ui, source: &str, lang: DiagramLang, rect: Rect,
+    ) {
+        let Some(scene) = self.diagram(source, lang) else {
+            return;
+        };
+        let scale = rect.width() / scene.size.x;
+        let at = |p: Pos2| rect.min + p.to_vec2() * scale;
+        let ppi = self.ctx.pixels_per_point();
+
+        let theme = self.ctx.get_lb_theme();
+        let text = self.text_format_document().color;
+        let line = egui::Stroke::new(1., theme.neutral_fg_secondary());
+        let fill = theme.neutral_bg();
+        let note = theme.bg().yellow;
+        let tab = theme.neutral_bg_tertiary();
+        // matches the code block behind the diagram
+        let background = match theme.code_variant() {
+            Some(code) => translucent_over(code.background, theme.neutral_bg(), 0.5),
+            None => theme.neutral_bg(),
+        };
+
+        let painter = ui.painter().clone();
+        for item in &scene.items {
+            match item {
+                Item::Shape { shape, rect } => {
+                    let rect = Rect::from_min_max(at(rect.min), at(rect.max));
+                    let (w, h) = (rect.width(), rect.height());
+                    let stroke_kind = egui::StrokeKind::Inside;
+                    match shape {
+                        Shape::Rect => {
+                            painter.rect(rect, 2., fill, line, stroke_kind);
+                        }
+                        Shape::Round => {
+                            painter.rect(rect, 8. * scale, fill, line, stroke_kind);
+                        }
+                        Shape::Stadium => {
+                            painter.rect(rect, h / 2., fill, line, stroke_kind);
+                        }
+                        Shape::Note => {
+                            painter.rect(rect, 0., note, line, stroke_kind);
+                        }
+                        Shape::Plain => {
+                            painter.rect_filled(rect, 2., background);
+                        }
+                        Shape::Diamond => {
+                            let c = rect.center();
+                            painter.add(egui::Shape::convex_polygon(
+                                vec![
+                                    Pos2::new(c.x, rect.top()),
+                                    Pos2::new(rect.right(), c.y),
+                                    Pos2::new(c.x, rect.bottom()),
+                                    Pos2::new(rect.left(), c.y),
+                                ],
+                                fill,
+                                line,
+                            ));
+                        }
+                        Shape::Hexagon => {
+                            let inset = (h / 2.).min(w / 4.);
+                            let c = rect.center();
+                            painter.add(egui::Shape::convex_polygon(
+                                vec![
+                                    Pos2::new(rect.left() + inset, rect.top()),
+                                    Pos2::new(rect.right() - inset, rect.top()),
+                                    Pos2::new(rect.right(), c.y),
+                                    Pos2::new(rect.right() - inset, rect.bottom()),
+                                    Pos2::new(rect.left() + inset, rect.bottom()),
+                                    Pos2::new(rect.left(), c.y),
+                                ],
+                                fill,
+                                line,
+                            ));
+                        }
+                        Shape::Circle | Shape::Ellipse => {
+                            let c = rect.center();
+                            let points = (0..48)
+                                .map(|i| {
+                                    let t = i as f32 / 48. * std::f32::consts::TAU;
+                                    c + Vec2::new(t.cos() * w / 2., t.sin() * h / 2.)
+                                })
+                                .collect();
+                            painter.add(egui::Shape::convex_polygon(points, fill, line));
+                        }
+                    }
+                }
+                Item::Path { points, stroke, tail, head } => {
+                    let points: Vec<Pos2> = points.iter().map(|&p| at(p)).collect();
+                    let width = if *stroke == Stroke::Thick { 2.5 } else { 1. };
+                    let path_stroke = egui::Stroke::new(width, line.color);
+                    match stroke {
+                        Stroke::Solid | Stroke::Thick => {
+                            painter.add(egui::Shape::line(points.clone(), path_stroke));
+                        }
+                        Stroke::Dashed => {
+                            painter.extend(egui::Shape::dashed_line(&points, path_stroke, 5., 3.));
+                        }
+                        Stroke::Dotted => {
+                            painter.extend(egui::Shape::dashed_line(&points, path_stroke, 2., 2.));
+                        }
+                    }
+                    let n = points.len();
+                    if n >= 2 {
+                        paint_head(&painter, *head, points[n - 2], points[n - 1], path_stroke);
+                        paint_head(&painter, *tail, points[1], points[0], path_stroke);
+                    }
+                }
+                Item::Frame { rect, tab: tab_rect } => {
+                    let rect = Rect::from_min_max(at(rect.min), at(rect.max));
+                    let tab_rect = Rect::from_min_max(at(tab_rect.min), at(tab_rect.max));
+                    painter.rect_stroke(rect, 0., line, egui::StrokeKind::Inside);
+                    painter.rect(tab_rect, 0., tab, line, egui::StrokeKind::Inside);
+                }
+                Item::Text { text: label, center, size } => {
+                    let size = size * scale;
+                    let buffer = self.upsert_glyphon_buffer_unwrapped(
+                        label,
+                        size,
+                        size,
+                        f32::MAX,
+                        &self.text_format_document(),
+                    );
+                    let (left, shaped) = {
+                        let buffer = buffer.read().unwrap();
+                        (buffer.shaped_left(ppi), buffer.shaped_size(ppi))
+                    };
+                    let min = at(*center) - shaped / 2. - Vec2::new(left, 0.);
+                    let [r, g, b, a] = text.to_array();
+                    self.text_areas.push(TextBufferArea::new(
+                        buffer,
+                        Rect::from_min_size(min, shaped + Vec2::new(left, 0.)),
+                        glyphon::Color::rgba(r, g, b, a),
+                        ui.ctx(),
+                        ui.clip_rect(),
+                    ));
+                }
+            }
+        }
+    }
+}
+
+/// The decoration for a path ending at `end`, coming from `from`.
+fn paint_head(painter: &egui::Painter, head: Head, from: Pos2, end: Pos2, stroke: egui::Stroke) {
+    let length = (end - from).length();
+    if length < f32::EPSILON {
+        return;
+    }
+    let dir = (end - from) / length;
+    let normal = dir.rot90();
+    let size = 8.;
+    let back = end - dir * size;
+    match head {
+        Head::None => {}
+        Head::Arrow => {
+            painter.add(egui::Shape::convex_polygon(
+                vec![end, back + normal * size / 2., back - normal * size / 2.],
+                stroke.color,
+                egui::Stroke::NONE,
+            ));
+        }
+        Head::Open => {
+            painter.line_segment([back + normal * size / 2., end], stroke);
+            painter.line_segment([back - normal * size / 2., end], stroke);
+        }
+        Head::Circle => {
+            painter.circle(end - dir * size / 3., size / 3., Color32::TRANSPARENT, stroke);
+        }
+        Head::Cross => {
+            let c = end - dir * size / 2.;
+            let (a, b) = ((dir + normal) * size / 3., (dir - normal) * size / 3.);
+            painter.line_segment([c - a, c + a], stroke);
+            painter.line_segment([c - b, c + b], stroke);
+        }
+    }
+}
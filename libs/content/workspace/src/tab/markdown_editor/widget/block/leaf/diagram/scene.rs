@@ -0,0 +1,170 @@
+//! The drawing a diagram lays out to: shapes, paths and text positioned in
+//! points, with the origin at the top left of the diagram. Layout produces a
+//! [`Scene`] once per source; painting scales it into the slot the editor
+//! reserved.
+
+use egui::{Pos2, Rect, Vec2};
+
+/// Space between a label and the border of the shape around it.
+pub const LABEL_PADDING: Vec2 = Vec2::new(12.0, 8.0);
+/// Margin around the whole drawing.
+pub const MARGIN: f32 = 8.0;
+
+/// Text measurement: the advance of `text` set at `size`. Layout is pure so
+/// it can be tested with a fixed-width stand-in for the editor's fonts.
+pub trait Measure {
+    fn width(&mut self, text: &str, size: f32) -> f32;
+}
+
+impl<F: FnMut(&str, f32) -> f32> Measure for F {
+    fn width(&mut self, text: &str, size: f32) -> f32 {
+        self(text, size)
+    }
+}
+
+/// The outline drawn around a node's label.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Shape {
+    #[default]
+    Rect,
+    Round,
+    Stadium,
+    Diamond,
+    Hexagon,
+    Circle,
+    Ellipse,
+    /// Label only, no outline (DOT's `plaintext`).
+    Plain,
+    /// Sticky-note fill behind a sequence diagram note.
+    Note,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Stroke {
+    #[default]
+    Solid,
+    Dashed,
+    Dotted,
+    Thick,
+}
+
+/// The decoration at one end of a path.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Head {
+    #[default]
+    None,
+    Arrow,
+    /// Open chevron, for asynchronous sequence messages.
+    Open,
+    Circle,
+    Cross,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Item {
+    Shape {
+        shape: Shape,
+        rect: Rect,
+    },
+    Path {
+        points: Vec<Pos2>,
+        stroke: Stroke,
+        tail: Head,
+        head: Head,
+    },
+    /// One line of text centered on `center`.
+    Text {
+        text: String,
+        center: Pos2,
+        size: f32,
+    },
+    /// A frame around part of a sequence diagram (`loop`, `alt`), with a tab
+    /// in its corner for the keyword.
+    Frame {
+        rect: Rect,
+        tab: Rect,
+    },
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Scene {
+    pub size: Vec2,
+    pub items: Vec<Item>,
+}
+
+impl Scene {
+    /// Extends `size` to cover `rect` plus the margin.
+    pub fn include(&mut self, rect: Rect) {
+        self.size = self.size.max(rect.max.to_vec2() + Vec2::splat(MARGIN));
+    }
+
+    /// Moves every item by `offset`.
+    pub fn translate(&mut self, offset: Vec2) {
+        for item in &mut self.items {
+            match item {
+                Item::Shape { rect, .. } => *rect = rect.translate(offset),
+                Item::Path { points, .. } => {
+                    for point in points {
+                        *point += offset;
+                    }
+                }
+                Item::Text { center, .. } => *center += offset,
+                Item::Frame { rect, tab } => {
+                    *rect = rect.translate(offset);
+                    *tab = tab.translate(offset);
+                }
+            }
+        }
+    }
+}
+
+/// A possibly multi-line label, measured: mermaid's `<br>` and DOT's `\n`
+/// both break lines.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Label {
+    pub lines: Vec<String>,
+    pub size: Vec2,
+    pub font_size: f32,
+}
+
+impl Label {
+    pub fn new(text: &str, font_size: f32, measure: &mut dyn Measure) -> Self {
+        let lines: Vec<String> = if text.is_empty() {
+            Vec::new()
+        } else {
+            text.split('\n')
+                .map(|line| line.trim().to_string())
+                .collect()
+        };
+        let width = lines
+            .iter()
+            .map(|line| measure.width(line, font_size))
+            .fold(0.0, f32::max);
+        let height = lines.len() as f32 * line_height(font_size);
+        Self { lines, size: Vec2::new(width, height), font_size }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// Emits one text item per line, the block centered on `center`.
+    pub fn place(&self, center: Pos2, items: &mut Vec<Item>) {
+        let line_height = line_height(self.font_size);
+        let top = center.y - self.size.y / 2.0;
+        for (i, line) in self.lines.iter().enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+            items.push(Item::Text {
+                text: line.clone(),
+                center: Pos2::new(center.x, top + (i as f32 + 0.5) * line_height),
+                size: self.font_size,
+            });
+        }
+    }
+}
+
+pub fn line_height(font_size: f32) -> f32 {
+    font_size * 1.25
+}
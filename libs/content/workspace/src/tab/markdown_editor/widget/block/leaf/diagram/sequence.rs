@@ -0,0 +1,403 @@
+//! Sequence diagrams: participants across the top with a lifeline each, and
+//! messages, notes and `loop`/`alt` frames stacked down the page in source
+//! order. Participants are spread just far enough apart for the labels
+//! between them.
+
+use egui::{Pos2, Rect, Vec2};
+
+use super::scene::{Head, Item, LABEL_PADDING, Label, MARGIN, Measure, Scene, Shape, Stroke};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Participant {
+    pub id: String,
+    pub label: String,
+    /// Declared with `actor` rather than `participant`.
+    pub actor: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Placement {
+    LeftOf(usize),
+    RightOf(usize),
+    Over(usize, usize),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    Message {
+        from: usize,
+        to: usize,
+        text: String,
+        stroke: Stroke,
+        head: Head,
+    },
+    Note {
+        placement: Placement,
+        text: String,
+    },
+    /// `loop`, `alt`, `opt`, `par`, `critical` or `break`, and its condition.
+    Open {
+        keyword: String,
+        text: String,
+    },
+    /// `else`, `and` or `option`: a new section of the open frame.
+    Divide {
+        text: String,
+    },
+    Close,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Sequence {
+    pub participants: Vec<Participant>,
+    pub events: Vec<Event>,
+    pub autonumber: bool,
+}
+
+impl Sequence {
+    /// The index of the participant with `id`, added if it's new.
+    pub fn participant(&mut self, id: &str) -> usize {
+        if let Some(idx) = self.participants.iter().position(|p| p.id == id) {
+            return idx;
+        }
+        self.participants
+            .push(Participant { id: id.into(), label: id.into(), actor: false });
+        self.participants.len() - 1
+    }
+}
+
+/// A frame being stacked: where it starts, its labels, and where each of
+/// its later sections starts.
+struct Frame {
+    top: f32,
+    keyword: Label,
+    text: Label,
+    dividers: Vec<(f32, Label)>,
+}
+
+pub fn layout(sequence: &Sequence, font_size: f32, measure: &mut dyn Measure) -> Scene {
+    let gap = font_size * 2.0;
+    let row_gap = font_size * 0.75;
+    let loop_size = font_size * 1.5;
+
+    let labels: Vec<Label> = sequence
+        .participants
+        .iter()
+        .map(|p| Label::new(&p.label, font_size, measure))
+        .collect();
+    let boxes: Vec<Vec2> = labels
+        .iter()
+        .map(|label| (label.size + 2.0 * LABEL_PADDING).max(Vec2::new(font_size * 4.0, 0.0)))
+        .collect();
+    let box_height = boxes.iter().map(|b| b.y).fold(0.0, f32::max);
+
+    let mut number = 0;
+    let texts: Vec<Label> = sequence
+        .events
+        .iter()
+        .map(|event| match event {
+            Event::Message { text, .. } if sequence.autonumber => {
+                number += 1;
+                Label::new(&format!("{number}. {text}"), font_size, measure)
+            }
+            Event::Message { text, .. }
+            | Event::Note { text, .. }
+            | Event::Open { text, .. }
+            | Event::Divide { text } => Label::new(text, font_size, measure),
+            Event::Close => Label::default(),
+        })
+        .collect();
+
+    // spread the participants so every label fits between its ends
+    let mut centers = Vec::with_capacity(boxes.len());
+    for (i, b) in boxes.iter().enumerate() {
+        let center = match i {
+            0 => b.x / 2.0,
+            _ => centers[i - 1] + boxes[i - 1].x / 2.0 + gap + b.x / 2.0,
+        };
+        centers.push(center);
+    }
+    let widen = |centers: &mut Vec<f32>, lo: usize, hi: usize, needed: f32| {
+        let deficit = needed - (centers[hi] - centers[lo]);
+        if deficit > 0.0 {
+            for center in &mut centers[hi..] {
+                *center += deficit;
+            }
+        }
+    };
+    for (event, text) in sequence.events.iter().zip(&texts) {
+        match *event {
+            Event::Message { from, to, .. } if from != to => {
+                let needed = text.size.x + 2.0 * LABEL_PADDING.x;
+                widen(&mut centers, from.min(to), from.max(to), needed);
+            }
+            Event::Message { from, .. } if from + 1 < centers.len() => {
+                let needed = loop_size + text.size.x + 2.0 * LABEL_PADDING.x;
+                widen(&mut centers, from, from + 1, needed);
+            }
+            Event::Note { placement: Placement::RightOf(p), .. } if p + 1 < centers.len() => {
+                let needed = text.size.x + 3.0 * LABEL_PADDING.x;
+                widen(&mut centers, p, p + 1, needed);
+            }
+            Event::Note { placement: Placement::LeftOf(p), .. } if p > 0 => {
+                let needed = text.size.x + 3.0 * LABEL_PADDING.x;
+                widen(&mut centers, p - 1, p, needed);
+            }
+            _ => {}
+        }
+    }
+    let left = centers
+        .iter()
+        .zip(&boxes)
+        .map(|(c, b)| c - b.x / 2.0)
+        .fold(f32::INFINITY, f32::min);
+    let right = centers
+        .iter()
+        .zip(&boxes)
+        .map(|(c, b)| c + b.x / 2.0)
+        .fold(f32::NEG_INFINITY, f32::max);
+
+    let mut items = Vec::new();
+    let mut frames = Vec::new();
+    let mut bounds = Rect::NOTHING;
+    let mut y = box_height + gap / 2.0;
+    let mut open: Vec<Frame> = Vec::new();
+    for (event, text) in sequence.events.iter().zip(texts) {
+        match event {
+            Event::Message { from, to, stroke, head, .. } => {
+                let (a, b) = (centers[*from], centers[*to]);
+                if from == to {
+                    let top = y;
+                    let bottom = y + loop_size;
+                    let x = a + loop_size;
+                    let center = Pos2::new(
+                        x + LABEL_PADDING.x / 2.0 + text.size.x / 2.0,
+                        (top + bottom) / 2.0,
+                    );
+                    bounds = bounds.union(Rect::from_center_size(center, text.size));
+                    text.place(center, &mut items);
+                    items.push(Item::Path {
+                        points: vec![
+                            Pos2::new(a, top),
+                            Pos2::new(x, top),
+                            Pos2::new(x, bottom),
+                            Pos2::new(a, bottom),
+                        ],
+                        stroke: *stroke,
+                        tail: Head::None,
+                        head: *head,
+                    });
+                    y = bottom.max(center.y + text.size.y / 2.0) + row_gap;
+                } else {
+                    let line = y + text.size.y + LABEL_PADDING.y / 2.0;
+                    text.place(Pos2::new((a + b) / 2.0, y + text.size.y / 2.0), &mut items);
+                    items.push(Item::Path {
+                        points: vec![Pos2::new(a, line), Pos2::new(b, line)],
+                        stroke: *stroke,
+                        tail: Head::None,
+                        head: *head,
+                    });
+                    y = line + row_gap;
+                }
+            }
+            Event::Note { placement, .. } => {
+                let size = text.size + 2.0 * LABEL_PADDING;
+                let rect = match *placement {
+                    Placement::LeftOf(p) => Rect::from_min_size(
+                        Pos2::new(centers[p] - LABEL_PADDING.x / 2.0 - size.x, y),
+                        size,
+                    ),
+                    Placement::RightOf(p) => {
+                        Rect::from_min_size(Pos2::new(centers[p] + LABEL_PADDING.x / 2.0, y), size)
+                    }
+                    Placement::Over(a, b) => {
+                        let (a, b) = (centers[a.min(b)], centers[a.max(b)]);
+                        let width = size.x.max(b - a + 2.0 * LABEL_PADDING.x);
+                        Rect::from_center_size(
+                            Pos2::new((a + b) / 2.0, y + size.y / 2.0),
+                            Vec2::new(width, size.y),
+                        )
+                    }
+                };
+                bounds = bounds.union(rect);
+                items.push(Item::Shape { shape: Shape::Note, rect });
+                text.place(rect.center(), &mut items);
+                y = rect.bottom() + row_gap;
+            }
+            Event::Open { keyword, .. } => {
+                let keyword = Label::new(keyword, font_size, measure);
+                let tab = keyword.size.y.max(text.size.y) + LABEL_PADDING.y;
+                open.push(Frame { top: y, keyword, text, dividers: Vec::new() });
+                y += tab + row_gap;
+            }
+            Event::Divide { .. } => {
+                if let Some(frame) = open.last_mut() {
+                    let height = text.size.y + LABEL_PADDING.y;
+                    frame.dividers.push((y, text));
+                    y += height + row_gap;
+                }
+            }
+            Event::Close => {
+                if let Some(frame) = open.pop() {
+                    let inset = open.len() as f32 * LABEL_PADDING.x / 2.0;
+                    frames.push((frame, y, inset));
+                    y += row_gap;
+                }
+            }
+        }
+    }
+    while let Some(frame) = open.pop() {
+        let inset = open.len() as f32 * LABEL_PADDING.x / 2.0;
+        frames.push((frame, y, inset));
+        y += row_gap;
+    }
+    let bottom = y + gap / 2.0 - row_gap;
+
+    // frames and lifelines go under everything else
+    let mut under = Vec::new();
+    for (frame, frame_bottom, inset) in frames {
+        let rect = Rect::from_min_max(
+            Pos2::new(left - LABEL_PADDING.x + inset, frame.top),
+            Pos2::new(right + LABEL_PADDING.x - inset, frame_bottom),
+        );
+        let tab = Rect::from_min_size(rect.min, frame.keyword.size + LABEL_PADDING);
+        bounds = bounds.union(rect);
+        under.push(Item::Frame { rect, tab });
+        frame.keyword.place(tab.center(), &mut under);
+        let text_center = Pos2::new(rect.center().x, tab.center().y);
+        bounds = bounds.union(Rect::from_center_size(text_center, frame.text.size));
+        bracketed(&frame.text).place(text_center, &mut under);
+        for (divider, text) in frame.dividers {
+            under.push(Item::Path {
+                points: vec![Pos2::new(rect.left(), divider), Pos2::new(rect.right(), divider)],
+                stroke: Stroke::Dashed,
+                tail: Head::None,
+                head: Head::None,
+            });
+            let center =
+                Pos2::new(rect.center().x, divider + (text.size.y + LABEL_PADDING.y) / 2.0);
+            bracketed(&text).place(center, &mut under);
+        }
+    }
+    for ((&center, &size), (label, participant)) in centers
+        .iter()
+        .zip(&boxes)
+        .zip(labels.iter().zip(&sequence.participants))
+    {
+        under.push(Item::Path {
+            points: vec![Pos2::new(center, box_height), Pos2::new(center, bottom)],
+            stroke: Stroke::Dashed,
+            tail: Head::None,
+            head: Head::None,
+        });
+        let shape = if participant.actor { Shape::Stadium } else { Shape::Rect };
+        for top in [0.0, bottom] {
+            let rect = Rect::from_min_size(
+                Pos2::new(center - size.x / 2.0, top),
+                Vec2::new(size.x, box_height),
+            );
+            bounds = bounds.union(rect);
+            under.push(Item::Shape { shape, rect });
+            label.place(rect.center(), &mut under);
+        }
+    }
+    under.extend(items);
+
+    let mut scene = Scene { size: Vec2::ZERO, items: under };
+    if bounds.is_positive() {
+        scene.translate(Vec2::splat(MARGIN) - bounds.min.to_vec2());
+        scene.size = bounds.size() + Vec2::splat(2.0 * MARGIN);
+    }
+    scene
+}
+
+/// A frame's condition, shown in brackets like mermaid does.
+fn bracketed(text: &Label) -> Label {
+    let mut label = text.clone();
+    if let Some(first) = label.lines.first_mut() {
+        *first = format!("[{first}");
+    }
+    if let Some(last) = label.lines.last_mut() {
+        last.push(']');
+    }
+    label
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mono(text: &str, size: f32) -> f32 {
+        text.chars().count() as f32 * size * 0.5
+    }
+
+    fn message(from: usize, to: usize, text: &str) -> Event {
+        Event::Message { from, to, text: text.into(), stroke: Stroke::Solid, head: Head::Arrow }
+    }
+
+    fn paths(scene: &Scene) -> Vec<&Vec<Pos2>> {
+        scene
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Path { points, head: Head::Arrow, .. } => Some(points),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn messages_stack_down_the_page() {
+        let mut sequence = Sequence::default();
+        let a = sequence.participant("Alice");
+        let b = sequence.participant("Bob");
+        sequence.events.push(message(a, b, "hello"));
+        sequence.events.push(message(b, a, "hi"));
+        let scene = layout(&sequence, 10.0, &mut mono);
+
+        let paths = paths(&scene);
+        assert_eq!(paths.len(), 2);
+        assert!(paths[0][0].y < paths[1][0].y);
+        assert_eq!(paths[0][0].x, paths[1][1].x);
+        assert!(paths[0][0].x < paths[0][1].x);
+    }
+
+    #[test]
+    fn long_labels_spread_participants() {
+        let mut sequence = Sequence::default();
+        let a = sequence.participant("A");
+        let b = sequence.participant("B");
+        let long = "a message much longer than the participant boxes";
+        sequence.events.push(message(a, b, long));
+        let scene = layout(&sequence, 10.0, &mut mono);
+
+        let path = paths(&scene)[0];
+        assert!(path[1].x - path[0].x >= mono(long, 10.0));
+        assert!(scene.size.x >= mono(long, 10.0));
+    }
+
+    #[test]
+    fn frames_cover_their_messages() {
+        let mut sequence = Sequence::default();
+        let a = sequence.participant("A");
+        let b = sequence.participant("B");
+        sequence
+            .events
+            .push(Event::Open { keyword: "loop".into(), text: "every second".into() });
+        sequence.events.push(message(a, b, "ping"));
+        sequence.events.push(Event::Close);
+        let scene = layout(&sequence, 10.0, &mut mono);
+
+        let frame = scene
+            .items
+            .iter()
+            .find_map(|item| match item {
+                Item::Frame { rect, .. } => Some(*rect),
+                _ => None,
+            })
+            .expect("a frame");
+        for point in paths(&scene)[0] {
+            assert!(frame.contains(*point));
+        }
+    }
+}
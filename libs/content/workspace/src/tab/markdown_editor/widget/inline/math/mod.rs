@@ -0,0 +1,212 @@
+//! Typeset math: `$...$` and `$$...$$` render through [`tex`] as an atomic
+//! embed ([`EmbedKind::Math`]) sized to the expression, and reveal their
+//! source for editing when the cursor enters or borders the node, like other
+//! inline syntax. Sizer ([`MdRender::layout_math`]) and painter
+//! ([`MdRender::paint_math`]) share one layout per expression via
+//! [`MathCache`].
+
+pub(crate) mod tex;
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use comrak::nodes::{AstNode, NodeValue};
+use egui::{Pos2, Rect, Vec2};
+use lb_rs::model::text::offset_types::{Grapheme, IntoRangeExt as _, RangeExt as _};
+
+use crate::TextBufferArea;
+use crate::tab::markdown_editor::MdRender;
+use crate::tab::markdown_editor::widget::utils::wrap_layout::{
+    BufferExt as _, EmbedKind, EmbedSpec, Format, Layout, StyleInfo,
+};
+use tex::{MathBox, MathItem, Variant};
+
+/// Typeset expressions by source, style and size, so each is laid out once
+/// rather than every frame. Entries not used during a frame are dropped at
+/// the end of it.
+#[derive(Default)]
+pub struct MathCache {
+    map: RefCell<HashMap<MathCacheKey, Arc<MathBox>>>,
+    used_this_frame: RefCell<HashSet<MathCacheKey>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct MathCacheKey {
+    source: String,
+    display: bool,
+    size_bits: u32,
+    ppi_bits: u32,
+}
+
+impl MathCache {
+    fn get_or_typeset(&self, key: MathCacheKey, typeset: impl FnOnce() -> MathBox) -> Arc<MathBox> {
+        self.used_this_frame.borrow_mut().insert(key.clone());
+        if let Some(math) = self.map.borrow().get(&key) {
+            return math.clone();
+        }
+        let math = Arc::new(typeset());
+        self.map.borrow_mut().insert(key, math.clone());
+        math
+    }
+
+    pub fn garbage_collect(&self) {
+        let used = std::mem::take(&mut *self.used_this_frame.borrow_mut());
+        self.map.borrow_mut().retain(|key, _| used.contains(key));
+    }
+}
+
+impl<'ast> MdRender {
+    pub fn text_format_math(&self, parent: &AstNode<'_>) -> Format {
+        self.text_format_code(parent)
+    }
+
+    fn text_format_math_glyphs(&self, variant: Variant) -> Format {
+        Format {
+            italic: variant == Variant::Italic,
+            bold: variant == Variant::Bold,
+            ..self.text_format_document()
+        }
+    }
+
+    /// Collapsed → emit one `InlineItem::Embed` the size of the typeset
+    /// expression. Revealed / multi-line / blank → the source, formatted like
+    /// inline code, so it can be edited.
+    pub fn layout_math(
+        &self, layout: &mut Layout, node: &'ast AstNode<'ast>, range: (Grapheme, Grapheme),
+    ) {
+        let node_range = self.node_range(node);
+        let (literal, display, dollar) = match &node.data.borrow().value {
+            NodeValue::Math(math) => (math.literal.clone(), math.display_math, math.dollar_math),
+            _ => return,
+        };
+        let single_line = range.contains_range(&node_range, true, true);
+        if !single_line || literal.trim().is_empty() || self.node_revealed(node) {
+            // `$`, or `$$` and `` $` `` for display and code math
+            let delimiter = if display || !dollar { 2 } else { 1 };
+            self.layout_math_source(layout, node, range, delimiter);
+            return;
+        }
+
+        let typeset = self.typeset_math(&literal, display);
+        layout.push_embed(EmbedSpec {
+            advance: typeset.width,
+            ascent: typeset.ascent,
+            descent: typeset.descent,
+            source_range: node_range,
+            url: literal,
+            kind: EmbedKind::Math { display },
+        });
+    }
+
+    /// Like [`Self::layout_code`], with delimiters `delimiter` graphemes long.
+    fn layout_math_source(
+        &self, layout: &mut Layout, node: &'ast AstNode<'ast>, range: (Grapheme, Grapheme),
+        delimiter: usize,
+    ) {
+        let node_range = self.node_range(node);
+        if node_range.trim(&range).is_empty() {
+            return;
+        }
+        let delimiter = delimiter.min(node_range.len().0 / 2);
+        let prefix = (node_range.start(), node_range.start() + delimiter).trim(&range);
+        let infix = (node_range.start() + delimiter, node_range.end() - delimiter).trim(&range);
+        let postfix = (node_range.end() - delimiter, node_range.end()).trim(&range);
+        let reveal = self.node_revealed(node);
+
+        layout
+            .style_open(StyleInfo::new(self.text_format_math(node.parent().unwrap()), node_range));
+        if !prefix.is_empty() {
+            if reveal {
+                layout.push_source(prefix, &self.buffer[prefix], self.text_format_syntax());
+            } else {
+                layout.push_override(prefix.start().into_range(), "", self.text_format_syntax());
+            }
+        }
+        if !infix.is_empty() {
+            layout.push_source(
+                infix,
+                &self.buffer[infix],
+                self.text_format_math(node.parent().unwrap()),
+            );
+        }
+        if !postfix.is_empty() {
+            if reveal {
+                layout.push_source(postfix, &self.buffer[postfix], self.text_format_syntax());
+            } else {
+                layout.push_override(postfix.end().into_range(), "", self.text_format_syntax());
+            }
+        }
+        layout.style_close();
+    }
+
+    /// The typeset expression at body text size, from the cache when it's
+    /// been laid out before.
+    pub fn typeset_math(&self, source: &str, display: bool) -> Arc<MathBox> {
+        let size = self.layout.row_height;
+        let ppi = self.ctx.pixels_per_point();
+        let key = MathCacheKey {
+            source: source.into(),
+            display,
+            size_bits: size.to_bits(),
+            ppi_bits: ppi.to_bits(),
+        };
+        self.math.get_or_typeset(key, || {
+            let mut measure = |text: &str, size: f32, variant: Variant| {
+                let format = self.text_format_math_glyphs(variant);
+                self.upsert_glyphon_buffer_unwrapped(text, size, size, f32::MAX, &format)
+                    .read()
+                    .unwrap()
+                    .shaped_size(ppi)
+                    .x
+            };
+            tex::typeset(source, display, size, &mut measure)
+        })
+    }
+
+    /// Paint a typeset expression into `rect` (the slot the layout reserved):
+    /// glyph runs through glyphon, rules as filled rects.
+    pub fn paint_math(&mut self, ui: &mut egui::Ui, source: &str, display: bool, rect: Rect) {
+        let math = self.typeset_math(source, display);
+        let ppi = self.ctx.pixels_per_point();
+        let color = self.text_format_document().color;
+        let origin = Pos2::new(rect.left(), rect.top() + math.ascent);
+        for item in &math.items {
+            match item {
+                MathItem::Glyphs { text, x, y, size, variant } => {
+                    let format = self.text_format_math_glyphs(*variant);
+                    let buffer =
+                        self.upsert_glyphon_buffer_unwrapped(text, *size, *size, f32::MAX, &format);
+                    let (left, baseline) = {
+                        let buffer = buffer.read().unwrap();
+                        let baseline = buffer
+                            .layout_runs()
+                            .next()
+                            .map(|run| run.line_y / ppi)
+                            .unwrap_or(*size);
+                        (buffer.shaped_left(ppi), baseline)
+                    };
+                    let min = origin + Vec2::new(x - left, y - baseline);
+                    let [r, g, b, a] = color.to_array();
+                    self.text_areas.push(TextBufferArea::new(
+                        buffer,
+                        Rect::from_min_size(min, Vec2::new(rect.width(), *size)),
+                        glyphon::Color::rgba(r, g, b, a),
+                        ui.ctx(),
+                        ui.clip_rect(),
+                    ));
+                }
+                MathItem::Rule { x, y, width, height } => {
+                    // at least a device pixel, so thin bars don't vanish
+                    let height = height.max(1.0 / ppi);
+                    let min = origin + Vec2::new(*x, *y);
+                    ui.painter().rect_filled(
+                        Rect::from_min_size(min, Vec2::new(*width, height)),
+                        0.0,
+                        color,
+                    );
+                }
+            }
+        }
+    }
+}
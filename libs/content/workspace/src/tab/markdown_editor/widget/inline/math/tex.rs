@@ -0,0 +1,1437 @@
+//! A small TeX math typesetter. [`parse`] reads the subset of LaTeX math that
+//! turns up in notes — scripts, fractions, roots, `\left` / `\right`, big
+//! operators, accents, `\text`, font switches, spacing and the matrix / cases /
+//! aligned environments — and [`typeset`] lays it out into positioned glyph
+//! runs and rules following TeX's math rules (Appendix G), simplified.
+//!
+//! Layout is font-free: advances come from a [`Measure`] so the caller shapes
+//! text however it paints it, and vertical extents are approximated per
+//! character. Unknown commands render verbatim rather than failing, since
+//! half-typed source is the common case.
+
+/// Height of the math axis — the centerline of `+`, `=` and fraction bars.
+const AXIS: f32 = 0.25;
+const X_HEIGHT: f32 = 0.45;
+/// Fraction bar, radical and overline thickness.
+const RULE: f32 = 0.05;
+/// Space after a sub- or superscript.
+const SCRIPT_SPACE: f32 = 0.05;
+/// Space either side of a fraction, and the width of a missing delimiter.
+const NULL_DELIMITER: f32 = 0.12;
+/// Extent above and below the baseline of `√`, which is scaled to fit its body.
+const RADICAL: (f32, f32) = (0.8, 0.2);
+/// Nesting beyond this renders the rest of the source verbatim, so hostile
+/// input like ten thousand `{` can't overflow the stack.
+const MAX_DEPTH: usize = 64;
+
+// All lengths above are in em.
+
+/// How a glyph run is styled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Variant {
+    Italic,
+    Upright,
+    Bold,
+}
+
+/// TeX's atom classes, which decide the space between neighbors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Class {
+    Ord,
+    Op,
+    Bin,
+    Rel,
+    Open,
+    Close,
+    Punct,
+    Inner,
+}
+
+/// Column alignment of an environment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Align {
+    Center,
+    Left,
+    /// `aligned`: columns alternate right- and left-aligned around `&`.
+    Aligned,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Accent {
+    Glyph(char),
+    Overline,
+    Underline,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Node {
+    Symbol {
+        text: String,
+        class: Class,
+        variant: Variant,
+    },
+    /// `\sum`, `\int`, `\lim`, ... `large` operators grow in display style;
+    /// `limits` ones take their scripts above and below in display style.
+    Operator {
+        text: String,
+        large: bool,
+        limits: bool,
+    },
+    Group(Vec<Node>),
+    Scripts {
+        base: Box<Node>,
+        sup: Option<Box<Node>>,
+        sub: Option<Box<Node>>,
+    },
+    Frac {
+        num: Box<Node>,
+        den: Box<Node>,
+        rule: bool,
+    },
+    Sqrt {
+        index: Option<Box<Node>>,
+        body: Box<Node>,
+    },
+    /// `\left( ... \right)`; an empty delimiter is `.`.
+    Delimited {
+        left: String,
+        body: Vec<Node>,
+        right: String,
+    },
+    Accent {
+        body: Box<Node>,
+        accent: Accent,
+    },
+    Array {
+        rows: Vec<Vec<Vec<Node>>>,
+        align: Align,
+        left: String,
+        right: String,
+    },
+    /// Explicit space in em, e.g. `\,` or `\quad`.
+    Space(f32),
+}
+
+impl Node {
+    fn class(&self) -> Option<Class> {
+        match self {
+            Node::Symbol { class, .. } => Some(*class),
+            Node::Operator { .. } => Some(Class::Op),
+            Node::Scripts { base, .. } => base.class(),
+            Node::Frac { .. } | Node::Delimited { .. } | Node::Array { .. } => Some(Class::Inner),
+            Node::Group(_) | Node::Sqrt { .. } | Node::Accent { .. } => Some(Class::Ord),
+            Node::Space(_) => None,
+        }
+    }
+}
+
+/// One piece of a typeset expression. Coordinates are relative to the left
+/// end of the expression's baseline, with y growing downward.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MathItem {
+    /// `text` at font `size`, its baseline at `y`.
+    Glyphs { text: String, x: f32, y: f32, size: f32, variant: Variant },
+    /// A filled rectangle: fraction bars, radicals, overlines.
+    Rule { x: f32, y: f32, width: f32, height: f32 },
+}
+
+/// A typeset expression: `width` wide, reaching `ascent` above and `descent`
+/// below its baseline.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MathBox {
+    pub width: f32,
+    pub ascent: f32,
+    pub descent: f32,
+    pub items: Vec<MathItem>,
+}
+
+impl MathBox {
+    /// Puts `other` with its baseline origin at (`x`, `y`), growing this box
+    /// to contain it.
+    fn place(&mut self, other: MathBox, x: f32, y: f32) {
+        self.width = self.width.max(x + other.width);
+        self.ascent = self.ascent.max(other.ascent - y);
+        self.descent = self.descent.max(other.descent + y);
+        self.items
+            .extend(other.items.into_iter().map(|item| match item {
+                MathItem::Glyphs { text, x: gx, y: gy, size, variant } => {
+                    MathItem::Glyphs { text, x: gx + x, y: gy + y, size, variant }
+                }
+                MathItem::Rule { x: rx, y: ry, width, height } => {
+                    MathItem::Rule { x: rx + x, y: ry + y, width, height }
+                }
+            }));
+    }
+
+    fn rule(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        self.width = self.width.max(x + width);
+        self.ascent = self.ascent.max(-y);
+        self.descent = self.descent.max(y + height);
+        self.items.push(MathItem::Rule { x, y, width, height });
+    }
+
+    fn shifted(self, y: f32) -> MathBox {
+        let mut result = MathBox::default();
+        result.place(self, 0.0, y);
+        result
+    }
+}
+
+/// Measures the advance width of `text` shaped at font `size`.
+pub trait Measure {
+    fn advance(&mut self, text: &str, size: f32, variant: Variant) -> f32;
+}
+
+impl<F: FnMut(&str, f32, Variant) -> f32> Measure for F {
+    fn advance(&mut self, text: &str, size: f32, variant: Variant) -> f32 {
+        self(text, size, variant)
+    }
+}
+
+/// Typesets `source` at font `size`, in display style (bigger operators,
+/// limits above and below) or inline text style.
+pub fn typeset(source: &str, display: bool, size: f32, measure: &mut dyn Measure) -> MathBox {
+    let style = if display { Style::Display } else { Style::Text };
+    Typesetter { measure, size }.list(&parse(source), style, None)
+}
+
+// ─── parser ─────────────────────────────────────────────────────────
+
+pub fn parse(source: &str) -> Vec<Node> {
+    let mut parser = Parser { chars: source.chars().collect(), pos: 0, depth: 0 };
+    let mut nodes = vec![];
+    loop {
+        nodes.extend(parser.list(None));
+        if !parser.skip_stray() {
+            return nodes;
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let matched = self.peek() == Some(c);
+        if matched {
+            self.pos += 1;
+        }
+        matched
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    /// The name of the command at the cursor, without consuming it: a run of
+    /// letters, or a single other character as in `\,` or `\\`.
+    fn peek_command(&self) -> Option<String> {
+        if self.peek() != Some('\\') {
+            return None;
+        }
+        let rest = &self.chars[self.pos + 1..];
+        let letters = rest.iter().take_while(|c| c.is_ascii_alphabetic()).count();
+        Some(match letters {
+            0 => rest.first().map(char::to_string).unwrap_or_default(),
+            n => rest[..n].iter().collect(),
+        })
+    }
+
+    fn command(&mut self) -> String {
+        let name = self.peek_command().unwrap_or_default();
+        self.pos += 1 + name.chars().count();
+        name
+    }
+
+    /// Atoms up to the end of the enclosing group: a `}`, `&`, `\\`, `\right`,
+    /// `\end`, `close`, or the end of the source, none of which is consumed.
+    fn list(&mut self, close: Option<char>) -> Vec<Node> {
+        let mut nodes = vec![];
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                None | Some('}') | Some('&') => break,
+                c if c == close => break,
+                Some('\\')
+                    if matches!(
+                        self.peek_command().as_deref(),
+                        Some("\\" | "cr" | "right" | "end")
+                    ) =>
+                {
+                    break;
+                }
+                _ => {}
+            }
+            if let Some(node) = self.atom_with_scripts() {
+                nodes.push(node);
+            }
+        }
+        nodes
+    }
+
+    /// Skips whatever ended a top-level list; `false` at the end of the source.
+    fn skip_stray(&mut self) -> bool {
+        match self.peek() {
+            None => false,
+            Some('\\') => {
+                if self.command() == "end" {
+                    self.raw_arg();
+                }
+                true
+            }
+            Some(_) => {
+                self.pos += 1;
+                true
+            }
+        }
+    }
+
+    fn atom_with_scripts(&mut self) -> Option<Node> {
+        let mut base = match self.peek() {
+            Some('^' | '_') => Node::Group(vec![]),
+            _ => self.atom(false)?,
+        };
+        let (mut sup, mut sub) = (None, None);
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some('^') => {
+                    self.pos += 1;
+                    sup = Some(Box::new(self.script()));
+                }
+                Some('_') => {
+                    self.pos += 1;
+                    sub = Some(Box::new(self.script()));
+                }
+                Some('\\')
+                    if matches!(self.peek_command().as_deref(), Some("limits" | "nolimits")) =>
+                {
+                    let limits = self.command() == "limits";
+                    if let Node::Operator { limits: l, .. } = &mut base {
+                        *l = limits;
+                    }
+                }
+                _ => break,
+            }
+        }
+        if sup.is_none() && sub.is_none() {
+            Some(base)
+        } else {
+            Some(Node::Scripts { base: Box::new(base), sup, sub })
+        }
+    }
+
+    /// A script is a single atom: `x^23` raises only the 2.
+    fn script(&mut self) -> Node {
+        self.skip_whitespace();
+        match self.peek() {
+            None | Some('}' | '&' | '^' | '_') => Node::Group(vec![]),
+            _ => self.atom(true).unwrap_or(Node::Group(vec![])),
+        }
+    }
+
+    /// A command argument: a braced group or a single atom.
+    fn arg(&mut self) -> Node {
+        self.script()
+    }
+
+    /// A braced argument's raw text, as for `\text{...}`.
+    fn raw_arg(&mut self) -> String {
+        self.skip_whitespace();
+        if !self.eat('{') {
+            return self.bump().map(String::from).unwrap_or_default();
+        }
+        let mut text = String::new();
+        let mut depth = 0;
+        while let Some(c) = self.bump() {
+            match c {
+                '{' => depth += 1,
+                '}' if depth == 0 => break,
+                '}' => depth -= 1,
+                '\\' => {
+                    if let Some(escaped) = self.bump() {
+                        text.push(escaped);
+                    }
+                }
+                c => text.push(c),
+            }
+        }
+        text
+    }
+
+    fn atom(&mut self, single: bool) -> Option<Node> {
+        if self.depth >= MAX_DEPTH {
+            let c = self.bump()?;
+            return Some(symbol(&c.to_string(), Class::Ord, Variant::Upright));
+        }
+        self.depth += 1;
+        let node = self.atom_inner(single);
+        self.depth -= 1;
+        node
+    }
+
+    fn atom_inner(&mut self, single: bool) -> Option<Node> {
+        let c = self.bump()?;
+        Some(match c {
+            '{' => {
+                let body = self.list(None);
+                self.eat('}');
+                Node::Group(body)
+            }
+            '\\' => {
+                self.pos -= 1;
+                let name = self.command();
+                return self.command_atom(&name);
+            }
+            '0'..='9' => {
+                let mut text = c.to_string();
+                while !single {
+                    match (self.peek(), self.chars.get(self.pos + 1).copied()) {
+                        (Some(d @ '0'..='9'), _) => text.push(d),
+                        (Some('.'), Some('0'..='9')) => text.push('.'),
+                        _ => break,
+                    }
+                    self.pos += 1;
+                }
+                symbol(&text, Class::Ord, Variant::Upright)
+            }
+            c if c.is_alphabetic() => symbol(&c.to_string(), Class::Ord, Variant::Italic),
+            '+' => symbol("+", Class::Bin, Variant::Upright),
+            '-' => symbol("−", Class::Bin, Variant::Upright),
+            '*' => symbol("∗", Class::Bin, Variant::Upright),
+            '=' | '<' | '>' | ':' => symbol(&c.to_string(), Class::Rel, Variant::Upright),
+            '(' | '[' => symbol(&c.to_string(), Class::Open, Variant::Upright),
+            ')' | ']' | '!' | '?' => symbol(&c.to_string(), Class::Close, Variant::Upright),
+            ',' | ';' => symbol(&c.to_string(), Class::Punct, Variant::Upright),
+            '\'' => symbol("′", Class::Ord, Variant::Upright),
+            '~' => Node::Space(0.33),
+            c => symbol(&c.to_string(), Class::Ord, Variant::Upright),
+        })
+    }
+
+    fn command_atom(&mut self, name: &str) -> Option<Node> {
+        let accent = |c| Some(Accent::Glyph(c));
+        let accent = match name {
+            "hat" | "widehat" => accent('ˆ'),
+            "tilde" | "widetilde" => accent('˜'),
+            "vec" | "overrightarrow" => accent('→'),
+            "dot" => accent('˙'),
+            "ddot" => accent('¨'),
+            "check" => accent('ˇ'),
+            "acute" => accent('´'),
+            "grave" => accent('`'),
+            "breve" => accent('˘'),
+            "bar" | "overline" => Some(Accent::Overline),
+            "underline" => Some(Accent::Underline),
+            _ => None,
+        };
+        if let Some(accent) = accent {
+            return Some(Node::Accent { body: Box::new(self.arg()), accent });
+        }
+
+        Some(match name {
+            "frac" | "dfrac" | "tfrac" | "cfrac" => {
+                let num = Box::new(self.arg());
+                let den = Box::new(self.arg());
+                Node::Frac { num, den, rule: true }
+            }
+            "binom" | "dbinom" | "tbinom" => {
+                let num = Box::new(self.arg());
+                let den = Box::new(self.arg());
+                Node::Delimited {
+                    left: "(".into(),
+                    body: vec![Node::Frac { num, den, rule: false }],
+                    right: ")".into(),
+                }
+            }
+            "sqrt" => {
+                self.skip_whitespace();
+                let index = if self.eat('[') {
+                    let index = self.list(Some(']'));
+                    self.eat(']');
+                    Some(Box::new(Node::Group(index)))
+                } else {
+                    None
+                };
+                Node::Sqrt { index, body: Box::new(self.arg()) }
+            }
+            "left" => {
+                let left = self.delimiter();
+                let body = self.list(None);
+                let right = if self.peek_command().as_deref() == Some("right") {
+                    self.command();
+                    self.delimiter()
+                } else {
+                    String::new()
+                };
+                Node::Delimited { left, body, right }
+            }
+            "big" | "Big" | "bigg" | "Bigg" | "bigl" | "Bigl" | "biggl" | "Biggl" | "bigr"
+            | "Bigr" | "biggr" | "Biggr" | "bigm" | "Bigm" => {
+                let class = match name.chars().last() {
+                    Some('l') => Class::Open,
+                    Some('r') => Class::Close,
+                    Some('m') => Class::Rel,
+                    _ => Class::Ord,
+                };
+                symbol(&self.delimiter(), class, Variant::Upright)
+            }
+            "text" | "textrm" | "textnormal" | "textsf" | "texttt" | "mbox" => {
+                symbol(&self.raw_arg(), Class::Ord, Variant::Upright)
+            }
+            "textit" => symbol(&self.raw_arg(), Class::Ord, Variant::Italic),
+            "textbf" => symbol(&self.raw_arg(), Class::Ord, Variant::Bold),
+            "operatorname" => Node::Operator { text: self.raw_arg(), large: false, limits: false },
+            "mathrm" | "mathsf" | "mathtt" | "mathcal" | "mathscr" | "mathfrak" => {
+                restyle(self.arg(), Variant::Upright)
+            }
+            "mathit" => restyle(self.arg(), Variant::Italic),
+            "mathbf" | "boldsymbol" | "bm" => restyle(self.arg(), Variant::Bold),
+            "mathbb" => blackboard(self.arg()),
+            "," | "thinspace" => Node::Space(3.0 / 18.0),
+            ":" | ">" | "medspace" => Node::Space(4.0 / 18.0),
+            ";" | "thickspace" => Node::Space(5.0 / 18.0),
+            "!" | "negthinspace" => Node::Space(-3.0 / 18.0),
+            " " => Node::Space(0.33),
+            "quad" => Node::Space(1.0),
+            "qquad" => Node::Space(2.0),
+            "begin" => self.environment(),
+            "displaystyle" | "textstyle" | "scriptstyle" | "limits" | "nolimits" | "nonumber"
+            | "notag" | "mathstrut" => return None,
+            name => command_symbol(name)
+                .unwrap_or_else(|| symbol(&format!("\\{name}"), Class::Ord, Variant::Upright)),
+        })
+    }
+
+    /// A delimiter after `\left`, `\right` or `\big`; empty for `.`.
+    fn delimiter(&mut self) -> String {
+        self.skip_whitespace();
+        if self.peek() == Some('\\') {
+            return match self.command().as_str() {
+                "{" | "lbrace" => "{",
+                "}" | "rbrace" => "}",
+                "|" | "Vert" | "lVert" | "rVert" => "‖",
+                "vert" | "lvert" | "rvert" => "|",
+                "langle" => "⟨",
+                "rangle" => "⟩",
+                "lfloor" => "⌊",
+                "rfloor" => "⌋",
+                "lceil" => "⌈",
+                "rceil" => "⌉",
+                "backslash" => "\\",
+                _ => "",
+            }
+            .into();
+        }
+        match self.bump() {
+            None | Some('.') => String::new(),
+            Some('<') => "⟨".into(),
+            Some('>') => "⟩".into(),
+            Some(c) => c.into(),
+        }
+    }
+
+    /// `\begin{name} a & b \\ c & d \end{name}`, with `\begin` consumed.
+    fn environment(&mut self) -> Node {
+        let name = self.raw_arg();
+        let (align, left, right) = match name.trim_end_matches('*') {
+            "pmatrix" => (Align::Center, "(", ")"),
+            "bmatrix" => (Align::Center, "[", "]"),
+            "Bmatrix" => (Align::Center, "{", "}"),
+            "vmatrix" => (Align::Center, "|", "|"),
+            "Vmatrix" => (Align::Center, "‖", "‖"),
+            "cases" => (Align::Left, "{", ""),
+            "aligned" | "align" | "alignat" | "split" | "eqnarray" => (Align::Aligned, "", ""),
+            "array" => {
+                self.raw_arg(); // column spec
+                (Align::Center, "", "")
+            }
+            _ => (Align::Center, "", ""),
+        };
+
+        let mut rows = vec![];
+        let mut row = vec![];
+        loop {
+            let cell = self.list(None);
+            row.push(cell);
+            match self.peek() {
+                Some('&') => self.pos += 1,
+                Some('\\') if self.peek_command().as_deref() != Some("right") => {
+                    if self.command() == "end" {
+                        self.raw_arg();
+                        break;
+                    }
+                    rows.push(std::mem::take(&mut row));
+                    // `\\[2pt]` spacing
+                    self.skip_whitespace();
+                    if self.eat('[') {
+                        self.list(Some(']'));
+                        self.eat(']');
+                    }
+                }
+                _ => break,
+            }
+        }
+        if !(row.len() == 1 && row[0].is_empty()) {
+            rows.push(row);
+        }
+        Node::Array { rows, align, left: left.into(), right: right.into() }
+    }
+}
+
+fn symbol(text: &str, class: Class, variant: Variant) -> Node {
+    Node::Symbol { text: text.into(), class, variant }
+}
+
+fn restyle(node: Node, to: Variant) -> Node {
+    match node {
+        Node::Symbol { text, class, .. } => Node::Symbol { text, class, variant: to },
+        Node::Group(nodes) => Node::Group(nodes.into_iter().map(|n| restyle(n, to)).collect()),
+        Node::Scripts { base, sup, sub } => Node::Scripts {
+            base: Box::new(restyle(*base, to)),
+            sup: sup.map(|n| Box::new(restyle(*n, to))),
+            sub: sub.map(|n| Box::new(restyle(*n, to))),
+        },
+        node => node,
+    }
+}
+
+fn blackboard(node: Node) -> Node {
+    match node {
+        Node::Symbol { text, class, .. } => {
+            let text = text
+                .chars()
+                .map(|c| match c {
+                    'C' => 'ℂ',
+                    'H' => 'ℍ',
+                    'N' => 'ℕ',
+                    'P' => 'ℙ',
+                    'Q' => 'ℚ',
+                    'R' => 'ℝ',
+                    'Z' => 'ℤ',
+                    c => c,
+                })
+                .collect();
+            Node::Symbol { text, class, variant: Variant::Upright }
+        }
+        Node::Group(nodes) => Node::Group(nodes.into_iter().map(blackboard).collect()),
+        node => node,
+    }
+}
+
+/// Symbols and operators named by a command, e.g. `\alpha` or `\sum`.
+fn command_symbol(name: &str) -> Option<Node> {
+    use Class::*;
+    use Variant::*;
+
+    let large = |text: &str, limits| Node::Operator { text: text.into(), large: true, limits };
+    let named = |limits| Node::Operator { text: name.into(), large: false, limits };
+    let (text, class, variant) = match name {
+        "sum" => return Some(large("∑", true)),
+        "prod" => return Some(large("∏", true)),
+        "coprod" => return Some(large("∐", true)),
+        "bigcup" => return Some(large("⋃", true)),
+        "bigcap" => return Some(large("⋂", true)),
+        "bigvee" => return Some(large("⋁", true)),
+        "bigwedge" => return Some(large("⋀", true)),
+        "bigoplus" => return Some(large("⨁", true)),
+        "bigotimes" => return Some(large("⨂", true)),
+        "int" => return Some(large("∫", false)),
+        "iint" => return Some(large("∬", false)),
+        "iiint" => return Some(large("∭", false)),
+        "oint" => return Some(large("∮", false)),
+        "lim" | "max" | "min" | "sup" | "inf" | "det" | "gcd" | "Pr" | "argmax" | "argmin" => {
+            return Some(named(true));
+        }
+        "liminf" => {
+            return Some(Node::Operator { text: "lim inf".into(), large: false, limits: true });
+        }
+        "limsup" => {
+            return Some(Node::Operator { text: "lim sup".into(), large: false, limits: true });
+        }
+        "sin" | "cos" | "tan" | "cot" | "sec" | "csc" | "sinh" | "cosh" | "tanh" | "coth"
+        | "arcsin" | "arccos" | "arctan" | "log" | "ln" | "lg" | "exp" | "deg" | "arg" | "dim"
+        | "hom" | "ker" => return Some(named(false)),
+
+        "alpha" => ("α", Ord, Italic),
+        "beta" => ("β", Ord, Italic),
+        "gamma" => ("γ", Ord, Italic),
+        "delta" => ("δ", Ord, Italic),
+        "epsilon" => ("ϵ", Ord, Italic),
+        "varepsilon" => ("ε", Ord, Italic),
+        "zeta" => ("ζ", Ord, Italic),
+        "eta" => ("η", Ord, Italic),
+        "theta" => ("θ", Ord, Italic),
+        "vartheta" => ("ϑ", Ord, Italic),
+        "iota" => ("ι", Ord, Italic),
+        "kappa" => ("κ", Ord, Italic),
+        "lambda" => ("λ", Ord, Italic),
+        "mu" => ("μ", Ord, Italic),
+        "nu" => ("ν", Ord, Italic),
+        "xi" => ("ξ", Ord, Italic),
+        "pi" => ("π", Ord, Italic),
+        "varpi" => ("ϖ", Ord, Italic),
+        "rho" => ("ρ", Ord, Italic),
+        "varrho" => ("ϱ", Ord, Italic),
+        "sigma" => ("σ", Ord, Italic),
+        "varsigma" => ("ς", Ord, Italic),
+        "tau" => ("τ", Ord, Italic),
+        "upsilon" => ("υ", Ord, Italic),
+        "phi" => ("ϕ", Ord, Italic),
+        "varphi" => ("φ", Ord, Italic),
+        "chi" => ("χ", Ord, Italic),
+        "psi" => ("ψ", Ord, Italic),
+        "omega" => ("ω", Ord, Italic),
+        "Gamma" => ("Γ", Ord, Upright),
+        "Delta" => ("Δ", Ord, Upright),
+        "Theta" => ("Θ", Ord, Upright),
+        "Lambda" => ("Λ", Ord, Upright),
+        "Xi" => ("Ξ", Ord, Upright),
+        "Pi" => ("Π", Ord, Upright),
+        "Sigma" => ("Σ", Ord, Upright),
+        "Upsilon" => ("Υ", Ord, Upright),
+        "Phi" => ("Φ", Ord, Upright),
+        "Psi" => ("Ψ", Ord, Upright),
+        "Omega" => ("Ω", Ord, Upright),
+
+        "infty" => ("∞", Ord, Upright),
+        "partial" => ("∂", Ord, Upright),
+        "nabla" => ("∇", Ord, Upright),
+        "forall" => ("∀", Ord, Upright),
+        "exists" => ("∃", Ord, Upright),
+        "nexists" => ("∄", Ord, Upright),
+        "neg" | "lnot" => ("¬", Ord, Upright),
+        "emptyset" | "varnothing" => ("∅", Ord, Upright),
+        "hbar" => ("ℏ", Ord, Italic),
+        "ell" => ("ℓ", Ord, Upright),
+        "Re" => ("ℜ", Ord, Upright),
+        "Im" => ("ℑ", Ord, Upright),
+        "aleph" => ("ℵ", Ord, Upright),
+        "angle" => ("∠", Ord, Upright),
+        "triangle" => ("△", Ord, Upright),
+        "prime" => ("′", Ord, Upright),
+        "degree" => ("°", Ord, Upright),
+        "top" => ("⊤", Ord, Upright),
+        "bot" => ("⊥", Ord, Upright),
+        "ldots" | "dots" => ("…", Inner, Upright),
+        "cdots" => ("⋯", Inner, Upright),
+        "vdots" => ("⋮", Ord, Upright),
+        "ddots" => ("⋱", Ord, Upright),
+        "{" | "lbrace" => ("{", Open, Upright),
+        "}" | "rbrace" => ("}", Close, Upright),
+        "langle" => ("⟨", Open, Upright),
+        "rangle" => ("⟩", Close, Upright),
+        "lfloor" => ("⌊", Open, Upright),
+        "rfloor" => ("⌋", Close, Upright),
+        "lceil" => ("⌈", Open, Upright),
+        "rceil" => ("⌉", Close, Upright),
+        "lvert" => ("|", Open, Upright),
+        "rvert" => ("|", Close, Upright),
+        "vert" => ("|", Ord, Upright),
+        "|" | "Vert" => ("‖", Ord, Upright),
+        "backslash" => ("\\", Ord, Upright),
+        "#" | "$" | "%" | "&" | "_" => (name, Ord, Upright),
+
+        "pm" => ("±", Bin, Upright),
+        "mp" => ("∓", Bin, Upright),
+        "times" => ("×", Bin, Upright),
+        "div" => ("÷", Bin, Upright),
+        "cdot" => ("⋅", Bin, Upright),
+        "ast" => ("∗", Bin, Upright),
+        "star" => ("⋆", Bin, Upright),
+        "circ" => ("∘", Bin, Upright),
+        "bullet" => ("∙", Bin, Upright),
+        "cap" => ("∩", Bin, Upright),
+        "cup" => ("∪", Bin, Upright),
+        "wedge" | "land" => ("∧", Bin, Upright),
+        "vee" | "lor" => ("∨", Bin, Upright),
+        "setminus" => ("∖", Bin, Upright),
+        "oplus" => ("⊕", Bin, Upright),
+        "ominus" => ("⊖", Bin, Upright),
+        "otimes" => ("⊗", Bin, Upright),
+        "odot" => ("⊙", Bin, Upright),
+
+        "le" | "leq" => ("≤", Rel, Upright),
+        "ge" | "geq" => ("≥", Rel, Upright),
+        "ne" | "neq" => ("≠", Rel, Upright),
+        "approx" => ("≈", Rel, Upright),
+        "equiv" => ("≡", Rel, Upright),
+        "sim" => ("∼", Rel, Upright),
+        "simeq" => ("≃", Rel, Upright),
+        "cong" => ("≅", Rel, Upright),
+        "propto" => ("∝", Rel, Upright),
+        "ll" => ("≪", Rel, Upright),
+        "gg" => ("≫", Rel, Upright),
+        "prec" => ("≺", Rel, Upright),
+        "succ" => ("≻", Rel, Upright),
+        "in" => ("∈", Rel, Upright),
+        "notin" => ("∉", Rel, Upright),
+        "ni" => ("∋", Rel, Upright),
+        "subset" => ("⊂", Rel, Upright),
+        "supset" => ("⊃", Rel, Upright),
+        "subseteq" => ("⊆", Rel, Upright),
+        "supseteq" => ("⊇", Rel, Upright),
+        "parallel" => ("∥", Rel, Upright),
+        "perp" => ("⊥", Rel, Upright),
+        "mid" => ("∣", Rel, Upright),
+        "vdash" => ("⊢", Rel, Upright),
+        "models" => ("⊨", Rel, Upright),
+        "coloneqq" => ("≔", Rel, Upright),
+        "doteq" => ("≐", Rel, Upright),
+        "to" | "rightarrow" => ("→", Rel, Upright),
+        "gets" | "leftarrow" => ("←", Rel, Upright),
+        "leftrightarrow" => ("↔", Rel, Upright),
+        "longrightarrow" => ("⟶", Rel, Upright),
+        "longleftarrow" => ("⟵", Rel, Upright),
+        "Rightarrow" => ("⇒", Rel, Upright),
+        "Leftarrow" => ("⇐", Rel, Upright),
+        "Leftrightarrow" => ("⇔", Rel, Upright),
+        "implies" => ("⟹", Rel, Upright),
+        "impliedby" => ("⟸", Rel, Upright),
+        "iff" => ("⟺", Rel, Upright),
+        "mapsto" => ("↦", Rel, Upright),
+        "uparrow" => ("↑", Rel, Upright),
+        "downarrow" => ("↓", Rel, Upright),
+        "colon" => (":", Punct, Upright),
+        _ => return None,
+    };
+    Some(symbol(text, class, variant))
+}
+
+// ─── layout ─────────────────────────────────────────────────────────
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Style {
+    Display,
+    Text,
+    Script,
+    ScriptScript,
+}
+
+impl Style {
+    fn scale(self) -> f32 {
+        match self {
+            Style::Display | Style::Text => 1.0,
+            Style::Script => 0.7,
+            Style::ScriptScript => 0.5,
+        }
+    }
+
+    fn is_script(self) -> bool {
+        matches!(self, Style::Script | Style::ScriptScript)
+    }
+
+    /// Style of sub- and superscripts.
+    fn script(self) -> Style {
+        match self {
+            Style::Display | Style::Text => Style::Script,
+            _ => Style::ScriptScript,
+        }
+    }
+
+    /// Style of numerators and denominators.
+    fn fraction(self) -> Style {
+        match self {
+            Style::Display => Style::Text,
+            Style::Text => Style::Script,
+            _ => Style::ScriptScript,
+        }
+    }
+}
+
+/// TeX's inter-atom spacing (The TeXbook, chapter 18), indexed by the left
+/// and right atoms' [`Class`]. In mu (1/18 em): 1 is a thin space, 2 medium,
+/// 3 thick; negative entries are dropped in script styles.
+const SPACING: [[i8; 8]; 8] = [
+    [0, 1, -2, -3, 0, 0, 0, -1],
+    [1, 1, 0, -3, 0, 0, 0, -1],
+    [-2, -2, 0, 0, -2, 0, 0, -2],
+    [-3, -3, 0, 0, -3, 0, 0, -3],
+    [0, 0, 0, 0, 0, 0, 0, 0],
+    [0, 1, -2, -3, 0, 0, 0, -1],
+    [-1, -1, 0, -1, -1, -1, -1, -1],
+    [-1, 1, -2, -3, -1, 0, -1, -1],
+];
+
+fn spacing(left: Class, right: Class, style: Style) -> f32 {
+    let entry = SPACING[left as usize][right as usize];
+    if entry < 0 && style.is_script() {
+        return 0.0;
+    }
+    match entry.abs() {
+        1 => 3.0 / 18.0,
+        2 => 4.0 / 18.0,
+        3 => 5.0 / 18.0,
+        _ => 0.0,
+    }
+}
+
+/// Each node's class, with binary operators that have nothing to operate on
+/// made ordinary: the minus in `-x`, `(-1)` and `= -1`.
+fn classes(nodes: &[Node], mut prev: Option<Class>) -> Vec<Option<Class>> {
+    let mut classes: Vec<Option<Class>> = nodes.iter().map(Node::class).collect();
+    for i in 0..classes.len() {
+        let Some(class) = classes[i] else {
+            continue;
+        };
+        if class == Class::Bin {
+            let next = classes[i + 1..].iter().flatten().next().copied();
+            let unary_after = matches!(
+                prev,
+                None | Some(Class::Bin | Class::Op | Class::Rel | Class::Open | Class::Punct)
+            );
+            let unary_before =
+                matches!(next, None | Some(Class::Rel | Class::Close | Class::Punct));
+            if unary_after || unary_before {
+                classes[i] = Some(Class::Ord);
+            }
+        }
+        prev = classes[i];
+    }
+    classes
+}
+
+/// Approximate (ascent, descent) of a character, in em.
+fn char_extent(c: char) -> (f32, f32) {
+    match c {
+        'a' | 'c' | 'e' | 'm' | 'n' | 'o' | 'r' | 's' | 'u' | 'v' | 'w' | 'x' | 'z' | 'α' | 'ε'
+        | 'ϵ' | 'ι' | 'κ' | 'ν' | 'ο' | 'π' | 'σ' | 'τ' | 'υ' | 'ω' | 'ϖ' => {
+            (X_HEIGHT, 0.0)
+        }
+        'g' | 'p' | 'q' | 'y' | 'γ' | 'η' | 'μ' | 'ρ' | 'ϱ' | 'φ' | 'ϕ' | 'χ' | 'ψ' | 'ς' => {
+            (X_HEIGHT, 0.22)
+        }
+        'f' | 'j' | 'β' | 'ζ' | 'ξ' => (0.72, 0.22),
+        '+' | '−' | '=' | '<' | '>' | '∗' | '×' | '÷' | '±' | '∓' | '≤' | '≥' | '≠' | '≈' | '≡'
+        | '∼' | '→' | '←' | '↔' | '⇒' | '⇐' | '⇔' | '∈' | '∉' | '⊂' | '⊃' | '⊆' | '⊇' | '∪'
+        | '∩' | '⋅' | '∘' => (0.58, 0.08),
+        '(' | ')' | '[' | ']' | '{' | '}' | '|' | '‖' | '⟨' | '⟩' | '⌊' | '⌋' | '⌈' | '⌉' | '/'
+        | '\\' => (0.75, 0.25),
+        '∫' | '∬' | '∭' | '∮' => (0.8, 0.25),
+        '√' => RADICAL,
+        ',' | ';' => (0.12, 0.18),
+        '.' | '…' | '⋯' | ' ' => (0.12, 0.0),
+        _ => (0.72, 0.0),
+    }
+}
+
+fn extent(text: &str) -> (f32, f32) {
+    text.chars()
+        .map(char_extent)
+        .fold((0.0f32, 0.0f32), |(a, d), (ca, cd)| (a.max(ca), d.max(cd)))
+}
+
+/// Where an accent glyph's ink starts above its baseline, in em.
+fn accent_bottom(c: char) -> f32 {
+    match c {
+        '→' => 0.18,
+        'ˆ' | 'ˇ' | '´' | '`' => 0.56,
+        _ => 0.58,
+    }
+}
+
+struct Typesetter<'m> {
+    measure: &'m mut dyn Measure,
+    size: f32,
+}
+
+impl Typesetter<'_> {
+    fn size(&self, style: Style) -> f32 {
+        self.size * style.scale()
+    }
+
+    /// Nodes side by side, spaced by class. `prev` is the class of whatever
+    /// precedes the list, as for the right-hand cells of `aligned`.
+    fn list(&mut self, nodes: &[Node], style: Style, prev: Option<Class>) -> MathBox {
+        let size = self.size(style);
+        let mut result = MathBox::default();
+        let mut prev_class = prev;
+        for (node, class) in nodes.iter().zip(classes(nodes, prev)) {
+            let Some(class) = class else {
+                if let Node::Space(em) = node {
+                    result.width += em * size;
+                }
+                continue;
+            };
+            if let Some(prev_class) = prev_class {
+                result.width += spacing(prev_class, class, style) * size;
+            }
+            let x = result.width;
+            let node = self.node(node, style);
+            result.place(node, x, 0.0);
+            prev_class = Some(class);
+        }
+        result
+    }
+
+    fn node(&mut self, node: &Node, style: Style) -> MathBox {
+        let size = self.size(style);
+        match node {
+            Node::Symbol { text, variant, .. } => self.glyphs(text, size, *variant),
+            Node::Operator { text, large: true, .. } => {
+                let glyph_size = size * if style == Style::Display { 1.8 } else { 1.3 };
+                self.centered(text, glyph_size, size)
+            }
+            Node::Operator { text, .. } => self.glyphs(text, size, Variant::Upright),
+            Node::Group(nodes) => self.list(nodes, style, None),
+            Node::Scripts { base, sup, sub } => {
+                self.scripts(base, sup.as_deref(), sub.as_deref(), style)
+            }
+            Node::Frac { num, den, rule } => self.frac(num, den, *rule, style),
+            Node::Sqrt { index, body } => self.sqrt(index.as_deref(), body, style),
+            Node::Delimited { left, body, right } => {
+                let inner = self.list(body, style, None);
+                self.delimited(left, inner, right, style)
+            }
+            Node::Accent { body, accent } => self.accent(body, *accent, style),
+            Node::Array { rows, align, left, right } => {
+                let inner = self.array(rows, *align, style);
+                self.delimited(left, inner, right, style)
+            }
+            Node::Space(em) => MathBox { width: em * size, ..Default::default() },
+        }
+    }
+
+    fn glyphs(&mut self, text: &str, size: f32, variant: Variant) -> MathBox {
+        if text.is_empty() {
+            return MathBox::default();
+        }
+        let (ascent, descent) = extent(text);
+        MathBox {
+            width: self.measure.advance(text, size, variant),
+            ascent: ascent * size,
+            descent: descent * size,
+            items: vec![MathItem::Glyphs { text: text.into(), x: 0.0, y: 0.0, size, variant }],
+        }
+    }
+
+    /// A glyph at `glyph_size`, shifted so it's vertically centered on the
+    /// axis of text at `size`: big operators and stretched delimiters.
+    fn centered(&mut self, text: &str, glyph_size: f32, size: f32) -> MathBox {
+        let glyph = self.glyphs(text, glyph_size, Variant::Upright);
+        let shift = (glyph.ascent - glyph.descent) / 2.0 - AXIS * size;
+        glyph.shifted(shift)
+    }
+
+    fn scripts(
+        &mut self, base_node: &Node, sup: Option<&Node>, sub: Option<&Node>, style: Style,
+    ) -> MathBox {
+        let size = self.size(style);
+        let base = self.node(base_node, style);
+        let script_style = style.script();
+        let script_size = self.size(script_style);
+        let sup = sup.map(|n| self.node(n, script_style));
+        let sub = sub.map(|n| self.node(n, script_style));
+
+        if style == Style::Display {
+            if let Node::Operator { limits: true, .. } = base_node {
+                return self.limits(base, sup, sub, size);
+            }
+        }
+
+        // scripts on a lone character sit at fixed heights, others hang off
+        // the base's extent
+        let (mut up, mut down) = match base_node {
+            Node::Symbol { .. } => (0.0, 0.0),
+            _ => (base.ascent - 0.386 * script_size, base.descent + 0.05 * script_size),
+        };
+        if let Some(sup) = &sup {
+            let min = if style == Style::Display { 0.413 } else { 0.363 };
+            up = up.max(min * size).max(sup.descent + 0.11 * size);
+        }
+        if let Some(sub) = &sub {
+            if sup.is_some() {
+                down = down.max(0.247 * size);
+            } else {
+                down = down
+                    .max(0.15 * size)
+                    .max(sub.ascent - 0.8 * X_HEIGHT * size);
+            }
+        }
+        if let (Some(sup), Some(sub)) = (&sup, &sub) {
+            let gap = (up - sup.descent) - (sub.ascent - down);
+            let min_gap = 4.0 * RULE * size;
+            if gap < min_gap {
+                down += min_gap - gap;
+            }
+        }
+
+        // italic letters lean into their superscripts
+        let kern = match base_node {
+            Node::Symbol { variant: Variant::Italic, .. } => 0.08 * size,
+            _ => 0.0,
+        };
+        let x = base.width;
+        let mut result = MathBox::default();
+        result.place(base, 0.0, 0.0);
+        let mut extra: f32 = 0.0;
+        if let Some(sup) = sup {
+            extra = extra.max(kern + sup.width);
+            result.place(sup, x + kern, -up);
+        }
+        if let Some(sub) = sub {
+            extra = extra.max(sub.width);
+            result.place(sub, x, down);
+        }
+        result.width = x + extra + SCRIPT_SPACE * size;
+        result
+    }
+
+    /// Scripts centered above and below a display-style operator.
+    fn limits(
+        &mut self, op: MathBox, sup: Option<MathBox>, sub: Option<MathBox>, size: f32,
+    ) -> MathBox {
+        let gap = 0.15 * size;
+        let width = [Some(&op), sup.as_ref(), sub.as_ref()]
+            .into_iter()
+            .flatten()
+            .map(|b| b.width)
+            .fold(0.0f32, f32::max);
+        let (op_ascent, op_descent) = (op.ascent, op.descent);
+        let mut result = MathBox::default();
+        let op_x = (width - op.width) / 2.0;
+        result.place(op, op_x, 0.0);
+        if let Some(sup) = sup {
+            let (x, y) = ((width - sup.width) / 2.0, -(op_ascent + gap + sup.descent));
+            result.place(sup, x, y);
+        }
+        if let Some(sub) = sub {
+            let (x, y) = ((width - sub.width) / 2.0, op_descent + gap + sub.ascent);
+            result.place(sub, x, y);
+        }
+        result
+    }
+
+    fn frac(&mut self, num: &Node, den: &Node, rule: bool, style: Style) -> MathBox {
+        let size = self.size(style);
+        let num = self.node(num, style.fraction());
+        let den = self.node(den, style.fraction());
+        let display = style == Style::Display;
+
+        let thickness = if rule { RULE * size } else { 0.0 };
+        let clearance = match (rule, display) {
+            (true, true) => 3.0 * RULE,
+            (true, false) => RULE,
+            (false, true) => 7.0 * RULE,
+            (false, false) => 3.0 * RULE,
+        } * size;
+        let axis = AXIS * size;
+        let up = (num.descent + axis + thickness / 2.0 + clearance)
+            .max(if display { 0.677 } else { 0.394 } * size);
+        let down = (den.ascent - axis + thickness / 2.0 + clearance)
+            .max(if display { 0.686 } else { 0.345 } * size);
+
+        let inner = num.width.max(den.width);
+        let pad = NULL_DELIMITER * size;
+        let (num_x, den_x) = (pad + (inner - num.width) / 2.0, pad + (inner - den.width) / 2.0);
+        let mut result = MathBox::default();
+        result.place(num, num_x, -up);
+        result.place(den, den_x, down);
+        if rule {
+            result.rule(pad, -axis - thickness / 2.0, inner, thickness);
+        }
+        result.width = inner + 2.0 * pad;
+        result
+    }
+
+    fn sqrt(&mut self, index: Option<&Node>, body: &Node, style: Style) -> MathBox {
+        let size = self.size(style);
+        let body = self.node(body, style);
+        let thickness = RULE * size;
+        let phi =
+            if style == Style::Display { thickness + X_HEIGHT * size / 4.0 } else { thickness };
+        let clearance = thickness + phi / 4.0;
+
+        // the radical is scaled to reach from below the body to the top of
+        // the rule over it
+        let top = body.ascent + clearance + thickness;
+        let height = top + body.descent;
+        let glyph_size = (height / (RADICAL.0 + RADICAL.1)).max(size);
+        let radical = self.glyphs("√", glyph_size, Variant::Upright);
+        let radical_y = RADICAL.0 * glyph_size - top;
+        let radical_width = radical.width;
+
+        let mut result = MathBox::default();
+        let mut x = 0.0;
+        if let Some(index) = index {
+            let index = self.node(index, Style::ScriptScript);
+            let raise = 0.6 * height - body.descent;
+            x = (index.width - 0.5 * radical_width).max(0.0);
+            result.place(index, 0.0, -raise);
+        }
+        result.place(radical, x, radical_y);
+        x += radical_width;
+        // overlap the radical a little so the rule joins its stroke
+        let overlap = 0.03 * glyph_size;
+        result.rule(x - overlap, -top, body.width + SCRIPT_SPACE * size + overlap, thickness);
+        result.place(body, x, 0.0);
+        result
+    }
+
+    /// `inner` between delimiters grown to cover it, centered on the axis.
+    fn delimited(&mut self, left: &str, inner: MathBox, right: &str, style: Style) -> MathBox {
+        let size = self.size(style);
+        let axis = AXIS * size;
+        let half = (inner.ascent - axis).max(inner.descent + axis);
+        let (asc, desc) = char_extent('(');
+        let glyph_size = (2.0 * half * 1.1 / (asc + desc)).max(size);
+
+        let mut result = MathBox::default();
+        let left = self.delimiter(left, glyph_size, size);
+        let mut x = left.width;
+        result.place(left, 0.0, 0.0);
+        let inner_width = inner.width;
+        result.place(inner, x, 0.0);
+        x += inner_width;
+        let right = self.delimiter(right, glyph_size, size);
+        result.place(right, x, 0.0);
+        result
+    }
+
+    fn delimiter(&mut self, text: &str, glyph_size: f32, size: f32) -> MathBox {
+        if text.is_empty() {
+            MathBox { width: NULL_DELIMITER * size, ..Default::default() }
+        } else {
+            self.centered(text, glyph_size, size)
+        }
+    }
+
+    fn accent(&mut self, body: &Node, accent: Accent, style: Style) -> MathBox {
+        let size = self.size(style);
+        let italic = matches!(body, Node::Symbol { variant: Variant::Italic, .. });
+        let body = self.node(body, style);
+        let thickness = RULE * size;
+        let (width, ascent, descent) = (body.width, body.ascent, body.descent);
+
+        let mut result = MathBox::default();
+        result.place(body, 0.0, 0.0);
+        match accent {
+            Accent::Overline => {
+                result.rule(0.0, -(ascent + 3.0 * thickness) - thickness, width, thickness);
+                result.ascent += thickness;
+            }
+            Accent::Underline => {
+                result.rule(0.0, descent + 3.0 * thickness, width, thickness);
+                result.descent += thickness;
+            }
+            Accent::Glyph(c) => {
+                let glyph_size = if c == '→' { 0.7 * size } else { size };
+                let glyph = self.glyphs(&c.to_string(), glyph_size, Variant::Upright);
+                let skew = if italic { 0.08 * size } else { 0.0 };
+                let x = (width - glyph.width) / 2.0 + skew;
+                let y = accent_bottom(c) * glyph_size - ascent.max(X_HEIGHT * size) - 0.06 * size;
+                result.place(glyph, x, y);
+                result.width = width;
+            }
+        }
+        result
+    }
+
+    fn array(&mut self, rows: &[Vec<Vec<Node>>], align: Align, style: Style) -> MathBox {
+        let size = self.size(style);
+        let cell_style = match (style.is_script(), align) {
+            (true, _) => style,
+            (false, Align::Aligned) => Style::Display,
+            (false, _) => Style::Text,
+        };
+        let mut cells: Vec<Vec<MathBox>> = vec![];
+        for row in rows {
+            let mut boxes = vec![];
+            for (c, cell) in row.iter().enumerate() {
+                // `x &= 1`: the relation after `&` is spaced as if something
+                // preceded it
+                let prev = (align == Align::Aligned && c % 2 == 1).then_some(Class::Ord);
+                boxes.push(self.list(cell, cell_style, prev));
+            }
+            cells.push(boxes);
+        }
+
+        let columns = cells.iter().map(Vec::len).max().unwrap_or(0);
+        let mut widths = vec![0.0f32; columns];
+        for row in &cells {
+            for (c, cell) in row.iter().enumerate() {
+                widths[c] = widths[c].max(cell.width);
+            }
+        }
+        let column_gap = |c: usize| match align {
+            Align::Aligned if c % 2 == 1 => 0.0,
+            _ => size,
+        };
+
+        let heights: Vec<(f32, f32)> = cells
+            .iter()
+            .map(|row| {
+                row.iter().fold((0.7 * size, 0.3 * size), |(a, d), cell| {
+                    (a.max(cell.ascent), d.max(cell.descent))
+                })
+            })
+            .collect();
+        let row_gap = 0.2 * size;
+        let total = heights.iter().map(|(a, d)| a + d).sum::<f32>()
+            + row_gap * heights.len().saturating_sub(1) as f32;
+
+        let mut result = MathBox::default();
+        let mut top = -total / 2.0 - AXIS * size;
+        for (row, (ascent, descent)) in cells.into_iter().zip(heights) {
+            let baseline = top + ascent;
+            let mut x = 0.0;
+            for (c, cell) in row.into_iter().enumerate() {
+                if c > 0 {
+                    x += column_gap(c);
+                }
+                let slack = widths[c] - cell.width;
+                let offset = match align {
+                    Align::Center => slack / 2.0,
+                    Align::Left => 0.0,
+                    Align::Aligned if c % 2 == 0 => slack,
+                    Align::Aligned => 0.0,
+                };
+                result.place(cell, x + offset, baseline);
+                x += widths[c];
+            }
+            top = baseline + descent + row_gap;
+        }
+        result.width = widths.iter().sum::<f32>() + (1..columns).map(column_gap).sum::<f32>();
+        result.ascent = result.ascent.max(total / 2.0 + AXIS * size);
+        result.descent = result.descent.max(total / 2.0 - AXIS * size);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every glyph half an em wide.
+    fn mono(text: &str, size: f32, _: Variant) -> f32 {
+        text.chars().count() as f32 * 0.5 * size
+    }
+
+    fn layout(source: &str, display: bool) -> MathBox {
+        typeset(source, display, 10.0, &mut mono)
+    }
+
+    fn glyph(math: &MathBox, text: &str) -> (f32, f32, f32) {
+        math.items
+            .iter()
+            .find_map(|item| match item {
+                MathItem::Glyphs { text: t, x, y, size, .. } if t == text => Some((*x, *y, *size)),
+                _ => None,
+            })
+            .unwrap_or_else(|| panic!("no glyph {text:?} in {math:?}"))
+    }
+
+    #[test]
+    fn parse_scripts() {
+        let x = || Box::new(symbol("x", Class::Ord, Variant::Italic));
+        assert_eq!(
+            parse("x^23"),
+            vec![
+                Node::Scripts {
+                    base: x(),
+                    sup: Some(Box::new(symbol("2", Class::Ord, Variant::Upright))),
+                    sub: None,
+                },
+                symbol("3", Class::Ord, Variant::Upright),
+            ]
+        );
+        assert_eq!(
+            parse("x_{i}"),
+            vec![Node::Scripts {
+                base: x(),
+                sup: None,
+                sub: Some(Box::new(Node::Group(vec![symbol("i", Class::Ord, Variant::Italic)]))),
+            }]
+        );
+    }
+
+    #[test]
+    fn parse_unknown_command_verbatim() {
+        assert_eq!(parse(r"\foo"), vec![symbol(r"\foo", Class::Ord, Variant::Upright)]);
+    }
+
+    #[test]
+    fn parse_survives_unbalanced_and_deep_input() {
+        parse("}}{{\\right)\\end{x}&\\\\");
+        parse(&"{".repeat(10_000));
+        parse(&"\\frac".repeat(10_000));
+    }
+
+    #[test]
+    fn parse_environment() {
+        let Node::Array { rows, left, right, .. } =
+            &parse(r"\begin{pmatrix} a & b \\ c & d \\ \end{pmatrix}")[0]
+        else {
+            panic!("not an array");
+        };
+        assert_eq!((left.as_str(), right.as_str()), ("(", ")"));
+        assert_eq!(rows.len(), 2);
+        assert!(rows.iter().all(|row| row.len() == 2));
+    }
+
+    #[test]
+    fn binary_spacing() {
+        // `a - b` gets medium spaces around the minus; a leading minus is unary
+        let binary = layout("a-b", false).width;
+        let unary = layout("-b", false).width;
+        assert_eq!(unary, 10.0);
+        assert!((binary - (15.0 + 2.0 * 4.0 / 18.0 * 10.0)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn frac_stacks_around_the_bar() {
+        let math = layout(r"\frac{a}{b}", false);
+        let rule = math
+            .items
+            .iter()
+            .find_map(|item| match item {
+                MathItem::Rule { y, height, .. } => Some((*y, *height)),
+                _ => None,
+            })
+            .expect("fraction bar");
+        let (_, num_y, num_size) = glyph(&math, "a");
+        let (_, den_y, _) = glyph(&math, "b");
+        assert!(num_y < rule.0 && rule.0 + rule.1 < den_y);
+        assert!(num_size < 10.0, "inline fractions shrink their parts");
+        assert!(math.ascent > 0.0 && math.descent > 0.0);
+    }
+
+    #[test]
+    fn display_limits() {
+        let display = layout(r"\sum_{i}^{n}", true);
+        let (op_x, _, _) = glyph(&display, "∑");
+        let (n_x, n_y, _) = glyph(&display, "n");
+        let (i_x, i_y, _) = glyph(&display, "i");
+        assert!(n_y < 0.0 && i_y > 0.0);
+        assert!(n_x > op_x && i_x > op_x, "limits are centered over the operator");
+
+        let inline = layout(r"\sum_{i}^{n}", false);
+        let (op_x, _, op_size) = glyph(&inline, "∑");
+        let (n_x, _, _) = glyph(&inline, "n");
+        assert!(n_x >= op_x + mono("∑", op_size, Variant::Upright), "scripts sit to the right");
+    }
+
+    #[test]
+    fn delimiters_grow_around_tall_content() {
+        let flat = layout(r"\left(x\right)", true);
+        let tall = layout(r"\left(\frac{a}{b}\right)", true);
+        assert_eq!(glyph(&flat, "(").2, 10.0);
+        assert!(glyph(&tall, "(").2 > 10.0);
+    }
+}
@@ -5,6 +5,7 @@ use unicode_segmentation::UnicodeSegmentation as _;
 
 use crate::TextBufferArea;
 use crate::tab::markdown_editor::MdRender;
+use crate::tab::markdown_editor::widget::block::leaf::diagram::DiagramLang;
 use crate::widgets::glyphon_cache::{GlyphonCache, GlyphonCacheKey, GlyphonFontFamily};
 
 pub trait BufferExt {
@@ -157,7 +158,9 @@ pub enum InlineItem {
 /// An embed's painted box and source span. `advance` is the box width; the
 /// box sits `ascent` above and `descent` below the row's text baseline.
 /// `source_range` covers the full source syntax (`![alt](url)` for an image,
-/// the bare URL for a link card).
+/// the bare URL for a link card, `$...$` for math, the code lines for a
+/// diagram). `url` is the math or diagram source for [`EmbedKind::Math`] and
+/// [`EmbedKind::Diagram`].
 #[derive(Clone, Debug)]
 pub struct EmbedSpec {
     pub advance: f32,
@@ -168,13 +171,16 @@ pub struct EmbedSpec {
     pub kind: EmbedKind,
 }
 
-/// What an [`EmbedSpec`] box paints: a decoded image texture, or a
-/// metadata-driven link-preview card. Both occupy an atomic inline slot and
-/// reuse the same hit-test / reveal / selection machinery.
+/// What an [`EmbedSpec`] box paints: a decoded image texture, a
+/// metadata-driven link-preview card, a typeset math expression, or a drawn
+/// diagram. All occupy an atomic inline slot and reuse the same hit-test /
+/// reveal / selection machinery.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum EmbedKind {
     Image,
     LinkCard,
+    Math { display: bool },
+    Diagram { lang: DiagramLang },
 }
 
 /// Style record for one inline-box instance. Snapshotted into each
@@ -1848,6 +1854,12 @@ impl MdRender {
                             egui::CornerRadius::same(2),
                         ),
                         EmbedKind::LinkCard => self.paint_link_card(embed_ui, url, screen_rect),
+                        EmbedKind::Math { display } => {
+                            self.paint_math(embed_ui, url, *display, screen_rect)
+                        }
+                        EmbedKind::Diagram { lang } => {
+                            self.paint_diagram(embed_ui, url, *lang, screen_rect)
+                        }
                     }
 
                     // The embed's opaque fill hides the selection slot behind it,
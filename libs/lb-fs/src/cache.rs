@@ -1,4 +1,8 @@
-use crate::{fs_impl::Drive, utils::file_id};
+use crate::fs_impl::Drive;
+use crate::utils::{file_id, owner};
+use lb_rs::Uuid;
+use lb_rs::model::access_info::UserAccessMode;
+use lb_rs::model::errors::{LbErrKind, LbResult};
 use lb_rs::model::file::File;
 use nfs3_server::nfs3_types::nfs3::{fattr3, ftype3, nfstime3};
 use std::time::{Duration, SystemTime};
@@ -7,14 +11,23 @@ use tracing::info;
 pub struct FileEntry {
     pub file: File,
     pub fattr: fattr3,
+
+    /// shared with us only to read, or pending; write bits are cleared and writes are refused
+    pub read_only: bool,
 }
 
 impl FileEntry {
-    pub fn from_file(file: File, size: u64) -> Self {
+    pub fn from_file(file: File, size: u64, read_only: bool) -> Self {
         let ftype = if file.is_folder() { ftype3::NF3DIR } else { ftype3::NF3REG };
 
         // todo this deserves some scrutiny and cross platform testing
-        let mode = if file.is_folder() { 0o755 } else { 0o644 };
+        let mode = match (file.is_folder(), read_only) {
+            (true, false) => 0o755,
+            (true, true) => 0o555,
+            (false, false) => 0o644,
+            (false, true) => 0o444,
+        };
+        let (uid, gid) = owner();
 
         let fileid = file_id(&file);
         // interestingly a number of key read operations rely on this being correct
@@ -28,8 +41,8 @@ impl FileEntry {
             type_: ftype,
             mode,
             nlink: 1, // hard links to this file
-            uid: 501, // todo: evaluate owner field? not resolved by this lib
-            gid: 20,  // group id
+            uid,
+            gid,
             size,
             used: size,               // ?
             rdev: Default::default(), // ?
@@ -40,7 +53,7 @@ impl FileEntry {
             ctime,
         };
 
-        Self { file, fattr }
+        Self { file, fattr, read_only }
     }
 
     pub fn ts_from_u64(version: u64) -> nfstime3 {
@@ -61,19 +74,39 @@ impl Drive {
         info!("preparing cache, are you release build?");
         let files = self.lb.list_metadatas().await.unwrap();
 
+        // sizes are read before the cache is locked; reading them can take a while
+        let mut entries = Vec::with_capacity(files.len());
+        for file in files {
+            let size =
+                if file.is_document() { self.size(file.id).await.unwrap_or_default() } else { 0 };
+            let read_only = self.read_only(&file).await;
+            entries.push(FileEntry::from_file(file, size, read_only));
+        }
+        for file in self.pending_files().await.unwrap_or_default() {
+            let size =
+                if file.is_document() { self.size(file.id).await.unwrap_or_default() } else { 0 };
+            entries.push(FileEntry::from_file(file, size, true));
+        }
+
         let mut data = self.data.lock().await;
         data.clear();
-        for file in files {
-            let id = file.id;
-            let size = self
-                .lb
-                .read_document(id, false)
-                .await
-                .unwrap_or_default()
-                .len() as u64;
-            let entry = FileEntry::from_file(file, size);
+        for entry in entries {
             data.insert(entry.file.id.into(), entry);
         }
         info!("cache ready");
     }
+
+    /// Whether a file was shared with us only to read, directly or by being in a folder that was.
+    pub async fn read_only(&self, file: &File) -> bool {
+        matches!(self.lb.get_access_mode(file.id).await, Ok(Some(UserAccessMode::Read)))
+    }
+
+    /// Refuses to change a file that's read-only, rather than leaving it to lb-rs to refuse once
+    /// the change is underway.
+    pub async fn check_writable(&self, id: Uuid) -> LbResult<()> {
+        match self.data.lock().await.get(&id.into()) {
+            Some(entry) if entry.read_only => Err(LbErrKind::InsufficientPermission.into()),
+            _ => Ok(()),
+        }
+    }
 }
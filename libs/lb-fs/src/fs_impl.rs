@@ -1,8 +1,12 @@
 use crate::cache::FileEntry;
 use crate::file_handle::UuidFileHandle;
+use crate::pending_shares::PENDING_SHARES;
 use crate::utils::{file_id, get_string};
+use crate::write_back::OpenDocuments;
+use lb_rs::model::errors::{LbErr, LbErrKind};
 use lb_rs::model::file::File;
 use lb_rs::model::file_metadata::FileType;
+use lb_rs::model::lazy::ValidationFailure;
 use lb_rs::{Lb, Uuid};
 use nfs3_server::nfs3_types::nfs3::{
     Nfs3Option, fattr3, filename3, nfspath3, nfsstat3, sattr3, set_atime, set_mtime,
@@ -34,6 +38,9 @@ pub struct Drive {
     /// 2. nfs needs to update timestamps to specified values
     /// 3. nfs models properties we don't, like file permission bits
     pub data: EntriesMap,
+
+    /// documents being written, see [crate::write_back]
+    pub open: OpenDocuments,
 }
 
 impl Drive {
@@ -50,8 +57,8 @@ impl Drive {
     /// might be to use an index as the cookie instead of the file ID.
     async fn load_children(
         &self, dirid: &UuidFileHandle, cookie: u64,
-    ) -> impl StdIterator<Item = File> + 'static {
-        let mut children = self.lb.get_children(dirid.as_uuid()).await.unwrap();
+    ) -> Result<impl StdIterator<Item = File> + 'static, nfsstat3> {
+        let mut children = self.children(*dirid.as_uuid()).await.map_err(nfs_err)?;
 
         children.sort_by(|a, b| a.id.cmp(&b.id));
 
@@ -66,7 +73,7 @@ impl Drive {
                 });
         }
 
-        children.into_iter().skip(start_index)
+        Ok(children.into_iter().skip(start_index))
     }
 }
 
@@ -109,7 +116,7 @@ impl NfsReadFileSystem for Drive {
         }
 
         // todo this should almost certainly just operate on the cache
-        let children = self.lb.get_children(&dir.id).await.unwrap();
+        let children = self.children(dir.id).await.map_err(nfs_err)?;
         let file_name = get_string(filename);
 
         for child in children {
@@ -141,30 +148,19 @@ impl NfsReadFileSystem for Drive {
     async fn read(
         &self, id: &Self::Handle, offset: u64, count: u32,
     ) -> Result<(Vec<u8>, bool), nfsstat3> {
-        let offset = offset as usize;
-        let count = count as usize;
-
-        let doc = self.lb.read_document(*id.as_uuid(), false).await.unwrap();
-
-        if offset >= doc.len() {
-            info!("[] EOF");
-            return Ok((vec![], true));
-        }
-
-        if offset + count >= doc.len() {
-            info!("|{}| EOF", doc[offset..].len());
-            return Ok((doc[offset..].to_vec(), true));
-        }
-
-        info!("|{}|", count);
-        return Ok((doc[offset..offset + count].to_vec(), false));
+        let (data, eof) = self
+            .read_at(*id.as_uuid(), offset, count)
+            .await
+            .map_err(nfs_err)?;
+        info!("|{}| eof={eof}", data.len());
+        Ok((data, eof))
     }
 
     #[instrument(skip(self), fields(dirid = dirid.to_string(), start_after = cookie))]
     async fn readdir(
         &self, dirid: &Self::Handle, cookie: u64,
     ) -> Result<impl nfs3_server::vfs::ReadDirIterator, nfsstat3> {
-        let iter = self.load_children(dirid, cookie).await;
+        let iter = self.load_children(dirid, cookie).await?;
         Ok(Iterator { inner: iter })
     }
 
@@ -172,7 +168,7 @@ impl NfsReadFileSystem for Drive {
     async fn readdirplus(
         &self, dirid: &Self::Handle, cookie: u64,
     ) -> Result<impl ReadDirPlusIterator<UuidFileHandle>, nfsstat3> {
-        let iter = self.load_children(dirid, cookie).await;
+        let iter = self.load_children(dirid, cookie).await?;
         let data = self.data.lock().await;
 
         let iter = iter
@@ -202,16 +198,29 @@ impl NfsReadFileSystem for Drive {
 impl NfsFileSystem for Drive {
     #[instrument(skip(self), fields(id = id.to_string()))]
     async fn setattr(&self, id: &Self::Handle, setattr: sattr3) -> Result<fattr3, nfsstat3> {
+        let resize = match setattr.size {
+            Nfs3Option::Some(new) => {
+                let data = self.data.lock().await;
+                let entry = data.get(id).ok_or(nfsstat3::NFS3ERR_STALE)?;
+                Some(new).filter(|new| entry.fattr.size != *new)
+            }
+            Nfs3Option::None => None,
+        };
+        if resize.is_some() {
+            self.check_writable(*id.as_uuid()).await.map_err(nfs_err)?;
+        }
+        // the cache isn't locked while the buffer is, see write_back
+        if let Some(new) = resize {
+            self.truncate(*id.as_uuid(), new).await.map_err(nfs_err)?;
+        }
+
         let mut data = self.data.lock().await;
         let now = FileEntry::now();
-        let entry = data.get_mut(id).unwrap();
-
-        if let Nfs3Option::Some(new) = setattr.size
-            && entry.fattr.size != new
-        {
-            let mut doc = self.lb.read_document(*id.as_uuid(), false).await.unwrap();
-            doc.resize(new as usize, 0);
-            self.lb.write_document(*id.as_uuid(), &doc).await.unwrap();
+        let entry = data.get_mut(id).ok_or(nfsstat3::NFS3ERR_STALE)?;
+
+        if let Some(new) = resize {
+            entry.fattr.size = new;
+            entry.fattr.used = new;
             entry.fattr.mtime = now;
             entry.fattr.ctime = now;
         }
@@ -249,7 +258,8 @@ impl NfsFileSystem for Drive {
         }
 
         if let Nfs3Option::Some(mode) = setattr.mode {
-            entry.fattr.mode = mode;
+            // read-only files stay that way
+            entry.fattr.mode = if entry.read_only { mode & !0o222 } else { mode };
             entry.fattr.ctime = now;
         }
 
@@ -261,28 +271,21 @@ impl NfsFileSystem for Drive {
     async fn write(
         &self, id: &Self::Handle, offset: u64, buffer: &[u8],
     ) -> Result<fattr3, nfsstat3> {
-        let offset = offset as usize;
+        self.check_writable(*id.as_uuid()).await.map_err(nfs_err)?;
+        let doc_size = self
+            .write_at(*id.as_uuid(), offset, buffer)
+            .await
+            .map_err(nfs_err)?;
 
         let mut data = self.data.lock().await;
-        let entry = data.get_mut(id).unwrap();
-
-        let mut doc = self.lb.read_document(*id.as_uuid(), false).await.unwrap();
-        let mut expanded = false;
-        if offset + buffer.len() > doc.len() {
-            doc.resize(offset + buffer.len(), 0);
-            doc[offset..].copy_from_slice(buffer);
-            expanded = true;
-        } else {
-            for (idx, datum) in buffer.iter().enumerate() {
-                doc[offset + idx] = *datum;
-            }
-        }
-        let doc_size = doc.len();
-        self.lb.write_document(*id.as_uuid(), &doc).await.unwrap();
-
-        entry.fattr.size = doc_size as u64;
+        let entry = data.get_mut(id).ok_or(nfsstat3::NFS3ERR_STALE)?;
+        let now = FileEntry::now();
+        entry.fattr.size = doc_size;
+        entry.fattr.used = doc_size;
+        entry.fattr.mtime = now;
+        entry.fattr.ctime = now;
 
-        info!("expanded={expanded}, fattr.size = {}", doc_size);
+        info!("fattr.size = {}", doc_size);
 
         Ok(entry.fattr.clone())
     }
@@ -292,18 +295,21 @@ impl NfsFileSystem for Drive {
     async fn create(
         &self, dirid: &Self::Handle, filename: &filename3<'_>, attr: sattr3,
     ) -> Result<(Self::Handle, fattr3), nfsstat3> {
+        self.check_writable(*dirid.as_uuid())
+            .await
+            .map_err(nfs_err)?;
         let filename = get_string(filename);
         let file = self
             .lb
             .create_file(&filename, dirid.as_uuid(), FileType::Document)
             .await
-            .unwrap();
+            .map_err(nfs_err)?;
 
         let id = file.id.into();
-        let entry = FileEntry::from_file(file, 0);
+        let entry = FileEntry::from_file(file, 0, false);
         self.data.lock().await.insert(id, entry);
 
-        let file = self.setattr(&id, attr).await.unwrap();
+        let file = self.setattr(&id, attr).await?;
 
         info!("({id}, size={})", file.size);
         Ok((id, file))
@@ -314,8 +320,11 @@ impl NfsFileSystem for Drive {
         &self, dirid: &Self::Handle, filename: &filename3<'_>,
         createverf: nfs3_server::nfs3_types::nfs3::createverf3,
     ) -> Result<Self::Handle, nfsstat3> {
+        self.check_writable(*dirid.as_uuid())
+            .await
+            .map_err(nfs_err)?;
         let filename = get_string(filename);
-        let children = self.lb.get_children(dirid.as_uuid()).await.unwrap();
+        let children = self.children(*dirid.as_uuid()).await.map_err(nfs_err)?;
         for child in children {
             if child.name == filename {
                 warn!("exists already");
@@ -327,10 +336,10 @@ impl NfsFileSystem for Drive {
             .lb
             .create_file(&filename, dirid.as_uuid(), FileType::Document)
             .await
-            .unwrap();
+            .map_err(nfs_err)?;
 
         let id = file.id.into();
-        let entry = FileEntry::from_file(file, 0);
+        let entry = FileEntry::from_file(file, 0, false);
         info!("({id}, size={})", entry.fattr.size);
         self.data.lock().await.insert(id, entry);
 
@@ -341,15 +350,18 @@ impl NfsFileSystem for Drive {
     async fn mkdir(
         &self, dirid: &Self::Handle, dirname: &filename3<'_>,
     ) -> Result<(Self::Handle, fattr3), nfsstat3> {
+        self.check_writable(*dirid.as_uuid())
+            .await
+            .map_err(nfs_err)?;
         let filename = get_string(dirname);
         let file = self
             .lb
             .create_file(&filename, dirid.as_uuid(), FileType::Folder)
             .await
-            .unwrap();
+            .map_err(nfs_err)?;
 
         let id = file.id.into();
-        let entry = FileEntry::from_file(file, 0);
+        let entry = FileEntry::from_file(file, 0, false);
         let fattr = entry.fattr.clone();
         self.data.lock().await.insert(id, entry);
 
@@ -362,16 +374,22 @@ impl NfsFileSystem for Drive {
     /// this should return Err(nfsstat3::NFS3ERR_ROFS)
     #[instrument(skip(self), fields(dirid = dirid.to_string(), filename = get_string(filename)))]
     async fn remove(&self, dirid: &Self::Handle, filename: &filename3<'_>) -> Result<(), nfsstat3> {
-        let mut data = self.data.lock().await;
-
-        let children = self.lb.get_children(dirid.as_uuid()).await.unwrap();
+        self.check_writable(*dirid.as_uuid())
+            .await
+            .map_err(nfs_err)?;
+        let children = self.children(*dirid.as_uuid()).await.map_err(nfs_err)?;
         let file_name = get_string(filename);
 
         for child in children {
             if file_name == child.name {
+                if child.id == PENDING_SHARES {
+                    info!("ACCES");
+                    return Err(nfsstat3::NFS3ERR_ACCES);
+                }
                 info!("deleted");
                 let _ = self.lb.delete(&child.id).await; // ignore errors
-                data.remove(&child.id.into());
+                self.discard(child.id).await;
+                self.data.lock().await.remove(&child.id.into());
                 return Ok(());
             }
         }
@@ -386,12 +404,13 @@ impl NfsFileSystem for Drive {
         &self, from_dirid: &Self::Handle, from_filename: &filename3<'a>, to_dirid: &Self::Handle,
         to_filename: &filename3<'a>,
     ) -> Result<(), nfsstat3> {
-        let mut data = self.data.lock().await;
-
         let from_filename = get_string(from_filename);
         let to_filename = get_string(to_filename);
 
-        let src_children = self.lb.get_children(from_dirid.as_uuid()).await.unwrap();
+        let src_children = self
+            .children(*from_dirid.as_uuid())
+            .await
+            .map_err(nfs_err)?;
 
         let mut from_id = None;
         let mut to_id = None;
@@ -406,7 +425,7 @@ impl NfsFileSystem for Drive {
         }
 
         if to_dirid != from_dirid {
-            let dst_children = self.lb.get_children(to_dirid.as_uuid()).await.unwrap();
+            let dst_children = self.children(*to_dirid.as_uuid()).await.map_err(nfs_err)?;
             for child in dst_children {
                 if child.name == to_filename {
                     to_id = Some(child.id);
@@ -414,19 +433,47 @@ impl NfsFileSystem for Drive {
             }
         }
 
-        let from_id = from_id.unwrap();
+        let from_id = from_id.ok_or(nfsstat3::NFS3ERR_NOENT)?;
+
+        self.check_writable(*to_dirid.as_uuid())
+            .await
+            .map_err(nfs_err)?;
+
+        // moving a pending share out of the pending shares folder accepts it
+        if *from_dirid.as_uuid() == PENDING_SHARES {
+            if to_id.is_some() {
+                return Err(nfsstat3::NFS3ERR_EXIST);
+            }
+            info!("accept {from_id} -> {to_dirid}");
+            self.accept_share(from_id, *to_dirid.as_uuid(), &to_filename)
+                .await
+                .map_err(nfs_err)?;
+            self.fill_cache().await;
+            return Ok(());
+        }
+        self.check_writable(*from_dirid.as_uuid())
+            .await
+            .map_err(nfs_err)?;
 
         match to_id {
             // we are overwriting a file
             Some(id) => {
                 info!("overwrite {from_id} -> {id}");
-                let from_doc = self.lb.read_document(from_id, false).await.unwrap();
+                self.check_writable(id).await.map_err(nfs_err)?;
+                self.flush(from_id).await.map_err(nfs_err)?;
+                let from_doc = self
+                    .lb
+                    .read_document(from_id, false)
+                    .await
+                    .map_err(nfs_err)?;
                 info!("|{}|", from_doc.len());
                 let doc_len = from_doc.len() as u64;
-                self.lb.write_document(id, &from_doc).await.unwrap();
-                self.lb.delete(&from_id).await.unwrap();
+                self.replace(id, from_doc).await.map_err(nfs_err)?;
+                self.lb.delete(&from_id).await.map_err(nfs_err)?;
+                self.discard(from_id).await;
 
-                let entry = data.get_mut(&id.into()).unwrap();
+                let mut data = self.data.lock().await;
+                let entry = data.get_mut(&id.into()).ok_or(nfsstat3::NFS3ERR_STALE)?;
                 entry.fattr.size = doc_len;
 
                 data.remove(&from_id.into());
@@ -439,17 +486,22 @@ impl NfsFileSystem for Drive {
                     self.lb
                         .move_file(&from_id, to_dirid.as_uuid())
                         .await
-                        .unwrap();
+                        .map_err(nfs_err)?;
                 }
 
                 if from_filename != to_filename {
                     info!("rename {} -> {}\t", from_id, to_filename);
-                    self.lb.rename_file(&from_id, &to_filename).await.unwrap();
+                    self.lb
+                        .rename_file(&from_id, &to_filename)
+                        .await
+                        .map_err(nfs_err)?;
                 }
 
-                let entry = data.get_mut(&from_id.into()).unwrap();
-
-                let file = self.lb.get_file_by_id(from_id).await.unwrap();
+                let file = self.lb.get_file_by_id(from_id).await.map_err(nfs_err)?;
+                let mut data = self.data.lock().await;
+                let entry = data
+                    .get_mut(&from_id.into())
+                    .ok_or(nfsstat3::NFS3ERR_STALE)?;
                 entry.file = file;
 
                 info!("ok");
@@ -468,6 +520,32 @@ impl NfsFileSystem for Drive {
     }
 }
 
+/// Errors nothing in particular is expected of become I/O errors.
+fn nfs_err(err: LbErr) -> nfsstat3 {
+    match err.kind {
+        LbErrKind::FileNonexistent | LbErrKind::FileParentNonexistent => nfsstat3::NFS3ERR_NOENT,
+        LbErrKind::FileNotFolder => nfsstat3::NFS3ERR_NOTDIR,
+        LbErrKind::FileNotDocument => nfsstat3::NFS3ERR_ISDIR,
+        LbErrKind::FileNameTooLong
+        | LbErrKind::Validation(ValidationFailure::FileNameTooLong(_)) => {
+            nfsstat3::NFS3ERR_NAMETOOLONG
+        }
+        LbErrKind::FileNameContainsSlash
+        | LbErrKind::FileNameEmpty
+        | LbErrKind::Validation(ValidationFailure::Cycle(_)) => nfsstat3::NFS3ERR_INVAL,
+        LbErrKind::InsufficientPermission | LbErrKind::RootModificationInvalid => {
+            nfsstat3::NFS3ERR_ACCES
+        }
+        LbErrKind::Validation(ValidationFailure::PathConflict(_)) => nfsstat3::NFS3ERR_EXIST,
+        LbErrKind::ReReadRequired => nfsstat3::NFS3ERR_JUKEBOX,
+        LbErrKind::UsageIsOverDataCap => nfsstat3::NFS3ERR_FBIG,
+        _ => {
+            warn!("{err:?}");
+            nfsstat3::NFS3ERR_IO
+        }
+    }
+}
+
 pub struct Iterator<I>
 where
     I: StdIterator<Item = File> + Send + Sync + 'static,
@@ -0,0 +1,621 @@
+//! A FUSE frontend for the same [Drive] the NFS server serves. FUSE mounts don't need root and
+//! don't have NFS's attribute caching, which some editors trip over.
+//!
+//! FUSE calls are synchronous and made from fuser's own thread, so they block on the tokio
+//! runtime that started the mount. FUSE names files by inode: inodes are the same numbers as NFS
+//! file ids, except the root, which FUSE expects at [FUSE_ROOT_ID].
+
+use crate::cache::FileEntry;
+use crate::fs_impl::Drive;
+use crate::pending_shares::PENDING_SHARES;
+use crate::utils::file_id;
+use fuser::{
+    BackgroundSession, FUSE_ROOT_ID, FileAttr, FileType, Filesystem, MountOption, ReplyAttr,
+    ReplyCreate, ReplyData, ReplyDirectory, ReplyEmpty, ReplyEntry, ReplyOpen, ReplyWrite, Request,
+    TimeOrNow,
+};
+use lb_rs::Uuid;
+use lb_rs::model::errors::{LbErr, LbErrKind};
+use lb_rs::model::file::File;
+use lb_rs::model::file_metadata::FileType as LbFileType;
+use lb_rs::model::lazy::ValidationFailure;
+use nfs3_server::nfs3_types::nfs3::{ftype3, nfstime3};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::runtime::Handle;
+use tracing::{info, instrument, warn};
+
+/// how long the kernel may cache names and attributes; short, so that synced changes show up
+const TTL: Duration = Duration::from_secs(1);
+
+pub fn mount(drive: Drive, mountpoint: &Path) -> io::Result<BackgroundSession> {
+    let fs = FuseDrive { drive, runtime: Handle::current(), inodes: HashMap::new() };
+    let options = [
+        MountOption::FSName("lockbook".to_string()),
+        MountOption::DefaultPermissions,
+        MountOption::NoAtime,
+    ];
+    fuser::spawn_mount2(fs, mountpoint, &options)
+}
+
+struct FuseDrive {
+    drive: Drive,
+    runtime: Handle,
+
+    /// every inode handed to the kernel, other than the root's
+    inodes: HashMap<u64, Uuid>,
+}
+
+impl FuseDrive {
+    fn id(&self, ino: u64) -> Result<Uuid, i32> {
+        if ino == FUSE_ROOT_ID {
+            return Ok(self.drive.root);
+        }
+        self.inodes.get(&ino).copied().ok_or(libc::ENOENT)
+    }
+
+    fn ino(&mut self, file: &File) -> u64 {
+        if file.id == self.drive.root {
+            return FUSE_ROOT_ID;
+        }
+        let ino = file_id(file);
+        self.inodes.insert(ino, file.id);
+        ino
+    }
+
+    /// The attributes of a file, from the cache, which files that just synced down aren't in yet.
+    fn attr(&mut self, file: File) -> FileAttr {
+        let ino = self.ino(&file);
+        let cached = self
+            .runtime
+            .block_on(self.drive.data.lock())
+            .get(&file.id.into())
+            .map(|entry| file_attr(ino, entry));
+        if let Some(attr) = cached {
+            return attr;
+        }
+
+        let size = if file.is_document() {
+            self.runtime
+                .block_on(self.drive.size(file.id))
+                .unwrap_or_default()
+        } else {
+            0
+        };
+        let read_only = self.runtime.block_on(async {
+            self.drive.read_only(&file).await
+                || self.drive.is_pending(file.id).await.unwrap_or_default()
+        });
+        let entry = FileEntry::from_file(file, size, read_only);
+        let attr = file_attr(ino, &entry);
+        self.runtime
+            .block_on(self.drive.data.lock())
+            .insert(entry.file.id.into(), entry);
+        attr
+    }
+
+    fn cached_attr(&mut self, ino: u64) -> Result<FileAttr, i32> {
+        let id = self.id(ino)?;
+        let data = self.runtime.block_on(self.drive.data.lock());
+        let entry = data.get(&id.into()).ok_or(libc::ENOENT)?;
+        Ok(file_attr(ino, entry))
+    }
+
+    fn writable(&self, id: Uuid) -> Result<(), i32> {
+        self.runtime
+            .block_on(self.drive.check_writable(id))
+            .map_err(errno)
+    }
+
+    fn child(&self, parent: u64, name: &OsStr) -> Result<File, i32> {
+        let parent = self.id(parent)?;
+        let name = name.to_str().ok_or(libc::EINVAL)?;
+        let children = self
+            .runtime
+            .block_on(self.drive.children(parent))
+            .map_err(errno)?;
+        children
+            .into_iter()
+            .find(|child| child.name == name)
+            .ok_or(libc::ENOENT)
+    }
+
+    fn create_file(
+        &mut self, parent: u64, name: &OsStr, file_type: LbFileType,
+    ) -> Result<FileAttr, i32> {
+        let parent = self.id(parent)?;
+        let name = name.to_str().ok_or(libc::EINVAL)?;
+        self.writable(parent)?;
+        let file = self
+            .runtime
+            .block_on(self.drive.lb.create_file(name, &parent, file_type))
+            .map_err(errno)?;
+        Ok(self.attr(file))
+    }
+
+    fn remove(&mut self, parent: u64, name: &OsStr, folder: bool) -> Result<(), i32> {
+        self.writable(self.id(parent)?)?;
+        let file = self.child(parent, name)?;
+        if file.id == PENDING_SHARES {
+            return Err(libc::EACCES);
+        }
+        if folder {
+            if !file.is_folder() {
+                return Err(libc::ENOTDIR);
+            }
+            let children = self
+                .runtime
+                .block_on(self.drive.children(file.id))
+                .map_err(errno)?;
+            if !children.is_empty() {
+                return Err(libc::ENOTEMPTY);
+            }
+        } else if file.is_folder() {
+            return Err(libc::EISDIR);
+        }
+
+        self.runtime
+            .block_on(self.drive.lb.delete(&file.id))
+            .map_err(errno)?;
+        self.runtime.block_on(self.drive.discard(file.id));
+        self.runtime
+            .block_on(self.drive.data.lock())
+            .remove(&file.id.into());
+        self.inodes.remove(&file_id(&file));
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn set_attr(
+        &mut self, ino: u64, mode: Option<u32>, uid: Option<u32>, gid: Option<u32>,
+        size: Option<u64>, atime: Option<TimeOrNow>, mtime: Option<TimeOrNow>,
+    ) -> Result<FileAttr, i32> {
+        let id = self.id(ino)?;
+        // the cache isn't locked while the buffer is, see write_back
+        if let Some(size) = size {
+            self.writable(id)?;
+            self.runtime
+                .block_on(self.drive.truncate(id, size))
+                .map_err(errno)?;
+        }
+
+        let mut data = self.runtime.block_on(self.drive.data.lock());
+        let entry = data.get_mut(&id.into()).ok_or(libc::ENOENT)?;
+        let now = FileEntry::now();
+
+        if let Some(size) = size.filter(|size| entry.fattr.size != *size) {
+            entry.fattr.size = size;
+            entry.fattr.used = size;
+            entry.fattr.mtime = now;
+            entry.fattr.ctime = now;
+        }
+        if let Some(atime) = atime {
+            entry.fattr.atime = nfs_time(atime);
+        }
+        if let Some(mtime) = mtime {
+            entry.fattr.mtime = nfs_time(mtime);
+            entry.fattr.ctime = now;
+        }
+        if let Some(mode) = mode {
+            // read-only files stay that way
+            let mode = if entry.read_only { mode & !0o222 } else { mode };
+            entry.fattr.mode = mode & 0o7777;
+            entry.fattr.ctime = now;
+        }
+        if let Some(uid) = uid {
+            entry.fattr.uid = uid;
+            entry.fattr.ctime = now;
+        }
+        if let Some(gid) = gid {
+            entry.fattr.gid = gid;
+            entry.fattr.ctime = now;
+        }
+        Ok(file_attr(ino, entry))
+    }
+
+    fn write_at(&mut self, ino: u64, offset: u64, buffer: &[u8]) -> Result<(), i32> {
+        let id = self.id(ino)?;
+        self.writable(id)?;
+        let size = self
+            .runtime
+            .block_on(self.drive.write_at(id, offset, buffer))
+            .map_err(errno)?;
+
+        if let Some(entry) = self
+            .runtime
+            .block_on(self.drive.data.lock())
+            .get_mut(&id.into())
+        {
+            let now = FileEntry::now();
+            entry.fattr.size = size;
+            entry.fattr.used = size;
+            entry.fattr.mtime = now;
+            entry.fattr.ctime = now;
+        }
+        Ok(())
+    }
+
+    /// Moves and/or renames a file. Replacing a document, which is how most editors save, writes
+    /// the new content into the replaced document, so that it keeps its id, shares and links.
+    fn rename_file(
+        &mut self, parent: u64, name: &OsStr, new_parent: u64, new_name: &OsStr,
+    ) -> Result<(), i32> {
+        let from = self.child(parent, name)?;
+        let to_parent = self.id(new_parent)?;
+        self.writable(to_parent)?;
+
+        // moving a pending share out of the pending shares folder accepts it
+        if self.id(parent)? == PENDING_SHARES {
+            if self.child(new_parent, new_name).is_ok() {
+                return Err(libc::EEXIST);
+            }
+            let new_name = new_name.to_str().ok_or(libc::EINVAL)?;
+            info!("accept {} -> {to_parent}", from.id);
+            self.runtime
+                .block_on(async {
+                    self.drive
+                        .accept_share(from.id, to_parent, new_name)
+                        .await?;
+                    self.drive.fill_cache().await;
+                    Ok::<_, LbErr>(())
+                })
+                .map_err(errno)?;
+            return Ok(());
+        }
+        self.writable(self.id(parent)?)?;
+
+        let lb = &self.drive.lb;
+        match self.child(new_parent, new_name) {
+            Ok(to) if to.id == from.id => {}
+            Ok(to) => {
+                if to.is_folder() || from.is_folder() {
+                    // folders are only replaced by moving into them, which lockbook doesn't do
+                    return Err(if to.is_folder() { libc::EISDIR } else { libc::ENOTDIR });
+                }
+                info!("overwrite {} -> {}", from.id, to.id);
+                self.writable(to.id)?;
+                let doc = self
+                    .runtime
+                    .block_on(async {
+                        self.drive.flush(from.id).await?;
+                        let doc = lb.read_document(from.id, false).await?;
+                        self.drive.replace(to.id, doc.clone()).await?;
+                        lb.delete(&from.id).await?;
+                        self.drive.discard(from.id).await;
+                        Ok::<_, LbErr>(doc)
+                    })
+                    .map_err(errno)?;
+
+                let mut data = self.runtime.block_on(self.drive.data.lock());
+                data.remove(&from.id.into());
+                if let Some(entry) = data.get_mut(&to.id.into()) {
+                    let now = FileEntry::now();
+                    entry.fattr.size = doc.len() as u64;
+                    entry.fattr.used = doc.len() as u64;
+                    entry.fattr.mtime = now;
+                    entry.fattr.ctime = now;
+                }
+                self.inodes.remove(&file_id(&from));
+            }
+            Err(libc::ENOENT) => {
+                let new_parent = self.id(new_parent)?;
+                let new_name = new_name.to_str().ok_or(libc::EINVAL)?;
+                if from.parent != new_parent {
+                    info!("move {} -> {new_parent}", from.id);
+                    self.runtime
+                        .block_on(lb.move_file(&from.id, &new_parent))
+                        .map_err(errno)?;
+                }
+                if from.name != new_name {
+                    info!("rename {} -> {new_name}", from.id);
+                    self.runtime
+                        .block_on(lb.rename_file(&from.id, new_name))
+                        .map_err(errno)?;
+                }
+
+                let file = self
+                    .runtime
+                    .block_on(lb.get_file_by_id(from.id))
+                    .map_err(errno)?;
+                if let Some(entry) = self
+                    .runtime
+                    .block_on(self.drive.data.lock())
+                    .get_mut(&from.id.into())
+                {
+                    entry.file = file;
+                    entry.fattr.ctime = FileEntry::now();
+                }
+            }
+            Err(err) => return Err(err),
+        }
+        Ok(())
+    }
+}
+
+impl Filesystem for FuseDrive {
+    #[instrument(skip(self, _req, reply))]
+    fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        match self.child(parent, name) {
+            Ok(file) => reply.entry(&TTL, &self.attr(file), 0),
+            Err(err) => reply.error(err),
+        }
+    }
+
+    #[instrument(skip(self, _req, reply))]
+    fn getattr(&mut self, _req: &Request<'_>, ino: u64, _fh: Option<u64>, reply: ReplyAttr) {
+        match self.cached_attr(ino) {
+            Ok(attr) => reply.attr(&TTL, &attr),
+            Err(err) => reply.error(err),
+        }
+    }
+
+    #[instrument(skip(self, _req, reply))]
+    fn setattr(
+        &mut self, _req: &Request<'_>, ino: u64, mode: Option<u32>, uid: Option<u32>,
+        gid: Option<u32>, size: Option<u64>, atime: Option<TimeOrNow>, mtime: Option<TimeOrNow>,
+        _ctime: Option<SystemTime>, _fh: Option<u64>, _crtime: Option<SystemTime>,
+        _chgtime: Option<SystemTime>, _bkuptime: Option<SystemTime>, _flags: Option<u32>,
+        reply: ReplyAttr,
+    ) {
+        match self.set_attr(ino, mode, uid, gid, size, atime, mtime) {
+            Ok(attr) => reply.attr(&TTL, &attr),
+            Err(err) => reply.error(err),
+        }
+    }
+
+    #[instrument(skip(self, _req, reply))]
+    fn readdir(
+        &mut self, _req: &Request<'_>, ino: u64, _fh: u64, offset: i64, mut reply: ReplyDirectory,
+    ) {
+        let id = match self.id(ino) {
+            Ok(id) => id,
+            Err(err) => return reply.error(err),
+        };
+        let (dir, mut children) = match self.runtime.block_on(async {
+            // the pending shares folder is only in the cache
+            let cached = self
+                .drive
+                .data
+                .lock()
+                .await
+                .get(&id.into())
+                .map(|entry| entry.file.clone());
+            let dir = match cached {
+                Some(dir) => dir,
+                None => self.drive.lb.get_file_by_id(id).await?,
+            };
+            let children = self.drive.children(id).await?;
+            Ok::<_, LbErr>((dir, children))
+        }) {
+            Ok(found) => found,
+            Err(err) => return reply.error(errno(err)),
+        };
+        if dir.is_document() {
+            return reply.error(libc::ENOTDIR);
+        }
+        children.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let parent =
+            if dir.parent == self.drive.root { FUSE_ROOT_ID } else { dir.parent.as_u64_pair().0 };
+        let mut entries = vec![(ino, FileType::Directory, ".".to_string())];
+        entries.push((parent, FileType::Directory, "..".to_string()));
+        for child in children {
+            let kind = if child.is_folder() { FileType::Directory } else { FileType::RegularFile };
+            entries.push((self.ino(&child), kind, child.name));
+        }
+
+        for (i, (ino, kind, name)) in entries.into_iter().enumerate().skip(offset as usize) {
+            // the offset of an entry is where the next call starts
+            if reply.add(ino, i as i64 + 1, kind, name) {
+                break;
+            }
+        }
+        reply.ok();
+    }
+
+    #[instrument(skip(self, _req, reply))]
+    fn read(
+        &mut self, _req: &Request<'_>, ino: u64, _fh: u64, offset: i64, size: u32, _flags: i32,
+        _lock_owner: Option<u64>, reply: ReplyData,
+    ) {
+        match self.id(ino).and_then(|id| {
+            self.runtime
+                .block_on(self.drive.read_at(id, offset as u64, size))
+                .map_err(errno)
+        }) {
+            Ok((data, _)) => reply.data(&data),
+            Err(err) => reply.error(err),
+        }
+    }
+
+    #[instrument(skip(self, _req, data, reply), fields(data = data.len()))]
+    fn write(
+        &mut self, _req: &Request<'_>, ino: u64, _fh: u64, offset: i64, data: &[u8],
+        _write_flags: u32, _flags: i32, _lock_owner: Option<u64>, reply: ReplyWrite,
+    ) {
+        match self.write_at(ino, offset as u64, data) {
+            Ok(()) => reply.written(data.len() as u32),
+            Err(err) => reply.error(err),
+        }
+    }
+
+    #[instrument(skip(self, _req, reply))]
+    fn create(
+        &mut self, _req: &Request<'_>, parent: u64, name: &OsStr, mode: u32, umask: u32,
+        _flags: i32, reply: ReplyCreate,
+    ) {
+        // creating opens, so the handle is released like any other
+        let result = self
+            .create_file(parent, name, LbFileType::Document)
+            .and_then(|attr| {
+                self.set_attr(attr.ino, Some(mode & !umask), None, None, None, None, None)
+            })
+            .and_then(|attr| {
+                let id = self.id(attr.ino)?;
+                self.runtime
+                    .block_on(self.drive.open_handle(id))
+                    .map_err(errno)?;
+                Ok(attr)
+            });
+        match result {
+            Ok(attr) => reply.created(&TTL, &attr, 0, 0, 0),
+            Err(err) => reply.error(err),
+        }
+    }
+
+    #[instrument(skip(self, _req, reply))]
+    fn mkdir(
+        &mut self, _req: &Request<'_>, parent: u64, name: &OsStr, _mode: u32, _umask: u32,
+        reply: ReplyEntry,
+    ) {
+        match self.create_file(parent, name, LbFileType::Folder) {
+            Ok(attr) => reply.entry(&TTL, &attr, 0),
+            Err(err) => reply.error(err),
+        }
+    }
+
+    #[instrument(skip(self, _req, reply))]
+    fn unlink(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+        match self.remove(parent, name, false) {
+            Ok(()) => reply.ok(),
+            Err(err) => reply.error(err),
+        }
+    }
+
+    #[instrument(skip(self, _req, reply))]
+    fn rmdir(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+        match self.remove(parent, name, true) {
+            Ok(()) => reply.ok(),
+            Err(err) => reply.error(err),
+        }
+    }
+
+    #[instrument(skip(self, _req, reply))]
+    fn rename(
+        &mut self, _req: &Request<'_>, parent: u64, name: &OsStr, newparent: u64, newname: &OsStr,
+        _flags: u32, reply: ReplyEmpty,
+    ) {
+        match self.rename_file(parent, name, newparent, newname) {
+            Ok(()) => reply.ok(),
+            Err(err) => reply.error(err),
+        }
+    }
+
+    #[instrument(skip(self, _req, reply))]
+    fn open(&mut self, _req: &Request<'_>, ino: u64, _flags: i32, reply: ReplyOpen) {
+        let result = self.id(ino).and_then(|id| {
+            let file = self
+                .runtime
+                .block_on(self.drive.lb.get_file_by_id(id))
+                .map_err(errno)?;
+            if file.is_document() {
+                self.runtime
+                    .block_on(self.drive.open_handle(id))
+                    .map_err(errno)?;
+            }
+            Ok(())
+        });
+        match result {
+            Ok(()) => reply.opened(0, 0),
+            Err(err) => reply.error(err),
+        }
+    }
+
+    #[instrument(skip(self, _req, reply))]
+    fn flush(
+        &mut self, _req: &Request<'_>, ino: u64, _fh: u64, _lock_owner: u64, reply: ReplyEmpty,
+    ) {
+        match self
+            .id(ino)
+            .and_then(|id| self.runtime.block_on(self.drive.flush(id)).map_err(errno))
+        {
+            Ok(()) => reply.ok(),
+            Err(err) => reply.error(err),
+        }
+    }
+
+    #[instrument(skip(self, _req, reply))]
+    fn release(
+        &mut self, _req: &Request<'_>, ino: u64, _fh: u64, _flags: i32, _lock_owner: Option<u64>,
+        _flush: bool, reply: ReplyEmpty,
+    ) {
+        match self.id(ino).and_then(|id| {
+            self.runtime
+                .block_on(self.drive.release_handle(id))
+                .map_err(errno)
+        }) {
+            Ok(()) => reply.ok(),
+            Err(err) => reply.error(err),
+        }
+    }
+
+    #[instrument(skip(self, _req, reply))]
+    fn fsync(
+        &mut self, _req: &Request<'_>, ino: u64, _fh: u64, _datasync: bool, reply: ReplyEmpty,
+    ) {
+        match self
+            .id(ino)
+            .and_then(|id| self.runtime.block_on(self.drive.flush(id)).map_err(errno))
+        {
+            Ok(()) => reply.ok(),
+            Err(err) => reply.error(err),
+        }
+    }
+}
+
+fn file_attr(ino: u64, entry: &FileEntry) -> FileAttr {
+    let fattr = &entry.fattr;
+    let kind =
+        if fattr.type_ == ftype3::NF3DIR { FileType::Directory } else { FileType::RegularFile };
+    FileAttr {
+        ino,
+        size: fattr.size,
+        blocks: fattr.size.div_ceil(512),
+        atime: system_time(&fattr.atime),
+        mtime: system_time(&fattr.mtime),
+        ctime: system_time(&fattr.ctime),
+        crtime: system_time(&fattr.ctime),
+        kind,
+        perm: (fattr.mode & 0o7777) as u16,
+        nlink: if kind == FileType::Directory { 2 } else { 1 },
+        uid: fattr.uid,
+        gid: fattr.gid,
+        rdev: 0,
+        blksize: 4096,
+        flags: 0,
+    }
+}
+
+fn system_time(time: &nfstime3) -> SystemTime {
+    UNIX_EPOCH + Duration::new(time.seconds as u64, time.nseconds)
+}
+
+fn nfs_time(time: TimeOrNow) -> nfstime3 {
+    match time {
+        TimeOrNow::SpecificTime(time) => time.try_into().unwrap_or_else(|_| FileEntry::now()),
+        TimeOrNow::Now => FileEntry::now(),
+    }
+}
+
+fn errno(err: LbErr) -> i32 {
+    match err.kind {
+        LbErrKind::FileNonexistent | LbErrKind::FileParentNonexistent => libc::ENOENT,
+        LbErrKind::FileNotFolder => libc::ENOTDIR,
+        LbErrKind::FileNotDocument => libc::EISDIR,
+        LbErrKind::FileNameTooLong => libc::ENAMETOOLONG,
+        LbErrKind::FileNameContainsSlash | LbErrKind::FileNameEmpty => libc::EINVAL,
+        LbErrKind::InsufficientPermission | LbErrKind::RootModificationInvalid => libc::EACCES,
+        LbErrKind::Validation(ValidationFailure::PathConflict(_)) => libc::EEXIST,
+        LbErrKind::Validation(ValidationFailure::Cycle(_)) => libc::EINVAL,
+        LbErrKind::Validation(ValidationFailure::FileNameTooLong(_)) => libc::ENAMETOOLONG,
+        LbErrKind::ReReadRequired => libc::EAGAIN,
+        LbErrKind::UsageIsOverDataCap => libc::EFBIG,
+        _ => {
+            warn!("{err:?}");
+            libc::EIO
+        }
+    }
+}
@@ -1,26 +1,54 @@
 use crate::cache::FileEntry;
 use crate::fs_impl::Drive;
 use crate::mount::{mount, umount};
+use crate::write_back::FLUSH_AFTER;
 use cli_rs::cli_error::{CliError, CliResult};
 use lb_rs::model::core_config::{ClientType, Config};
 use lb_rs::model::errors::Unexpected;
 use lb_rs::service::events::{Actor, Event};
 use lb_rs::{Lb, Uuid};
 use nfs3_server::tcp::{NFSTcp, NFSTcpListener};
-use std::io;
 use std::io::IsTerminal;
+use std::path::PathBuf;
 use std::process::exit;
+use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
+use std::{fs, io};
 use tokio::time;
 use tracing::{debug, error, info};
 
 pub mod cache;
 pub(crate) mod file_handle;
 pub mod fs_impl;
+#[cfg(unix)]
+pub mod fuse;
 pub mod logger;
 pub mod mount;
+pub mod pending_shares;
 pub mod utils;
+pub mod write_back;
+
+/// How the drive is presented to the operating system: as an NFS server mounted at
+/// `/tmp/lockbook`, which needs privileges on linux, or as a FUSE filesystem mounted anywhere.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Backend {
+    #[default]
+    Nfs,
+    Fuse,
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "nfs" => Ok(Self::Nfs),
+            "fuse" => Ok(Self::Fuse),
+            _ => Err(format!("unknown backend '{s}', expected nfs or fuse")),
+        }
+    }
+}
 
 impl Drive {
     pub async fn init() -> Self {
@@ -40,8 +68,9 @@ impl Drive {
         let root = lb.root().await.map(|file| file.id).unwrap_or(Uuid::nil());
 
         let data = Arc::default();
+        let open = Arc::default();
 
-        let fs = Self { lb, root, data };
+        let fs = Self { lb, root, data, open };
         fs.clone().monitor_lb().await;
 
         fs
@@ -79,8 +108,10 @@ impl Drive {
         info!("registering sig handler");
 
         // capture ctrl_c and try to cleanup
+        let flusher = drive.clone();
         tokio::spawn(async move {
             tokio::signal::ctrl_c().await.unwrap();
+            flusher.flush_idle(Duration::ZERO).await;
             let mut unmount_success = umount().await;
             while !unmount_success {
                 error!("unmount failed, please close any apps using lb-fs! Retrying in 1s.");
@@ -91,19 +122,85 @@ impl Drive {
             exit(0);
         });
 
+        drive.spawn_background_work();
+
+        // todo have a better port selection strategy
+        info!("creating server");
+        let listener = NFSTcpListener::bind("127.0.0.1:11111", drive)
+            .await
+            .unwrap();
+
+        info!("mounting");
+        mount();
+
+        info!("ready");
+        listener.handle_forever().await.unwrap();
+        Ok(())
+    }
+
+    pub async fn mount_with(backend: Backend, mountpoint: Option<PathBuf>) -> CliResult<()> {
+        match backend {
+            Backend::Nfs if mountpoint.is_some() => {
+                Err(CliError::from("the nfs backend always mounts to /tmp/lockbook"))
+            }
+            Backend::Nfs => Self::mount().await,
+            #[cfg(unix)]
+            Backend::Fuse => {
+                Self::mount_fuse(mountpoint.unwrap_or_else(|| PathBuf::from("/tmp/lockbook"))).await
+            }
+            #[cfg(not(unix))]
+            Backend::Fuse => Err(CliError::from("fuse is only available on linux and macOS")),
+        }
+    }
+
+    #[cfg(unix)]
+    pub async fn mount_fuse(mountpoint: PathBuf) -> CliResult<()> {
+        let drive = Self::init().await;
+        drive.lb.sync().await.unwrap();
+        drive.fill_cache().await;
+        drive.spawn_background_work();
+
+        fs::create_dir_all(&mountpoint)?;
+        info!("mounting");
+        let session = fuse::mount(drive.clone(), &mountpoint).map_err(|err| {
+            CliError::from(format!("failed to mount {}: {err}", mountpoint.display()))
+        })?;
+        info!("ready");
+
+        tokio::signal::ctrl_c().await.unwrap();
+        drive.flush_idle(Duration::ZERO).await;
+
+        // dropping the session unmounts, and waits for fuser's thread, which blocks on this runtime
+        let _ = tokio::task::spawn_blocking(move || drop(session)).await;
+        info!("cleaned up, goodbye!");
+        Ok(())
+    }
+
+    /// Syncs every 30 seconds, flushes documents that are done being written, and keeps the
+    /// cache up to date with what syncs bring down.
+    fn spawn_background_work(&self) {
         // sync periodically in the background
-        let syncer = drive.clone();
+        let syncer = self.clone();
         tokio::spawn(async move {
             loop {
                 info!("will sync in 30 seconds");
                 tokio::time::sleep(Duration::from_secs(30)).await;
                 info!("syncing");
+                syncer.flush_idle(Duration::ZERO).await;
                 syncer.lb.sync().await.map_unexpected().log_and_ignore();
             }
         });
 
+        let flusher = self.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                flusher.flush_idle(FLUSH_AFTER).await;
+            }
+        });
+
         // monitor changes to lb
-        let event_handler = drive.clone();
+        let event_handler = self.clone();
         tokio::spawn(async move {
             let mut events = event_handler.lb.subscribe();
             loop {
@@ -123,19 +220,30 @@ impl Drive {
                 match event {
                     Event::MetadataChanged(Actor::Sync) => event_handler.fill_cache().await,
                     Event::DocumentWritten(dirty_id, Actor::Sync) => {
-                        let file = event_handler.lb.get_file_by_id(dirty_id).await.unwrap();
+                        // a file that can't be read keeps its old entry until the next fill
+                        let file = match event_handler.lb.get_file_by_id(dirty_id).await {
+                            Ok(file) => file,
+                            Err(err) => {
+                                error!("could not refresh {dirty_id}: {err:?}");
+                                continue;
+                            }
+                        };
+                        event_handler.reload(dirty_id).await.log_and_ignore();
                         let size = if file.is_document() {
-                            event_handler
-                                .lb
-                                .read_document(dirty_id, false)
-                                .await
-                                .unwrap()
-                                .len()
+                            match event_handler.read_size(dirty_id).await {
+                                Ok(size) => size,
+                                Err(err) => {
+                                    error!("could not size {dirty_id}: {err:?}");
+                                    continue;
+                                }
+                            }
                         } else {
                             0
                         };
 
-                        let mut entry = FileEntry::from_file(file, size as u64);
+                        let read_only = event_handler.read_only(&file).await
+                            || event_handler.is_pending(file.id).await.unwrap_or_default();
+                        let mut entry = FileEntry::from_file(file, size, read_only);
 
                         let now = FileEntry::now();
 
@@ -152,19 +260,6 @@ impl Drive {
                 }
             }
         });
-
-        // todo have a better port selection strategy
-        info!("creating server");
-        let listener = NFSTcpListener::bind("127.0.0.1:11111", drive)
-            .await
-            .unwrap();
-
-        info!("mounting");
-        mount();
-
-        info!("ready");
-        listener.handle_forever().await.unwrap();
-        Ok(())
     }
 
     async fn monitor_lb(self) {
@@ -1,7 +1,10 @@
 use cli_rs::cli_error::{CliResult, Exit};
 use cli_rs::command::Command;
+use cli_rs::flag::Flag;
 use cli_rs::parser::Cmd;
+use lb_fs::Backend;
 use lb_fs::fs_impl::Drive;
+use std::path::PathBuf;
 
 fn main() {
     Command::name("lb-fs")
@@ -13,8 +16,15 @@ fn main() {
         )
         .subcommand(
             Command::name("mount")
-                .description("start an NFS server and mount it to /tmp/lockbook")
-                .handler(mount),
+                .description(
+                    "start an NFS server and mount it to /tmp/lockbook, or mount with FUSE",
+                )
+                .input(Flag::<Backend>::new("backend").description("nfs (the default) or fuse"))
+                .input(
+                    Flag::<PathBuf>::new("mountpoint")
+                        .description("where to mount with fuse, /tmp/lockbook by default"),
+                )
+                .handler(|backend, mountpoint| mount(backend.get(), mountpoint.get())),
         )
         .parse()
         .exit()
@@ -27,7 +37,8 @@ async fn import() -> CliResult<()> {
 }
 
 #[tokio::main]
-async fn mount() -> CliResult<()> {
-    Drive::mount().await?;
+async fn mount(backend: Backend, mountpoint: PathBuf) -> CliResult<()> {
+    let mountpoint = Some(mountpoint).filter(|path| !path.as_os_str().is_empty());
+    Drive::mount_with(backend, mountpoint).await?;
     Ok(())
 }
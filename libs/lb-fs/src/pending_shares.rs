@@ -0,0 +1,95 @@
+//! Files shared with us that we haven't accepted or rejected show up in a read-only
+//! `Pending Shares` folder in the root of the mount. The folder isn't in lockbook: it has an id of
+//! its own, and the pending shares in it have their parents pointed at it. Moving a pending share
+//! out of it accepts the share, by creating a link to it where it was moved to.
+
+use crate::fs_impl::Drive;
+use lb_rs::Uuid;
+use lb_rs::model::errors::{LbErrKind, LbResult};
+use lb_rs::model::file::File;
+use lb_rs::model::file_metadata::FileType;
+
+pub const PENDING_SHARES: Uuid = Uuid::from_u128(0x7065_6e64_696e_6720_7368_6172_6573_0000);
+pub const PENDING_SHARES_NAME: &str = "Pending Shares";
+
+impl Drive {
+    /// The children of a folder, which for the root includes the pending shares folder.
+    pub async fn children(&self, id: Uuid) -> LbResult<Vec<File>> {
+        if id == PENDING_SHARES {
+            return self.lb.get_pending_shares().await.map(|files| {
+                files
+                    .into_iter()
+                    .map(|file| File { parent: PENDING_SHARES, ..file })
+                    .collect()
+            });
+        }
+        if self.is_pending(id).await? {
+            let files = self.lb.get_pending_share_files().await?;
+            return Ok(files.into_iter().filter(|file| file.parent == id).collect());
+        }
+
+        let mut children = self.lb.get_children(&id).await?;
+        if id == self.root
+            && !children
+                .iter()
+                .any(|child| child.name == PENDING_SHARES_NAME)
+        {
+            children.push(self.pending_shares_folder()?);
+        }
+        Ok(children)
+    }
+
+    /// Every pending share and everything in them, with the pending shares folder first.
+    pub async fn pending_files(&self) -> LbResult<Vec<File>> {
+        let roots: Vec<Uuid> = self
+            .lb
+            .get_pending_shares()
+            .await?
+            .into_iter()
+            .map(|file| file.id)
+            .collect();
+
+        let mut files = vec![self.pending_shares_folder()?];
+        for file in self.lb.get_pending_share_files().await? {
+            let parent = if roots.contains(&file.id) { PENDING_SHARES } else { file.parent };
+            files.push(File { parent, ..file });
+        }
+        Ok(files)
+    }
+
+    /// Whether a file is the pending shares folder or is in it.
+    pub async fn is_pending(&self, id: Uuid) -> LbResult<bool> {
+        if id == PENDING_SHARES {
+            return Ok(true);
+        }
+        let pending = self.lb.get_pending_share_files().await?;
+        Ok(pending.iter().any(|file| file.id == id))
+    }
+
+    /// Accepts a pending share by linking to it from `parent`, named `name`, and returns the
+    /// file as it now appears there.
+    pub async fn accept_share(&self, id: Uuid, parent: Uuid, name: &str) -> LbResult<File> {
+        if parent == PENDING_SHARES || self.is_pending(parent).await? {
+            return Err(LbErrKind::InsufficientPermission.into());
+        }
+        self.lb
+            .create_file(name, &parent, FileType::Link { target: id })
+            .await?;
+        Ok(File { parent, name: name.to_string(), ..self.lb.get_file_by_id(id).await? })
+    }
+
+    fn pending_shares_folder(&self) -> LbResult<File> {
+        let account = self.lb.get_account()?;
+        Ok(File {
+            id: PENDING_SHARES,
+            parent: self.root,
+            name: PENDING_SHARES_NAME.to_string(),
+            file_type: FileType::Folder,
+            last_modified: 0,
+            last_modified_by: account.username.clone(),
+            owner: account.username,
+            shares: vec![],
+            size_bytes: 0,
+        })
+    }
+}
@@ -9,3 +9,14 @@ pub fn get_string(f: &filename3) -> String {
 pub fn file_id(f: &File) -> u64 {
     f.id.as_u64_pair().0
 }
+
+/// The user and group lb-fs runs as, who own every file in the mount.
+#[cfg(unix)]
+pub fn owner() -> (u32, u32) {
+    unsafe { (libc::getuid(), libc::getgid()) }
+}
+
+#[cfg(not(unix))]
+pub fn owner() -> (u32, u32) {
+    (0, 0)
+}
@@ -0,0 +1,314 @@
+//! Writes are buffered per open document and written to lockbook whole when the document is
+//! flushed, rather than re-encrypting the whole document on every write: a GUI editor saving a
+//! few MB sends hundreds of writes.
+//!
+//! FUSE opens and closes files, and documents are flushed when they're closed. NFS doesn't, so
+//! documents written over NFS are flushed once they've been idle for [FLUSH_AFTER]. Everything is
+//! flushed before syncing.
+//!
+//! A buffer remembers the hmac of the version of the document it was read from, and flushes with
+//! a `safe_write` at that hmac, so that what a sync brought down meanwhile isn't overwritten. Text
+//! is merged the way sync merges it; anything else stays as lockbook has it, and what was written
+//! is kept beside it in a `.lockbook-conflict` file. Advisory locks are left to the kernel: FUSE
+//! keeps them locally when the filesystem doesn't, and NFS is mounted `nolock`.
+
+use crate::cache::FileEntry;
+use crate::fs_impl::Drive;
+use lb_rs::Uuid;
+use lb_rs::model::errors::{LbErrKind, LbResult};
+use lb_rs::model::file_metadata::{DocumentHmac, FileType};
+use lb_rs::model::filename::DocumentType;
+use lb_rs::model::lazy::ValidationFailure;
+use lb_rs::model::text::buffer::Buffer;
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+pub const FLUSH_AFTER: Duration = Duration::from_secs(2);
+
+/// Documents aren't written or truncated past this, so that an offset far past the end of a
+/// document is refused rather than allocated.
+pub const MAX_DOCUMENT_SIZE: u64 = 1 << 30;
+
+pub type OpenDocuments = Arc<Mutex<HashMap<Uuid, OpenDocument>>>;
+
+pub struct OpenDocument {
+    /// the version the buffer was read from, and the one merged against
+    base: Vec<u8>,
+    hmac: Option<DocumentHmac>,
+
+    content: Vec<u8>,
+    dirty: bool,
+    last_write: Instant,
+
+    /// FUSE handles open on the document; NFS doesn't open documents
+    handles: usize,
+}
+
+impl Drive {
+    /// Reads `len` bytes at `offset`, including what's been written but not flushed, and whether
+    /// that's the end of the document.
+    pub async fn read_at(&self, id: Uuid, offset: u64, len: u32) -> LbResult<(Vec<u8>, bool)> {
+        let open = self.open.lock().await;
+        let read;
+        let doc = match open.get(&id) {
+            Some(doc) => &doc.content,
+            None => {
+                read = self.lb.read_document(id, false).await?;
+                &read
+            }
+        };
+        let start = (offset as usize).min(doc.len());
+        let end = (start + len as usize).min(doc.len());
+        Ok((doc[start..end].to_vec(), end == doc.len()))
+    }
+
+    /// A document's size, including what's been written but not flushed. Documents that aren't
+    /// open are sized from the cache, and only read when it doesn't have them.
+    pub async fn size(&self, id: Uuid) -> LbResult<u64> {
+        if let Some(doc) = self.open.lock().await.get(&id) {
+            return Ok(doc.content.len() as u64);
+        }
+        if let Some(entry) = self.data.lock().await.get(&id.into()) {
+            return Ok(entry.fattr.size);
+        }
+        self.read_size(id).await
+    }
+
+    /// A document's size like [Self::size], but read rather than cached, for documents a sync
+    /// has written.
+    pub async fn read_size(&self, id: Uuid) -> LbResult<u64> {
+        if let Some(doc) = self.open.lock().await.get(&id) {
+            return Ok(doc.content.len() as u64);
+        }
+        Ok(self.lb.read_document(id, false).await?.len() as u64)
+    }
+
+    /// Writes into a document's buffer, and returns the document's new size.
+    pub async fn write_at(&self, id: Uuid, offset: u64, data: &[u8]) -> LbResult<u64> {
+        check_size(offset.checked_add(data.len() as u64))?;
+        let mut open = self.open.lock().await;
+        let doc = self.buffer(&mut open, id).await?;
+        let offset = offset as usize;
+        if offset + data.len() > doc.content.len() {
+            doc.content.resize(offset + data.len(), 0);
+        }
+        doc.content[offset..offset + data.len()].copy_from_slice(data);
+        doc.dirty = true;
+        doc.last_write = Instant::now();
+        Ok(doc.content.len() as u64)
+    }
+
+    pub async fn truncate(&self, id: Uuid, size: u64) -> LbResult<()> {
+        check_size(Some(size))?;
+        let mut open = self.open.lock().await;
+        let doc = self.buffer(&mut open, id).await?;
+        doc.content.resize(size as usize, 0);
+        doc.dirty = true;
+        doc.last_write = Instant::now();
+        Ok(())
+    }
+
+    /// Replaces a document's content and flushes it, which is how editors that save by renaming
+    /// a new file over the old one save.
+    pub async fn replace(&self, id: Uuid, content: Vec<u8>) -> LbResult<()> {
+        let mut open = self.open.lock().await;
+        let doc = self.buffer(&mut open, id).await?;
+        doc.content = content;
+        doc.dirty = true;
+        self.flush_document(id, &mut open).await?;
+        self.close_if_unused(id, &mut open);
+        Ok(())
+    }
+
+    pub async fn open_handle(&self, id: Uuid) -> LbResult<()> {
+        let mut open = self.open.lock().await;
+        self.buffer(&mut open, id).await?.handles += 1;
+        Ok(())
+    }
+
+    /// Flushes a document when a handle to it is closed, and lets go of its buffer when it was
+    /// the last one.
+    pub async fn release_handle(&self, id: Uuid) -> LbResult<()> {
+        let mut open = self.open.lock().await;
+        if let Some(doc) = open.get_mut(&id) {
+            doc.handles = doc.handles.saturating_sub(1);
+        }
+        let result = self.flush_document(id, &mut open).await;
+        self.close_if_unused(id, &mut open);
+        result
+    }
+
+    pub async fn flush(&self, id: Uuid) -> LbResult<()> {
+        let mut open = self.open.lock().await;
+        self.flush_document(id, &mut open).await
+    }
+
+    /// Flushes documents that haven't been written to for `idle`, and lets go of the buffers of
+    /// those that aren't open.
+    pub async fn flush_idle(&self, idle: Duration) {
+        let mut open = self.open.lock().await;
+        let ids: Vec<Uuid> = open
+            .iter()
+            .filter(|(_, doc)| doc.last_write.elapsed() >= idle)
+            .map(|(id, _)| *id)
+            .collect();
+        for id in ids {
+            if let Err(err) = self.flush_document(id, &mut open).await {
+                warn!("failed to flush {id}: {err:?}");
+                continue;
+            }
+            self.close_if_unused(id, &mut open);
+        }
+    }
+
+    /// Forgets what's been written to a document that's been deleted.
+    pub async fn discard(&self, id: Uuid) {
+        self.open.lock().await.remove(&id);
+    }
+
+    /// Picks up what a sync wrote to a document, unless it's been written to since it was last
+    /// flushed, in which case the flush will merge them.
+    pub async fn reload(&self, id: Uuid) -> LbResult<()> {
+        let mut open = self.open.lock().await;
+        let Some(doc) = open.get_mut(&id) else { return Ok(()) };
+        if doc.dirty {
+            return Ok(());
+        }
+        let (hmac, content) = self.lb.read_document_with_hmac(id, false).await?;
+        doc.base = content.clone();
+        doc.content = content;
+        doc.hmac = hmac;
+        Ok(())
+    }
+
+    async fn buffer<'a>(
+        &self, open: &'a mut HashMap<Uuid, OpenDocument>, id: Uuid,
+    ) -> LbResult<&'a mut OpenDocument> {
+        let entry = match open.entry(id) {
+            Entry::Occupied(entry) => return Ok(entry.into_mut()),
+            Entry::Vacant(entry) => entry,
+        };
+        let (hmac, content) = self.lb.read_document_with_hmac(id, false).await?;
+        Ok(entry.insert(OpenDocument {
+            base: content.clone(),
+            hmac,
+            content,
+            dirty: false,
+            last_write: Instant::now(),
+            handles: 0,
+        }))
+    }
+
+    fn close_if_unused(&self, id: Uuid, open: &mut HashMap<Uuid, OpenDocument>) {
+        if open
+            .get(&id)
+            .is_some_and(|doc| doc.handles == 0 && !doc.dirty)
+        {
+            open.remove(&id);
+        }
+    }
+
+    async fn flush_document(
+        &self, id: Uuid, open: &mut HashMap<Uuid, OpenDocument>,
+    ) -> LbResult<()> {
+        let Some(doc) = open.get_mut(&id) else { return Ok(()) };
+        if !doc.dirty {
+            return Ok(());
+        }
+
+        info!("flushing {id}, |{}|", doc.content.len());
+        match self
+            .lb
+            .safe_write(id, doc.hmac, doc.content.clone(), None)
+            .await
+        {
+            Ok(hmac) => {
+                doc.hmac = Some(hmac);
+                doc.base = doc.content.clone();
+            }
+            // a sync changed the document since it was read
+            Err(err) if err.kind == LbErrKind::ReReadRequired => {
+                let file = self.lb.get_file_by_id(id).await?;
+                let (hmac, theirs) = self.lb.read_document_with_hmac(id, false).await?;
+                let resolved = match DocumentType::from_file_name_using_extension(&file.name) {
+                    DocumentType::Text => {
+                        info!("merging {id}");
+                        Buffer::from(String::from_utf8_lossy(&doc.base).as_ref())
+                            .merge(
+                                String::from_utf8_lossy(&doc.content).into(),
+                                String::from_utf8_lossy(&theirs).into(),
+                            )
+                            .into_bytes()
+                    }
+                    _ => {
+                        let conflict = self
+                            .save_conflict(&file.name, file.parent, &doc.content)
+                            .await?;
+                        warn!("{id} changed while it was being written, saved as {conflict}");
+                        theirs
+                    }
+                };
+                let hmac = self.lb.safe_write(id, hmac, resolved.clone(), None).await?;
+                doc.hmac = Some(hmac);
+                doc.base = resolved.clone();
+                doc.content = resolved;
+            }
+            Err(err) => return Err(err),
+        }
+        doc.dirty = false;
+
+        let size = doc.content.len() as u64;
+        if let Some(entry) = self.data.lock().await.get_mut(&id.into()) {
+            entry.fattr.size = size;
+            entry.fattr.used = size;
+        }
+        Ok(())
+    }
+
+    /// Saves `content` beside the document it was meant for, as `<name>.lockbook-conflict`, and
+    /// returns the name it was saved under.
+    async fn save_conflict(&self, name: &str, parent: Uuid, content: &[u8]) -> LbResult<String> {
+        let mut n = 1;
+        let file = loop {
+            let conflict = match n {
+                1 => format!("{name}.lockbook-conflict"),
+                n => format!("{name}.lockbook-conflict-{n}"),
+            };
+            match self
+                .lb
+                .create_file(&conflict, &parent, FileType::Document)
+                .await
+            {
+                Ok(file) => break file,
+                Err(err)
+                    if matches!(
+                        err.kind,
+                        LbErrKind::Validation(ValidationFailure::PathConflict(_))
+                    ) =>
+                {
+                    n += 1
+                }
+                Err(err) => return Err(err),
+            }
+        };
+        self.lb.write_document(file.id, content).await?;
+
+        let name = file.name.clone();
+        let read_only = self.read_only(&file).await;
+        let entry = FileEntry::from_file(file, content.len() as u64, read_only);
+        self.data.lock().await.insert(entry.file.id.into(), entry);
+        Ok(name)
+    }
+}
+
+/// Refuses sizes past [MAX_DOCUMENT_SIZE] as over the data cap, which reads as `EFBIG`.
+fn check_size(size: Option<u64>) -> LbResult<()> {
+    match size {
+        Some(size) if size <= MAX_DOCUMENT_SIZE => Ok(()),
+        _ => Err(LbErrKind::UsageIsOverDataCap.into()),
+    }
+}
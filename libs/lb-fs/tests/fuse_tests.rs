@@ -0,0 +1,193 @@
+#![cfg(target_os = "linux")]
+
+use lb_fs::fs_impl::Drive;
+use lb_fs::fuse;
+use lb_rs::model::file::ShareMode;
+use lb_rs::{Lb, Uuid};
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, UNIX_EPOCH};
+use test_utils::*;
+
+/// Mounts a drive for `core` and runs `test` against it off the runtime, since fuse serves the
+/// mount by blocking on it.
+async fn with_mount(core: &Lb, test: impl FnOnce(PathBuf) + Send + 'static) {
+    let root = core.root().await.unwrap().id;
+    let drive =
+        Drive { lb: core.clone(), root, data: Default::default(), open: Default::default() };
+    drive.fill_cache().await;
+
+    let mountpoint = std::env::temp_dir().join(format!("lb-fs-{}", Uuid::new_v4()));
+    fs::create_dir_all(&mountpoint).unwrap();
+    let session = fuse::mount(drive, &mountpoint).unwrap();
+
+    let result = tokio::task::spawn_blocking(move || test(mountpoint)).await;
+    tokio::task::spawn_blocking(move || drop(session))
+        .await
+        .unwrap();
+    result.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn files_written_through_the_mount_are_in_lockbook() {
+    let core = test_core_with_account().await;
+    with_mount(&core, |mount| {
+        fs::create_dir(mount.join("notes")).unwrap();
+        fs::write(mount.join("notes/plan.md"), "# plan").unwrap();
+        assert_eq!(fs::read_to_string(mount.join("notes/plan.md")).unwrap(), "# plan");
+
+        let names: Vec<String> = fs::read_dir(mount.join("notes"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        assert_eq!(names, ["plan.md"]);
+    })
+    .await;
+
+    let doc = core.get_by_path("/notes/plan.md").await.unwrap();
+    assert_eq!(core.read_document(doc.id, false).await.unwrap(), b"# plan");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn saving_over_a_document_keeps_its_id() {
+    let core = test_core_with_account().await;
+    let doc = core.create_at_path("/doc.md").await.unwrap();
+    core.write_document(doc.id, b"old").await.unwrap();
+
+    with_mount(&core, |mount| {
+        // how most editors save
+        fs::write(mount.join(".doc.md.swp"), "new").unwrap();
+        fs::rename(mount.join(".doc.md.swp"), mount.join("doc.md")).unwrap();
+        fs::rename(mount.join("doc.md"), mount.join("renamed.md")).unwrap();
+        assert_eq!(fs::read_to_string(mount.join("renamed.md")).unwrap(), "new");
+    })
+    .await;
+
+    let renamed = core.get_by_path("/renamed.md").await.unwrap();
+    assert_eq!(renamed.id, doc.id);
+    assert_eq!(core.read_document(doc.id, false).await.unwrap(), b"new");
+    assert_eq!(
+        core.get_children(&core.root().await.unwrap().id)
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn removing_and_touching() {
+    let core = test_core_with_account().await;
+    core.create_at_path("/folder/doc.md").await.unwrap();
+
+    with_mount(&core, |mount| {
+        assert!(fs::remove_dir(mount.join("folder")).is_err());
+        fs::remove_file(mount.join("folder/doc.md")).unwrap();
+        fs::remove_dir(mount.join("folder")).unwrap();
+
+        fs::write(mount.join("touched.md"), "").unwrap();
+        let modified = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let file = fs::File::options()
+            .write(true)
+            .open(mount.join("touched.md"))
+            .unwrap();
+        file.set_modified(modified).unwrap();
+        drop(file);
+        assert_eq!(
+            fs::metadata(mount.join("touched.md"))
+                .unwrap()
+                .modified()
+                .unwrap(),
+            modified
+        );
+    })
+    .await;
+
+    assert!(core.get_by_path("/folder").await.is_err());
+    core.get_by_path("/touched.md").await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn text_written_during_a_sync_is_merged() {
+    let core = test_core_with_account().await;
+    let doc = core.create_at_path("/notes.md").await.unwrap();
+    core.write_document(doc.id, b"a\nb\nc\n").await.unwrap();
+
+    let (runtime, synced) = (tokio::runtime::Handle::current(), core.clone());
+    with_mount(&core, move |mount| {
+        let mut file = fs::File::options()
+            .write(true)
+            .open(mount.join("notes.md"))
+            .unwrap();
+        file.write_all(b"A").unwrap();
+
+        // what a sync would bring down while the file is open
+        runtime
+            .block_on(synced.write_document(doc.id, b"a\nb\nC\n"))
+            .unwrap();
+        drop(file);
+    })
+    .await;
+
+    assert_eq!(core.read_document(doc.id, false).await.unwrap(), b"A\nb\nC\n");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn other_documents_written_during_a_sync_are_kept_beside() {
+    let core = test_core_with_account().await;
+    let doc = core.create_at_path("/photo.png").await.unwrap();
+    core.write_document(doc.id, b"original").await.unwrap();
+
+    let (runtime, synced) = (tokio::runtime::Handle::current(), core.clone());
+    with_mount(&core, move |mount| {
+        let mut file = fs::File::options()
+            .write(true)
+            .open(mount.join("photo.png"))
+            .unwrap();
+        file.write_all(b"ours").unwrap();
+        runtime
+            .block_on(synced.write_document(doc.id, b"theirs"))
+            .unwrap();
+        drop(file);
+    })
+    .await;
+
+    assert_eq!(core.read_document(doc.id, false).await.unwrap(), b"theirs");
+    let conflict = core
+        .get_by_path("/photo.png.lockbook-conflict")
+        .await
+        .unwrap();
+    assert_eq!(core.read_document(conflict.id, false).await.unwrap(), b"oursinal");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn read_only_shares_are_refused_and_pending_shares_accepted_by_moving() {
+    let sharer = test_core_with_account().await;
+    let core = test_core_with_account().await;
+    let folder = sharer.create_at_path("/shared/").await.unwrap();
+    let doc = sharer.create_at_path("/shared/doc.md").await.unwrap();
+    sharer.write_document(doc.id, b"theirs").await.unwrap();
+    sharer
+        .share_file(folder.id, &core.get_account().unwrap().username, ShareMode::Read)
+        .await
+        .unwrap();
+    sharer.sync().await.unwrap();
+    core.sync().await.unwrap();
+
+    with_mount(&core, |mount| {
+        let pending = mount.join("Pending Shares/shared");
+        assert_eq!(fs::read_to_string(pending.join("doc.md")).unwrap(), "theirs");
+        assert!(fs::write(pending.join("doc.md"), "ours").is_err());
+
+        fs::rename(&pending, mount.join("shared")).unwrap();
+        let accepted = mount.join("shared/doc.md");
+        assert!(fs::metadata(&accepted).unwrap().permissions().readonly());
+        assert!(fs::write(&accepted, "ours").is_err());
+        assert!(fs::write(mount.join("shared/new.md"), "").is_err());
+    })
+    .await;
+
+    assert!(core.get_pending_shares().await.unwrap().is_empty());
+    assert_eq!(core.read_document(doc.id, false).await.unwrap(), b"theirs");
+}
@@ -49,6 +49,7 @@ pub enum LbEC {
     InsufficientPermission,
     InvalidPurchaseToken,
     InvalidAuthDetails,
+    InviteRequired,
     KeyPhraseInvalid,
     LinkInSharedFolder,
     LinkTargetIsOwned,
@@ -118,10 +119,12 @@ impl From<&LbErrKind> for LbEC {
             LbErrKind::FileNameEmpty => Self::FileNameEmpty,
             LbErrKind::FileNonexistent => Self::FileNonexistent,
             LbErrKind::FileNotDocument => Self::FileNotDocument,
+            LbErrKind::FileNotFolder => Self::FileNotFolder,
             LbErrKind::FileParentNonexistent => Self::FileParentNonexistent,
             LbErrKind::InsufficientPermission => Self::InsufficientPermission,
             LbErrKind::InvalidPurchaseToken => Self::InvalidPurchaseToken,
             LbErrKind::InvalidAuthDetails => Self::InvalidAuthDetails,
+            LbErrKind::InviteRequired => Self::InviteRequired,
             LbErrKind::KeyPhraseInvalid => Self::KeyPhraseInvalid,
             LbErrKind::NotPremium => Self::NotPremium,
             LbErrKind::UsageIsOverDataCap => Self::UsageIsOverDataCap,
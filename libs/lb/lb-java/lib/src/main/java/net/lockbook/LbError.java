@@ -38,6 +38,7 @@ public class LbError extends Exception {
         InsufficientPermission,
         InvalidPurchaseToken,
         InvalidAuthDetails,
+        InviteRequired,
         KeyPhraseInvalid,
         LinkInSharedFolder,
         LinkTargetIsOwned,
@@ -84,10 +84,12 @@ pub(crate) fn throw_err<'local>(env: &mut JNIEnv<'local>, err: LbErr) -> JObject
         LbErrKind::FileNameEmpty => "FileNameEmpty",
         LbErrKind::FileNonexistent => "FileNonexistent",
         LbErrKind::FileNotDocument => "FileNotDocument",
+        LbErrKind::FileNotFolder => "FileNotFolder",
         LbErrKind::FileParentNonexistent => "FileParentNonexistent",
         LbErrKind::InsufficientPermission => "InsufficientPermission",
         LbErrKind::InvalidPurchaseToken => "InvalidPurchaseToken",
         LbErrKind::InvalidAuthDetails => "InvalidAuthDetails",
+        LbErrKind::InviteRequired => "InviteRequired",
         LbErrKind::KeyPhraseInvalid => "KeyPhraseInvalid",
         LbErrKind::NotPremium => "NotPremium",
         LbErrKind::UsageIsOverDataCap => "UsageIsOverDataCap",
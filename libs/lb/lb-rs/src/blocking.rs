@@ -5,8 +5,9 @@ use uuid::Uuid;
 
 use crate::model::account::{Account, Username};
 use crate::model::api::{
-    AccountFilter, AccountIdentifier, AccountInfo, AdminFileInfoResponse, AdminSetUserTierInfo,
-    AdminValidateAccount, AdminValidateServer, ServerIndex, StripeAccountTier, SubscriptionInfo,
+    AccountFilter, AccountIdentifier, AccountInfo, AdminFileInfoResponse,
+    AdminMigrateDocumentsResponse, AdminSetUserTierInfo, AdminValidateAccount, AdminValidateServer,
+    InviteInfo, ServerIndex, StripeAccountTier, SubscriptionInfo, UnixTimeMillis,
 };
 use crate::model::core_config::Config;
 use crate::model::crypto::DecryptedDocument;
@@ -18,6 +19,8 @@ use crate::service::activity::RankingWeights;
 
 use crate::service::events::Event;
 use crate::service::import_export::{ExportFileInfo, ImportStatus};
+use crate::service::links::DocumentLink;
+use crate::service::render::RenderFormat;
 use crate::service::usage::UsageMetrics;
 use crate::subscribers::status::Status;
 
@@ -77,6 +80,17 @@ impl Lb {
         self.block_on(self.lb.create_account(username, api_url, welcome_doc))
     }
 
+    pub fn create_account_with_invite(
+        &self, username: &str, api_url: &str, invite_code: Option<&str>, welcome_doc: bool,
+    ) -> LbResult<Account> {
+        self.block_on(self.lb.create_account_with_invite(
+            username,
+            api_url,
+            invite_code,
+            welcome_doc,
+        ))
+    }
+
     pub fn import_account(&self, key: &str, api_url: Option<&str>) -> LbResult<Account> {
         self.block_on(self.lb.import_account(key, api_url))
     }
@@ -161,6 +175,14 @@ impl Lb {
         self.block_on(self.lb.move_file(id, new_parent))
     }
 
+    pub fn rename_file_with_link_update(&self, id: &Uuid, new_name: &str) -> LbResult<Vec<Uuid>> {
+        self.block_on(self.lb.rename_file_with_link_update(id, new_name))
+    }
+
+    pub fn move_file_with_link_update(&self, id: &Uuid, new_parent: &Uuid) -> LbResult<Vec<Uuid>> {
+        self.block_on(self.lb.move_file_with_link_update(id, new_parent))
+    }
+
     pub fn share_file(&self, id: Uuid, username: &str, mode: ShareMode) -> LbResult<()> {
         self.block_on(self.lb.share_file(id, username, mode))
     }
@@ -273,6 +295,10 @@ impl Lb {
         self.block_on(self.lb.export_file(id, dest, edit, export_progress))
     }
 
+    pub fn export_rendered(&self, id: Uuid, format: RenderFormat) -> LbResult<Vec<u8>> {
+        self.block_on(self.lb.export_rendered(id, format))
+    }
+
     pub fn get_file_link_url(&self, id: Uuid) -> LbResult<String> {
         self.block_on(self.lb.get_file_link_url(id))
     }
@@ -285,6 +311,24 @@ impl Lb {
         crate::search::ContentSearcher::new(self)
     }
 
+    pub fn search(
+        &self, query: &str, filter: Option<crate::search::SearchFilter>, limit: usize,
+    ) -> LbResult<Vec<crate::search::SearchHit>> {
+        self.block_on(self.lb.search(query, filter, limit))
+    }
+
+    pub fn backlinks(&self, id: Uuid) -> LbResult<Vec<DocumentLink>> {
+        self.block_on(self.lb.backlinks(id))
+    }
+
+    pub fn outgoing_links(&self, id: Uuid) -> LbResult<Vec<DocumentLink>> {
+        self.block_on(self.lb.outgoing_links(id))
+    }
+
+    pub fn broken_links(&self) -> LbResult<Vec<DocumentLink>> {
+        self.block_on(self.lb.broken_links())
+    }
+
     pub fn validate(&self) -> LbResult<Vec<Warning>> {
         self.block_on(self.lb.test_repo_integrity(true))
     }
@@ -355,6 +399,24 @@ impl Lb {
         self.block_on(self.lb.rebuild_index(index))
     }
 
+    pub fn admin_migrate_documents(&self) -> LbResult<AdminMigrateDocumentsResponse> {
+        self.block_on(self.lb.migrate_documents())
+    }
+
+    pub fn admin_create_invite(
+        &self, expires_at: Option<UnixTimeMillis>, max_uses: Option<u32>,
+    ) -> LbResult<InviteInfo> {
+        self.block_on(self.lb.create_invite(expires_at, max_uses))
+    }
+
+    pub fn admin_revoke_invite(&self, code: &str) -> LbResult<()> {
+        self.block_on(self.lb.revoke_invite(code))
+    }
+
+    pub fn admin_list_invites(&self) -> LbResult<Vec<InviteInfo>> {
+        self.block_on(self.lb.list_invites())
+    }
+
     pub fn admin_set_user_tier(&self, username: &str, info: AdminSetUserTierInfo) -> LbResult<()> {
         self.block_on(self.lb.set_user_tier(username, info))
     }
@@ -14,6 +14,7 @@ use uuid::Uuid;
 use {
     crate::model::errors::Unexpected,
     std::io::ErrorKind,
+    std::time::{Duration, SystemTime},
     tokio::{
         fs::{self, File, OpenOptions},
         io::{AsyncReadExt, AsyncWriteExt},
@@ -53,6 +54,12 @@ impl AsyncDocs {
         Ok(None)
     }
 
+    pub async fn maybe_size(
+        &self, _id: Uuid, _hmac: Option<DocumentHmac>,
+    ) -> LbResult<Option<u64>> {
+        Ok(None)
+    }
+
     pub async fn delete(&self, _id: Uuid, _hmac: Option<DocumentHmac>) -> LbResult<()> {
         Ok(())
     }
@@ -64,6 +71,40 @@ impl AsyncDocs {
     pub(crate) async fn retain(&self, _file_hmacs: HashSet<(Uuid, [u8; 32])>) -> LbResult<()> {
         Ok(())
     }
+
+    pub async fn insert_chunk(
+        &self, _id: Uuid, _hmac: DocumentHmac, _chunk: &EncryptedDocument,
+    ) -> LbResult<()> {
+        Ok(())
+    }
+
+    pub async fn maybe_get_chunk(
+        &self, _id: Uuid, _hmac: DocumentHmac,
+    ) -> LbResult<Option<EncryptedDocument>> {
+        Ok(None)
+    }
+
+    pub fn chunk_exists(&self, _id: Uuid, _hmac: DocumentHmac) -> bool {
+        false
+    }
+
+    pub async fn copy_chunk(&self, _from: Uuid, _to: Uuid, _hmac: DocumentHmac) -> LbResult<()> {
+        Ok(())
+    }
+
+    pub async fn maybe_chunk_size(&self, _id: Uuid, _hmac: DocumentHmac) -> LbResult<Option<u64>> {
+        Ok(None)
+    }
+
+    pub async fn delete_chunk(&self, _id: Uuid, _hmac: DocumentHmac) -> LbResult<()> {
+        Ok(())
+    }
+
+    pub(crate) async fn retain_chunks(
+        &self, _chunks: HashSet<(Uuid, DocumentHmac)>,
+    ) -> LbResult<()> {
+        Ok(())
+    }
 }
 
 #[cfg(not(target_family = "wasm"))]
@@ -213,6 +254,99 @@ impl AsyncDocs {
     }
 }
 
+#[cfg(not(target_family = "wasm"))]
+impl AsyncDocs {
+    pub async fn insert_chunk(
+        &self, id: Uuid, hmac: DocumentHmac, chunk: &EncryptedDocument,
+    ) -> LbResult<()> {
+        let value = &bincode::serialize(chunk).map_unexpected()?;
+        let final_path = chunk_path(&self.location, id, hmac);
+        let pending = final_path.clone() + ".pending";
+        trace!("write chunk\t{} {:?} bytes", &final_path, value.len());
+        fs::create_dir_all(chunk_namespace_path(&self.location)).await?;
+        let mut f = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&pending)
+            .await?;
+        f.write_all(value).await?;
+        Ok(fs::rename(&pending, &final_path).await?)
+    }
+
+    pub async fn maybe_get_chunk(
+        &self, id: Uuid, hmac: DocumentHmac,
+    ) -> LbResult<Option<EncryptedDocument>> {
+        let path_str = chunk_path(&self.location, id, hmac);
+        trace!("read chunk\t{}", &path_str);
+        match fs::read(&path_str).await {
+            Ok(data) => Ok(Some(bincode::deserialize(&data).map_unexpected()?)),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn chunk_exists(&self, id: Uuid, hmac: DocumentHmac) -> bool {
+        Path::new(&chunk_path(&self.location, id, hmac)).exists()
+    }
+
+    /// chunks are stored per document, so a document that's copied needs its own copy of them
+    pub async fn copy_chunk(&self, from: Uuid, to: Uuid, hmac: DocumentHmac) -> LbResult<()> {
+        let from = chunk_path(&self.location, from, hmac);
+        let to = chunk_path(&self.location, to, hmac);
+        fs::copy(&from, &to).await?;
+        Ok(())
+    }
+
+    pub async fn maybe_chunk_size(&self, id: Uuid, hmac: DocumentHmac) -> LbResult<Option<u64>> {
+        let path_str = chunk_path(&self.location, id, hmac);
+        Ok(Path::new(&path_str).metadata().ok().map(|meta| meta.len()))
+    }
+
+    pub async fn delete_chunk(&self, id: Uuid, hmac: DocumentHmac) -> LbResult<()> {
+        let path_str = chunk_path(&self.location, id, hmac);
+        trace!("delete chunk\t{}", &path_str);
+        match fs::remove_file(&path_str).await {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// deletes chunks that aren't in `chunks`, other than ones written too recently to tell
+    pub(crate) async fn retain_chunks(
+        &self, chunks: HashSet<(Uuid, DocumentHmac)>,
+    ) -> LbResult<()> {
+        let dir_path = chunk_namespace_path(&self.location);
+        fs::create_dir_all(&dir_path).await?;
+        let mut entries = fs::read_dir(&dir_path).await?;
+
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            let retained = parse_chunk_name(name).is_some_and(|chunk| chunks.contains(&chunk));
+            if retained {
+                continue;
+            }
+
+            // chunks of a document still being written aren't in a manifest yet
+            let age = entry
+                .metadata()
+                .await?
+                .modified()
+                .ok()
+                .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+                .unwrap_or_default();
+            if age > UNREFERENCED_CHUNK_GRACE {
+                trace!("delete chunk\t{:?}", &path);
+                fs::remove_file(&path).await.map_unexpected()?;
+            }
+        }
+        Ok(())
+    }
+}
+
 pub fn namespace_path(writeable_path: &Path) -> String {
     format!("{}/documents", writeable_path.to_str().unwrap())
 }
@@ -222,6 +356,30 @@ pub fn key_path(writeable_path: &Path, key: Uuid, hmac: DocumentHmac) -> String
     format!("{}/{}-{}", namespace_path(writeable_path), key, hmac)
 }
 
+pub fn chunk_namespace_path(writeable_path: &Path) -> String {
+    format!("{}/chunks", writeable_path.to_str().unwrap())
+}
+
+pub fn chunk_path(writeable_path: &Path, key: Uuid, hmac: DocumentHmac) -> String {
+    let hmac = base64::encode_config(hmac, base64::URL_SAFE);
+    format!("{}/{}-{}", chunk_namespace_path(writeable_path), key, hmac)
+}
+
+/// the id and hmac of a file named by [chunk_path]; `None` for anything else, like a chunk that's
+/// still being written
+#[cfg(not(target_family = "wasm"))]
+fn parse_chunk_name(name: &str) -> Option<(Uuid, DocumentHmac)> {
+    let (id, hmac) = name.split_at_checked(36)?; // UUIDs are 36 characters long in string form
+    let id = Uuid::parse_str(id).ok()?;
+    let hmac = base64::decode_config(hmac.strip_prefix('-')?, base64::URL_SAFE).ok()?;
+    Some((id, hmac.try_into().ok()?))
+}
+
+/// how long a chunk that no manifest refers to is kept, in case it belongs to a document that's
+/// still being written
+#[cfg(not(target_family = "wasm"))]
+const UNREFERENCED_CHUNK_GRACE: Duration = Duration::from_secs(24 * 60 * 60);
+
 fn pending_path(writeable_path: &Path, key: Uuid, hmac: DocumentHmac) -> String {
     key_path(writeable_path, key, hmac) + ".pending"
 }
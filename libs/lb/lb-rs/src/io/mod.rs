@@ -8,15 +8,18 @@
 pub mod docs;
 pub mod network;
 
-use crate::LocalLb;
 use crate::model::account::Account;
-use crate::model::file_metadata::Owner;
+use crate::model::api::{ContentFormat, GroupInfo};
+use crate::model::file_metadata::{DocumentHmac, Owner};
 use crate::model::signed_meta::SignedMeta;
 use crate::service::activity::DocEvent;
 use crate::service::lb_id::LbID;
+use crate::service::sync_policy::SyncPolicy;
+use crate::{LbResult, LocalLb};
 use db_rs::hasher::UuidIdentityHasherBuilder;
 use db_rs::{Db, List, LookupTable, Single, TxHandle};
 use db_rs_derive::Schema;
+use std::collections::HashMap;
 use std::ops::{Deref, DerefMut};
 use std::sync::Arc;
 use tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
@@ -47,6 +50,58 @@ pub struct CoreV4 {
     /// most recent panic file we've already uploaded. `None` means we have never
     /// sent debug info; `Some(0)` means we've sent before but no panic file existed.
     pub last_extracted_panic: Single<i64>,
+
+    /// folders with a [SyncPolicy], which applies to their descendants too
+    pub sync_policies: LookupTable<Uuid, SyncPolicy>,
+    /// how many bytes of on-demand documents may stay cached, unlimited if unset
+    pub disk_budget: Single<u64>,
+    /// chunked documents whose chunks haven't been uploaded yet: the manifest's hmac and the
+    /// chunks it refers to
+    pub chunk_uploads: LookupTable<Uuid, (DocumentHmac, Vec<DocumentHmac>)>,
+    /// the groups this account is a member of, as of the last sync
+    pub groups: LookupTable<Uuid, GroupInfo>,
+    /// versions of documents on this device whose content isn't the document itself, with how
+    /// their content is read
+    pub content_formats: LookupTable<Uuid, HashMap<DocumentHmac, ContentFormat>>,
+}
+
+impl CoreV4 {
+    /// how the content stored for this version of a document is read
+    pub fn content_format(&self, id: Uuid, hmac: DocumentHmac) -> ContentFormat {
+        self.content_formats
+            .get()
+            .get(&id)
+            .and_then(|formats| formats.get(&hmac))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// how the content stored for each version of a document is read, for versions whose content
+    /// isn't the document itself
+    pub fn content_formats_of(&self, id: Uuid) -> HashMap<DocumentHmac, ContentFormat> {
+        self.content_formats
+            .get()
+            .get(&id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn set_content_format(
+        &mut self, id: Uuid, hmac: DocumentHmac, format: ContentFormat,
+    ) -> LbResult<()> {
+        let mut formats = self.content_formats_of(id);
+        if format == ContentFormat::Document {
+            formats.remove(&hmac);
+        } else {
+            formats.insert(hmac, format);
+        }
+        if formats.is_empty() {
+            self.content_formats.remove(&id)?;
+        } else {
+            self.content_formats.insert(id, formats)?;
+        }
+        Ok(())
+    }
 }
 
 pub struct LbRO<'a> {
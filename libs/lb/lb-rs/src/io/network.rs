@@ -1,6 +1,5 @@
 use web_time::{Duration, Instant};
 
-#[cfg(not(target_family = "wasm"))]
 use bytes::Bytes;
 #[cfg(not(target_family = "wasm"))]
 use futures::stream;
@@ -13,12 +12,24 @@ use crate::model::clock::{Timestamp, get_time};
 use crate::model::core_config::ClientType;
 use crate::model::errors::LbErr;
 use crate::model::pubkey;
-use crate::model::wire::{CLIENT_HEADER, OS_HEADER, WIRE_FORMAT_HEADER, WireFormat};
+use crate::model::wire::{
+    CLIENT_HEADER, OS_HEADER, SESSION_HEADER, WIRE_FORMAT_HEADER, WireFormat,
+};
+use uuid::Uuid;
 
 const STREAM_CHUNK_BYTES: usize = 4 * 1024 * 1024;
 
 const STREAM_BODY_THRESHOLD: usize = 1024 * 1024 * 1024;
 
+/// how many times a rate limited request is retried before the error is returned
+const RATE_LIMIT_RETRIES: u32 = 3;
+
+/// the shortest and longest waits before retrying a rate limited request; the shortest doubles
+/// with each retry. The request is signed once, so the longest wait times the retries must stay
+/// well within the server's max auth delay
+const RATE_LIMIT_MIN_WAIT: Duration = Duration::from_millis(500);
+const RATE_LIMIT_MAX_WAIT: Duration = Duration::from_secs(10);
+
 impl<E> From<ErrorWrapper<E>> for ApiError<E> {
     fn from(err: ErrorWrapper<E>) -> Self {
         match err {
@@ -28,6 +39,7 @@ impl<E> From<ErrorWrapper<E>> for ApiError<E> {
             ErrorWrapper::ExpiredAuth => ApiError::ExpiredAuth,
             ErrorWrapper::InternalError => ApiError::InternalError,
             ErrorWrapper::BadRequest => ApiError::BadRequest,
+            ErrorWrapper::RateLimited { retry_after } => ApiError::RateLimited { retry_after },
         }
     }
 }
@@ -41,6 +53,7 @@ pub enum ApiError<E> {
     ExpiredAuth,
     InternalError,
     BadRequest,
+    RateLimited { retry_after: u64 },
     Sign(LbErr),
     Serialize(String),
     SendFailed(String),
@@ -55,6 +68,7 @@ pub struct Network {
     pub get_code_version: fn() -> &'static str,
     pub get_time: fn() -> Timestamp,
     pub client_type: ClientType,
+    pub session: Uuid,
 }
 
 impl Default for Network {
@@ -64,6 +78,7 @@ impl Default for Network {
             get_code_version,
             get_time,
             client_type: ClientType::Unknown,
+            session: Uuid::new_v4(),
         }
     }
 }
@@ -80,9 +95,10 @@ impl Network {
         let client_version = String::from((self.get_code_version)());
 
         let wire_format = WireFormat::CLIENT_DEFAULT;
-        let serialized_request = wire_format
+        let serialized_request: Bytes = wire_format
             .serialize(&RequestWrapper { signed_request, client_version: client_version.clone() })
-            .map_err(|err| ApiError::Serialize(err.to_string()))?;
+            .map_err(|err| ApiError::Serialize(err.to_string()))?
+            .into();
 
         if serialized_request.len() > 10 * 1024 * 1024 {
             warn!(
@@ -92,7 +108,33 @@ impl Network {
             );
         }
 
-        let url = &account.api_url;
+        let mut retries = 0;
+        loop {
+            let response = self
+                .send::<T>(
+                    &account.api_url,
+                    &client_version,
+                    wire_format,
+                    serialized_request.clone(),
+                )
+                .await?;
+            match response {
+                Err(ErrorWrapper::RateLimited { retry_after }) if retries < RATE_LIMIT_RETRIES => {
+                    let wait = Duration::from_millis(retry_after)
+                        .max(RATE_LIMIT_MIN_WAIT * 2u32.pow(retries))
+                        .min(RATE_LIMIT_MAX_WAIT);
+                    warn!("rate limited, retrying in {wait:?}");
+                    tokio::time::sleep(wait).await;
+                    retries += 1;
+                }
+                response => return response.map_err(ApiError::from),
+            }
+        }
+    }
+
+    async fn send<T: Request>(
+        &self, url: &str, client_version: &str, wire_format: WireFormat, serialized_request: Bytes,
+    ) -> Result<Result<T::Response, ErrorWrapper<T::Error>>, ApiError<T::Error>> {
         let start = Instant::now();
         let body = body_for(serialized_request);
         let sent = self
@@ -103,13 +145,16 @@ impl Network {
             .header(WIRE_FORMAT_HEADER, wire_format.as_str())
             .header(OS_HEADER, client_os())
             .header(CLIENT_HEADER, self.client_type.as_str())
+            .header(SESSION_HEADER, self.session.to_string())
             .send()
             .await
             .map_err(|e| {
                 warn!("send failed: {e:?}");
                 ApiError::SendFailed(e.to_string())
             })?;
-        if start.elapsed() > Duration::from_millis(1000) {
+        // waiting for updates is held open by the server on purpose
+        if start.elapsed() > Duration::from_millis(1000) && T::ROUTE != WaitForUpdatesRequest::ROUTE
+        {
             warn!("network request took {:?}", start.elapsed());
         }
 
@@ -117,10 +162,9 @@ impl Network {
             .bytes()
             .await
             .map_err(|err| ApiError::ReceiveFailed(err.to_string()))?;
-        let response: Result<T::Response, ErrorWrapper<T::Error>> = wire_format
+        wire_format
             .deserialize(&serialized_response)
-            .map_err(|err| ApiError::Deserialize(err.to_string()))?;
-        response.map_err(ApiError::from)
+            .map_err(|err| ApiError::Deserialize(err.to_string()))
     }
 }
 
@@ -141,11 +185,11 @@ fn client_os() -> &'static str {
 }
 
 #[cfg(not(target_family = "wasm"))]
-fn body_for(serialized_request: Vec<u8>) -> Body {
+fn body_for(serialized_request: Bytes) -> Body {
     if serialized_request.len() < STREAM_BODY_THRESHOLD {
         return Body::from(serialized_request);
     }
-    let mut buf = Bytes::from(serialized_request);
+    let mut buf = serialized_request;
     let mut chunks: Vec<Result<Bytes, std::io::Error>> =
         Vec::with_capacity(buf.len().div_ceil(STREAM_CHUNK_BYTES));
     while !buf.is_empty() {
@@ -156,6 +200,6 @@ fn body_for(serialized_request: Vec<u8>) -> Body {
 }
 
 #[cfg(target_family = "wasm")]
-fn body_for(serialized_request: Vec<u8>) -> Body {
+fn body_for(serialized_request: Bytes) -> Body {
     Body::from(serialized_request)
 }
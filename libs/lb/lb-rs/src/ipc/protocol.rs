@@ -7,12 +7,18 @@ use uuid::Uuid;
 use crate::model::account::Account;
 use crate::model::api::{
     AccountFilter, AccountIdentifier, AdminSetUserTierInfo, ServerIndex, StripeAccountTier,
+    UnixTimeMillis,
 };
+use crate::model::chunks::{ChunkManifest, ChunkRef};
+use crate::model::crypto::AESKey;
 use crate::model::file::ShareMode;
 use crate::model::file_metadata::{DocumentHmac, FileType};
 use crate::model::path_ops::Filter;
+use crate::search::SearchFilter;
 use crate::service::activity::RankingWeights;
 use crate::service::events::Event;
+use crate::service::render::RenderFormat;
+use crate::service::sync_policy::SyncPolicy;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub enum Frame {
@@ -52,6 +58,7 @@ pub enum Request {
     CreateAccount {
         username: String,
         api_url: String,
+        invite_code: Option<String>,
         welcome_doc: bool,
     },
     ImportAccount {
@@ -99,6 +106,15 @@ pub enum Request {
     RebuildIndex {
         index: ServerIndex,
     },
+    MigrateDocuments,
+    CreateInvite {
+        expires_at: Option<UnixTimeMillis>,
+        max_uses: Option<u32>,
+    },
+    RevokeInvite {
+        code: String,
+    },
+    ListInvites,
     SetUserTier {
         username: String,
         info: AdminSetUserTierInfo,
@@ -163,6 +179,14 @@ pub enum Request {
         id: Uuid,
         new_parent: Uuid,
     },
+    RenameFileWithLinkUpdate {
+        id: Uuid,
+        new_name: String,
+    },
+    MoveFileWithLinkUpdate {
+        id: Uuid,
+        new_parent: Uuid,
+    },
     Delete {
         id: Uuid,
     },
@@ -177,9 +201,16 @@ pub enum Request {
     GetFileById {
         id: Uuid,
     },
+    GetAccessMode {
+        id: Uuid,
+    },
     GetFileLinkUrl {
         id: Uuid,
     },
+    ExportRendered {
+        id: Uuid,
+        format: RenderFormat,
+    },
     LocalChanges,
 
     TestRepoIntegrity {
@@ -206,6 +237,20 @@ pub enum Request {
         filter: Option<Filter>,
     },
 
+    Search {
+        query: String,
+        filter: Option<SearchFilter>,
+        limit: usize,
+    },
+    DocumentTags,
+    Backlinks {
+        id: Uuid,
+    },
+    OutgoingLinks {
+        id: Uuid,
+    },
+    BrokenLinks,
+
     ShareFile {
         id: Uuid,
         username: String,
@@ -217,6 +262,28 @@ pub enum Request {
     RejectShare {
         id: Uuid,
     },
+    RevokeShare {
+        id: Uuid,
+        username: String,
+        rotate_keys: bool,
+    },
+    CreateGroup {
+        name: String,
+    },
+    AddGroupMember {
+        group_id: Uuid,
+        username: String,
+    },
+    RemoveGroupMember {
+        group_id: Uuid,
+        username: String,
+    },
+    ShareFileWithGroup {
+        id: Uuid,
+        group_id: Uuid,
+        mode: ShareMode,
+    },
+    ListGroups,
 
     PinFile {
         id: Uuid,
@@ -226,8 +293,71 @@ pub enum Request {
     },
     ListPinned,
 
+    SetSyncPolicy {
+        id: Uuid,
+        policy: SyncPolicy,
+    },
+    ClearSyncPolicy {
+        id: Uuid,
+    },
+    GetSyncPolicy {
+        id: Uuid,
+    },
+    SetDiskBudget {
+        bytes: Option<u64>,
+    },
+    GetDiskBudget,
+
+    CreatePublicLink {
+        id: Uuid,
+    },
+    ListPublicLinks,
+    RevokePublicLink {
+        id: Uuid,
+    },
+
+    ReadDocumentContent {
+        id: Uuid,
+        user_activity: bool,
+    },
+    ReadChunk {
+        id: Uuid,
+        key: AESKey,
+        chunk: ChunkRef,
+    },
+    WriteChunk {
+        id: Uuid,
+        key: AESKey,
+        content: Vec<u8>,
+    },
+    CommitChunks {
+        id: Uuid,
+        manifest: ChunkManifest,
+    },
+    ChunkKey {
+        id: Uuid,
+    },
+
     GetUsage,
 
+    ListTrash,
+    Restore {
+        id: Uuid,
+    },
+    EmptyTrash,
+
+    ListVersions {
+        id: Uuid,
+    },
+    ReadVersion {
+        id: Uuid,
+        hmac: DocumentHmac,
+    },
+    RestoreVersion {
+        id: Uuid,
+        hmac: DocumentHmac,
+    },
+
     Sync,
     Status,
     GetLastSynced,
@@ -107,9 +107,9 @@ fn enc_plain<Out: Serialize>(value: Out) -> Vec<u8> {
 
 pub(crate) async fn dispatch(lb: &LocalLb, req: Request) -> Vec<u8> {
     match req {
-        Request::CreateAccount { username, api_url, welcome_doc } => {
-            enc(lb.create_account(&username, &api_url, welcome_doc).await)
-        }
+        Request::CreateAccount { username, api_url, invite_code, welcome_doc } => enc(lb
+            .create_account_with_invite(&username, &api_url, invite_code.as_deref(), welcome_doc)
+            .await),
         Request::ImportAccount { key, api_url } => {
             enc(lb.import_account(&key, api_url.as_deref()).await)
         }
@@ -150,6 +150,12 @@ pub(crate) async fn dispatch(lb: &LocalLb, req: Request) -> Vec<u8> {
         Request::AdminValidateServer => enc(lb.validate_server().await),
         Request::AdminFileInfo { id } => enc(lb.file_info(id).await),
         Request::RebuildIndex { index } => enc(lb.rebuild_index(index).await),
+        Request::MigrateDocuments => enc(lb.migrate_documents().await),
+        Request::CreateInvite { expires_at, max_uses } => {
+            enc(lb.create_invite(expires_at, max_uses).await)
+        }
+        Request::RevokeInvite { code } => enc(lb.revoke_invite(&code).await),
+        Request::ListInvites => enc(lb.list_invites().await),
         Request::SetUserTier { username, info } => enc(lb.set_user_tier(&username, info).await),
 
         Request::UpgradeAccountStripe { account_tier } => {
@@ -189,6 +195,12 @@ pub(crate) async fn dispatch(lb: &LocalLb, req: Request) -> Vec<u8> {
         }
         Request::RenameFile { id, new_name } => enc(lb.rename_file(&id, &new_name).await),
         Request::MoveFile { id, new_parent } => enc(lb.move_file(&id, &new_parent).await),
+        Request::RenameFileWithLinkUpdate { id, new_name } => {
+            enc(lb.rename_file_with_link_update(&id, &new_name).await)
+        }
+        Request::MoveFileWithLinkUpdate { id, new_parent } => {
+            enc(lb.move_file_with_link_update(&id, &new_parent).await)
+        }
         Request::Delete { id } => enc(lb.delete(&id).await),
         Request::DuplicateFile { id } => enc(lb.duplicate_file(&id).await),
         Request::Root => enc(lb.root().await),
@@ -198,7 +210,9 @@ pub(crate) async fn dispatch(lb: &LocalLb, req: Request) -> Vec<u8> {
             enc(lb.get_and_get_children_recursively(&id).await)
         }
         Request::GetFileById { id } => enc(lb.get_file_by_id(id).await),
+        Request::GetAccessMode { id } => enc(lb.get_access_mode(id).await),
         Request::GetFileLinkUrl { id } => enc(lb.get_file_link_url(id).await),
+        Request::ExportRendered { id, format } => enc(lb.export_rendered(id, format).await),
         Request::LocalChanges => enc_plain(lb.local_changes().await),
 
         Request::TestRepoIntegrity { check_docs } => enc(lb.test_repo_integrity(check_docs).await),
@@ -212,18 +226,61 @@ pub(crate) async fn dispatch(lb: &LocalLb, req: Request) -> Vec<u8> {
         Request::ListPaths { filter } => enc(lb.list_paths(filter).await),
         Request::ListPathsWithIds { filter } => enc(lb.list_paths_with_ids(filter).await),
 
+        Request::Search { query, filter, limit } => enc(lb.search(&query, filter, limit).await),
+        Request::DocumentTags => enc(lb.document_tags().await),
+        Request::Backlinks { id } => enc(lb.backlinks(id).await),
+        Request::OutgoingLinks { id } => enc(lb.outgoing_links(id).await),
+        Request::BrokenLinks => enc(lb.broken_links().await),
+
         Request::ShareFile { id, username, mode } => enc(lb.share_file(id, &username, mode).await),
         Request::GetPendingShares => enc(lb.get_pending_shares().await),
         Request::GetPendingShareFiles => enc(lb.get_pending_share_files().await),
         Request::KnownUsernames => enc(lb.known_usernames().await),
         Request::RejectShare { id } => enc(lb.reject_share(&id).await),
+        Request::RevokeShare { id, username, rotate_keys } => {
+            enc(lb.revoke_share(id, &username, rotate_keys).await)
+        }
+        Request::CreateGroup { name } => enc(lb.create_group(&name).await),
+        Request::AddGroupMember { group_id, username } => {
+            enc(lb.add_group_member(group_id, &username).await)
+        }
+        Request::RemoveGroupMember { group_id, username } => {
+            enc(lb.remove_group_member(group_id, &username).await)
+        }
+        Request::ShareFileWithGroup { id, group_id, mode } => {
+            enc(lb.share_file_with_group(id, group_id, mode).await)
+        }
+        Request::ListGroups => enc(lb.list_groups().await),
 
         Request::PinFile { id } => enc(lb.pin_file(id).await),
         Request::UnpinFile { id } => enc(lb.unpin_file(id).await),
         Request::ListPinned => enc(lb.list_pinned().await),
+        Request::SetSyncPolicy { id, policy } => enc(lb.set_sync_policy(id, policy).await),
+        Request::ClearSyncPolicy { id } => enc(lb.clear_sync_policy(id).await),
+        Request::GetSyncPolicy { id } => enc(lb.get_sync_policy(id).await),
+        Request::SetDiskBudget { bytes } => enc(lb.set_disk_budget(bytes).await),
+        Request::GetDiskBudget => enc(lb.get_disk_budget().await),
+        Request::CreatePublicLink { id } => enc(lb.create_public_link(id).await),
+        Request::ListPublicLinks => enc(lb.list_public_links().await),
+        Request::RevokePublicLink { id } => enc(lb.revoke_public_link(id).await),
+        Request::ReadDocumentContent { id, user_activity } => {
+            enc(lb.read_document_content(id, user_activity).await)
+        }
+        Request::ReadChunk { id, key, chunk } => enc(lb.read_chunk(id, key, chunk).await),
+        Request::WriteChunk { id, key, content } => enc(lb.write_chunk(id, key, content).await),
+        Request::CommitChunks { id, manifest } => enc(lb.commit_chunks(id, manifest).await),
+        Request::ChunkKey { id } => enc(lb.chunk_key(id).await),
 
         Request::GetUsage => enc(lb.get_usage().await),
 
+        Request::ListTrash => enc(lb.list_trash().await),
+        Request::Restore { id } => enc(lb.restore(&id).await),
+        Request::EmptyTrash => enc(lb.empty_trash().await),
+
+        Request::ListVersions { id } => enc(lb.list_versions(id).await),
+        Request::ReadVersion { id, hmac } => enc(lb.read_version(id, hmac).await),
+        Request::RestoreVersion { id, hmac } => enc(lb.restore_version(id, hmac).await),
+
         Request::Sync => enc(lb.sync().await),
         Request::Status => enc_plain(lb.status().await),
         Request::GetLastSynced => enc(lb.get_last_synced().await),
@@ -49,6 +49,7 @@ pub struct LocalLb {
     pub events: EventSubs,
     pub status: StatusUpdater,
     pub syncer: Syncer,
+    pub search: SearchIndexer,
 }
 
 impl LocalLb {
@@ -61,11 +62,15 @@ impl LocalLb {
         let db_cfg = db_rs::Config { fs_locks: false, ..db_cfg };
         let db = CoreDb::init(db_cfg).map_err(|err| LbErrKind::Unexpected(format!("{err:#?}")))?;
         let keychain = Keychain::from(db.account.get());
+        if db.account.get().is_some() {
+            keychain.set_groups(db.groups.get().values())?;
+        }
         let db = Arc::new(RwLock::new(db));
         let client = Network { client_type: config.client_type, ..Network::default() };
 
         let status = StatusUpdater::default();
         let syncer = Default::default();
+        let search = Default::default();
         let events = EventSubs::default();
         let user_last_seen = Arc::new(RwLock::new(Instant::now()));
         let user_wake = Arc::new(Notify::new());
@@ -77,6 +82,7 @@ impl LocalLb {
             docs,
             client,
             syncer,
+            search,
             events,
             status,
             user_last_seen,
@@ -86,6 +92,7 @@ impl LocalLb {
         #[cfg(not(target_family = "wasm"))]
         {
             result.setup_syncer();
+            result.setup_search_index();
             result.setup_status().await?;
         }
 
@@ -115,14 +122,24 @@ impl Lb {
 impl Lb {
     pub async fn create_account(
         &self, username: &str, api_url: &str, welcome_doc: bool,
+    ) -> LbResult<Account> {
+        self.create_account_with_invite(username, api_url, None, welcome_doc)
+            .await
+    }
+
+    pub async fn create_account_with_invite(
+        &self, username: &str, api_url: &str, invite_code: Option<&str>, welcome_doc: bool,
     ) -> LbResult<Account> {
         if let Some(local) = self.local.get() {
-            return local.create_account(username, api_url, welcome_doc).await;
+            return local
+                .create_account_with_invite(username, api_url, invite_code, welcome_doc)
+                .await;
         }
         let account = self
             .call::<Account>(Request::CreateAccount {
                 username: username.to_string(),
                 api_url: api_url.to_string(),
+                invite_code: invite_code.map(|s| s.to_string()),
                 welcome_doc,
             })
             .await?;
@@ -287,6 +304,38 @@ impl Lb {
         self.call(Request::RebuildIndex { index }).await
     }
 
+    pub async fn migrate_documents(&self) -> LbResult<AdminMigrateDocumentsResponse> {
+        if let Some(local) = self.local.get() {
+            return local.migrate_documents().await;
+        }
+        self.call(Request::MigrateDocuments).await
+    }
+
+    pub async fn create_invite(
+        &self, expires_at: Option<UnixTimeMillis>, max_uses: Option<u32>,
+    ) -> LbResult<InviteInfo> {
+        if let Some(local) = self.local.get() {
+            return local.create_invite(expires_at, max_uses).await;
+        }
+        self.call(Request::CreateInvite { expires_at, max_uses })
+            .await
+    }
+
+    pub async fn revoke_invite(&self, code: &str) -> LbResult<()> {
+        if let Some(local) = self.local.get() {
+            return local.revoke_invite(code).await;
+        }
+        self.call(Request::RevokeInvite { code: code.to_string() })
+            .await
+    }
+
+    pub async fn list_invites(&self) -> LbResult<Vec<InviteInfo>> {
+        if let Some(local) = self.local.get() {
+            return local.list_invites().await;
+        }
+        self.call(Request::ListInvites).await
+    }
+
     pub async fn set_user_tier(&self, username: &str, info: AdminSetUserTierInfo) -> LbResult<()> {
         if let Some(local) = self.local.get() {
             return local.set_user_tier(username, info).await;
@@ -436,6 +485,26 @@ impl Lb {
             .await
     }
 
+    pub async fn rename_file_with_link_update(
+        &self, id: &Uuid, new_name: &str,
+    ) -> LbResult<Vec<Uuid>> {
+        if let Some(local) = self.local.get() {
+            return local.rename_file_with_link_update(id, new_name).await;
+        }
+        self.call(Request::RenameFileWithLinkUpdate { id: *id, new_name: new_name.to_string() })
+            .await
+    }
+
+    pub async fn move_file_with_link_update(
+        &self, id: &Uuid, new_parent: &Uuid,
+    ) -> LbResult<Vec<Uuid>> {
+        if let Some(local) = self.local.get() {
+            return local.move_file_with_link_update(id, new_parent).await;
+        }
+        self.call(Request::MoveFileWithLinkUpdate { id: *id, new_parent: *new_parent })
+            .await
+    }
+
     pub async fn duplicate_file(&self, id: &Uuid) -> LbResult<File> {
         if let Some(local) = self.local.get() {
             return local.duplicate_file(id).await;
@@ -486,6 +555,13 @@ impl Lb {
         self.call(Request::GetFileById { id }).await
     }
 
+    pub async fn get_access_mode(&self, id: Uuid) -> LbResult<Option<UserAccessMode>> {
+        if let Some(local) = self.local.get() {
+            return local.get_access_mode(id).await;
+        }
+        self.call(Request::GetAccessMode { id }).await
+    }
+
     pub async fn get_file_link_url(&self, id: Uuid) -> LbResult<String> {
         if let Some(local) = self.local.get() {
             return local.get_file_link_url(id).await;
@@ -493,6 +569,13 @@ impl Lb {
         self.call(Request::GetFileLinkUrl { id }).await
     }
 
+    pub async fn export_rendered(&self, id: Uuid, format: RenderFormat) -> LbResult<Vec<u8>> {
+        if let Some(local) = self.local.get() {
+            return local.export_rendered(id, format).await;
+        }
+        self.call(Request::ExportRendered { id, format }).await
+    }
+
     pub async fn local_changes(&self) -> Vec<Uuid> {
         if let Some(local) = self.local.get() {
             return local.local_changes().await;
@@ -556,6 +639,44 @@ impl Lb {
         self.call(Request::ListPathsWithIds { filter }).await
     }
 
+    pub async fn search(
+        &self, query: &str, filter: Option<SearchFilter>, limit: usize,
+    ) -> LbResult<Vec<SearchHit>> {
+        if let Some(local) = self.local.get() {
+            return local.search(query, filter, limit).await;
+        }
+        self.call(Request::Search { query: query.to_string(), filter, limit })
+            .await
+    }
+
+    pub async fn document_tags(&self) -> LbResult<HashMap<Uuid, Vec<String>>> {
+        if let Some(local) = self.local.get() {
+            return local.document_tags().await;
+        }
+        self.call(Request::DocumentTags).await
+    }
+
+    pub async fn backlinks(&self, id: Uuid) -> LbResult<Vec<DocumentLink>> {
+        if let Some(local) = self.local.get() {
+            return local.backlinks(id).await;
+        }
+        self.call(Request::Backlinks { id }).await
+    }
+
+    pub async fn outgoing_links(&self, id: Uuid) -> LbResult<Vec<DocumentLink>> {
+        if let Some(local) = self.local.get() {
+            return local.outgoing_links(id).await;
+        }
+        self.call(Request::OutgoingLinks { id }).await
+    }
+
+    pub async fn broken_links(&self) -> LbResult<Vec<DocumentLink>> {
+        if let Some(local) = self.local.get() {
+            return local.broken_links().await;
+        }
+        self.call(Request::BrokenLinks).await
+    }
+
     pub async fn share_file(&self, id: Uuid, username: &str, mode: ShareMode) -> LbResult<()> {
         if let Some(local) = self.local.get() {
             return local.share_file(id, username, mode).await;
@@ -603,6 +724,55 @@ impl Lb {
         self.call(Request::RejectShare { id: *id }).await
     }
 
+    pub async fn revoke_share(&self, id: Uuid, username: &str, rotate_keys: bool) -> LbResult<()> {
+        if let Some(local) = self.local.get() {
+            return local.revoke_share(id, username, rotate_keys).await;
+        }
+        self.call(Request::RevokeShare { id, username: username.to_string(), rotate_keys })
+            .await
+    }
+
+    pub async fn create_group(&self, name: &str) -> LbResult<Group> {
+        if let Some(local) = self.local.get() {
+            return local.create_group(name).await;
+        }
+        self.call(Request::CreateGroup { name: name.to_string() })
+            .await
+    }
+
+    pub async fn add_group_member(&self, group_id: Uuid, username: &str) -> LbResult<()> {
+        if let Some(local) = self.local.get() {
+            return local.add_group_member(group_id, username).await;
+        }
+        self.call(Request::AddGroupMember { group_id, username: username.to_string() })
+            .await
+    }
+
+    pub async fn remove_group_member(&self, group_id: Uuid, username: &str) -> LbResult<()> {
+        if let Some(local) = self.local.get() {
+            return local.remove_group_member(group_id, username).await;
+        }
+        self.call(Request::RemoveGroupMember { group_id, username: username.to_string() })
+            .await
+    }
+
+    pub async fn share_file_with_group(
+        &self, id: Uuid, group_id: Uuid, mode: ShareMode,
+    ) -> LbResult<()> {
+        if let Some(local) = self.local.get() {
+            return local.share_file_with_group(id, group_id, mode).await;
+        }
+        self.call(Request::ShareFileWithGroup { id, group_id, mode })
+            .await
+    }
+
+    pub async fn list_groups(&self) -> LbResult<Vec<Group>> {
+        if let Some(local) = self.local.get() {
+            return local.list_groups().await;
+        }
+        self.call(Request::ListGroups).await
+    }
+
     pub async fn pin_file(&self, id: Uuid) -> LbResult<()> {
         if let Some(local) = self.local.get() {
             return local.pin_file(id).await;
@@ -624,6 +794,102 @@ impl Lb {
         self.call(Request::ListPinned).await
     }
 
+    pub async fn set_sync_policy(&self, id: Uuid, policy: SyncPolicy) -> LbResult<()> {
+        if let Some(local) = self.local.get() {
+            return local.set_sync_policy(id, policy).await;
+        }
+        self.call(Request::SetSyncPolicy { id, policy }).await
+    }
+
+    pub async fn clear_sync_policy(&self, id: Uuid) -> LbResult<()> {
+        if let Some(local) = self.local.get() {
+            return local.clear_sync_policy(id).await;
+        }
+        self.call(Request::ClearSyncPolicy { id }).await
+    }
+
+    pub async fn get_sync_policy(&self, id: Uuid) -> LbResult<Option<SyncPolicy>> {
+        if let Some(local) = self.local.get() {
+            return local.get_sync_policy(id).await;
+        }
+        self.call(Request::GetSyncPolicy { id }).await
+    }
+
+    pub async fn set_disk_budget(&self, bytes: Option<u64>) -> LbResult<()> {
+        if let Some(local) = self.local.get() {
+            return local.set_disk_budget(bytes).await;
+        }
+        self.call(Request::SetDiskBudget { bytes }).await
+    }
+
+    pub async fn get_disk_budget(&self) -> LbResult<Option<u64>> {
+        if let Some(local) = self.local.get() {
+            return local.get_disk_budget().await;
+        }
+        self.call(Request::GetDiskBudget).await
+    }
+
+    pub async fn create_public_link(&self, id: Uuid) -> LbResult<PublicLink> {
+        if let Some(local) = self.local.get() {
+            return local.create_public_link(id).await;
+        }
+        self.call(Request::CreatePublicLink { id }).await
+    }
+
+    pub async fn list_public_links(&self) -> LbResult<Vec<PublicLink>> {
+        if let Some(local) = self.local.get() {
+            return local.list_public_links().await;
+        }
+        self.call(Request::ListPublicLinks).await
+    }
+
+    pub async fn revoke_public_link(&self, id: Uuid) -> LbResult<()> {
+        if let Some(local) = self.local.get() {
+            return local.revoke_public_link(id).await;
+        }
+        self.call(Request::RevokePublicLink { id }).await
+    }
+
+    pub async fn read_document_content(
+        &self, id: Uuid, user_activity: bool,
+    ) -> LbResult<DocumentContent> {
+        if let Some(local) = self.local.get() {
+            return local.read_document_content(id, user_activity).await;
+        }
+        self.call(Request::ReadDocumentContent { id, user_activity })
+            .await
+    }
+
+    pub async fn read_chunk(
+        &self, id: Uuid, key: AESKey, chunk: ChunkRef,
+    ) -> LbResult<DecryptedDocument> {
+        if let Some(local) = self.local.get() {
+            return local.read_chunk(id, key, chunk).await;
+        }
+        self.call(Request::ReadChunk { id, key, chunk }).await
+    }
+
+    pub async fn write_chunk(&self, id: Uuid, key: AESKey, content: Vec<u8>) -> LbResult<ChunkRef> {
+        if let Some(local) = self.local.get() {
+            return local.write_chunk(id, key, content).await;
+        }
+        self.call(Request::WriteChunk { id, key, content }).await
+    }
+
+    pub async fn commit_chunks(&self, id: Uuid, manifest: ChunkManifest) -> LbResult<()> {
+        if let Some(local) = self.local.get() {
+            return local.commit_chunks(id, manifest).await;
+        }
+        self.call(Request::CommitChunks { id, manifest }).await
+    }
+
+    pub async fn chunk_key(&self, id: Uuid) -> LbResult<AESKey> {
+        if let Some(local) = self.local.get() {
+            return local.chunk_key(id).await;
+        }
+        self.call(Request::ChunkKey { id }).await
+    }
+
     pub async fn get_usage(&self) -> LbResult<UsageMetrics> {
         if let Some(local) = self.local.get() {
             return local.get_usage().await;
@@ -631,6 +897,48 @@ impl Lb {
         self.call(Request::GetUsage).await
     }
 
+    pub async fn list_trash(&self) -> LbResult<Vec<File>> {
+        if let Some(local) = self.local.get() {
+            return local.list_trash().await;
+        }
+        self.call(Request::ListTrash).await
+    }
+
+    pub async fn restore(&self, id: &Uuid) -> LbResult<()> {
+        if let Some(local) = self.local.get() {
+            return local.restore(id).await;
+        }
+        self.call(Request::Restore { id: *id }).await
+    }
+
+    pub async fn empty_trash(&self) -> LbResult<()> {
+        if let Some(local) = self.local.get() {
+            return local.empty_trash().await;
+        }
+        self.call(Request::EmptyTrash).await
+    }
+
+    pub async fn list_versions(&self, id: Uuid) -> LbResult<Vec<DocumentVersion>> {
+        if let Some(local) = self.local.get() {
+            return local.list_versions(id).await;
+        }
+        self.call(Request::ListVersions { id }).await
+    }
+
+    pub async fn read_version(&self, id: Uuid, hmac: DocumentHmac) -> LbResult<DecryptedDocument> {
+        if let Some(local) = self.local.get() {
+            return local.read_version(id, hmac).await;
+        }
+        self.call(Request::ReadVersion { id, hmac }).await
+    }
+
+    pub async fn restore_version(&self, id: Uuid, hmac: DocumentHmac) -> LbResult<()> {
+        if let Some(local) = self.local.get() {
+            return local.restore_version(id, hmac).await;
+        }
+        self.call(Request::RestoreVersion { id, hmac }).await
+    }
+
     pub async fn sync(&self) -> LbResult<()> {
         if let Some(local) = self.local.get() {
             return local.sync().await;
@@ -693,6 +1001,7 @@ pub static CORE_CODE_VERSION: &str = env!("CARGO_PKG_VERSION");
 
 use crate::io::CoreDb;
 use crate::ipc::client::RemoteLb;
+use crate::subscribers::search::SearchIndexer;
 use crate::subscribers::syncer::Syncer;
 use db_rs::Db;
 
@@ -704,6 +1013,7 @@ use model::core_config::Config;
 pub use model::errors::{LbErrKind, LbResult};
 use service::events::EventSubs;
 use service::keychain::Keychain;
+use std::collections::HashMap;
 use std::sync::{Arc, OnceLock};
 use subscribers::status::StatusUpdater;
 use tokio::sync::{Notify, RwLock};
@@ -711,18 +1021,28 @@ pub use uuid::Uuid;
 use web_time::Instant;
 
 use crate::ipc::protocol::Request;
+use crate::model::access_info::UserAccessMode;
 use crate::model::account::{Account, Username};
 use crate::model::api::{
-    AccountFilter, AccountIdentifier, AccountInfo, AdminFileInfoResponse, AdminSetUserTierInfo,
-    AdminValidateAccount, AdminValidateServer, ServerIndex, StripeAccountTier, SubscriptionInfo,
+    AccountFilter, AccountIdentifier, AccountInfo, AdminFileInfoResponse,
+    AdminMigrateDocumentsResponse, AdminSetUserTierInfo, AdminValidateAccount, AdminValidateServer,
+    DocumentVersion, InviteInfo, ServerIndex, StripeAccountTier, SubscriptionInfo, UnixTimeMillis,
 };
-use crate::model::crypto::DecryptedDocument;
+use crate::model::chunks::{ChunkManifest, ChunkRef};
+use crate::model::crypto::{AESKey, DecryptedDocument};
 use crate::model::errors::Warning;
 use crate::model::file::{File, ShareMode};
 use crate::model::file_metadata::{DocumentHmac, FileType};
 use crate::model::path_ops::Filter;
+use crate::search::{SearchFilter, SearchHit};
 use crate::service::activity::RankingWeights;
 #[cfg(not(target_family = "wasm"))]
 use crate::service::debug::DebugInfo;
+use crate::service::groups::Group;
+use crate::service::links::DocumentLink;
+use crate::service::public_links::PublicLink;
+use crate::service::render::RenderFormat;
+use crate::service::streams::DocumentContent;
+use crate::service::sync_policy::SyncPolicy;
 use crate::service::usage::UsageMetrics;
 use crate::subscribers::status::Status;
@@ -1,7 +1,7 @@
 use crate::model::account::Account;
 use crate::model::crypto::{AESEncrypted, AESKey};
 use crate::model::{pubkey, symkey};
-use libsecp256k1::PublicKey;
+use libsecp256k1::{PublicKey, SecretKey};
 use serde::{Deserialize, Serialize};
 
 use super::errors::LbResult;
@@ -52,7 +52,13 @@ impl UserAccessInfo {
     }
 
     pub fn decrypt(&self, account: &Account) -> LbResult<AESKey> {
-        let shared_secret = pubkey::get_aes_key(&account.private_key, &self.encrypted_by)?;
+        self.decrypt_with(&account.private_key)
+    }
+
+    /// decrypts with the private key of whoever the access is for, which for a group isn't an
+    /// account's
+    pub fn decrypt_with(&self, private_key: &SecretKey) -> LbResult<AESKey> {
+        let shared_secret = pubkey::get_aes_key(private_key, &self.encrypted_by)?;
         let encrypted = &self.access_key;
         let decrypted = symkey::decrypt(&shared_secret, encrypted)?;
         Ok(decrypted)
@@ -42,6 +42,11 @@ pub enum ErrorWrapper<E> {
     ExpiredAuth,
     InternalError,
     BadRequest,
+    /// too many requests to this route from this account or ip; the request can be retried after
+    /// this many milliseconds
+    RateLimited {
+        retry_after: u64,
+    },
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
@@ -111,6 +116,10 @@ pub enum ChangeDocError {
     OldVersionIncorrect,
     DiffMalformed,
     UsageIsOverDataCap,
+    /// the server no longer has the version a delta was made against
+    BaseNotFound,
+    /// too many deltas are stacked on the base; the document should be uploaded in full
+    DeltaChainTooLong,
 }
 
 impl Request for ChangeDocRequestV2 {
@@ -120,6 +129,21 @@ impl Request for ChangeDocRequestV2 {
     const ROUTE: &'static str = "/change-document-content-v2";
 }
 
+/// Changes a document's content by uploading an encrypted [crate::model::delta::DocumentDelta]
+/// against the content in `diff.old`, instead of the whole document.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct ChangeDocDeltaRequest {
+    pub diff: FileDiff<SignedMeta>,
+    pub delta: EncryptedDocument,
+}
+
+impl Request for ChangeDocDeltaRequest {
+    type Response = ();
+    type Error = ChangeDocError;
+    const METHOD: Method = Method::PUT;
+    const ROUTE: &'static str = "/change-document-delta";
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub struct GetDocRequest {
     pub id: Uuid,
@@ -145,6 +169,380 @@ impl Request for GetDocRequest {
     const ROUTE: &'static str = "/get-document";
 }
 
+/// Like [GetDocRequest], but the content may be a [crate::model::delta::DocumentDelta] that the
+/// client applies. Content stored as a delta is refused to a [GetDocRequest] with
+/// [ErrorWrapper::ClientUpdateRequired], so clients that can't apply deltas never see one.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct GetDocRequestV2 {
+    pub id: Uuid,
+    pub hmac: DocumentHmac,
+}
+
+/// How stored content is read. The server knows this from the requests that stored the content,
+/// and says so alongside it, so that clients never have to guess it from the content itself.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum ContentFormat {
+    /// the document itself
+    #[default]
+    Document,
+    /// a [crate::model::chunks::ChunkManifest] listing the chunks the document is stored in
+    Manifest,
+    /// a [crate::model::delta::DocumentDelta] against an earlier version of the document
+    Delta,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct GetDocumentResponseV2 {
+    pub content: EncryptedDocument,
+    pub format: ContentFormat,
+}
+
+impl Request for GetDocRequestV2 {
+    type Response = GetDocumentResponseV2;
+    type Error = GetDocumentError;
+    const METHOD: Method = Method::GET;
+    const ROUTE: &'static str = "/get-document-v2";
+}
+
+/// A prior revision of a document retained by the server after it was overwritten. The contents
+/// can be fetched with a [GetDocRequestV2] using this version's hmac.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub struct DocumentVersion {
+    pub hmac: DocumentHmac,
+    pub size_bytes: u64,
+    /// when this version was replaced by a newer one
+    pub replaced_at: UnixTimeMillis,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct GetDocVersionsRequest {
+    pub id: Uuid,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct GetDocVersionsResponse {
+    /// newest first
+    pub versions: Vec<DocumentVersion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub enum GetDocVersionsError {
+    DocumentNotFound,
+    NotPermissioned,
+}
+
+impl Request for GetDocVersionsRequest {
+    type Response = GetDocVersionsResponse;
+    type Error = GetDocVersionsError;
+    const METHOD: Method = Method::GET;
+    const ROUTE: &'static str = "/get-document-versions";
+}
+
+/// Registers the chunks a chunked document's manifest refers to, ahead of uploading them. Chunks
+/// the server already has for this document don't need to be uploaded again.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct DeclareChunksRequest {
+    pub id: Uuid,
+    /// hmac of the manifest that will become the document's content
+    pub hmac: DocumentHmac,
+    pub chunks: Vec<DocumentHmac>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct DeclareChunksResponse {
+    pub missing: Vec<DocumentHmac>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub enum DeclareChunksError {
+    DocumentNotFound,
+    NotPermissioned,
+}
+
+impl Request for DeclareChunksRequest {
+    type Response = DeclareChunksResponse;
+    type Error = DeclareChunksError;
+    const METHOD: Method = Method::POST;
+    const ROUTE: &'static str = "/declare-chunks";
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct UpsertChunkRequest {
+    pub id: Uuid,
+    pub hmac: DocumentHmac,
+    pub content: EncryptedDocument,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub enum UpsertChunkError {
+    DocumentNotFound,
+    NotPermissioned,
+    ChunkNotDeclared,
+    UsageIsOverDataCap,
+}
+
+impl Request for UpsertChunkRequest {
+    type Response = ();
+    type Error = UpsertChunkError;
+    const METHOD: Method = Method::POST;
+    const ROUTE: &'static str = "/upsert-chunk";
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct GetChunkRequest {
+    pub id: Uuid,
+    pub hmac: DocumentHmac,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct GetChunkResponse {
+    pub content: EncryptedDocument,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub enum GetChunkError {
+    DocumentNotFound,
+    NotPermissioned,
+    ChunkNotFound,
+    BandwidthExceeded,
+}
+
+impl Request for GetChunkRequest {
+    type Response = GetChunkResponse;
+    type Error = GetChunkError;
+    const METHOD: Method = Method::GET;
+    const ROUTE: &'static str = "/get-chunk";
+}
+
+/// Permanently deletes the requester's deleted files rather than waiting for the trash period to
+/// elapse.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EmptyTrashRequest {}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub enum EmptyTrashError {
+    UserNotFound,
+}
+
+impl Request for EmptyTrashRequest {
+    type Response = ();
+    type Error = EmptyTrashError;
+    const METHOD: Method = Method::POST;
+    const ROUTE: &'static str = "/empty-trash";
+}
+
+/// A link through which anyone can view a snapshot of a file without an account. The snapshot is
+/// encrypted with a key of the link's own, which is only ever in the link itself.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct PublicLinkInfo {
+    pub id: Uuid,
+    pub file_id: Uuid,
+    pub owner: Owner,
+    /// the link's key, encrypted with the key of the file it shares so the link can be listed on
+    /// any device with access to the file
+    pub encrypted_key: AESEncrypted<AESKey>,
+    pub size_bytes: u64,
+    pub created_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct CreatePublicLinkRequest {
+    pub id: Uuid,
+    pub file_id: Uuid,
+    pub encrypted_key: AESEncrypted<AESKey>,
+    pub snapshot: EncryptedDocument,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub enum CreatePublicLinkError {
+    FileNotFound,
+    NotPermissioned,
+    LinkExists,
+    UsageIsOverDataCap,
+}
+
+impl Request for CreatePublicLinkRequest {
+    type Response = ();
+    type Error = CreatePublicLinkError;
+    const METHOD: Method = Method::POST;
+    const ROUTE: &'static str = "/create-public-link";
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct ListPublicLinksRequest {}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct ListPublicLinksResponse {
+    pub links: Vec<PublicLinkInfo>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub enum ListPublicLinksError {
+    UserNotFound,
+}
+
+impl Request for ListPublicLinksRequest {
+    type Response = ListPublicLinksResponse;
+    type Error = ListPublicLinksError;
+    const METHOD: Method = Method::GET;
+    const ROUTE: &'static str = "/list-public-links";
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct RevokePublicLinkRequest {
+    pub id: Uuid,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub enum RevokePublicLinkError {
+    LinkNotFound,
+    NotPermissioned,
+}
+
+impl Request for RevokePublicLinkRequest {
+    type Response = ();
+    type Error = RevokePublicLinkError;
+    const METHOD: Method = Method::POST;
+    const ROUTE: &'static str = "/revoke-public-link";
+}
+
+/// A named set of users that files can be shared with as one. The group has a keypair of its own:
+/// files are shared with its public key, and each member can decrypt its private key. Removing a
+/// member replaces the keypair, so files shared with the group from then on use a key the removed
+/// member never had.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct GroupInfo {
+    pub id: Uuid,
+    pub name: String,
+    pub public_key: PublicKey,
+    /// the keys the group had before members were removed, oldest first. Files shared with the
+    /// group before then are still shared with these.
+    pub previous_public_keys: Vec<PublicKey>,
+    /// the only user who can add and remove members; always a member
+    pub owner: Owner,
+    pub members: Vec<GroupMember>,
+}
+
+impl GroupInfo {
+    /// every key the group has had, each of which files may be shared with
+    pub fn public_keys(&self) -> impl Iterator<Item = PublicKey> + '_ {
+        self.previous_public_keys
+            .iter()
+            .copied()
+            .chain(Some(self.public_key))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct GroupMember {
+    pub public_key: PublicKey,
+    /// the group's private key, encrypted with the key shared by the group's owner and the member
+    pub encrypted_private_key: AESEncrypted<[u8; 32]>,
+    /// the group's previous private keys, in the order of [GroupInfo::previous_public_keys],
+    /// encrypted the same way
+    pub previous_private_keys: Vec<AESEncrypted<[u8; 32]>>,
+    /// when the member was added, so they're sent the files already shared with the group
+    pub added_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct CreateGroupRequest {
+    pub id: Uuid,
+    pub name: String,
+    pub public_key: PublicKey,
+    /// the group's private key, encrypted for its owner, who is its first member
+    pub encrypted_private_key: AESEncrypted<[u8; 32]>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub enum CreateGroupError {
+    GroupExists,
+}
+
+impl Request for CreateGroupRequest {
+    type Response = ();
+    type Error = CreateGroupError;
+    const METHOD: Method = Method::POST;
+    const ROUTE: &'static str = "/create-group";
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct AddGroupMemberRequest {
+    pub group_id: Uuid,
+    pub member: PublicKey,
+    pub encrypted_private_key: AESEncrypted<[u8; 32]>,
+    /// the group's previous private keys, see [GroupMember::previous_private_keys]
+    pub previous_private_keys: Vec<AESEncrypted<[u8; 32]>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub enum AddGroupMemberError {
+    GroupNotFound,
+    NotPermissioned,
+    UserNotFound,
+    AlreadyMember,
+    /// the group's keys changed since the requester fetched it
+    KeysOutdated,
+}
+
+impl Request for AddGroupMemberRequest {
+    type Response = ();
+    type Error = AddGroupMemberError;
+    const METHOD: Method = Method::POST;
+    const ROUTE: &'static str = "/add-group-member";
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct RemoveGroupMemberRequest {
+    pub group_id: Uuid,
+    pub member: PublicKey,
+    /// the group's new public key
+    pub public_key: PublicKey,
+    /// the group's new private key for each remaining member, encrypted like
+    /// [GroupMember::encrypted_private_key]
+    pub encrypted_private_keys: Vec<(PublicKey, AESEncrypted<[u8; 32]>)>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub enum RemoveGroupMemberError {
+    GroupNotFound,
+    NotPermissioned,
+    NotMember,
+    /// a group's owner can't leave it
+    CannotRemoveOwner,
+    /// the new private key wasn't encrypted for exactly the remaining members, who changed since
+    /// the requester fetched the group
+    KeysOutdated,
+}
+
+impl Request for RemoveGroupMemberRequest {
+    type Response = ();
+    type Error = RemoveGroupMemberError;
+    const METHOD: Method = Method::POST;
+    const ROUTE: &'static str = "/remove-group-member";
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct GetGroupsRequest {}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct GetGroupsResponse {
+    /// the groups the requester is a member of
+    pub groups: Vec<GroupInfo>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub enum GetGroupsError {
+    UserNotFound,
+}
+
+impl Request for GetGroupsRequest {
+    type Response = GetGroupsResponse;
+    type Error = GetGroupsError;
+    const METHOD: Method = Method::GET;
+    const ROUTE: &'static str = "/get-groups";
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub struct GetPublicKeyRequest {
     pub username: String,
@@ -272,6 +670,38 @@ impl Request for GetUpdatesRequestV2 {
     const ROUTE: &'static str = "/get-updates-v2";
 }
 
+/// Held by the server until a file the requester can see has changed since
+/// `since_metadata_version`, or until it's waited for [UPDATES_WAIT_SECS]; a pull tells the
+/// client what changed. Changes made by the client that's waiting, as identified by
+/// [crate::model::wire::SESSION_HEADER], don't count.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct WaitForUpdatesRequest {
+    pub since_metadata_version: u64,
+}
+
+pub const UPDATES_WAIT_SECS: u64 = 30;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct WaitForUpdatesResponse {
+    /// false when the wait timed out
+    pub updated: bool,
+    /// when nothing was updated, the version to wait from next time, which is past any changes
+    /// the waiting client made itself
+    pub as_of: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub enum WaitForUpdatesError {
+    UserNotFound,
+}
+
+impl Request for WaitForUpdatesRequest {
+    type Response = WaitForUpdatesResponse;
+    type Error = WaitForUpdatesError;
+    const METHOD: Method = Method::GET;
+    const ROUTE: &'static str = "/wait-for-updates";
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct NewAccountRequestV2 {
     pub username: Username,
@@ -297,6 +727,41 @@ impl Request for NewAccountRequestV2 {
     const ROUTE: &'static str = "/new-account-v2";
 }
 
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct NewAccountRequestV3 {
+    pub username: Username,
+    pub public_key: PublicKey,
+    pub root_folder: SignedMeta,
+    /// required by servers that only accept accounts by invitation
+    pub invite_code: Option<String>,
+}
+
+impl NewAccountRequestV3 {
+    pub fn new(account: &Account, root_folder: &SignedMeta, invite_code: Option<&str>) -> Self {
+        let root_folder = root_folder.clone();
+        NewAccountRequestV3 {
+            username: account.username.clone(),
+            public_key: account.public_key(),
+            root_folder,
+            invite_code: invite_code.map(String::from),
+        }
+    }
+}
+
+impl From<NewAccountRequestV2> for NewAccountRequestV3 {
+    fn from(value: NewAccountRequestV2) -> Self {
+        let NewAccountRequestV2 { username, public_key, root_folder } = value;
+        NewAccountRequestV3 { username, public_key, root_folder, invite_code: None }
+    }
+}
+
+impl Request for NewAccountRequestV3 {
+    type Response = NewAccountResponse;
+    type Error = NewAccountError;
+    const METHOD: Method = Method::POST;
+    const ROUTE: &'static str = "/new-account-v3";
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub struct NewAccountResponse {
     pub last_synced: u64,
@@ -310,6 +775,7 @@ pub enum NewAccountError {
     FileIdTaken,
     Disabled,
     RateLimited,
+    InviteRequired,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
@@ -848,6 +1314,115 @@ impl Request for AdminRebuildIndexRequest {
     const ROUTE: &'static str = "/admin-rebuild-index";
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AdminMigrateDocumentsRequest {}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct AdminMigrateDocumentsResponse {
+    pub copied: u64,
+    pub already_present: u64,
+    /// files on disk that no document, version, chunk or public link refers to, which aren't
+    /// copied
+    pub unreferenced: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum AdminMigrateDocumentsError {
+    NotPermissioned,
+    /// documents are already stored on disk, where they'd be copied from
+    NotUsingObjectStorage,
+}
+
+impl Request for AdminMigrateDocumentsRequest {
+    type Response = AdminMigrateDocumentsResponse;
+    type Error = AdminMigrateDocumentsError;
+    const METHOD: Method = Method::POST;
+    const ROUTE: &'static str = "/admin-migrate-documents";
+}
+
+/// A code that lets someone create an account on a server that only accepts accounts by
+/// invitation. Codes are made by admins, and can be limited in how many accounts they create and
+/// until when.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct InviteInfo {
+    pub code: String,
+    pub created_by: Owner,
+    pub created_at: UnixTimeMillis,
+    /// `None` for codes that work until they're revoked
+    pub expires_at: Option<UnixTimeMillis>,
+    /// `Some(1)` for single-use codes, `None` for codes without a limit
+    pub max_uses: Option<u32>,
+    pub uses: u32,
+}
+
+impl InviteInfo {
+    pub fn is_usable(&self, now: UnixTimeMillis) -> bool {
+        self.expires_at.is_none_or(|expires_at| now < expires_at)
+            && self.max_uses.is_none_or(|max_uses| self.uses < max_uses)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct AdminCreateInviteRequest {
+    pub expires_at: Option<UnixTimeMillis>,
+    pub max_uses: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct AdminCreateInviteResponse {
+    pub invite: InviteInfo,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub enum AdminCreateInviteError {
+    NotPermissioned,
+}
+
+impl Request for AdminCreateInviteRequest {
+    type Response = AdminCreateInviteResponse;
+    type Error = AdminCreateInviteError;
+    const METHOD: Method = Method::POST;
+    const ROUTE: &'static str = "/admin-create-invite";
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct AdminRevokeInviteRequest {
+    pub code: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub enum AdminRevokeInviteError {
+    NotPermissioned,
+    InviteNotFound,
+}
+
+impl Request for AdminRevokeInviteRequest {
+    type Response = ();
+    type Error = AdminRevokeInviteError;
+    const METHOD: Method = Method::POST;
+    const ROUTE: &'static str = "/admin-revoke-invite";
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct AdminListInvitesRequest {}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct AdminListInvitesResponse {
+    pub invites: Vec<InviteInfo>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub enum AdminListInvitesError {
+    NotPermissioned,
+}
+
+impl Request for AdminListInvitesRequest {
+    type Response = AdminListInvitesResponse;
+    type Error = AdminListInvitesError;
+    const METHOD: Method = Method::GET;
+    const ROUTE: &'static str = "/admin-list-invites";
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub enum StripeAccountState {
     Ok,
@@ -0,0 +1,167 @@
+//! Large documents are stored as a series of chunks, each compressed and encrypted on its own,
+//! so they can be written, read, and uploaded without holding the whole document in memory. The
+//! document's content is then a [ChunkManifest] listing those chunks in order. Chunks are
+//! encrypted with a key of their own kept in the manifest, so they can be copied between documents
+//! as they are.
+//!
+//! Whether a document's content is a manifest is recorded alongside the content, as a
+//! [super::api::ContentFormat], rather than read from the content.
+//!
+//! Chunk boundaries are content-defined: they're found with a rolling hash over the bytes rather
+//! than at fixed offsets, so an edit in the middle of a document only changes the chunks around
+//! the edit, and everything else is already on the server.
+
+use serde::{Deserialize, Serialize};
+
+use super::crypto::AESKey;
+use super::errors::{LbResult, Unexpected};
+use super::file_metadata::DocumentHmac;
+
+pub const MIN_CHUNK_SIZE: usize = 256 * 1024;
+pub const MAX_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+/// chunks average about 1 MiB: a boundary is a hash with its lowest 20 bits unset
+const BOUNDARY_MASK: u64 = (1 << 20) - 1;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct ChunkRef {
+    /// hmac of the chunk's plaintext, keyed by the manifest's key, which identifies the chunk
+    pub hmac: DocumentHmac,
+    /// size of the chunk's plaintext
+    pub size: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Default)]
+pub struct ChunkManifest {
+    pub key: AESKey,
+    pub chunks: Vec<ChunkRef>,
+}
+
+impl ChunkManifest {
+    pub fn to_bytes(&self) -> LbResult<Vec<u8>> {
+        bincode::serialize(self).map_unexpected()
+    }
+
+    pub fn from_bytes(content: &[u8]) -> LbResult<Self> {
+        bincode::deserialize(content).map_unexpected()
+    }
+
+    /// size of the document's plaintext
+    pub fn len(&self) -> u64 {
+        self.chunks.iter().map(|chunk| chunk.size).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Finds chunk boundaries in a stream of bytes using a gear hash.
+#[derive(Default)]
+pub struct Chunker {
+    hash: u64,
+    len: usize,
+}
+
+impl Chunker {
+    /// Consumes `data` as the continuation of the current chunk. Returns how many bytes of `data`
+    /// complete the current chunk, or `None` if all of it belongs to the current chunk and the
+    /// chunk continues. After a boundary is returned, the next call starts a new chunk.
+    pub fn next_boundary(&mut self, data: &[u8]) -> Option<usize> {
+        for (i, byte) in data.iter().enumerate() {
+            self.hash = (self.hash << 1).wrapping_add(GEAR[*byte as usize]);
+            self.len += 1;
+
+            let is_boundary = self.len >= MAX_CHUNK_SIZE
+                || (self.len >= MIN_CHUNK_SIZE && self.hash & BOUNDARY_MASK == 0);
+            if is_boundary {
+                *self = Self::default();
+                return Some(i + 1);
+            }
+        }
+        None
+    }
+}
+
+/// splits `data` into chunks, for when the whole document is already in memory
+pub fn split(data: &[u8]) -> Vec<&[u8]> {
+    let mut chunker = Chunker::default();
+    let mut chunks = vec![];
+    let mut rest = data;
+    while let Some(boundary) = chunker.next_boundary(rest) {
+        let (chunk, remaining) = rest.split_at(boundary);
+        chunks.push(chunk);
+        rest = remaining;
+    }
+    if !rest.is_empty() {
+        chunks.push(rest);
+    }
+    chunks
+}
+
+/// random values for each byte, fixed so every client finds the same boundaries
+const GEAR: [u64; 256] = {
+    let mut table = [0u64; 256];
+    let mut state: u64 = 0x6c6f_636b_626f_6f6b; // "lockbook"
+    let mut i = 0;
+    while i < 256 {
+        // splitmix64
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+};
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn pseudo_random(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn chunk_sizes_bounded() {
+        let data = pseudo_random(20 * 1024 * 1024, 1);
+        let chunks = split(&data);
+
+        assert_eq!(chunks.concat(), data);
+        for chunk in &chunks[..chunks.len() - 1] {
+            assert!(chunk.len() >= MIN_CHUNK_SIZE);
+            assert!(chunk.len() <= MAX_CHUNK_SIZE);
+        }
+    }
+
+    #[test]
+    fn edit_preserves_later_chunks() {
+        let data = pseudo_random(20 * 1024 * 1024, 2);
+        let mut edited = data.clone();
+        edited.splice(1000..1000, b"an insertion near the start".iter().copied());
+
+        let chunks = split(&data);
+        let edited_chunks = split(&edited);
+
+        assert_eq!(chunks[1..], edited_chunks[1..]);
+    }
+
+    #[test]
+    fn manifest_round_trip() {
+        let manifest =
+            ChunkManifest { key: [3; 32], chunks: vec![ChunkRef { hmac: [7; 32], size: 42 }] };
+        let bytes = manifest.to_bytes().unwrap();
+
+        assert_eq!(ChunkManifest::from_bytes(&bytes).unwrap(), manifest);
+    }
+}
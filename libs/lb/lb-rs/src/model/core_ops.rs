@@ -4,7 +4,7 @@ use crate::model::crypto::{AESKey, DecryptedDocument, EncryptedDocument};
 use crate::model::errors::{LbErrKind, LbResult};
 use crate::model::file::{File, Share, ShareMode};
 use crate::model::file_metadata::{DocumentHmac, FileType, Owner};
-use crate::model::lazy::LazyTree;
+use crate::model::lazy::{LazyTree, ValidationFailure};
 use crate::model::meta::Meta;
 use crate::model::secret_filename::{HmacSha256, SecretFileName};
 use crate::model::staged::{StagedTree, StagedTreeLike};
@@ -14,6 +14,7 @@ use crate::service::keychain::Keychain;
 use db_rs::LookupTable;
 use hmac::{Mac, NewMac};
 use libsecp256k1::PublicKey;
+use std::collections::HashMap;
 use tracing::debug;
 use uuid::Uuid;
 
@@ -177,7 +178,7 @@ where
         let owner = self.find(new_parent)?.owner();
         file.set_owner(owner);
         file.set_parent(*new_parent);
-        file.set_folder_access_keys(symkey::encrypt(&parent_key, &key)?);
+        file.set_folder_access_keys(symkey::encrypt_key(&parent_key, &key)?);
         file.set_name(SecretFileName::from_str(&self.name(id, keychain)?, &key, &parent_key)?);
         let file = file.sign(keychain)?;
 
@@ -203,6 +204,36 @@ where
         Ok(file)
     }
 
+    pub fn undelete_op(&self, id: &Uuid, keychain: &Keychain) -> LbResult<SignedMeta> {
+        let mut file = self.find(id)?.timestamped_value.value.clone();
+
+        file.set_deleted(false);
+        let file = file.sign(keychain)?;
+
+        Ok(file)
+    }
+
+    /// Un-deletes a file, moving it to `root` if its parent is deleted. Descendants that were
+    /// deleted along with it come back too; descendants deleted individually stay deleted.
+    pub fn restore_op(
+        &mut self, id: &Uuid, root: &Uuid, keychain: &Keychain,
+    ) -> LbResult<Vec<SignedMeta>> {
+        let parent = *self.find(id)?.parent();
+        let parent_deleted =
+            self.maybe_find(&parent).is_some() && self.calculate_deleted(&parent)?;
+        let mut result = if parent_deleted {
+            self.move_op(id, root, keychain)?
+        } else {
+            vec![self.find(id)?.clone()]
+        };
+
+        let mut file = result[0].timestamped_value.value.clone();
+        file.set_deleted(false);
+        result[0] = file.sign(keychain)?;
+
+        Ok(result)
+    }
+
     pub fn add_share_op(
         &mut self, id: Uuid, sharee: Owner, mode: ShareMode, keychain: &Keychain,
     ) -> LbResult<SignedMeta> {
@@ -284,6 +315,136 @@ where
         Ok(result)
     }
 
+    /// Gives `id` and its descendants new keys, so that users who have had access can't use the
+    /// keys they had to read anything written from now on. Names and the content of `documents`,
+    /// which must hold the current content of each document in the subtree, are re-encrypted
+    /// with the new keys, and shares are re-issued with them; the share of `revoked`, if any, is
+    /// deleted instead. Deleted files keep their keys, which are re-encrypted for their new
+    /// parent key.
+    pub fn rotate_keys_op(
+        &mut self, id: &Uuid, revoked: Option<PublicKey>,
+        documents: &HashMap<Uuid, (DocumentHmac, DecryptedDocument)>, keychain: &Keychain,
+    ) -> LbResult<(Vec<SignedMeta>, Vec<(Uuid, DocumentHmac, EncryptedDocument)>)> {
+        let account = keychain.get_account()?;
+        let pk = keychain.get_pk()?;
+        let file = self.find(id)?;
+        validate::not_root(file)?;
+        if file.owner().0 != pk {
+            return Err(LbErrKind::InsufficientPermission.into());
+        }
+        if let Some(revoked) = revoked {
+            if !file
+                .user_access_keys()
+                .iter()
+                .any(|k| !k.deleted && k.encrypted_for == revoked)
+            {
+                return Err(LbErrKind::ShareNonexistent.into());
+            }
+        }
+
+        let parent = *file.parent();
+
+        let mut metas = vec![];
+        let mut encrypted_documents = vec![];
+        let parent_key = self.decrypt_key(&parent, keychain)?;
+        let mut to_rotate = vec![(*id, parent_key)];
+        while let Some((id, parent_key)) = to_rotate.pop() {
+            let name = self.name(&id, keychain)?;
+            let mut file = self.find(&id)?.timestamped_value.value.clone();
+
+            // deleted files and their descendants keep their keys, so they stay readable if
+            // they're restored
+            let key = if file.explicitly_deleted() {
+                self.decrypt_key(&id, keychain)?
+            } else {
+                let key = symkey::generate_key();
+                for access in file.user_access_keys_mut() {
+                    if access.deleted {
+                        continue;
+                    }
+                    if Some(access.encrypted_for) == revoked {
+                        access.deleted = true;
+                    } else {
+                        *access = UserAccessInfo::encrypt(
+                            account,
+                            &pk,
+                            &access.encrypted_for,
+                            &key,
+                            access.mode,
+                        )?;
+                    }
+                }
+                if file.document_hmac().is_some() {
+                    let (hmac, content) = documents.get(&id).ok_or_else(|| {
+                        LbErrKind::Unexpected(format!("content of {id} not provided for rotation"))
+                    })?;
+                    if Some(hmac) != file.document_hmac() {
+                        return Err(LbErrKind::ReReadRequired.into());
+                    }
+                    let hmac = {
+                        let mut mac = HmacSha256::new_from_slice(&key).map_err(|err| {
+                            LbErrKind::Unexpected(format!("hmac creation error: {err:?}"))
+                        })?;
+                        mac.update(content);
+                        mac.finalize().into_bytes()
+                    }
+                    .into();
+                    let content = compression_service::compress(content)?;
+                    let content = symkey::encrypt(&key, &content)?;
+                    file.set_hmac_and_size(Some(hmac), Some(content.value.len()));
+                    encrypted_documents.push((id, hmac, content));
+                }
+                for child in self.children(&id)? {
+                    to_rotate.push((child, key));
+                }
+                key
+            };
+
+            file.set_folder_access_keys(symkey::encrypt_key(&parent_key, &key)?);
+            file.set_name(SecretFileName::from_str(&name, &key, &parent_key)?);
+            metas.push(file.sign(keychain)?);
+        }
+
+        Ok((metas, encrypted_documents))
+    }
+
+    /// Gives `id` the `key` a rotation chose for it elsewhere, re-encrypting its name and shares
+    /// with it. The shares of `revoked` keep the key they had, to be deleted with other share
+    /// changes. Its children keep their keys, which are re-encrypted for it.
+    pub fn rekey_op(
+        &mut self, id: &Uuid, key: AESKey, revoked: &[PublicKey], keychain: &Keychain,
+    ) -> LbResult<Vec<SignedMeta>> {
+        let account = keychain.get_account()?;
+        let pk = keychain.get_pk()?;
+        let parent = *self.find(id)?.parent();
+        let parent_key = self.decrypt_key(&parent, keychain)?;
+        let name = self.name(id, keychain)?;
+        let children = self.children(id)?;
+
+        let mut file = self.find(id)?.timestamped_value.value.clone();
+        for access in file.user_access_keys_mut() {
+            if access.deleted || revoked.contains(&access.encrypted_for) {
+                continue;
+            }
+            *access =
+                UserAccessInfo::encrypt(account, &pk, &access.encrypted_for, &key, access.mode)?;
+        }
+        file.set_folder_access_keys(symkey::encrypt_key(&parent_key, &key)?);
+        file.set_name(SecretFileName::from_str(&name, &key, &parent_key)?);
+        let mut result = vec![file.sign(keychain)?];
+
+        for child in children {
+            let child_key = self.decrypt_key(&child, keychain)?;
+            let child_name = self.name(&child, keychain)?;
+            let mut child = self.find(&child)?.timestamped_value.value.clone();
+            child.set_folder_access_keys(symkey::encrypt_key(&key, &child_key)?);
+            child.set_name(SecretFileName::from_str(&child_name, &child_key, &key)?);
+            result.push(child.sign(keychain)?);
+        }
+
+        Ok(result)
+    }
+
     pub fn decrypt_document(
         &mut self, id: &Uuid, doc: &EncryptedDocument, keychain: &Keychain,
     ) -> LbResult<DecryptedDocument> {
@@ -358,7 +519,7 @@ where
         }
 
         let (op, id) = self.create_op(id, key, parent, name, file_type, keychain)?;
-        self.stage_validate_and_promote(Some(op), Owner(keychain.get_pk()?))?;
+        self.stage_validate_and_promote(Some(op), Owner(keychain.get_pk()?), &keychain.groups()?)?;
         Ok(id)
     }
 
@@ -372,7 +533,7 @@ where
 
     pub fn rename(&mut self, id: &Uuid, name: &str, keychain: &Keychain) -> LbResult<()> {
         let op = self.rename_op(id, name, keychain)?;
-        self.stage_validate_and_promote(Some(op), Owner(keychain.get_pk()?))?;
+        self.stage_validate_and_promote(Some(op), Owner(keychain.get_pk()?), &keychain.groups()?)?;
         Ok(())
     }
 
@@ -388,8 +549,20 @@ where
         if self.maybe_find(new_parent).is_none() || self.calculate_deleted(new_parent)? {
             return Err(LbErrKind::FileParentNonexistent.into());
         }
+        // deleted files leave the trash by being restored
+        if self.calculate_deleted(id)? {
+            return Err(LbErrKind::Validation(ValidationFailure::DeletedFileUpdated(*id)).into());
+        }
         let op = self.move_op(id, new_parent, keychain)?;
-        self.stage_validate_and_promote(op, Owner(keychain.get_pk()?))?;
+        self.stage_validate_and_promote(op, Owner(keychain.get_pk()?), &keychain.groups()?)?;
+        Ok(())
+    }
+
+    pub fn rekey_unvalidated(
+        &mut self, id: &Uuid, key: AESKey, revoked: &[PublicKey], keychain: &Keychain,
+    ) -> LbResult<()> {
+        let op = self.rekey_op(id, key, revoked, keychain)?;
+        self.stage_and_promote(op)?;
         Ok(())
     }
 
@@ -401,7 +574,27 @@ where
 
     pub fn delete(&mut self, id: &Uuid, keychain: &Keychain) -> LbResult<()> {
         let op = self.delete_op(id, keychain)?;
-        self.stage_validate_and_promote(Some(op), Owner(keychain.get_pk()?))?;
+        self.stage_validate_and_promote(Some(op), Owner(keychain.get_pk()?), &keychain.groups()?)?;
+        Ok(())
+    }
+
+    pub fn undelete_unvalidated(&mut self, id: &Uuid, keychain: &Keychain) -> LbResult<()> {
+        let op = self.undelete_op(id, keychain)?;
+        self.stage_and_promote(Some(op))?;
+        Ok(())
+    }
+
+    pub fn restore_unvalidated(
+        &mut self, id: &Uuid, root: &Uuid, keychain: &Keychain,
+    ) -> LbResult<()> {
+        let op = self.restore_op(id, root, keychain)?;
+        self.stage_and_promote(op)?;
+        Ok(())
+    }
+
+    pub fn restore(&mut self, id: &Uuid, root: &Uuid, keychain: &Keychain) -> LbResult<()> {
+        let op = self.restore_op(id, root, keychain)?;
+        self.stage_validate_and_promote(op, Owner(keychain.get_pk()?), &keychain.groups()?)?;
         Ok(())
     }
 
@@ -417,7 +610,7 @@ where
         &mut self, id: Uuid, sharee: Owner, mode: ShareMode, keychain: &Keychain,
     ) -> LbResult<()> {
         let op = self.add_share_op(id, sharee, mode, keychain)?;
-        self.stage_validate_and_promote(Some(op), Owner(keychain.get_pk()?))?;
+        self.stage_validate_and_promote(Some(op), Owner(keychain.get_pk()?), &keychain.groups()?)?;
         Ok(())
     }
 
@@ -433,7 +626,7 @@ where
         &mut self, id: &Uuid, maybe_encrypted_for: Option<PublicKey>, keychain: &Keychain,
     ) -> LbResult<()> {
         let op = self.delete_share_op(id, maybe_encrypted_for, keychain)?;
-        self.stage_validate_and_promote(op, Owner(keychain.get_pk()?))?;
+        self.stage_validate_and_promote(op, Owner(keychain.get_pk()?), &keychain.groups()?)?;
         Ok(())
     }
 
@@ -458,7 +651,7 @@ where
         &mut self, id: &Uuid, document: &[u8], keychain: &Keychain,
     ) -> LbResult<EncryptedDocument> {
         let (op, document) = self.update_document_op(id, document, keychain)?;
-        self.stage_validate_and_promote(Some(op), Owner(keychain.get_pk()?))?;
+        self.stage_validate_and_promote(Some(op), Owner(keychain.get_pk()?), &keychain.groups()?)?;
         Ok(document)
     }
 
@@ -467,7 +660,7 @@ where
         keychain: &Keychain,
     ) -> LbResult<()> {
         let op = self.overwrite_document_hmac_op(id, new_hmac, new_size, keychain)?;
-        self.stage_validate_and_promote(Some(op), Owner(keychain.get_pk()?))?;
+        self.stage_validate_and_promote(Some(op), Owner(keychain.get_pk()?), &keychain.groups()?)?;
         Ok(())
     }
 }
@@ -0,0 +1,109 @@
+//! Small edits to large text documents are uploaded as a [DocumentDelta]: the edit's replacements
+//! against the version the server already has. The server can't read document content, so it
+//! can't apply the delta itself; it stores the delta in place of the new version, keeps the base
+//! it refers to, and clients apply the delta when they read the document.
+//!
+//! Whether content is a delta is recorded alongside it, as a [super::api::ContentFormat], rather
+//! than read from the content.
+
+use serde::{Deserialize, Serialize};
+
+use super::errors::{LbErrKind, LbResult, Unexpected};
+use super::file_metadata::DocumentHmac;
+use super::text;
+use super::text::unicode_segs;
+
+/// how many deltas may be stacked on top of a complete version before the server asks for the
+/// document to be uploaded in full, which bounds the work of reading a document
+pub const MAX_DELTA_CHAIN: usize = 16;
+
+/// documents smaller than this are always uploaded in full
+pub const MIN_DELTA_DOCUMENT_SIZE: usize = 64 * 1024;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct DocumentDelta {
+    /// hmac of the version the delta applies to
+    pub base: DocumentHmac,
+    /// non-overlapping replacements in the order they appear in the base
+    pub ops: Vec<DeltaOp>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct DeltaOp {
+    /// byte range of the base that's replaced
+    pub range: (usize, usize),
+    pub text: String,
+}
+
+impl DocumentDelta {
+    pub fn new(base_hmac: DocumentHmac, base: &str, new: &str) -> Self {
+        let segs = unicode_segs::calc(base);
+        let ops = text::diff(base, new)
+            .into_iter()
+            .map(|replace| {
+                let (start, end) = segs.range_to_byte(replace.range);
+                DeltaOp { range: (start.0, end.0), text: replace.text }
+            })
+            .collect();
+        Self { base: base_hmac, ops }
+    }
+
+    pub fn apply(&self, base: &[u8]) -> LbResult<Vec<u8>> {
+        let mut result = Vec::with_capacity(base.len());
+        let mut copied = 0;
+        for op in &self.ops {
+            let (start, end) = op.range;
+            if start < copied || end < start || end > base.len() {
+                return Err(LbErrKind::Unexpected(format!(
+                    "delta op {:?} out of order or out of bounds",
+                    op.range
+                ))
+                .into());
+            }
+            result.extend_from_slice(&base[copied..start]);
+            result.extend_from_slice(op.text.as_bytes());
+            copied = end;
+        }
+        result.extend_from_slice(&base[copied..]);
+        Ok(result)
+    }
+
+    pub fn to_bytes(&self) -> LbResult<Vec<u8>> {
+        bincode::serialize(self).map_unexpected()
+    }
+
+    pub fn from_bytes(content: &[u8]) -> LbResult<Self> {
+        bincode::deserialize(content).map_unexpected()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn apply_reproduces_edit() {
+        let base = "# journal\n\nmonday: 🌧️ rain\ntuesday: sun\n";
+        let new = "# journal\n\nmonday: 🌧️ rain all day\ntuesday: sun\nwednesday: ☁️\n";
+
+        let delta = DocumentDelta::new([1; 32], base, new);
+        assert_eq!(delta.apply(base.as_bytes()).unwrap(), new.as_bytes());
+    }
+
+    #[test]
+    fn apply_rejects_out_of_bounds() {
+        let delta = DocumentDelta {
+            base: [1; 32],
+            ops: vec![DeltaOp { range: (4, 40), text: "x".to_string() }],
+        };
+        assert!(delta.apply(b"short").is_err());
+    }
+
+    #[test]
+    fn delta_round_trip() {
+        let delta = DocumentDelta::new([1; 32], "hello world", "hello rust");
+        let bytes = delta.to_bytes().unwrap();
+
+        assert_eq!(DocumentDelta::from_bytes(&bytes).unwrap(), delta);
+    }
+}
@@ -85,7 +85,9 @@ impl Display for LbErrKind {
             LbErrKind::FileNameEmpty => write!(f, "A file name cannot be empty"),
             LbErrKind::FileNonexistent => write!(f, "That file does not exist"),
             LbErrKind::FileNotDocument => write!(f, "That file is not a document"),
+            LbErrKind::FileNotFolder => write!(f, "That file is not a folder"),
             LbErrKind::FileParentNonexistent => write!(f, "Could not find that file parent"),
+            LbErrKind::GroupNonexistent => write!(f, "That group does not exist"),
             LbErrKind::InsufficientPermission => {
                 write!(f, "You don't have the permission to do that")
             }
@@ -93,6 +95,9 @@ impl Display for LbErrKind {
             LbErrKind::InvalidAuthDetails => {
                 write!(f, "Our server failed to authenticate your request, please try again")
             }
+            LbErrKind::InviteRequired => {
+                write!(f, "This server needs a valid invite code to create an account")
+            }
             LbErrKind::KeyPhraseInvalid => {
                 write!(f, "Your private key phrase is wrong")
             }
@@ -315,10 +320,13 @@ pub enum LbErrKind {
     FileNameEmpty,
     FileNonexistent,
     FileNotDocument,
+    FileNotFolder,
     FileParentNonexistent,
+    GroupNonexistent,
     InsufficientPermission,
     InvalidPurchaseToken,
     InvalidAuthDetails,
+    InviteRequired,
     KeyPhraseInvalid,
     NotPremium,
     UsageIsOverDataCap,
@@ -432,6 +440,7 @@ impl From<ApiError<api::NewAccountError>> for LbErr {
             ApiError::Endpoint(api::NewAccountError::UsernameTaken) => LbErrKind::UsernameTaken,
             ApiError::Endpoint(api::NewAccountError::InvalidUsername) => LbErrKind::UsernameInvalid,
             ApiError::Endpoint(api::NewAccountError::Disabled) => LbErrKind::ServerDisabled,
+            ApiError::Endpoint(api::NewAccountError::InviteRequired) => LbErrKind::InviteRequired,
             e => core_err_unexpected(e),
         }
         .into()
@@ -488,6 +497,17 @@ impl From<ApiError<api::GetUpdatesError>> for LbErr {
     }
 }
 
+impl From<ApiError<api::WaitForUpdatesError>> for LbErr {
+    fn from(e: ApiError<api::WaitForUpdatesError>) -> Self {
+        match e {
+            ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+            ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
+            e => core_err_unexpected(e),
+        }
+        .into()
+    }
+}
+
 impl From<ApiError<api::GetDocumentError>> for LbErr {
     fn from(e: ApiError<api::GetDocumentError>) -> Self {
         match e {
@@ -499,6 +519,184 @@ impl From<ApiError<api::GetDocumentError>> for LbErr {
     }
 }
 
+impl From<ApiError<api::GetDocVersionsError>> for LbErr {
+    fn from(e: ApiError<api::GetDocVersionsError>) -> Self {
+        match e {
+            ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+            ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
+            ApiError::Endpoint(api::GetDocVersionsError::DocumentNotFound) => {
+                LbErrKind::FileNonexistent
+            }
+            ApiError::Endpoint(api::GetDocVersionsError::NotPermissioned) => {
+                LbErrKind::InsufficientPermission
+            }
+            e => core_err_unexpected(e),
+        }
+        .into()
+    }
+}
+
+impl From<ApiError<api::DeclareChunksError>> for LbErr {
+    fn from(e: ApiError<api::DeclareChunksError>) -> Self {
+        match e {
+            ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+            ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
+            e => core_err_unexpected(e),
+        }
+        .into()
+    }
+}
+
+impl From<ApiError<api::UpsertChunkError>> for LbErr {
+    fn from(e: ApiError<api::UpsertChunkError>) -> Self {
+        match e {
+            ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+            ApiError::Endpoint(api::UpsertChunkError::UsageIsOverDataCap) => {
+                LbErrKind::UsageIsOverDataCap
+            }
+            ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
+            e => core_err_unexpected(e),
+        }
+        .into()
+    }
+}
+
+impl From<ApiError<api::GetChunkError>> for LbErr {
+    fn from(e: ApiError<api::GetChunkError>) -> Self {
+        match e {
+            ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+            ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
+            e => core_err_unexpected(e),
+        }
+        .into()
+    }
+}
+
+impl From<ApiError<api::CreatePublicLinkError>> for LbErr {
+    fn from(e: ApiError<api::CreatePublicLinkError>) -> Self {
+        match e {
+            ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+            ApiError::Endpoint(api::CreatePublicLinkError::FileNotFound) => {
+                LbErrKind::FileNonexistent
+            }
+            ApiError::Endpoint(api::CreatePublicLinkError::NotPermissioned) => {
+                LbErrKind::InsufficientPermission
+            }
+            ApiError::Endpoint(api::CreatePublicLinkError::UsageIsOverDataCap) => {
+                LbErrKind::UsageIsOverDataCap
+            }
+            ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
+            e => core_err_unexpected(e),
+        }
+        .into()
+    }
+}
+
+impl From<ApiError<api::ListPublicLinksError>> for LbErr {
+    fn from(e: ApiError<api::ListPublicLinksError>) -> Self {
+        match e {
+            ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+            ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
+            e => core_err_unexpected(e),
+        }
+        .into()
+    }
+}
+
+impl From<ApiError<api::RevokePublicLinkError>> for LbErr {
+    fn from(e: ApiError<api::RevokePublicLinkError>) -> Self {
+        match e {
+            ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+            ApiError::Endpoint(api::RevokePublicLinkError::LinkNotFound) => {
+                LbErrKind::ShareNonexistent
+            }
+            ApiError::Endpoint(api::RevokePublicLinkError::NotPermissioned) => {
+                LbErrKind::InsufficientPermission
+            }
+            ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
+            e => core_err_unexpected(e),
+        }
+        .into()
+    }
+}
+
+impl From<ApiError<api::CreateGroupError>> for LbErr {
+    fn from(e: ApiError<api::CreateGroupError>) -> Self {
+        match e {
+            ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+            ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
+            e => core_err_unexpected(e),
+        }
+        .into()
+    }
+}
+
+impl From<ApiError<api::AddGroupMemberError>> for LbErr {
+    fn from(e: ApiError<api::AddGroupMemberError>) -> Self {
+        match e {
+            ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+            ApiError::Endpoint(api::AddGroupMemberError::GroupNotFound) => {
+                LbErrKind::GroupNonexistent
+            }
+            ApiError::Endpoint(api::AddGroupMemberError::NotPermissioned) => {
+                LbErrKind::InsufficientPermission
+            }
+            ApiError::Endpoint(api::AddGroupMemberError::UserNotFound) => {
+                LbErrKind::UsernameNotFound
+            }
+            ApiError::Endpoint(api::AddGroupMemberError::AlreadyMember) => {
+                LbErrKind::ShareAlreadyExists
+            }
+            ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
+            e => core_err_unexpected(e),
+        }
+        .into()
+    }
+}
+
+impl From<ApiError<api::RemoveGroupMemberError>> for LbErr {
+    fn from(e: ApiError<api::RemoveGroupMemberError>) -> Self {
+        match e {
+            ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+            ApiError::Endpoint(api::RemoveGroupMemberError::GroupNotFound) => {
+                LbErrKind::GroupNonexistent
+            }
+            ApiError::Endpoint(api::RemoveGroupMemberError::NotPermissioned)
+            | ApiError::Endpoint(api::RemoveGroupMemberError::CannotRemoveOwner) => {
+                LbErrKind::InsufficientPermission
+            }
+            ApiError::Endpoint(api::RemoveGroupMemberError::NotMember) => {
+                LbErrKind::ShareNonexistent
+            }
+            ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
+            e => core_err_unexpected(e),
+        }
+        .into()
+    }
+}
+
+impl From<ApiError<api::GetGroupsError>> for LbErr {
+    fn from(e: ApiError<api::GetGroupsError>) -> Self {
+        match e {
+            ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+            ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
+            e => core_err_unexpected(e),
+        }
+        .into()
+    }
+}
+
+impl From<ApiError<api::EmptyTrashError>> for LbErr {
+    fn from(e: ApiError<api::EmptyTrashError>) -> Self {
+        match e {
+            ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+            ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
+            e => core_err_unexpected(e),
+        }
+        .into()
+    }
+}
+
 impl From<ApiError<api::UpsertError>> for LbErr {
     fn from(e: ApiError<api::UpsertError>) -> Self {
         match e {
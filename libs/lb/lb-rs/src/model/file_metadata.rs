@@ -53,7 +53,7 @@ impl FileMetadata {
                 &key,
                 UserAccessMode::Write,
             )?],
-            folder_access_key: symkey::encrypt(&key, &key)?,
+            folder_access_key: symkey::encrypt_key(&key, &key)?,
         })
     }
 
@@ -70,7 +70,7 @@ impl FileMetadata {
             is_deleted: false,
             document_hmac: None,
             user_access_keys: Default::default(),
-            folder_access_key: symkey::encrypt(parent_key, &key)?,
+            folder_access_key: symkey::encrypt_key(parent_key, &key)?,
         })
     }
 
@@ -161,6 +161,7 @@ impl<F: FileLike> Debug for FileDiff<F> {
                 Diff::Deleted => result.field("new_deleted", &self.new.explicitly_deleted()),
                 Diff::Hmac => result.field("new_hmac", &self.new.document_hmac()),
                 Diff::UserKeys => result.field("new_user_keys", &true),
+                Diff::FolderKey => result.field("new_folder_key", &true),
             };
         }
         result.finish()
@@ -176,6 +177,7 @@ pub enum Diff {
     Deleted,
     Hmac,
     UserKeys,
+    FolderKey,
 }
 
 impl<F: FileLike> FileDiff<F> {
@@ -215,6 +217,10 @@ impl<F: FileLike> FileDiff<F> {
                     changes.push(UserKeys);
                 }
 
+                if old.folder_access_key() != new.folder_access_key() {
+                    changes.push(FolderKey);
+                }
+
                 changes
             }
         }
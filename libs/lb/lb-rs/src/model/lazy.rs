@@ -36,12 +36,22 @@ impl<T: TreeLike> LazyTree<T> {
 
 impl<T: TreeLike> LazyTree<T> {
     pub fn access_mode(&self, owner: Owner, id: &Uuid) -> LbResult<Option<UserAccessMode>> {
+        self.access_mode_in_groups(owner, &[], id)
+    }
+
+    /// the access `owner` has to `id`, whether it's shared with them or with any of `groups`
+    pub fn access_mode_in_groups(
+        &self, owner: Owner, groups: &[Owner], id: &Uuid,
+    ) -> LbResult<Option<UserAccessMode>> {
         let mut file = self.find(id)?;
         let mut max_access_mode = None;
         let mut visited_ids = vec![];
         while !visited_ids.contains(file.id()) {
             visited_ids.push(*file.id());
-            let access_mode = file.access_mode(&owner);
+            let access_mode = groups
+                .iter()
+                .map(|group| file.access_mode(group))
+                .fold(file.access_mode(&owner), Option::max);
             if access_mode > max_access_mode {
                 max_access_mode = access_mode;
             }
@@ -158,51 +168,46 @@ impl<T: TreeLike> LazyTree<T> {
         let mut file_id = *self.find(id)?.id();
         let mut visited_ids = vec![];
 
-        loop {
-            if keychain.contains_aes_key(&file_id)? {
-                break;
+        let mut key = loop {
+            let file = self.find(&file_id)?;
+            if let Some(key) = keychain.get_aes_key(&file_id, file.folder_access_key())? {
+                break key;
             }
 
             let my_pk = keychain.get_pk()?;
 
-            let maybe_file_key = if let Some(user_access) = self
-                .find(&file_id)?
+            let maybe_file_key = if let Some(user_access) = file
                 .user_access_keys()
                 .iter()
                 .find(|access| access.encrypted_for == my_pk)
             {
                 Some(user_access.decrypt(keychain.get_account()?)?)
             } else {
-                None
+                let mut group_key = None;
+                for user_access in file.user_access_keys() {
+                    if let Some(private_key) = keychain.get_group_key(&user_access.encrypted_for)? {
+                        group_key = Some(user_access.decrypt_with(&private_key)?);
+                        break;
+                    }
+                }
+                group_key
             };
             if let Some(file_key) = maybe_file_key {
-                keychain.insert_aes_key(file_id, file_key)?;
-                break;
+                keychain.insert_aes_key(file_id, file.folder_access_key(), file_key)?;
+                break file_key;
             }
 
             visited_ids.push(file_id);
-            file_id = *self.find_parent(self.find(&file_id)?)?.id();
-        }
+            file_id = *self.find_parent(file)?.id();
+        };
 
         for id in visited_ids.iter().rev() {
-            let decrypted_key = {
-                let file = self.find(id)?;
-                let parent = self.find_parent(file)?;
-                let parent_key =
-                    keychain
-                        .get_aes_key(parent.id())?
-                        .ok_or(LbErrKind::Unexpected(
-                            "parent key should have been populated by prior routine".to_string(),
-                        ))?;
-                let encrypted_key = file.folder_access_key();
-                symkey::decrypt(&parent_key, encrypted_key)?
-            };
-            keychain.insert_aes_key(*id, decrypted_key)?;
+            let encrypted_key = self.find(id)?.folder_access_key();
+            key = symkey::decrypt(&key, encrypted_key)?;
+            keychain.insert_aes_key(*id, encrypted_key, key)?;
         }
 
-        Ok(keychain.get_aes_key(id)?.ok_or(LbErrKind::Unexpected(
-            "parent key should have been populated by prior routine (2)".to_string(),
-        ))?)
+        Ok(key)
     }
 
     pub fn name(&mut self, id: &Uuid, keychain: &Keychain) -> LbResult<String> {
@@ -341,14 +346,18 @@ impl<T: TreeLike> LazyTree<T> {
     pub fn pending_roots(&mut self, keychain: &Keychain) -> LbResult<Vec<Uuid>> {
         let mut result = Vec::new();
         let owner = Owner(keychain.get_pk()?);
+        let groups = keychain.groups()?;
         for id in self.ids() {
             // file must be owned by another user
             if self.find(&id)?.owner() == owner {
                 continue;
             }
 
-            // file must be shared with this user
-            if self.find(&id)?.access_mode(&owner).is_none() {
+            // file must be shared with this user or a group they're in
+            let file = self.find(&id)?;
+            if file.access_mode(&owner).is_none()
+                && groups.iter().all(|group| file.access_mode(group).is_none())
+            {
                 continue;
             }
 
@@ -482,11 +491,11 @@ where
     }
 
     pub fn stage_validate_and_promote<S: TreeLikeMut<F = T::F>>(
-        &mut self, mut staged: S, owner: Owner,
+        &mut self, mut staged: S, owner: Owner, groups: &[Owner],
     ) -> LbResult<()> {
         StagedTree::new(&self.tree, &mut staged)
             .to_lazy()
-            .validate(owner)?;
+            .validate_in_groups(owner, groups)?;
         self.stage_and_promote(staged)?;
         Ok(())
     }
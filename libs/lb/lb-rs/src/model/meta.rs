@@ -52,7 +52,7 @@ impl Meta {
                 &key,
                 UserAccessMode::Write,
             )?],
-            folder_access_key: symkey::encrypt(&key, &key)?,
+            folder_access_key: symkey::encrypt_key(&key, &key)?,
         })
     }
 
@@ -70,7 +70,7 @@ impl Meta {
             doc_hmac: None,
             doc_size: None,
             user_access_keys: Default::default(),
-            folder_access_key: symkey::encrypt(parent_key, &key)?,
+            folder_access_key: symkey::encrypt_key(parent_key, &key)?,
         })
     }
 
@@ -161,8 +161,7 @@ impl PartialEq for Meta {
                     is_deleted,
                     doc_hmac,
                     user_access_keys,
-                    // todo: verify that ignoring this is intentional
-                    folder_access_key: _,
+                    folder_access_key,
                     doc_size,
                 },
                 Meta::V1 {
@@ -174,8 +173,7 @@ impl PartialEq for Meta {
                     is_deleted: other_is_deleted,
                     doc_hmac: other_doc_hmac,
                     user_access_keys: other_user_access_keys,
-                    // todo: verify that ignoring this is intentional
-                    folder_access_key: _other_folder_access_key,
+                    folder_access_key: other_folder_access_key,
                     doc_size: other_doc_size,
                 },
             ) => {
@@ -187,6 +185,8 @@ impl PartialEq for Meta {
                     && is_deleted == other_is_deleted
                     && doc_hmac == other_doc_hmac
                     && user_access_keys == other_user_access_keys
+                    // a key encrypted for the same parent is always the same; see symkey::encrypt_key
+                    && folder_access_key == other_folder_access_key
                     && doc_size == other_doc_size
             }
         }
@@ -9,12 +9,14 @@ pub mod access_info;
 pub mod account;
 pub mod api;
 pub mod chat;
+pub mod chunks;
 pub mod clock;
 pub mod compression_service;
 pub mod core_config;
 pub mod core_ops;
 pub mod core_tree;
 pub mod crypto;
+pub mod delta;
 pub mod errors;
 pub mod feature_flag;
 pub mod file;
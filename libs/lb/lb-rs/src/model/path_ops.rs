@@ -178,13 +178,13 @@ where
                         }
                         FileType::Folder => child,
                         FileType::Link { target } => {
-                            let current = self.find(&target)?;
-                            if current.access_mode(&Owner(keychain.get_pk()?))
+                            let owner = Owner(keychain.get_pk()?);
+                            if self.access_mode_in_groups(owner, &keychain.groups()?, &target)?
                                 < Some(UserAccessMode::Write)
                             {
                                 return Err(LbErrKind::InsufficientPermission.into());
                             }
-                            *current.id()
+                            target
                         }
                     };
                     continue 'path;
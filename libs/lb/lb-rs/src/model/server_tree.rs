@@ -33,18 +33,39 @@ impl<'a> ServerTree<'a> {
                 }
             };
 
-        let mut ids = HashSet::new();
-        ids.extend(owned_ids);
-        ids.extend(shared_ids.clone());
+        let mut tree = Self { ids: owned_ids, owned_files, shared_files, file_children, files };
+        tree.extend_shared(shared_ids);
+        Ok(tree)
+    }
+
+    /// includes the files shared with any of `groups`, which the tree's owner is a member of
+    pub fn with_groups(mut self, groups: &[Owner]) -> Self {
+        for group in groups {
+            let shared_ids = self
+                .shared_files
+                .get()
+                .get(group)
+                .cloned()
+                .unwrap_or_default();
+            self.extend_shared(shared_ids);
+        }
+        self
+    }
+
+    fn extend_shared(&mut self, shared_ids: HashSet<Uuid>) {
+        self.ids.extend(shared_ids.clone());
 
         let mut to_get_descendants = Vec::from_iter(shared_ids);
         while let Some(id) = to_get_descendants.pop() {
-            let children = file_children.get().get(&id).cloned().unwrap_or_default();
-            ids.extend(children.clone());
+            let children = self
+                .file_children
+                .get()
+                .get(&id)
+                .cloned()
+                .unwrap_or_default();
+            self.ids.extend(children.clone());
             to_get_descendants.extend(children);
         }
-
-        Ok(Self { ids, owned_files, shared_files, file_children, files })
     }
 }
 
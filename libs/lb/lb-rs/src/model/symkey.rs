@@ -1,7 +1,9 @@
 use crate::model::crypto::*;
+use crate::model::secret_filename::HmacSha256;
 use aes_gcm::Aes256Gcm;
 use aes_gcm::aead::generic_array::GenericArray;
 use aes_gcm::aead::{Aead, KeyInit, Payload};
+use hmac::{Mac, NewMac};
 use rand::RngCore;
 use rand::rngs::OsRng;
 use serde::Serialize;
@@ -17,9 +19,25 @@ pub fn generate_key() -> AESKey {
 
 pub fn encrypt<T: Serialize + DeserializeOwned>(
     key: &AESKey, to_encrypt: &T,
+) -> LbResult<AESEncrypted<T>> {
+    encrypt_with_nonce(key, to_encrypt, &generate_nonce())
+}
+
+/// Encrypts a file's `key` for its parent. The nonce is derived from both keys, so encrypting the
+/// same key for the same parent again (a file re-created by sync, or moved away and back) gives
+/// the same metadata, and only a rotated key compares as changed.
+pub fn encrypt_key(parent_key: &AESKey, key: &AESKey) -> LbResult<AESEncrypted<AESKey>> {
+    let mut mac = HmacSha256::new_from_slice(parent_key).map_unexpected()?;
+    mac.update(key);
+    let mut nonce = [0u8; 12];
+    nonce.copy_from_slice(&mac.finalize().into_bytes()[..12]);
+    encrypt_with_nonce(parent_key, key, &nonce)
+}
+
+fn encrypt_with_nonce<T: Serialize + DeserializeOwned>(
+    key: &AESKey, to_encrypt: &T, nonce: &[u8; 12],
 ) -> LbResult<AESEncrypted<T>> {
     let serialized = bincode::serialize(to_encrypt).map_unexpected()?;
-    let nonce = &generate_nonce();
     let encrypted = convert_key(key)
         .encrypt(GenericArray::from_slice(nonce), Payload { msg: &serialized, aad: &[] })
         .map_unexpected()?;
@@ -49,7 +67,7 @@ pub fn generate_nonce() -> [u8; 12] {
 mod unit_tests {
     use uuid::Uuid;
 
-    use crate::model::symkey::{decrypt, encrypt, generate_key};
+    use crate::model::symkey::{decrypt, encrypt, encrypt_key, generate_key};
 
     #[test]
     fn test_generate_encrypt_decrypt() {
@@ -59,4 +77,14 @@ mod unit_tests {
         let decrypted = decrypt(&key, &encrypted).unwrap();
         assert_eq!(test_value, decrypted)
     }
+
+    #[test]
+    fn test_encrypt_key_deterministic() {
+        let parent_key = generate_key();
+        let key = generate_key();
+        let encrypted = encrypt_key(&parent_key, &key).unwrap();
+        assert_eq!(encrypted, encrypt_key(&parent_key, &key).unwrap());
+        assert_ne!(encrypted, encrypt_key(&parent_key, &generate_key()).unwrap());
+        assert_eq!(key, decrypt(&parent_key, &encrypted).unwrap());
+    }
 }
@@ -51,11 +51,17 @@ where
     Local: TreeLike<F = T::F>,
 {
     pub fn validate(&mut self, owner: Owner) -> LbResult<()> {
+        self.validate_in_groups(owner, &[])
+    }
+
+    /// validates changes made by `owner`, who has the access of each of `groups` in addition to
+    /// their own
+    pub fn validate_in_groups(&mut self, owner: Owner, groups: &[Owner]) -> LbResult<()> {
         // point checks
         self.assert_no_root_changes()?;
         self.assert_no_changes_to_deleted_files()?;
         self.assert_all_filenames_size_limit()?;
-        self.assert_all_files_decryptable(owner)?;
+        self.assert_all_files_decryptable(owner, groups)?;
         self.assert_only_folders_have_children()?;
         self.assert_all_files_same_owner_as_parent()?;
 
@@ -68,19 +74,19 @@ where
         self.assert_no_owned_links()?;
 
         // authorization check
-        self.assert_changes_authorized(owner)?;
+        self.assert_changes_authorized(owner, groups)?;
 
         Ok(())
     }
 
     // note: deleted access keys permissible
-    pub fn assert_all_files_decryptable(&mut self, owner: Owner) -> LbResult<()> {
+    pub fn assert_all_files_decryptable(&mut self, owner: Owner, groups: &[Owner]) -> LbResult<()> {
         for file in self.ids().into_iter().filter_map(|id| self.maybe_find(&id)) {
             if self.maybe_find_parent(file).is_none()
                 && !file
                     .user_access_keys()
                     .iter()
-                    .any(|k| k.encrypted_for == owner.0)
+                    .any(|k| k.encrypted_for == owner.0 || groups.contains(&Owner(k.encrypted_for)))
             {
                 Err(LbErrKind::Validation(ValidationFailure::Orphan(*file.id())))?;
             }
@@ -275,13 +281,26 @@ where
 
     pub fn assert_no_changes_to_deleted_files(&mut self) -> LbResult<()> {
         for id in self.tree.staged().ids() {
-            // already deleted files cannot have updates
+            // already deleted files cannot have updates, except to be restored (possibly to a
+            // new parent) or to have their key re-encrypted when an ancestor's key is rotated
             let mut base = self.tree.base().to_lazy();
-            if base.maybe_find(&id).is_some() && base.calculate_deleted(&id)? {
-                Err(LbErrKind::Validation(ValidationFailure::DeletedFileUpdated(id)))?;
+            let already_deleted = base.maybe_find(&id).is_some() && base.calculate_deleted(&id)?;
+            if already_deleted {
+                let diff = FileDiff::edit(base.find(&id)?.clone(), self.find(&id)?.clone()).diff();
+                let restored = !self.calculate_deleted(&id)?
+                    && diff.iter().all(|d| {
+                        matches!(d, Diff::Deleted | Diff::Parent | Diff::Name | Diff::FolderKey)
+                    });
+                let rekeyed = diff.contains(&Diff::FolderKey)
+                    && diff
+                        .iter()
+                        .all(|d| matches!(d, Diff::Name | Diff::FolderKey));
+                if !restored && !rekeyed {
+                    Err(LbErrKind::Validation(ValidationFailure::DeletedFileUpdated(id)))?;
+                }
             }
             // newly deleted files cannot have non-deletion updates
-            if self.calculate_deleted(&id)? {
+            if !already_deleted && self.calculate_deleted(&id)? {
                 if let Some(base) = self.tree.base().maybe_find(&id) {
                     if FileDiff::edit(base.clone(), self.find(&id)?.clone())
                         .diff()
@@ -296,7 +315,7 @@ where
         Ok(())
     }
 
-    pub fn assert_changes_authorized(&mut self, owner: Owner) -> LbResult<()> {
+    pub fn assert_changes_authorized(&mut self, owner: Owner, groups: &[Owner]) -> LbResult<()> {
         // Design rationale:
         // * No combination of individually valid changes should compose into an invalid change.
         //   * Owner and write access must be indistinguishable, otherwise you could e.g. move a
@@ -336,7 +355,7 @@ where
                         if !new_files.contains(file.parent()) {
                             // must have parent and have write access to parent
                             if let Some(parent) = self.maybe_find(file.parent()) {
-                                if self.access_mode(owner, parent.id())?
+                                if self.access_mode_in_groups(owner, groups, parent.id())?
                                     < Some(UserAccessMode::Write)
                                 {
                                     // parent is shared with access < write
@@ -362,7 +381,7 @@ where
 
                             // must have parent and have write access to parent
                             if let Some(parent) = self.maybe_find(parent) {
-                                if self.access_mode(owner, parent.id())?
+                                if self.access_mode_in_groups(owner, groups, parent.id())?
                                     < Some(UserAccessMode::Write)
                                 {
                                     // parent is shared with access < write
@@ -381,7 +400,7 @@ where
                             if !new_files.contains(parent) {
                                 // must have parent and have write access to parent
                                 if let Some(parent) = self.maybe_find(parent) {
-                                    if self.access_mode(owner, parent.id())?
+                                    if self.access_mode_in_groups(owner, groups, parent.id())?
                                         < Some(UserAccessMode::Write)
                                     {
                                         // parent is shared with access < write
@@ -396,7 +415,25 @@ where
                     }
                     Diff::Hmac => {
                         // check self access
-                        if self.access_mode(owner, file_diff.id())? < Some(UserAccessMode::Write) {
+                        if self.access_mode_in_groups(owner, groups, file_diff.id())?
+                            < Some(UserAccessMode::Write)
+                        {
+                            Err(LbErrKind::InsufficientPermission)?;
+                        }
+                    }
+                    Diff::FolderKey => {
+                        // a moved file's key is re-encrypted for its new parent, which the parent
+                        // checks cover; otherwise the key is being rotated, which only the owner
+                        // can do
+                        if !file_diff.diff().contains(&Diff::Parent)
+                            && file_diff.new.owner() != owner
+                        {
+                            Err(LbErrKind::InsufficientPermission)?;
+                        }
+                        // check self access
+                        if self.access_mode_in_groups(owner, groups, file_diff.id())?
+                            < Some(UserAccessMode::Write)
+                        {
                             Err(LbErrKind::InsufficientPermission)?;
                         }
                     }
@@ -428,7 +465,7 @@ where
                                 // cannot delete someone else's share without write access
                                 if *staged_deleted
                                     && !*base_deleted
-                                    && self.access_mode(owner, file_diff.id())?
+                                    && self.access_mode_in_groups(owner, groups, file_diff.id())?
                                         < Some(UserAccessMode::Write)
                                     && owner.0 != key.encrypted_for
                                 {
@@ -436,7 +473,7 @@ where
                                 }
                                 // cannot grant yourself write access
                                 if staged_mode != base_mode
-                                    && self.access_mode(owner, file_diff.id())?
+                                    && self.access_mode_in_groups(owner, groups, file_diff.id())?
                                         < Some(UserAccessMode::Write)
                                 {
                                     Err(LbErrKind::InsufficientPermission)?;
@@ -445,7 +482,9 @@ where
                                 // adding a new share
 
                                 // to add a share, need equal access
-                                if self.access_mode(owner, file_diff.id())? < Some(key.mode) {
+                                if self.access_mode_in_groups(owner, groups, file_diff.id())?
+                                    < Some(key.mode)
+                                {
                                     Err(LbErrKind::InsufficientPermission)?;
                                 }
                             }
@@ -9,6 +9,9 @@ pub const OS_HEADER: &str = "X-Lockbook-OS";
 
 pub const CLIENT_HEADER: &str = "X-Lockbook-Client";
 
+/// Identifies one running client, so the server doesn't wake it for changes it made itself.
+pub const SESSION_HEADER: &str = "X-Lockbook-Session";
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum WireFormat {
     Json,
@@ -1,9 +1,11 @@
 use super::path::split_path;
-use super::{ContentMatch, SearchFilter, SearchResult, build_descendants};
+use super::query::{self, ParsedQuery};
+use super::tags::extract_tags;
+use super::{ContentMatch, FilterContext, SearchFilter, SearchResult};
 use crate::blocking::Lb;
 use crate::model::file::File;
 use std::cmp::Reverse;
-use std::collections::{HashMap, HashSet};
+use std::collections::HashSet;
 use std::ops::Range;
 use std::sync::{Arc, Mutex};
 use std::thread;
@@ -20,10 +22,10 @@ struct Document {
 pub struct ContentSearcher {
     documents: Vec<Document>,
     results: Vec<SearchResult>,
-    submitted_query: String,
+    submitted_query: ParsedQuery,
     build_time: Duration,
-    descendants: HashMap<Uuid, Vec<Uuid>>,
-    path_to_id: HashMap<String, Uuid>,
+    context: FilterContext,
+    filter: Option<SearchFilter>,
     filter_ids: Option<HashSet<Uuid>>,
 }
 
@@ -33,12 +35,10 @@ impl ContentSearcher {
         let metas = lb.list_metadatas().unwrap_or_default();
         let paths = Arc::new(lb.list_paths_with_ids(None).unwrap_or_default());
 
-        let descendants = build_descendants(&metas);
-        let path_to_id = paths.iter().map(|(id, path)| (path.clone(), *id)).collect();
-
         let md_files: Vec<File> = metas
-            .into_iter()
+            .iter()
             .filter(|m| m.is_document() && m.name.ends_with(".md"))
+            .cloned()
             .collect();
 
         let queue = Arc::new(Mutex::new(md_files));
@@ -89,48 +89,66 @@ impl ContentSearcher {
             h.join().unwrap();
         }
 
-        let documents = Arc::try_unwrap(documents)
+        let documents: Vec<Document> = Arc::try_unwrap(documents)
             .ok()
             .expect("all workers joined")
             .into_inner()
             .unwrap();
 
+        let tags = documents
+            .iter()
+            .map(|doc| (doc.file.id, extract_tags(&doc.lowercased_content)))
+            .collect();
+        let context = FilterContext::new(&metas, &paths, tags);
+
         Self {
             documents,
             results: Vec::new(),
-            submitted_query: String::new(),
+            submitted_query: ParsedQuery::default(),
             build_time: start.elapsed(),
-            descendants,
-            path_to_id,
+            context,
+            filter: None,
             filter_ids: None,
         }
     }
 
     /// Update the active filter and refresh results for the current query.
     pub fn update_filter(&mut self, filter: Option<SearchFilter>) {
-        self.filter_ids = super::resolve_filter(filter, &self.path_to_id, &self.descendants);
+        self.filter = filter;
+        self.resolve_filter();
         self.rebuild();
     }
 
-    /// Update the search query. Results available via `results()`.
+    /// Update the search query, see [query] for its syntax. Results available via `results()`.
     pub fn query(&mut self, input: &str) {
-        let query = input.trim().to_lowercase();
-        if self.submitted_query == query {
+        let mut parsed = query::parse(input);
+        parsed.text = parsed.plain_text().to_lowercase();
+        if self.submitted_query == parsed {
             return;
         }
-        self.submitted_query = query;
+        let filter_changed = parsed.filter != self.submitted_query.filter;
+        self.submitted_query = parsed;
+        if filter_changed {
+            self.resolve_filter();
+        }
         self.rebuild();
     }
 
+    /// Resolve the filter from `update_filter` together with the one from the query.
+    fn resolve_filter(&mut self) {
+        let filter = query::and(self.filter.clone(), self.submitted_query.filter.clone());
+        self.filter_ids = self.context.resolve(filter.as_ref());
+    }
+
     /// Rebuild `results` from the current query and filter. Shared by `query` and
     /// `update_filter`.
     fn rebuild(&mut self) {
         self.results.clear();
-        if self.submitted_query.is_empty() {
+        if self.submitted_query.text.is_empty() {
             return;
         }
 
-        let query = &self.submitted_query;
+        let query = &self.submitted_query.text;
         let words: Vec<&str> = query.split_whitespace().collect();
 
         let mut scored = Vec::new();
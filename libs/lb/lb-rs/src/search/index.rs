@@ -0,0 +1,414 @@
+use super::tags::extract_tags;
+use crate::model::file_metadata::DocumentHmac;
+use crate::service::links::{Link, parse_links};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+use uuid::Uuid;
+
+/// Bumped whenever tokenization or the layout of [SearchIndex] changes, so indexes written by
+/// older versions are rebuilt rather than misread.
+pub const INDEX_VERSION: u32 = 3;
+
+// bm25 tuning, the usual defaults
+const K1: f32 = 1.2;
+const B: f32 = 0.75;
+
+/// how much more a term in a document's name counts than one in its content
+const NAME_BOOST: f32 = 2.0;
+
+/// An inverted index over the text of documents, so a search only touches the documents that
+/// contain its terms. Maintained incrementally by [crate::subscribers::search] as documents are
+/// written and synced.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchIndex {
+    pub version: u32,
+    docs: HashMap<Uuid, IndexedDoc>,
+    /// term -> document -> positions of the term within the document, in tokens
+    postings: HashMap<String, HashMap<Uuid, Vec<u32>>>,
+    total_tokens: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IndexedDoc {
+    /// the version of the document that was indexed
+    hmac: Option<DocumentHmac>,
+    name: String,
+    name_terms: Vec<String>,
+    /// the distinct terms of the content, so they can be found when the document is removed
+    terms: Vec<String>,
+    tokens: u32,
+    tags: Vec<String>,
+    links: Vec<Link>,
+}
+
+/// A word of a document or query, normalized for matching.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub term: String,
+    /// byte range of the word in the text it was read from
+    pub range: Range<usize>,
+}
+
+/// Part of a query every result has to match.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Clause {
+    Term(String),
+    /// terms that have to appear next to one another, in order
+    Phrase(Vec<String>),
+}
+
+impl Default for SearchIndex {
+    fn default() -> Self {
+        Self {
+            version: INDEX_VERSION,
+            docs: Default::default(),
+            postings: Default::default(),
+            total_tokens: 0,
+        }
+    }
+}
+
+impl SearchIndex {
+    /// the version of `id` that was indexed, if it was
+    pub fn indexed_hmac(&self, id: &Uuid) -> Option<Option<DocumentHmac>> {
+        self.docs.get(id).map(|doc| doc.hmac)
+    }
+
+    /// the tags of every indexed document that has any
+    pub fn tags(&self) -> HashMap<Uuid, Vec<String>> {
+        self.docs
+            .iter()
+            .filter(|(_, doc)| !doc.tags.is_empty())
+            .map(|(id, doc)| (*id, doc.tags.clone()))
+            .collect()
+    }
+
+    /// the links in every indexed document that has any
+    pub fn links(&self) -> HashMap<Uuid, Vec<Link>> {
+        self.docs
+            .iter()
+            .filter(|(_, doc)| !doc.links.is_empty())
+            .map(|(id, doc)| (*id, doc.links.clone()))
+            .collect()
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = &Uuid> {
+        self.docs.keys()
+    }
+
+    pub fn len(&self) -> usize {
+        self.docs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.docs.is_empty()
+    }
+
+    /// Indexes `content` as the contents of `id` at `hmac`, replacing whatever was indexed for
+    /// it before.
+    pub fn insert(&mut self, id: Uuid, hmac: Option<DocumentHmac>, name: &str, content: &str) {
+        self.remove(&id);
+
+        let tokens = tokenize(content);
+        let mut positions: HashMap<String, Vec<u32>> = HashMap::new();
+        for (position, token) in tokens.iter().enumerate() {
+            positions
+                .entry(token.term.clone())
+                .or_default()
+                .push(position as u32);
+        }
+
+        let terms = positions.keys().cloned().collect();
+        for (term, positions) in positions {
+            self.postings.entry(term).or_default().insert(id, positions);
+        }
+        self.total_tokens += tokens.len() as u64;
+        self.docs.insert(
+            id,
+            IndexedDoc {
+                hmac,
+                name: name.to_string(),
+                name_terms: name_terms(name),
+                terms,
+                tokens: tokens.len() as u32,
+                tags: extract_tags(content),
+                links: parse_links(content),
+            },
+        );
+    }
+
+    /// Updates the name of an indexed document; returns whether it changed.
+    pub fn rename(&mut self, id: &Uuid, name: &str) -> bool {
+        match self.docs.get_mut(id) {
+            Some(doc) if doc.name != name => {
+                doc.name = name.to_string();
+                doc.name_terms = name_terms(name);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Removes `id` from the index; returns whether it was indexed.
+    pub fn remove(&mut self, id: &Uuid) -> bool {
+        let Some(doc) = self.docs.remove(id) else {
+            return false;
+        };
+        for term in &doc.terms {
+            if let Some(docs) = self.postings.get_mut(term) {
+                docs.remove(id);
+                if docs.is_empty() {
+                    self.postings.remove(term);
+                }
+            }
+        }
+        self.total_tokens -= doc.tokens as u64;
+        true
+    }
+
+    /// Ranks the documents that match every clause, best first. A clause matches a document if
+    /// it appears in its content or, for terms, in its name. `scope` restricts the documents
+    /// considered.
+    pub fn search(&self, clauses: &[Clause], scope: Option<&HashSet<Uuid>>) -> Vec<(Uuid, f32)> {
+        if clauses.is_empty() {
+            return vec![];
+        }
+
+        let doc_count = self.docs.len() as f32;
+        let avg_tokens = (self.total_tokens as f32 / doc_count.max(1.0)).max(1.0);
+
+        let mut scores: Option<HashMap<Uuid, f32>> = None;
+        for clause in clauses {
+            let frequencies = self.frequencies(clause);
+            let names = match clause {
+                Clause::Term(term) => self
+                    .docs
+                    .iter()
+                    .filter(|(_, doc)| doc.name_terms.contains(term))
+                    .map(|(id, _)| *id)
+                    .collect(),
+                Clause::Phrase(_) => HashSet::new(),
+            };
+
+            let matched = frequencies
+                .keys()
+                .chain(&names)
+                .collect::<HashSet<_>>()
+                .len() as f32;
+            let idf = (1.0 + (doc_count - matched + 0.5) / (matched + 0.5)).ln();
+
+            let mut clause_scores: HashMap<Uuid, f32> = HashMap::new();
+            for (id, frequency) in frequencies {
+                let tokens = self.docs[&id].tokens as f32;
+                let frequency = frequency as f32;
+                let score = idf * frequency * (K1 + 1.0)
+                    / (frequency + K1 * (1.0 - B + B * tokens / avg_tokens));
+                clause_scores.insert(id, score);
+            }
+            for id in names {
+                *clause_scores.entry(id).or_default() += idf * NAME_BOOST;
+            }
+
+            scores = Some(match scores {
+                None => clause_scores,
+                Some(scores) => scores
+                    .into_iter()
+                    .filter_map(|(id, score)| clause_scores.get(&id).map(|s| (id, score + s)))
+                    .collect(),
+            });
+        }
+
+        let mut results: Vec<(Uuid, f32)> = scores
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(id, _)| scope.map(|scope| scope.contains(id)).unwrap_or(true))
+            .collect();
+        results.sort_by(|(a_id, a), (b_id, b)| b.total_cmp(a).then(a_id.cmp(b_id)));
+        results
+    }
+
+    /// how many times `clause` occurs in each document whose content contains it
+    fn frequencies(&self, clause: &Clause) -> HashMap<Uuid, usize> {
+        match clause {
+            Clause::Term(term) => self
+                .postings
+                .get(term)
+                .map(|docs| {
+                    docs.iter()
+                        .map(|(id, positions)| (*id, positions.len()))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            Clause::Phrase(terms) => {
+                let Some(postings) = terms
+                    .iter()
+                    .map(|term| self.postings.get(term))
+                    .collect::<Option<Vec<_>>>()
+                else {
+                    return HashMap::new();
+                };
+                let Some((first, rest)) = postings.split_first() else {
+                    return HashMap::new();
+                };
+
+                first
+                    .iter()
+                    .filter_map(|(id, starts)| {
+                        let rest = rest
+                            .iter()
+                            .map(|docs| docs.get(id))
+                            .collect::<Option<Vec<_>>>()?;
+                        let count = starts
+                            .iter()
+                            .filter(|&&start| {
+                                rest.iter().enumerate().all(|(offset, positions)| {
+                                    positions
+                                        .binary_search(&(start + offset as u32 + 1))
+                                        .is_ok()
+                                })
+                            })
+                            .count();
+                        (count > 0).then_some((*id, count))
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Parses a query into the clauses every result has to match: words, and "quoted phrases".
+pub fn parse_query(query: &str) -> Vec<Clause> {
+    let mut clauses = vec![];
+    for (i, part) in query.split('"').enumerate() {
+        let terms: Vec<String> = tokenize(part).into_iter().map(|t| t.term).collect();
+        if i % 2 == 1 && terms.len() > 1 {
+            clauses.push(Clause::Phrase(terms));
+        } else {
+            clauses.extend(terms.into_iter().map(Clause::Term));
+        }
+    }
+    clauses.dedup();
+    clauses
+}
+
+/// Splits text into words, lowercased and stemmed so that "Notes" matches "note".
+pub fn tokenize(text: &str) -> Vec<Token> {
+    text.unicode_word_indices()
+        .map(|(start, word)| Token {
+            term: stem(&word.to_lowercase()),
+            range: start..start + word.len(),
+        })
+        .collect()
+}
+
+/// The byte ranges in `text` where `clauses` match, for highlighting.
+pub fn match_ranges(text: &str, clauses: &[Clause]) -> Vec<Range<usize>> {
+    let tokens = tokenize(text);
+    let mut ranges = vec![];
+    for clause in clauses {
+        let terms = match clause {
+            Clause::Term(term) => std::slice::from_ref(term),
+            Clause::Phrase(terms) => terms.as_slice(),
+        };
+        for window in tokens.windows(terms.len()) {
+            if window
+                .iter()
+                .zip(terms)
+                .all(|(token, term)| &token.term == term)
+            {
+                ranges.push(window[0].range.start..window[terms.len() - 1].range.end);
+            }
+        }
+    }
+    ranges.sort_by_key(|range| range.start);
+    ranges
+}
+
+fn name_terms(name: &str) -> Vec<String> {
+    let stem = name.rsplit_once('.').map(|(stem, _)| stem).unwrap_or(name);
+    tokenize(stem).into_iter().map(|t| t.term).collect()
+}
+
+/// The first step of the Porter stemmer, which folds plurals and -ed / -ing forms of English
+/// words together. Words that aren't plain lowercase ascii are left alone.
+pub fn stem(word: &str) -> String {
+    if word.len() <= 2 || !word.bytes().all(|b| b.is_ascii_lowercase()) {
+        return word.to_string();
+    }
+    let mut w = word.as_bytes().to_vec();
+
+    // step 1a: plurals
+    if w.ends_with(b"sses") || w.ends_with(b"ies") {
+        w.truncate(w.len() - 2);
+    } else if w.ends_with(b"s") && !w.ends_with(b"ss") {
+        w.pop();
+    }
+
+    // step 1b: past tenses and participles
+    if w.ends_with(b"eed") {
+        if measure(&w[..w.len() - 3]) > 0 {
+            w.pop();
+        }
+    } else if let Some(suffix) = [b"ed".as_slice(), b"ing"]
+        .into_iter()
+        .find(|suffix| w.ends_with(suffix) && has_vowel(&w[..w.len() - suffix.len()]))
+    {
+        w.truncate(w.len() - suffix.len());
+        if w.ends_with(b"at") || w.ends_with(b"bl") || w.ends_with(b"iz") {
+            w.push(b'e');
+        } else if ends_with_double_consonant(&w) && !matches!(w.last(), Some(b'l' | b's' | b'z')) {
+            w.pop();
+        } else if measure(&w) == 1 && ends_with_cvc(&w) {
+            w.push(b'e');
+        }
+    }
+
+    // step 1c
+    if w.ends_with(b"y") && has_vowel(&w[..w.len() - 1]) {
+        *w.last_mut().unwrap() = b'i';
+    }
+
+    String::from_utf8(w).unwrap_or_else(|_| word.to_string())
+}
+
+fn is_consonant(w: &[u8], i: usize) -> bool {
+    match w[i] {
+        b'a' | b'e' | b'i' | b'o' | b'u' => false,
+        b'y' => i == 0 || !is_consonant(w, i - 1),
+        _ => true,
+    }
+}
+
+/// the number of vowel-consonant sequences in `w`
+fn measure(w: &[u8]) -> usize {
+    let mut m = 0;
+    let mut after_vowel = false;
+    for i in 0..w.len() {
+        let consonant = is_consonant(w, i);
+        if consonant && after_vowel {
+            m += 1;
+        }
+        after_vowel = !consonant;
+    }
+    m
+}
+
+fn has_vowel(w: &[u8]) -> bool {
+    (0..w.len()).any(|i| !is_consonant(w, i))
+}
+
+fn ends_with_double_consonant(w: &[u8]) -> bool {
+    let n = w.len();
+    n >= 2 && w[n - 1] == w[n - 2] && is_consonant(w, n - 1)
+}
+
+fn ends_with_cvc(w: &[u8]) -> bool {
+    let n = w.len();
+    n >= 3
+        && is_consonant(w, n - 3)
+        && !is_consonant(w, n - 2)
+        && is_consonant(w, n - 1)
+        && !matches!(w[n - 1], b'w' | b'x' | b'y')
+}
@@ -1,6 +1,10 @@
 pub mod content;
+pub mod index;
 pub mod path;
+pub mod query;
+pub mod tags;
 
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::ops::Range;
 use uuid::Uuid;
@@ -24,11 +28,46 @@ pub struct SearchResult {
     pub content_matches: Vec<ContentMatch>,
 }
 
-/// Scopes a search to a subset of the working set.
-#[derive(Debug, Clone)]
+/// Scopes a search to a subset of the working set. Usually parsed from a query, see [query].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum SearchFilter {
     /// Restrict results to a folder path.
     Path(String),
+    /// Documents with this extension, or folders for `folder`.
+    Type(String),
+    /// Files last modified at or after `after` and before `before`, in ms since the epoch.
+    Modified {
+        after: Option<u64>,
+        before: Option<u64>,
+    },
+    Owner(String),
+    /// Files last modified by this user.
+    EditedBy(String),
+    /// Files that are, or aren't, in a share.
+    Shared(bool),
+    /// Markdown documents with this tag, see [tags::extract_tags].
+    Tag(String),
+    And(Vec<SearchFilter>),
+    Or(Vec<SearchFilter>),
+    Not(Box<SearchFilter>),
+}
+
+/// A document that matched a search, see [crate::LocalLb::search].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchHit {
+    pub id: Uuid,
+    pub filename: String,
+    pub parent_path: String,
+    pub score: f32,
+    /// some of the document around its first match, if it could be read
+    pub snippet: Option<Snippet>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snippet {
+    pub text: String,
+    /// byte ranges into `text` that matched the query
+    pub matches: Vec<Range<usize>>,
 }
 
 /// A match highlight within document content.
@@ -50,7 +89,7 @@ impl Lb {
 
 /// Map each file id to all of its descendant ids (children, grandchildren, ...).
 /// Built once when an executor's index is constructed.
-pub(crate) fn build_descendants(files: &[File]) -> HashMap<Uuid, Vec<Uuid>> {
+fn build_descendants(files: &[File]) -> HashMap<Uuid, Vec<Uuid>> {
     let parent_of: HashMap<Uuid, Uuid> = files.iter().map(|f| (f.id, f.parent)).collect();
 
     let mut descendants: HashMap<Uuid, Vec<Uuid>> = HashMap::new();
@@ -68,17 +107,118 @@ pub(crate) fn build_descendants(files: &[File]) -> HashMap<Uuid, Vec<Uuid>> {
     descendants
 }
 
-/// Resolve a filter to the set of file ids it admits (the scoped folder's
-/// descendants), or `None` when there is no filter.
-pub(crate) fn resolve_filter(
-    filter: Option<SearchFilter>, path_to_id: &HashMap<String, Uuid>,
-    descendants: &HashMap<Uuid, Vec<Uuid>>,
-) -> Option<HashSet<Uuid>> {
-    filter.map(|SearchFilter::Path(path)| {
-        path_to_id
-            .get(&path)
-            .and_then(|id| descendants.get(id))
-            .map(|ids| ids.iter().copied().collect())
-            .unwrap_or_default()
-    })
+/// What filters are evaluated against, built once when an executor's index is constructed.
+pub(crate) struct FilterContext {
+    files: HashMap<Uuid, File>,
+    /// folder paths without their trailing `/`
+    path_to_id: HashMap<String, Uuid>,
+    descendants: HashMap<Uuid, Vec<Uuid>>,
+    tags: HashMap<Uuid, Vec<String>>,
+}
+
+impl FilterContext {
+    pub(crate) fn new(
+        files: &[File], paths: &[(Uuid, String)], tags: HashMap<Uuid, Vec<String>>,
+    ) -> Self {
+        Self {
+            files: files.iter().map(|f| (f.id, f.clone())).collect(),
+            path_to_id: paths
+                .iter()
+                .map(|(id, path)| (normalize_path(path), *id))
+                .collect(),
+            descendants: build_descendants(files),
+            tags,
+        }
+    }
+
+    /// Resolve a filter to the set of file ids it admits, or `None` when there is no filter.
+    pub(crate) fn resolve(&self, filter: Option<&SearchFilter>) -> Option<HashSet<Uuid>> {
+        filter.map(|filter| self.admitted(filter))
+    }
+
+    fn admitted(&self, filter: &SearchFilter) -> HashSet<Uuid> {
+        match filter {
+            SearchFilter::Path(path) => self
+                .path_to_id
+                .get(&normalize_path(path))
+                .and_then(|id| self.descendants.get(id))
+                .map(|ids| ids.iter().copied().collect())
+                .unwrap_or_default(),
+            SearchFilter::And(filters) => {
+                let mut filters = filters.iter();
+                let Some(first) = filters.next() else {
+                    return self.files.keys().copied().collect();
+                };
+                let mut admitted = self.admitted(first);
+                for filter in filters {
+                    let next = self.admitted(filter);
+                    admitted.retain(|id| next.contains(id));
+                }
+                admitted
+            }
+            SearchFilter::Or(filters) => filters.iter().flat_map(|f| self.admitted(f)).collect(),
+            SearchFilter::Not(filter) => {
+                let excluded = self.admitted(filter);
+                self.files
+                    .keys()
+                    .filter(|id| !excluded.contains(id))
+                    .copied()
+                    .collect()
+            }
+            filter => self
+                .files
+                .values()
+                .filter(|file| self.matches(filter, file))
+                .map(|file| file.id)
+                .collect(),
+        }
+    }
+
+    /// whether a file matches a filter that only depends on the file itself
+    fn matches(&self, filter: &SearchFilter, file: &File) -> bool {
+        match filter {
+            SearchFilter::Type(kind) if kind == "folder" => file.is_folder(),
+            SearchFilter::Type(extension) => {
+                file.is_document()
+                    && file
+                        .name
+                        .rsplit_once('.')
+                        .is_some_and(|(_, ext)| ext.eq_ignore_ascii_case(extension))
+            }
+            SearchFilter::Modified { after, before } => {
+                after.is_none_or(|after| file.last_modified >= after)
+                    && before.is_none_or(|before| file.last_modified < before)
+            }
+            SearchFilter::Owner(owner) => file.owner.eq_ignore_ascii_case(owner),
+            SearchFilter::EditedBy(editor) => file.last_modified_by.eq_ignore_ascii_case(editor),
+            SearchFilter::Shared(shared) => self.is_shared(file) == *shared,
+            SearchFilter::Tag(tag) => self
+                .tags
+                .get(&file.id)
+                .is_some_and(|tags| tags.contains(tag)),
+            SearchFilter::Path(_)
+            | SearchFilter::And(_)
+            | SearchFilter::Or(_)
+            | SearchFilter::Not(_) => self.admitted(filter).contains(&file.id),
+        }
+    }
+
+    /// whether a file, or a folder it's in, is shared with or by someone
+    fn is_shared(&self, file: &File) -> bool {
+        let mut file = file;
+        loop {
+            if !file.shares.is_empty() {
+                return true;
+            }
+            match self.files.get(&file.parent) {
+                Some(parent) if !file.is_root() => file = parent,
+                _ => return false,
+            }
+        }
+    }
+}
+
+fn normalize_path(path: &str) -> String {
+    let path = path.trim_end_matches('/');
+    if path.starts_with('/') { path.to_string() } else { format!("/{path}") }
 }
@@ -1,4 +1,5 @@
-use super::{SearchFilter, SearchResult, build_descendants};
+use super::query::{self, ParsedQuery};
+use super::{FilterContext, SearchFilter, SearchResult};
 use crate::Lb;
 use crate::model::file::File;
 use nucleo::{
@@ -6,7 +7,7 @@ use nucleo::{
     pattern::{CaseMatching, Normalization},
 };
 use std::cmp::Reverse;
-use std::collections::{HashMap, HashSet};
+use std::collections::HashSet;
 use std::sync::Arc;
 use uuid::Uuid;
 
@@ -44,15 +45,16 @@ fn path_result(entry: &PathEntry, path_indices: Vec<u32>) -> SearchResult {
 pub struct PathSearcher {
     nucleo: Nucleo<PathEntry>,
     results: Vec<SearchResult>,
-    submitted_query: String,
-    descendants: HashMap<Uuid, Vec<Uuid>>,
-    path_to_id: HashMap<String, Uuid>,
+    submitted_query: ParsedQuery,
+    context: FilterContext,
+    filter: Option<SearchFilter>,
     filter_ids: Option<HashSet<Uuid>>,
 }
 
 impl PathSearcher {
     pub async fn new(lb: &Lb) -> Self {
         let files = lb.list_metadatas().await.unwrap_or_default();
+        let tags = lb.document_tags().await.unwrap_or_default();
         let mut paths = lb.list_paths_with_ids(None).await.unwrap_or_default();
         paths.retain(|(_, path)| path != "/");
 
@@ -77,15 +79,14 @@ impl PathSearcher {
             }
         }
 
-        let descendants = build_descendants(&files);
-        let path_to_id = paths.iter().map(|(id, path)| (path.clone(), *id)).collect();
+        let context = FilterContext::new(&files, &paths, tags);
 
         let mut searcher = Self {
             nucleo,
             results: Vec::new(),
-            submitted_query: String::new(),
-            descendants,
-            path_to_id,
+            submitted_query: ParsedQuery::default(),
+            context,
+            filter: None,
             filter_ids: None,
         };
         searcher.query("");
@@ -94,33 +95,46 @@ impl PathSearcher {
 
     /// Update the active filter and refresh results for the current query.
     pub fn update_filter(&mut self, filter: Option<SearchFilter>) {
-        self.filter_ids = super::resolve_filter(filter, &self.path_to_id, &self.descendants);
+        self.filter = filter;
+        self.resolve_filter();
         self.rebuild();
     }
 
-    /// Update the search query. Results available via `results()`.
+    /// Update the search query, see [query] for its syntax. Results available via `results()`.
     pub fn query(&mut self, input: &str) {
+        let parsed = query::parse(input);
+        let text = parsed.plain_text();
         self.nucleo.pattern.reparse(
             0,
-            input,
+            &text,
             CaseMatching::Smart,
             Normalization::Smart,
-            input.starts_with(&self.submitted_query),
+            text.starts_with(&self.submitted_query.plain_text()),
         );
-        self.submitted_query = input.to_string();
+        let filter_changed = parsed.filter != self.submitted_query.filter;
+        self.submitted_query = parsed;
+        if filter_changed {
+            self.resolve_filter();
+        }
 
         while self.nucleo.tick(10).running {}
 
         self.rebuild();
     }
 
+    /// Resolve the filter from `update_filter` together with the one from the query.
+    fn resolve_filter(&mut self) {
+        let filter = query::and(self.filter.clone(), self.submitted_query.filter.clone());
+        self.filter_ids = self.context.resolve(filter.as_ref());
+    }
+
     /// Rebuild `results` from the current query and filter without re-running the
     /// matcher. Shared by `query` (after a reparse) and `update_filter`.
     fn rebuild(&mut self) {
         self.results.clear();
         let snapshot = self.nucleo.snapshot();
 
-        if self.submitted_query.is_empty() {
+        if self.submitted_query.text.is_empty() {
             let mut entries: Vec<&PathEntry> = (0..snapshot.matched_item_count())
                 .filter_map(|i| snapshot.get_matched_item(i).map(|item| item.data))
                 .filter(|e| {
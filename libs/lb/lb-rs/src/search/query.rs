@@ -0,0 +1,267 @@
+//! The query language shared by every searcher. A query is free text mixed with fields:
+//!
+//! - `in:/notes` files inside a folder
+//! - `type:md|svg|pdf|chat` documents by extension, `type:folder` for folders and `type:drawing`
+//!   for svgs
+//! - `modified:>2026-01-01` files by when they were last modified. Takes `>`, `>=`, `<`, `<=` or
+//!   no comparison (that day), and dates (in UTC), `today`, `yesterday` or a time ago like `7d`,
+//!   `2w`, `3m` or `1y`: `modified:>7d` is anything modified in the last week
+//! - `owner:alice` files owned by a user, `by:bob` files last modified by a user
+//! - `shared:yes` or `shared:no` files that are or aren't in a share
+//! - `#tag` markdown documents with a tag, see [super::tags::extract_tags]
+//!
+//! Fields combine with `AND` (implied between neighbours), `OR`, `NOT` and parentheses. Free text
+//! is always matched in full by the searcher, whatever operators surround it. Fields with values
+//! that can't be understood are searched for as text.
+
+use super::SearchFilter;
+use crate::model::clock::get_time;
+use chrono::NaiveDate;
+
+const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+
+/// A query split into the text to search for and the fields to filter by.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParsedQuery {
+    /// the free text of the query, with "quoted phrases" kept quoted
+    pub text: String,
+    pub filter: Option<SearchFilter>,
+}
+
+impl ParsedQuery {
+    /// the free text without quotes, for searchers that match it as a whole
+    pub fn plain_text(&self) -> String {
+        self.text.replace('"', "")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Field(SearchFilter),
+    Text(String),
+}
+
+pub fn parse(query: &str) -> ParsedQuery {
+    let tokens = tokenize(query);
+    let mut parser = Parser { tokens: &tokens, pos: 0, text: vec![] };
+
+    let mut filter = None;
+    while parser.pos < tokens.len() {
+        let next = parser.or();
+        filter = and(filter, next);
+        // an unmatched `)`
+        parser.pos += 1;
+    }
+
+    ParsedQuery { text: parser.text.join(" "), filter }
+}
+
+/// Combines two filters that both have to match.
+pub fn and(a: Option<SearchFilter>, b: Option<SearchFilter>) -> Option<SearchFilter> {
+    match (a, b) {
+        (None, f) | (f, None) => f,
+        (Some(SearchFilter::And(mut filters)), Some(f)) => {
+            filters.push(f);
+            Some(SearchFilter::And(filters))
+        }
+        (Some(a), Some(b)) => Some(SearchFilter::And(vec![a, b])),
+    }
+}
+
+fn or(a: Option<SearchFilter>, b: Option<SearchFilter>) -> Option<SearchFilter> {
+    match (a, b) {
+        (None, f) | (f, None) => f,
+        (Some(SearchFilter::Or(mut filters)), Some(f)) => {
+            filters.push(f);
+            Some(SearchFilter::Or(filters))
+        }
+        (Some(a), Some(b)) => Some(SearchFilter::Or(vec![a, b])),
+    }
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    text: Vec<String>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn or(&mut self) -> Option<SearchFilter> {
+        let mut filter = self.and();
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            let next = self.and();
+            filter = or(filter, next);
+        }
+        filter
+    }
+
+    fn and(&mut self) -> Option<SearchFilter> {
+        let mut filter = self.unary();
+        loop {
+            match self.peek() {
+                Some(Token::And) => self.pos += 1,
+                Some(Token::Or) | Some(Token::Close) | None => return filter,
+                _ => {}
+            }
+            let next = self.unary();
+            filter = and(filter, next);
+        }
+    }
+
+    fn unary(&mut self) -> Option<SearchFilter> {
+        let Some(token) = self.peek().cloned() else {
+            return None;
+        };
+        self.pos += 1;
+        match token {
+            Token::Not => self.unary().map(|f| SearchFilter::Not(Box::new(f))),
+            Token::Open => {
+                let filter = self.or();
+                if self.peek() == Some(&Token::Close) {
+                    self.pos += 1;
+                }
+                filter
+            }
+            Token::Field(filter) => Some(filter),
+            Token::Text(text) => {
+                self.text.push(text);
+                None
+            }
+            // operators missing an operand
+            Token::And | Token::Or | Token::Close => None,
+        }
+    }
+}
+
+fn tokenize(query: &str) -> Vec<Token> {
+    let mut words = vec![];
+    let mut word = String::new();
+    let mut in_quotes = false;
+    for c in query.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                word.push(c);
+            }
+            '(' | ')' if !in_quotes => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+                words.push(c.to_string());
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+            c => word.push(c),
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+
+    words
+        .into_iter()
+        .map(|word| match word.as_str() {
+            "(" => Token::Open,
+            ")" => Token::Close,
+            "AND" => Token::And,
+            "OR" => Token::Or,
+            "NOT" => Token::Not,
+            _ => field(&word).map(Token::Field).unwrap_or(Token::Text(word)),
+        })
+        .collect()
+}
+
+fn field(word: &str) -> Option<SearchFilter> {
+    if let Some(tag) = word.strip_prefix('#') {
+        let tag = tag.trim_matches('"');
+        return (!tag.is_empty()).then(|| SearchFilter::Tag(tag.to_lowercase()));
+    }
+
+    let (key, value) = word.split_once(':')?;
+    let value = value.trim_matches('"');
+    if value.is_empty() {
+        return None;
+    }
+    match key {
+        "in" => Some(SearchFilter::Path(value.to_string())),
+        "type" => value
+            .split('|')
+            .map(|kind| {
+                Some(SearchFilter::Type(match kind.to_lowercase().as_str() {
+                    "" => return None,
+                    "drawing" => "svg".to_string(),
+                    kind => kind.to_string(),
+                }))
+            })
+            .reduce(or)
+            .flatten(),
+        "modified" => modified(value),
+        "owner" => Some(SearchFilter::Owner(value.to_lowercase())),
+        "by" => Some(SearchFilter::EditedBy(value.to_lowercase())),
+        "shared" => match value.to_lowercase().as_str() {
+            "yes" | "true" => Some(SearchFilter::Shared(true)),
+            "no" | "false" => Some(SearchFilter::Shared(false)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn modified(value: &str) -> Option<SearchFilter> {
+    let (comparison, value) = [">=", "<=", ">", "<", "="]
+        .into_iter()
+        .find_map(|op| value.strip_prefix(op).map(|rest| (op, rest)))
+        .unwrap_or(("", value));
+
+    let (start, end) = instant_range(value)?;
+    let (after, before) = match comparison {
+        ">" => (Some(end), None),
+        ">=" => (Some(start), None),
+        "<" => (None, Some(start)),
+        "<=" => (None, Some(end)),
+        _ => (Some(start), (end != start).then_some(end)),
+    };
+    Some(SearchFilter::Modified { after, before })
+}
+
+/// the span of time a value refers to, in ms since the epoch: a whole day for dates, or an
+/// instant for times ago
+fn instant_range(value: &str) -> Option<(u64, u64)> {
+    let now = get_time().0 as u64;
+    let today = now - now % DAY_MS;
+    match value {
+        "today" => return Some((today, today + DAY_MS)),
+        "yesterday" => return Some((today - DAY_MS, today)),
+        _ => {}
+    }
+
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        let start = date.and_hms_opt(0, 0, 0)?.and_utc().timestamp_millis();
+        let start = u64::try_from(start).ok()?;
+        return Some((start, start + DAY_MS));
+    }
+
+    let unit = match value.chars().last()? {
+        'd' => DAY_MS,
+        'w' => 7 * DAY_MS,
+        'm' => 30 * DAY_MS,
+        'y' => 365 * DAY_MS,
+        _ => return None,
+    };
+    let count: u64 = value[..value.len() - 1].parse().ok()?;
+    let instant = now.saturating_sub(count.checked_mul(unit)?);
+    Some((instant, instant))
+}
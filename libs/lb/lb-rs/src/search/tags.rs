@@ -0,0 +1,88 @@
+/// The tags of a markdown document: those listed under `tags` in its front matter, and inline
+/// `#tags` outside of code. Tags are lowercased, deduplicated and sorted.
+pub fn extract_tags(content: &str) -> Vec<String> {
+    let mut tags = vec![];
+    let mut body = content;
+
+    if let Some(rest) = content.strip_prefix("---\n") {
+        if let Some(end) = rest.find("\n---") {
+            front_matter_tags(&rest[..end], &mut tags);
+            body = &rest[end + 4..];
+        }
+    }
+
+    let mut in_code_block = false;
+    for line in body.lines() {
+        if line.trim_start().starts_with("```") {
+            in_code_block = !in_code_block;
+            continue;
+        }
+        if !in_code_block {
+            inline_tags(line, &mut tags);
+        }
+    }
+
+    tags.sort();
+    tags.dedup();
+    tags
+}
+
+/// `tags: [a, b]`, `tags: a, b` or a `tags:` line followed by `- a` items
+fn front_matter_tags(front_matter: &str, tags: &mut Vec<String>) {
+    let mut in_list = false;
+    for line in front_matter.lines() {
+        if in_list {
+            match line.trim_start().strip_prefix("- ") {
+                Some(item) => push_tag(item, tags),
+                None => in_list = false,
+            }
+            continue;
+        }
+
+        let Some(value) = line.strip_prefix("tags:") else {
+            continue;
+        };
+        let value = value.trim().trim_start_matches('[').trim_end_matches(']');
+        if value.is_empty() {
+            in_list = true;
+        } else {
+            for item in value.split(',') {
+                push_tag(item, tags);
+            }
+        }
+    }
+}
+
+fn inline_tags(line: &str, tags: &mut Vec<String>) {
+    let mut in_code = false;
+    let mut prev = ' ';
+    for (i, c) in line.char_indices() {
+        if c == '`' {
+            in_code = !in_code;
+        } else if c == '#' && !in_code && prev.is_whitespace() {
+            let tag: String = line[i + 1..]
+                .chars()
+                .take_while(|&c| is_tag_char(c))
+                .collect();
+            // `#1` is an issue number and `# heading` a heading, neither are tags
+            if tag.chars().any(|c| !c.is_ascii_digit()) {
+                push_tag(&tag, tags);
+            }
+        }
+        prev = c;
+    }
+}
+
+fn is_tag_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '-' | '_' | '/')
+}
+
+fn push_tag(tag: &str, tags: &mut Vec<String>) {
+    let tag = tag
+        .trim()
+        .trim_matches(|c| c == '"' || c == '\'')
+        .trim_start_matches('#');
+    if !tag.is_empty() {
+        tags.push(tag.to_lowercase());
+    }
+}
@@ -1,7 +1,7 @@
 use crate::experiments::{WelcomeDoc, cohort};
 use crate::model::account::{Account, MAX_USERNAME_LENGTH};
 use crate::model::api::{
-    DeleteAccountRequest, GetPublicKeyRequest, GetUsernameRequest, NewAccountRequestV2,
+    DeleteAccountRequest, GetPublicKeyRequest, GetUsernameRequest, NewAccountRequestV3,
 };
 use crate::model::errors::{LbErrKind, LbResult, core_err_unexpected};
 use crate::model::file_like::FileLike;
@@ -21,9 +21,18 @@ impl LocalLb {
     /// CoreError::ServerDisabled,
     /// CoreError::ServerUnreachable,
     /// CoreError::ClientUpdateRequired,
-    #[instrument(level = "debug", skip(self), err(Debug))]
     pub async fn create_account(
         &self, username: &str, api_url: &str, welcome_doc: bool,
+    ) -> LbResult<Account> {
+        self.create_account_with_invite(username, api_url, None, welcome_doc)
+            .await
+    }
+
+    /// Like [Self::create_account], for servers that only accept accounts by invitation, which
+    /// fail with CoreError::InviteRequired when the code is missing or wrong.
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn create_account_with_invite(
+        &self, username: &str, api_url: &str, invite_code: Option<&str>, welcome_doc: bool,
     ) -> LbResult<Account> {
         let username = String::from(username).to_lowercase();
 
@@ -45,7 +54,7 @@ impl LocalLb {
 
         let last_synced = self
             .client
-            .request(&account, NewAccountRequestV2::new(&account, &root))
+            .request(&account, NewAccountRequestV3::new(&account, &root, invite_code))
             .await?
             .last_synced;
 
@@ -219,6 +228,8 @@ impl LocalLb {
                 _ => core_err_unexpected(err),
             })?;
 
+        self.clear_search_index().await?;
+
         let mut tx = self.begin_tx().await;
         let db = tx.db();
 
@@ -228,6 +239,7 @@ impl LocalLb {
         db.root.clear()?;
         db.local_metadata.clear()?;
         db.pub_key_lookup.clear()?;
+        db.groups.clear()?;
 
         // todo: clear cache?
 
@@ -174,6 +174,86 @@ impl LocalLb {
             })
     }
 
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn migrate_documents(&self) -> LbResult<AdminMigrateDocumentsResponse> {
+        let account = self.get_account()?;
+        self.client
+            .request(account, AdminMigrateDocumentsRequest {})
+            .await
+            .map_err(|err| {
+                match err {
+                    ApiError::Endpoint(AdminMigrateDocumentsError::NotPermissioned) => {
+                        LbErrKind::InsufficientPermission
+                    }
+                    ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+                    ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
+                    _ => core_err_unexpected(err),
+                }
+                .into()
+            })
+    }
+
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn create_invite(
+        &self, expires_at: Option<UnixTimeMillis>, max_uses: Option<u32>,
+    ) -> LbResult<InviteInfo> {
+        let account = self.get_account()?;
+        self.client
+            .request(account, AdminCreateInviteRequest { expires_at, max_uses })
+            .await
+            .map(|resp| resp.invite)
+            .map_err(|err| {
+                match err {
+                    ApiError::Endpoint(AdminCreateInviteError::NotPermissioned) => {
+                        LbErrKind::InsufficientPermission
+                    }
+                    ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+                    ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
+                    _ => core_err_unexpected(err),
+                }
+                .into()
+            })
+    }
+
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn revoke_invite(&self, code: &str) -> LbResult<()> {
+        let account = self.get_account()?;
+        self.client
+            .request(account, AdminRevokeInviteRequest { code: code.to_string() })
+            .await
+            .map_err(|err| {
+                match err {
+                    ApiError::Endpoint(AdminRevokeInviteError::NotPermissioned) => {
+                        LbErrKind::InsufficientPermission
+                    }
+                    ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+                    ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
+                    _ => core_err_unexpected(err),
+                }
+                .into()
+            })
+    }
+
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn list_invites(&self) -> LbResult<Vec<InviteInfo>> {
+        let account = self.get_account()?;
+        self.client
+            .request(account, AdminListInvitesRequest {})
+            .await
+            .map(|resp| resp.invites)
+            .map_err(|err| {
+                match err {
+                    ApiError::Endpoint(AdminListInvitesError::NotPermissioned) => {
+                        LbErrKind::InsufficientPermission
+                    }
+                    ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+                    ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
+                    _ => core_err_unexpected(err),
+                }
+                .into()
+            })
+    }
+
     #[instrument(level = "debug", skip(self), err(Debug))]
     pub async fn set_user_tier(&self, username: &str, info: AdminSetUserTierInfo) -> LbResult<()> {
         let account = self.get_account()?;
@@ -1,9 +1,12 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use crate::LocalLb;
+use crate::model::api::{ContentFormat, GetDocRequestV2};
+use crate::model::chunks::ChunkManifest;
 use crate::model::clock::get_time;
 use crate::model::crypto::{AESKey, DecryptedDocument, EncryptedDocument};
-use crate::model::errors::{LbErrKind, LbResult};
+use crate::model::delta::DocumentDelta;
+use crate::model::errors::{LbErrKind, LbResult, Unexpected};
 use crate::model::file_like::FileLike;
 use crate::model::file_metadata::{DocumentHmac, FileType};
 use crate::model::secret_filename::HmacSha256;
@@ -14,6 +17,7 @@ use uuid::Uuid;
 
 use super::activity;
 use super::events::Actor;
+use super::streams::DocumentContent;
 
 impl LocalLb {
     #[instrument(level = "debug", skip(self), err(Debug))]
@@ -67,6 +71,19 @@ impl LocalLb {
     pub async fn read_document_with_hmac(
         &self, id: Uuid, user_activity: bool,
     ) -> LbResult<(Option<DocumentHmac>, DecryptedDocument)> {
+        let (hmac, content) = self.read_raw_document_with_hmac(id, user_activity).await?;
+        match content {
+            DocumentContent::Chunked(manifest) => {
+                Ok((hmac, self.read_chunks(id, &manifest).await?))
+            }
+            DocumentContent::Whole(doc) => Ok((hmac, doc)),
+        }
+    }
+
+    /// reads the document as it's stored, which for chunked documents is their manifest
+    pub(crate) async fn read_raw_document_with_hmac(
+        &self, id: Uuid, user_activity: bool,
+    ) -> LbResult<(Option<DocumentHmac>, DocumentContent)> {
         // get info + on-disk bytes so we can decrypt without holding the lock
         let info: Option<(DocumentHmac, AESKey, Option<EncryptedDocument>)> = {
             let tx = self.ro_tx().await;
@@ -97,16 +114,46 @@ impl LocalLb {
 
         // do decrypt + decompress without holding the lock; fetch from the
         // server first if the blob wasn't already local.
-        let (hmac, doc) = match info {
-            None => (None, vec![]),
+        let mut fetched = false;
+        let (hmac, content) = match info {
+            None => (None, DocumentContent::Whole(vec![])),
             Some((hmac, key, local_blob)) => {
                 let encrypted = match local_blob {
                     Some(blob) => blob,
                     // todo: if document not found -- need to trigger a pull
-                    None => self.fetch_doc(id, hmac).await?,
+                    None => {
+                        fetched = true;
+                        self.fetch_doc(id, hmac).await?
+                    }
                 };
                 let doc = decrypt_decompress_document(&key, &encrypted)?;
-                (Some(hmac), doc)
+                // fetched just now if it wasn't on this device, which records its format
+                let formats = self.ro_tx().await.db().content_formats_of(id);
+                let format = formats.get(&hmac).copied().unwrap_or_default();
+                let content = match format {
+                    ContentFormat::Document => DocumentContent::Whole(doc),
+                    ContentFormat::Manifest => {
+                        DocumentContent::Chunked(ChunkManifest::from_bytes(&doc)?)
+                    }
+                    ContentFormat::Delta => {
+                        // materialize the delta once so later reads don't fetch its base again
+                        let doc = self.apply_deltas(id, &key, doc, format, &formats).await?;
+                        let (materialized_hmac, encrypted) = compress_encrypt_document(&key, &doc)?;
+                        if materialized_hmac != hmac {
+                            return Err(LbErrKind::Unexpected(format!(
+                                "delta for {id} did not reproduce its document"
+                            ))
+                            .into());
+                        }
+                        self.docs.insert(id, Some(hmac), &encrypted).await?;
+                        let mut tx = self.begin_tx().await;
+                        tx.db()
+                            .set_content_format(id, hmac, ContentFormat::Document)?;
+                        tx.end();
+                        DocumentContent::Whole(doc)
+                    }
+                };
+                (Some(hmac), content)
             }
         };
 
@@ -115,7 +162,12 @@ impl LocalLb {
                 .await?;
         }
 
-        Ok((hmac, doc))
+        // a document fetched on demand grows the cache
+        if fetched {
+            self.evict_on_demand_docs().await.log_and_ignore();
+        }
+
+        Ok((hmac, content))
     }
 
     #[instrument(level = "debug", skip(self, content), err(Debug))]
@@ -173,6 +225,51 @@ impl LocalLb {
         Ok(hmac)
     }
 
+    /// Documents edited on another device may have been uploaded as a [DocumentDelta] against a
+    /// prior version. Applies deltas to their bases until it reaches complete content. Bases are
+    /// fetched from the server if they aren't on this device, but aren't stored. `format` is how
+    /// `doc` is read, and `formats` how the versions of the document on this device are.
+    pub(crate) async fn apply_deltas(
+        &self, id: Uuid, key: &AESKey, mut doc: DecryptedDocument, mut format: ContentFormat,
+        formats: &HashMap<DocumentHmac, ContentFormat>,
+    ) -> LbResult<DecryptedDocument> {
+        let mut deltas = vec![];
+        while format == ContentFormat::Delta {
+            let delta = DocumentDelta::from_bytes(&doc)?;
+            let base = match self.docs.maybe_get(id, Some(delta.base)).await? {
+                Some(base) => {
+                    format = formats.get(&delta.base).copied().unwrap_or_default();
+                    base
+                }
+                None => {
+                    let response = self
+                        .client
+                        .request(self.get_account()?, GetDocRequestV2 { id, hmac: delta.base })
+                        .await?;
+                    format = response.format;
+                    response.content
+                }
+            };
+            doc = decrypt_decompress_document(key, &base)?;
+            deltas.push(delta);
+        }
+
+        for delta in deltas.into_iter().rev() {
+            doc = delta.apply(&doc)?;
+        }
+        Ok(doc)
+    }
+
+    /// the manifest stored on this device for a version of a chunked document, if it's here
+    pub(crate) async fn stored_manifest(
+        &self, id: Uuid, hmac: DocumentHmac, key: &AESKey,
+    ) -> LbResult<Option<ChunkManifest>> {
+        let Some(encrypted) = self.docs.maybe_get(id, Some(hmac)).await? else {
+            return Ok(None);
+        };
+        Ok(Some(ChunkManifest::from_bytes(&decrypt_decompress_document(key, &encrypted)?)?))
+    }
+
     pub(crate) async fn cleanup(&self) -> LbResult<()> {
         let tx = self.ro_tx().await;
         let db = tx.db();
@@ -182,20 +279,59 @@ impl LocalLb {
         let base_files = tree.base.all_files()?.into_iter();
         let local_files = tree.staged.all_files()?.into_iter();
 
-        let file_hmacs = base_files
-            .chain(local_files)
+        let files = base_files.chain(local_files).collect::<Vec<_>>();
+
+        let file_hmacs = files
+            .iter()
             .filter_map(|f| f.document_hmac().map(|hmac| (*f.id(), *hmac)))
             .collect::<HashSet<_>>();
 
-        self.docs.retain(file_hmacs).await?;
+        // chunks waiting to be uploaded are kept, as are the chunks of the base and local
+        // manifests of documents with chunks here
+        let mut chunks: HashSet<(Uuid, DocumentHmac)> = db
+            .chunk_uploads
+            .get()
+            .iter()
+            .flat_map(|(id, (_, chunks))| chunks.iter().map(move |hmac| (*id, *hmac)))
+            .collect();
+        let mut manifests = vec![];
+        let mut tree = (&db.base_metadata).to_staged(&db.local_metadata).to_lazy();
+        for &(id, hmac) in &file_hmacs {
+            if db.content_format(id, hmac) == ContentFormat::Manifest {
+                manifests.push((id, hmac, tree.decrypt_key(&id, &self.keychain)?));
+            }
+        }
 
         drop(tx);
 
+        for (id, hmac, key) in manifests {
+            if let Some(manifest) = self.stored_manifest(id, hmac, &key).await? {
+                chunks.extend(manifest.chunks.iter().map(|chunk| (id, chunk.hmac)));
+            }
+        }
+
+        let mut tx = self.begin_tx().await;
+        let db = tx.db();
+        let formats: Vec<(Uuid, DocumentHmac)> = db
+            .content_formats
+            .get()
+            .iter()
+            .flat_map(|(id, formats)| formats.keys().map(move |hmac| (*id, *hmac)))
+            .filter(|version| !file_hmacs.contains(version))
+            .collect();
+        for (id, hmac) in formats {
+            db.set_content_format(id, hmac, ContentFormat::Document)?;
+        }
+        tx.end();
+
+        self.docs.retain(file_hmacs).await?;
+        self.docs.retain_chunks(chunks).await?;
+
         Ok(())
     }
 }
 
-fn compress_encrypt_document(
+pub(crate) fn compress_encrypt_document(
     key: &AESKey, content: &[u8],
 ) -> LbResult<(DocumentHmac, EncryptedDocument)> {
     let hmac: DocumentHmac = {
@@ -210,7 +346,7 @@ fn compress_encrypt_document(
     Ok((hmac, encrypted))
 }
 
-fn decrypt_decompress_document(
+pub(crate) fn decrypt_decompress_document(
     key: &AESKey, encrypted: &EncryptedDocument,
 ) -> LbResult<DecryptedDocument> {
     let compressed = symkey::decrypt(key, encrypted)?;
@@ -195,7 +195,10 @@ impl LocalLb {
         if tree.calculate_deleted(&id)? {
             return Err(LbErrKind::FileNonexistent.into());
         }
-        if tree.access_mode(Owner(self.keychain.get_pk()?), &id)? < Some(UserAccessMode::Read) {
+        let owner = Owner(self.keychain.get_pk()?);
+        if tree.access_mode_in_groups(owner, &self.keychain.groups()?, &id)?
+            < Some(UserAccessMode::Read)
+        {
             return Err(LbErrKind::FileNonexistent.into());
         }
 
@@ -204,6 +207,23 @@ impl LocalLb {
         Ok(file)
     }
 
+    /// The access this account has to a file, whether it's through its own shares, its
+    /// ancestors' or a group's: [UserAccessMode::Owner] for its own files, and `None` for files
+    /// it can't see.
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn get_access_mode(&self, id: Uuid) -> LbResult<Option<UserAccessMode>> {
+        let tx = self.ro_tx().await;
+        let db = tx.db();
+
+        let tree = (&db.base_metadata).to_staged(&db.local_metadata).to_lazy();
+        let owner = Owner(self.keychain.get_pk()?);
+        // an account's own root is shared with it for writing, like any other share
+        if tree.find(&id)?.owner() == owner {
+            return Ok(Some(UserAccessMode::Owner));
+        }
+        tree.access_mode_in_groups(owner, &self.keychain.groups()?, &id)
+    }
+
     #[instrument(level = "debug", skip(self), err(Debug))]
     pub async fn get_file_link_url(&self, id: Uuid) -> LbResult<String> {
         let tx = self.ro_tx().await;
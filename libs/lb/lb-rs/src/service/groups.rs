@@ -0,0 +1,302 @@
+use crate::LocalLb;
+use crate::io::CoreDb;
+use crate::model::api::{
+    AddGroupMemberRequest, CreateGroupRequest, GetGroupsRequest, GetUsernameRequest, GroupInfo,
+    RemoveGroupMemberRequest,
+};
+use crate::model::errors::{LbErrKind, LbResult};
+use crate::model::file::ShareMode;
+use crate::model::file_metadata::Owner;
+use crate::model::tree_like::TreeLike;
+use crate::model::{pubkey, symkey};
+use crate::service::events::Actor;
+use libsecp256k1::{PublicKey, SecretKey};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// A named set of users that files can be shared with as one, see
+/// [LocalLb::share_file_with_group]. Adding or removing a member changes their access to every
+/// file shared with the group at once.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Group {
+    pub id: Uuid,
+    pub name: String,
+    pub owner: String,
+    pub members: Vec<String>,
+}
+
+impl LocalLb {
+    /// creates a group owned by this account, which is its first member. Only the owner can add
+    /// and remove members.
+    ///
+    /// callers of this function should be prepared to handle:
+    /// - [crate::LbErrKind::ServerUnreachable]
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn create_group(&self, name: &str) -> LbResult<Group> {
+        let account = self.get_account()?;
+        let id = Uuid::new_v4();
+        let private_key = pubkey::generate_key();
+
+        self.client
+            .request(
+                account,
+                CreateGroupRequest {
+                    id,
+                    name: name.to_string(),
+                    public_key: PublicKey::from_secret_key(&private_key),
+                    encrypted_private_key: self
+                        .encrypt_group_key(&private_key, &account.public_key())?,
+                },
+            )
+            .await?;
+        self.fetch_groups().await?;
+
+        self.get_group(id).await
+    }
+
+    /// adds `username` to a group this account owns, giving them access to the files shared
+    /// with it
+    ///
+    /// callers of this function should be prepared to handle:
+    /// - [crate::LbErrKind::GroupNonexistent]
+    /// - [crate::LbErrKind::InsufficientPermission]
+    /// - [crate::LbErrKind::UsernameNotFound]
+    /// - [crate::LbErrKind::ShareAlreadyExists], if they're already a member
+    /// - [crate::LbErrKind::ServerUnreachable]
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn add_group_member(&self, group_id: Uuid, username: &str) -> LbResult<()> {
+        let member = self.public_key_of(username).await?;
+        let group = self.group_info(group_id).await?;
+        // they're sent the files shared with the group's previous keys too
+        let mut private_keys = vec![];
+        for public_key in group.public_keys() {
+            let private_key = self
+                .keychain
+                .get_group_key(&public_key)?
+                .ok_or(LbErrKind::GroupNonexistent)?;
+            private_keys.push(self.encrypt_group_key(&private_key, &member)?);
+        }
+        let encrypted_private_key = private_keys.pop().ok_or(LbErrKind::GroupNonexistent)?;
+
+        self.client
+            .request(
+                self.get_account()?,
+                AddGroupMemberRequest {
+                    group_id,
+                    member,
+                    encrypted_private_key,
+                    previous_private_keys: private_keys,
+                },
+            )
+            .await?;
+        self.fetch_groups().await
+    }
+
+    /// removes `username` from a group this account owns. They're no longer sent the files
+    /// shared with the group, and the group gets a new keypair, encrypted for the remaining
+    /// members only, so files shared with the group from now on aren't shared with a key they
+    /// had. They keep the keys of files already shared with the group, as with
+    /// [LocalLb::revoke_share]; rotate those files' keys to stop them reading what's written to
+    /// them from now on.
+    ///
+    /// callers of this function should be prepared to handle:
+    /// - [crate::LbErrKind::GroupNonexistent]
+    /// - [crate::LbErrKind::InsufficientPermission], including for removing the group's owner
+    /// - [crate::LbErrKind::ShareNonexistent], if they're not a member
+    /// - [crate::LbErrKind::ServerUnreachable]
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn remove_group_member(&self, group_id: Uuid, username: &str) -> LbResult<()> {
+        let member = self.public_key_of(username).await?;
+        // the new key goes to exactly the members the server has
+        self.fetch_groups().await?;
+        let group = self.group_info(group_id).await?;
+
+        let private_key = pubkey::generate_key();
+        let mut encrypted_private_keys = vec![];
+        for remaining in group.members.iter().filter(|m| m.public_key != member) {
+            encrypted_private_keys.push((
+                remaining.public_key,
+                self.encrypt_group_key(&private_key, &remaining.public_key)?,
+            ));
+        }
+
+        self.client
+            .request(
+                self.get_account()?,
+                RemoveGroupMemberRequest {
+                    group_id,
+                    member,
+                    public_key: PublicKey::from_secret_key(&private_key),
+                    encrypted_private_keys,
+                },
+            )
+            .await?;
+        self.fetch_groups().await
+    }
+
+    /// shares a file with every member of a group, including those added later
+    ///
+    /// callers of this function should be prepared to handle:
+    /// - [crate::LbErrKind::GroupNonexistent]
+    /// - [crate::LbErrKind::ShareAlreadyExists]
+    /// - [crate::LbErrKind::InsufficientPermission]
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn share_file_with_group(
+        &self, id: Uuid, group_id: Uuid, mode: ShareMode,
+    ) -> LbResult<()> {
+        let mut tx = self.begin_tx().await;
+        let db = tx.db();
+
+        let group = Owner(
+            db.groups
+                .get()
+                .get(&group_id)
+                .ok_or(LbErrKind::GroupNonexistent)?
+                .public_key,
+        );
+        let mut tree = (&db.base_metadata)
+            .to_staged(&mut db.local_metadata)
+            .to_lazy();
+
+        tree.add_share(id, group, mode, &self.keychain)?;
+
+        tx.end();
+
+        self.events.meta_changed(Actor::User(None));
+
+        Ok(())
+    }
+
+    /// the groups this account is a member of, as of the last sync
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn list_groups(&self) -> LbResult<Vec<Group>> {
+        let tx = self.ro_tx().await;
+        let db = tx.db();
+
+        let mut groups: Vec<Group> = db.groups.get().values().map(|g| to_group(db, g)).collect();
+        groups.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(groups)
+    }
+
+    async fn get_group(&self, id: Uuid) -> LbResult<Group> {
+        let tx = self.ro_tx().await;
+        let db = tx.db();
+        let group = db
+            .groups
+            .get()
+            .get(&id)
+            .ok_or(LbErrKind::GroupNonexistent)?;
+        Ok(to_group(db, group))
+    }
+
+    async fn group_info(&self, id: Uuid) -> LbResult<GroupInfo> {
+        let tx = self.ro_tx().await;
+        Ok(tx
+            .db()
+            .groups
+            .get()
+            .get(&id)
+            .ok_or(LbErrKind::GroupNonexistent)?
+            .clone())
+    }
+
+    /// Replaces the groups this account is a member of with the server's, along with the group
+    /// keys in the keychain, and learns the usernames of their members.
+    pub(crate) async fn fetch_groups(&self) -> LbResult<()> {
+        let account = self.get_account()?;
+        let groups = self
+            .client
+            .request(account, GetGroupsRequest {})
+            .await?
+            .groups;
+
+        let missing_members: Vec<Owner> = {
+            let tx = self.ro_tx().await;
+            let db = tx.db();
+            groups
+                .iter()
+                .flat_map(|group| &group.members)
+                .map(|member| Owner(member.public_key))
+                .filter(|member| !db.pub_key_lookup.get().contains_key(member))
+                .collect()
+        };
+        let mut usernames = HashMap::new();
+        for member in missing_members {
+            if let Ok(response) = self
+                .client
+                .request(account, GetUsernameRequest { key: member.0 })
+                .await
+            {
+                usernames.insert(member, response.username);
+            }
+        }
+
+        let mut tx = self.begin_tx().await;
+        let db = tx.db();
+
+        let changed = db.groups.get().len() != groups.len()
+            || groups
+                .iter()
+                .any(|group| db.groups.get().get(&group.id) != Some(group));
+
+        db.groups.clear()?;
+        for group in &groups {
+            db.groups.insert(group.id, group.clone())?;
+            // so shares with the group show its name
+            for public_key in group.public_keys() {
+                db.pub_key_lookup
+                    .insert(Owner(public_key), group.name.clone())?;
+            }
+        }
+        for (member, username) in usernames {
+            db.pub_key_lookup.insert(member, username)?;
+        }
+        self.keychain.set_groups(&groups)?;
+
+        tx.end();
+
+        if changed {
+            self.events.meta_changed(Actor::Sync);
+        }
+
+        Ok(())
+    }
+
+    /// encrypts a group's private key with the key this account, the group's owner, shares with
+    /// `member`
+    fn encrypt_group_key(
+        &self, private_key: &SecretKey, member: &PublicKey,
+    ) -> LbResult<crate::model::crypto::AESEncrypted<[u8; 32]>> {
+        let shared_secret = pubkey::get_aes_key(&self.get_account()?.private_key, member)?;
+        symkey::encrypt(&shared_secret, &private_key.serialize())
+    }
+}
+
+/// whether `owner` is the public key of a group rather than of an account
+pub(crate) fn is_group(db: &CoreDb, owner: &Owner) -> bool {
+    db.groups
+        .get()
+        .values()
+        .any(|group| group.public_keys().any(|public_key| public_key == owner.0))
+}
+
+fn to_group(db: &CoreDb, group: &GroupInfo) -> Group {
+    let username = |pk: &PublicKey| {
+        db.pub_key_lookup
+            .get()
+            .get(&Owner(*pk))
+            .cloned()
+            .unwrap_or_else(|| String::from("<unknown>"))
+    };
+    Group {
+        id: group.id,
+        name: group.name.clone(),
+        owner: username(&group.owner.0),
+        members: group
+            .members
+            .iter()
+            .map(|member| username(&member.public_key))
+            .collect(),
+    }
+}
@@ -22,7 +22,7 @@ impl LocalLb {
             return Err(LbErrKind::RootNonexistent)?;
         }
 
-        tree.validate(Owner(self.keychain.get_pk()?))?;
+        tree.validate_in_groups(Owner(self.keychain.get_pk()?), &self.keychain.groups()?)?;
 
         for id in tree.ids() {
             let name = tree.name(&id, &self.keychain)?;
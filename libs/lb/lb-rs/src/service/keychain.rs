@@ -2,19 +2,30 @@ use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
 
 use crate::LocalLb;
+use crate::model::access_info::EncryptedFolderAccessKey;
 use crate::model::account::Account;
+use crate::model::api::GroupInfo;
 use crate::model::crypto::AESKey;
-use crate::model::errors::{LbErrKind, LbResult};
+use crate::model::errors::{LbErrKind, LbResult, Unexpected};
+use crate::model::file_metadata::Owner;
+use crate::model::{pubkey, symkey};
 use db_rs::hasher::UuidIdentityHasherBuilder;
-use libsecp256k1::PublicKey;
+use libsecp256k1::{PublicKey, SecretKey};
 use tokio::sync::OnceCell;
 use uuid::Uuid;
 
-pub type KeyCache = Arc<RwLock<HashMap<Uuid, AESKey, UuidIdentityHasherBuilder>>>;
+/// file keys by id, alongside the nonce of the folder access key each was decrypted from. Keys are
+/// replaced when a share is revoked with key rotation, which re-encrypts the folder access key, so
+/// a cached key only applies to metadata whose folder access key has the same nonce.
+pub type KeyCache = Arc<RwLock<HashMap<Uuid, (Vec<u8>, AESKey), UuidIdentityHasherBuilder>>>;
+
+/// private keys of the groups this account is a member of, by the groups' public keys
+pub type GroupKeys = Arc<RwLock<HashMap<Owner, SecretKey>>>;
 
 #[derive(Default, Clone)]
 pub struct Keychain {
     key_cache: KeyCache,
+    group_keys: GroupKeys,
     account: Arc<OnceCell<Account>>,
     public_key: Arc<OnceCell<PublicKey>>,
 }
@@ -31,6 +42,7 @@ impl From<Option<&Account>> for Keychain {
                     account: Arc::new(OnceCell::from(account)),
                     public_key: Arc::new(OnceCell::from(pk)),
                     key_cache,
+                    group_keys: Default::default(),
                 }
             }
             None => Self::default(),
@@ -71,16 +83,60 @@ impl Keychain {
         Ok(())
     }
 
-    pub fn contains_aes_key(&self, id: &Uuid) -> LbResult<bool> {
-        Ok(self.key_cache.read()?.contains_key(id))
+    pub fn insert_aes_key(
+        &self, id: Uuid, folder_access_key: &EncryptedFolderAccessKey, key: AESKey,
+    ) -> LbResult<()> {
+        self.key_cache
+            .write()?
+            .insert(id, (folder_access_key.nonce.clone(), key));
+        Ok(())
     }
 
-    pub fn insert_aes_key(&self, id: Uuid, key: AESKey) -> LbResult<()> {
-        self.key_cache.write()?.insert(id, key);
+    pub fn get_aes_key(
+        &self, id: &Uuid, folder_access_key: &EncryptedFolderAccessKey,
+    ) -> LbResult<Option<AESKey>> {
+        Ok(self
+            .key_cache
+            .read()?
+            .get(id)
+            .filter(|(nonce, _)| nonce == &folder_access_key.nonce)
+            .map(|(_, key)| *key))
+    }
+
+    /// replaces the group keys with those of `groups`, decrypting the private keys, current and
+    /// previous, each group's owner encrypted for this account. Groups this account isn't a
+    /// member of are skipped.
+    pub fn set_groups<'a>(&self, groups: impl IntoIterator<Item = &'a GroupInfo>) -> LbResult<()> {
+        let account = self.get_account()?;
+        let pk = self.get_pk()?;
+
+        let mut group_keys = HashMap::new();
+        for group in groups {
+            let Some(member) = group.members.iter().find(|m| m.public_key == pk) else {
+                continue;
+            };
+            let shared_secret = pubkey::get_aes_key(&account.private_key, &group.owner.0)?;
+            let private_keys = member
+                .previous_private_keys
+                .iter()
+                .chain(Some(&member.encrypted_private_key));
+            for (public_key, private_key) in group.public_keys().zip(private_keys) {
+                let private_key = symkey::decrypt(&shared_secret, private_key)?;
+                let private_key = SecretKey::parse(&private_key).map_unexpected()?;
+                group_keys.insert(Owner(public_key), private_key);
+            }
+        }
+
+        *self.group_keys.write()? = group_keys;
         Ok(())
     }
 
-    pub fn get_aes_key(&self, id: &Uuid) -> LbResult<Option<AESKey>> {
-        Ok(self.key_cache.read()?.get(id).copied())
+    pub fn get_group_key(&self, group: &PublicKey) -> LbResult<Option<SecretKey>> {
+        Ok(self.group_keys.read()?.get(&Owner(*group)).cloned())
+    }
+
+    /// the public keys of the groups this account is a member of
+    pub fn groups(&self) -> LbResult<Vec<Owner>> {
+        Ok(self.group_keys.read()?.keys().copied().collect())
     }
 }
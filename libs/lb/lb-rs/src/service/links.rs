@@ -0,0 +1,704 @@
+//! The links between documents. Links are parsed out of markdown documents as they're indexed,
+//! see [crate::subscribers::search], and resolved against the file tree whenever they're asked
+//! for, so renames and moves are reflected without reading any documents again.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::LocalLb;
+use crate::model::access_info::UserAccessMode;
+use crate::model::errors::{LbErrKind, LbResult};
+use crate::model::file::File;
+use crate::model::file_like::FileLike;
+use crate::model::file_metadata::{DocumentHmac, Owner};
+use crate::model::filename::MAX_FILENAME_LENGTH;
+use crate::model::tree_like::TreeLike;
+use crate::service::documents::compress_encrypt_document;
+use crate::service::events::Actor;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LinkKind {
+    /// `[text](target)`, `![alt](target)` or a `[label]: target` definition
+    Markdown,
+    /// `[[target]]`, `[[target|text]]` or `[[target#heading]]`
+    Wiki,
+    /// a bare url like `lb://<id>`, or one in `<angle brackets>`
+    Url,
+}
+
+/// A link as it's written in a document.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Link {
+    pub kind: LinkKind,
+    /// what the link points at: a url or path, or a title for wikilinks
+    pub target: String,
+    /// where `target` is in the document, in bytes
+    pub range: Range<usize>,
+}
+
+/// What a link points at.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LinkTarget {
+    File(Uuid),
+    /// a web page, email address or heading in the same document
+    External(String),
+    /// a path, title or file that doesn't exist, or that's a folder
+    Broken,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DocumentLink {
+    /// the document the link is in
+    pub from: Uuid,
+    pub link: Link,
+    pub to: LinkTarget,
+}
+
+impl LocalLb {
+    /// Links in other documents (or this one) that point at a file.
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn backlinks(&self, id: Uuid) -> LbResult<Vec<DocumentLink>> {
+        self.get_file_by_id(id).await?;
+        let mut links = self.resolved_links().await?;
+        links.retain(|link| link.to == LinkTarget::File(id));
+        Ok(links)
+    }
+
+    /// The links in a markdown document, in the order they appear.
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn outgoing_links(&self, id: Uuid) -> LbResult<Vec<DocumentLink>> {
+        self.get_file_by_id(id).await?;
+        let mut links = self.resolved_links().await?;
+        links.retain(|link| link.from == id);
+        Ok(links)
+    }
+
+    /// Links in any document that don't point at a file that exists.
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn broken_links(&self) -> LbResult<Vec<DocumentLink>> {
+        let mut links = self.resolved_links().await?;
+        links.retain(|link| link.to == LinkTarget::Broken);
+        Ok(links)
+    }
+
+    /// Moves a file like [LocalLb::move_file], rewriting links to it (or to documents in it)
+    /// so they still point at it, along with relative links in it that would otherwise break.
+    /// Links are rewritten in the same transaction as the move. Returns the documents that
+    /// were rewritten; documents this user can't edit are left alone.
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn move_file_with_link_update(
+        &self, id: &Uuid, new_parent: &Uuid,
+    ) -> LbResult<Vec<Uuid>> {
+        self.relocate_with_link_update(*id, Relocation::Move(*new_parent))
+            .await
+    }
+
+    /// Renames a file like [LocalLb::rename_file], rewriting links to it like
+    /// [LocalLb::move_file_with_link_update].
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn rename_file_with_link_update(
+        &self, id: &Uuid, new_name: &str,
+    ) -> LbResult<Vec<Uuid>> {
+        self.relocate_with_link_update(*id, Relocation::Rename(new_name))
+            .await
+    }
+
+    async fn relocate_with_link_update(
+        &self, id: Uuid, relocation: Relocation<'_>,
+    ) -> LbResult<Vec<Uuid>> {
+        if let Relocation::Rename(name) = relocation {
+            if name.len() > MAX_FILENAME_LENGTH {
+                return Err(LbErrKind::FileNameTooLong.into());
+            }
+        }
+
+        // the tree as it is and as it will be
+        let before = self.list_metadatas().await?;
+        let mut after = before.clone();
+        let file = after
+            .iter_mut()
+            .find(|f| f.id == id)
+            .ok_or(LbErrKind::FileNonexistent)?;
+        match relocation {
+            Relocation::Move(parent) => file.parent = parent,
+            Relocation::Rename(name) => file.name = name.to_string(),
+        }
+        let old = Resolver::new(&before);
+        let new = Resolver::new(&after);
+
+        let broken_by = |from: Uuid, link: &Link| match old.resolve(from, link) {
+            LinkTarget::File(target) if new.resolve(from, link) != LinkTarget::File(target) => {
+                Some(target)
+            }
+            _ => None,
+        };
+        let affected: Vec<Uuid> = self
+            .indexed_links()
+            .await?
+            .into_iter()
+            .filter(|(from, links)| links.iter().any(|link| broken_by(*from, link).is_some()))
+            .map(|(from, _)| from)
+            .collect();
+
+        let keys = {
+            let tx = self.ro_tx().await;
+            let db = tx.db();
+            let mut tree = (&db.base_metadata).to_staged(&db.local_metadata).to_lazy();
+            let owner = Owner(self.keychain.get_pk()?);
+            let groups = self.keychain.groups()?;
+
+            let mut keys = vec![];
+            for doc in affected {
+                if tree.access_mode_in_groups(owner, &groups, &doc)? >= Some(UserAccessMode::Write)
+                {
+                    keys.push((doc, tree.decrypt_key(&doc, &self.keychain)?));
+                }
+            }
+            keys
+        };
+
+        // rewrite documents against what was read, so a concurrent edit fails the move
+        let mut rewrites = vec![];
+        for (doc, key) in keys {
+            let (read_hmac, content) = self.read_document_with_hmac(doc, false).await?;
+            let mut content = String::from_utf8_lossy(&content).into_owned();
+
+            let mut changed = false;
+            for link in parse_links(&content).into_iter().rev() {
+                let Some(target) = broken_by(doc, &link) else {
+                    continue;
+                };
+                if let Some(rewritten) = new.rewrite(doc, &link, target, &content, &old) {
+                    content.replace_range(link.range, &rewritten);
+                    changed = true;
+                }
+            }
+            if !changed {
+                continue;
+            }
+
+            let (hmac, encrypted) = compress_encrypt_document(&key, content.as_bytes())?;
+            self.docs.insert_pending(doc, hmac, &encrypted).await?;
+            rewrites.push(Rewrite { id: doc, read_hmac, hmac, size: encrypted.value.len() });
+        }
+
+        {
+            let mut tx = self.begin_tx().await;
+            let db = tx.db();
+            let mut tree = (&db.base_metadata)
+                .to_staged(&mut db.local_metadata)
+                .to_lazy();
+
+            for rewrite in &rewrites {
+                if tree.find(&rewrite.id)?.document_hmac() != rewrite.read_hmac.as_ref() {
+                    return Err(LbErrKind::ReReadRequired.into());
+                }
+            }
+
+            let id = &tree.linked_by(&id)?.unwrap_or(id);
+            match relocation {
+                Relocation::Move(parent) => tree.move_file(id, &parent, &self.keychain)?,
+                Relocation::Rename(name) => tree.rename(id, name, &self.keychain)?,
+            }
+
+            for rewrite in &rewrites {
+                self.docs.promote_pending(rewrite.id, rewrite.hmac).await?;
+                tree.overwrite_document_hmac(
+                    &rewrite.id,
+                    Some(rewrite.hmac),
+                    Some(rewrite.size),
+                    &self.keychain,
+                )?;
+            }
+            tx.end();
+        }
+
+        self.events.meta_changed(Actor::User(None));
+        for rewrite in &rewrites {
+            self.events.doc_written(rewrite.id, Actor::User(None));
+        }
+
+        Ok(rewrites.into_iter().map(|rewrite| rewrite.id).collect())
+    }
+
+    /// Every link in every indexed document, resolved against the current tree. Documents kept
+    /// on demand only contribute links if they were indexed while they were on this device.
+    async fn resolved_links(&self) -> LbResult<Vec<DocumentLink>> {
+        let files = self.list_metadatas().await?;
+        let resolver = &Resolver::new(&files);
+
+        let mut links: Vec<DocumentLink> = self
+            .indexed_links()
+            .await?
+            .into_iter()
+            .filter(|(from, _)| resolver.files.contains_key(from))
+            .flat_map(|(from, links)| {
+                links.into_iter().map(move |link| {
+                    let to = resolver.resolve(from, &link);
+                    DocumentLink { from, link, to }
+                })
+            })
+            .collect();
+        links.sort_by_key(|link| (link.from, link.link.range.start));
+        Ok(links)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Relocation<'a> {
+    Move(Uuid),
+    Rename(&'a str),
+}
+
+/// a document rewritten for a move, waiting to be committed
+struct Rewrite {
+    id: Uuid,
+    read_hmac: Option<DocumentHmac>,
+    hmac: DocumentHmac,
+    size: usize,
+}
+
+/// The links in a markdown document, skipping any in code.
+pub fn parse_links(content: &str) -> Vec<Link> {
+    let mut links = vec![];
+    let mut fence: Option<&str> = None;
+    let mut offset = 0;
+
+    for line in content.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+
+        let trimmed = line.trim_start();
+        if let Some(open) = fence {
+            if trimmed.starts_with(open) {
+                fence = None;
+            }
+            continue;
+        }
+        if let Some(open) = ["```", "~~~"].into_iter().find(|f| trimmed.starts_with(f)) {
+            fence = Some(open);
+            continue;
+        }
+
+        line_links(line.trim_end_matches(['\n', '\r']), start, &mut links);
+    }
+
+    links
+}
+
+fn line_links(line: &str, offset: usize, links: &mut Vec<Link>) {
+    let bytes = line.as_bytes();
+    let mut line_links = vec![];
+    let mut i = 0;
+
+    // `[label]: target` definitions for reference links
+    let indent = line.len() - line.trim_start().len();
+    if bytes.get(indent) == Some(&b'[') && bytes.get(indent + 1) != Some(&b'[') {
+        if let Some(close) = line[indent..].find("]:").map(|i| indent + i) {
+            let rest = &line[close + 2..];
+            let target_start = close + 2 + rest.len() - rest.trim_start().len();
+            let target_end = line[target_start..]
+                .find(char::is_whitespace)
+                .map_or(line.len(), |i| target_start + i);
+            let target = line[target_start..target_end].trim_start_matches('<');
+            let target = target.trim_end_matches('>');
+            if !target.is_empty() {
+                let start = line[target_start..].find(target).unwrap() + target_start;
+                line_links.push((LinkKind::Markdown, start..start + target.len()));
+                i = target_end;
+            }
+        }
+    }
+
+    let mut in_code = false;
+    let mut open_brackets = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'`' => in_code = !in_code,
+            _ if in_code => {}
+            b'[' if bytes.get(i + 1) == Some(&b'[') => {
+                if let Some(len) = line[i + 2..].find("]]") {
+                    let inner = &line[i + 2..i + 2 + len];
+                    let title_len = inner.find(['|', '#']).unwrap_or(inner.len());
+                    let title = inner[..title_len].trim();
+                    if !title.is_empty() && !inner.contains('[') {
+                        let start = i + 2 + inner.find(title).unwrap();
+                        line_links.push((LinkKind::Wiki, start..start + title.len()));
+                    }
+                    i += 2 + len + 2;
+                    continue;
+                }
+                open_brackets += 1;
+            }
+            b'[' => open_brackets += 1,
+            b']' if bytes.get(i + 1) == Some(&b'(') && open_brackets > 0 => {
+                open_brackets -= 1;
+                if let Some((range, end)) = link_destination(line, i + 2) {
+                    line_links.push((LinkKind::Markdown, range));
+                    i = end;
+                    continue;
+                }
+            }
+            b']' => open_brackets = (open_brackets - 1).max(0),
+            _ if !line.is_char_boundary(i) => {}
+            _ => {
+                if let Some(len) = bare_url(line, i) {
+                    line_links.push((LinkKind::Url, i..i + len));
+                    i += len;
+                    continue;
+                }
+            }
+        }
+        i += 1;
+    }
+
+    line_links.sort_by_key(|(_, range)| range.start);
+    links.extend(line_links.into_iter().map(|(kind, range)| Link {
+        kind,
+        target: line[range.clone()].to_string(),
+        range: offset + range.start..offset + range.end,
+    }));
+}
+
+/// The target of an inline link starting at `start`, just inside its `(`, and where the link
+/// ends. Takes `(target)`, `(<target>)` and `(target "title")`.
+fn link_destination(line: &str, start: usize) -> Option<(Range<usize>, usize)> {
+    let bytes = line.as_bytes();
+    let mut i = start;
+    while bytes.get(i).is_some_and(|b| b.is_ascii_whitespace()) {
+        i += 1;
+    }
+
+    let range = if bytes.get(i) == Some(&b'<') {
+        let len = line[i + 1..].find('>')?;
+        i += 1 + len + 1;
+        i - len - 1..i - 1
+    } else {
+        let target_start = i;
+        let mut depth = 0;
+        while let Some(&b) = bytes.get(i) {
+            match b {
+                b'(' => depth += 1,
+                b')' if depth == 0 => break,
+                b')' => depth -= 1,
+                b if b.is_ascii_whitespace() => break,
+                _ => {}
+            }
+            i += 1;
+        }
+        target_start..i
+    };
+
+    // skip a title, if there is one
+    let end = i + line[i..].find(')')?;
+    (!range.is_empty()).then_some((range, end + 1))
+}
+
+/// The length of a url starting at `start` that isn't part of any markdown syntax.
+fn bare_url(line: &str, start: usize) -> Option<usize> {
+    let rest = &line[start..];
+    if !["lb://", "http://", "https://"]
+        .iter()
+        .any(|scheme| rest.starts_with(scheme))
+    {
+        return None;
+    }
+    if line[..start]
+        .chars()
+        .next_back()
+        .is_some_and(|c| !c.is_whitespace() && c != '<')
+    {
+        return None;
+    }
+
+    let len = rest
+        .find(|c: char| c.is_whitespace() || c == '>')
+        .unwrap_or(rest.len());
+    let url = rest[..len].trim_end_matches(['.', ',', ';', ':', '!', '?', ')']);
+    Some(url.len())
+}
+
+/// Resolves links against a snapshot of the tree, following the same rules as the editor.
+pub(crate) struct Resolver<'a> {
+    files: HashMap<Uuid, &'a File>,
+    children: HashMap<Uuid, Vec<&'a File>>,
+    root: Option<Uuid>,
+}
+
+impl<'a> Resolver<'a> {
+    pub(crate) fn new(files: &'a [File]) -> Self {
+        let mut children: HashMap<Uuid, Vec<&File>> = HashMap::new();
+        for file in files.iter().filter(|f| !f.is_root()) {
+            children.entry(file.parent).or_default().push(file);
+        }
+        Self {
+            files: files.iter().map(|f| (f.id, f)).collect(),
+            children,
+            root: files.iter().find(|f| f.is_root()).map(|f| f.id),
+        }
+    }
+
+    pub(crate) fn resolve(&self, from: Uuid, link: &Link) -> LinkTarget {
+        let target = match link.kind {
+            LinkKind::Wiki => self.resolve_wikilink(from, &link.target),
+            LinkKind::Markdown | LinkKind::Url => self.resolve_url(from, &link.target),
+        };
+        target.unwrap_or(LinkTarget::Broken)
+    }
+
+    /// `lb://<id>` and links from [crate::LocalLb::get_file_link_url] point at a file by id,
+    /// other urls are external, and anything else is a path, relative to the linking document
+    /// unless it starts with `/`.
+    pub(crate) fn resolve_url(&self, from: Uuid, url: &str) -> Option<LinkTarget> {
+        if let Some(id) = url.strip_prefix("lb://") {
+            return self.document(file_id(id)?);
+        }
+        if let Some((scheme, rest)) = url.split_once(':') {
+            if !scheme.contains('/') {
+                if let Some(id) = rest.rsplit_once("/open/").and_then(|(_, id)| file_id(id)) {
+                    return self.document(id);
+                }
+                return Some(LinkTarget::External(url.to_string()));
+            }
+        }
+        if url.starts_with('#') {
+            return Some(LinkTarget::External(url.to_string()));
+        }
+
+        let path = url.split(['#', '?']).next().unwrap_or_default();
+        let path = percent_decode(path);
+        let file = match path.strip_prefix('/') {
+            Some(path) => self.walk(self.root?, path)?,
+            None => self.walk(self.files.get(&from)?.parent, &path)?,
+        };
+        self.document(file.id)
+    }
+
+    /// A wikilink's title matches a document's name, with or without its extension, and a full
+    /// name beats one without its extension. Titles with a `/` are relative to the linking
+    /// document, others match anywhere, the nearest document winning. A tie is broken.
+    fn resolve_wikilink(&self, from: Uuid, title: &str) -> Option<LinkTarget> {
+        if let Some((dir, title)) = title.rsplit_once('/') {
+            let dir = match dir.strip_prefix('/') {
+                Some(dir) => self.walk(self.root?, dir)?,
+                None => self.walk(self.files.get(&from)?.parent, dir)?,
+            };
+            let siblings = self.children.get(&dir.id)?;
+            let docs = siblings.iter().filter(|f| f.is_document());
+            let exact: Vec<&&File> = docs
+                .clone()
+                .filter(|f| f.name.eq_ignore_ascii_case(title))
+                .collect();
+            let pool = if exact.is_empty() {
+                docs.filter(|f| title_matches(&f.name, title)).collect()
+            } else {
+                exact
+            };
+            return match pool.as_slice() {
+                [file] => Some(LinkTarget::File(file.id)),
+                _ => None,
+            };
+        }
+
+        let candidates: Vec<&File> = self
+            .files
+            .values()
+            .filter(|f| f.is_document() && title_matches(&f.name, title))
+            .copied()
+            .collect();
+        let exact: Vec<&File> = candidates
+            .iter()
+            .filter(|f| f.name.eq_ignore_ascii_case(title))
+            .copied()
+            .collect();
+        let pool = if exact.is_empty() { candidates } else { exact };
+
+        let from_path = self.ancestry(from);
+        let distance = |file: &File| {
+            let to_path = self.ancestry(file.id);
+            let common = from_path
+                .iter()
+                .zip(&to_path)
+                .take_while(|(a, b)| a == b)
+                .count();
+            from_path.len() + to_path.len() - 2 * common
+        };
+        let nearest = pool.iter().map(|f| distance(f)).min()?;
+        let mut tied = pool.into_iter().filter(|f| distance(f) == nearest);
+        let first = tied.next()?;
+        tied.next().is_none().then_some(LinkTarget::File(first.id))
+    }
+
+    /// A new target for a link in `from` so it points at `target` in this tree, written the way
+    /// the link was: by relative or absolute path, percent-encoded or not, and for wikilinks by
+    /// title or path, with or without an extension, falling back to a path if a title is
+    /// ambiguous. `old` is the tree the link
+    /// was written against.
+    fn rewrite(
+        &self, from: Uuid, link: &Link, target: Uuid, content: &str, old: &Resolver,
+    ) -> Option<String> {
+        let file = self.files.get(&target)?;
+        let dir = self.relative_path(self.files.get(&from)?.parent, file.parent);
+
+        let candidates = match link.kind {
+            LinkKind::Wiki => {
+                let old_title = link.target.rsplit('/').next().unwrap_or_default();
+                let with_extension = old
+                    .files
+                    .get(&target)
+                    .is_some_and(|f| f.name.eq_ignore_ascii_case(old_title));
+                let name = if with_extension { file.name.as_str() } else { stem(&file.name) };
+                let mut candidates =
+                    vec![name.to_string(), format!("{dir}{name}"), format!("{dir}{}", file.name)];
+                if link.target.contains('/') {
+                    candidates.swap(0, 1);
+                }
+                candidates
+            }
+            LinkKind::Markdown | LinkKind::Url => {
+                let split = link.target.find(['#', '?']).unwrap_or(link.target.len());
+                let (path, suffix) = link.target.split_at(split);
+                let mut path = if path.starts_with('/') {
+                    format!("/{}", self.names(target).join("/"))
+                } else {
+                    format!("{dir}{}", file.name)
+                };
+
+                let in_angle_brackets = content[..link.range.start].ends_with('<');
+                if link.target.contains('%')
+                    || (!in_angle_brackets && path.contains([' ', '(', ')']))
+                {
+                    path = percent_encode(&path);
+                }
+                vec![format!("{path}{suffix}")]
+            }
+        };
+
+        candidates.into_iter().find(|candidate| {
+            let link = Link { kind: link.kind, target: candidate.clone(), range: 0..0 };
+            self.resolve(from, &link) == LinkTarget::File(target)
+        })
+    }
+
+    /// the path from one folder to another, ending in `/` unless they're the same
+    fn relative_path(&self, from: Uuid, to: Uuid) -> String {
+        let from = self.ancestry(from);
+        let to_names = self.names(to);
+        let common = from
+            .iter()
+            .zip(&self.ancestry(to))
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        let mut path = "../".repeat(from.len() - common);
+        for name in &to_names[common..] {
+            path.push_str(name);
+            path.push('/');
+        }
+        path
+    }
+
+    /// the names of the folders from the root down to a file, and the file's
+    fn names(&self, id: Uuid) -> Vec<&str> {
+        self.ancestry(id)
+            .iter()
+            .filter_map(|id| self.files.get(id))
+            .map(|f| f.name.as_str())
+            .collect()
+    }
+
+    fn document(&self, id: Uuid) -> Option<LinkTarget> {
+        let file = self.files.get(&id)?;
+        file.is_document().then_some(LinkTarget::File(id))
+    }
+
+    /// follows a relative path from a folder, never above the root
+    fn walk(&self, from: Uuid, path: &str) -> Option<&'a File> {
+        let mut current = *self.files.get(&from)?;
+        for component in path.split('/') {
+            current = match component {
+                "" | "." => current,
+                ".." if current.is_root() => return None,
+                ".." => *self.files.get(&current.parent)?,
+                name => *self
+                    .children
+                    .get(&current.id)?
+                    .iter()
+                    .find(|f| f.name == name)?,
+            };
+        }
+        Some(current)
+    }
+
+    /// the ids from the root down to a file
+    fn ancestry(&self, id: Uuid) -> Vec<Uuid> {
+        let mut ancestry = vec![];
+        let mut current = id;
+        while let Some(file) = self.files.get(&current) {
+            if file.is_root() || ancestry.contains(&current) {
+                break;
+            }
+            ancestry.push(current);
+            current = file.parent;
+        }
+        ancestry.reverse();
+        ancestry
+    }
+}
+
+fn file_id(id: &str) -> Option<Uuid> {
+    let id = id.split(['#', '?', '/']).next()?;
+    Uuid::parse_str(id).ok()
+}
+
+/// whether a wikilink title is a file's name, with or without its extension
+fn title_matches(name: &str, title: &str) -> bool {
+    name.eq_ignore_ascii_case(title) || stem(name).eq_ignore_ascii_case(title)
+}
+
+fn stem(name: &str) -> &str {
+    match name.rfind('.') {
+        Some(i) if i > 0 => &name[..i],
+        _ => name,
+    }
+}
+
+/// escapes what can't appear in a link destination as is, like spaces
+fn percent_encode(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for c in path.chars() {
+        if c.is_alphanumeric() || "/-._~!$&'*+,;=:@".contains(c) {
+            encoded.push(c);
+        } else {
+            let mut buf = [0; 4];
+            for byte in c.encode_utf8(&mut buf).bytes() {
+                encoded.push_str(&format!("%{byte:02X}"));
+            }
+        }
+    }
+    encoded
+}
+
+/// decodes `%20` style escapes, leaving the path alone if they don't decode to utf-8
+fn percent_decode(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = path.get(i + 1..i + 3);
+        match hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+            Some(byte) if bytes[i] == b'%' => {
+                decoded.push(byte);
+                i += 3;
+            }
+            _ => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).unwrap_or_else(|_| path.to_string())
+}
@@ -11,12 +11,20 @@ pub mod debug;
 pub mod documents;
 pub mod events;
 pub mod file;
+pub mod groups;
 pub mod import_export;
 pub mod integrity;
 pub mod keychain;
 pub mod lb_id;
+pub mod links;
 pub mod logging;
 pub mod path;
 pub mod pin;
+pub mod public_links;
+pub mod render;
 pub mod share;
+pub mod streams;
+pub mod sync_policy;
+pub mod trash;
 pub mod usage;
+pub mod versions;
@@ -0,0 +1,188 @@
+use crate::LocalLb;
+use crate::model::api::{
+    CreatePublicLinkRequest, ListPublicLinksRequest, PublicLinkInfo, RevokePublicLinkRequest,
+};
+use crate::model::clock::get_time;
+use crate::model::crypto::{AESKey, EncryptedDocument};
+use crate::model::errors::{LbErrKind, LbResult, Unexpected};
+use crate::model::file_like::FileLike;
+use crate::model::symkey;
+use crate::model::tree_like::TreeLike;
+use aes_gcm::aead::generic_array::GenericArray;
+use aes_gcm::aead::{Aead, Payload};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A read-only link to a snapshot of a file, viewable in a browser without an account.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PublicLink {
+    pub id: Uuid,
+    pub file_id: Uuid,
+    /// `None` for links to files this device can no longer read, or whose key has been rotated
+    /// since the link was created
+    pub url: Option<String>,
+    pub size_bytes: u64,
+    pub created_at: u64,
+}
+
+/// What the viewer decrypts: the shared file, or every document in the shared folder.
+#[derive(Serialize)]
+struct Snapshot {
+    name: String,
+    files: Vec<SnapshotFile>,
+}
+
+#[derive(Serialize)]
+struct SnapshotFile {
+    /// relative to the shared folder, or the document's name if a document was shared
+    path: String,
+    /// base64, since documents aren't necessarily text
+    content: String,
+}
+
+impl LocalLb {
+    /// uploads a snapshot of a document, or of every document in a folder, and returns a link
+    /// anyone can view it at. The snapshot is encrypted with a new key that's only in the link's
+    /// fragment, which browsers don't send to the server. Later edits aren't reflected; create
+    /// another link to share them.
+    ///
+    /// callers of this function should be prepared to handle:
+    /// - [crate::LbErrKind::FileNonexistent], including for files that haven't been synced yet
+    /// - [crate::LbErrKind::InsufficientPermission], for files shared with this account read-only
+    /// - [crate::LbErrKind::UsageIsOverDataCap]
+    /// - [crate::LbErrKind::ServerUnreachable]
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn create_public_link(&self, id: Uuid) -> LbResult<PublicLink> {
+        let (name, file_key, docs) = {
+            let tx = self.ro_tx().await;
+            let db = tx.db();
+            let mut tree = (&db.base_metadata).to_staged(&db.local_metadata).to_lazy();
+
+            let file = tree.maybe_find(&id).ok_or(LbErrKind::FileNonexistent)?;
+            if file.is_link() || tree.calculate_deleted(&id)? {
+                return Err(LbErrKind::FileNonexistent.into());
+            }
+
+            let name = tree.name(&id, &self.keychain)?;
+            let mut docs = vec![];
+            if tree.find(&id)?.is_document() {
+                docs.push((id, name.clone()));
+            } else {
+                for descendant in tree.descendants(&id)? {
+                    if !tree.find(&descendant)?.is_document()
+                        || tree.calculate_deleted(&descendant)?
+                    {
+                        continue;
+                    }
+                    let mut path = tree.name(&descendant, &self.keychain)?;
+                    let mut parent = *tree.find(&descendant)?.parent();
+                    while parent != id {
+                        path = format!("{}/{path}", tree.name(&parent, &self.keychain)?);
+                        parent = *tree.find(&parent)?.parent();
+                    }
+                    docs.push((descendant, path));
+                }
+                docs.sort_by(|(_, a), (_, b)| a.cmp(b));
+            }
+
+            (name, tree.decrypt_key(&id, &self.keychain)?, docs)
+        };
+
+        let mut files = vec![];
+        for (doc, path) in docs {
+            let content = self.read_document(doc, false).await?;
+            files.push(SnapshotFile { path, content: base64::encode(content) });
+        }
+        let snapshot = serde_json::to_vec(&Snapshot { name, files }).map_unexpected()?;
+
+        let link_key = symkey::generate_key();
+        let snapshot = encrypt_for_browser(&link_key, &snapshot)?;
+        let size_bytes = snapshot.value.len() as u64;
+
+        let link_id = Uuid::new_v4();
+        self.client
+            .request(
+                self.get_account()?,
+                CreatePublicLinkRequest {
+                    id: link_id,
+                    file_id: id,
+                    encrypted_key: symkey::encrypt(&file_key, &link_key)?,
+                    snapshot,
+                },
+            )
+            .await?;
+
+        Ok(PublicLink {
+            id: link_id,
+            file_id: id,
+            url: Some(self.public_link_url(link_id, &link_key)?),
+            size_bytes,
+            created_at: get_time().0 as u64,
+        })
+    }
+
+    /// lists the public links this account has created, newest first
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn list_public_links(&self) -> LbResult<Vec<PublicLink>> {
+        let mut infos = self
+            .client
+            .request(self.get_account()?, ListPublicLinksRequest {})
+            .await?
+            .links;
+        infos.sort_by_key(|info| std::cmp::Reverse(info.created_at));
+
+        let tx = self.ro_tx().await;
+        let db = tx.db();
+        let mut tree = (&db.base_metadata).to_staged(&db.local_metadata).to_lazy();
+
+        let mut links = vec![];
+        for PublicLinkInfo { id, file_id, encrypted_key, size_bytes, created_at, .. } in infos {
+            let url = match tree.maybe_find(&file_id) {
+                Some(_) => {
+                    let file_key = tree.decrypt_key(&file_id, &self.keychain)?;
+                    // the link's key was encrypted with a key the file no longer has
+                    match symkey::decrypt(&file_key, &encrypted_key) {
+                        Ok(link_key) => Some(self.public_link_url(id, &link_key)?),
+                        Err(_) => None,
+                    }
+                }
+                None => None,
+            };
+            links.push(PublicLink { id, file_id, url, size_bytes, created_at });
+        }
+
+        Ok(links)
+    }
+
+    /// stops serving a public link's snapshot. Anyone who already viewed it may have kept a copy.
+    ///
+    /// callers of this function should be prepared to handle:
+    /// - [crate::LbErrKind::ShareNonexistent]
+    /// - [crate::LbErrKind::ServerUnreachable]
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn revoke_public_link(&self, id: Uuid) -> LbResult<()> {
+        self.client
+            .request(self.get_account()?, RevokePublicLinkRequest { id })
+            .await?;
+        Ok(())
+    }
+
+    fn public_link_url(&self, id: Uuid, key: &AESKey) -> LbResult<String> {
+        let api_url = &self.get_account()?.api_url;
+        Ok(format!(
+            "{}/public/{id}#{}",
+            api_url.trim_end_matches('/'),
+            base64::encode_config(key, base64::URL_SAFE_NO_PAD)
+        ))
+    }
+}
+
+/// AES-256-GCM over the raw bytes, without the serialization [symkey::encrypt] does, so the viewer
+/// can decrypt it with the browser's Web Crypto API alone
+fn encrypt_for_browser(key: &AESKey, content: &[u8]) -> LbResult<EncryptedDocument> {
+    let nonce = symkey::generate_nonce();
+    let encrypted = symkey::convert_key(key)
+        .encrypt(GenericArray::from_slice(&nonce), Payload { msg: content, aad: &[] })
+        .map_unexpected()?;
+    Ok(EncryptedDocument::new(encrypted, nonce))
+}
@@ -0,0 +1,171 @@
+//! Drawings as they're exported: the strokes and images of a [Buffer] in drawing coordinates,
+//! with the images it refers to by id read in. Strokes are split into the segments they're drawn
+//! with so pen pressure survives as a change in width.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use glam::DVec2;
+use uuid::Uuid;
+
+use crate::model::svg::buffer::{Buffer, u_transform_to_bezier};
+use crate::model::svg::element::{Color, Element};
+
+use super::raster::Raster;
+
+pub(crate) struct Drawing {
+    pub min: DVec2,
+    pub max: DVec2,
+    /// bottom to top
+    pub items: Vec<Item>,
+}
+
+pub(crate) enum Item {
+    Stroke { segments: Vec<Segment>, color: Color, opacity: f32 },
+    Image { min: DVec2, size: DVec2, opacity: f32, raster: Raster },
+}
+
+pub(crate) struct Segment {
+    pub start: DVec2,
+    /// the control points of a cubic curve, none for a line
+    pub handles: Option<(DVec2, DVec2)>,
+    pub end: DVec2,
+    pub width: f64,
+}
+
+impl Drawing {
+    /// `None` if there's nothing to draw.
+    pub(crate) fn new(buffer: &Buffer, images: &HashMap<Uuid, Raster>) -> Option<Self> {
+        let mut items = vec![];
+        for (index, (id, el)) in buffer.elements.iter().enumerate() {
+            let Element::Path(path) = el else { continue };
+            let Some(stroke) = path.stroke else { continue };
+            if path.deleted || path.data.len() < 2 {
+                continue;
+            }
+
+            let transform = u_transform_to_bezier(&path.transform);
+            let scale = ((path.transform.sx + path.transform.sy) / 2.0).abs() as f64;
+            let pressures = buffer.weak_path_pressures.get(id);
+            let mut segments = vec![];
+            let mut i = 0;
+            while let Some(bezier) = path.data.get_segment(i) {
+                let bezier = bezier.apply_transformation(|p| transform.transform_point2(p));
+                let (start, end) = (bezier.start(), bezier.end());
+                let handles = match (bezier.handle_start(), bezier.handle_end()) {
+                    (Some(handle_start), Some(handle_end)) => Some((handle_start, handle_end)),
+                    (Some(handle), None) => Some((
+                        start + (handle - start) * 2.0 / 3.0,
+                        end + (handle - end) * 2.0 / 3.0,
+                    )),
+                    _ => None,
+                };
+                let pressure = pressures
+                    .and_then(|pressures| pressures.get(i).or(pressures.last()))
+                    .copied()
+                    .unwrap_or_default();
+                let width = stroke.width as f64 * scale * (1.0 + pressure as f64);
+                segments.push(Segment { start, handles, end, width });
+                i += 1;
+            }
+
+            let opacity = stroke.opacity * path.opacity;
+            items.push((index, Item::Stroke { segments, color: stroke.color.light, opacity }));
+        }
+        for image in buffer.weak_images.values() {
+            if let Some(raster) = images.get(&image.href) {
+                let item = Item::Image {
+                    min: DVec2::new(image.x as f64, image.y as f64),
+                    size: DVec2::new(image.width as f64, image.height as f64),
+                    opacity: image.opacity,
+                    raster: raster.clone(),
+                };
+                items.push((image.z_index, item));
+            }
+        }
+        items.sort_by_key(|(z_index, _)| *z_index);
+        let items: Vec<Item> = items.into_iter().map(|(_, item)| item).collect();
+
+        let (mut min, mut max) = (DVec2::splat(f64::INFINITY), DVec2::splat(f64::NEG_INFINITY));
+        for item in &items {
+            match item {
+                Item::Stroke { segments, .. } => {
+                    for segment in segments {
+                        let mut points = vec![segment.start, segment.end];
+                        if let Some((handle_start, handle_end)) = segment.handles {
+                            points.extend([handle_start, handle_end]);
+                        }
+                        for point in points {
+                            min = min.min(point - segment.width / 2.0);
+                            max = max.max(point + segment.width / 2.0);
+                        }
+                    }
+                }
+                Item::Image { min: image_min, size, .. } => {
+                    min = min.min(*image_min);
+                    max = max.max(*image_min + *size);
+                }
+            }
+        }
+        if min.x >= max.x || min.y >= max.y {
+            return None;
+        }
+
+        Some(Self { min, max, items })
+    }
+
+    pub(crate) fn size(&self) -> DVec2 {
+        self.max - self.min
+    }
+
+    /// A standalone svg that any browser can draw, images included.
+    pub(crate) fn to_svg(&self) -> String {
+        let size = self.size();
+        let mut svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="{} {} {} {}">"#,
+            size.x, size.y, self.min.x, self.min.y, size.x, size.y
+        );
+        for item in &self.items {
+            match item {
+                Item::Stroke { segments, color, opacity } => {
+                    // consecutive segments of the same width share a path
+                    for run in segments.chunk_by(|a, b| a.width == b.width && a.end == b.start) {
+                        let mut d = format!("M{} {}", run[0].start.x, run[0].start.y);
+                        for segment in run {
+                            let end = segment.end;
+                            let _ = match segment.handles {
+                                Some((a, b)) => {
+                                    write!(
+                                        d,
+                                        " C{} {} {} {} {} {}",
+                                        a.x, a.y, b.x, b.y, end.x, end.y
+                                    )
+                                }
+                                None => write!(d, " L{} {}", end.x, end.y),
+                            };
+                        }
+                        let _ = write!(
+                            svg,
+                            r#"<path d="{d}" fill="none" stroke="rgb({},{},{})" stroke-opacity="{opacity}" stroke-width="{}" stroke-linecap="round" stroke-linejoin="round"/>"#,
+                            color.red, color.green, color.blue, run[0].width
+                        );
+                    }
+                }
+                Item::Image { min, size, opacity, raster } => {
+                    let _ = write!(
+                        svg,
+                        r#"<image x="{}" y="{}" width="{}" height="{}" opacity="{opacity}" preserveAspectRatio="none" href="data:{};base64,{}"/>"#,
+                        min.x,
+                        min.y,
+                        size.x,
+                        size.y,
+                        raster.format.mime(),
+                        base64::encode(&raster.bytes)
+                    );
+                }
+            }
+        }
+        svg.push_str("</svg>");
+        svg
+    }
+}
@@ -0,0 +1,382 @@
+//! The fonts pdfs are set in: the ones the editor draws with, falling back to the rest of the
+//! Noto family for characters they don't have. Fonts are embedded in each pdf with only the
+//! glyphs it uses, so exports look the same in every reader and aren't limited to the characters
+//! of the fonts every reader has.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+
+use ttf_parser::{Face, GlyphId, name_id};
+
+use crate::model::errors::{LbErrKind, LbResult};
+
+use super::pdf::{Writer, deflate};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(super) enum Font {
+    Regular,
+    Bold,
+    Italic,
+    /// the bold face, slanted; the editor has no bold italic face either
+    BoldItalic,
+    Mono,
+}
+
+impl Font {
+    /// the face glyphs are looked up in first
+    fn face(self) -> usize {
+        match self {
+            Font::Regular => 0,
+            Font::Bold | Font::BoldItalic => 1,
+            Font::Italic => 2,
+            Font::Mono => 3,
+        }
+    }
+
+    /// how far text leans, as the tangent of its angle
+    pub(super) fn slant(self) -> f32 {
+        if self == Font::BoldItalic { 0.2 } else { 0.0 }
+    }
+}
+
+/// A glyph of one of the faces, the character it draws, and how far it advances, in thousandths
+/// of the font size.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(super) struct Glyph {
+    pub face: usize,
+    pub id: u16,
+    pub c: char,
+    pub width: f32,
+}
+
+pub(super) struct Fonts {
+    /// regular, bold, italic and mono, then the fallbacks
+    faces: Vec<Face<'static>>,
+    data: Vec<&'static [u8]>,
+}
+
+impl Fonts {
+    pub(super) fn new() -> LbResult<Self> {
+        let primary = [
+            lb_fonts::NOTO_SANS_REGULAR,
+            lb_fonts::NOTO_SANS_BOLD,
+            lb_fonts::NOTO_SANS_ITALIC,
+            lb_fonts::NOTO_SANS_MONO_REGULAR,
+        ];
+        let mut fonts = Self { faces: vec![], data: vec![] };
+        for data in primary {
+            let face = Face::parse(data, 0).map_err(|err| {
+                LbErrKind::Unexpected(format!("bundled font doesn't parse: {err}"))
+            })?;
+            fonts.faces.push(face);
+            fonts.data.push(data);
+        }
+        for data in lb_fonts::NOTO.iter().copied() {
+            if primary.iter().any(|font| font.as_ptr() == data.as_ptr()) {
+                continue;
+            }
+            if let Ok(face) = Face::parse(data, 0) {
+                fonts.faces.push(face);
+                fonts.data.push(data);
+            }
+        }
+        Ok(fonts)
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.faces.len()
+    }
+
+    /// The glyph `font` draws `c` with, from a fallback if it has none, or its missing glyph if
+    /// no face has one.
+    pub(super) fn glyph(&self, font: Font, c: char) -> Glyph {
+        let c = if c == '\t' { ' ' } else { c };
+        let primary = font.face();
+        let fallbacks = 4..self.faces.len();
+        for face in std::iter::once(primary).chain(fallbacks) {
+            if let Some(id) = self.faces[face].glyph_index(c) {
+                return Glyph { face, id: id.0, c, width: self.advance(face, id.0) };
+            }
+        }
+        Glyph { face: primary, id: 0, c, width: self.advance(primary, 0) }
+    }
+
+    fn advance(&self, face: usize, id: u16) -> f32 {
+        let face = &self.faces[face];
+        let advance = face.glyph_hor_advance(GlyphId(id)).unwrap_or_default();
+        advance as f32 * 1000.0 / face.units_per_em() as f32
+    }
+
+    /// Writes the objects that embed a face with the glyphs in `used`, numbered from `id`: the
+    /// font itself, its one descendant, its descriptor, the font file and the cmap that maps
+    /// glyphs back to text for copying and searching.
+    pub(super) fn embed(
+        &self, face: usize, used: &BTreeMap<u16, char>, id: usize, pdf: &mut Writer,
+    ) {
+        let (descendant, descriptor, file, cmap) = (id + 1, id + 2, id + 3, id + 4);
+        let data = self.data[face];
+        let parsed = &self.faces[face];
+        let scale = 1000.0 / parsed.units_per_em() as f32;
+        let name = format!("{}+{}", subset_tag(face), postscript_name(parsed, face));
+
+        let mut glyphs: BTreeSet<u16> = used.keys().copied().collect();
+        glyphs.insert(0);
+        let (subtype, file_entry) = if parsed.tables().glyf.is_some() {
+            let subset = subset(data, &glyphs).unwrap_or_else(|| data.to_vec());
+            let dict = format!("/Filter /FlateDecode /Length1 {}", subset.len());
+            pdf.stream(file, &dict, &deflate(&subset));
+            ("CIDFontType2", "FontFile2")
+        } else {
+            // cff outlines are embedded whole
+            pdf.stream(file, "/Subtype /OpenType /Filter /FlateDecode", &deflate(data));
+            ("CIDFontType0", "FontFile3")
+        };
+
+        pdf.object(
+            id,
+            &format!(
+                "<< /Type /Font /Subtype /Type0 /BaseFont /{name} /Encoding /Identity-H \
+                 /DescendantFonts [{descendant} 0 R] /ToUnicode {cmap} 0 R >>"
+            ),
+        );
+
+        let mut widths = String::new();
+        for glyph in &glyphs {
+            let _ = write!(widths, " {glyph} [{:.2}]", self.advance(face, *glyph));
+        }
+        pdf.object(
+            descendant,
+            &format!(
+                "<< /Type /Font /Subtype /{subtype} /BaseFont /{name} \
+                 /CIDSystemInfo << /Registry (Adobe) /Ordering (Identity) /Supplement 0 >> \
+                 /FontDescriptor {descriptor} 0 R /W [{widths} ] /CIDToGIDMap /Identity >>"
+            ),
+        );
+
+        // symbolic, as glyphs are picked by id rather than by a standard encoding
+        let mut flags = 4;
+        if parsed.is_monospaced() {
+            flags |= 1;
+        }
+        if parsed.is_italic() {
+            flags |= 64;
+        }
+        let bbox = parsed.global_bounding_box();
+        pdf.object(
+            descriptor,
+            &format!(
+                "<< /Type /FontDescriptor /FontName /{name} /Flags {flags} \
+                 /FontBBox [{:.0} {:.0} {:.0} {:.0}] /ItalicAngle {:.1} /Ascent {:.0} \
+                 /Descent {:.0} /CapHeight {:.0} /StemV 80 /{file_entry} {file} 0 R >>",
+                bbox.x_min as f32 * scale,
+                bbox.y_min as f32 * scale,
+                bbox.x_max as f32 * scale,
+                bbox.y_max as f32 * scale,
+                parsed.italic_angle(),
+                parsed.ascender() as f32 * scale,
+                parsed.descender() as f32 * scale,
+                parsed.capital_height().unwrap_or(parsed.ascender()) as f32 * scale,
+            ),
+        );
+
+        pdf.stream(cmap, "/Filter /FlateDecode", &deflate(to_unicode(used).as_bytes()));
+    }
+}
+
+/// a pdf string that's shown in a font: the glyphs' ids as two-byte codes
+pub(super) fn codes(glyphs: &[Glyph]) -> String {
+    let mut codes = String::with_capacity(glyphs.len() * 4 + 2);
+    codes.push('<');
+    for glyph in glyphs {
+        let _ = write!(codes, "{:04X}", glyph.id);
+    }
+    codes.push('>');
+    codes
+}
+
+/// a pdf text string, for text that's shown outside of pages, like the title
+pub(super) fn text_string(text: &str) -> String {
+    let mut string = String::from("<FEFF");
+    for unit in text.encode_utf16() {
+        let _ = write!(string, "{unit:04X}");
+    }
+    string.push('>');
+    string
+}
+
+/// Subset fonts are named with six capital letters ahead of the font's name.
+fn subset_tag(face: usize) -> String {
+    let mut tag = *b"LBAAAA";
+    let mut n = face;
+    for letter in tag.iter_mut().rev().take(4) {
+        *letter = b'A' + (n % 26) as u8;
+        n /= 26;
+    }
+    String::from_utf8_lossy(&tag).into_owned()
+}
+
+fn postscript_name(face: &Face, index: usize) -> String {
+    let name = face
+        .names()
+        .into_iter()
+        .filter(|name| name.name_id == name_id::POST_SCRIPT_NAME)
+        .find_map(|name| name.to_string())
+        .unwrap_or_default();
+    let name: String = name
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-')
+        .collect();
+    if name.is_empty() { format!("Font{index}") } else { name }
+}
+
+fn to_unicode(used: &BTreeMap<u16, char>) -> String {
+    let mut cmap = String::from(
+        "/CIDInit /ProcSet findresource begin\n12 dict begin\nbegincmap\n\
+         /CIDSystemInfo << /Registry (Adobe) /Ordering (UCS) /Supplement 0 >> def\n\
+         /CMapName /Adobe-Identity-UCS def\n/CMapType 2 def\n\
+         1 begincodespacerange\n<0000> <FFFF>\nendcodespacerange\n",
+    );
+    let used: Vec<_> = used.iter().collect();
+    for chunk in used.chunks(100) {
+        let _ = writeln!(cmap, "{} beginbfchar", chunk.len());
+        for (id, c) in chunk {
+            let mut units = String::new();
+            for unit in c.encode_utf16(&mut [0; 2]) {
+                let _ = write!(units, "{unit:04X}");
+            }
+            let _ = writeln!(cmap, "<{id:04X}> <{units}>");
+        }
+        cmap.push_str("endbfchar\n");
+    }
+    cmap.push_str("endcmap\nCMapName currentdict /CMap defineresource pop\nend\nend\n");
+    cmap
+}
+
+/// the tables a pdf reader draws truetype glyphs with
+const KEEP: [&[u8; 4]; 9] =
+    [b"cvt ", b"fpgm", b"glyf", b"head", b"hhea", b"hmtx", b"loca", b"maxp", b"prep"];
+
+/// A truetype font with only the tables pdf readers use and the outlines of only `glyphs` and
+/// the glyphs they're built from. Glyphs keep their ids; the rest are left empty. `None` if the
+/// font isn't laid out the way it should be.
+fn subset(data: &[u8], glyphs: &BTreeSet<u16>) -> Option<Vec<u8>> {
+    let u16_at = |at: usize| {
+        data.get(at..at + 2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]))
+    };
+    let u32_at = |at: usize| {
+        data.get(at..at + 4)
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    };
+
+    let mut tables = BTreeMap::new();
+    for i in 0..u16_at(4)? as usize {
+        let record = 12 + 16 * i;
+        let tag: [u8; 4] = data.get(record..record + 4)?.try_into().ok()?;
+        let (offset, length) = (u32_at(record + 8)? as usize, u32_at(record + 12)? as usize);
+        tables.insert(tag, data.get(offset..offset + length)?);
+    }
+    let head = *tables.get(b"head")?;
+    let glyf = *tables.get(b"glyf")?;
+    let loca = *tables.get(b"loca")?;
+    let count = u16::from_be_bytes(tables.get(b"maxp")?.get(4..6)?.try_into().ok()?) as usize;
+    let long = i16::from_be_bytes(head.get(50..52)?.try_into().ok()?) == 1;
+    let offset = |id: usize| -> Option<usize> {
+        if long {
+            Some(u32::from_be_bytes(loca.get(id * 4..id * 4 + 4)?.try_into().ok()?) as usize)
+        } else {
+            Some(u16::from_be_bytes(loca.get(id * 2..id * 2 + 2)?.try_into().ok()?) as usize * 2)
+        }
+    };
+    let outline = |id: usize| -> Option<&[u8]> { glyf.get(offset(id)?..offset(id + 1)?) };
+
+    // composite glyphs are drawn from other glyphs, which need keeping too
+    let mut keep: BTreeSet<usize> = BTreeSet::new();
+    let mut pending: Vec<usize> = glyphs.iter().map(|id| *id as usize).collect();
+    while let Some(id) = pending.pop() {
+        if id >= count || !keep.insert(id) {
+            continue;
+        }
+        let outline = outline(id)?;
+        if outline.len() < 10 || i16::from_be_bytes([outline[0], outline[1]]) >= 0 {
+            continue;
+        }
+        let mut at = 10;
+        loop {
+            let flags = u16::from_be_bytes(outline.get(at..at + 2)?.try_into().ok()?);
+            let component = u16::from_be_bytes(outline.get(at + 2..at + 4)?.try_into().ok()?);
+            pending.push(component as usize);
+            at += 4 + if flags & 0x0001 != 0 { 4 } else { 2 };
+            // a scale, separate x and y scales, or a two by two matrix
+            if flags & 0x0008 != 0 {
+                at += 2;
+            } else if flags & 0x0040 != 0 {
+                at += 4;
+            } else if flags & 0x0080 != 0 {
+                at += 8;
+            }
+            if flags & 0x0020 == 0 {
+                break;
+            }
+        }
+    }
+
+    let mut new_glyf = vec![];
+    let mut new_loca = Vec::with_capacity((count + 1) * 4);
+    for id in 0..count {
+        new_loca.extend_from_slice(&(new_glyf.len() as u32).to_be_bytes());
+        if keep.contains(&id) {
+            new_glyf.extend_from_slice(outline(id)?);
+            new_glyf.resize(new_glyf.len().next_multiple_of(4), 0);
+        }
+    }
+    new_loca.extend_from_slice(&(new_glyf.len() as u32).to_be_bytes());
+    let mut new_head = head.to_vec();
+    new_head.get_mut(8..12)?.copy_from_slice(&[0; 4]); // the whole font's checksum
+    new_head
+        .get_mut(50..52)?
+        .copy_from_slice(&1i16.to_be_bytes());
+
+    let kept: Vec<([u8; 4], Vec<u8>)> = KEEP
+        .iter()
+        .filter_map(|tag| {
+            let table = match *tag {
+                b"glyf" => new_glyf.clone(),
+                b"loca" => new_loca.clone(),
+                b"head" => new_head.clone(),
+                _ => tables.get(*tag)?.to_vec(),
+            };
+            Some((**tag, table))
+        })
+        .collect();
+
+    let count = kept.len() as u16;
+    let power = 1u16 << (15 - count.leading_zeros());
+    let mut font = vec![];
+    font.extend_from_slice(&0x00010000u32.to_be_bytes());
+    font.extend_from_slice(&count.to_be_bytes());
+    font.extend_from_slice(&(power * 16).to_be_bytes());
+    font.extend_from_slice(&(15 - count.leading_zeros() as u16).to_be_bytes());
+    font.extend_from_slice(&((count - power) * 16).to_be_bytes());
+    let mut offset = 12 + 16 * kept.len();
+    for (tag, table) in &kept {
+        font.extend_from_slice(tag);
+        font.extend_from_slice(&checksum(table).to_be_bytes());
+        font.extend_from_slice(&(offset as u32).to_be_bytes());
+        font.extend_from_slice(&(table.len() as u32).to_be_bytes());
+        offset += table.len().next_multiple_of(4);
+    }
+    for (_, table) in &kept {
+        font.extend_from_slice(table);
+        font.resize(font.len().next_multiple_of(4), 0);
+    }
+    Some(font)
+}
+
+fn checksum(table: &[u8]) -> u32 {
+    table.chunks(4).fold(0u32, |sum, chunk| {
+        let mut word = [0; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        sum.wrapping_add(u32::from_be_bytes(word))
+    })
+}
@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+
+use comrak::Arena;
+use comrak::nodes::NodeValue;
+
+use super::{Embed, comrak_options};
+
+const STYLE: &str = "
+body { max-width: 46rem; margin: 2rem auto; padding: 0 1rem; color: #1f2328; background: #fff;
+    font: 16px/1.6 -apple-system, BlinkMacSystemFont, 'Segoe UI', Helvetica, Arial, sans-serif; }
+h1, h2 { border-bottom: 1px solid #d1d9e0; padding-bottom: .3em; }
+a { color: #0969da; }
+img { max-width: 100%; }
+code, pre { font-family: ui-monospace, SFMono-Regular, Menlo, Consolas, monospace; font-size: 85%; }
+code { background: #eff1f3; border-radius: 4px; padding: .2em .4em; }
+pre { background: #f6f8fa; border-radius: 6px; padding: 1em; overflow: auto; }
+pre code { background: none; padding: 0; font-size: 100%; }
+blockquote { margin: 0; padding: 0 1em; color: #59636e; border-left: .25em solid #d1d9e0; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #d1d9e0; padding: .4em .8em; }
+tr:nth-child(2n) { background: #f6f8fa; }
+mark { background: #fff8c5; }
+span.spoiler { background: #1f2328; }
+span.spoiler:hover { background: none; }
+ul.contains-task-list { list-style: none; padding-left: 1.2em; }
+.markdown-alert { padding: .5em 1em; margin-bottom: 1em; border-left: .25em solid #0969da; }
+.markdown-alert-title { font-weight: 600; }
+.markdown-alert-tip { border-color: #1a7f37; }
+.markdown-alert-important { border-color: #8250df; }
+.markdown-alert-warning { border-color: #9a6700; }
+.markdown-alert-caution { border-color: #cf222e; }
+@media print { body { max-width: none; margin: 0; } pre { white-space: pre-wrap; } }
+";
+
+/// A standalone html page. Embedded images are written as data urls; they're swapped in after
+/// rendering so that the renderer's url filter, which only lets a few image types through, still
+/// applies to everything the document says.
+pub(super) fn render(md: &str, title: &str, embeds: &HashMap<String, Embed>) -> String {
+    let arena = Arena::new();
+    let options = comrak_options();
+    let root = comrak::parse_document(&arena, md, &options);
+
+    let mut urls: Vec<String> = vec![];
+    let mut placeholders: HashMap<String, usize> = HashMap::new();
+    for node in root.descendants() {
+        if let NodeValue::Image(link) = &mut node.data.borrow_mut().value {
+            let Some(embed) = embeds.get(&link.url) else { continue };
+            let index = *placeholders.entry(link.url.clone()).or_insert_with(|| {
+                urls.push(match embed {
+                    Embed::Raster(raster) => format!(
+                        "data:{};base64,{}",
+                        raster.format.mime(),
+                        base64::encode(&raster.bytes)
+                    ),
+                    Embed::Drawing(drawing) => {
+                        format!("data:image/svg+xml;base64,{}", base64::encode(drawing.to_svg()))
+                    }
+                });
+                urls.len() - 1
+            });
+            link.url = format!("lb-embed:{index}");
+        }
+    }
+
+    let mut body = String::new();
+    let _ = comrak::format_html(root, &options, &mut body);
+    for (i, url) in urls.iter().enumerate() {
+        body = body.replace(&format!("src=\"lb-embed:{i}\""), &format!("src=\"{url}\""));
+    }
+
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+        <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+        <title>{}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n{body}</body>\n</html>\n",
+        escape(title)
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,150 @@
+//! Markdown documents rendered into files that can be shared outside of lockbook: a
+//! self-contained HTML page, or a paginated PDF. Documents are parsed the same way the editor
+//! parses them, and the images they refer to are read out of the tree and embedded, with
+//! drawings drawn rather than embedded as lockbook's own svg. PDFs are behind the `pdf` feature,
+//! as they embed fonts.
+
+mod drawing;
+#[cfg(feature = "pdf")]
+mod font;
+mod html;
+#[cfg(feature = "pdf")]
+mod pdf;
+mod raster;
+
+use std::collections::HashMap;
+
+use comrak::nodes::NodeValue;
+use comrak::{Arena, Options};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::LocalLb;
+use crate::model::errors::{LbErrKind, LbResult};
+use crate::model::file::File;
+use crate::model::svg::buffer::Buffer;
+use crate::service::links::{LinkTarget, Resolver};
+
+use drawing::Drawing;
+use raster::Raster;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RenderFormat {
+    Html,
+    #[cfg(feature = "pdf")]
+    Pdf,
+}
+
+/// The options markdown is parsed with, shared with the editor so that exports render what the
+/// editor shows.
+pub fn comrak_options() -> Options<'static> {
+    let mut options = Options::default();
+    options.parse.smart = true;
+    options.parse.ignore_setext = true;
+    options.extension.alerts = true;
+    options.extension.autolink = true;
+    options.extension.description_lists = false; // todo: is this a good way to power workspace-wide term definitions?
+    options.extension.footnotes = false;
+    options.extension.front_matter_delimiter = Some("---".to_string());
+    options.extension.greentext = false;
+    options.extension.header_ids = None; // intended for HTML renderers
+    options.extension.highlight = true;
+    options.extension.math_code = true;
+    options.extension.math_dollars = true;
+    options.extension.multiline_block_quotes = false; // todo
+    options.extension.shortcodes = true;
+    options.extension.spoiler = true;
+    options.extension.strikethrough = true;
+    options.extension.subscript = true;
+    options.extension.superscript = true;
+    options.extension.table = true;
+    options.extension.tagfilter = false; // intended for HTML renderers
+    options.extension.tasklist = true;
+    options.extension.underline = true;
+    options.extension.wikilinks_title_after_pipe = true; // matches obsidian
+    options.extension.wikilinks_title_before_pipe = false; // would not match obsidian
+    options.render.escaped_char_spans = true;
+    options
+}
+
+/// An image a document refers to, read out of the tree.
+pub(crate) enum Embed {
+    Raster(Raster),
+    Drawing(Drawing),
+}
+
+impl LocalLb {
+    /// Renders a markdown document as a single file in `format`. Images are resolved like links,
+    /// by path relative to the document, absolute path or `lb://` url, and embedded in the
+    /// output; images that don't resolve to a readable png, jpeg, gif, webp or drawing are left
+    /// as they're written in html and replaced by their alt text in pdf.
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn export_rendered(&self, id: Uuid, format: RenderFormat) -> LbResult<Vec<u8>> {
+        let file = self.get_file_by_id(id).await?;
+        if !file.is_document() {
+            return Err(LbErrKind::FileNotDocument.into());
+        }
+
+        let md = String::from_utf8_lossy(&self.read_document(id, false).await?).into_owned();
+        let files = self.list_metadatas().await?;
+        let resolver = Resolver::new(&files);
+
+        let mut embeds = HashMap::new();
+        for url in image_urls(&md) {
+            if let Some(LinkTarget::File(image)) = resolver.resolve_url(id, &url) {
+                if let Some(embed) = self.embed(&files, image).await {
+                    embeds.insert(url, embed);
+                }
+            }
+        }
+
+        let title = match file.name.rfind('.') {
+            Some(i) if i > 0 => &file.name[..i],
+            _ => &file.name,
+        };
+        Ok(match format {
+            RenderFormat::Html => html::render(&md, title, &embeds).into_bytes(),
+            #[cfg(feature = "pdf")]
+            RenderFormat::Pdf => pdf::render(&md, title, &embeds)?,
+        })
+    }
+
+    async fn embed(&self, files: &[File], id: Uuid) -> Option<Embed> {
+        let file = files.iter().find(|f| f.id == id)?;
+        let bytes = self.read_document(id, false).await.ok()?;
+        if !file.name.to_lowercase().ends_with(".svg") {
+            return Raster::decode(bytes).map(Embed::Raster);
+        }
+
+        let svg = String::from_utf8_lossy(&bytes);
+        let hrefs: Vec<Uuid> = Buffer::new(&svg)
+            .weak_images
+            .values()
+            .map(|image| image.href)
+            .collect();
+        let mut images = HashMap::new();
+        for href in hrefs {
+            if let Ok(image) = self.read_document(href, false).await {
+                if let Some(raster) = Raster::decode(image) {
+                    images.insert(href, raster);
+                }
+            }
+        }
+        Drawing::new(&Buffer::new(&svg), &images).map(Embed::Drawing)
+    }
+}
+
+/// the destinations of the images in a document, as they're written
+fn image_urls(md: &str) -> Vec<String> {
+    let arena = Arena::new();
+    let root = comrak::parse_document(&arena, md, &comrak_options());
+    let mut urls: Vec<String> = vec![];
+    for node in root.descendants() {
+        if let NodeValue::Image(link) = &node.data.borrow().value {
+            if !urls.contains(&link.url) {
+                urls.push(link.url.clone());
+            }
+        }
+    }
+    urls
+}
@@ -0,0 +1,1171 @@
+//! A small pdf typesetter: enough to lay out the markdown the editor understands on letter-sized
+//! pages, set in the editor's fonts, see [super::font].
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::mem;
+
+use comrak::Arena;
+use comrak::nodes::{AlertType, AstNode, ListDelimType, ListType, NodeValue, TableAlignment};
+use flate2::Compression;
+use flate2::write::ZlibEncoder;
+
+use super::drawing::{Drawing, Item};
+use super::font::{Font, Fonts, Glyph, codes, text_string};
+use super::raster::{Format, Raster};
+use super::{Embed, comrak_options};
+use crate::model::errors::LbResult;
+
+const PAGE_WIDTH: f32 = 612.0;
+const PAGE_HEIGHT: f32 = 792.0;
+const MARGIN: f32 = 60.0;
+const BODY_SIZE: f32 = 11.0;
+const CODE_SIZE: f32 = 9.5;
+const TABLE_SIZE: f32 = 10.0;
+const HEADING_SIZES: [f32; 6] = [22.0, 18.0, 15.0, 13.0, 11.5, 11.0];
+/// line height, in multiples of the font size
+const LEADING: f32 = 1.4;
+/// the distance from the top of a line to its baseline, in multiples of the font size
+const ASCENT: f32 = 1.1;
+const INDENT: f32 = 20.0;
+/// points per css pixel, the unit images and drawings are sized in
+const PX: f32 = 0.75;
+
+const TEXT: Rgb = Rgb(0.12, 0.14, 0.16);
+const MUTED: Rgb = Rgb(0.35, 0.39, 0.43);
+const LINK: Rgb = Rgb(0.04, 0.41, 0.85);
+const RULE: Rgb = Rgb(0.82, 0.85, 0.88);
+const CODE_BACKGROUND: Rgb = Rgb(0.95, 0.96, 0.97);
+const HIGHLIGHT: Rgb = Rgb(1.0, 0.97, 0.77);
+
+/// the objects each face that's drawn with is embedded as, see [Fonts::embed]
+const FONT_OBJECTS: usize = 5;
+
+pub(super) fn render(md: &str, title: &str, embeds: &HashMap<String, Embed>) -> LbResult<Vec<u8>> {
+    let arena = Arena::new();
+    let root = comrak::parse_document(&arena, md, &comrak_options());
+
+    let fonts = Fonts::new()?;
+    let mut typesetter = Typesetter::new(embeds, &fonts);
+    typesetter.blocks(root);
+    Ok(typesetter.finish(title))
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Rgb(f32, f32, f32);
+
+impl Rgb {
+    fn fill(self) -> String {
+        format!("{:.3} {:.3} {:.3} rg", self.0, self.1, self.2)
+    }
+
+    fn stroke(self) -> String {
+        format!("{:.3} {:.3} {:.3} RG", self.0, self.1, self.2)
+    }
+}
+
+/// the operators that show glyphs at `size`, switching to each face they're drawn from
+fn show(glyphs: &[Glyph], size: f32) -> String {
+    let mut show = String::new();
+    for run in glyphs.chunk_by(|a, b| a.face == b.face) {
+        let _ = write!(show, "/F{} {size:.2} Tf {} Tj ", run[0].face, codes(run));
+    }
+    show
+}
+
+/// a pdf string, with what can't appear in one as is escaped
+fn literal(bytes: &[u8]) -> String {
+    let mut literal = String::with_capacity(bytes.len() + 2);
+    literal.push('(');
+    for &byte in bytes {
+        match byte {
+            b'(' | b')' | b'\\' => {
+                literal.push('\\');
+                literal.push(byte as char);
+            }
+            0x20..=0x7e => literal.push(byte as char),
+            _ => {
+                let _ = write!(literal, "\\{byte:03o}");
+            }
+        }
+    }
+    literal.push(')');
+    literal
+}
+
+pub(super) fn deflate(bytes: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    let _ = encoder.write_all(bytes);
+    encoder.finish().unwrap_or_default()
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+enum Script {
+    #[default]
+    Normal,
+    Super,
+    Sub,
+}
+
+#[derive(Clone, Default, PartialEq, Debug)]
+struct Style {
+    bold: bool,
+    italic: bool,
+    code: bool,
+    strike: bool,
+    underline: bool,
+    highlight: bool,
+    script: Script,
+    link: Option<String>,
+    color: Option<Rgb>,
+}
+
+impl Style {
+    fn font(&self) -> Font {
+        match (self.code, self.bold, self.italic) {
+            (true, _, _) => Font::Mono,
+            (_, true, true) => Font::BoldItalic,
+            (_, true, false) => Font::Bold,
+            (_, false, true) => Font::Italic,
+            (_, false, false) => Font::Regular,
+        }
+    }
+
+    fn size(&self, size: f32) -> f32 {
+        if self.script == Script::Normal { size } else { size * 0.7 }
+    }
+
+    fn measure(&self, glyph: &Glyph, size: f32) -> f32 {
+        glyph.width * self.size(size) / 1000.0
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Piece {
+    Text(String, Style),
+    Break,
+    Image { url: String, alt: String },
+}
+
+#[derive(Debug)]
+struct Fragment {
+    text: Vec<Glyph>,
+    style: Style,
+    /// from the start of the line
+    x: f32,
+    width: f32,
+}
+
+#[derive(Default, Debug)]
+struct Line {
+    fragments: Vec<Fragment>,
+    width: f32,
+}
+
+/// Breaks text into lines no wider than `max`, between words where it can.
+fn wrap(fonts: &Fonts, pieces: &[Piece], size: f32, max: f32) -> Vec<Line> {
+    let mut wrapper =
+        Wrapper { fonts, size, max, lines: vec![], line: Line::default(), word: vec![] };
+    for piece in pieces {
+        match piece {
+            Piece::Text(text, style) => wrapper.text(text, style),
+            Piece::Image { alt, .. } => wrapper.text(alt, &Style::default()),
+            Piece::Break => {
+                wrapper.flush_word();
+                wrapper.finish_line();
+            }
+        }
+    }
+    wrapper.flush_word();
+    if !wrapper.line.fragments.is_empty() {
+        wrapper.finish_line();
+    }
+    wrapper.lines
+}
+
+struct Wrapper<'f> {
+    fonts: &'f Fonts,
+    size: f32,
+    max: f32,
+    lines: Vec<Line>,
+    line: Line,
+    /// the word being read, which may change style part way through
+    word: Vec<Fragment>,
+}
+
+impl Wrapper<'_> {
+    fn text(&mut self, text: &str, style: &Style) {
+        for c in text.chars() {
+            if c.is_whitespace() && c != '\u{a0}' {
+                self.flush_word();
+                if !self.line.fragments.is_empty() {
+                    self.push(self.fonts.glyph(style.font(), ' '), style);
+                }
+                continue;
+            }
+
+            let glyph = self.fonts.glyph(style.font(), c);
+            let width = style.measure(&glyph, self.size);
+            match self.word.last_mut() {
+                Some(fragment) if fragment.style == *style => {
+                    fragment.text.push(glyph);
+                    fragment.width += width;
+                }
+                _ => self.word.push(Fragment {
+                    text: vec![glyph],
+                    style: style.clone(),
+                    x: 0.0,
+                    width,
+                }),
+            }
+        }
+    }
+
+    fn flush_word(&mut self) {
+        let word = mem::take(&mut self.word);
+        let width: f32 = word.iter().map(|fragment| fragment.width).sum();
+        if self.line.width + width > self.max && !self.line.fragments.is_empty() {
+            self.finish_line();
+        }
+        for fragment in word {
+            for glyph in fragment.text {
+                // only words wider than a whole line are broken
+                let width = fragment.style.measure(&glyph, self.size);
+                if self.line.width + width > self.max && !self.line.fragments.is_empty() {
+                    self.finish_line();
+                }
+                self.push(glyph, &fragment.style);
+            }
+        }
+    }
+
+    fn push(&mut self, glyph: Glyph, style: &Style) {
+        let width = style.measure(&glyph, self.size);
+        match self.line.fragments.last_mut() {
+            Some(fragment) if fragment.style == *style => {
+                fragment.text.push(glyph);
+                fragment.width += width;
+            }
+            _ => self.line.fragments.push(Fragment {
+                text: vec![glyph],
+                style: style.clone(),
+                x: self.line.width,
+                width,
+            }),
+        }
+        self.line.width += width;
+    }
+
+    fn finish_line(&mut self) {
+        while let Some(fragment) = self.line.fragments.last_mut() {
+            let Some(space) = fragment.text.pop_if(|glyph| glyph.c == ' ') else {
+                break;
+            };
+            let width = fragment.style.measure(&space, self.size);
+            fragment.width -= width;
+            self.line.width -= width;
+            if fragment.text.is_empty() {
+                self.line.fragments.pop();
+            }
+        }
+        self.lines.push(mem::take(&mut self.line));
+    }
+}
+
+#[derive(Default)]
+struct Page {
+    content: String,
+    /// clickable areas: left, bottom, right and top, and where they go
+    links: Vec<([f32; 4], String)>,
+}
+
+enum Marker {
+    Bullet { depth: usize },
+    Number(String),
+    Check { checked: bool },
+}
+
+struct Image {
+    width: u32,
+    height: u32,
+    data: ImageData,
+}
+
+enum ImageData {
+    Jpeg {
+        bytes: Vec<u8>,
+        components: u8,
+    },
+    /// deflated samples
+    Pixels {
+        channels: u8,
+        color: Vec<u8>,
+        alpha: Option<Vec<u8>>,
+    },
+}
+
+struct Typesetter<'e> {
+    embeds: &'e HashMap<String, Embed>,
+    fonts: &'e Fonts,
+    /// the glyphs drawn from each face, and the characters they were drawn for
+    used: Vec<BTreeMap<u16, char>>,
+    pages: Vec<Page>,
+    page: Page,
+    /// the top of what's laid out next, measured up from the bottom of the page
+    y: f32,
+    left: f32,
+    right: f32,
+    /// the bars drawn alongside block quotes and alerts, by where they're drawn
+    bars: Vec<(f32, Rgb)>,
+    /// drawn alongside the next line
+    marker: Option<Marker>,
+    list_depth: usize,
+    tight: bool,
+    images: Vec<Image>,
+    /// indexes into `images` by url, or `None` for images that can't be drawn
+    image_indexes: HashMap<String, Option<usize>>,
+    /// the opacities drawn with, out of 255
+    opacities: Vec<u8>,
+}
+
+impl<'e> Typesetter<'e> {
+    fn new(embeds: &'e HashMap<String, Embed>, fonts: &'e Fonts) -> Self {
+        Self {
+            embeds,
+            fonts,
+            used: vec![BTreeMap::new(); fonts.len()],
+            pages: vec![],
+            page: Page::default(),
+            y: PAGE_HEIGHT - MARGIN,
+            left: MARGIN,
+            right: PAGE_WIDTH - MARGIN,
+            bars: vec![],
+            marker: None,
+            list_depth: 0,
+            tight: false,
+            images: vec![],
+            image_indexes: HashMap::new(),
+            opacities: vec![],
+        }
+    }
+
+    fn blocks<'a>(&mut self, node: &'a AstNode<'a>) {
+        for child in node.children() {
+            self.block(child);
+        }
+    }
+
+    fn block<'a>(&mut self, node: &'a AstNode<'a>) {
+        match &node.data.borrow().value {
+            NodeValue::Paragraph => {
+                let mut pieces = vec![];
+                inlines(node, &Style::default(), &mut pieces);
+                self.paragraph(&pieces, BODY_SIZE);
+                self.gap(if self.tight { 0.3 } else { 0.8 } * BODY_SIZE);
+            }
+            NodeValue::Heading(heading) => {
+                let size = HEADING_SIZES[(heading.level as usize).clamp(1, 6) - 1];
+                self.gap(size * 0.6);
+                let mut pieces = vec![];
+                inlines(node, &Style { bold: true, ..Default::default() }, &mut pieces);
+                self.paragraph(&pieces, size);
+                if heading.level <= 2 {
+                    let top = self.line(6.0, 0.0);
+                    self.rule(top - 3.0, RULE);
+                }
+                self.gap(size * 0.4);
+            }
+            NodeValue::BlockQuote | NodeValue::MultilineBlockQuote(_) => {
+                self.quote(node, RULE, None);
+            }
+            NodeValue::Alert(alert) => {
+                let (color, default_title) = match alert.alert_type {
+                    AlertType::Note => (LINK, "Note"),
+                    AlertType::Tip => (Rgb(0.1, 0.5, 0.22), "Tip"),
+                    AlertType::Important => (Rgb(0.51, 0.31, 0.87), "Important"),
+                    AlertType::Warning => (Rgb(0.6, 0.4, 0.0), "Warning"),
+                    AlertType::Caution => (Rgb(0.81, 0.13, 0.18), "Caution"),
+                };
+                let title = alert.title.as_deref().unwrap_or(default_title);
+                self.quote(node, color, Some((title, color)));
+            }
+            NodeValue::List(list) => {
+                let ordered = matches!(list.list_type, ListType::Ordered);
+                let delimiter =
+                    if matches!(list.delimiter, ListDelimType::Paren) { ")" } else { "." };
+                self.list(node, ordered.then_some((list.start, delimiter)), list.tight);
+            }
+            NodeValue::CodeBlock(code_block) => {
+                self.code_block(&code_block.literal);
+                self.gap(BODY_SIZE * 0.8);
+            }
+            NodeValue::ThematicBreak => {
+                let top = self.line(BODY_SIZE * 1.5, 0.0);
+                self.rule(top - BODY_SIZE * 0.75, RULE);
+                self.gap(BODY_SIZE * 0.5);
+            }
+            NodeValue::Table(table) => {
+                self.table(node, &table.alignments);
+                self.gap(BODY_SIZE * 0.8);
+            }
+            NodeValue::HtmlBlock(_) | NodeValue::FrontMatter(_) => {}
+            _ => self.blocks(node),
+        }
+    }
+
+    /// Typesets text, giving images that can be drawn a line of their own.
+    fn paragraph(&mut self, pieces: &[Piece], size: f32) {
+        let mut text = vec![];
+        for piece in pieces {
+            if let Piece::Image { url, alt } = piece {
+                if self.embeds.contains_key(url) {
+                    self.lines(&mem::take(&mut text), size);
+                    self.image(url, alt);
+                    continue;
+                }
+                let style = Style { italic: true, color: Some(MUTED), ..Default::default() };
+                text.push(Piece::Text(alt.clone(), style));
+                continue;
+            }
+            text.push(piece.clone());
+        }
+        self.lines(&text, size);
+    }
+
+    fn lines(&mut self, pieces: &[Piece], size: f32) {
+        if pieces.is_empty() {
+            return;
+        }
+        for line in wrap(self.fonts, pieces, size, self.right - self.left) {
+            let top = self.line(size * LEADING, size * ASCENT);
+            self.draw_line(&line, self.left, top - size * ASCENT, size);
+        }
+    }
+
+    fn draw_line(&mut self, line: &Line, x: f32, baseline: f32, size: f32) {
+        for fragment in &line.fragments {
+            let style = &fragment.style;
+            let (x, width) = (x + fragment.x, fragment.width);
+            if style.highlight {
+                self.fill_rect(x, baseline - size * 0.25, width, size * 1.15, HIGHLIGHT);
+            }
+            if style.code {
+                self.fill_rect(
+                    x - 1.0,
+                    baseline - size * 0.25,
+                    width + 2.0,
+                    size * 1.1,
+                    CODE_BACKGROUND,
+                );
+            }
+
+            let color = match (&style.link, style.color) {
+                (Some(_), _) => LINK,
+                (None, Some(color)) => color,
+                (None, None) => TEXT,
+            };
+            let rise = match style.script {
+                Script::Normal => 0.0,
+                Script::Super => size * 0.35,
+                Script::Sub => -size * 0.15,
+            };
+            self.text(x, baseline + rise, style.font(), style.size(size), color, &fragment.text);
+
+            if style.underline {
+                self.stroke_line(x, baseline - size * 0.12, x + width, color);
+            }
+            if style.strike {
+                self.stroke_line(x, baseline + size * 0.3, x + width, color);
+            }
+            if let Some(url) = &style.link {
+                if ["http://", "https://", "mailto:"]
+                    .iter()
+                    .any(|scheme| url.starts_with(scheme))
+                {
+                    let area = [x, baseline - size * 0.25, x + width, baseline + size * 0.9];
+                    self.page.links.push((area, url.clone()));
+                }
+            }
+        }
+    }
+
+    fn quote<'a>(&mut self, node: &'a AstNode<'a>, color: Rgb, title: Option<(&str, Rgb)>) {
+        let left = self.left;
+        self.bars.push((left, color));
+        self.left += 14.0;
+        if let Some((title, color)) = title {
+            let style = Style { bold: true, color: Some(color), ..Default::default() };
+            self.lines(&[Piece::Text(title.into(), style)], BODY_SIZE);
+            self.gap(BODY_SIZE * 0.3);
+        }
+        self.blocks(node);
+        self.left = left;
+        self.bars.pop();
+    }
+
+    /// `numbering` is where an ordered list starts and what follows its numbers
+    fn list<'a>(&mut self, node: &'a AstNode<'a>, numbering: Option<(usize, &str)>, tight: bool) {
+        let (left, was_tight) = (self.left, self.tight);
+        self.tight = tight;
+        for (i, item) in node.children().enumerate() {
+            self.marker = Some(match (&item.data.borrow().value, numbering) {
+                (NodeValue::TaskItem(task), _) => Marker::Check { checked: task.symbol.is_some() },
+                (_, Some((start, delimiter))) => {
+                    Marker::Number(format!("{}{delimiter}", start + i))
+                }
+                (_, None) => Marker::Bullet { depth: self.list_depth },
+            });
+            self.left = left + INDENT;
+            self.list_depth += 1;
+            self.blocks(item);
+            self.list_depth -= 1;
+            if self.marker.is_some() {
+                // an empty item
+                self.line(BODY_SIZE * LEADING, BODY_SIZE * ASCENT);
+            }
+            self.left = left;
+        }
+        self.tight = was_tight;
+        if tight {
+            self.gap(BODY_SIZE * 0.5);
+        }
+    }
+
+    fn code_block(&mut self, literal: &str) {
+        let padding = 8.0;
+        let height = CODE_SIZE * 1.35;
+        let columns =
+            ((self.right - self.left - 2.0 * padding) / (CODE_SIZE * 0.6)).max(1.0) as usize;
+
+        let mut rows: Vec<Vec<Glyph>> = vec![];
+        for line in literal.trim_end_matches('\n').split('\n') {
+            let line: Vec<Glyph> = line
+                .replace('\t', "    ")
+                .chars()
+                .map(|c| self.fonts.glyph(Font::Mono, c))
+                .collect();
+            if line.is_empty() {
+                rows.push(line);
+            } else {
+                rows.extend(line.chunks(columns).map(<[Glyph]>::to_vec));
+            }
+        }
+
+        let count = rows.len();
+        for (i, row) in rows.into_iter().enumerate() {
+            let above = if i == 0 { padding } else { 0.0 };
+            let below = if i + 1 == count { padding } else { 0.0 };
+            let top = self.line(above + height + below, above + CODE_SIZE * ASCENT);
+            let bottom = top - above - height - below;
+            self.fill_rect(
+                self.left,
+                bottom,
+                self.right - self.left,
+                top - bottom,
+                CODE_BACKGROUND,
+            );
+            let baseline = top - above - CODE_SIZE * ASCENT;
+            self.text(self.left + padding, baseline, Font::Mono, CODE_SIZE, TEXT, &row);
+        }
+    }
+
+    fn table<'a>(&mut self, node: &'a AstNode<'a>, alignments: &[TableAlignment]) {
+        let padding = 5.0;
+        let mut rows = vec![];
+        for row in node.children() {
+            let header = matches!(row.data.borrow().value, NodeValue::TableRow(true));
+            let style = Style { bold: header, ..Default::default() };
+            let cells: Vec<Vec<Piece>> = row
+                .children()
+                .map(|cell| {
+                    let mut pieces = vec![];
+                    inlines(cell, &style, &mut pieces);
+                    pieces
+                })
+                .collect();
+            rows.push((header, cells));
+        }
+
+        let columns = rows.iter().map(|(_, cells)| cells.len()).max().unwrap_or(0);
+        if columns == 0 {
+            return;
+        }
+        let mut natural = vec![0.0f32; columns];
+        for (_, cells) in &rows {
+            for (column, pieces) in cells.iter().enumerate() {
+                let width = wrap(self.fonts, pieces, TABLE_SIZE, f32::INFINITY)
+                    .iter()
+                    .map(|line| line.width)
+                    .fold(0.0, f32::max);
+                natural[column] = natural[column].max(width + 2.0 * padding);
+            }
+        }
+        let widths = column_widths(&natural, self.right - self.left);
+        let table_width: f32 = widths.iter().sum();
+
+        for (header, cells) in rows {
+            let wrapped: Vec<Vec<Line>> = (0..columns)
+                .map(|column| match cells.get(column) {
+                    Some(pieces) => {
+                        wrap(self.fonts, pieces, TABLE_SIZE, widths[column] - 2.0 * padding)
+                    }
+                    None => vec![],
+                })
+                .collect();
+            let line_count = wrapped.iter().map(Vec::len).max().unwrap_or(0).max(1);
+            let height = line_count as f32 * TABLE_SIZE * LEADING + 2.0 * padding;
+
+            let top = self.line(height, padding + TABLE_SIZE * ASCENT);
+            if header {
+                self.fill_rect(self.left, top - height, table_width, height, CODE_BACKGROUND);
+            }
+            let mut x = self.left;
+            for (column, lines) in wrapped.iter().enumerate() {
+                let inner = widths[column] - 2.0 * padding;
+                for (i, line) in lines.iter().enumerate() {
+                    let offset = match alignments.get(column) {
+                        Some(TableAlignment::Center) => (inner - line.width) / 2.0,
+                        Some(TableAlignment::Right) => inner - line.width,
+                        _ => 0.0,
+                    };
+                    let baseline =
+                        top - padding - TABLE_SIZE * ASCENT - i as f32 * TABLE_SIZE * LEADING;
+                    self.draw_line(line, x + padding + offset, baseline, TABLE_SIZE);
+                }
+                self.stroke_rect(x, top - height, widths[column], height, RULE);
+                x += widths[column];
+            }
+        }
+    }
+
+    /// Draws an image on a line of its own, scaled down to fit on a page, or its alt text if it
+    /// can't be drawn.
+    fn image(&mut self, url: &str, alt: &str) {
+        let Some(embed) = self.embeds.get(url) else { return };
+        let (width, height) = match embed {
+            Embed::Raster(raster) => (raster.width as f32, raster.height as f32),
+            Embed::Drawing(drawing) => (drawing.size().x as f32, drawing.size().y as f32),
+        };
+        let (width, height) = (width * PX, height * PX);
+        let scale = ((self.right - self.left) / width)
+            .min((PAGE_HEIGHT - 2.0 * MARGIN) / height)
+            .min(1.0);
+        let (width, height) = (width * scale, height * scale);
+
+        match embed {
+            Embed::Raster(raster) => {
+                let Some(name) = self.xobject(url.to_string(), raster) else {
+                    let style = Style { italic: true, color: Some(MUTED), ..Default::default() };
+                    self.lines(&[Piece::Text(alt.into(), style)], BODY_SIZE);
+                    return;
+                };
+                let top = self.line(height, BODY_SIZE * ASCENT);
+                let _ = writeln!(
+                    self.page.content,
+                    "q {width:.2} 0 0 {height:.2} {:.2} {:.2} cm /{name} Do Q",
+                    self.left,
+                    top - height
+                );
+            }
+            Embed::Drawing(drawing) => {
+                let top = self.line(height, BODY_SIZE * ASCENT);
+                self.drawing(url, drawing, top, scale * PX);
+            }
+        }
+        self.gap(4.0);
+    }
+
+    fn drawing(&mut self, url: &str, drawing: &Drawing, top: f32, scale: f32) {
+        let (min, size) = (drawing.min, drawing.size());
+        // drawings measure down from their top left, pages up from their bottom left
+        let _ = writeln!(
+            self.page.content,
+            "q {scale:.4} 0 0 {:.4} {:.2} {:.2} cm {:.2} {:.2} {:.2} {:.2} re W n 1 J 1 j",
+            -scale,
+            self.left - min.x as f32 * scale,
+            top + min.y as f32 * scale,
+            min.x,
+            min.y,
+            size.x,
+            size.y
+        );
+        for (i, item) in drawing.items.iter().enumerate() {
+            match item {
+                Item::Stroke { segments, color, opacity } => {
+                    let color = Rgb(
+                        color.red as f32 / 255.0,
+                        color.green as f32 / 255.0,
+                        color.blue as f32 / 255.0,
+                    );
+                    let state = self.opacity(*opacity);
+                    let _ = writeln!(self.page.content, "q /{state} gs {}", color.stroke());
+                    for run in segments.chunk_by(|a, b| a.width == b.width && a.end == b.start) {
+                        let start = run[0].start;
+                        let _ = write!(
+                            self.page.content,
+                            "{:.2} w {:.2} {:.2} m",
+                            run[0].width, start.x, start.y
+                        );
+                        for segment in run {
+                            let end = segment.end;
+                            let _ = match segment.handles {
+                                Some((a, b)) => write!(
+                                    self.page.content,
+                                    " {:.2} {:.2} {:.2} {:.2} {:.2} {:.2} c",
+                                    a.x, a.y, b.x, b.y, end.x, end.y
+                                ),
+                                None => write!(self.page.content, " {:.2} {:.2} l", end.x, end.y),
+                            };
+                        }
+                        self.page.content.push_str(" S\n");
+                    }
+                    self.page.content.push_str("Q\n");
+                }
+                Item::Image { min, size, opacity, raster } => {
+                    if let Some(name) = self.xobject(format!("{url}#{i}"), raster) {
+                        let state = self.opacity(*opacity);
+                        let _ = writeln!(
+                            self.page.content,
+                            "q /{state} gs {:.2} 0 0 {:.2} {:.2} {:.2} cm /{name} Do Q",
+                            size.x,
+                            -size.y,
+                            min.x,
+                            min.y + size.y
+                        );
+                    }
+                }
+            }
+        }
+        self.page.content.push_str("Q\n");
+    }
+
+    /// the name of an image's xobject, `None` if it's in a format pdf can't embed
+    fn xobject(&mut self, key: String, raster: &Raster) -> Option<String> {
+        if let Some(index) = self.image_indexes.get(&key) {
+            return index.map(|index| format!("Im{index}"));
+        }
+        let data = match raster.format {
+            Format::Jpeg { components: components @ (1 | 3 | 4) } => {
+                Some(ImageData::Jpeg { bytes: raster.bytes.clone(), components })
+            }
+            Format::Png => raster.pixels().map(|pixels| ImageData::Pixels {
+                channels: pixels.channels,
+                color: deflate(&pixels.color),
+                alpha: pixels.alpha.map(|alpha| deflate(&alpha)),
+            }),
+            _ => None,
+        };
+        let index = data.map(|data| {
+            self.images
+                .push(Image { width: raster.width, height: raster.height, data });
+            self.images.len() - 1
+        });
+        self.image_indexes.insert(key, index);
+        index.map(|index| format!("Im{index}"))
+    }
+
+    /// the name of the graphics state that draws with an opacity
+    fn opacity(&mut self, opacity: f32) -> String {
+        let level = (opacity.clamp(0.0, 1.0) * 255.0).round() as u8;
+        if !self.opacities.contains(&level) {
+            self.opacities.push(level);
+        }
+        format!("GS{level}")
+    }
+
+    /// Makes room for a line and returns its top, starting a new page if it doesn't fit. Draws
+    /// what runs alongside lines: the bars of quotes it's in, and the marker of a list item it
+    /// starts, on the baseline `ascent` below the top.
+    fn line(&mut self, height: f32, ascent: f32) -> f32 {
+        if self.y - height < MARGIN && self.y < PAGE_HEIGHT - MARGIN {
+            self.pages.push(mem::take(&mut self.page));
+            self.y = PAGE_HEIGHT - MARGIN;
+        }
+        let top = self.y;
+        for (x, color) in self.bars.clone() {
+            self.fill_rect(x, top - height, 3.0, height, color);
+        }
+        if let Some(marker) = self.marker.take() {
+            self.marker(marker, top - ascent);
+        }
+        self.y -= height;
+        top
+    }
+
+    /// Space between blocks, which isn't needed at the top of a page.
+    fn gap(&mut self, height: f32) {
+        if self.y >= PAGE_HEIGHT - MARGIN {
+            return;
+        }
+        let height = height.min(self.y - MARGIN).max(0.0);
+        for (x, color) in self.bars.clone() {
+            self.fill_rect(x, self.y - height, 3.0, height, color);
+        }
+        self.y -= height;
+    }
+
+    fn marker(&mut self, marker: Marker, baseline: f32) {
+        match marker {
+            Marker::Bullet { depth } => {
+                let (x, y, r) = (self.left - 10.0, baseline + BODY_SIZE * 0.3, 2.2);
+                // a circle from four curves
+                let k = r * 0.5523;
+                let _ = writeln!(
+                    self.page.content,
+                    "{} {} 0.8 w {:.2} {:.2} m {:.2} {:.2} {:.2} {:.2} {:.2} {:.2} c \
+                     {:.2} {:.2} {:.2} {:.2} {:.2} {:.2} c {:.2} {:.2} {:.2} {:.2} {:.2} {:.2} c \
+                     {:.2} {:.2} {:.2} {:.2} {:.2} {:.2} c {}",
+                    TEXT.fill(),
+                    TEXT.stroke(),
+                    x + r,
+                    y,
+                    x + r,
+                    y + k,
+                    x + k,
+                    y + r,
+                    x,
+                    y + r,
+                    x - k,
+                    y + r,
+                    x - r,
+                    y + k,
+                    x - r,
+                    y,
+                    x - r,
+                    y - k,
+                    x - k,
+                    y - r,
+                    x,
+                    y - r,
+                    x + k,
+                    y - r,
+                    x + r,
+                    y - k,
+                    x + r,
+                    y,
+                    if depth % 2 == 0 { "f" } else { "s" }
+                );
+            }
+            Marker::Number(label) => {
+                let label: Vec<Glyph> = label
+                    .chars()
+                    .map(|c| self.fonts.glyph(Font::Regular, c))
+                    .collect();
+                let style = Style::default();
+                let width: f32 = label
+                    .iter()
+                    .map(|glyph| style.measure(glyph, BODY_SIZE))
+                    .sum();
+                self.text(
+                    self.left - 5.0 - width,
+                    baseline,
+                    Font::Regular,
+                    BODY_SIZE,
+                    TEXT,
+                    &label,
+                );
+            }
+            Marker::Check { checked } => {
+                let (x, y, side) = (self.left - 15.0, baseline - 1.0, 9.0);
+                self.stroke_rect(x, y, side, side, MUTED);
+                if checked {
+                    let _ = writeln!(
+                        self.page.content,
+                        "{} 1.5 w {:.2} {:.2} m {:.2} {:.2} l {:.2} {:.2} l S",
+                        TEXT.stroke(),
+                        x + 2.0,
+                        y + 4.5,
+                        x + 4.0,
+                        y + 2.0,
+                        x + 7.5,
+                        y + 7.5
+                    );
+                }
+            }
+        }
+    }
+
+    fn text(&mut self, x: f32, baseline: f32, font: Font, size: f32, color: Rgb, text: &[Glyph]) {
+        for glyph in text {
+            self.used[glyph.face].entry(glyph.id).or_insert(glyph.c);
+        }
+        let _ = writeln!(
+            self.page.content,
+            "BT {} 1 0 {:.2} 1 {x:.2} {baseline:.2} Tm {} ET",
+            color.fill(),
+            font.slant(),
+            show(text, size)
+        );
+    }
+
+    fn fill_rect(&mut self, x: f32, y: f32, width: f32, height: f32, color: Rgb) {
+        let _ = writeln!(
+            self.page.content,
+            "{} {x:.2} {y:.2} {width:.2} {height:.2} re f",
+            color.fill()
+        );
+    }
+
+    fn stroke_rect(&mut self, x: f32, y: f32, width: f32, height: f32, color: Rgb) {
+        let _ = writeln!(
+            self.page.content,
+            "{} 0.6 w {x:.2} {y:.2} {width:.2} {height:.2} re S",
+            color.stroke()
+        );
+    }
+
+    fn stroke_line(&mut self, x: f32, y: f32, to: f32, color: Rgb) {
+        let _ = writeln!(
+            self.page.content,
+            "{} 0.6 w {x:.2} {y:.2} m {to:.2} {y:.2} l S",
+            color.stroke()
+        );
+    }
+
+    /// a horizontal line across the text
+    fn rule(&mut self, y: f32, color: Rgb) {
+        self.fill_rect(self.left, y - 0.5, self.right - self.left, 1.0, color);
+    }
+
+    /// Numbers the pages and writes out the document.
+    fn finish(mut self, title: &str) -> Vec<u8> {
+        self.pages.push(mem::take(&mut self.page));
+        let count = self.pages.len();
+        for i in 0..count {
+            let label: Vec<Glyph> = format!("{} / {count}", i + 1)
+                .chars()
+                .map(|c| self.fonts.glyph(Font::Regular, c))
+                .collect();
+            for glyph in &label {
+                self.used[glyph.face].entry(glyph.id).or_insert(glyph.c);
+            }
+            let width: f32 = label.iter().map(|glyph| glyph.width * 9.0 / 1000.0).sum();
+            let _ = writeln!(
+                self.pages[i].content,
+                "BT {} {:.2} {:.2} Td {} ET",
+                MUTED.fill(),
+                (PAGE_WIDTH - width) / 2.0,
+                MARGIN / 2.0,
+                show(&label, 9.0)
+            );
+        }
+
+        // catalog, page tree, info and resources, then fonts, images and pages
+        let faces: Vec<usize> = (0..self.used.len())
+            .filter(|face| !self.used[*face].is_empty())
+            .collect();
+        let fonts = 5;
+        let mut next = fonts + FONT_OBJECTS * faces.len();
+        let mut image_ids = vec![];
+        for image in &self.images {
+            let has_mask = matches!(image.data, ImageData::Pixels { alpha: Some(_), .. });
+            image_ids.push(next);
+            next += if has_mask { 2 } else { 1 };
+        }
+        let page_ids: Vec<usize> = (0..count).map(|i| next + 2 * i).collect();
+        let mut pdf = Writer::new(next - 1 + 2 * count);
+
+        pdf.object(1, "<< /Type /Catalog /Pages 2 0 R >>");
+        let kids: Vec<String> = page_ids.iter().map(|id| format!("{id} 0 R")).collect();
+        pdf.object(
+            2,
+            &format!(
+                "<< /Type /Pages /Kids [{}] /Count {count} /MediaBox [0 0 {PAGE_WIDTH} {PAGE_HEIGHT}] >>",
+                kids.join(" ")
+            ),
+        );
+        pdf.object(3, &format!("<< /Title {} /Producer (Lockbook) >>", text_string(title)));
+
+        let mut resources = String::from("<< /Font <<");
+        for (i, face) in faces.iter().enumerate() {
+            let _ = write!(resources, " /F{face} {} 0 R", fonts + FONT_OBJECTS * i);
+        }
+        resources.push_str(" >> /XObject <<");
+        for (i, id) in image_ids.iter().enumerate() {
+            let _ = write!(resources, " /Im{i} {id} 0 R");
+        }
+        resources.push_str(" >> /ExtGState <<");
+        for level in &self.opacities {
+            let alpha = *level as f32 / 255.0;
+            let _ = write!(resources, " /GS{level} << /ca {alpha:.3} /CA {alpha:.3} >>");
+        }
+        resources.push_str(" >> >>");
+        pdf.object(4, &resources);
+
+        for (i, face) in faces.iter().enumerate() {
+            self.fonts
+                .embed(*face, &self.used[*face], fonts + FONT_OBJECTS * i, &mut pdf);
+        }
+
+        for (image, id) in self.images.iter().zip(&image_ids) {
+            let size = format!(
+                "/Type /XObject /Subtype /Image /Width {} /Height {} /BitsPerComponent 8",
+                image.width, image.height
+            );
+            match &image.data {
+                ImageData::Jpeg { bytes, components } => {
+                    let color_space = match components {
+                        1 => "/DeviceGray",
+                        3 => "/DeviceRGB",
+                        _ => "/DeviceCMYK /Decode [1 0 1 0 1 0 1 0]",
+                    };
+                    pdf.stream(
+                        *id,
+                        &format!("{size} /ColorSpace {color_space} /Filter /DCTDecode"),
+                        bytes,
+                    );
+                }
+                ImageData::Pixels { channels, color, alpha } => {
+                    let color_space = if *channels == 1 { "/DeviceGray" } else { "/DeviceRGB" };
+                    let mask = if alpha.is_some() {
+                        format!(" /SMask {} 0 R", id + 1)
+                    } else {
+                        String::new()
+                    };
+                    pdf.stream(
+                        *id,
+                        &format!("{size} /ColorSpace {color_space} /Filter /FlateDecode{mask}"),
+                        color,
+                    );
+                    if let Some(alpha) = alpha {
+                        pdf.stream(
+                            id + 1,
+                            &format!("{size} /ColorSpace /DeviceGray /Filter /FlateDecode"),
+                            alpha,
+                        );
+                    }
+                }
+            }
+        }
+
+        for (page, id) in self.pages.iter().zip(&page_ids) {
+            let mut annotations = String::new();
+            for ([left, bottom, right, top], url) in &page.links {
+                let _ = write!(
+                    annotations,
+                    " << /Type /Annot /Subtype /Link /Rect [{left:.2} {bottom:.2} {right:.2} {top:.2}] \
+                     /Border [0 0 0] /A << /S /URI /URI {} >> >>",
+                    literal(url.as_bytes())
+                );
+            }
+            pdf.object(
+                *id,
+                &format!(
+                    "<< /Type /Page /Parent 2 0 R /Resources 4 0 R /Contents {} 0 R /Annots [{annotations} ] >>",
+                    id + 1
+                ),
+            );
+            pdf.stream(id + 1, "/Filter /FlateDecode", &deflate(page.content.as_bytes()));
+        }
+
+        pdf.finish()
+    }
+}
+
+/// the text of a block, with its formatting
+fn inlines<'a>(node: &'a AstNode<'a>, style: &Style, pieces: &mut Vec<Piece>) {
+    for child in node.children() {
+        let mut style = style.clone();
+        match &child.data.borrow().value {
+            NodeValue::Text(text) => pieces.push(Piece::Text(text.to_string(), style)),
+            NodeValue::Code(code) => {
+                style.code = true;
+                pieces.push(Piece::Text(code.literal.clone(), style));
+            }
+            NodeValue::Math(math) => {
+                style.code = true;
+                pieces.push(Piece::Text(math.literal.clone(), style));
+            }
+            NodeValue::ShortCode(short_code) => {
+                pieces.push(Piece::Text(format!(":{}:", short_code.code), style));
+            }
+            NodeValue::SoftBreak => pieces.push(Piece::Text(" ".into(), style)),
+            NodeValue::LineBreak => pieces.push(Piece::Break),
+            NodeValue::Image(link) => {
+                pieces.push(Piece::Image { url: link.url.clone(), alt: plain_text(child) });
+            }
+            NodeValue::HtmlInline(_) | NodeValue::FootnoteReference(_) => {}
+            value => {
+                match value {
+                    NodeValue::Emph => style.italic = true,
+                    NodeValue::Strong => style.bold = true,
+                    NodeValue::Strikethrough => style.strike = true,
+                    NodeValue::Underline => style.underline = true,
+                    NodeValue::Highlight => style.highlight = true,
+                    NodeValue::Superscript => style.script = Script::Super,
+                    NodeValue::Subscript => style.script = Script::Sub,
+                    NodeValue::Link(link) => style.link = Some(link.url.clone()),
+                    NodeValue::WikiLink(link) => style.link = Some(link.url.clone()),
+                    _ => {}
+                }
+                inlines(child, &style, pieces);
+            }
+        }
+    }
+}
+
+/// Columns keep their natural width when the table fits. When it doesn't, columns narrower than
+/// an even share of the width still do, and the rest split what's left in proportion.
+fn column_widths(natural: &[f32], available: f32) -> Vec<f32> {
+    if natural.iter().sum::<f32>() <= available {
+        return natural.to_vec();
+    }
+    let share = available / natural.len() as f32;
+    let narrow: f32 = natural.iter().filter(|width| **width <= share).sum();
+    let wide: f32 = natural.iter().filter(|width| **width > share).sum();
+    natural
+        .iter()
+        .map(|width| if *width <= share { *width } else { (available - narrow) * width / wide })
+        .collect()
+}
+
+fn plain_text<'a>(node: &'a AstNode<'a>) -> String {
+    let mut text = String::new();
+    for descendant in node.descendants() {
+        match &descendant.data.borrow().value {
+            NodeValue::Text(t) => text.push_str(t),
+            NodeValue::Code(code) => text.push_str(&code.literal),
+            _ => {}
+        }
+    }
+    text
+}
+
+/// Writes objects, keeping track of where each one starts for the cross-reference table.
+pub(super) struct Writer {
+    bytes: Vec<u8>,
+    offsets: Vec<usize>,
+}
+
+impl Writer {
+    fn new(objects: usize) -> Self {
+        Self { bytes: b"%PDF-1.6\n%\xe2\xe3\xcf\xd3\n".to_vec(), offsets: vec![0; objects] }
+    }
+
+    pub(super) fn object(&mut self, id: usize, body: &str) {
+        self.offsets[id - 1] = self.bytes.len();
+        let _ = write!(self.bytes, "{id} 0 obj\n{body}\nendobj\n");
+    }
+
+    pub(super) fn stream(&mut self, id: usize, dict: &str, data: &[u8]) {
+        self.offsets[id - 1] = self.bytes.len();
+        let _ = write!(self.bytes, "{id} 0 obj\n<< {dict} /Length {} >>\nstream\n", data.len());
+        self.bytes.extend_from_slice(data);
+        self.bytes.extend_from_slice(b"\nendstream\nendobj\n");
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        let xref = self.bytes.len();
+        let _ = write!(self.bytes, "xref\n0 {}\n0000000000 65535 f \n", self.offsets.len() + 1);
+        for offset in &self.offsets {
+            let _ = writeln!(self.bytes, "{offset:010} 00000 n ");
+        }
+        let _ = writeln!(
+            self.bytes,
+            "trailer\n<< /Size {} /Root 1 0 R /Info 3 0 R >>\nstartxref\n{xref}\n%%EOF",
+            self.offsets.len() + 1
+        );
+        self.bytes
+    }
+}
@@ -0,0 +1,268 @@
+//! Just enough of the image formats documents embed to size them, and to lay out a png's pixels
+//! for formats that can't embed it as it is.
+
+#[cfg(feature = "pdf")]
+use std::io::Read;
+
+#[cfg(feature = "pdf")]
+use flate2::read::ZlibDecoder;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Format {
+    Png,
+    Jpeg { components: u8 },
+    Gif,
+    Webp,
+}
+
+impl Format {
+    pub(crate) fn mime(self) -> &'static str {
+        match self {
+            Format::Png => "image/png",
+            Format::Jpeg { .. } => "image/jpeg",
+            Format::Gif => "image/gif",
+            Format::Webp => "image/webp",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct Raster {
+    pub format: Format,
+    /// only pdfs size images themselves
+    #[cfg_attr(not(feature = "pdf"), allow(dead_code))]
+    pub width: u32,
+    #[cfg_attr(not(feature = "pdf"), allow(dead_code))]
+    pub height: u32,
+    pub bytes: Vec<u8>,
+}
+
+/// A png's pixels, 8 bits a sample.
+#[cfg(feature = "pdf")]
+pub(crate) struct Pixels {
+    /// 1 for grayscale, 3 for rgb
+    pub channels: u8,
+    pub color: Vec<u8>,
+    pub alpha: Option<Vec<u8>>,
+}
+
+impl Raster {
+    /// Recognizes an image by its header, `None` if it's not an image or it's malformed.
+    pub(crate) fn decode(bytes: Vec<u8>) -> Option<Self> {
+        let (format, width, height) = if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            (Format::Png, be32(&bytes, 16)?, be32(&bytes, 20)?)
+        } else if bytes.starts_with(&[0xff, 0xd8]) {
+            jpeg_header(&bytes)?
+        } else if bytes.starts_with(b"GIF8") {
+            (Format::Gif, le16(&bytes, 6)? as u32, le16(&bytes, 8)? as u32)
+        } else if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WEBP") {
+            let (width, height) = webp_size(&bytes)?;
+            (Format::Webp, width, height)
+        } else {
+            return None;
+        };
+        if width == 0 || height == 0 {
+            return None;
+        }
+        Some(Self { format, width, height, bytes })
+    }
+
+    /// The pixels of a non-interlaced png, `None` for other images.
+    #[cfg(feature = "pdf")]
+    pub(crate) fn pixels(&self) -> Option<Pixels> {
+        if self.format != Format::Png {
+            return None;
+        }
+
+        let mut header = None;
+        let mut palette: &[u8] = &[];
+        let mut transparency: &[u8] = &[];
+        let mut compressed = vec![];
+        let mut i = 8;
+        while i + 8 <= self.bytes.len() {
+            let len = be32(&self.bytes, i)? as usize;
+            let kind = &self.bytes[i + 4..i + 8];
+            let data = self.bytes.get(i + 8..i + 8 + len)?;
+            match kind {
+                b"IHDR" => header = Some(data),
+                b"PLTE" => palette = data,
+                b"tRNS" => transparency = data,
+                b"IDAT" => compressed.extend_from_slice(data),
+                b"IEND" => break,
+                _ => {}
+            }
+            i += 12 + len;
+        }
+
+        let header = header?;
+        let (depth, color_type, interlace) = (*header.get(8)?, *header.get(9)?, *header.get(12)?);
+        if interlace != 0 {
+            return None;
+        }
+        let samples = match (color_type, depth) {
+            (0, 1 | 2 | 4 | 8 | 16) | (3, 1 | 2 | 4 | 8) => 1,
+            (2, 8 | 16) => 3,
+            (4, 8 | 16) => 2,
+            (6, 8 | 16) => 4,
+            _ => return None,
+        };
+
+        let (width, height) = (self.width as usize, self.height as usize);
+        let bits = samples * depth as usize;
+        let stride = (width * bits).div_ceil(8);
+        let filter_width = bits.div_ceil(8);
+
+        let mut filtered = vec![];
+        ZlibDecoder::new(compressed.as_slice())
+            .read_to_end(&mut filtered)
+            .ok()?;
+        if filtered.len() < (stride + 1) * height {
+            return None;
+        }
+
+        let mut rows = vec![0; stride * height];
+        for y in 0..height {
+            let filter = filtered[y * (stride + 1)];
+            let line = &filtered[y * (stride + 1) + 1..(y + 1) * (stride + 1)];
+            let (done, row) = rows.split_at_mut(y * stride);
+            let row = &mut row[..stride];
+            let above = if y == 0 { None } else { Some(&done[(y - 1) * stride..]) };
+            for x in 0..stride {
+                let a = if x >= filter_width { row[x - filter_width] } else { 0 };
+                let b = above.map_or(0, |above| above[x]);
+                let c = match above {
+                    Some(above) if x >= filter_width => above[x - filter_width],
+                    _ => 0,
+                };
+                let predicted = match filter {
+                    0 => 0,
+                    1 => a,
+                    2 => b,
+                    3 => ((a as u16 + b as u16) / 2) as u8,
+                    4 => paeth(a, b, c),
+                    _ => return None,
+                };
+                row[x] = line[x].wrapping_add(predicted);
+            }
+        }
+
+        // samples wider than a byte keep their high byte, narrower ones are scaled up to fill one
+        let sample = |row: &[u8], index: usize| -> u8 {
+            match depth {
+                16 => row[index * 2],
+                8 => row[index],
+                _ => {
+                    let per_byte = 8 / depth as usize;
+                    let byte = row[index / per_byte];
+                    let shift = 8 - depth as usize * (index % per_byte + 1);
+                    let value = (byte >> shift) & ((1 << depth) - 1);
+                    if color_type == 3 { value } else { value * (255 / ((1 << depth) - 1)) }
+                }
+            }
+        };
+
+        let channels = if matches!(color_type, 0 | 4) { 1 } else { 3 };
+        let has_alpha =
+            matches!(color_type, 4 | 6) || (color_type == 3 && !transparency.is_empty());
+        let mut color = Vec::with_capacity(width * height * channels);
+        let mut alpha = Vec::with_capacity(if has_alpha { width * height } else { 0 });
+        for row in rows.chunks(stride) {
+            for x in 0..width {
+                match color_type {
+                    0 => color.push(sample(row, x)),
+                    2 => color.extend((0..3).map(|s| sample(row, x * 3 + s))),
+                    3 => {
+                        let index = sample(row, x) as usize;
+                        color.extend_from_slice(palette.get(index * 3..index * 3 + 3)?);
+                        if has_alpha {
+                            alpha.push(transparency.get(index).copied().unwrap_or(255));
+                        }
+                    }
+                    4 => {
+                        color.push(sample(row, x * 2));
+                        alpha.push(sample(row, x * 2 + 1));
+                    }
+                    _ => {
+                        color.extend((0..3).map(|s| sample(row, x * 4 + s)));
+                        alpha.push(sample(row, x * 4 + 3));
+                    }
+                }
+            }
+        }
+
+        Some(Pixels {
+            channels: channels as u8,
+            color,
+            alpha: if has_alpha { Some(alpha) } else { None },
+        })
+    }
+}
+
+#[cfg(feature = "pdf")]
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// a jpeg's size and number of components, from its start of frame segment
+fn jpeg_header(bytes: &[u8]) -> Option<(Format, u32, u32)> {
+    let mut i = 2;
+    loop {
+        if *bytes.get(i)? != 0xff {
+            return None;
+        }
+        let marker = *bytes.get(i + 1)?;
+        if marker == 0xff {
+            i += 1;
+            continue;
+        }
+        if (0xd0..=0xd9).contains(&marker) || marker == 0x01 {
+            i += 2;
+            continue;
+        }
+        if (0xc0..=0xcf).contains(&marker) && !matches!(marker, 0xc4 | 0xc8 | 0xcc) {
+            let height = be16(bytes, i + 5)? as u32;
+            let width = be16(bytes, i + 7)? as u32;
+            let components = *bytes.get(i + 9)?;
+            return Some((Format::Jpeg { components }, width, height));
+        }
+        i += 2 + be16(bytes, i + 2)? as usize;
+    }
+}
+
+fn webp_size(bytes: &[u8]) -> Option<(u32, u32)> {
+    match bytes.get(12..16)? {
+        b"VP8X" => {
+            let le24 = |i: usize| Some(le16(bytes, i)? as u32 | (*bytes.get(i + 2)? as u32) << 16);
+            Some((le24(24)? + 1, le24(27)? + 1))
+        }
+        b"VP8 " => Some((le16(bytes, 26)? as u32 & 0x3fff, le16(bytes, 28)? as u32 & 0x3fff)),
+        b"VP8L" => {
+            let b = bytes.get(21..25)?;
+            let width = 1 + (((b[1] as u32 & 0x3f) << 8) | b[0] as u32);
+            let height =
+                1 + (((b[3] as u32 & 0xf) << 10) | (b[2] as u32) << 2 | (b[1] as u32 & 0xc0) >> 6);
+            Some((width, height))
+        }
+        _ => None,
+    }
+}
+
+fn be32(bytes: &[u8], i: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(bytes.get(i..i + 4)?.try_into().ok()?))
+}
+
+fn be16(bytes: &[u8], i: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(bytes.get(i..i + 2)?.try_into().ok()?))
+}
+
+fn le16(bytes: &[u8], i: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(i..i + 2)?.try_into().ok()?))
+}
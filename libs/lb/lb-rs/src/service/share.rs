@@ -1,11 +1,15 @@
 use crate::LocalLb;
-use crate::model::api::GetPublicKeyRequest;
+use crate::model::api::{ContentFormat, GetPublicKeyRequest};
 use crate::model::errors::{LbErr, LbResult};
 use crate::model::file::{File, ShareMode};
+use crate::model::file_like::FileLike;
 use crate::model::file_metadata::Owner;
 use crate::model::tree_like::TreeLike;
 use crate::service::events::Actor;
+use crate::service::groups::is_group;
+use crate::service::streams::DocumentContent;
 use libsecp256k1::PublicKey;
+use std::collections::HashMap;
 use uuid::Uuid;
 
 impl LocalLb {
@@ -86,12 +90,131 @@ impl LocalLb {
         Ok(())
     }
 
+    /// Removes `username`'s share of `id`. Without `rotate_keys`, they lose access to the file
+    /// but keep the keys they had, which still decrypt anything they cached. With `rotate_keys`,
+    /// `id` and everything in it get new keys: names and documents are re-encrypted, and other
+    /// users it's shared with get the new key. Only the owner can rotate keys, and the documents
+    /// in the folder are read, so it may download documents this device doesn't have. Chunked
+    /// documents are stored again under a new chunk key. Versions retained from before the
+    /// rotation can't be read anymore.
+    ///
+    /// callers of this function should be prepared to handle:
+    /// - [crate::LbErrKind::ShareNonexistent]
+    /// - [crate::LbErrKind::InsufficientPermission]
+    /// - [crate::LbErrKind::ServerUnreachable]
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn revoke_share(&self, id: Uuid, username: &str, rotate_keys: bool) -> LbResult<()> {
+        let sharee = self.public_key_of(username).await?;
+        if !rotate_keys {
+            return self.delete_share(&id, Some(sharee)).await;
+        }
+
+        let docs = {
+            let tx = self.ro_tx().await;
+            let db = tx.db();
+            let mut tree = (&db.base_metadata).to_staged(&db.local_metadata).to_lazy();
+
+            let mut docs = vec![];
+            for descendant in tree.descendants(&id)?.into_iter().chain(Some(id)) {
+                let file = tree.find(&descendant)?;
+                if file.is_document()
+                    && file.document_hmac().is_some()
+                    && !tree.calculate_deleted(&descendant)?
+                {
+                    docs.push(descendant);
+                }
+            }
+            docs
+        };
+
+        let mut documents = HashMap::new();
+        let mut rekeyed_chunks = HashMap::new();
+        for doc in docs {
+            if let (Some(hmac), content) = self.read_raw_document_with_hmac(doc, false).await? {
+                // chunks have a key of their own, which the revoked user knows too
+                let content = match content {
+                    DocumentContent::Chunked(manifest) if cfg!(target_family = "wasm") => {
+                        self.read_chunks(doc, &manifest).await?
+                    }
+                    DocumentContent::Chunked(manifest) => {
+                        let manifest = self.rekey_chunks(doc, &manifest).await?;
+                        let chunks = manifest.chunks.iter().map(|chunk| chunk.hmac).collect();
+                        rekeyed_chunks.insert(doc, chunks);
+                        manifest.to_bytes()?
+                    }
+                    DocumentContent::Whole(content) => content,
+                };
+                documents.insert(doc, (hmac, content));
+            }
+        }
+
+        let mut tx = self.begin_tx().await;
+        let db = tx.db();
+        let mut tree = (&db.base_metadata)
+            .to_staged(&mut db.local_metadata)
+            .to_lazy();
+
+        let (metas, encrypted_documents) =
+            tree.rotate_keys_op(&id, Some(sharee), &documents, &self.keychain)?;
+        let mut manifests = vec![];
+        for (id, hmac, document) in &encrypted_documents {
+            self.docs.insert(*id, Some(*hmac), document).await?;
+            if let Some(chunks) = rekeyed_chunks.remove(id) {
+                db.chunk_uploads.insert(*id, (*hmac, chunks))?;
+                manifests.push((*id, *hmac));
+            }
+        }
+        tree.stage_validate_and_promote(
+            metas,
+            Owner(self.keychain.get_pk()?),
+            &self.keychain.groups()?,
+        )?;
+        for (id, hmac) in manifests {
+            db.set_content_format(id, hmac, ContentFormat::Manifest)?;
+        }
+
+        tx.end();
+        self.events.meta_changed(Actor::User(None));
+
+        Ok(())
+    }
+
+    /// looks `username` up among known users before asking the server
+    pub(crate) async fn public_key_of(&self, username: &str) -> LbResult<PublicKey> {
+        let username = username.to_lowercase();
+        let known = {
+            let tx = self.ro_tx().await;
+            let db = tx.db();
+            db.pub_key_lookup
+                .get()
+                .iter()
+                .find(|(owner, known)| **known == username && !is_group(db, owner))
+                .map(|(owner, _)| owner.0)
+        };
+        if let Some(pk) = known {
+            return Ok(pk);
+        }
+
+        Ok(self
+            .client
+            .request(self.get_account()?, GetPublicKeyRequest { username })
+            .await
+            .map_err(LbErr::from)?
+            .key)
+    }
+
     #[instrument(level = "debug", skip(self))]
     pub async fn known_usernames(&self) -> LbResult<Vec<String>> {
         let db = self.ro_tx().await;
         let db = db.db();
 
-        Ok(db.pub_key_lookup.get().values().cloned().collect())
+        Ok(db
+            .pub_key_lookup
+            .get()
+            .iter()
+            .filter(|(owner, _)| !is_group(db, owner))
+            .map(|(_, username)| username.clone())
+            .collect())
     }
 
     /// Whether `username` is a known Lockbook account (local cache, then server).
@@ -0,0 +1,271 @@
+use std::collections::VecDeque;
+use std::mem;
+
+use crate::model::api::{ContentFormat, GetChunkRequest};
+use crate::model::chunks::{ChunkManifest, ChunkRef, Chunker};
+use crate::model::clock::get_time;
+use crate::model::crypto::{AESKey, DecryptedDocument};
+use crate::model::errors::{LbErrKind, LbResult};
+use crate::model::tree_like::TreeLike;
+use crate::model::{symkey, validate};
+use crate::{Lb, LocalLb};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::activity;
+use super::documents::{compress_encrypt_document, decrypt_decompress_document};
+use super::events::Actor;
+
+/// A document's content as it's stored: the whole document, or the manifest of a document that's
+/// stored in chunks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum DocumentContent {
+    Whole(DecryptedDocument),
+    Chunked(ChunkManifest),
+}
+
+impl LocalLb {
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn read_document_content(
+        &self, id: Uuid, user_activity: bool,
+    ) -> LbResult<DocumentContent> {
+        let (_, content) = self.read_raw_document_with_hmac(id, user_activity).await?;
+        Ok(content)
+    }
+
+    /// reads one chunk of a chunked document, fetching it from the server if it isn't on this
+    /// device
+    #[instrument(level = "debug", skip(self, key), err(Debug))]
+    pub async fn read_chunk(
+        &self, id: Uuid, key: AESKey, chunk: ChunkRef,
+    ) -> LbResult<DecryptedDocument> {
+        let encrypted = match self.docs.maybe_get_chunk(id, chunk.hmac).await? {
+            Some(encrypted) => encrypted,
+            None => {
+                let encrypted = self
+                    .client
+                    .request(self.get_account()?, GetChunkRequest { id, hmac: chunk.hmac })
+                    .await?
+                    .content;
+                self.docs.insert_chunk(id, chunk.hmac, &encrypted).await?;
+                encrypted
+            }
+        };
+        decrypt_decompress_document(&key, &encrypted)
+    }
+
+    /// encrypts and stores one chunk of a document being written. The document doesn't change
+    /// until a manifest including the chunk is committed with [Self::commit_chunks].
+    #[instrument(level = "debug", skip(self, key, content), err(Debug))]
+    pub async fn write_chunk(&self, id: Uuid, key: AESKey, content: Vec<u8>) -> LbResult<ChunkRef> {
+        no_chunks_on_wasm()?;
+        let (hmac, encrypted) = compress_encrypt_document(&key, &content)?;
+        if !self.docs.chunk_exists(id, hmac) {
+            self.docs.insert_chunk(id, hmac, &encrypted).await?;
+        }
+        Ok(ChunkRef { hmac, size: content.len() as u64 })
+    }
+
+    /// makes `manifest` the document's content. Its chunks are uploaded the next time we sync,
+    /// before the manifest itself.
+    #[instrument(level = "debug", skip(self, manifest), err(Debug))]
+    pub async fn commit_chunks(&self, id: Uuid, manifest: ChunkManifest) -> LbResult<()> {
+        no_chunks_on_wasm()?;
+        let key = {
+            let tx = self.ro_tx().await;
+            let db = tx.db();
+            let mut tree = (&db.base_metadata).to_staged(&db.local_metadata).to_lazy();
+            validate::is_document(tree.find(&id)?)?;
+            tree.decrypt_key(&id, &self.keychain)?
+        };
+
+        let (hmac, encrypted) = compress_encrypt_document(&key, &manifest.to_bytes()?)?;
+        let encrypted_size = encrypted.value.len();
+        self.docs.insert_pending(id, hmac, &encrypted).await?;
+
+        {
+            let mut tx = self.begin_tx().await;
+            let db = tx.db();
+            let mut tree = (&db.base_metadata)
+                .to_staged(&mut db.local_metadata)
+                .to_lazy();
+            self.docs.promote_pending(id, hmac).await?;
+            tree.overwrite_document_hmac(&id, Some(hmac), Some(encrypted_size), &self.keychain)?;
+            let chunks = manifest.chunks.iter().map(|chunk| chunk.hmac).collect();
+            db.chunk_uploads.insert(id, (hmac, chunks))?;
+            db.set_content_format(id, hmac, ContentFormat::Manifest)?;
+            tx.end();
+        }
+
+        self.events.doc_written(id, Actor::User(None));
+        self.add_doc_event(activity::DocEvent::Write(id, get_time().0))
+            .await?;
+
+        Ok(())
+    }
+
+    /// the key for new chunks of this document. Chunked documents keep the key they have, so
+    /// chunks that didn't change between versions don't need to be stored or uploaded again. The
+    /// key is only replaced when the document's keys are rotated; see [Self::rekey_chunks].
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn chunk_key(&self, id: Uuid) -> LbResult<AESKey> {
+        Ok(match self.read_document_content(id, false).await? {
+            DocumentContent::Chunked(manifest) => manifest.key,
+            DocumentContent::Whole(_) => symkey::generate_key(),
+        })
+    }
+
+    /// stores a chunked document's chunks again under a new key, for when the users who knew the
+    /// old key shouldn't be able to read what's written from now on
+    pub(crate) async fn rekey_chunks(
+        &self, id: Uuid, manifest: &ChunkManifest,
+    ) -> LbResult<ChunkManifest> {
+        let key = symkey::generate_key();
+        let mut chunks = Vec::with_capacity(manifest.chunks.len());
+        for chunk in &manifest.chunks {
+            let content = self.read_chunk(id, manifest.key, *chunk).await?;
+            chunks.push(self.write_chunk(id, key, content).await?);
+        }
+        Ok(ChunkManifest { key, chunks })
+    }
+
+    pub(crate) async fn read_chunks(
+        &self, id: Uuid, manifest: &ChunkManifest,
+    ) -> LbResult<DecryptedDocument> {
+        let mut doc = Vec::with_capacity(manifest.len() as usize);
+        for chunk in &manifest.chunks {
+            doc.extend(self.read_chunk(id, manifest.key, *chunk).await?);
+        }
+        Ok(doc)
+    }
+}
+
+/// Chunks can't be stored on wasm, so documents written there are always written whole. Chunked
+/// documents written elsewhere can still be read; their chunks are fetched each time.
+fn no_chunks_on_wasm() -> LbResult<()> {
+    if cfg!(target_family = "wasm") {
+        return Err(
+            LbErrKind::Unexpected("chunked documents can't be written on wasm".into()).into()
+        );
+    }
+    Ok(())
+}
+
+impl Lb {
+    /// Opens a document for reading a piece at a time. Documents written with [Self::open_writer]
+    /// are read a chunk at a time; other documents are read all at once.
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn open_reader(&self, id: Uuid) -> LbResult<DocumentReader> {
+        Ok(match self.read_document_content(id, true).await? {
+            DocumentContent::Whole(doc) => DocumentReader {
+                lb: self.clone(),
+                id,
+                key: Default::default(),
+                len: doc.len() as u64,
+                whole: Some(doc),
+                chunks: Default::default(),
+            },
+            DocumentContent::Chunked(manifest) => DocumentReader {
+                lb: self.clone(),
+                id,
+                key: manifest.key,
+                len: manifest.len(),
+                whole: None,
+                chunks: manifest.chunks.into(),
+            },
+        })
+    }
+
+    /// Opens a document for writing a piece at a time, replacing its content once
+    /// [DocumentWriter::finish] is called. Content is split into chunks as it's written, and at
+    /// most one chunk is held in memory at a time. On wasm, content is held until it's finished
+    /// and written whole.
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn open_writer(&self, id: Uuid) -> LbResult<DocumentWriter> {
+        let key = self.chunk_key(id).await?;
+        Ok(DocumentWriter {
+            lb: self.clone(),
+            id,
+            key,
+            chunker: Default::default(),
+            buffer: vec![],
+            chunks: vec![],
+        })
+    }
+}
+
+pub struct DocumentReader {
+    lb: Lb,
+    id: Uuid,
+    key: AESKey,
+    len: u64,
+    whole: Option<DecryptedDocument>,
+    chunks: VecDeque<ChunkRef>,
+}
+
+impl DocumentReader {
+    /// size of the whole document
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// the next piece of the document, or `None` once all of it has been read
+    pub async fn read_next(&mut self) -> LbResult<Option<DecryptedDocument>> {
+        if let Some(doc) = self.whole.take() {
+            return Ok(Some(doc));
+        }
+        match self.chunks.pop_front() {
+            Some(chunk) => Ok(Some(self.lb.read_chunk(self.id, self.key, chunk).await?)),
+            None => Ok(None),
+        }
+    }
+}
+
+pub struct DocumentWriter {
+    lb: Lb,
+    id: Uuid,
+    key: AESKey,
+    chunker: Chunker,
+    buffer: Vec<u8>,
+    chunks: Vec<ChunkRef>,
+}
+
+impl DocumentWriter {
+    pub async fn write(&mut self, mut data: &[u8]) -> LbResult<()> {
+        if cfg!(target_family = "wasm") {
+            self.buffer.extend_from_slice(data);
+            return Ok(());
+        }
+        while let Some(boundary) = self.chunker.next_boundary(data) {
+            let (end_of_chunk, rest) = data.split_at(boundary);
+            self.buffer.extend_from_slice(end_of_chunk);
+            self.flush_chunk().await?;
+            data = rest;
+        }
+        self.buffer.extend_from_slice(data);
+        Ok(())
+    }
+
+    /// replaces the document's content with everything that was written
+    pub async fn finish(mut self) -> LbResult<()> {
+        if cfg!(target_family = "wasm") {
+            return self.lb.write_document(self.id, &self.buffer).await;
+        }
+        if !self.buffer.is_empty() {
+            self.flush_chunk().await?;
+        }
+        let manifest = ChunkManifest { key: self.key, chunks: self.chunks };
+        self.lb.commit_chunks(self.id, manifest).await
+    }
+
+    async fn flush_chunk(&mut self) -> LbResult<()> {
+        let content = mem::take(&mut self.buffer);
+        let chunk = self.lb.write_chunk(self.id, self.key, content).await?;
+        self.chunks.push(chunk);
+        Ok(())
+    }
+}
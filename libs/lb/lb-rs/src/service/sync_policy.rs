@@ -0,0 +1,212 @@
+use crate::LocalLb;
+use crate::model::api::ContentFormat;
+use crate::model::chunks::ChunkRef;
+use crate::model::crypto::AESKey;
+use crate::model::errors::{LbErrKind, LbResult};
+use crate::model::file_like::FileLike;
+use crate::model::file_metadata::DocumentHmac;
+use crate::model::tree_like::TreeLike;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Which documents sync keeps on this device. A policy set on a folder applies to everything
+/// inside it, unless a folder closer to the file has a policy of its own. Files without a policy
+/// are pulled when they change if we already had a prior version of them.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum SyncPolicy {
+    /// every document is pulled during sync and is never evicted
+    AlwaysLocal,
+    /// documents are only pulled when they're read, and may be evicted to stay under the disk
+    /// budget
+    OnDemand,
+}
+
+impl LocalLb {
+    /// callers of this function should be prepared to handle:
+    /// - [crate::LbErrKind::FileNonexistent]
+    /// - [crate::LbErrKind::FileNotFolder]
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn set_sync_policy(&self, id: Uuid, policy: SyncPolicy) -> LbResult<()> {
+        let mut tx = self.begin_tx().await;
+        let db = tx.db();
+
+        let mut tree = (&db.base_metadata).to_staged(&db.local_metadata).to_lazy();
+
+        let file = tree.maybe_find(&id).ok_or(LbErrKind::FileNonexistent)?;
+
+        if !file.is_folder() {
+            return Err(LbErrKind::FileNotFolder.into());
+        }
+
+        if tree.calculate_deleted(&id)? {
+            return Err(LbErrKind::FileNonexistent.into());
+        }
+
+        db.sync_policies.insert(id, policy)?;
+        tx.end();
+
+        self.evict_on_demand_docs().await
+    }
+
+    /// removes the policy set on this folder, after which it inherits its parent's policy
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn clear_sync_policy(&self, id: Uuid) -> LbResult<()> {
+        let mut tx = self.begin_tx().await;
+        let db = tx.db();
+
+        db.sync_policies.remove(&id)?;
+        tx.end();
+
+        self.evict_on_demand_docs().await
+    }
+
+    /// the policy that applies to this file, whether it was set on the file or inherited
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn get_sync_policy(&self, id: Uuid) -> LbResult<Option<SyncPolicy>> {
+        let tx = self.ro_tx().await;
+        let db = tx.db();
+
+        let tree = (&db.base_metadata).to_staged(&db.local_metadata);
+        if tree.maybe_find(&id).is_none() {
+            return Err(LbErrKind::FileNonexistent.into());
+        }
+
+        effective_sync_policy(&tree, db.sync_policies.get(), &id)
+    }
+
+    /// sets how many bytes of on-demand documents may stay cached on this device. `None` means
+    /// there's no limit, and documents that were read stay cached.
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn set_disk_budget(&self, bytes: Option<u64>) -> LbResult<()> {
+        let mut tx = self.begin_tx().await;
+        let db = tx.db();
+
+        match bytes {
+            Some(bytes) => {
+                db.disk_budget.insert(bytes)?;
+            }
+            None => {
+                db.disk_budget.clear()?;
+            }
+        }
+        tx.end();
+
+        self.evict_on_demand_docs().await
+    }
+
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn get_disk_budget(&self) -> LbResult<Option<u64>> {
+        let tx = self.ro_tx().await;
+        Ok(tx.db().disk_budget.get().copied())
+    }
+
+    /// deletes cached on-demand documents, least recently used first, until what's left fits in
+    /// the disk budget. Only documents without unsynced edits are evicted; they're fetched again
+    /// the next time they're read. A chunked document's chunks count towards the budget and are
+    /// evicted along with its manifest.
+    pub(crate) async fn evict_on_demand_docs(&self) -> LbResult<()> {
+        let mut candidates: Vec<(Uuid, DocumentHmac, Option<AESKey>, i64)> = vec![];
+        let budget = {
+            let tx = self.ro_tx().await;
+            let db = tx.db();
+
+            let Some(budget) = db.disk_budget.get().copied() else {
+                return Ok(());
+            };
+
+            let mut last_used: HashMap<Uuid, i64> = HashMap::new();
+            for event in db.doc_events.get() {
+                let used = last_used.entry(event.id()).or_default();
+                *used = (*used).max(event.timestamp());
+            }
+
+            let policies = db.sync_policies.get();
+            let mut tree = (&db.base_metadata).to_staged(&db.local_metadata).to_lazy();
+            for id in tree.ids() {
+                let file = tree.find(&id)?;
+                if !file.is_document() {
+                    continue;
+                }
+                let Some(hmac) = file.document_hmac().copied() else {
+                    continue;
+                };
+                let synced = tree
+                    .tree
+                    .base
+                    .maybe_find(&id)
+                    .is_some_and(|base| base.document_hmac() == Some(&hmac));
+                if !synced || tree.calculate_deleted(&id)? {
+                    continue;
+                }
+                if effective_sync_policy(&tree, policies, &id)? != Some(SyncPolicy::OnDemand) {
+                    continue;
+                }
+                // only chunked documents need their manifest read
+                let key = if db.content_format(id, hmac) == ContentFormat::Manifest {
+                    Some(tree.decrypt_key(&id, &self.keychain)?)
+                } else {
+                    None
+                };
+                candidates.push((id, hmac, key, last_used.get(&id).copied().unwrap_or_default()));
+            }
+
+            budget
+        };
+
+        let mut cached = vec![];
+        let mut total = 0;
+        for (id, hmac, key, last_used) in candidates {
+            let Some(mut size) = self.docs.maybe_size(id, Some(hmac)).await? else {
+                continue;
+            };
+            let mut chunks: Vec<ChunkRef> = vec![];
+            if let Some(key) = key {
+                if let Some(manifest) = self.stored_manifest(id, hmac, &key).await? {
+                    for chunk in manifest.chunks {
+                        if let Some(chunk_size) = self.docs.maybe_chunk_size(id, chunk.hmac).await?
+                        {
+                            size += chunk_size;
+                            chunks.push(chunk);
+                        }
+                    }
+                }
+            }
+            total += size;
+            cached.push((id, hmac, chunks, last_used, size));
+        }
+
+        cached.sort_by_key(|(_, _, _, last_used, _)| *last_used);
+        for (id, hmac, chunks, _, size) in cached {
+            if total <= budget {
+                break;
+            }
+            self.docs.delete(id, Some(hmac)).await?;
+            for chunk in chunks {
+                self.docs.delete_chunk(id, chunk.hmac).await?;
+            }
+            total -= size;
+        }
+
+        Ok(())
+    }
+}
+
+/// the policy of the nearest folder (or the file itself) that has one
+pub(crate) fn effective_sync_policy<T: TreeLike>(
+    tree: &T, policies: &HashMap<Uuid, SyncPolicy>, id: &Uuid,
+) -> LbResult<Option<SyncPolicy>> {
+    let mut id = *id;
+    loop {
+        if let Some(policy) = policies.get(&id) {
+            return Ok(Some(*policy));
+        }
+        let Some(file) = tree.maybe_find(&id) else {
+            return Ok(None);
+        };
+        if file.is_root() {
+            return Ok(None);
+        }
+        id = *file.parent();
+    }
+}
@@ -0,0 +1,78 @@
+use crate::LocalLb;
+use crate::model::api::EmptyTrashRequest;
+use crate::model::errors::{LbErrKind, LbResult};
+use crate::model::file::File;
+use crate::model::file_like::FileLike;
+use crate::model::tree_like::TreeLike;
+use uuid::Uuid;
+
+use super::events::Actor;
+
+impl LocalLb {
+    /// lists files that were deleted and can still be restored. Only the files that were deleted
+    /// explicitly are listed; their descendants come back with them when they're restored.
+    /// Files stay in the trash until the server's trash period elapses or the trash is emptied.
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn list_trash(&self) -> LbResult<Vec<File>> {
+        let tx = self.ro_tx().await;
+        let db = tx.db();
+
+        let mut tree = (&db.base_metadata).to_staged(&db.local_metadata).to_lazy();
+
+        let mut trashed = vec![];
+        for id in tree.ids() {
+            let file = tree.find(&id)?;
+            if !file.explicitly_deleted() || file.is_link() {
+                continue;
+            }
+            if tree.in_pending_share(&id)? {
+                continue;
+            }
+            trashed.push(id);
+        }
+
+        tree.decrypt_all(&self.keychain, trashed.into_iter(), &db.pub_key_lookup, false)
+    }
+
+    /// brings a deleted file (and whatever was deleted along with it) back. If the file's parent
+    /// is still deleted, the file is restored to the root.
+    ///
+    /// callers of this function should be prepared to handle:
+    /// - [crate::LbErrKind::FileNonexistent]
+    /// - [crate::LbErrKind::Validation] with a path conflict, if a file with the same name now
+    ///   exists where the file is being restored to
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn restore(&self, id: &Uuid) -> LbResult<()> {
+        let mut tx = self.begin_tx().await;
+        let db = tx.db();
+
+        let root = db.root.get().copied().ok_or(LbErrKind::RootNonexistent)?;
+        let mut tree = (&db.base_metadata)
+            .to_staged(&mut db.local_metadata)
+            .to_lazy();
+
+        if tree.maybe_find(id).is_none() {
+            return Err(LbErrKind::FileNonexistent.into());
+        }
+        if !tree.calculate_deleted(id)? {
+            return Ok(());
+        }
+
+        tree.restore(id, &root, &self.keychain)?;
+
+        tx.end();
+
+        self.events.meta_changed(Actor::User(None));
+
+        Ok(())
+    }
+
+    /// permanently deletes everything in the trash, on this device and on the server. Other
+    /// devices drop these files the next time they sync.
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn empty_trash(&self) -> LbResult<()> {
+        let acc = self.get_account()?;
+        self.client.request(acc, EmptyTrashRequest {}).await?;
+        self.sync().await
+    }
+}
@@ -0,0 +1,86 @@
+use crate::LocalLb;
+use crate::model::api::{ContentFormat, DocumentVersion, GetDocRequestV2, GetDocVersionsRequest};
+use crate::model::chunks::ChunkManifest;
+use crate::model::crypto::DecryptedDocument;
+use crate::model::errors::{LbErrKind, LbResult};
+use crate::model::file_like::FileLike;
+use crate::model::file_metadata::{DocumentHmac, FileType};
+use crate::model::tree_like::TreeLike;
+use crate::model::validate;
+use uuid::Uuid;
+
+use super::documents::decrypt_decompress_document;
+
+impl LocalLb {
+    /// lists the prior versions of a document the server has retained, newest first. How many
+    /// versions are kept, and for how long, depends on the owner's tier. Retained versions count
+    /// against the owner's usage.
+    ///
+    /// callers of this function should be prepared to handle:
+    /// - [crate::LbErrKind::FileNonexistent]
+    /// - [crate::LbErrKind::FileNotDocument]
+    /// - [crate::LbErrKind::ServerUnreachable]
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn list_versions(&self, id: Uuid) -> LbResult<Vec<DocumentVersion>> {
+        let id = self.version_target(id).await?;
+        let acc = self.get_account()?;
+        let resp = self
+            .client
+            .request(acc, GetDocVersionsRequest { id })
+            .await?;
+        Ok(resp.versions)
+    }
+
+    /// fetches and decrypts a prior version of a document. Nothing is written locally.
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn read_version(&self, id: Uuid, hmac: DocumentHmac) -> LbResult<DecryptedDocument> {
+        let id = self.version_target(id).await?;
+        let acc = self.get_account()?;
+        let version = self
+            .client
+            .request(acc, GetDocRequestV2 { id, hmac })
+            .await?;
+
+        let (key, formats) = {
+            let tx = self.ro_tx().await;
+            let db = tx.db();
+            let mut tree = (&db.base_metadata).to_staged(&db.local_metadata).to_lazy();
+            (tree.decrypt_key(&id, &self.keychain)?, db.content_formats_of(id))
+        };
+        let doc = decrypt_decompress_document(&key, &version.content)?;
+        match version.format {
+            ContentFormat::Document | ContentFormat::Delta => {
+                self.apply_deltas(id, &key, doc, version.format, &formats)
+                    .await
+            }
+            ContentFormat::Manifest => {
+                self.read_chunks(id, &ChunkManifest::from_bytes(&doc)?)
+                    .await
+            }
+        }
+    }
+
+    /// writes the contents of a prior version as the document's current contents. The contents
+    /// being replaced become a version themselves, so a restore can be undone.
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn restore_version(&self, id: Uuid, hmac: DocumentHmac) -> LbResult<()> {
+        let content = self.read_version(id, hmac).await?;
+        self.write_document(id, &content).await
+    }
+
+    /// resolves links and checks that the target is a live document
+    async fn version_target(&self, id: Uuid) -> LbResult<Uuid> {
+        let tx = self.ro_tx().await;
+        let db = tx.db();
+        let mut tree = (&db.base_metadata).to_staged(&db.local_metadata).to_lazy();
+        let id = match tree.find(&id)?.file_type() {
+            FileType::Document | FileType::Folder => id,
+            FileType::Link { target } => target,
+        };
+        validate::is_document(tree.find(&id)?)?;
+        if tree.calculate_deleted(&id)? {
+            return Err(LbErrKind::FileNonexistent.into());
+        }
+        Ok(id)
+    }
+}
@@ -1,3 +1,4 @@
 //! members of this module are *consumers* of the subscription stream of lb-rs
+pub mod search;
 pub mod status;
 pub mod syncer;
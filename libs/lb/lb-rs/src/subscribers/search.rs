@@ -0,0 +1,348 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::Mutex;
+use tokio::sync::broadcast::error::{RecvError, TryRecvError};
+use uuid::Uuid;
+
+use crate::LocalLb;
+use crate::model::crypto::{AESEncrypted, AESKey};
+use crate::model::errors::{LbResult, Unexpected};
+use crate::model::file_like::FileLike;
+use crate::model::file_metadata::DocumentHmac;
+use crate::model::tree_like::TreeLike;
+use crate::model::{pubkey, symkey};
+use crate::search::index::{self, INDEX_VERSION, SearchIndex};
+use crate::search::path::split_path;
+use crate::search::{FilterContext, SearchFilter, SearchHit, Snippet, query};
+use crate::service::events::{Event, SyncIncrement};
+use crate::service::links::Link;
+use crate::service::sync_policy::{SyncPolicy, effective_sync_policy};
+
+const INDEX_FILE: &str = "search_index";
+
+/// how much text to show on either side of a match
+const SNIPPET_CONTEXT_CHARS: usize = 60;
+
+/// Keeps the full-text [SearchIndex] in step with the documents in lb-rs. The index is loaded
+/// lazily, updated as documents are written and synced, and stored encrypted in the writeable
+/// path so it survives restarts.
+#[derive(Clone, Default)]
+pub struct SearchIndexer {
+    index: Arc<Mutex<Option<SearchIndex>>>,
+}
+
+impl LocalLb {
+    /// Full-text search over the contents and names of markdown documents, ranked best first.
+    /// Words match their plurals and -ed / -ing forms, "quoted phrases" match words next to one
+    /// another, and every word or phrase has to match for a document to be returned. The query
+    /// can also filter by fields, see [crate::search::query]; a query with only fields returns
+    /// the documents that match them, most recently modified first.
+    ///
+    /// Documents kept on demand, see [SyncPolicy], are found if they were indexed while they were
+    /// on this device.
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn search(
+        &self, query: &str, filter: Option<SearchFilter>, limit: usize,
+    ) -> LbResult<Vec<SearchHit>> {
+        let parsed = query::parse(query);
+        let clauses = index::parse_query(&parsed.text);
+        let filter = query::and(filter, parsed.filter);
+        if (clauses.is_empty() && filter.is_none()) || limit == 0 {
+            return Ok(vec![]);
+        }
+
+        self.update_search_index().await?;
+
+        let ranked = {
+            let index = self.search.index.lock().await;
+            let Some(index) = index.as_ref() else {
+                return Ok(vec![]);
+            };
+
+            let mut metas = vec![];
+            let mut scope = None;
+            if filter.is_some() {
+                metas = self.list_metadatas().await?;
+                let paths = self.list_paths_with_ids(None).await?;
+                let context = FilterContext::new(&metas, &paths, index.tags());
+                scope = context.resolve(filter.as_ref());
+            }
+
+            if clauses.is_empty() {
+                let scope = scope.unwrap_or_default();
+                metas.retain(|file| {
+                    scope.contains(&file.id) && index.indexed_hmac(&file.id).is_some()
+                });
+                metas.sort_by_key(|file| Reverse(file.last_modified));
+                metas.into_iter().map(|file| (file.id, 0.0)).collect()
+            } else {
+                index.search(&clauses, scope.as_ref())
+            }
+        };
+
+        let mut hits = vec![];
+        for (id, score) in ranked.into_iter().take(limit) {
+            let path = self.get_path_by_id(id).await?;
+            let (parent_path, filename) = split_path(&path);
+            let snippet = if clauses.is_empty() {
+                None
+            } else {
+                match self.read_document(id, false).await {
+                    Ok(content) => snippet(&String::from_utf8_lossy(&content), &clauses),
+                    Err(err) => {
+                        debug!(?id, ?err, "could not read document for snippet");
+                        None
+                    }
+                }
+            };
+            hits.push(SearchHit {
+                id,
+                filename: filename.to_string(),
+                parent_path: parent_path.to_string(),
+                score,
+                snippet,
+            });
+        }
+
+        Ok(hits)
+    }
+
+    /// The tags of markdown documents, see [crate::search::tags::extract_tags], for those that
+    /// have any.
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn document_tags(&self) -> LbResult<HashMap<Uuid, Vec<String>>> {
+        self.update_search_index().await?;
+        Ok(self
+            .search
+            .index
+            .lock()
+            .await
+            .as_ref()
+            .map(|index| index.tags())
+            .unwrap_or_default())
+    }
+
+    /// The links in markdown documents, see [crate::service::links], for those that have any.
+    pub(crate) async fn indexed_links(&self) -> LbResult<HashMap<Uuid, Vec<Link>>> {
+        self.update_search_index().await?;
+        Ok(self
+            .search
+            .index
+            .lock()
+            .await
+            .as_ref()
+            .map(|index| index.links())
+            .unwrap_or_default())
+    }
+
+    pub(crate) fn setup_search_index(&self) {
+        if self.config.background_work {
+            self.clone().search_index_worker();
+        }
+    }
+
+    /// Indexes documents as they change, a little after the fact so that a burst of writes is
+    /// indexed once. Searches bring the index up to date themselves, this only keeps them fast.
+    fn search_index_worker(self) {
+        #[cfg(not(target_family = "wasm"))]
+        tokio::spawn(async move {
+            let mut events = self.subscribe();
+
+            let index_criteria = |e: &Event| {
+                matches!(
+                    e,
+                    Event::MetadataChanged(_)
+                        | Event::DocumentWritten(_, _)
+                        | Event::UserSignedIn
+                        | Event::Sync(SyncIncrement::SyncFinished(_))
+                )
+            };
+
+            // bring the index up to date with whatever happened while we weren't running
+            self.update_search_index().await.log_and_ignore();
+
+            loop {
+                match events.recv().await {
+                    Ok(event) if index_criteria(&event) => {}
+                    Ok(_) => continue,
+                    // we missed some events, catch up on all of them
+                    Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => return,
+                }
+
+                // drain the channel, so we index once for a burst of keystrokes
+                tokio::time::sleep(Duration::from_secs(2)).await;
+                loop {
+                    match events.try_recv() {
+                        Ok(_) | Err(TryRecvError::Lagged(_)) => {}
+                        Err(TryRecvError::Empty) => break,
+                        Err(TryRecvError::Closed) => return,
+                    }
+                }
+
+                self.update_search_index().await.log_and_ignore();
+            }
+        });
+    }
+
+    /// Indexes documents whose contents changed since they were last indexed and drops
+    /// documents that were deleted, then saves the index if anything changed.
+    pub(crate) async fn update_search_index(&self) -> LbResult<()> {
+        let Ok(account) = self.get_account() else {
+            return Ok(());
+        };
+        let key = pubkey::get_aes_key(&account.private_key, &account.public_key())?;
+
+        let mut guard = self.search.index.lock().await;
+        if guard.is_none() {
+            *guard = Some(self.load_search_index(&key).await.unwrap_or_default());
+        }
+        let index = guard.as_mut().unwrap();
+
+        let current: HashMap<Uuid, (String, Option<DocumentHmac>, bool)> = {
+            let tx = self.ro_tx().await;
+            let db = tx.db();
+            let mut tree = (&db.base_metadata).to_staged(&db.local_metadata).to_lazy();
+
+            let mut current = HashMap::new();
+            for id in tree.ids() {
+                let file = tree.find(&id)?;
+                let is_document = file.is_document();
+                let hmac = file.document_hmac().copied();
+                if !is_document || tree.calculate_deleted(&id)? {
+                    continue;
+                }
+                let Ok(name) = tree.name(&id, &self.keychain) else {
+                    continue;
+                };
+                if name.ends_with(".md") {
+                    let on_demand = effective_sync_policy(&tree, db.sync_policies.get(), &id)?
+                        == Some(SyncPolicy::OnDemand);
+                    current.insert(id, (name, hmac, on_demand));
+                }
+            }
+            current
+        };
+
+        let mut changed = false;
+
+        let removed: Vec<Uuid> = index
+            .ids()
+            .filter(|id| !current.contains_key(id))
+            .copied()
+            .collect();
+        for id in removed {
+            changed |= index.remove(&id);
+        }
+
+        for (id, (name, hmac, on_demand)) in current {
+            if index.indexed_hmac(&id) == Some(hmac) {
+                changed |= index.rename(&id, &name);
+                continue;
+            }
+            // documents kept on demand aren't downloaded just to index them
+            if on_demand && hmac.is_some() && !self.docs.exists(id, hmac) {
+                continue;
+            }
+            match self.read_document_with_hmac(id, false).await {
+                Ok((read_hmac, content)) => {
+                    index.insert(id, read_hmac, &name, &String::from_utf8_lossy(&content));
+                    changed = true;
+                }
+                Err(err) => debug!(?id, ?err, "could not index document"),
+            }
+        }
+
+        if changed {
+            self.save_search_index(index, &key).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Forgets the index, along with its copy on disk.
+    pub(crate) async fn clear_search_index(&self) -> LbResult<()> {
+        *self.search.index.lock().await = None;
+        #[cfg(not(target_family = "wasm"))]
+        {
+            let path = std::path::Path::new(&self.config.writeable_path).join(INDEX_FILE);
+            if path.exists() {
+                tokio::fs::remove_file(path).await?;
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    async fn load_search_index(&self, key: &AESKey) -> Option<SearchIndex> {
+        let path = std::path::Path::new(&self.config.writeable_path).join(INDEX_FILE);
+        let bytes = tokio::fs::read(path).await.ok()?;
+        let encrypted: AESEncrypted<SearchIndex> = bincode::deserialize(&bytes).ok()?;
+        let index = symkey::decrypt(key, &encrypted).ok()?;
+        (index.version == INDEX_VERSION).then_some(index)
+    }
+
+    #[cfg(target_family = "wasm")]
+    async fn load_search_index(&self, _key: &AESKey) -> Option<SearchIndex> {
+        None
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    async fn save_search_index(&self, index: &SearchIndex, key: &AESKey) -> LbResult<()> {
+        let dir = std::path::Path::new(&self.config.writeable_path);
+        let encrypted = symkey::encrypt(key, index)?;
+        let bytes = bincode::serialize(&encrypted).map_unexpected()?;
+
+        // write then rename so a crash never leaves half an index behind
+        let pending = dir.join(format!("{INDEX_FILE}.pending"));
+        tokio::fs::write(&pending, bytes).await?;
+        tokio::fs::rename(&pending, dir.join(INDEX_FILE)).await?;
+        Ok(())
+    }
+
+    #[cfg(target_family = "wasm")]
+    async fn save_search_index(&self, _index: &SearchIndex, _key: &AESKey) -> LbResult<()> {
+        Ok(())
+    }
+}
+
+/// The text around the first match of `clauses` in `content`, snapped to word boundaries.
+fn snippet(content: &str, clauses: &[index::Clause]) -> Option<Snippet> {
+    let ranges = index::match_ranges(content, clauses);
+    let first = ranges.first()?.clone();
+
+    let mut start = content[..first.start]
+        .char_indices()
+        .rev()
+        .nth(SNIPPET_CONTEXT_CHARS)
+        .map(|(i, _)| i)
+        .unwrap_or(0);
+    if start > 0 {
+        if let Some(space) = content[start..first.start].find(char::is_whitespace) {
+            start += space + 1;
+        }
+    }
+
+    let mut end = content[first.end..]
+        .char_indices()
+        .nth(SNIPPET_CONTEXT_CHARS)
+        .map(|(i, _)| first.end + i)
+        .unwrap_or(content.len());
+    if end < content.len() {
+        if let Some(space) = content[first.end..end].rfind(char::is_whitespace) {
+            end = first.end + space;
+        }
+    }
+
+    Some(Snippet {
+        text: content[start..end].to_string(),
+        matches: ranges
+            .into_iter()
+            .filter(|range| range.start >= start && range.end <= end)
+            .map(|range| range.start - start..range.end - start)
+            .collect(),
+    })
+}
@@ -20,11 +20,15 @@ use crate::{
         access_info::UserAccessMode,
         account::Account,
         api::{
-            ChangeDocRequestV2, GetDocRequest, GetFileIdsRequest, GetUpdatesRequestV2,
-            GetUsernameError, GetUsernameRequest, UpsertDebugInfoRequest, UpsertRequestV2,
+            ChangeDocDeltaRequest, ChangeDocError, ChangeDocRequestV2, ContentFormat,
+            DeclareChunksRequest, GetDocRequestV2, GetFileIdsRequest, GetUpdatesRequestV2,
+            GetUsernameError, GetUsernameRequest, UpsertChunkRequest, UpsertDebugInfoRequest,
+            UpsertRequestV2, WaitForUpdatesRequest, WaitForUpdatesResponse,
         },
         chat,
+        chunks::ChunkManifest,
         crypto::{DecryptedDocument, EncryptedDocument},
+        delta::{DocumentDelta, MIN_DELTA_DOCUMENT_SIZE},
         errors::{LbErr, Unexpected},
         file::ShareMode,
         file_like::FileLike,
@@ -38,7 +42,11 @@ use crate::{
         tree_like::TreeLike,
         validate,
     },
-    service::events::{Actor, Event, SyncIncrement},
+    service::{
+        documents::{compress_encrypt_document, decrypt_decompress_document},
+        events::{Actor, Event, SyncIncrement},
+        sync_policy::{SyncPolicy, effective_sync_policy},
+    },
 };
 
 pub type Syncer = Arc<Mutex<SyncState>>;
@@ -59,6 +67,9 @@ pub struct SyncState {
 
     /// what docs did we pull as a result of this sync
     pulled_docs: Vec<Uuid>,
+
+    /// what docs did we push as a result of this sync
+    pushed_docs: Vec<Uuid>,
 }
 
 // we are gonna have a fetch metadata fn which will get the docs that it needs to get, the ones
@@ -75,7 +86,7 @@ impl LocalLb {
 
         let pipeline: LbResult<()> = async {
             self.pull_updates(&mut sync_state).await?;
-            self.push_local_changes().await?;
+            self.push_local_changes(&mut sync_state).await?;
             Ok(())
         }
         .await;
@@ -86,6 +97,11 @@ impl LocalLb {
 
         self.cleanup().await?;
 
+        // pulled documents grow the cache, and pushed ones are no longer pinned by local edits
+        if !sync_state.pulled_docs.is_empty() || !sync_state.pushed_docs.is_empty() {
+            self.evict_on_demand_docs().await?;
+        }
+
         pipeline?;
 
         let account = self.get_account()?.clone();
@@ -100,6 +116,8 @@ impl LocalLb {
 
     pub(crate) async fn pull_updates(&self, sync_state: &mut SyncState) -> LbResult<()> {
         self.inital_sync_state(sync_state).await?;
+        // group membership decides which shared files are sent to us and which keys we hold
+        self.fetch_groups().await?;
         self.process_deletions().await?;
         self.fetch_meta(sync_state).await?;
         self.fetch_required_docs(sync_state).await?;
@@ -115,9 +133,9 @@ impl LocalLb {
         Ok(())
     }
 
-    pub(crate) async fn push_local_changes(&self) -> LbResult<()> {
+    pub(crate) async fn push_local_changes(&self, state: &mut SyncState) -> LbResult<()> {
         self.push_meta().await?;
-        self.push_docs().await?;
+        self.push_docs(state).await?;
 
         Ok(())
     }
@@ -193,6 +211,7 @@ impl LocalLb {
         // this loop implicitly prunes remote orphans
         let mut without_orphans = Vec::new();
         let me = Owner(self.keychain.get_pk()?);
+        let groups = self.keychain.groups()?;
         let remote = db.base_metadata.stage(updates.file_metadata).to_lazy();
         for id in remote.tree.staged.ids() {
             let meta = remote.find(&id)?;
@@ -200,7 +219,7 @@ impl LocalLb {
                 || meta
                     .user_access_keys()
                     .iter()
-                    .any(|k| k.encrypted_for == me.0)
+                    .any(|k| k.encrypted_for == me.0 || groups.contains(&Owner(k.encrypted_for)))
             {
                 without_orphans.push(remote.find(&id)?.clone());
             }
@@ -229,18 +248,18 @@ impl LocalLb {
     }
 
     async fn fetch_required_docs(&self, state: &mut SyncState) -> LbResult<()> {
-        let mut docs_to_pull = vec![];
+        let mut docs_to_pull = HashSet::new();
 
         let tx = self.ro_tx().await;
         let db = tx.db();
 
-        let mut files_with_local_edits = vec![];
+        let mut files_with_local_edits = HashSet::new();
         let local = db.base_metadata.stage(&db.local_metadata);
         for id in local.staged.ids() {
             if let Some(base) = local.base.maybe_find(&id) {
                 if let Some(local_hmac) = local.find(&id)?.document_hmac() {
                     if Some(local_hmac) != base.document_hmac() {
-                        files_with_local_edits.push(id);
+                        files_with_local_edits.insert(id);
                         println!("local edits found");
                     }
                 }
@@ -251,11 +270,13 @@ impl LocalLb {
             .base_metadata
             .stage(state.remote_changes.clone())
             .to_lazy();
+        let policies = db.sync_policies.get();
 
         for id in remote.tree.staged.ids() {
             if remote.calculate_deleted(&id)? {
                 continue;
             }
+            let policy = effective_sync_policy(&remote, policies, &id)?;
             let remote_hmac = remote.find(&id)?.document_hmac().cloned();
             let base_hmac = remote
                 .tree
@@ -271,25 +292,48 @@ impl LocalLb {
                 // pull a file if we have a prior base, this is our heuristic -- do they have the
                 // ability to edit this file while we release the lock and are pulling all the
                 // files
-                if self.docs.exists(id, base_hmac) && !self.docs.exists(id, Some(remote_hmac)) {
-                    docs_to_pull.push((id, remote_hmac));
+                if policy != Some(SyncPolicy::OnDemand)
+                    && self.docs.exists(id, base_hmac)
+                    && !self.docs.exists(id, Some(remote_hmac))
+                {
+                    docs_to_pull.insert((id, remote_hmac));
                 }
 
                 // this clause captures documents which went from being new -> multiple parties
                 // having updates. We'll still need the updates
                 if files_with_local_edits.contains(&id)
-                    && !docs_to_pull
-                        .iter()
-                        .any(|(already_pulling, _)| already_pulling == &id)
+                    && !docs_to_pull.contains(&(id, remote_hmac))
                 {
                     if let Some(base_hmac) = base_hmac {
                         if !self.docs.exists(id, Some(base_hmac)) {
                             // this scenario basically only comes up in tests
                             // someone modifies a file directly without reading the prior version
-                            docs_to_pull.push((id, base_hmac));
+                            docs_to_pull.insert((id, base_hmac));
                         }
                     }
-                    docs_to_pull.push((id, remote_hmac));
+                    docs_to_pull.insert((id, remote_hmac));
+                }
+            }
+        }
+
+        // always-local documents are pulled even if we never had them, or they were evicted
+        if policies
+            .values()
+            .any(|policy| *policy == SyncPolicy::AlwaysLocal)
+        {
+            let pulling: HashSet<Uuid> = docs_to_pull.iter().map(|(id, _)| *id).collect();
+            for id in remote.ids() {
+                if pulling.contains(&id) {
+                    continue;
+                }
+                let Some(remote_hmac) = remote.find(&id)?.document_hmac().cloned() else {
+                    continue;
+                };
+                if self.docs.exists(id, Some(remote_hmac)) || remote.calculate_deleted(&id)? {
+                    continue;
+                }
+                if effective_sync_policy(&remote, policies, &id)? == Some(SyncPolicy::AlwaysLocal) {
+                    docs_to_pull.insert((id, remote_hmac));
                 }
             }
         }
@@ -328,11 +372,17 @@ impl LocalLb {
             .sync_update(SyncIncrement::PullingDocument(id, true));
         let remote_document = self
             .client
-            .request(self.get_account()?, GetDocRequest { id, hmac })
+            .request(self.get_account()?, GetDocRequestV2 { id, hmac })
             .await?;
         self.docs
             .insert(id, Some(hmac), &remote_document.content)
             .await?;
+        if remote_document.format != ContentFormat::Document {
+            let mut tx = self.begin_tx().await;
+            tx.db()
+                .set_content_format(id, hmac, remote_document.format)?;
+            tx.end();
+        }
         self.events
             .sync_update(SyncIncrement::PullingDocument(id, false));
 
@@ -359,6 +409,13 @@ impl LocalLb {
 
         // fetch document updates and local documents for merge
         let me = Owner(self.keychain.get_pk()?);
+        let groups = self.keychain.groups()?;
+
+        // chunked documents duplicated due to conflicts, whose chunks need uploading
+        let mut duplicate_chunk_uploads: HashMap<Uuid, (DocumentHmac, Vec<DocumentHmac>)> =
+            HashMap::new();
+        // versions written during the merge whose contents are chunk manifests
+        let mut merged_manifests: Vec<(Uuid, DocumentHmac)> = vec![];
 
         // compute merge changes
         let merge_changes = {
@@ -375,6 +432,17 @@ impl LocalLb {
             let mut rename_increments: HashMap<Uuid, usize> = HashMap::new();
             let mut duplicate_file_ids: HashMap<Uuid, Uuid> = HashMap::new();
 
+            // files deleted in base that were restored locally
+            let mut restorations: HashSet<Uuid> = HashSet::new();
+            for id in db.local_metadata.ids() {
+                if base.maybe_find(&id).is_some()
+                    && base.calculate_deleted(&id)?
+                    && !local.calculate_deleted(&id)?
+                {
+                    restorations.insert(id);
+                }
+            }
+
             'merge_construction: loop {
                 // process just the edits which allow us to check deletions in the result
                 let mut deletions = {
@@ -421,6 +489,13 @@ impl LocalLb {
                         .into());
                     }
 
+                    // restorations
+                    for &id in &restorations {
+                        if deletions.find(&id)?.explicitly_deleted() {
+                            deletions.undelete_unvalidated(&id, &self.keychain)?;
+                        }
+                    }
+
                     // moves (creations happen first in case a file is moved into a new folder)
                     for id in db.local_metadata.ids() {
                         let local_file = local.find(&id)?.clone();
@@ -499,14 +574,53 @@ impl LocalLb {
                         .into());
                     }
 
+                    // restorations
+                    for &id in &restorations {
+                        if merge.find(&id)?.explicitly_deleted() {
+                            merge.undelete_unvalidated(&id, &self.keychain)?;
+                        }
+                    }
+
+                    // key rotations, before anything is encrypted with the rotated keys; parents
+                    // first, since each file's key is encrypted with its parent's
+                    let mut rotations = vec![];
+                    for id in db.local_metadata.ids() {
+                        if base.maybe_find(&id).is_none()
+                            || merge.maybe_find(&id).is_none()
+                            || merge.calculate_deleted(&id)?
+                        {
+                            continue;
+                        }
+                        let base_key = base.decrypt_key(&id, &self.keychain)?;
+                        let local_key = local.decrypt_key(&id, &self.keychain)?;
+                        if local_key != base_key
+                            && remote.decrypt_key(&id, &self.keychain)? == base_key
+                        {
+                            rotations.push((local.ancestors(&id)?.len(), id, local_key));
+                        }
+                    }
+                    rotations.sort_by_key(|(depth, _, _)| *depth);
+                    for (_, id, key) in rotations {
+                        let revoked = local
+                            .find(&id)?
+                            .user_access_keys()
+                            .iter()
+                            .filter(|access| access.deleted)
+                            .map(|access| access.encrypted_for)
+                            .collect::<Vec<_>>();
+                        merge.rekey_unvalidated(&id, key, &revoked, &self.keychain)?;
+                    }
+
                     // moves, renames, edits, and shares
-                    // creations happen first in case a file is moved into a new folder
+                    // creations and restorations happen first in case a file is moved into a new
+                    // or restored folder
                     for id in db.local_metadata.ids() {
                         // skip files that are already deleted or will be deleted
                         if deletions.maybe_find(&id).is_none()
                             || deletions.calculate_deleted(&id)?
                             || (remote.maybe_find(&id).is_some()
-                                && remote.calculate_deleted(&id)?)
+                                && remote.calculate_deleted(&id)?
+                                && !restorations.contains(&id))
                         {
                             continue;
                         }
@@ -595,23 +709,36 @@ impl LocalLb {
                         let remote_hmac =
                             maybe_remote_file.and_then(|f| f.document_hmac().cloned());
                         let local_hmac = local_file.document_hmac().cloned();
-                        if merge.access_mode(me, &id)? >= Some(UserAccessMode::Write)
+                        if merge.access_mode_in_groups(me, &groups, &id)?
+                            >= Some(UserAccessMode::Write)
                             && local_hmac != base_hmac
                         {
                             if remote_hmac != base_hmac && remote_hmac != local_hmac {
                                 // merge
                                 let merge_name = merge.name(&id, &self.keychain)?;
-                                let document_type =
+                                let formats = db.content_formats_of(id);
+                                let mut document_type =
                                     DocumentType::from_file_name_using_extension(&merge_name);
 
                                 // todo these accesses are potentially problematic
                                 // maybe not if service/docs is the persion doing network io
                                 let base_document =
-                                    self.read_document_helper(id, &mut base).await?;
+                                    self.read_document_helper(id, &mut base, &formats).await?;
                                 let remote_document =
-                                    self.read_document_helper(id, &mut remote).await?;
+                                    self.read_document_helper(id, &mut remote, &formats).await?;
                                 let local_document =
-                                    self.read_document_helper(id, &mut local).await?;
+                                    self.read_document_helper(id, &mut local, &formats).await?;
+
+                                // chunked documents aren't merged, whatever their extension
+                                if [base_hmac, remote_hmac, local_hmac]
+                                    .into_iter()
+                                    .flatten()
+                                    .any(|hmac| {
+                                        db.content_format(id, hmac) == ContentFormat::Manifest
+                                    })
+                                {
+                                    document_type = DocumentType::Other;
+                                }
 
                                 match document_type {
                                     DocumentType::Text => {
@@ -742,6 +869,63 @@ impl LocalLb {
                                                 &encrypted_document,
                                             )
                                             .await?;
+
+                                        // the duplicate shares the manifest's key, so its chunks
+                                        // are copied as they are
+                                        let chunked = local_hmac.is_some_and(|hmac| {
+                                            db.content_format(id, hmac) == ContentFormat::Manifest
+                                        });
+                                        if let (true, Some(duplicate_hmac)) =
+                                            (chunked, duplicate_hmac)
+                                        {
+                                            let manifest =
+                                                ChunkManifest::from_bytes(&local_document)?;
+                                            for chunk in &manifest.chunks {
+                                                self.docs
+                                                    .copy_chunk(id, duplicate_id, chunk.hmac)
+                                                    .await?;
+                                            }
+                                            let chunks = manifest
+                                                .chunks
+                                                .iter()
+                                                .map(|chunk| chunk.hmac)
+                                                .collect();
+                                            duplicate_chunk_uploads
+                                                .insert(duplicate_id, (duplicate_hmac, chunks));
+                                            merged_manifests.push((duplicate_id, duplicate_hmac));
+                                        }
+                                    }
+                                }
+                            } else if merge.decrypt_key(&id, &self.keychain)?
+                                != local.decrypt_key(&id, &self.keychain)?
+                            {
+                                // the document's key was rotated remotely, so the local edit is
+                                // encrypted with a key that's no longer in use
+                                let formats = db.content_formats_of(id);
+                                let local_document =
+                                    self.read_document_helper(id, &mut local, &formats).await?;
+                                let encrypted_document = merge.update_document_unvalidated(
+                                    &id,
+                                    &local_document,
+                                    &self.keychain,
+                                )?;
+                                let hmac = merge.find(&id)?.document_hmac().copied();
+                                self.docs.insert(id, hmac, &encrypted_document).await?;
+
+                                // a manifest is still a manifest under the new key, and its
+                                // chunks, which have a key of their own, still need uploading
+                                if let (Some(local_hmac), Some(hmac)) = (local_hmac, hmac) {
+                                    if db.content_format(id, local_hmac) == ContentFormat::Manifest
+                                    {
+                                        merged_manifests.push((id, hmac));
+                                    }
+                                    if let Some((upload_hmac, chunks)) =
+                                        db.chunk_uploads.get().get(&id)
+                                    {
+                                        if *upload_hmac == local_hmac {
+                                            duplicate_chunk_uploads
+                                                .insert(id, (hmac, chunks.clone()));
+                                        }
                                     }
                                 }
                             } else {
@@ -798,7 +982,7 @@ impl LocalLb {
                     }
                 }
 
-                let validate_result = merge.validate(me);
+                let validate_result = merge.validate_in_groups(me, &groups);
                 match validate_result {
                     // merge changeset is valid
                     Ok(_) => {
@@ -952,6 +1136,13 @@ impl LocalLb {
         // self.cleanup_local_metadata()?;
         db.base_metadata.stage(&mut db.local_metadata).prune()?;
 
+        for (id, upload) in duplicate_chunk_uploads {
+            db.chunk_uploads.insert(id, upload)?;
+        }
+        for (id, hmac) in merged_manifests {
+            db.set_content_format(id, hmac, ContentFormat::Manifest)?;
+        }
+
         if start.elapsed() > web_time::Duration::from_millis(100) {
             warn!("sync merge held lock for {:?}", start.elapsed());
         }
@@ -1100,9 +1291,8 @@ impl LocalLb {
     // todo: make this so that all document updates are attempted and we don't just return the
     // first error. Once an attempt is made we can return any or all errors, either would be an
     // improvement
-    async fn push_docs(&self) -> LbResult<()> {
+    async fn push_docs(&self, state: &mut SyncState) -> LbResult<()> {
         let mut updates = vec![];
-        let mut local_changes_digests_only = vec![];
 
         let tx = self.ro_tx().await;
         let db = tx.db();
@@ -1128,8 +1318,7 @@ impl LocalLb {
 
             let local_change = local_change.sign(&self.keychain)?;
 
-            updates.push(FileDiff { old: Some(base_file), new: local_change.clone() });
-            local_changes_digests_only.push(local_change);
+            updates.push(FileDiff { old: Some(base_file), new: local_change });
             self.events
                 .sync_update(SyncIncrement::PushingDocument(id, true));
         }
@@ -1139,7 +1328,7 @@ impl LocalLb {
             warn!("sync push_docs held lock for {:?}", start.elapsed());
         }
 
-        let futures = updates.clone().into_iter().map(|diff| self.push_doc(diff));
+        let futures = updates.into_iter().map(|diff| self.push_doc(diff));
 
         let mut stream = stream::iter(futures).buffer_unordered(
             thread::available_parallelism()
@@ -1147,15 +1336,15 @@ impl LocalLb {
                 .into(),
         );
 
-        let mut docs_without_errors = vec![];
+        let mut local_changes_digests_only = vec![];
         let mut last_error: Option<LbErr> = None;
 
         while let Some(fut) = stream.next().await {
             match fut {
-                Ok(id) => {
-                    docs_without_errors.push(id);
+                Ok(pushed) => {
                     self.events
-                        .sync_update(SyncIncrement::PushingDocument(id, false));
+                        .sync_update(SyncIncrement::PushingDocument(*pushed.id(), false));
+                    local_changes_digests_only.push(pushed);
                 }
                 Err(err) => {
                     last_error = Some(err);
@@ -1163,10 +1352,35 @@ impl LocalLb {
             }
         }
 
-        local_changes_digests_only.retain(|f| docs_without_errors.contains(f.id()));
+        state.pushed_docs = local_changes_digests_only
+            .iter()
+            .map(|change| *change.id())
+            .collect();
 
         let mut tx = self.begin_tx().await;
         let db = tx.db();
+        for change in &local_changes_digests_only {
+            let pushed = db
+                .chunk_uploads
+                .get()
+                .get(change.id())
+                .is_some_and(|(hmac, _)| Some(hmac) == change.document_hmac());
+            if pushed {
+                db.chunk_uploads.remove(change.id())?;
+            }
+
+            // local still declares the whole document's size if a delta was pushed
+            let local = db.local_metadata.get().get(change.id()).cloned();
+            if let Some(local) = local.filter(|local| {
+                local.document_hmac() == change.document_hmac()
+                    && local.doc_size() != change.doc_size()
+            }) {
+                let mut local = local.timestamped_value.value;
+                local.set_hmac_and_size(change.document_hmac().copied(), change.doc_size());
+                db.local_metadata
+                    .insert(*change.id(), local.sign(&self.keychain)?)?;
+            }
+        }
         // base = local (metadata)
         (&mut db.base_metadata)
             .to_lazy()
@@ -1180,18 +1394,147 @@ impl LocalLb {
         if let Some(err) = last_error { Err(err) } else { Ok(()) }
     }
 
-    async fn push_doc(&self, diff: FileDiff<SignedMeta>) -> LbResult<Uuid> {
+    /// returns the metadata that was pushed, which declares the delta's size if a delta was
+    /// uploaded instead of the document
+    async fn push_doc(&self, diff: FileDiff<SignedMeta>) -> LbResult<SignedMeta> {
         let id = *diff.new.id();
         let hmac = diff.new.document_hmac();
+
+        // chunks uploaded for a manifest we've since replaced aren't needed
+        let chunk_upload = {
+            let tx = self.ro_tx().await;
+            tx.db()
+                .chunk_uploads
+                .get()
+                .get(&id)
+                .filter(|(manifest_hmac, _)| Some(manifest_hmac) == hmac)
+                .cloned()
+        };
+        if let Some((manifest_hmac, chunks)) = chunk_upload {
+            self.push_chunks(id, manifest_hmac, chunks).await?;
+        }
+
         let local_document_change = self.docs.get(id, hmac.copied()).await?;
+
+        if let Some(delta) = self.delta_upload(&diff, &local_document_change).await? {
+            // the server charges for what it stores, which is the delta
+            let mut delta_meta = diff.new.timestamped_value.value.clone();
+            delta_meta.set_hmac_and_size(hmac.copied(), Some(delta.value.len()));
+            let delta_diff =
+                FileDiff { old: diff.old.clone(), new: delta_meta.sign(&self.keychain)? };
+
+            let result = self
+                .client
+                .request(
+                    self.get_account()?,
+                    ChangeDocDeltaRequest { diff: delta_diff.clone(), delta },
+                )
+                .await;
+            match result {
+                Ok(()) => return Ok(delta_diff.new),
+                // the server can't accept a delta against this base; upload the whole document
+                Err(ApiError::Endpoint(
+                    ChangeDocError::BaseNotFound | ChangeDocError::DeltaChainTooLong,
+                )) => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+
         self.client
             .request(
                 self.get_account()?,
-                ChangeDocRequestV2 { diff, new_content: local_document_change },
+                ChangeDocRequestV2 { diff: diff.clone(), new_content: local_document_change },
             )
             .await?;
 
-        Ok(id)
+        Ok(diff.new)
+    }
+
+    /// An encrypted delta to upload instead of `new_content`, for large text documents whose
+    /// base we still have when the delta is meaningfully smaller than the document.
+    async fn delta_upload(
+        &self, diff: &FileDiff<SignedMeta>, new_content: &EncryptedDocument,
+    ) -> LbResult<Option<EncryptedDocument>> {
+        let id = *diff.new.id();
+        if new_content.value.len() < MIN_DELTA_DOCUMENT_SIZE {
+            return Ok(None);
+        }
+        let Some(base_hmac) = diff
+            .old
+            .as_ref()
+            .and_then(|old| old.document_hmac())
+            .copied()
+        else {
+            return Ok(None);
+        };
+        let Some(base) = self.docs.maybe_get(id, Some(base_hmac)).await? else {
+            return Ok(None);
+        };
+
+        let (key, formats) = {
+            let tx = self.ro_tx().await;
+            let db = tx.db();
+            let mut tree = (&db.base_metadata).to_staged(&db.local_metadata).to_lazy();
+            let name = tree.name(&id, &self.keychain)?;
+            if DocumentType::from_file_name_using_extension(&name) != DocumentType::Text {
+                return Ok(None);
+            }
+            // chunked documents are uploaded as manifests, which aren't diffed
+            let versions = [Some(base_hmac), diff.new.document_hmac().copied()];
+            if versions
+                .into_iter()
+                .flatten()
+                .any(|hmac| db.content_format(id, hmac) == ContentFormat::Manifest)
+            {
+                return Ok(None);
+            }
+            (tree.decrypt_key(&id, &self.keychain)?, db.content_formats_of(id))
+        };
+
+        // the base may be encrypted with a key that's since been rotated
+        let Ok(base) = decrypt_decompress_document(&key, &base) else {
+            return Ok(None);
+        };
+        let base_format = formats.get(&base_hmac).copied().unwrap_or_default();
+        let base = self
+            .apply_deltas(id, &key, base, base_format, &formats)
+            .await?;
+        let new = decrypt_decompress_document(&key, new_content)?;
+        let (Ok(base), Ok(new)) = (String::from_utf8(base), String::from_utf8(new)) else {
+            return Ok(None);
+        };
+
+        let delta = DocumentDelta::new(base_hmac, &base, &new).to_bytes()?;
+        let (_, delta) = compress_encrypt_document(&key, &delta)?;
+        if delta.value.len() * 2 > new_content.value.len() {
+            return Ok(None);
+        }
+        Ok(Some(delta))
+    }
+
+    /// uploads the chunks of a chunked document that the server doesn't have yet, which must
+    /// happen before the manifest referring to them is pushed
+    async fn push_chunks(
+        &self, id: Uuid, manifest_hmac: DocumentHmac, chunks: Vec<DocumentHmac>,
+    ) -> LbResult<()> {
+        let missing = self
+            .client
+            .request(self.get_account()?, DeclareChunksRequest { id, hmac: manifest_hmac, chunks })
+            .await?
+            .missing;
+
+        for hmac in missing {
+            let content = self
+                .docs
+                .maybe_get_chunk(id, hmac)
+                .await?
+                .ok_or_else(|| LbErrKind::Unexpected(format!("chunk missing locally: {id}")))?;
+            self.client
+                .request(self.get_account()?, UpsertChunkRequest { id, hmac, content })
+                .await?;
+        }
+
+        Ok(())
     }
 
     #[cfg(not(target_family = "wasm"))]
@@ -1252,7 +1595,7 @@ impl LocalLb {
     }
 
     async fn read_document_helper<T>(
-        &self, id: Uuid, tree: &mut LazyTree<T>,
+        &self, id: Uuid, tree: &mut LazyTree<T>, formats: &HashMap<DocumentHmac, ContentFormat>,
     ) -> LbResult<DecryptedDocument>
     where
         T: TreeLike<F = SignedMeta>,
@@ -1268,7 +1611,10 @@ impl LocalLb {
         let doc = match hmac {
             Some(hmac) => {
                 let doc = self.docs.get(id, Some(hmac)).await?;
-                tree.decrypt_document(&id, &doc, &self.keychain)?
+                let key = tree.decrypt_key(&id, &self.keychain)?;
+                let doc = decrypt_decompress_document(&key, &doc)?;
+                let format = formats.get(&hmac).copied().unwrap_or_default();
+                self.apply_deltas(id, &key, doc, format, formats).await?
             }
             None => vec![],
         };
@@ -1293,6 +1639,7 @@ impl LocalLb {
         if self.config.background_work {
             self.clone().local_change_worker();
             self.clone().periodic_sync_worker();
+            self.clone().notified_sync_worker();
             self.clone().post_sync_worker();
         }
     }
@@ -1363,6 +1710,53 @@ impl LocalLb {
         });
     }
 
+    /// Syncs as soon as the server says something we can see has changed, so that people editing
+    /// the same folder see each other's changes without waiting for the next periodic sync.
+    fn notified_sync_worker(self) {
+        #[cfg(not(target_family = "wasm"))]
+        tokio::spawn(async move {
+            let mut as_of = 0;
+            loop {
+                match self.wait_for_updates(as_of).await {
+                    Ok(response) if response.updated => {
+                        if self
+                            .sync()
+                            .await
+                            .map_unexpected()
+                            .log_and_ignore()
+                            .is_none()
+                        {
+                            // otherwise a sync that keeps failing is retried as fast as the
+                            // server can say there's something new
+                            tokio::time::sleep(Duration::from_secs(30)).await;
+                        }
+                    }
+                    Ok(response) => as_of = response.as_of,
+                    // signed out, offline, or a server that doesn't notify; periodic syncs
+                    // carry on regardless
+                    Err(err) => {
+                        debug!("waiting for updates failed: {err:?}");
+                        tokio::time::sleep(Duration::from_secs(30)).await;
+                    }
+                }
+            }
+        });
+    }
+
+    /// `as_of` is where the last wait that timed out left off, which can be past what we last
+    /// pulled if we pushed changes since
+    async fn wait_for_updates(&self, as_of: u64) -> LbResult<WaitForUpdatesResponse> {
+        let last_synced = {
+            let tx = self.ro_tx().await;
+            tx.db().last_synced.get().copied().unwrap_or_default() as u64
+        };
+        let since_metadata_version = last_synced.max(as_of);
+        Ok(self
+            .client
+            .request(self.get_account()?, WaitForUpdatesRequest { since_metadata_version })
+            .await?)
+    }
+
     async fn user_active(&self) -> bool {
         let last_seen = self.user_last_seen.read().await;
         last_seen.elapsed() < Duration::from_secs(15)
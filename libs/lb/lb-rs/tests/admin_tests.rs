@@ -1,4 +1,5 @@
 use lb_rs::model::api::ServerIndex;
+use lb_rs::model::errors::LbErrKind;
 use lb_rs::model::file::ShareMode;
 use test_utils::*;
 
@@ -295,3 +296,76 @@ async fn admin_rebuild_shared_files_index_test() {
     let cust2_new_device = test_core_from(&customer2).await;
     cust2_new_device.test_repo_integrity(true).await.unwrap();
 }
+
+#[tokio::test]
+async fn migrate_documents_requires_admin() {
+    let customer = test_core_with_account().await;
+    let result = customer.migrate_documents().await;
+    assert!(matches!(result.unwrap_err().kind, LbErrKind::InsufficientPermission));
+}
+
+/// run against a server with FILES_BACKEND=s3, pointed at a MinIO started from local.env
+#[tokio::test]
+#[ignore]
+async fn admin_migrate_documents_test() {
+    let admin_core = test_core().await;
+    admin_core
+        .create_account("admin1", &url(), false)
+        .await
+        .unwrap();
+
+    let customer = test_core_with_account().await;
+    let doc = customer.create_at_path("test.md").await.unwrap();
+    customer.write_document(doc.id, b"migrated").await.unwrap();
+    customer.sync().await.unwrap();
+
+    // whatever the first run copies, the second finds already there
+    let migration = admin_core.migrate_documents().await.unwrap();
+    let again = admin_core.migrate_documents().await.unwrap();
+    assert_eq!(again.copied, 0);
+    assert_eq!(again.already_present, migration.copied + migration.already_present);
+
+    let new_device = test_core_from(&customer).await;
+    assert_eq!(new_device.read_document(doc.id, false).await.unwrap(), b"migrated");
+}
+
+#[tokio::test]
+async fn create_invite_requires_admin() {
+    let customer = test_core_with_account().await;
+    let result = customer.create_invite(None, None).await;
+    assert!(matches!(result.unwrap_err().kind, LbErrKind::InsufficientPermission));
+}
+
+#[tokio::test]
+#[ignore]
+async fn admin_invite_test() {
+    let admin_core = test_core().await;
+    admin_core
+        .create_account("admin1", &url(), false)
+        .await
+        .unwrap();
+
+    let invite = admin_core.create_invite(None, Some(1)).await.unwrap();
+    assert!(admin_core.list_invites().await.unwrap().contains(&invite));
+
+    let invitee = test_core().await;
+    invitee
+        .create_account_with_invite(&random_name(), &url(), Some(&invite.code), false)
+        .await
+        .unwrap();
+
+    let invites = admin_core.list_invites().await.unwrap();
+    let used = invites.iter().find(|i| i.code == invite.code).unwrap();
+    assert_eq!(used.uses, 1);
+
+    admin_core.revoke_invite(&invite.code).await.unwrap();
+    assert!(
+        !admin_core
+            .list_invites()
+            .await
+            .unwrap()
+            .iter()
+            .any(|i| i.code == invite.code)
+    );
+    assert!(admin_core.revoke_invite(&invite.code).await.is_err());
+}
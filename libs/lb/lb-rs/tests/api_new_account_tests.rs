@@ -24,6 +24,19 @@ async fn new_account() {
     test_account(&random_account().await).await.unwrap();
 }
 
+#[tokio::test]
+async fn new_account_with_unneeded_invite() {
+    let account = random_account().await;
+    let root = Meta::create_root(&account)
+        .unwrap()
+        .sign_with(&account)
+        .unwrap();
+    Network::default()
+        .request(&account, NewAccountRequestV3::new(&account, &root, Some("unneeded")))
+        .await
+        .unwrap();
+}
+
 #[tokio::test]
 async fn new_account_duplicate_pk() {
     let first = random_account().await;
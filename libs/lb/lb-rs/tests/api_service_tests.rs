@@ -7,6 +7,7 @@ use lb_rs::model::clock::{Timestamp, get_time};
 use lb_rs::model::core_config::ClientType;
 use test_utils::local;
 use test_utils::{assert_matches, test_core_with_account};
+use uuid::Uuid;
 
 static CODE_VERSION: fn() -> &'static str = || "0.0.0";
 
@@ -20,6 +21,7 @@ async fn forced_upgrade() {
         get_code_version: CODE_VERSION,
         get_time,
         client_type: ClientType::Unknown,
+        session: Uuid::new_v4(),
     };
 
     let result: Result<PublicKey, ApiError<GetPublicKeyError>> = client
@@ -42,6 +44,7 @@ async fn expired_request() {
         get_code_version,
         get_time: EARLY_CLOCK,
         client_type: ClientType::Unknown,
+        session: Uuid::new_v4(),
     };
 
     let result = client
@@ -77,3 +80,20 @@ async fn wrong_url() {
 }
 
 // todo: test for invalid signature, signature mismatch during create account request
+
+/// run against a server with FEATURE_RATE_LIMIT=true and RATE_LIMIT_PER_ACCOUNT=60
+#[tokio::test]
+#[ignore]
+async fn rate_limited_requests_are_retried() {
+    let core = test_core_with_account().await;
+    let account = core.get_account().unwrap();
+
+    // the bucket holds a minute's worth; past that the client waits for tokens and retries
+    for _ in 0..65 {
+        local(&core)
+            .client
+            .request(&account, GetPublicKeyRequest { username: account.username.clone() })
+            .await
+            .unwrap();
+    }
+}
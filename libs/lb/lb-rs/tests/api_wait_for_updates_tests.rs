@@ -0,0 +1,90 @@
+use std::time::Duration;
+
+use lb_rs::model::api::WaitForUpdatesRequest;
+use lb_rs::model::clock::get_time;
+use lb_rs::model::file::ShareMode;
+use test_utils::{local, test_core_from, test_core_with_account};
+
+#[tokio::test]
+async fn returns_at_once_when_already_updated() {
+    let core = test_core_with_account().await;
+    let account = core.get_account().unwrap();
+
+    let response = local(&core)
+        .client
+        .request(&account, WaitForUpdatesRequest { since_metadata_version: 0 })
+        .await
+        .unwrap();
+    assert!(response.updated);
+}
+
+#[tokio::test]
+async fn returns_when_a_sharee_changes_a_shared_folder() {
+    let cores = [test_core_with_account().await, test_core_with_account().await];
+    let accounts = cores
+        .iter()
+        .map(|core| core.get_account().unwrap())
+        .collect::<Vec<_>>();
+
+    let folder = cores[0].create_at_path("/folder/").await.unwrap();
+    cores[0]
+        .share_file(folder.id, &accounts[1].username, ShareMode::Write)
+        .await
+        .unwrap();
+    cores[0].sync().await.unwrap();
+    cores[1].sync().await.unwrap();
+    cores[1]
+        .create_link_at_path("/folder", folder.id)
+        .await
+        .unwrap();
+    cores[1].sync().await.unwrap();
+
+    let since_metadata_version = get_time().0 as u64;
+    let (client, account) = (local(&cores[0]).client.clone(), accounts[0].clone());
+    let waiting = tokio::spawn(async move {
+        client
+            .request(&account, WaitForUpdatesRequest { since_metadata_version })
+            .await
+            .unwrap()
+    });
+
+    cores[1].create_at_path("/folder/document").await.unwrap();
+    cores[1].sync().await.unwrap();
+
+    let response = tokio::time::timeout(Duration::from_secs(5), waiting)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(response.updated);
+}
+
+#[tokio::test]
+async fn not_woken_by_its_own_changes() {
+    let core = test_core_with_account().await;
+    let account = core.get_account().unwrap();
+    core.sync().await.unwrap();
+    let other_device = test_core_from(&core).await;
+
+    let since_metadata_version = get_time().0 as u64;
+    let (client, waiting_account) = (local(&core).client.clone(), account.clone());
+    let waiting = tokio::spawn(async move {
+        client
+            .request(&waiting_account, WaitForUpdatesRequest { since_metadata_version })
+            .await
+            .unwrap()
+    });
+
+    core.create_at_path("/mine").await.unwrap();
+    core.sync().await.unwrap();
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    assert!(!waiting.is_finished());
+
+    other_device.create_at_path("/theirs").await.unwrap();
+    other_device.sync().await.unwrap();
+
+    let response = tokio::time::timeout(Duration::from_secs(5), waiting)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(response.updated);
+}
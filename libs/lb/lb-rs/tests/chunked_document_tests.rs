@@ -0,0 +1,151 @@
+use lb_rs::Lb;
+use lb_rs::model::api::GetChunkRequest;
+use lb_rs::model::chunks::MAX_CHUNK_SIZE;
+use lb_rs::model::file::ShareMode;
+use lb_rs::model::symkey;
+use lb_rs::service::streams::DocumentContent;
+use test_utils::*;
+use uuid::Uuid;
+
+fn pseudo_random(len: usize) -> Vec<u8> {
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}
+
+async fn write_chunked(core: &Lb, id: Uuid, content: &[u8]) {
+    let mut writer = core.open_writer(id).await.unwrap();
+    // written in pieces that don't line up with chunk boundaries
+    for piece in content.chunks(100_000) {
+        writer.write(piece).await.unwrap();
+    }
+    writer.finish().await.unwrap();
+}
+
+async fn read_chunked(core: &Lb, id: Uuid) -> Vec<u8> {
+    let mut reader = core.open_reader(id).await.unwrap();
+    let mut content = vec![];
+    while let Some(piece) = reader.read_next().await.unwrap() {
+        content.extend(piece);
+    }
+    assert_eq!(content.len() as u64, reader.len());
+    content
+}
+
+async fn pending_chunk_uploads(core: &Lb) -> usize {
+    let lb = local(core);
+    let tx = lb.ro_tx().await;
+    tx.db().chunk_uploads.get().len()
+}
+
+#[tokio::test]
+async fn write_read_round_trip() {
+    let core = test_core_with_account().await;
+    let doc = core.create_at_path("video.mp4").await.unwrap();
+    let content = pseudo_random(3 * MAX_CHUNK_SIZE);
+
+    write_chunked(&core, doc.id, &content).await;
+
+    let DocumentContent::Chunked(manifest) =
+        core.read_document_content(doc.id, false).await.unwrap()
+    else {
+        panic!("document was not chunked");
+    };
+    assert!(manifest.chunks.len() > 1);
+    assert_eq!(read_chunked(&core, doc.id).await, content);
+    assert_eq!(core.read_document(doc.id, false).await.unwrap(), content);
+}
+
+#[tokio::test]
+async fn reader_reads_regular_documents() {
+    let core = test_core_with_account().await;
+    let doc = core.create_at_path("doc.md").await.unwrap();
+    core.write_document(doc.id, b"contents").await.unwrap();
+
+    assert_eq!(read_chunked(&core, doc.id).await, b"contents");
+}
+
+#[tokio::test]
+async fn chunked_document_syncs() {
+    let core = test_core_with_account().await;
+    let doc = core.create_at_path("video.mp4").await.unwrap();
+    let content = pseudo_random(2 * MAX_CHUNK_SIZE);
+    write_chunked(&core, doc.id, &content).await;
+    core.sync().await.unwrap();
+    assert_eq!(pending_chunk_uploads(&core).await, 0);
+
+    let other = test_core_from(&core).await;
+    assert_eq!(read_chunked(&other, doc.id).await, content);
+    assert_eq!(other.read_document(doc.id, false).await.unwrap(), content);
+}
+
+#[tokio::test]
+async fn edit_keeps_chunk_key() {
+    let core = test_core_with_account().await;
+    let doc = core.create_at_path("video.mp4").await.unwrap();
+    let mut content = pseudo_random(2 * MAX_CHUNK_SIZE);
+    write_chunked(&core, doc.id, &content).await;
+    core.sync().await.unwrap();
+    let key = core.chunk_key(doc.id).await.unwrap();
+
+    content.splice(1000..1000, b"an insertion near the start".iter().copied());
+    write_chunked(&core, doc.id, &content).await;
+    assert_eq!(core.chunk_key(doc.id).await.unwrap(), key);
+    core.sync().await.unwrap();
+
+    let other = test_core_from(&core).await;
+    assert_eq!(read_chunked(&other, doc.id).await, content);
+}
+
+#[tokio::test]
+async fn revoked_sharee_cant_read_chunks_written_after_rotation() {
+    let cores = [test_core_with_account().await, test_core_with_account().await];
+    let owner = cores[0].get_account().unwrap();
+    let sharee = cores[1].get_account().unwrap().username.clone();
+    let folder = cores[0].create_at_path("videos/").await.unwrap();
+    let doc = cores[0].create_at_path("videos/video.mp4").await.unwrap();
+    let mut content = pseudo_random(2 * MAX_CHUNK_SIZE);
+    write_chunked(&cores[0], doc.id, &content).await;
+    cores[0]
+        .share_file(folder.id, &sharee, ShareMode::Read)
+        .await
+        .unwrap();
+    cores[0].sync().await.unwrap();
+    cores[1].sync().await.unwrap();
+    let revoked_key = cores[1].chunk_key(doc.id).await.unwrap();
+
+    cores[0]
+        .revoke_share(folder.id, &sharee, true)
+        .await
+        .unwrap();
+    cores[0].sync().await.unwrap();
+    assert_ne!(cores[0].chunk_key(doc.id).await.unwrap(), revoked_key);
+
+    content.extend(pseudo_random(MAX_CHUNK_SIZE));
+    write_chunked(&cores[0], doc.id, &content).await;
+    cores[0].sync().await.unwrap();
+
+    let DocumentContent::Chunked(manifest) =
+        cores[0].read_document_content(doc.id, false).await.unwrap()
+    else {
+        panic!("document was not chunked");
+    };
+    for chunk in manifest.chunks {
+        let encrypted = local(&cores[0])
+            .client
+            .request(&owner, GetChunkRequest { id: doc.id, hmac: chunk.hmac })
+            .await
+            .unwrap()
+            .content;
+        assert!(symkey::decrypt(&revoked_key, &encrypted).is_err());
+    }
+
+    let other = test_core_from(&cores[0]).await;
+    assert_eq!(read_chunked(&other, doc.id).await, content);
+}
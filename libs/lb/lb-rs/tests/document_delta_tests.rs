@@ -0,0 +1,160 @@
+use lb_rs::Lb;
+use lb_rs::io::network::ApiError;
+use lb_rs::model::api::{ContentFormat, GetDocRequest, GetDocRequestV2};
+use lb_rs::model::delta::DocumentDelta;
+use lb_rs::model::file_like::FileLike;
+use lb_rs::model::file_metadata::DocumentHmac;
+use lb_rs::model::tree_like::TreeLike;
+use test_utils::*;
+use uuid::Uuid;
+
+/// a journal that doesn't compress well, so it stays large once it's encrypted
+fn journal(lines: u64) -> String {
+    let mut state: u64 = 0x006a_6f75_726e_616c;
+    let mut journal = String::new();
+    for _ in 0..lines {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        journal.push_str(&format!("{state:016x}\n"));
+    }
+    journal
+}
+
+/// hmac of the document's content as of the last sync
+async fn synced_hmac(core: &Lb, id: Uuid) -> DocumentHmac {
+    let lb = local(core);
+    let tx = lb.ro_tx().await;
+    tx.db()
+        .base_metadata
+        .get()
+        .get(&id)
+        .unwrap()
+        .document_hmac()
+        .copied()
+        .unwrap()
+}
+
+/// whether the server stored the document's current content as a delta
+async fn stored_as_delta(core: &Lb, id: Uuid) -> bool {
+    let lb = local(core);
+    let hmac = synced_hmac(core, id).await;
+    let response = lb
+        .client
+        .request(&core.get_account().unwrap(), GetDocRequestV2 { id, hmac })
+        .await
+        .unwrap();
+    if response.format != ContentFormat::Delta {
+        return false;
+    }
+
+    let tx = lb.ro_tx().await;
+    let db = tx.db();
+    let mut tree = (&db.base_metadata).to_staged(&db.local_metadata).to_lazy();
+    let content = tree
+        .decrypt_document(&id, &response.content, &lb.keychain)
+        .unwrap();
+    DocumentDelta::from_bytes(&content).is_ok()
+}
+
+#[tokio::test]
+async fn small_edit_uploads_delta() {
+    let core = test_core_with_account().await;
+    let doc = core.create_at_path("journal.md").await.unwrap();
+    let mut content = journal(20_000);
+    core.write_document(doc.id, content.as_bytes())
+        .await
+        .unwrap();
+    core.sync().await.unwrap();
+    assert!(!stored_as_delta(&core, doc.id).await);
+
+    content.insert_str(1700, "a sentence typed into the middle of the journal\n");
+    core.write_document(doc.id, content.as_bytes())
+        .await
+        .unwrap();
+    core.sync().await.unwrap();
+    assert!(stored_as_delta(&core, doc.id).await);
+
+    let other = test_core_from(&core).await;
+    assert_eq!(other.read_document(doc.id, false).await.unwrap(), content.as_bytes());
+}
+
+#[tokio::test]
+async fn stacked_deltas() {
+    let core = test_core_with_account().await;
+    let doc = core.create_at_path("journal.md").await.unwrap();
+    let mut content = journal(20_000);
+    core.write_document(doc.id, content.as_bytes())
+        .await
+        .unwrap();
+    core.sync().await.unwrap();
+    let other = test_core_from(&core).await;
+
+    for day in 0..3 {
+        content.push_str(&format!("day {day}: more writing\n"));
+        core.write_document(doc.id, content.as_bytes())
+            .await
+            .unwrap();
+        core.sync().await.unwrap();
+    }
+
+    other.sync().await.unwrap();
+    assert_eq!(other.read_document(doc.id, false).await.unwrap(), content.as_bytes());
+}
+
+#[tokio::test]
+async fn small_document_uploaded_whole() {
+    let core = test_core_with_account().await;
+    let doc = core.create_at_path("note.md").await.unwrap();
+    core.write_document(doc.id, b"a short note").await.unwrap();
+    core.sync().await.unwrap();
+
+    core.write_document(doc.id, b"a short note, edited")
+        .await
+        .unwrap();
+    core.sync().await.unwrap();
+    assert!(!stored_as_delta(&core, doc.id).await);
+}
+
+#[tokio::test]
+async fn non_text_document_uploaded_whole() {
+    let core = test_core_with_account().await;
+    let doc = core.create_at_path("data.bin").await.unwrap();
+    let mut content = journal(20_000);
+    core.write_document(doc.id, content.as_bytes())
+        .await
+        .unwrap();
+    core.sync().await.unwrap();
+
+    content.push_str("appended\n");
+    core.write_document(doc.id, content.as_bytes())
+        .await
+        .unwrap();
+    core.sync().await.unwrap();
+    assert!(!stored_as_delta(&core, doc.id).await);
+}
+
+#[tokio::test]
+async fn delta_refused_to_old_readers() {
+    let core = test_core_with_account().await;
+    let doc = core.create_at_path("journal.md").await.unwrap();
+    let mut content = journal(20_000);
+    core.write_document(doc.id, content.as_bytes())
+        .await
+        .unwrap();
+    core.sync().await.unwrap();
+
+    content.insert_str(1700, "a sentence typed into the middle of the journal\n");
+    core.write_document(doc.id, content.as_bytes())
+        .await
+        .unwrap();
+    core.sync().await.unwrap();
+    assert!(stored_as_delta(&core, doc.id).await);
+
+    let hmac = synced_hmac(&core, doc.id).await;
+    let result = local(&core)
+        .client
+        .request(&core.get_account().unwrap(), GetDocRequest { id: doc.id, hmac })
+        .await;
+    assert!(matches!(result, Err(ApiError::ClientUpdateRequired)));
+}
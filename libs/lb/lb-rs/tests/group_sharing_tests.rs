@@ -0,0 +1,307 @@
+use lb_rs::model::errors::LbErrKind;
+use lb_rs::model::file::ShareMode;
+use test_utils::*;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn create_group() {
+    let core = test_core_with_account().await;
+    let account = core.get_account().unwrap();
+
+    let group = core.create_group("editors").await.unwrap();
+    assert_eq!(group.name, "editors");
+    assert_eq!(group.owner, account.username);
+    assert_eq!(group.members, vec![account.username.clone()]);
+
+    let other = test_core_from(&core).await;
+    other.sync().await.unwrap();
+    assert_eq!(other.list_groups().await.unwrap(), vec![group]);
+}
+
+#[tokio::test]
+async fn add_member() {
+    let cores = [test_core_with_account().await, test_core_with_account().await];
+    let member = cores[1].get_account().unwrap().username.clone();
+
+    let group = cores[0].create_group("editors").await.unwrap();
+    cores[0].add_group_member(group.id, &member).await.unwrap();
+
+    cores[1].sync().await.unwrap();
+    let groups = cores[1].list_groups().await.unwrap();
+    assert_eq!(groups.len(), 1);
+    assert_eq!(groups[0].id, group.id);
+    assert!(groups[0].members.contains(&member));
+}
+
+#[tokio::test]
+async fn add_member_twice() {
+    let cores = [test_core_with_account().await, test_core_with_account().await];
+    let member = cores[1].get_account().unwrap().username.clone();
+
+    let group = cores[0].create_group("editors").await.unwrap();
+    cores[0].add_group_member(group.id, &member).await.unwrap();
+
+    let result = cores[0].add_group_member(group.id, &member).await;
+    assert_matches!(result.unwrap_err().kind, LbErrKind::ShareAlreadyExists);
+}
+
+#[tokio::test]
+async fn add_member_not_owner() {
+    let cores = [
+        test_core_with_account().await,
+        test_core_with_account().await,
+        test_core_with_account().await,
+    ];
+    let member = cores[1].get_account().unwrap().username.clone();
+    let outsider = cores[2].get_account().unwrap().username.clone();
+
+    let group = cores[0].create_group("editors").await.unwrap();
+    cores[0].add_group_member(group.id, &member).await.unwrap();
+    cores[1].sync().await.unwrap();
+
+    let result = cores[1].add_group_member(group.id, &outsider).await;
+    assert_matches!(result.unwrap_err().kind, LbErrKind::InsufficientPermission);
+}
+
+#[tokio::test]
+async fn add_member_nonexistent_group() {
+    let cores = [test_core_with_account().await, test_core_with_account().await];
+    let member = cores[1].get_account().unwrap().username.clone();
+
+    let result = cores[0].add_group_member(Uuid::new_v4(), &member).await;
+    assert_matches!(result.unwrap_err().kind, LbErrKind::GroupNonexistent);
+}
+
+#[tokio::test]
+async fn share_with_group() {
+    let cores = [test_core_with_account().await, test_core_with_account().await];
+    let member = cores[1].get_account().unwrap().username.clone();
+
+    let group = cores[0].create_group("editors").await.unwrap();
+    cores[0].add_group_member(group.id, &member).await.unwrap();
+    let folder = cores[0].create_at_path("/drafts/").await.unwrap();
+    let document = cores[0].create_at_path("/drafts/chapter.md").await.unwrap();
+    cores[0]
+        .write_document(document.id, b"it was a dark and stormy night")
+        .await
+        .unwrap();
+    cores[0]
+        .share_file_with_group(folder.id, group.id, ShareMode::Write)
+        .await
+        .unwrap();
+    cores[0].sync().await.unwrap();
+
+    let shares = cores[0].get_file_by_id(folder.id).await.unwrap().shares;
+    assert_eq!(shares.len(), 1);
+    assert_eq!(shares[0].shared_with, "editors");
+
+    cores[1].sync().await.unwrap();
+    let pending = cores[1].get_pending_shares().await.unwrap();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].id, folder.id);
+    assert_eq!(
+        cores[1].read_document(document.id, false).await.unwrap(),
+        b"it was a dark and stormy night"
+    );
+}
+
+#[tokio::test]
+async fn member_edits_group_share() {
+    let cores = [test_core_with_account().await, test_core_with_account().await];
+    let member = cores[1].get_account().unwrap().username.clone();
+
+    let group = cores[0].create_group("editors").await.unwrap();
+    cores[0].add_group_member(group.id, &member).await.unwrap();
+    let folder = cores[0].create_at_path("/drafts/").await.unwrap();
+    let document = cores[0].create_at_path("/drafts/chapter.md").await.unwrap();
+    cores[0]
+        .share_file_with_group(folder.id, group.id, ShareMode::Write)
+        .await
+        .unwrap();
+    cores[0].sync().await.unwrap();
+
+    cores[1].sync().await.unwrap();
+    cores[1]
+        .create_link_at_path("/drafts", folder.id)
+        .await
+        .unwrap();
+    cores[1]
+        .write_document(document.id, b"edited by a member")
+        .await
+        .unwrap();
+    cores[1].sync().await.unwrap();
+
+    cores[0].sync().await.unwrap();
+    assert_eq!(cores[0].read_document(document.id, false).await.unwrap(), b"edited by a member");
+}
+
+#[tokio::test]
+async fn member_cannot_edit_read_group_share() {
+    let cores = [test_core_with_account().await, test_core_with_account().await];
+    let member = cores[1].get_account().unwrap().username.clone();
+
+    let group = cores[0].create_group("readers").await.unwrap();
+    cores[0].add_group_member(group.id, &member).await.unwrap();
+    let document = cores[0].create_at_path("/chapter.md").await.unwrap();
+    cores[0]
+        .share_file_with_group(document.id, group.id, ShareMode::Read)
+        .await
+        .unwrap();
+    cores[0].sync().await.unwrap();
+
+    cores[1].sync().await.unwrap();
+    let result = cores[1].write_document(document.id, b"edited").await;
+    assert_matches!(result.unwrap_err().kind, LbErrKind::InsufficientPermission);
+}
+
+#[tokio::test]
+async fn member_added_after_share() {
+    let cores = [test_core_with_account().await, test_core_with_account().await];
+    let member = cores[1].get_account().unwrap().username.clone();
+
+    let group = cores[0].create_group("editors").await.unwrap();
+    let document = cores[0].create_at_path("/chapter.md").await.unwrap();
+    cores[0]
+        .write_document(document.id, b"shared before you joined")
+        .await
+        .unwrap();
+    cores[0]
+        .share_file_with_group(document.id, group.id, ShareMode::Read)
+        .await
+        .unwrap();
+    cores[0].sync().await.unwrap();
+
+    // synced before joining, so the share predates what the member has pulled
+    cores[1].sync().await.unwrap();
+    assert!(cores[1].get_pending_shares().await.unwrap().is_empty());
+
+    cores[0].add_group_member(group.id, &member).await.unwrap();
+    cores[1].sync().await.unwrap();
+    let pending = cores[1].get_pending_shares().await.unwrap();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(
+        cores[1].read_document(document.id, false).await.unwrap(),
+        b"shared before you joined"
+    );
+}
+
+#[tokio::test]
+async fn remove_member() {
+    let cores = [test_core_with_account().await, test_core_with_account().await];
+    let member = cores[1].get_account().unwrap().username.clone();
+
+    let group = cores[0].create_group("editors").await.unwrap();
+    cores[0].add_group_member(group.id, &member).await.unwrap();
+    let document = cores[0].create_at_path("/chapter.md").await.unwrap();
+    cores[0]
+        .share_file_with_group(document.id, group.id, ShareMode::Read)
+        .await
+        .unwrap();
+    cores[0].sync().await.unwrap();
+    cores[1].sync().await.unwrap();
+    assert_eq!(cores[1].get_pending_shares().await.unwrap().len(), 1);
+
+    cores[0]
+        .remove_group_member(group.id, &member)
+        .await
+        .unwrap();
+    cores[1].sync().await.unwrap();
+    assert!(cores[1].list_groups().await.unwrap().is_empty());
+    assert!(cores[1].get_pending_shares().await.unwrap().is_empty());
+    assert_matches!(
+        cores[1]
+            .read_document(document.id, false)
+            .await
+            .unwrap_err()
+            .kind,
+        LbErrKind::FileNonexistent
+    );
+}
+
+#[tokio::test]
+async fn remove_member_replaces_group_key() {
+    let cores = [
+        test_core_with_account().await,
+        test_core_with_account().await,
+        test_core_with_account().await,
+    ];
+    let staying = cores[1].get_account().unwrap().username.clone();
+    let leaving = cores[2].get_account().unwrap().username.clone();
+
+    let group = cores[0].create_group("editors").await.unwrap();
+    cores[0].add_group_member(group.id, &staying).await.unwrap();
+    cores[0].add_group_member(group.id, &leaving).await.unwrap();
+    let before = cores[0].create_at_path("/before.md").await.unwrap();
+    cores[0]
+        .write_document(before.id, b"shared before")
+        .await
+        .unwrap();
+    cores[0]
+        .share_file_with_group(before.id, group.id, ShareMode::Read)
+        .await
+        .unwrap();
+    cores[0].sync().await.unwrap();
+    cores[2].sync().await.unwrap();
+    let leaving_keys = local(&cores[2]).keychain.groups().unwrap();
+
+    cores[0]
+        .remove_group_member(group.id, &leaving)
+        .await
+        .unwrap();
+    let after = cores[0].create_at_path("/after.md").await.unwrap();
+    cores[0]
+        .write_document(after.id, b"shared after")
+        .await
+        .unwrap();
+    cores[0]
+        .share_file_with_group(after.id, group.id, ShareMode::Read)
+        .await
+        .unwrap();
+    cores[0].sync().await.unwrap();
+
+    // files shared from now on use a key the removed member never had
+    let key = {
+        let lb = local(&cores[0]);
+        let tx = lb.ro_tx().await;
+        tx.db().groups.get().get(&group.id).unwrap().public_key
+    };
+    assert!(!leaving_keys.iter().any(|owner| owner.0 == key));
+
+    // and the remaining members read what's shared with both keys
+    cores[1].sync().await.unwrap();
+    assert_eq!(cores[1].read_document(before.id, false).await.unwrap(), b"shared before");
+    assert_eq!(cores[1].read_document(after.id, false).await.unwrap(), b"shared after");
+}
+
+#[tokio::test]
+async fn remove_owner() {
+    let core = test_core_with_account().await;
+    let owner = core.get_account().unwrap().username.clone();
+
+    let group = core.create_group("editors").await.unwrap();
+    let result = core.remove_group_member(group.id, &owner).await;
+    assert_matches!(result.unwrap_err().kind, LbErrKind::InsufficientPermission);
+}
+
+#[tokio::test]
+async fn remove_non_member() {
+    let cores = [test_core_with_account().await, test_core_with_account().await];
+    let outsider = cores[1].get_account().unwrap().username.clone();
+
+    let group = cores[0].create_group("editors").await.unwrap();
+    let result = cores[0].remove_group_member(group.id, &outsider).await;
+    assert_matches!(result.unwrap_err().kind, LbErrKind::ShareNonexistent);
+}
+
+#[tokio::test]
+async fn group_not_listed_as_username() {
+    let core = test_core_with_account().await;
+    let group = core.create_group("editors").await.unwrap();
+    let document = core.create_at_path("/chapter.md").await.unwrap();
+    core.share_file_with_group(document.id, group.id, ShareMode::Read)
+        .await
+        .unwrap();
+    core.sync().await.unwrap();
+
+    assert!(!core.known_usernames().await.unwrap().contains(&group.name));
+}
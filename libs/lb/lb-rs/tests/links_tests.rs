@@ -0,0 +1,205 @@
+use lb_rs::Lb;
+use lb_rs::service::links::{LinkKind, LinkTarget, parse_links};
+use test_utils::*;
+
+#[test]
+fn parses_links() {
+    let doc = "see [notes](../notes.md \"Notes\") and ![img](<a b.png>)\n\
+        [[Todo|my list]] or [[plans/q3#goals]] at lb://6f1c\n\
+        `[skip](me.md)` [ref]: /ref.md\n\
+        ```\n[[skipped]]\n```\n\
+        [ref]: /ref.md\n";
+    let links = parse_links(doc);
+    let targets: Vec<(LinkKind, &str)> =
+        links.iter().map(|l| (l.kind, l.target.as_str())).collect();
+    assert_eq!(
+        targets,
+        vec![
+            (LinkKind::Markdown, "../notes.md"),
+            (LinkKind::Markdown, "a b.png"),
+            (LinkKind::Wiki, "Todo"),
+            (LinkKind::Wiki, "plans/q3"),
+            (LinkKind::Url, "lb://6f1c"),
+            (LinkKind::Markdown, "/ref.md"),
+        ]
+    );
+    for link in links {
+        assert_eq!(&doc[link.range], link.target);
+    }
+}
+
+#[tokio::test]
+async fn outgoing_links() {
+    let core = test_core_with_account().await;
+    let todo = core.create_at_path("/todo.md").await.unwrap();
+    let sketch = core.create_at_path("/drawings/sketch.svg").await.unwrap();
+    let content = format!(
+        "[sketch](drawings/sketch.svg) [[todo]] [by id](lb://{}) [web](https://lockbook.net) \
+         [gone](missing.md) [folder](/drawings/)",
+        todo.id
+    );
+    let notes = create(&core, "/notes.md", content.as_bytes()).await;
+
+    let links = core.outgoing_links(notes).await.unwrap();
+    let targets: Vec<LinkTarget> = links.into_iter().map(|l| l.to).collect();
+    assert_eq!(
+        targets,
+        vec![
+            LinkTarget::File(sketch.id),
+            LinkTarget::File(todo.id),
+            LinkTarget::File(todo.id),
+            LinkTarget::External("https://lockbook.net".to_string()),
+            LinkTarget::Broken,
+            LinkTarget::Broken,
+        ]
+    );
+}
+
+#[tokio::test]
+async fn backlinks() {
+    let core = test_core_with_account().await;
+    let todo = create(&core, "/todo.md", b"nothing here").await;
+    let a = create(&core, "/a.md", b"[[todo]]").await;
+    let b = create(&core, "/work/b.md", b"[todo](../todo.md)").await;
+    create(&core, "/c.md", b"[[elsewhere]]").await;
+
+    let mut from: Vec<_> = core
+        .backlinks(todo)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|l| l.from)
+        .collect();
+    from.sort();
+    let mut expected = vec![a, b];
+    expected.sort();
+    assert_eq!(from, expected);
+}
+
+#[tokio::test]
+async fn nearest_wikilink_wins() {
+    let core = test_core_with_account().await;
+    create(&core, "/todo.md", b"").await;
+    let near = create(&core, "/work/todo.md", b"").await;
+    let from = create(&core, "/work/plan.md", b"[[todo]]").await;
+    create(&core, "/a/todo.md", b"").await;
+    create(&core, "/b/todo.md", b"").await;
+    create(&core, "/c.md", b"[[todo]]").await;
+
+    let links = core.outgoing_links(from).await.unwrap();
+    assert_eq!(links[0].to, LinkTarget::File(near));
+
+    let broken = core.broken_links().await.unwrap();
+    assert!(broken.is_empty());
+
+    // without /todo.md, /c.md is as near to three todos
+    delete_path(&core, "/todo.md").await.unwrap();
+    let broken = core.broken_links().await.unwrap();
+    assert_eq!(broken.len(), 1);
+    assert_eq!(broken[0].link.target, "todo");
+}
+
+#[tokio::test]
+async fn follows_renames() {
+    let core = test_core_with_account().await;
+    let todo = create(&core, "/todo.md", b"").await;
+    create(&core, "/notes.md", b"[todo](todo.md)").await;
+    assert!(core.broken_links().await.unwrap().is_empty());
+
+    core.rename_file(&todo, "done.md").await.unwrap();
+    assert_eq!(core.broken_links().await.unwrap().len(), 1);
+    assert!(core.backlinks(todo).await.unwrap().is_empty());
+
+    create(&core, "/todo.md", b"").await;
+    assert!(core.broken_links().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn synced_links() {
+    let core = test_core_with_account().await;
+    let todo = create(&core, "/todo.md", b"").await;
+    create(&core, "/notes.md", b"[[todo]]").await;
+    core.sync().await.unwrap();
+
+    let other = test_core_from(&core).await;
+    assert_eq!(other.backlinks(todo).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn rename_updates_links() {
+    let core = test_core_with_account().await;
+    let todo = create(&core, "/work/todo.md", b"").await;
+    let a = create(&core, "/a.md", b"[[todo]] and [[work/todo.md]]").await;
+    let b = create(&core, "/work/b.md", b"[todo](todo.md#today), [abs](/work/todo.md)").await;
+    let c =
+        create(&core, "/c.md", b"[[elsewhere]] [by id](lb://00000000-0000-0000-0000-000000000000)")
+            .await;
+
+    let mut updated = core
+        .rename_file_with_link_update(&todo, "to do.md")
+        .await
+        .unwrap();
+    updated.sort();
+    let mut expected = vec![a, b];
+    expected.sort();
+    assert_eq!(updated, expected);
+
+    assert_eq!(read(&core, a).await, "[[to do]] and [[work/to do.md]]");
+    assert_eq!(read(&core, b).await, "[todo](to%20do.md#today), [abs](/work/to%20do.md)");
+    assert_eq!(
+        read(&core, c).await,
+        "[[elsewhere]] [by id](lb://00000000-0000-0000-0000-000000000000)"
+    );
+    assert_eq!(core.get_by_path("/work/to do.md").await.unwrap().id, todo);
+    assert_eq!(core.broken_links().await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn move_updates_links() {
+    let core = test_core_with_account().await;
+    let archive = core.create_at_path("/archive/").await.unwrap();
+    create(&core, "/work/sketch.svg", b"").await;
+    let todo = create(&core, "/work/todo.md", b"[sketch](sketch.svg) [[todo]]").await;
+    let notes = create(&core, "/notes.md", b"[todo](work/todo.md) [[todo]]").await;
+
+    let mut updated = core
+        .move_file_with_link_update(&todo, &archive.id)
+        .await
+        .unwrap();
+    updated.sort();
+    let mut expected = vec![todo, notes];
+    expected.sort();
+    assert_eq!(updated, expected);
+
+    assert_eq!(read(&core, todo).await, "[sketch](../work/sketch.svg) [[todo]]");
+    assert_eq!(read(&core, notes).await, "[todo](archive/todo.md) [[todo]]");
+    assert!(core.broken_links().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn move_folder_updates_links() {
+    let core = test_core_with_account().await;
+    let work = core.create_at_path("/work/").await.unwrap();
+    let archive = core.create_at_path("/archive/").await.unwrap();
+    create(&core, "/work/todo.md", b"[plan](plan.md)").await;
+    create(&core, "/work/plan.md", b"").await;
+    let notes = create(&core, "/notes.md", b"[todo](work/todo.md)").await;
+
+    let updated = core
+        .move_file_with_link_update(&work.id, &archive.id)
+        .await
+        .unwrap();
+    assert_eq!(updated, vec![notes]);
+    assert_eq!(read(&core, notes).await, "[todo](archive/work/todo.md)");
+    assert!(core.broken_links().await.unwrap().is_empty());
+}
+
+async fn read(core: &Lb, id: lb_rs::Uuid) -> String {
+    String::from_utf8(core.read_document(id, false).await.unwrap()).unwrap()
+}
+
+async fn create(core: &Lb, path: &str, content: &[u8]) -> lb_rs::Uuid {
+    let file = core.create_at_path(path).await.unwrap();
+    core.write_document(file.id, content).await.unwrap();
+    file.id
+}
@@ -0,0 +1,125 @@
+use lb_rs::model::errors::LbErrKind;
+use lb_rs::model::file::ShareMode;
+use test_utils::*;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn create_link_to_document() {
+    let core = test_core_with_account().await;
+    let doc = core.create_at_path("notes/recipe.md").await.unwrap();
+    core.write_document(doc.id, b"flour, water, salt")
+        .await
+        .unwrap();
+    core.sync().await.unwrap();
+
+    let link = core.create_public_link(doc.id).await.unwrap();
+    let url = link.url.clone().unwrap();
+    assert!(url.contains(&format!("/public/{}#", link.id)));
+    assert_eq!(link.file_id, doc.id);
+
+    let links = core.list_public_links().await.unwrap();
+    assert_eq!(links.len(), 1);
+    assert_eq!(links[0].id, link.id);
+    assert_eq!(links[0].url, Some(url));
+}
+
+#[tokio::test]
+async fn create_link_to_folder() {
+    let core = test_core_with_account().await;
+    let folder = core.create_at_path("notes/").await.unwrap();
+    let doc = core.create_at_path("notes/drafts/recipe.md").await.unwrap();
+    core.write_document(doc.id, b"flour, water, salt")
+        .await
+        .unwrap();
+    core.sync().await.unwrap();
+
+    let link = core.create_public_link(folder.id).await.unwrap();
+    let links = core.list_public_links().await.unwrap();
+    assert_eq!(links.len(), 1);
+    assert_eq!(links[0].url, link.url);
+}
+
+#[tokio::test]
+async fn links_listed_on_other_devices() {
+    let core = test_core_with_account().await;
+    let doc = core.create_at_path("recipe.md").await.unwrap();
+    core.sync().await.unwrap();
+    let link = core.create_public_link(doc.id).await.unwrap();
+
+    let other = test_core_from(&core).await;
+    let links = other.list_public_links().await.unwrap();
+    assert_eq!(links.len(), 1);
+    assert_eq!(links[0].url, link.url);
+}
+
+#[tokio::test]
+async fn revoke_link() {
+    let core = test_core_with_account().await;
+    let doc = core.create_at_path("recipe.md").await.unwrap();
+    core.sync().await.unwrap();
+
+    let first = core.create_public_link(doc.id).await.unwrap();
+    let second = core.create_public_link(doc.id).await.unwrap();
+    core.revoke_public_link(first.id).await.unwrap();
+
+    let links = core.list_public_links().await.unwrap();
+    assert_eq!(links.len(), 1);
+    assert_eq!(links[0].id, second.id);
+}
+
+#[tokio::test]
+async fn revoke_nonexistent_link() {
+    let core = test_core_with_account().await;
+    let result = core.revoke_public_link(Uuid::new_v4()).await;
+    assert!(matches!(result.unwrap_err().kind, LbErrKind::ShareNonexistent));
+}
+
+#[tokio::test]
+async fn link_to_unsynced_file() {
+    let core = test_core_with_account().await;
+    let doc = core.create_at_path("recipe.md").await.unwrap();
+
+    let result = core.create_public_link(doc.id).await;
+    assert!(matches!(result.unwrap_err().kind, LbErrKind::FileNonexistent));
+}
+
+#[tokio::test]
+async fn reader_cannot_create_link() {
+    let cores = [test_core_with_account().await, test_core_with_account().await];
+    let sharee = cores[1].get_account().unwrap().username.clone();
+    let folder = cores[0].create_at_path("notes/").await.unwrap();
+    cores[0]
+        .share_file(folder.id, &sharee, ShareMode::Read)
+        .await
+        .unwrap();
+    cores[0].sync().await.unwrap();
+    cores[1].sync().await.unwrap();
+
+    let result = cores[1].create_public_link(folder.id).await;
+    assert!(matches!(result.unwrap_err().kind, LbErrKind::InsufficientPermission));
+    assert!(cores[1].list_public_links().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn link_listed_after_key_rotation() {
+    let cores = [test_core_with_account().await, test_core_with_account().await];
+    let sharee = cores[1].get_account().unwrap().username.clone();
+    let folder = cores[0].create_at_path("notes/").await.unwrap();
+    cores[0]
+        .share_file(folder.id, &sharee, ShareMode::Read)
+        .await
+        .unwrap();
+    cores[0].sync().await.unwrap();
+    let link = cores[0].create_public_link(folder.id).await.unwrap();
+
+    cores[0]
+        .revoke_share(folder.id, &sharee, true)
+        .await
+        .unwrap();
+    cores[0].sync().await.unwrap();
+
+    let links = cores[0].list_public_links().await.unwrap();
+    assert_eq!(links.len(), 1);
+    assert_eq!(links[0].id, link.id);
+    assert_eq!(links[0].url, None);
+}
@@ -0,0 +1,80 @@
+use lb_rs::model::errors::LbErrKind;
+use lb_rs::service::render::RenderFormat;
+use lb_rs::{Lb, Uuid};
+use test_utils::*;
+
+/// a single transparent pixel
+const PIXEL: &str = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNkYPhfDwAChwGA60e6kgAAAABJRU5ErkJggg==";
+
+const DRAWING: &str = "<svg xmlns=\"http://www.w3.org/2000/svg\"><path d=\"M10 10 L 40 40 L 60 20\" \
+    stroke-width='3' stroke='rgba(42,136,49,1)' fill='none' id='6772800c-244a-4e50-9c54-605d31ef70c5'/></svg>";
+
+async fn create(core: &Lb, path: &str, content: &[u8]) -> Uuid {
+    let id = core.create_at_path(path).await.unwrap().id;
+    core.write_document(id, content).await.unwrap();
+    id
+}
+
+#[tokio::test]
+async fn html_embeds_images() {
+    let core = test_core_with_account().await;
+    create(&core, "/notes/images/pixel.png", &base64::decode(PIXEL).unwrap()).await;
+    let sketch = create(&core, "/sketch.svg", DRAWING.as_bytes()).await;
+    let doc = create(
+        &core,
+        "/notes/doc.md",
+        format!("# Doc\n\n![pixel](images/pixel.png)\n\n![sketch](lb://{sketch})\n\n![gone](missing.png)\n")
+            .as_bytes(),
+    )
+    .await;
+
+    let html = core.export_rendered(doc, RenderFormat::Html).await.unwrap();
+    let html = String::from_utf8(html).unwrap();
+    assert!(html.contains("<title>doc</title>"));
+    assert!(html.contains("src=\"data:image/png;base64,"));
+    assert!(html.contains("src=\"data:image/svg+xml;base64,"));
+    assert!(html.contains("src=\"missing.png\""));
+}
+
+#[cfg(feature = "pdf")]
+#[tokio::test]
+async fn pdf_is_complete() {
+    let core = test_core_with_account().await;
+    create(&core, "/pixel.png", &base64::decode(PIXEL).unwrap()).await;
+    create(&core, "/sketch.svg", DRAWING.as_bytes()).await;
+    let mut md = String::from("# Title\n\n![pixel](pixel.png) ![sketch](sketch.svg)\n\n");
+    md.push_str("- [x] done\n- [ ] todo\n\n| a | b |\n|---|--:|\n| 1 | 2 |\n\n```\ncode\n```\n\n");
+    for i in 0..200 {
+        md.push_str(&format!(
+            "paragraph {i} with **bold**, *italic* and [a link](https://lockbook.net)\n\n"
+        ));
+    }
+    let doc = create(&core, "/doc.md", md.as_bytes()).await;
+
+    let pdf = core.export_rendered(doc, RenderFormat::Pdf).await.unwrap();
+    assert!(pdf.starts_with(b"%PDF-"));
+    assert!(pdf.ends_with(b"%%EOF\n"));
+}
+
+#[cfg(feature = "pdf")]
+#[tokio::test]
+async fn pdf_embeds_fonts() {
+    let core = test_core_with_account().await;
+    let doc =
+        create(&core, "/doc.md", "# Überblick\n\nπ ≈ 3.14, Привет, `naïve`\n".as_bytes()).await;
+
+    let pdf = core.export_rendered(doc, RenderFormat::Pdf).await.unwrap();
+    let contains = |needle: &[u8]| pdf.windows(needle.len()).any(|window| window == needle);
+    assert!(contains(b"/FontFile2"));
+    assert!(contains(b"/ToUnicode"));
+    assert!(!contains(b"/Type1"));
+}
+
+#[tokio::test]
+async fn folders_cannot_be_rendered() {
+    let core = test_core_with_account().await;
+    let folder = core.create_at_path("/folder/").await.unwrap();
+
+    let result = core.export_rendered(folder.id, RenderFormat::Html).await;
+    assert_eq!(result.unwrap_err().kind, LbErrKind::FileNotDocument);
+}
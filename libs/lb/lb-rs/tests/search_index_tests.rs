@@ -0,0 +1,143 @@
+use lb_rs::Lb;
+use lb_rs::search::SearchFilter;
+use std::path::Path;
+use test_utils::*;
+
+#[tokio::test]
+async fn finds_documents_by_content() {
+    let core = test_core_with_account().await;
+    create(&core, "/story.md", b"it was a dark and stormy night").await;
+    create(&core, "/recipe.md", b"flour, water, salt").await;
+
+    let hits = core.search("stormy", None, 10).await.unwrap();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].filename, "story.md");
+    assert_eq!(hits[0].parent_path, "/");
+
+    let snippet = hits[0].snippet.clone().unwrap();
+    assert_eq!(snippet.text, "it was a dark and stormy night");
+    assert_eq!(&snippet.text[snippet.matches[0].clone()], "stormy");
+}
+
+#[tokio::test]
+async fn matches_word_forms() {
+    let core = test_core_with_account().await;
+    create(&core, "/log.md", b"She runs every morning").await;
+
+    let hits = core.search("Running", None, 10).await.unwrap();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(&hits[0].snippet.clone().unwrap().text, "She runs every morning");
+}
+
+#[tokio::test]
+async fn requires_every_word() {
+    let core = test_core_with_account().await;
+    create(&core, "/a.md", b"apples and pears").await;
+    create(&core, "/b.md", b"apples and plums").await;
+
+    let hits = core.search("apple pear", None, 10).await.unwrap();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].filename, "a.md");
+}
+
+#[tokio::test]
+async fn phrases() {
+    let core = test_core_with_account().await;
+    create(&core, "/story.md", b"it was a dark and stormy night").await;
+
+    assert!(
+        core.search("\"dark night\"", None, 10)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+    assert_eq!(
+        core.search("\"stormy night\"", None, 10)
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+}
+
+#[tokio::test]
+async fn ranks_by_relevance() {
+    let core = test_core_with_account().await;
+    create(&core, "/once.md", b"apple banana cherry date").await;
+    create(&core, "/often.md", b"apple apple apple banana").await;
+
+    let hits = core.search("apple", None, 10).await.unwrap();
+    assert_eq!(hits.len(), 2);
+    assert_eq!(hits[0].filename, "often.md");
+    assert!(hits[0].score > hits[1].score);
+
+    let hits = core.search("apple", None, 1).await.unwrap();
+    assert_eq!(hits.len(), 1);
+}
+
+#[tokio::test]
+async fn matches_names() {
+    let core = test_core_with_account().await;
+    create(&core, "/groceries.md", b"flour, water, salt").await;
+
+    let hits = core.search("grocery", None, 10).await.unwrap();
+    assert_eq!(hits.len(), 1);
+    assert!(hits[0].snippet.is_none());
+}
+
+#[tokio::test]
+async fn reflects_edits_and_deletes() {
+    let core = test_core_with_account().await;
+    create(&core, "/story.md", b"it was a dark and stormy night").await;
+    assert_eq!(core.search("stormy", None, 10).await.unwrap().len(), 1);
+
+    write_path(&core, "/story.md", b"it was a bright and sunny morning")
+        .await
+        .unwrap();
+    assert!(core.search("stormy", None, 10).await.unwrap().is_empty());
+    assert_eq!(core.search("sunny", None, 10).await.unwrap().len(), 1);
+
+    delete_path(&core, "/story.md").await.unwrap();
+    assert!(core.search("sunny", None, 10).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn filters_by_path() {
+    let core = test_core_with_account().await;
+    create(&core, "/work/notes.md", b"quarterly planning").await;
+    create(&core, "/home/notes.md", b"vacation planning").await;
+
+    let hits = core
+        .search("planning", Some(SearchFilter::Path("/work/".to_string())), 10)
+        .await
+        .unwrap();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].parent_path, "/work");
+}
+
+#[tokio::test]
+async fn finds_synced_documents() {
+    let core = test_core_with_account().await;
+    create(&core, "/story.md", b"it was a dark and stormy night").await;
+    core.sync().await.unwrap();
+
+    let other = test_core_from(&core).await;
+    let hits = other.search("stormy", None, 10).await.unwrap();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].filename, "story.md");
+}
+
+#[tokio::test]
+async fn index_encrypted_at_rest() {
+    let core = test_core_with_account().await;
+    create(&core, "/story.md", b"it was a dark and stormy night").await;
+    core.search("stormy", None, 10).await.unwrap();
+
+    let index = std::fs::read(Path::new(&core.config.writeable_path).join("search_index")).unwrap();
+    assert!(!index.windows(b"stormy".len()).any(|w| w == b"stormy"));
+}
+
+async fn create(core: &Lb, path: &str, content: &[u8]) {
+    let file = core.create_at_path(path).await.unwrap();
+    core.write_document(file.id, content).await.unwrap();
+}
@@ -0,0 +1,194 @@
+use lb_rs::Lb;
+use lb_rs::model::file::ShareMode;
+use lb_rs::search::SearchFilter::*;
+use lb_rs::search::query::parse;
+use lb_rs::search::tags::extract_tags;
+use test_utils::*;
+
+const JAN_2_2026: u64 = 1767312000000;
+
+#[test]
+fn parse_fields() {
+    let parsed = parse("recipe in:/notes type:md|svg");
+    assert_eq!(parsed.text, "recipe");
+    assert_eq!(
+        parsed.filter,
+        Some(And(vec![
+            Path("/notes".to_string()),
+            Or(vec![Type("md".to_string()), Type("svg".to_string())])
+        ]))
+    );
+}
+
+#[test]
+fn parse_operators() {
+    let parsed = parse("type:svg OR type:pdf NOT #draft");
+    assert_eq!(parsed.text, "");
+    assert_eq!(
+        parsed.filter,
+        Some(Or(vec![
+            Type("svg".to_string()),
+            And(vec![Type("pdf".to_string()), Not(Box::new(Tag("draft".to_string())))])
+        ]))
+    );
+
+    let parsed = parse("(type:svg OR type:pdf) by:Bob");
+    assert_eq!(
+        parsed.filter,
+        Some(And(vec![
+            Or(vec![Type("svg".to_string()), Type("pdf".to_string())]),
+            EditedBy("bob".to_string())
+        ]))
+    );
+}
+
+#[test]
+fn parse_modified() {
+    assert_eq!(
+        parse("modified:>2026-01-01").filter,
+        Some(Modified { after: Some(JAN_2_2026), before: None })
+    );
+    assert_eq!(
+        parse("modified:<2026-01-02").filter,
+        Some(Modified { after: None, before: Some(JAN_2_2026) })
+    );
+    assert!(matches!(
+        parse("modified:>7d").filter,
+        Some(Modified { after: Some(_), before: None })
+    ));
+}
+
+#[test]
+fn parse_unknown_fields_as_text() {
+    let parsed = parse("modified:soon colour:red \"dark night\"");
+    assert_eq!(parsed.text, "modified:soon colour:red \"dark night\"");
+    assert_eq!(parsed.filter, None);
+}
+
+#[test]
+fn tags_from_front_matter_and_text() {
+    let doc = "---\ntitle: Bread\ntags: [Recipes, baking]\n---\n\
+        # Sourdough\n\
+        a #favorite, see issue #12\n\
+        ```\n#notatag\n```\n";
+    assert_eq!(extract_tags(doc), vec!["baking", "favorite", "recipes"]);
+
+    let doc = "---\ntags:\n  - one\n  - two\n---\nbody";
+    assert_eq!(extract_tags(doc), vec!["one", "two"]);
+}
+
+#[tokio::test]
+async fn path_search_by_type() {
+    let core = test_core_with_account().await;
+    core.create_at_path("/notes/todo.md").await.unwrap();
+    core.create_at_path("/notes/sketch.svg").await.unwrap();
+
+    let mut searcher = core.path_searcher().await;
+    searcher.query("type:drawing");
+    let names: Vec<&str> = searcher
+        .results()
+        .iter()
+        .map(|r| r.filename.as_str())
+        .collect();
+    assert_eq!(names, vec!["sketch.svg"]);
+
+    searcher.query("type:folder");
+    let names: Vec<&str> = searcher
+        .results()
+        .iter()
+        .map(|r| r.filename.as_str())
+        .collect();
+    assert_eq!(names, vec!["notes"]);
+}
+
+#[tokio::test]
+async fn path_search_by_editor() {
+    let core = test_core_with_account().await;
+    let username = core.get_account().unwrap().username;
+    core.create_at_path("/sketch.svg").await.unwrap();
+
+    let mut searcher = core.path_searcher().await;
+    searcher.query(&format!("type:svg by:{username} modified:>1d"));
+    assert_eq!(searcher.results().len(), 1);
+
+    searcher.query("type:svg by:someone-else");
+    assert!(searcher.results().is_empty());
+
+    searcher.query("type:svg modified:<2020-01-01");
+    assert!(searcher.results().is_empty());
+}
+
+#[tokio::test]
+async fn search_by_tag() {
+    let core = test_core_with_account().await;
+    create(&core, "/bread.md", b"---\ntags: [recipes]\n---\nflour, water, salt").await;
+    create(&core, "/cake.md", b"flour, sugar, eggs #recipes #dessert").await;
+    create(&core, "/shopping.md", b"flour").await;
+
+    let tags = core.document_tags().await.unwrap();
+    assert_eq!(tags.len(), 2);
+
+    let hits = core.search("flour #recipes", None, 10).await.unwrap();
+    let mut names: Vec<&str> = hits.iter().map(|h| h.filename.as_str()).collect();
+    names.sort();
+    assert_eq!(names, vec!["bread.md", "cake.md"]);
+
+    let hits = core
+        .search("flour #recipes NOT #dessert", None, 10)
+        .await
+        .unwrap();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].filename, "bread.md");
+
+    let mut searcher = core.path_searcher().await;
+    searcher.query("#dessert");
+    assert_eq!(searcher.results().len(), 1);
+    assert_eq!(searcher.results()[0].filename, "cake.md");
+}
+
+#[tokio::test]
+async fn search_with_only_fields() {
+    let core = test_core_with_account().await;
+    create(&core, "/work/plan.md", b"quarterly planning").await;
+    create(&core, "/home/plan.md", b"vacation planning").await;
+
+    let hits = core.search("in:/work", None, 10).await.unwrap();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].parent_path, "/work");
+    assert!(hits[0].snippet.is_none());
+}
+
+#[tokio::test]
+async fn search_shared() {
+    let cores = [test_core_with_account().await, test_core_with_account().await];
+    let sharee = cores[1].get_account().unwrap().username;
+    let folder = cores[0].create_at_path("/shared/").await.unwrap();
+    cores[0].create_at_path("/shared/plan.md").await.unwrap();
+    cores[0].create_at_path("/private.md").await.unwrap();
+    cores[0]
+        .share_file(folder.id, &sharee, ShareMode::Read)
+        .await
+        .unwrap();
+
+    let mut searcher = cores[0].path_searcher().await;
+    searcher.query("shared:yes type:md");
+    let names: Vec<&str> = searcher
+        .results()
+        .iter()
+        .map(|r| r.filename.as_str())
+        .collect();
+    assert_eq!(names, vec!["plan.md"]);
+
+    searcher.query("shared:no type:md");
+    let names: Vec<&str> = searcher
+        .results()
+        .iter()
+        .map(|r| r.filename.as_str())
+        .collect();
+    assert_eq!(names, vec!["private.md"]);
+}
+
+async fn create(core: &Lb, path: &str, content: &[u8]) {
+    let file = core.create_at_path(path).await.unwrap();
+    core.write_document(file.id, content).await.unwrap();
+}
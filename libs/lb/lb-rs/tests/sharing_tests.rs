@@ -1,8 +1,14 @@
 use lb_rs::Lb;
+use lb_rs::io::network::ApiError;
 use lb_rs::model::ValidationFailure;
+use lb_rs::model::access_info::UserAccessMode;
+use lb_rs::model::api::{GetDocRequest, UpsertError, UpsertRequestV2};
+use lb_rs::model::crypto::AESKey;
 use lb_rs::model::errors::LbErrKind;
 use lb_rs::model::file::ShareMode;
-use lb_rs::model::file_metadata::FileType;
+use lb_rs::model::file_metadata::{FileDiff, FileType};
+use lb_rs::model::symkey;
+use lb_rs::model::tree_like::TreeLike;
 use test_utils::*;
 use uuid::Uuid;
 
@@ -2561,3 +2567,360 @@ async fn populate_last_modified_by() {
     let doc = c2.get_file_by_id(doc.id).await.unwrap();
     assert_eq!(doc.last_modified_by, a2.username);
 }
+
+#[tokio::test]
+async fn revoke_share() {
+    let cores = [test_core_with_account().await, test_core_with_account().await];
+    let accounts = cores
+        .iter()
+        .map(|core| core.get_account().unwrap())
+        .collect::<Vec<_>>();
+
+    let folder = cores[0].create_at_path("/folder/").await.unwrap();
+    cores[0].create_at_path("/folder/document").await.unwrap();
+    cores[0]
+        .share_file(folder.id, &accounts[1].username, ShareMode::Read)
+        .await
+        .unwrap();
+    cores[0].sync().await.unwrap();
+    cores[1].sync().await.unwrap();
+    assert_eq!(cores[1].get_pending_shares().await.unwrap().len(), 1);
+
+    cores[0]
+        .revoke_share(folder.id, &accounts[1].username, false)
+        .await
+        .unwrap();
+    cores[0].sync().await.unwrap();
+    cores[1].sync().await.unwrap();
+
+    assert!(cores[1].get_pending_shares().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn revoke_share_nonexistent() {
+    let cores = [test_core_with_account().await, test_core_with_account().await];
+    let accounts = cores
+        .iter()
+        .map(|core| core.get_account().unwrap())
+        .collect::<Vec<_>>();
+
+    let folder = cores[0].create_at_path("/folder/").await.unwrap();
+    for rotate_keys in [false, true] {
+        let result = cores[0]
+            .revoke_share(folder.id, &accounts[1].username, rotate_keys)
+            .await;
+        assert_matches!(result.unwrap_err().kind, LbErrKind::ShareNonexistent);
+    }
+}
+
+#[tokio::test]
+async fn revoke_share_rotate_keys() {
+    let cores = [
+        test_core_with_account().await,
+        test_core_with_account().await,
+        test_core_with_account().await,
+    ];
+    let accounts = cores
+        .iter()
+        .map(|core| core.get_account().unwrap())
+        .collect::<Vec<_>>();
+
+    let folder = cores[0].create_at_path("/folder/").await.unwrap();
+    let document = cores[0]
+        .create_at_path("/folder/nested/document.md")
+        .await
+        .unwrap();
+    cores[0]
+        .write_document(document.id, b"before revocation")
+        .await
+        .unwrap();
+    for sharee in &accounts[1..] {
+        cores[0]
+            .share_file(folder.id, &sharee.username, ShareMode::Read)
+            .await
+            .unwrap();
+    }
+    cores[0].sync().await.unwrap();
+    cores[1].sync().await.unwrap();
+    cores[2].sync().await.unwrap();
+
+    let revoked_key = {
+        let lb = local(&cores[1]);
+        let tx = lb.ro_tx().await;
+        let db = tx.db();
+        let mut tree = (&db.base_metadata).to_staged(&db.local_metadata).to_lazy();
+        tree.decrypt_key(&document.id, &lb.keychain).unwrap()
+    };
+
+    cores[0]
+        .revoke_share(folder.id, &accounts[1].username, true)
+        .await
+        .unwrap();
+    cores[0].sync().await.unwrap();
+    cores[0]
+        .write_document(document.id, b"after revocation")
+        .await
+        .unwrap();
+    cores[0].sync().await.unwrap();
+
+    // the key the revoked user had doesn't decrypt what's written now
+    let (hmac, _) = cores[0]
+        .read_document_with_hmac(document.id, false)
+        .await
+        .unwrap();
+    let content = local(&cores[0])
+        .client
+        .request(&accounts[0], GetDocRequest { id: document.id, hmac: hmac.unwrap() })
+        .await
+        .unwrap()
+        .content;
+    assert!(symkey::decrypt(&revoked_key, &content).is_err());
+
+    cores[1].sync().await.unwrap();
+    cores[2].sync().await.unwrap();
+    assert!(cores[1].get_pending_shares().await.unwrap().is_empty());
+    assert_eq!(cores[2].read_document(document.id, false).await.unwrap(), b"after revocation");
+    assert_eq!(cores[2].get_file_by_id(document.id).await.unwrap().name, "document.md");
+
+    let other_device = test_core_from(&cores[0]).await;
+    assert_eq!(
+        other_device
+            .read_document(document.id, false)
+            .await
+            .unwrap(),
+        b"after revocation"
+    );
+    for core in [&cores[0], &cores[2], &other_device] {
+        core.test_repo_integrity(true).await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn revoke_share_rotate_keys_concurrent_sharee_edit() {
+    let cores = [
+        test_core_with_account().await,
+        test_core_with_account().await,
+        test_core_with_account().await,
+    ];
+    let accounts = cores
+        .iter()
+        .map(|core| core.get_account().unwrap())
+        .collect::<Vec<_>>();
+
+    let folder = cores[0].create_at_path("/folder/").await.unwrap();
+    let document = cores[0]
+        .create_at_path("/folder/document.md")
+        .await
+        .unwrap();
+    cores[0]
+        .write_document(document.id, b"original")
+        .await
+        .unwrap();
+    for sharee in &accounts[1..] {
+        cores[0]
+            .share_file(folder.id, &sharee.username, ShareMode::Write)
+            .await
+            .unwrap();
+    }
+    cores[0].sync().await.unwrap();
+    cores[1].sync().await.unwrap();
+    cores[2].sync().await.unwrap();
+
+    cores[2]
+        .write_document(document.id, b"edited by sharee")
+        .await
+        .unwrap();
+    let created = cores[2]
+        .create_file("created by sharee.md", &folder.id, FileType::Document)
+        .await
+        .unwrap();
+    cores[2]
+        .write_document(created.id, b"new document")
+        .await
+        .unwrap();
+
+    cores[0]
+        .revoke_share(folder.id, &accounts[1].username, true)
+        .await
+        .unwrap();
+    cores[0].sync().await.unwrap();
+    cores[2].sync().await.unwrap();
+    cores[0].sync().await.unwrap();
+
+    for core in [&cores[0], &cores[2]] {
+        assert_eq!(core.read_document(document.id, false).await.unwrap(), b"edited by sharee");
+        assert_eq!(core.read_document(created.id, false).await.unwrap(), b"new document");
+        core.test_repo_integrity(true).await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn revoke_share_rotate_keys_with_deleted_files() {
+    let cores = [test_core_with_account().await, test_core_with_account().await];
+    let accounts = cores
+        .iter()
+        .map(|core| core.get_account().unwrap())
+        .collect::<Vec<_>>();
+
+    let folder = cores[0].create_at_path("/folder/").await.unwrap();
+    let deleted = cores[0]
+        .create_at_path("/folder/deleted/document.md")
+        .await
+        .unwrap();
+    cores[0]
+        .write_document(deleted.id, b"deleted content")
+        .await
+        .unwrap();
+    cores[0]
+        .share_file(folder.id, &accounts[1].username, ShareMode::Read)
+        .await
+        .unwrap();
+    cores[0].sync().await.unwrap();
+    let deleted_folder = cores[0].get_by_path("/folder/deleted").await.unwrap();
+    cores[0].delete(&deleted_folder.id).await.unwrap();
+    cores[0].sync().await.unwrap();
+
+    cores[0]
+        .revoke_share(folder.id, &accounts[1].username, true)
+        .await
+        .unwrap();
+    cores[0].sync().await.unwrap();
+
+    let other_device = test_core_from(&cores[0]).await;
+    for core in [&cores[0], &other_device] {
+        core.test_repo_integrity(true).await.unwrap();
+        assert::all_paths(core, &["/", "/folder/"]).await;
+    }
+}
+
+/// key of `id` as of the last sync
+async fn synced_key(core: &Lb, id: Uuid) -> AESKey {
+    let lb = local(core);
+    let tx = lb.ro_tx().await;
+    (&tx.db().base_metadata)
+        .to_lazy()
+        .decrypt_key(&id, &lb.keychain)
+        .unwrap()
+}
+
+#[tokio::test]
+async fn revoke_share_rotate_keys_synced() {
+    let cores = [test_core_with_account().await, test_core_with_account().await];
+    let accounts = cores
+        .iter()
+        .map(|core| core.get_account().unwrap())
+        .collect::<Vec<_>>();
+
+    let folder = cores[0].create_at_path("/folder/").await.unwrap();
+    cores[0]
+        .share_file(folder.id, &accounts[1].username, ShareMode::Read)
+        .await
+        .unwrap();
+    cores[0].sync().await.unwrap();
+    let revoked_key = synced_key(&cores[0], folder.id).await;
+
+    cores[0]
+        .revoke_share(folder.id, &accounts[1].username, true)
+        .await
+        .unwrap();
+    cores[0].sync().await.unwrap();
+    let rotated_key = synced_key(&cores[0], folder.id).await;
+    assert_ne!(rotated_key, revoked_key);
+
+    let other_device = test_core_from(&cores[0]).await;
+    assert_eq!(synced_key(&other_device, folder.id).await, rotated_key);
+    for core in [&cores[0], &other_device] {
+        core.test_repo_integrity(true).await.unwrap();
+        assert::all_paths(core, &["/", "/folder/"]).await;
+    }
+}
+
+#[tokio::test]
+async fn revoke_share_rotate_keys_not_owner() {
+    let cores = [
+        test_core_with_account().await,
+        test_core_with_account().await,
+        test_core_with_account().await,
+    ];
+    let accounts = cores
+        .iter()
+        .map(|core| core.get_account().unwrap())
+        .collect::<Vec<_>>();
+
+    let folder = cores[0].create_at_path("/folder/").await.unwrap();
+    cores[0]
+        .share_file(folder.id, &accounts[1].username, ShareMode::Write)
+        .await
+        .unwrap();
+    cores[0].sync().await.unwrap();
+    cores[1].sync().await.unwrap();
+    cores[1]
+        .share_file(folder.id, &accounts[2].username, ShareMode::Read)
+        .await
+        .unwrap();
+    cores[1].sync().await.unwrap();
+
+    let result = cores[1]
+        .revoke_share(folder.id, &accounts[2].username, true)
+        .await;
+    assert_matches!(result.unwrap_err().kind, LbErrKind::InsufficientPermission);
+}
+
+#[tokio::test]
+async fn access_mode_is_inherited() {
+    let cores = [test_core_with_account().await, test_core_with_account().await];
+    let accounts = cores
+        .iter()
+        .map(|core| core.get_account().unwrap())
+        .collect::<Vec<_>>();
+
+    let folder = cores[0].create_at_path("/folder/").await.unwrap();
+    let document = cores[0].create_at_path("/folder/document").await.unwrap();
+    cores[0]
+        .share_file(folder.id, &accounts[1].username, ShareMode::Read)
+        .await
+        .unwrap();
+    cores[0].sync().await.unwrap();
+    cores[1].sync().await.unwrap();
+
+    assert_eq!(cores[0].get_access_mode(document.id).await.unwrap(), Some(UserAccessMode::Owner));
+    assert_eq!(cores[1].get_access_mode(document.id).await.unwrap(), Some(UserAccessMode::Read));
+}
+
+#[tokio::test]
+async fn writer_cannot_rotate_keys() {
+    let cores = [test_core_with_account().await, test_core_with_account().await];
+    let accounts = cores
+        .iter()
+        .map(|core| core.get_account().unwrap())
+        .collect::<Vec<_>>();
+
+    let folder = cores[0].create_at_path("/folder/").await.unwrap();
+    let document = cores[0].create_at_path("/folder/document").await.unwrap();
+    cores[0]
+        .share_file(folder.id, &accounts[1].username, ShareMode::Write)
+        .await
+        .unwrap();
+    cores[0].sync().await.unwrap();
+    cores[1].sync().await.unwrap();
+
+    // the writer re-encrypts the document under a key of their own choosing, in place
+    let (old, new) = {
+        let lb = local(&cores[1]);
+        let tx = lb.ro_tx().await;
+        let db = tx.db();
+        let mut tree = (&db.base_metadata).to_staged(&db.local_metadata).to_lazy();
+        let folder_key = tree.decrypt_key(&folder.id, &lb.keychain).unwrap();
+        let old = db.base_metadata.get().get(&document.id).unwrap().clone();
+        let mut new = old.clone();
+        new.timestamped_value
+            .value
+            .set_folder_access_keys(symkey::encrypt(&folder_key, &symkey::generate_key()).unwrap());
+        (old, new)
+    };
+    let result = local(&cores[1])
+        .client
+        .request(&accounts[1], UpsertRequestV2 { updates: vec![FileDiff::edit(old, new)] })
+        .await;
+    assert_matches!(result, Err(ApiError::<UpsertError>::Endpoint(UpsertError::NotPermissioned)));
+}
@@ -0,0 +1,153 @@
+use lb_rs::Lb;
+use lb_rs::model::chunks::MAX_CHUNK_SIZE;
+use lb_rs::model::errors::LbErrKind;
+use lb_rs::model::file_like::FileLike;
+use lb_rs::service::streams::DocumentContent;
+use lb_rs::service::sync_policy::SyncPolicy;
+use test_utils::*;
+use uuid::Uuid;
+
+async fn cached(core: &Lb, id: Uuid) -> bool {
+    let lb = local(core);
+    let tx = lb.ro_tx().await;
+    let hmac = tx
+        .db()
+        .base_metadata
+        .get()
+        .get(&id)
+        .unwrap()
+        .document_hmac()
+        .copied();
+    lb.docs.exists(id, hmac)
+}
+
+#[tokio::test]
+async fn sync_policy_on_document() {
+    let core = test_core_with_account().await;
+    let doc = core.create_at_path("doc.md").await.unwrap();
+
+    assert_eq!(
+        core.set_sync_policy(doc.id, SyncPolicy::OnDemand)
+            .await
+            .unwrap_err()
+            .kind,
+        LbErrKind::FileNotFolder
+    );
+}
+
+#[tokio::test]
+async fn sync_policy_inherited() {
+    let core = test_core_with_account().await;
+    let doc = core.create_at_path("assets/pdfs/doc.md").await.unwrap();
+    let assets = core.get_by_path("assets").await.unwrap();
+    assert_eq!(core.get_sync_policy(doc.id).await.unwrap(), None);
+
+    core.set_sync_policy(assets.id, SyncPolicy::OnDemand)
+        .await
+        .unwrap();
+    core.set_sync_policy(doc.parent, SyncPolicy::AlwaysLocal)
+        .await
+        .unwrap();
+    assert_eq!(core.get_sync_policy(doc.id).await.unwrap(), Some(SyncPolicy::AlwaysLocal));
+
+    core.clear_sync_policy(doc.parent).await.unwrap();
+    assert_eq!(core.get_sync_policy(doc.id).await.unwrap(), Some(SyncPolicy::OnDemand));
+}
+
+#[tokio::test]
+async fn always_local_pulls() {
+    let core = test_core_with_account().await;
+    let doc = core.create_at_path("assets/doc.md").await.unwrap();
+    core.write_document(doc.id, b"contents").await.unwrap();
+    core.sync().await.unwrap();
+
+    let other = test_core_from(&core).await;
+    assert!(!cached(&other, doc.id).await);
+
+    other
+        .set_sync_policy(doc.parent, SyncPolicy::AlwaysLocal)
+        .await
+        .unwrap();
+    other.sync().await.unwrap();
+    assert!(cached(&other, doc.id).await);
+}
+
+#[tokio::test]
+async fn on_demand_evicted_over_budget() {
+    let core = test_core_with_account().await;
+    let doc = core.create_at_path("assets/doc.md").await.unwrap();
+    core.write_document(doc.id, b"contents").await.unwrap();
+    core.sync().await.unwrap();
+    assert!(cached(&core, doc.id).await);
+
+    core.set_sync_policy(doc.parent, SyncPolicy::OnDemand)
+        .await
+        .unwrap();
+    core.set_disk_budget(Some(0)).await.unwrap();
+    core.sync().await.unwrap();
+    assert!(!cached(&core, doc.id).await);
+
+    // evicted documents are fetched again when they're read
+    assert_eq!(core.read_document(doc.id, false).await.unwrap(), b"contents");
+}
+
+#[tokio::test]
+async fn budget_change_evicts_without_sync() {
+    let core = test_core_with_account().await;
+    let doc = core.create_at_path("assets/doc.md").await.unwrap();
+    core.write_document(doc.id, b"contents").await.unwrap();
+    core.sync().await.unwrap();
+
+    core.set_disk_budget(Some(0)).await.unwrap();
+    assert!(cached(&core, doc.id).await);
+
+    core.set_sync_policy(doc.parent, SyncPolicy::OnDemand)
+        .await
+        .unwrap();
+    assert!(!cached(&core, doc.id).await);
+}
+
+#[tokio::test]
+async fn on_demand_within_budget() {
+    let core = test_core_with_account().await;
+    let doc = core.create_at_path("assets/doc.md").await.unwrap();
+    core.write_document(doc.id, b"contents").await.unwrap();
+    core.set_sync_policy(doc.parent, SyncPolicy::OnDemand)
+        .await
+        .unwrap();
+    core.set_disk_budget(Some(1024 * 1024)).await.unwrap();
+    core.sync().await.unwrap();
+
+    assert!(cached(&core, doc.id).await);
+}
+
+#[tokio::test]
+async fn on_demand_evicts_chunks() {
+    let core = test_core_with_account().await;
+    let doc = core.create_at_path("assets/doc.md").await.unwrap();
+    let content = vec![7; 2 * MAX_CHUNK_SIZE + 1];
+    let mut writer = core.open_writer(doc.id).await.unwrap();
+    writer.write(&content).await.unwrap();
+    writer.finish().await.unwrap();
+    core.sync().await.unwrap();
+
+    let DocumentContent::Chunked(manifest) = local(&core)
+        .read_document_content(doc.id, false)
+        .await
+        .unwrap()
+    else {
+        panic!("document wasn't chunked");
+    };
+    let chunk = manifest.chunks[0].hmac;
+    assert!(local(&core).docs.chunk_exists(doc.id, chunk));
+
+    core.set_sync_policy(doc.parent, SyncPolicy::OnDemand)
+        .await
+        .unwrap();
+    core.set_disk_budget(Some(0)).await.unwrap();
+    core.sync().await.unwrap();
+    assert!(!cached(&core, doc.id).await);
+    assert!(!local(&core).docs.chunk_exists(doc.id, chunk));
+
+    assert_eq!(core.read_document(doc.id, false).await.unwrap(), content);
+}
@@ -0,0 +1,92 @@
+use lb_rs::model::errors::LbErrKind;
+use lb_rs::model::file_metadata::FileType;
+use test_utils::*;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn deleted_file_in_trash() {
+    let core = test_core_with_account().await;
+    let doc = core.create_at_path("folder/doc.md").await.unwrap();
+    core.sync().await.unwrap();
+    core.delete(&doc.parent).await.unwrap();
+
+    // only the explicitly deleted folder is listed, not its contents
+    let trash = core.list_trash().await.unwrap();
+    assert_eq!(trash.len(), 1);
+    assert_eq!(trash[0].id, doc.parent);
+}
+
+#[tokio::test]
+async fn restore_synced_delete() {
+    let core = test_core_with_account().await;
+    let doc = core.create_at_path("folder/doc.md").await.unwrap();
+    core.write_document(doc.id, b"contents").await.unwrap();
+    core.sync().await.unwrap();
+    core.delete(&doc.parent).await.unwrap();
+    core.sync().await.unwrap();
+
+    core.restore(&doc.parent).await.unwrap();
+    assert!(core.list_trash().await.unwrap().is_empty());
+    assert_eq!(core.read_document(doc.id, false).await.unwrap(), b"contents");
+    core.sync().await.unwrap();
+
+    let other = test_core_from(&core).await;
+    assert_eq!(other.get_by_path("folder/doc.md").await.unwrap().id, doc.id);
+    assert_eq!(other.read_document(doc.id, false).await.unwrap(), b"contents");
+}
+
+#[tokio::test]
+async fn restore_into_deleted_parent() {
+    let core = test_core_with_account().await;
+    let root = core.root().await.unwrap();
+    let doc = core.create_at_path("folder/doc.md").await.unwrap();
+    core.sync().await.unwrap();
+    core.delete(&doc.id).await.unwrap();
+    core.delete(&doc.parent).await.unwrap();
+    core.sync().await.unwrap();
+
+    core.restore(&doc.id).await.unwrap();
+    assert_eq!(core.get_file_by_id(doc.id).await.unwrap().parent, root.id);
+    core.sync().await.unwrap();
+
+    let other = test_core_from(&core).await;
+    assert_eq!(other.get_by_path("doc.md").await.unwrap().id, doc.id);
+}
+
+#[tokio::test]
+async fn restore_not_deleted() {
+    let core = test_core_with_account().await;
+    let root = core.root().await.unwrap();
+    let doc = core
+        .create_file(&random_name(), &root.id, FileType::Document)
+        .await
+        .unwrap();
+
+    core.restore(&doc.id).await.unwrap();
+    assert_eq!(core.get_file_by_id(doc.id).await.unwrap(), doc);
+}
+
+#[tokio::test]
+async fn restore_nonexistent() {
+    let core = test_core_with_account().await;
+
+    assert_eq!(core.restore(&Uuid::new_v4()).await.unwrap_err().kind, LbErrKind::FileNonexistent);
+}
+
+#[tokio::test]
+async fn empty_trash() {
+    let core = test_core_with_account().await;
+    let doc = core.create_at_path("doc.md").await.unwrap();
+    core.write_document(doc.id, b"contents").await.unwrap();
+    core.sync().await.unwrap();
+    let other = test_core_from(&core).await;
+    core.delete(&doc.id).await.unwrap();
+    core.sync().await.unwrap();
+
+    core.empty_trash().await.unwrap();
+    assert!(core.list_trash().await.unwrap().is_empty());
+    assert_eq!(core.restore(&doc.id).await.unwrap_err().kind, LbErrKind::FileNonexistent);
+
+    other.sync().await.unwrap();
+    assert!(other.list_trash().await.unwrap().is_empty());
+}
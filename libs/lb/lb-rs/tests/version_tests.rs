@@ -0,0 +1,114 @@
+use lb_rs::model::api::METADATA_FEE;
+use lb_rs::model::errors::LbErrKind;
+use lb_rs::model::file_metadata::FileType;
+use test_utils::*;
+
+#[tokio::test]
+async fn no_versions_before_overwrite() {
+    let core = test_core_with_account().await;
+    let root = core.root().await.unwrap();
+    let doc = core
+        .create_file(&random_name(), &root.id, FileType::Document)
+        .await
+        .unwrap();
+    core.write_document(doc.id, b"one").await.unwrap();
+    core.sync().await.unwrap();
+
+    assert!(core.list_versions(doc.id).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn overwrite_retains_version() {
+    let core = test_core_with_account().await;
+    let root = core.root().await.unwrap();
+    let doc = core
+        .create_file(&random_name(), &root.id, FileType::Document)
+        .await
+        .unwrap();
+    core.write_document(doc.id, b"one").await.unwrap();
+    core.sync().await.unwrap();
+    core.write_document(doc.id, b"two").await.unwrap();
+    core.sync().await.unwrap();
+    core.write_document(doc.id, b"three").await.unwrap();
+    core.sync().await.unwrap();
+
+    let versions = core.list_versions(doc.id).await.unwrap();
+    assert_eq!(versions.len(), 2);
+    assert_eq!(core.read_version(doc.id, versions[0].hmac).await.unwrap(), b"two");
+    assert_eq!(core.read_version(doc.id, versions[1].hmac).await.unwrap(), b"one");
+}
+
+#[tokio::test]
+async fn restore_version() {
+    let core = test_core_with_account().await;
+    let root = core.root().await.unwrap();
+    let doc = core
+        .create_file(&random_name(), &root.id, FileType::Document)
+        .await
+        .unwrap();
+    core.write_document(doc.id, b"original").await.unwrap();
+    core.sync().await.unwrap();
+    core.write_document(doc.id, b"clobbered").await.unwrap();
+    core.sync().await.unwrap();
+
+    let versions = core.list_versions(doc.id).await.unwrap();
+    core.restore_version(doc.id, versions[0].hmac)
+        .await
+        .unwrap();
+    assert_eq!(core.read_document(doc.id, false).await.unwrap(), b"original");
+    core.sync().await.unwrap();
+
+    // the restored contents are current again, the clobbered contents are now the only version
+    let versions = core.list_versions(doc.id).await.unwrap();
+    assert_eq!(versions.len(), 1);
+    assert_eq!(core.read_version(doc.id, versions[0].hmac).await.unwrap(), b"clobbered");
+
+    let other = test_core_from(&core).await;
+    assert_eq!(other.read_document(doc.id, false).await.unwrap(), b"original");
+}
+
+#[tokio::test]
+async fn versions_count_against_usage() {
+    let core = test_core_with_account().await;
+    let root = core.root().await.unwrap();
+    let doc = core
+        .create_file(&random_name(), &root.id, FileType::Document)
+        .await
+        .unwrap();
+    core.write_document(doc.id, b"one").await.unwrap();
+    core.sync().await.unwrap();
+    core.write_document(doc.id, b"two").await.unwrap();
+    core.sync().await.unwrap();
+
+    let version_size = core.list_versions(doc.id).await.unwrap()[0].size_bytes;
+    let usage = core.get_usage().await.unwrap();
+    let doc_usage = usage.usages.iter().find(|u| u.file_id == doc.id).unwrap();
+    assert!(doc_usage.size_bytes > version_size + METADATA_FEE);
+}
+
+#[tokio::test]
+async fn versions_of_folder() {
+    let core = test_core_with_account().await;
+    let root = core.root().await.unwrap();
+    let folder = core
+        .create_file(&random_name(), &root.id, FileType::Folder)
+        .await
+        .unwrap();
+
+    assert_eq!(core.list_versions(folder.id).await.unwrap_err().kind, LbErrKind::FileNotDocument);
+}
+
+#[tokio::test]
+async fn versions_of_deleted_document() {
+    let core = test_core_with_account().await;
+    let root = core.root().await.unwrap();
+    let doc = core
+        .create_file(&random_name(), &root.id, FileType::Document)
+        .await
+        .unwrap();
+    core.write_document(doc.id, b"one").await.unwrap();
+    core.sync().await.unwrap();
+    core.delete(&doc.id).await.unwrap();
+
+    assert_eq!(core.list_versions(doc.id).await.unwrap_err().kind, LbErrKind::FileNonexistent);
+}
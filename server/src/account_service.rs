@@ -17,7 +17,7 @@ use lb_rs::model::api::{
     AdminListUsersResponse, DeleteAccountError, DeleteAccountRequest, FileUsage, GetPublicKeyError,
     GetPublicKeyRequest, GetPublicKeyResponse, GetUsageError, GetUsageRequest, GetUsageResponse,
     GetUsernameError, GetUsernameRequest, GetUsernameResponse, METADATA_FEE, NewAccountError,
-    NewAccountRequestV2, NewAccountResponse, PaymentPlatform,
+    NewAccountRequestV2, NewAccountRequestV3, NewAccountResponse, PaymentPlatform,
 };
 use lb_rs::model::clock::get_time;
 use lb_rs::model::file_like::FileLike;
@@ -27,10 +27,11 @@ use lb_rs::model::server_tree::ServerTree;
 use lb_rs::model::tree_like::TreeLike;
 use lb_rs::model::usage::bytes_to_human;
 use libsecp256k1::PublicKey;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
 use std::ops::DerefMut;
 use tracing::warn;
+use uuid::Uuid;
 
 impl<S, A, G, D> ServerState<S, A, G, D>
 where
@@ -39,11 +40,20 @@ where
     G: GooglePlayClient,
     D: DocumentService,
 {
+    /// For clients from before invite codes, which never have one.
+    pub async fn new_account_v2(
+        &self, context: RequestContext<NewAccountRequestV2>,
+    ) -> Result<NewAccountResponse, ServerError<NewAccountError>> {
+        let RequestContext { request, public_key, ip, session } = context;
+        self.new_account_v3(RequestContext { request: request.into(), public_key, ip, session })
+            .await
+    }
+
     /// Create a new account given a username, public_key, and root folder.
     /// Checks that username is valid, and that username, public_key and root_folder are new.
     /// Inserts all of these values into their respective keys along with the default free account tier size
-    pub async fn new_account_v2(
-        &self, mut context: RequestContext<NewAccountRequestV2>,
+    pub async fn new_account_v3(
+        &self, mut context: RequestContext<NewAccountRequestV3>,
     ) -> Result<NewAccountResponse, ServerError<NewAccountError>> {
         context.request.username = context.request.username.to_lowercase();
         let request = &context.request;
@@ -83,6 +93,18 @@ where
             return Err(ClientError(FileIdTaken));
         }
 
+        let invite = request
+            .invite_code
+            .as_deref()
+            .and_then(|code| Self::usable_invite(&db, code, now));
+        let configured_invite = request
+            .invite_code
+            .as_ref()
+            .is_some_and(|code| self.config.admin.invite_codes.contains(code));
+        if self.config.features.invite_only && invite.is_none() && !configured_invite {
+            return Err(ClientError(NewAccountError::InviteRequired));
+        }
+
         if self.config.features.new_account_rate_limit {
             if let Some(ip) = context.ip {
                 self.did_create_account(ip.ip()).await;
@@ -103,6 +125,13 @@ where
         db.shared_files.create_key(owner)?;
         db.file_children.create_key(*root.id())?;
         db.metas.insert(*root.id(), root.clone())?;
+        // the root is the account's first change, for requests waiting on updates
+        db.last_changed.insert(owner, root.version)?;
+        if let Some(mut invite) = invite {
+            invite.uses += 1;
+            db.invited_by.insert(owner, invite.created_by)?;
+            db.invites.insert(invite.code.clone(), invite)?;
+        }
 
         handle.drop_safely()?;
 
@@ -156,6 +185,14 @@ where
 
         let cap = Self::get_cap(db, &context.public_key)?;
         let owner = Owner(context.public_key);
+        let document_versions = db.document_versions.get();
+        let chunks = db.chunks.get();
+        let mut links_size: HashMap<Uuid, u64> = HashMap::new();
+        for link in db.public_links.get().values() {
+            if link.owner == owner {
+                *links_size.entry(link.file_id).or_default() += link.size_bytes;
+            }
+        }
 
         let mut tree = ServerTree::new(
             owner,
@@ -174,11 +211,25 @@ where
                     return None;
                 }
                 let file = tree.find(&file_id).ok()?;
+                let links_size = links_size.get(&file_id).copied().unwrap_or_default();
                 if file.owner() != owner {
-                    return None;
+                    // links to files shared with this account are charged to it too
+                    return (links_size > 0)
+                        .then_some(FileUsage { file_id, size_bytes: links_size });
                 }
                 let file_size = file.doc_size().unwrap_or(0) as u64;
-                Some(FileUsage { file_id, size_bytes: file_size + METADATA_FEE })
+                let versions_size: u64 = document_versions
+                    .get(&file_id)
+                    .map(|versions| versions.iter().map(|version| version.size_bytes).sum())
+                    .unwrap_or_default();
+                let chunks_size: u64 = chunks
+                    .get(&file_id)
+                    .map(|chunks| chunks.values().sum())
+                    .unwrap_or_default();
+                Some(FileUsage {
+                    file_id,
+                    size_bytes: file_size + versions_size + chunks_size + links_size + METADATA_FEE,
+                })
             })
             .collect();
 
@@ -428,6 +479,10 @@ where
                         }
                         db.metas.remove(&id)?;
                         db.file_children.clear_key(&id)?;
+                        if let Some(versions) = db.document_versions.remove(&id)? {
+                            docs_to_delete
+                                .extend(versions.into_iter().map(|version| (id, version.hmac)));
+                        }
                     }
                 }
             }
@@ -2,8 +2,8 @@ use crate::ServerError;
 use crate::config::Config;
 use google_androidpublisher3::api::SubscriptionPurchase;
 use lb_rs::model::api::{
-    AppStoreAccountState, FREE_TIER_USAGE_SIZE, GooglePlayAccountState, PREMIUM_TIER_USAGE_SIZE,
-    StripeAccountState, UnixTimeMillis, UpgradeAccountGooglePlayError,
+    AppStoreAccountState, DocumentVersion, FREE_TIER_USAGE_SIZE, GooglePlayAccountState,
+    PREMIUM_TIER_USAGE_SIZE, StripeAccountState, UnixTimeMillis, UpgradeAccountGooglePlayError,
 };
 use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
@@ -60,6 +60,40 @@ impl SubscriptionProfile {
     pub fn is_premium(&self) -> bool {
         self.data_cap() == PREMIUM_TIER_USAGE_SIZE
     }
+
+    pub fn version_retention(&self) -> VersionRetention {
+        if self.is_premium() {
+            VersionRetention { max_versions: 100, max_age: 1000 * 60 * 60 * 24 * 30 }
+        } else {
+            VersionRetention { max_versions: 10, max_age: 1000 * 60 * 60 * 24 * 7 }
+        }
+    }
+}
+
+/// How many prior versions of each document are kept, and for how long (in millis). A version is
+/// dropped as soon as either limit is exceeded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VersionRetention {
+    pub max_versions: usize,
+    pub max_age: u64,
+}
+
+impl VersionRetention {
+    /// splits `versions` (newest first) into those to keep and those to expire
+    pub fn split(
+        &self, mut versions: Vec<DocumentVersion>, now: UnixTimeMillis,
+    ) -> (Vec<DocumentVersion>, Vec<DocumentVersion>) {
+        let mut expired = if versions.len() > self.max_versions {
+            versions.split_off(self.max_versions)
+        } else {
+            vec![]
+        };
+        let (keep, too_old): (Vec<_>, Vec<_>) = versions
+            .into_iter()
+            .partition(|version| now.saturating_sub(version.replaced_at) <= self.max_age);
+        expired.extend(too_old);
+        (keep, expired)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -0,0 +1,101 @@
+use async_trait::async_trait;
+use google_androidpublisher3::api::SubscriptionPurchase;
+use lb_rs::model::api::UpgradeAccountAppStoreError;
+
+use crate::ServerError;
+use crate::config::{AppleConfig, Config};
+
+use super::app_store_client::AppStoreClient;
+use super::app_store_model::{LastTransactionItem, TransactionInfo};
+use super::google_play_client::{GooglePlayClient, SimpleGCPError};
+use super::stripe_client::StripeClient;
+use super::stripe_error::SimplifiedStripeError;
+
+/// Billing for servers without payment providers, like self-hosted ones. Nothing can be bought,
+/// and there's nothing to cancel; accounts get their tiers from admins instead.
+#[derive(Clone)]
+pub struct Nop {}
+
+const BILLING_DISABLED: &str = "this server does not take payments";
+
+#[async_trait]
+impl StripeClient for Nop {
+    async fn create_customer(
+        &self, _customer_name: String, _payment_method_id: stripe::PaymentMethodId,
+    ) -> Result<stripe::Customer, SimplifiedStripeError> {
+        Err(SimplifiedStripeError::Other(BILLING_DISABLED.to_string()))
+    }
+
+    async fn create_payment_method(
+        &self, _card_number: &str, _exp_month: i32, _exp_year: i32, _cvc: &str,
+    ) -> Result<stripe::PaymentMethod, SimplifiedStripeError> {
+        Err(SimplifiedStripeError::Other(BILLING_DISABLED.to_string()))
+    }
+
+    async fn create_setup_intent(
+        &self, _customer_id: stripe::CustomerId, _payment_method_id: stripe::PaymentMethodId,
+    ) -> Result<stripe::SetupIntent, SimplifiedStripeError> {
+        Err(SimplifiedStripeError::Other(BILLING_DISABLED.to_string()))
+    }
+
+    async fn create_subscription(
+        &self, _customer_id: stripe::CustomerId, _payment_method_id: &str, _price_id: &str,
+    ) -> Result<stripe::Subscription, SimplifiedStripeError> {
+        Err(SimplifiedStripeError::Other(BILLING_DISABLED.to_string()))
+    }
+
+    async fn detach_payment_method_from_customer(
+        &self, _payment_method_id: &stripe::PaymentMethodId,
+    ) -> Result<(), SimplifiedStripeError> {
+        Ok(())
+    }
+
+    async fn cancel_subscription(
+        &self, _subscription_id: &stripe::SubscriptionId,
+    ) -> Result<(), SimplifiedStripeError> {
+        Ok(())
+    }
+
+    async fn get_subscription(
+        &self, _subscription_id: &stripe::SubscriptionId,
+    ) -> Result<stripe::Subscription, SimplifiedStripeError> {
+        Err(SimplifiedStripeError::Other(BILLING_DISABLED.to_string()))
+    }
+
+    async fn retrieve_invoice(
+        &self, _invoice_id: &stripe::InvoiceId,
+    ) -> Result<stripe::Invoice, SimplifiedStripeError> {
+        Err(SimplifiedStripeError::Other(BILLING_DISABLED.to_string()))
+    }
+}
+
+#[async_trait]
+impl GooglePlayClient for Nop {
+    async fn acknowledge_subscription(
+        &self, _config: &Config, _purchase_token: &str,
+    ) -> Result<(), SimpleGCPError> {
+        Err(SimpleGCPError::Unexpected(BILLING_DISABLED.to_string()))
+    }
+
+    async fn cancel_subscription(
+        &self, _config: &Config, _purchase_token: &str,
+    ) -> Result<(), SimpleGCPError> {
+        Ok(())
+    }
+
+    async fn get_subscription(
+        &self, _config: &Config, _purchase_token: &str,
+    ) -> Result<SubscriptionPurchase, SimpleGCPError> {
+        Err(SimpleGCPError::Unexpected(BILLING_DISABLED.to_string()))
+    }
+}
+
+#[async_trait]
+impl AppStoreClient for Nop {
+    async fn get_sub_status(
+        &self, _config: &AppleConfig, _original_transaction_id: &str,
+    ) -> Result<(LastTransactionItem, TransactionInfo), ServerError<UpgradeAccountAppStoreError>>
+    {
+        Err(internal!("{}", BILLING_DISABLED))
+    }
+}
@@ -0,0 +1,400 @@
+use crate::ServerError::ClientError;
+use crate::billing::app_store_client::AppStoreClient;
+use crate::billing::google_play_client::GooglePlayClient;
+use crate::billing::stripe_client::StripeClient;
+use crate::document_service::DocumentService;
+use crate::schema::ServerDb;
+use crate::{RequestContext, ServerError, ServerState};
+use db_rs::{Db, DbError};
+use lb_rs::model::access_info::UserAccessMode;
+use lb_rs::model::api::*;
+use lb_rs::model::clock::get_time;
+use lb_rs::model::errors::LbErr;
+use lb_rs::model::file_like::FileLike;
+use lb_rs::model::file_metadata::{DocumentHmac, Owner};
+use lb_rs::model::server_tree::ServerTree;
+use lb_rs::model::tree_like::TreeLike;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+use std::ops::DerefMut;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+/// how long a declared manifest's chunks are kept for, if the manifest never becomes the
+/// document's content; long enough for a large upload to finish
+const DECLARATION_TIMEOUT_MS: i64 = 24 * 60 * 60 * 1000;
+
+impl<S, A, G, D> ServerState<S, A, G, D>
+where
+    S: StripeClient,
+    A: AppStoreClient,
+    G: GooglePlayClient,
+    D: DocumentService,
+{
+    pub async fn declare_chunks(
+        &self, context: RequestContext<DeclareChunksRequest>,
+    ) -> Result<DeclareChunksResponse, ServerError<DeclareChunksError>> {
+        let DeclareChunksRequest { id, hmac, chunks } = context.request;
+        let requester = Owner(context.public_key);
+
+        let mut lock = self.index_db.lock().await;
+        let db = lock.deref_mut();
+        let tx = db.begin_transaction()?;
+
+        Self::check_chunk_access(
+            db,
+            requester,
+            id,
+            UserAccessMode::Write,
+            DeclareChunksError::DocumentNotFound,
+            DeclareChunksError::NotPermissioned,
+        )?;
+
+        let stored = db.chunks.get().get(&id).cloned().unwrap_or_default();
+        let mut missing = vec![];
+        for chunk in &chunks {
+            if !stored.contains_key(chunk) && !missing.contains(chunk) {
+                missing.push(*chunk);
+            }
+        }
+
+        let mut manifests = db
+            .chunk_manifests
+            .get()
+            .get(&id)
+            .cloned()
+            .unwrap_or_default();
+        manifests.insert(hmac, chunks);
+        db.chunk_manifests.insert(id, manifests)?;
+        if !Self::is_committed_manifest(db, id, hmac) {
+            db.chunk_declarations.insert((id, hmac), get_time().0)?;
+        }
+
+        tx.drop_safely()?;
+
+        Ok(DeclareChunksResponse { missing })
+    }
+
+    pub async fn upsert_chunk(
+        &self, context: RequestContext<UpsertChunkRequest>,
+    ) -> Result<(), ServerError<UpsertChunkError>> {
+        let UpsertChunkRequest { id, hmac, content } = context.request;
+        let requester = Owner(context.public_key);
+        let size = content.value.len() as u64;
+
+        // phase 1: validate request before io
+        {
+            let mut lock = self.index_db.lock().await;
+            let stored = Self::check_chunk_upload(lock.deref_mut(), requester, id, hmac, size)?;
+            if stored {
+                return Ok(());
+            }
+        }
+
+        self.document_service.insert(&id, &hmac, &content).await?;
+
+        // phase 2: access or the owner's cap may have changed during io
+        let result = async {
+            let mut lock = self.index_db.lock().await;
+            let db = lock.deref_mut();
+            let tx = db.begin_transaction()?;
+
+            if !Self::check_chunk_upload(db, requester, id, hmac, size)? {
+                let mut chunks = db.chunks.get().get(&id).cloned().unwrap_or_default();
+                chunks.insert(hmac, size);
+                db.chunks.insert(id, chunks)?;
+            }
+
+            tx.drop_safely()?;
+            Ok::<_, ServerError<UpsertChunkError>>(())
+        }
+        .await;
+
+        if result.is_err() {
+            // the chunk wasn't stored before phase 1, and failing phase 2 means it still isn't
+            self.document_service.delete(&id, &hmac).await?;
+            debug!(?id, "cleaned up chunk contents after failed upsert");
+        }
+
+        result
+    }
+
+    /// Checks that `requester` can upload this chunk and that storing it keeps the document's
+    /// owner under their cap. Returns whether the chunk is already stored, in which case there's
+    /// nothing to upload.
+    fn check_chunk_upload(
+        db: &mut ServerDb, requester: Owner, id: Uuid, hmac: DocumentHmac, size: u64,
+    ) -> Result<bool, ServerError<UpsertChunkError>> {
+        use UpsertChunkError::*;
+
+        Self::check_chunk_access(
+            db,
+            requester,
+            id,
+            UserAccessMode::Write,
+            DocumentNotFound,
+            NotPermissioned,
+        )?;
+
+        let declared = db
+            .chunk_manifests
+            .get()
+            .get(&id)
+            .is_some_and(|manifests| manifests.values().flatten().any(|chunk| chunk == &hmac));
+        if !declared {
+            return Err(ClientError(ChunkNotDeclared));
+        }
+
+        if db
+            .chunks
+            .get()
+            .get(&id)
+            .is_some_and(|chunks| chunks.contains_key(&hmac))
+        {
+            return Ok(true);
+        }
+
+        let Some(owner) = db.metas.get().get(&id).map(|meta| meta.owner()) else {
+            return Err(ClientError(DocumentNotFound));
+        };
+        let usage_cap = Self::get_cap(db, &owner.0).map_err(|err| internal!("{:?}", err))?;
+        let other_usage = Self::stored_usage(db, &owner);
+        let mut tree = ServerTree::new(
+            owner,
+            &mut db.owned_files,
+            &mut db.shared_files,
+            &mut db.file_children,
+            &mut db.metas,
+        )?
+        .to_lazy();
+        let usage = tree.calculate_usage(owner)? + other_usage;
+        debug!(?usage, ?size, ?usage_cap, "usage caps on upsert chunk");
+        if usage + size > usage_cap {
+            warn!(?owner, "user over cap");
+            return Err(ClientError(UsageIsOverDataCap));
+        }
+
+        Ok(false)
+    }
+
+    pub async fn get_chunk(
+        &self, context: RequestContext<GetChunkRequest>,
+    ) -> Result<GetChunkResponse, ServerError<GetChunkError>> {
+        use GetChunkError::*;
+        let GetChunkRequest { id, hmac } = context.request;
+        let requester = Owner(context.public_key);
+
+        {
+            let mut lock = self.index_db.lock().await;
+            let db = lock.deref_mut();
+
+            Self::check_chunk_access(
+                db,
+                requester,
+                id,
+                UserAccessMode::Read,
+                DocumentNotFound,
+                NotPermissioned,
+            )?;
+
+            if !db
+                .chunks
+                .get()
+                .get(&id)
+                .is_some_and(|chunks| chunks.contains_key(&hmac))
+            {
+                return Err(ClientError(ChunkNotFound));
+            }
+        }
+
+        let Some(content) = self.document_service.maybe_get(&id, &hmac).await? else {
+            return Err(ClientError(ChunkNotFound));
+        };
+
+        let mut lock = self.index_db.lock().await;
+        let db = lock.deref_mut();
+        let tx = db.begin_transaction()?;
+        if !self.record_egress(db, requester, content.value.len())? {
+            return Err(ClientError(BandwidthExceeded));
+        }
+        tx.drop_safely()?;
+
+        Ok(GetChunkResponse { content })
+    }
+
+    /// Chunks are available to whoever can access their document, which must not be deleted.
+    fn check_chunk_access<E: Debug>(
+        db: &mut ServerDb, requester: Owner, id: Uuid, mode: UserAccessMode, not_found: E,
+        not_permissioned: E,
+    ) -> Result<(), ServerError<E>>
+    where
+        ServerError<E>: From<LbErr>,
+    {
+        let meta_exists = db.metas.get().get(&id).is_some();
+        let groups = Self::groups_of(&db.groups, &requester);
+
+        let mut tree = ServerTree::new(
+            requester,
+            &mut db.owned_files,
+            &mut db.shared_files,
+            &mut db.file_children,
+            &mut db.metas,
+        )?
+        .with_groups(&groups)
+        .to_lazy();
+
+        if tree.maybe_find(&id).is_none() {
+            return Err(ClientError(if meta_exists { not_permissioned } else { not_found }));
+        }
+
+        if tree.calculate_deleted(&id)? {
+            return Err(ClientError(not_found));
+        }
+
+        if tree.access_mode_in_groups(requester, &groups, &id)? < Some(mode) {
+            return Err(ClientError(not_permissioned));
+        }
+
+        Ok(())
+    }
+
+    /// bytes held by chunks of documents owned by `owner`, other than documents in the trash
+    pub fn chunk_usage(db: &ServerDb, owner: &Owner) -> u64 {
+        let Some(owned) = db.owned_files.get().get(owner) else {
+            return 0;
+        };
+        owned
+            .iter()
+            .filter(|id| {
+                let trashed = db
+                    .metas
+                    .get()
+                    .get(id)
+                    .and_then(|meta| meta.document_hmac())
+                    .is_some_and(|hmac| {
+                        db.scheduled_file_cleanups
+                            .get()
+                            .contains_key(&(**id, *hmac))
+                    });
+                !trashed
+            })
+            .filter_map(|id| db.chunks.get().get(id))
+            .flat_map(|chunks| chunks.values())
+            .sum()
+    }
+
+    /// whether this manifest is the document's content or one of its retained versions
+    fn is_committed_manifest(db: &ServerDb, id: Uuid, hmac: DocumentHmac) -> bool {
+        let current = db
+            .metas
+            .get()
+            .get(&id)
+            .and_then(|meta| meta.document_hmac())
+            == Some(&hmac);
+        let retained = db
+            .document_versions
+            .get()
+            .get(&id)
+            .is_some_and(|versions| versions.iter().any(|version| version.hmac == hmac));
+        current || retained
+    }
+
+    /// Schedules cleanup for chunks no manifest refers to anymore. A manifest stops counting once
+    /// its own cleanup is due, once its document is gone entirely, or if it was declared more
+    /// than [DECLARATION_TIMEOUT_MS] ago and never became the document's content.
+    pub fn release_chunks(db: &mut ServerDb) -> Result<(), DbError> {
+        let now = get_time().0;
+        let mut updates: Vec<(Uuid, HashMap<DocumentHmac, Vec<DocumentHmac>>)> = vec![];
+        let mut released: Vec<(Uuid, Vec<DocumentHmac>)> = vec![];
+
+        // declarations are done with once their manifest is committed or has expired
+        let settled: Vec<(Uuid, DocumentHmac)> = db
+            .chunk_declarations
+            .get()
+            .iter()
+            .filter(|((id, hmac), declared_at)| {
+                Self::is_committed_manifest(db, *id, *hmac)
+                    || now - **declared_at > DECLARATION_TIMEOUT_MS
+            })
+            .map(|(key, _)| *key)
+            .collect();
+
+        let ids: HashSet<Uuid> = db
+            .chunks
+            .get()
+            .keys()
+            .chain(db.chunk_manifests.get().keys())
+            .copied()
+            .collect();
+        for id in ids {
+            let mut manifests = db
+                .chunk_manifests
+                .get()
+                .get(&id)
+                .cloned()
+                .unwrap_or_default();
+            if db.metas.get().get(&id).is_none() {
+                manifests.clear();
+            }
+            manifests.retain(|manifest, _| {
+                let cleanup_due = db
+                    .scheduled_file_cleanups
+                    .get()
+                    .get(&(id, *manifest))
+                    .is_some_and(|time| now - time > 1000 * 60 * 5);
+                let pending = db
+                    .chunk_declarations
+                    .get()
+                    .get(&(id, *manifest))
+                    .is_some_and(|declared_at| now - declared_at <= DECLARATION_TIMEOUT_MS);
+                !cleanup_due && (pending || Self::is_committed_manifest(db, id, *manifest))
+            });
+
+            let referenced: HashSet<&DocumentHmac> = manifests.values().flatten().collect();
+            let unreferenced: Vec<DocumentHmac> = db
+                .chunks
+                .get()
+                .get(&id)
+                .map(|chunks| {
+                    chunks
+                        .keys()
+                        .filter(|chunk| !referenced.contains(chunk))
+                        .copied()
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            if !unreferenced.is_empty() {
+                released.push((id, unreferenced));
+            }
+            updates.push((id, manifests));
+        }
+
+        for (id, manifests) in updates {
+            if manifests.is_empty() {
+                db.chunk_manifests.remove(&id)?;
+            } else {
+                db.chunk_manifests.insert(id, manifests)?;
+            }
+        }
+
+        for key in settled {
+            db.chunk_declarations.remove(&key)?;
+        }
+
+        for (id, unreferenced) in released {
+            let mut chunks = db.chunks.get().get(&id).cloned().unwrap_or_default();
+            for chunk in unreferenced {
+                chunks.remove(&chunk);
+                db.scheduled_file_cleanups.insert((id, chunk), now)?;
+            }
+            if chunks.is_empty() {
+                db.chunks.remove(&id)?;
+            } else {
+                db.chunks.insert(id, chunks)?;
+            }
+        }
+
+        Ok(())
+    }
+}
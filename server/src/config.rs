@@ -1,9 +1,11 @@
 use crate::config::Environment::{Local, Prod, Unknown};
 use lb_rs::model::account::Username;
 use semver::VersionReq;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Display;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::OnceLock;
 use std::time::Duration;
 use std::{env, fmt, fs};
 
@@ -31,6 +33,29 @@ impl Config {
         }
     }
 
+    /// Reads the config from a file of `NAME=value` lines (like `local.env`), for servers that are
+    /// run directly rather than by a service manager. Environment variables override the file.
+    pub fn from_file(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)
+            .unwrap_or_else(|err| panic!("Could not read config file {path:?}: {err}"));
+        let vars = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                let (name, value) = line.split_once('=').unwrap_or_else(|| {
+                    panic!("Invalid config file line, expected NAME=value: {line}")
+                });
+                (name.trim().to_string(), value.trim().trim_matches('"').to_string())
+            })
+            .collect();
+        if FILE_VARS.set(vars).is_err() {
+            panic!("Config was already read from a file");
+        }
+        Self::from_env_vars()
+    }
+
     pub fn is_prod(&self) -> bool {
         self.server.env == Prod
     }
@@ -47,7 +72,7 @@ impl IndexDbConf {
         Self {
             db_location: env_or_panic("INDEX_DB_LOCATION"),
             time_between_compacts: Duration::from_secs(
-                env_or_panic("MINUTES_BETWEEN_BACKGROUND_COMPACTS")
+                env_or_self_hosted_default("MINUTES_BETWEEN_BACKGROUND_COMPACTS", "60")
                     .parse::<u64>()
                     .unwrap()
                     * 60,
@@ -59,16 +84,39 @@ impl IndexDbConf {
 #[derive(Clone, Debug, Default)]
 pub struct AdminConfig {
     pub admins: HashSet<Username>,
+    /// codes that let someone create an account when `FEATURE_INVITE_ONLY` is set
+    pub invite_codes: HashSet<String>,
 }
 
+/// the codes `self-hosted.env` suggests, which anyone who's read it knows
+const SAMPLE_INVITE_CODES: [&str; 2] = ["change-me", "and-me"];
+
 impl AdminConfig {
     pub fn from_env_vars() -> Self {
+        let invite_codes: HashSet<String> = var("INVITE_CODES")
+            .unwrap_or_default()
+            .split(", ")
+            .filter(|part| !part.is_empty())
+            .map(|part| part.to_string())
+            .collect();
+        if self_hosted() {
+            if let Some(code) = invite_codes
+                .iter()
+                .find(|code| SAMPLE_INVITE_CODES.contains(&code.as_str()))
+            {
+                panic!(
+                    "Invalid config, INVITE_CODES has {code} from self-hosted.env; pick codes of your own"
+                );
+            }
+        }
+
         Self {
-            admins: env::var("ADMINS")
+            admins: var("ADMINS")
                 .unwrap_or_else(|_| "".to_string())
                 .split(", ")
                 .map(|part| part.to_string())
                 .collect(),
+            invite_codes,
         }
     }
 }
@@ -76,32 +124,59 @@ impl AdminConfig {
 #[derive(Clone, Debug)]
 pub struct FeatureFlags {
     pub new_accounts: bool,
+    /// new accounts need one of the configured invite codes; on by default for self-hosted servers
+    pub invite_only: bool,
     pub new_account_rate_limit: bool,
     pub bandwidth_controls: bool,
+    /// limits how often each account and each ip can make requests to each route
+    pub rate_limit: bool,
+    /// requests per minute an account can make to a route, which is also how many it can make at
+    /// once after being idle
+    pub rate_limit_per_account: u32,
+    /// likewise for an ip, which many accounts can be behind
+    pub rate_limit_per_ip: u32,
 }
 
 impl FeatureFlags {
     pub fn from_env_vars() -> Self {
         Self {
-            new_accounts: env::var("FEATURE_NEW_ACCOUNTS")
+            new_accounts: var("FEATURE_NEW_ACCOUNTS")
                 .unwrap_or_else(|_| "true".to_string())
                 .parse()
                 .unwrap(),
-            new_account_rate_limit: env::var("FEATURE_NEW_ACCOUNT_LIMITS")
+            invite_only: parse_var("FEATURE_INVITE_ONLY").unwrap_or_else(self_hosted),
+            new_account_rate_limit: var("FEATURE_NEW_ACCOUNT_LIMITS")
                 .unwrap_or_else(|_| "false".to_string())
                 .parse()
                 .unwrap(),
-            bandwidth_controls: env::var("FEATURE_BANDWIDTH_CONTROLS")
+            bandwidth_controls: var("FEATURE_BANDWIDTH_CONTROLS")
                 .unwrap_or_else(|_| "true".to_string())
                 .parse()
                 .unwrap(),
+            rate_limit: var("FEATURE_RATE_LIMIT")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .unwrap(),
+            rate_limit_per_account: var("RATE_LIMIT_PER_ACCOUNT")
+                .unwrap_or_else(|_| "600".to_string())
+                .parse()
+                .unwrap(),
+            rate_limit_per_ip: var("RATE_LIMIT_PER_IP")
+                .unwrap_or_else(|_| "3000".to_string())
+                .parse()
+                .unwrap(),
         }
     }
 }
 
 #[derive(Clone, Debug)]
 pub struct FilesConfig {
+    /// where document contents are stored when they're stored on disk, and where they're migrated
+    /// from when they're stored in object storage
     pub path: PathBuf,
+    /// how long deleted files (and their contents) are kept around to be restored
+    pub trash_period: Duration,
+    pub backend: DocumentBackend,
 }
 
 impl FilesConfig {
@@ -109,7 +184,61 @@ impl FilesConfig {
         let path = env_or_panic("FILES_PATH");
         let path = PathBuf::from(path);
         fs::create_dir_all(&path).unwrap();
-        Self { path }
+        let trash_period = Duration::from_secs(
+            var("DAYS_IN_TRASH")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse::<u64>()
+                .unwrap()
+                * 60
+                * 60
+                * 24,
+        );
+        let backend = DocumentBackend::from_env_vars();
+        Self { path, trash_period, backend }
+    }
+}
+
+/// Where document contents are stored, set with `FILES_BACKEND`.
+#[derive(Clone, Debug)]
+pub enum DocumentBackend {
+    /// one file per document version in `FILES_PATH`
+    Disk,
+    /// one object per document version, in an S3 bucket or anything that speaks the S3 API
+    S3(S3Config),
+}
+
+impl DocumentBackend {
+    pub fn from_env_vars() -> Self {
+        match var("FILES_BACKEND").as_deref() {
+            Err(_) | Ok("disk") => Self::Disk,
+            Ok("s3") => Self::S3(S3Config::from_env_vars()),
+            Ok(other) => panic!("Invalid config, FILES_BACKEND must be disk or s3, not {other}"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct S3Config {
+    /// unset for AWS itself; set for MinIO and other S3-compatible stores
+    pub endpoint: Option<String>,
+    pub region: String,
+    pub bucket: String,
+    /// prepended to every key, so that a bucket can be shared
+    pub prefix: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+}
+
+impl S3Config {
+    pub fn from_env_vars() -> Self {
+        Self {
+            endpoint: env_or_empty("S3_ENDPOINT"),
+            region: var("S3_REGION").unwrap_or_else(|_| "us-east-1".to_string()),
+            bucket: env_or_panic("S3_BUCKET"),
+            prefix: var("S3_PREFIX").unwrap_or_default(),
+            access_key_id: env_or_panic("S3_ACCESS_KEY_ID"),
+            secret_access_key: env_or_panic("S3_SECRET_ACCESS_KEY"),
+        }
     }
 }
 
@@ -122,7 +251,7 @@ pub enum Environment {
 
 impl Environment {
     pub fn from_env_vars() -> Self {
-        match env::var("ENVIRONMENT") {
+        match var("ENVIRONMENT") {
             Ok(var) => match var.to_lowercase().as_str() {
                 "production" | "prod" => Prod,
                 "local" | "localhost" => Local,
@@ -142,6 +271,8 @@ impl Display for Environment {
 #[derive(Clone, Debug)]
 pub struct ServerConfig {
     pub env: Environment,
+    /// run without billing providers, with defaults for everything that isn't needed to get going
+    pub self_hosted: bool,
     pub port: u16,
     pub max_auth_delay: u128,
     pub log_path: String,
@@ -155,17 +286,25 @@ pub struct ServerConfig {
 impl ServerConfig {
     pub fn from_env_vars() -> Self {
         let env = Environment::from_env_vars();
-        let port = env_or_panic("SERVER_PORT").parse().unwrap();
-        let max_auth_delay = env_or_panic("MAX_AUTH_DELAY").parse().unwrap();
+        let self_hosted = self_hosted();
+        let port = env_or_self_hosted_default("SERVER_PORT", "8000")
+            .parse()
+            .unwrap();
+        let max_auth_delay = env_or_self_hosted_default("MAX_AUTH_DELAY", "200000")
+            .parse()
+            .unwrap();
         let log_path = env_or_panic("LOG_PATH").parse().unwrap();
         let pd_api_key = env_or_empty("PD_KEY");
         let discord_webhook_url = env_or_empty("DISCORD_WEBHOOK_URL");
         let ssl_cert_location = env_or_empty("SSL_CERT_LOCATION");
         let ssl_private_key_location = env_or_empty("SSL_PRIVATE_KEY_LOCATION");
-        let min_core_version = VersionReq::parse(&env_or_panic("MIN_CORE_VERSION")).unwrap();
+        let min_core_version =
+            VersionReq::parse(&env_or_self_hosted_default("MIN_CORE_VERSION", "*")).unwrap();
 
         match (&discord_webhook_url, &pd_api_key, &ssl_cert_location, &ssl_private_key_location) {
             (Some(_), Some(_), Some(_), Some(_)) | (None, None, None, None) => {}
+            // self-hosted servers serve tls without our alerting
+            (None, None, Some(_), Some(_)) if self_hosted => {}
             _ => panic!(
                 "Invalid config, discord & pd & ssl must all be Some (production) or all be None (local)"
             ),
@@ -173,6 +312,7 @@ impl ServerConfig {
 
         Self {
             env,
+            self_hosted,
             port,
             max_auth_delay,
             log_path,
@@ -195,13 +335,13 @@ impl MetricsConfig {
     pub fn from_env_vars() -> Self {
         Self {
             time_between_metrics_refresh: Duration::from_secs(
-                env_or_panic("MINUTES_BETWEEN_METRICS_REFRESH")
+                env_or_self_hosted_default("MINUTES_BETWEEN_METRICS_REFRESH", "5")
                     .parse::<u64>()
                     .unwrap()
                     * 60,
             ),
             time_between_metrics: Duration::from_millis(
-                env_or_panic("MILLIS_BETWEEN_METRICS")
+                env_or_self_hosted_default("MILLIS_BETWEEN_METRICS", "100")
                     .parse::<u64>()
                     .unwrap(),
             ),
@@ -220,6 +360,17 @@ pub struct BillingConfig {
 
 impl BillingConfig {
     pub fn from_env_vars() -> Self {
+        if self_hosted() {
+            // accounts are only ever given a tier by an admin
+            return Self {
+                millis_between_user_payment_flows: 0,
+                time_between_lock_attempts: Duration::ZERO,
+                google: Default::default(),
+                stripe: Default::default(),
+                apple: Default::default(),
+            };
+        }
+
         Self {
             millis_between_user_payment_flows: env_or_panic("MILLIS_BETWEEN_PAYMENT_FLOWS")
                 .parse()
@@ -236,7 +387,7 @@ impl BillingConfig {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct AppleConfig {
     pub iap_key: String,
     pub iap_key_id: String,
@@ -286,7 +437,7 @@ impl AppleConfig {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct GoogleConfig {
     pub service_account_key: Option<String>,
     pub premium_subscription_product_id: String,
@@ -309,7 +460,7 @@ impl GoogleConfig {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct StripeConfig {
     pub stripe_secret: String,
     pub signing_secret: String,
@@ -326,10 +477,51 @@ impl StripeConfig {
     }
 }
 
+/// Variables read by [`Config::from_file`], consulted after the environment.
+static FILE_VARS: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+pub fn var(var_name: &str) -> Result<String, env::VarError> {
+    env::var(var_name).or_else(|err| {
+        FILE_VARS
+            .get()
+            .and_then(|vars| vars.get(var_name))
+            .cloned()
+            .ok_or(err)
+    })
+}
+
+/// Parses a variable, if it's set, panicking with its name if it doesn't parse.
+fn parse_var<T>(var_name: &str) -> Option<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    var(var_name).ok().map(|value| {
+        value
+            .parse()
+            .unwrap_or_else(|err| panic!("Invalid environment variable {var_name}={value}: {err}"))
+    })
+}
+
+fn self_hosted() -> bool {
+    parse_var("SELF_HOSTED").unwrap_or(false)
+}
+
 fn env_or_panic(var_name: &str) -> String {
-    env::var(var_name).unwrap_or_else(|_| panic!("Missing environment variable {var_name}"))
+    var(var_name).unwrap_or_else(|_| panic!("Missing environment variable {var_name}"))
+}
+
+/// Required, unless the server is self-hosted, in which case `default` is used.
+fn env_or_self_hosted_default(var_name: &str, default: &str) -> String {
+    var(var_name).unwrap_or_else(|_| {
+        if self_hosted() {
+            default.to_string()
+        } else {
+            panic!("Missing environment variable {var_name}")
+        }
+    })
 }
 
 fn env_or_empty(var_name: &str) -> Option<String> {
-    env::var(var_name).ok()
+    var(var_name).ok()
 }
@@ -2,10 +2,11 @@ use std::{
     collections::HashMap,
     net::IpAddr,
     ops::Deref,
-    time::{SystemTime, UNIX_EPOCH},
+    time::{Instant, SystemTime, UNIX_EPOCH},
 };
 
 use google_androidpublisher3::chrono::{Datelike, Local};
+use lb_rs::model::file_metadata::Owner;
 use serde::{Deserialize, Serialize};
 use time::Duration;
 
@@ -61,9 +62,8 @@ impl BandwidthReport {
     }
 }
 
-/// This struct helps us ensure that a given IP isn't making too many accounts
-/// this could be expanded upon as a broader rate limit, for now we're just going
-/// to apply the pattern where it's needed (new-account).
+/// This struct helps us ensure that a given IP isn't making too many accounts. Other requests are
+/// limited by [`RateLimiter`], which can't tell one new account from the next.
 #[derive(Copy, Debug, Clone, Serialize, Deserialize)]
 pub struct IpData {
     ip: IpAddr,
@@ -71,6 +71,81 @@ pub struct IpData {
 }
 static MAX_IPS: u16 = 1000;
 
+/// Token buckets for each route, for each account and each ip making requests to it. A bucket
+/// holds up to a minute's worth of requests and refills continuously; a request takes a token
+/// from its ip's bucket before it's parsed, and from its account's once it's verified.
+#[derive(Default)]
+pub struct RateLimiter {
+    buckets: HashMap<(&'static str, Requester), Bucket>,
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+enum Requester {
+    Account(Owner),
+    Ip(IpAddr),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    per_minute: u32,
+    refilled_at: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, now: Instant) {
+        let per_minute = self.per_minute as f64;
+        let elapsed = now.duration_since(self.refilled_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * per_minute / 60.0).min(per_minute);
+        self.refilled_at = now;
+    }
+
+    fn is_full(&self) -> bool {
+        self.tokens >= self.per_minute as f64
+    }
+
+    /// milliseconds until this bucket has a token
+    fn wait(&self) -> u64 {
+        if self.tokens >= 1.0 {
+            0
+        } else {
+            ((1.0 - self.tokens) * 60_000.0 / self.per_minute as f64).ceil() as u64
+        }
+    }
+}
+
+impl RateLimiter {
+    /// Takes a token from the requester's bucket for this route, or if it's empty, returns how
+    /// many milliseconds until it has one.
+    fn take(
+        &mut self, route: &'static str, requester: Requester, per_minute: u32,
+    ) -> Result<(), u64> {
+        let now = Instant::now();
+        let bucket = self.buckets.entry((route, requester)).or_insert(Bucket {
+            tokens: per_minute as f64,
+            per_minute,
+            refilled_at: now,
+        });
+        bucket.refill(now);
+        match bucket.wait() {
+            0 => {
+                bucket.tokens -= 1.0;
+                Ok(())
+            }
+            retry_after => Err(retry_after),
+        }
+    }
+
+    /// Drops full buckets; a full bucket is the same as no bucket.
+    fn prune(&mut self) {
+        let now = Instant::now();
+        self.buckets.retain(|_, bucket| {
+            bucket.refill(now);
+            !bucket.is_full()
+        });
+    }
+}
+
 impl<S, A, G, D> ServerState<S, A, G, D>
 where
     S: StripeClient,
@@ -103,6 +178,53 @@ where
         true
     }
 
+    /// Checks whether the server is configured to rate limit, and if so takes a token for this
+    /// request from its ip's bucket for the route. This happens before the request is parsed, so
+    /// that flooding the server with requests costs it as little as possible. When the request
+    /// isn't allowed, returns how many milliseconds the client should wait before retrying.
+    pub async fn rate_limit_ip(&self, route: &'static str, ip: IpAddr) -> Result<(), u64> {
+        let per_minute = self.config.features.rate_limit_per_ip;
+        self.rate_limit(route, Requester::Ip(ip), per_minute).await
+    }
+
+    /// Like [Self::rate_limit_ip], for the account whose signature the request carries.
+    pub async fn rate_limit_account(&self, route: &'static str, owner: Owner) -> Result<(), u64> {
+        let per_minute = self.config.features.rate_limit_per_account;
+        self.rate_limit(route, Requester::Account(owner), per_minute)
+            .await
+    }
+
+    async fn rate_limit(
+        &self, route: &'static str, requester: Requester, per_minute: u32,
+    ) -> Result<(), u64> {
+        if !self.config.features.rate_limit {
+            return Ok(());
+        }
+
+        let result = self
+            .rate_limiter
+            .lock()
+            .await
+            .take(route, requester, per_minute);
+        if result.is_err() {
+            tracing::warn!("{route} not permitted due to rate limit");
+        }
+        result
+    }
+
+    /// Periodically drops the buckets of requesters that have gone quiet, so that requests don't
+    /// pay for it while they wait on the rate limiter.
+    pub fn start_rate_limit_pruner(&self) {
+        let bg_self = self.clone();
+
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+                bg_self.rate_limiter.lock().await.prune();
+            }
+        });
+    }
+
     pub async fn did_create_account(&self, ip: IpAddr) {
         let mut ips = self.recent_new_account_ips.lock().await;
         ips.retain(|visitor| visitor.ip != ip);
@@ -1,14 +1,19 @@
 use crate::ServerError;
-use crate::config::Config;
+use crate::config::{Config, S3Config};
 use async_trait::async_trait;
+use aws_sdk_s3::config::{BehaviorVersion, Credentials, Region};
+use aws_sdk_s3::error::DisplayErrorContext;
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::types::{CompletedMultipartUpload, CompletedPart};
 use lb_rs::model::crypto::EncryptedDocument;
 use lb_rs::model::file_metadata::DocumentHmac;
 use std::collections::HashMap;
 use std::fmt::Debug;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use tokio::fs::{File, remove_file};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tracing::error;
 use uuid::Uuid;
 
 #[async_trait]
@@ -24,10 +29,17 @@ pub trait DocumentService: Send + Sync + Clone + 'static {
     ) -> Result<Option<EncryptedDocument>, ServerError<T>>;
     async fn delete<T: Debug>(&self, id: &Uuid, hmac: &DocumentHmac) -> Result<(), ServerError<T>>;
 
-    fn exists(&self, id: &Uuid, hmac: &DocumentHmac) -> bool;
+    async fn exists(&self, id: &Uuid, hmac: &DocumentHmac) -> bool;
+    /// where the content is stored, for logs
     fn get_path(&self, id: &Uuid, hmac: &DocumentHmac) -> PathBuf;
 }
 
+/// The name a document version is stored under, on disk or in a bucket.
+fn file_name(id: &Uuid, hmac: &DocumentHmac) -> String {
+    let hmac = base64::encode_config(hmac, base64::URL_SAFE);
+    format!("{id}-{hmac}")
+}
+
 #[derive(Clone)]
 pub struct OnDiskDocuments {
     config: Config,
@@ -89,19 +101,294 @@ impl DocumentService for OnDiskDocuments {
         Ok(())
     }
 
-    fn exists(&self, id: &Uuid, hmac: &DocumentHmac) -> bool {
+    async fn exists(&self, id: &Uuid, hmac: &DocumentHmac) -> bool {
         self.get_path(id, hmac).exists()
     }
 
     fn get_path(&self, id: &Uuid, hmac: &DocumentHmac) -> PathBuf {
         let mut path = self.config.files.path.clone();
         // we may need to truncate this
-        let hmac = base64::encode_config(hmac, base64::URL_SAFE);
-        path.push(format!("{id}-{hmac}"));
+        path.push(file_name(id, hmac));
         path
     }
 }
 
+impl OnDiskDocuments {
+    /// The document version a file in `FILES_PATH` holds, if it holds one.
+    pub fn parse_file_name(name: &str) -> Option<(Uuid, DocumentHmac)> {
+        let (id, hmac) = (name.get(..36)?, name.get(36..)?.strip_prefix('-')?);
+        let id = Uuid::parse_str(id).ok()?;
+        let hmac = base64::decode_config(hmac, base64::URL_SAFE).ok()?;
+        Some((id, hmac.try_into().ok()?))
+    }
+}
+
+/// Contents stored in an S3 bucket, or in anything that speaks the S3 API, like MinIO. Objects are
+/// named the way files are named on disk, so a bucket can be filled by copying `FILES_PATH` over.
+#[derive(Clone)]
+pub struct S3Documents {
+    client: aws_sdk_s3::Client,
+    bucket: String,
+    prefix: String,
+    /// where contents that aren't in the bucket are read from, until they've been migrated
+    read_through: Option<OnDiskDocuments>,
+}
+
+/// Contents larger than this are uploaded in parts of this size, rather than all at once.
+const S3_PART_BYTES: usize = 16 * 1024 * 1024;
+
+impl From<&S3Config> for S3Documents {
+    fn from(value: &S3Config) -> Self {
+        let credentials = Credentials::new(
+            &value.access_key_id,
+            &value.secret_access_key,
+            None,
+            None,
+            "lockbook-server",
+        );
+        let mut config = aws_sdk_s3::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .region(Region::new(value.region.clone()))
+            .credentials_provider(credentials);
+        if let Some(endpoint) = &value.endpoint {
+            // stand-ins like MinIO don't serve buckets from subdomains
+            config = config.endpoint_url(endpoint).force_path_style(true);
+        }
+
+        Self {
+            client: aws_sdk_s3::Client::from_conf(config.build()),
+            bucket: value.bucket.clone(),
+            prefix: value.prefix.clone(),
+            read_through: None,
+        }
+    }
+}
+
+impl S3Documents {
+    /// Reads contents that aren't in the bucket from `disk`, so that a server moved to object
+    /// storage can serve them before they're migrated. Contents are deleted from both.
+    pub fn reading_through(self, disk: OnDiskDocuments) -> Self {
+        Self { read_through: Some(disk), ..self }
+    }
+
+    fn key(&self, id: &Uuid, hmac: &DocumentHmac) -> String {
+        format!("{}{}", self.prefix, file_name(id, hmac))
+    }
+
+    /// Copies a file holding a document version, as [OnDiskDocuments] stores it, into the bucket,
+    /// holding at most one part of it in memory at a time.
+    pub async fn copy_file<T: Debug>(
+        &self, id: &Uuid, hmac: &DocumentHmac, path: &Path,
+    ) -> Result<(), ServerError<T>> {
+        let key = self.key(id, hmac);
+        let mut file = File::open(path).await?;
+        if file.metadata().await?.len() > S3_PART_BYTES as u64 {
+            return self.upload_in_parts(&key, file).await;
+        }
+
+        let mut content = vec![];
+        file.read_to_end(&mut content).await?;
+        self.put(&key, content).await
+    }
+
+    async fn put<T: Debug>(&self, key: &str, content: Vec<u8>) -> Result<(), ServerError<T>> {
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .body(ByteStream::from(content))
+            .send()
+            .await
+            .map_err(|err| internal!("{}", DisplayErrorContext(err)))?;
+        Ok(())
+    }
+
+    async fn upload_in_parts<T: Debug>(
+        &self, key: &str, mut content: impl AsyncRead + Unpin,
+    ) -> Result<(), ServerError<T>> {
+        let upload = self
+            .client
+            .create_multipart_upload()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(|err| internal!("{}", DisplayErrorContext(err)))?;
+        let upload_id = upload
+            .upload_id()
+            .ok_or_else(|| internal!("no upload id for {key}"))?;
+
+        let mut parts = vec![];
+        loop {
+            let mut part = Vec::with_capacity(S3_PART_BYTES);
+            let read = (&mut content)
+                .take(S3_PART_BYTES as u64)
+                .read_to_end(&mut part)
+                .await;
+            let part = match read {
+                Ok(0) if !parts.is_empty() => break,
+                Ok(_) => part,
+                Err(err) => {
+                    self.abort_upload(key, upload_id).await;
+                    return Err(internal!("{:?}", err));
+                }
+            };
+            let part_number = parts.len() as i32 + 1;
+            let uploaded = self
+                .client
+                .upload_part()
+                .bucket(&self.bucket)
+                .key(key)
+                .upload_id(upload_id)
+                .part_number(part_number)
+                .body(ByteStream::from(part))
+                .send()
+                .await;
+            match uploaded {
+                Ok(uploaded) => parts.push(
+                    CompletedPart::builder()
+                        .set_e_tag(uploaded.e_tag().map(String::from))
+                        .part_number(part_number)
+                        .build(),
+                ),
+                Err(err) => {
+                    self.abort_upload(key, upload_id).await;
+                    return Err(internal!("{}", DisplayErrorContext(err)));
+                }
+            }
+        }
+
+        self.client
+            .complete_multipart_upload()
+            .bucket(&self.bucket)
+            .key(key)
+            .upload_id(upload_id)
+            .multipart_upload(
+                CompletedMultipartUpload::builder()
+                    .set_parts(Some(parts))
+                    .build(),
+            )
+            .send()
+            .await
+            .map_err(|err| internal!("{}", DisplayErrorContext(err)))?;
+        Ok(())
+    }
+
+    /// otherwise the parts uploaded so far are kept, and billed
+    async fn abort_upload(&self, key: &str, upload_id: &str) {
+        let _ = self
+            .client
+            .abort_multipart_upload()
+            .bucket(&self.bucket)
+            .key(key)
+            .upload_id(upload_id)
+            .send()
+            .await;
+    }
+}
+
+#[async_trait]
+impl DocumentService for S3Documents {
+    async fn insert<T: Debug>(
+        &self, id: &Uuid, hmac: &DocumentHmac, content: &EncryptedDocument,
+    ) -> Result<(), ServerError<T>> {
+        let content = bincode::serialize(content)?;
+        let key = self.key(id, hmac);
+        if content.len() > S3_PART_BYTES {
+            return self.upload_in_parts(&key, content.as_slice()).await;
+        }
+        self.put(&key, content).await
+    }
+
+    async fn get<T: Debug>(
+        &self, id: &Uuid, hmac: &DocumentHmac,
+    ) -> Result<EncryptedDocument, ServerError<T>> {
+        self.maybe_get(id, hmac)
+            .await?
+            .ok_or_else(|| internal!("{} not found", self.key(id, hmac)))
+    }
+
+    async fn maybe_get<T: Debug>(
+        &self, id: &Uuid, hmac: &DocumentHmac,
+    ) -> Result<Option<EncryptedDocument>, ServerError<T>> {
+        let object = match self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(self.key(id, hmac))
+            .send()
+            .await
+        {
+            Ok(object) => object,
+            Err(err)
+                if err
+                    .as_service_error()
+                    .is_some_and(|err| err.is_no_such_key()) =>
+            {
+                return match &self.read_through {
+                    Some(disk) => disk.maybe_get(id, hmac).await,
+                    None => Ok(None),
+                };
+            }
+            Err(err) => return Err(internal!("{}", DisplayErrorContext(err))),
+        };
+
+        let mut content =
+            Vec::with_capacity(object.content_length().unwrap_or_default().max(0) as usize);
+        let mut body = object.body;
+        while let Some(bytes) = body
+            .try_next()
+            .await
+            .map_err(|err| internal!("{}", DisplayErrorContext(err)))?
+        {
+            content.extend_from_slice(&bytes);
+        }
+        Ok(Some(bincode::deserialize(&content)?))
+    }
+
+    async fn delete<T: Debug>(&self, id: &Uuid, hmac: &DocumentHmac) -> Result<(), ServerError<T>> {
+        // deleting what isn't there succeeds
+        self.client
+            .delete_object()
+            .bucket(&self.bucket)
+            .key(self.key(id, hmac))
+            .send()
+            .await
+            .map_err(|err| internal!("{}", DisplayErrorContext(err)))?;
+        if let Some(disk) = &self.read_through {
+            disk.delete(id, hmac).await?;
+        }
+        Ok(())
+    }
+
+    async fn exists(&self, id: &Uuid, hmac: &DocumentHmac) -> bool {
+        match self
+            .client
+            .head_object()
+            .bucket(&self.bucket)
+            .key(self.key(id, hmac))
+            .send()
+            .await
+        {
+            Ok(_) => true,
+            Err(err) if err.as_service_error().is_some_and(|err| err.is_not_found()) => {
+                match &self.read_through {
+                    Some(disk) => disk.exists(id, hmac).await,
+                    None => false,
+                }
+            }
+            Err(err) => {
+                error!("could not check for {}: {}", self.key(id, hmac), DisplayErrorContext(err));
+                false
+            }
+        }
+    }
+
+    fn get_path(&self, id: &Uuid, hmac: &DocumentHmac) -> PathBuf {
+        PathBuf::from(format!("s3://{}/{}", self.bucket, self.key(id, hmac)))
+    }
+}
+
 /// For use with fuzzer, not to be hooked up in prod
 #[derive(Clone, Default)]
 pub struct InMemDocuments {
@@ -135,7 +422,7 @@ impl DocumentService for InMemDocuments {
         Ok(self.docs.lock().unwrap().get(&key).cloned())
     }
 
-    fn exists(&self, id: &Uuid, hmac: &DocumentHmac) -> bool {
+    async fn exists(&self, id: &Uuid, hmac: &DocumentHmac) -> bool {
         let hmac = base64::encode_config(hmac, base64::URL_SAFE);
         let key = format!("{id}-{hmac}");
         self.docs.lock().unwrap().contains_key(&key)
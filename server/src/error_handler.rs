@@ -214,6 +214,7 @@ impl From<ServerError<LockBillingWorkflowError>> for ServerError<UpgradeAccountG
                 ClientError(UpgradeAccountGooglePlayError::UserNotFound)
             }
             InternalError(msg) => InternalError(msg),
+            ServerError::ClientUpdateRequired => ServerError::ClientUpdateRequired,
         }
     }
 }
@@ -228,6 +229,7 @@ impl From<ServerError<LockBillingWorkflowError>> for ServerError<UpgradeAccountA
                 ClientError(UpgradeAccountAppStoreError::UserNotFound)
             }
             InternalError(msg) => InternalError(msg),
+            ServerError::ClientUpdateRequired => ServerError::ClientUpdateRequired,
         }
     }
 }
@@ -242,6 +244,7 @@ impl From<ServerError<LockBillingWorkflowError>> for ServerError<UpgradeAccountS
                 ClientError(UpgradeAccountStripeError::UserNotFound)
             }
             InternalError(msg) => InternalError(msg),
+            ServerError::ClientUpdateRequired => ServerError::ClientUpdateRequired,
         }
     }
 }
@@ -256,6 +259,7 @@ impl From<ServerError<LockBillingWorkflowError>> for ServerError<CancelSubscript
                 ClientError(CancelSubscriptionError::UserNotFound)
             }
             InternalError(msg) => InternalError(msg),
+            ServerError::ClientUpdateRequired => ServerError::ClientUpdateRequired,
         }
     }
 }
@@ -270,6 +274,7 @@ impl From<ServerError<LockBillingWorkflowError>> for ServerError<AdminSetUserTie
                 ClientError(AdminSetUserTierError::UserNotFound)
             }
             InternalError(msg) => InternalError(msg),
+            ServerError::ClientUpdateRequired => ServerError::ClientUpdateRequired,
         }
     }
 }
@@ -287,6 +292,7 @@ impl From<ServerError<DeleteAccountHelperError>> for ServerError<DeleteAccountEr
                 ClientError(DeleteAccountError::UserNotFound)
             }
             InternalError(msg) => InternalError(msg),
+            ServerError::ClientUpdateRequired => ServerError::ClientUpdateRequired,
         }
     }
 }
@@ -298,6 +304,7 @@ impl From<ServerError<DeleteAccountHelperError>> for ServerError<AdminDisappearA
                 ClientError(AdminDisappearAccountError::UserNotFound)
             }
             InternalError(msg) => InternalError(msg),
+            ServerError::ClientUpdateRequired => ServerError::ClientUpdateRequired,
         }
     }
 }
@@ -369,6 +376,54 @@ impl From<LbErr> for ServerError<GetDocumentError> {
     }
 }
 
+impl From<LbErr> for ServerError<GetDocVersionsError> {
+    fn from(err: LbErr) -> Self {
+        internal!("{:?}", err)
+    }
+}
+
+impl From<LbErr> for ServerError<EmptyTrashError> {
+    fn from(err: LbErr) -> Self {
+        internal!("{:?}", err)
+    }
+}
+
+impl From<LbErr> for ServerError<DeclareChunksError> {
+    fn from(err: LbErr) -> Self {
+        internal!("{:?}", err)
+    }
+}
+
+impl From<LbErr> for ServerError<UpsertChunkError> {
+    fn from(err: LbErr) -> Self {
+        internal!("{:?}", err)
+    }
+}
+
+impl From<LbErr> for ServerError<GetChunkError> {
+    fn from(err: LbErr) -> Self {
+        internal!("{:?}", err)
+    }
+}
+
+impl From<LbErr> for ServerError<CreatePublicLinkError> {
+    fn from(err: LbErr) -> Self {
+        internal!("{:?}", err)
+    }
+}
+
+impl From<LbErr> for ServerError<ListPublicLinksError> {
+    fn from(err: LbErr) -> Self {
+        internal!("{:?}", err)
+    }
+}
+
+impl From<LbErr> for ServerError<RevokePublicLinkError> {
+    fn from(err: LbErr) -> Self {
+        internal!("{:?}", err)
+    }
+}
+
 impl From<LbErr> for ServerError<GetFileIdsError> {
     fn from(err: LbErr) -> Self {
         internal!("{:?}", err)
@@ -381,6 +436,12 @@ impl From<LbErr> for ServerError<GetUpdatesError> {
     }
 }
 
+impl From<LbErr> for ServerError<WaitForUpdatesError> {
+    fn from(err: LbErr) -> Self {
+        internal!("{:?}", err)
+    }
+}
+
 impl<T: Debug> From<DbError> for ServerError<T> {
     fn from(value: DbError) -> Self {
         internal!("db-rs error {:?}", value)
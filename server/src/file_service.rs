@@ -1,26 +1,34 @@
 use crate::ServerError;
 use crate::ServerError::ClientError;
 use crate::billing::app_store_client::AppStoreClient;
+use crate::billing::billing_model::SubscriptionProfile;
 use crate::billing::google_play_client::GooglePlayClient;
 use crate::billing::stripe_client::StripeClient;
+use crate::config::DocumentBackend;
 use crate::defense::SERVER_BANDWIDTH_CAP;
-use crate::document_service::DocumentService;
-use crate::schema::ServerDb;
+use crate::document_service::{DocumentService, OnDiskDocuments, S3Documents};
+use crate::notification_service::MetadataUpdate;
+use crate::public_link_service::SNAPSHOT_HMAC;
+use crate::schema::{DeltaBase, ServerDb};
 
 use crate::{RequestContext, ServerState};
-use db_rs::Db;
+use db_rs::{Db, DbError};
 use lb_rs::model::api::{UpsertError, *};
 use lb_rs::model::clock::get_time;
+use lb_rs::model::crypto::EncryptedDocument;
+use lb_rs::model::delta::MAX_DELTA_CHAIN;
 use lb_rs::model::errors::{LbErrKind, LbResult};
 use lb_rs::model::file_like::FileLike;
-use lb_rs::model::file_metadata::{Diff, Owner};
+use lb_rs::model::file_metadata::{Diff, DocumentHmac, FileDiff, Owner};
 use lb_rs::model::server_meta::{IntoServerMeta, ServerMeta};
 use lb_rs::model::server_tree::ServerTree;
+use lb_rs::model::signed_meta::SignedMeta;
 use lb_rs::model::tree_like::TreeLike;
 use std::collections::{HashMap, HashSet};
 use std::hash::Hash;
 use std::ops::DerefMut;
 use tracing::{debug, error, warn};
+use uuid::Uuid;
 
 impl<S, A, G, D> ServerState<S, A, G, D>
 where
@@ -41,6 +49,7 @@ where
         let mut lock = self.index_db.lock().await;
         let db = lock.deref_mut();
         let tx = db.begin_transaction()?;
+        let groups = Self::groups_of(&db.groups, &req_owner);
 
         // fail fast on things like access control
         let mut tree = ServerTree::new(
@@ -50,6 +59,7 @@ where
             &mut db.file_children,
             &mut db.metas,
         )?
+        .with_groups(&groups)
         .to_lazy();
 
         for id in tree.ids() {
@@ -59,7 +69,7 @@ where
         }
 
         let mut tree = tree.stage_diff_v2(updates.clone())?;
-        tree.validate(req_owner)?;
+        tree.validate_in_groups(req_owner, &groups)?;
 
         for id in tree.ids() {
             if tree.calculate_deleted(&id)? {
@@ -79,6 +89,7 @@ where
         // Each owner needs their own tree to see all their files
         for &owner in &affected_owners {
             let usage_cap = Self::get_cap(db, &owner.0).map_err(|err| internal!("{:?}", err))?;
+            let stored_usage = Self::stored_usage(db, &owner);
 
             let mut tree = ServerTree::new(
                 owner,
@@ -89,9 +100,9 @@ where
             )?
             .to_lazy();
 
-            let old_usage = tree.calculate_usage(owner)?;
+            let old_usage = tree.calculate_usage(owner)? + stored_usage;
             let mut tree = tree.stage_unvalidated(updates.clone());
-            let new_usage = tree.calculate_usage(owner)?;
+            let new_usage = tree.calculate_usage(owner)? + stored_usage;
 
             debug!(?owner, ?old_usage, ?new_usage, ?usage_cap, "usage caps on upsert");
 
@@ -108,24 +119,53 @@ where
             &mut db.file_children,
             &mut db.metas,
         )?
+        .with_groups(&groups)
         .to_lazy();
 
         let tree = tree.stage_unvalidated(updates.clone());
         let tree = tree.promote()?;
 
+        // contents of deleted documents are kept for the trash period in case they're restored
+        let trash_expiry = get_time().0 + self.config.files.trash_period.as_millis() as i64;
         for id in tree.ids() {
-            if tree.find(&id)?.is_document()
-                && current_deleted.contains(&id)
-                && !prior_deleted.contains(&id)
-            {
-                let meta = tree.find(&id)?;
-                if let Some(hmac) = meta.file.timestamped_value.value.document_hmac().copied() {
+            if !tree.find(&id)?.is_document() {
+                continue;
+            }
+            let meta = tree.find(&id)?;
+            let mut hmacs: Vec<DocumentHmac> = db
+                .document_versions
+                .get()
+                .get(&id)
+                .map(|versions| versions.iter().map(|version| version.hmac).collect())
+                .unwrap_or_default();
+            hmacs.extend(meta.document_hmac().copied());
+
+            if current_deleted.contains(&id) && !prior_deleted.contains(&id) {
+                for hmac in hmacs {
                     db.scheduled_file_cleanups
-                        .insert((*meta.id(), hmac), get_time().0)?;
+                        .insert((id, hmac), trash_expiry)?;
+                }
+            } else if prior_deleted.contains(&id) && !current_deleted.contains(&id) {
+                for hmac in hmacs {
+                    db.scheduled_file_cleanups.remove(&(id, hmac))?;
                 }
             }
         }
 
+        for update in &updates {
+            let was_trashed = update
+                .old
+                .as_ref()
+                .map(|old| old.explicitly_deleted())
+                .unwrap_or_default();
+            if update.new.explicitly_deleted() && !was_trashed {
+                db.trash.insert(*update.new.id(), get_time().0 as u64)?;
+            }
+            if was_trashed && !update.new.explicitly_deleted() {
+                db.trash.remove(update.new.id())?;
+            }
+        }
+
         let all_files: Vec<ServerMeta> = tree.all_files()?.into_iter().cloned().collect();
         for meta in all_files {
             let id = meta.id();
@@ -134,12 +174,23 @@ where
                     db.shared_files
                         .remove(&Owner(user_access_info.encrypted_for), id)?;
                 }
+            } else if prior_deleted.contains(id) && !current_deleted.contains(id) {
+                for user_access_info in meta.user_access_keys() {
+                    if !user_access_info.deleted {
+                        db.shared_files
+                            .insert(Owner(user_access_info.encrypted_for), *id)?;
+                    }
+                }
             }
         }
 
         db.last_seen.insert(req_owner, get_time().0 as u64)?;
+        let update = MetadataUpdate::new(db, &updates, context.session);
+        update.record(db)?;
 
         tx.drop_safely()?;
+        drop(lock);
+        self.notify(update);
 
         Ok(())
     }
@@ -147,14 +198,34 @@ where
     pub async fn change_doc_v2(
         &self, context: RequestContext<ChangeDocRequestV2>,
     ) -> Result<(), ServerError<ChangeDocError>> {
-        use ChangeDocError::*;
         let ChangeDocRequestV2 { diff, new_content } = context.request;
+        let requester = Owner(context.public_key);
 
-        // Validate Diff
-        if diff.diff() != vec![Diff::Hmac] {
-            return Err(ClientError(DiffMalformed));
-        }
+        Self::check_new_size(&diff, &new_content)?;
+        self.change_doc_content(requester, context.session, diff, new_content, false)
+            .await
+    }
 
+    /// Like [Self::change_doc_v2], but the content is a delta against the document's current
+    /// content. We can't apply it, so it's stored as it is, and the content it applies to is kept
+    /// for as long as the delta is.
+    pub async fn change_doc_delta(
+        &self, context: RequestContext<ChangeDocDeltaRequest>,
+    ) -> Result<(), ServerError<ChangeDocError>> {
+        let ChangeDocDeltaRequest { diff, delta } = context.request;
+        let requester = Owner(context.public_key);
+
+        Self::check_new_size(&diff, &delta)?;
+        self.change_doc_content(requester, context.session, diff, delta, true)
+            .await
+    }
+
+    /// the size recorded in the new metadata is what's charged for, so it has to be the size of
+    /// what's uploaded
+    fn check_new_size(
+        diff: &FileDiff<SignedMeta>, new_content: &EncryptedDocument,
+    ) -> Result<(), ServerError<ChangeDocError>> {
+        use ChangeDocError::*;
         match diff.new.timestamped_value.value.doc_size() {
             Some(size) => {
                 if *size != new_content.value.len() {
@@ -168,14 +239,47 @@ where
                 }
             }
         }
+        Ok(())
+    }
+
+    async fn change_doc_content(
+        &self, requester: Owner, session: Option<Uuid>, diff: FileDiff<SignedMeta>,
+        new_content: EncryptedDocument, is_delta: bool,
+    ) -> Result<(), ServerError<ChangeDocError>> {
+        use ChangeDocError::*;
+
+        // Validate Diff
+        if diff.diff() != vec![Diff::Hmac] {
+            return Err(ClientError(DiffMalformed));
+        }
 
         let hmac_bytes = *diff.new.document_hmac().ok_or(ClientError(HmacMissing))?;
         let hmac = base64::encode_config(hmac_bytes, base64::URL_SAFE);
 
-        let requester = Owner(context.public_key);
         let id = *diff.id();
         let new_meta = diff.new.clone().add_time(get_time().0 as u64);
 
+        // the base comes from the request, so it's looked for before taking the db; whether it's
+        // still the document's content is checked with everything else below
+        let delta_base = if is_delta {
+            let base = diff
+                .old
+                .as_ref()
+                .and_then(|old| {
+                    Some(DeltaBase {
+                        hmac: *old.document_hmac()?,
+                        size_bytes: old.doc_size().unwrap_or_default() as u64,
+                    })
+                })
+                .ok_or(ClientError(BaseNotFound))?;
+            if !self.document_service.exists(&id, &base.hmac).await {
+                return Err(ClientError(BaseNotFound));
+            }
+            Some(base)
+        } else {
+            None
+        };
+
         // phase 1: validate request before io
         let mut lock = self.index_db.lock().await;
         let db = lock.deref_mut();
@@ -187,7 +291,15 @@ where
             .clone();
         let tree_owner = og_meta.owner();
 
+        if let Some(base) = delta_base {
+            if Self::delta_chain_len(db, id, base.hmac) >= MAX_DELTA_CHAIN {
+                return Err(ClientError(DeltaChainTooLong));
+            }
+        }
+
         let usage_cap = Self::get_cap(db, &tree_owner.0).map_err(|err| internal!("{:?}", err))?;
+        let stored_usage = Self::stored_usage(db, &tree_owner);
+        let groups = Self::groups_of(&db.groups, &requester);
 
         let tree = ServerTree::new(
             requester,
@@ -196,6 +308,7 @@ where
             &mut db.file_children,
             &mut db.metas,
         )?
+        .with_groups(&groups)
         .to_lazy();
 
         let current_meta = &tree
@@ -212,7 +325,7 @@ where
         }
 
         let mut tree = tree.stage(vec![new_meta.clone()]);
-        tree.validate(requester)?;
+        tree.validate_in_groups(requester, &groups)?;
 
         let mut tree = ServerTree::new(
             tree_owner,
@@ -223,9 +336,9 @@ where
         )?
         .to_lazy();
 
-        let old_usage = tree.calculate_usage(tree_owner)?;
+        let old_usage = tree.calculate_usage(tree_owner)? + stored_usage;
         let mut tree = tree.stage(vec![new_meta.clone()]); // todo check if this used to be stage
-        let new_usage = tree.calculate_usage(tree_owner)?;
+        let new_usage = tree.calculate_usage(tree_owner)? + stored_usage;
         debug!(?old_usage, ?new_usage, ?usage_cap, "usage caps on change doc");
 
         if new_usage > usage_cap && new_usage >= old_usage {
@@ -245,6 +358,8 @@ where
             let mut lock = self.index_db.lock().await;
             let db = lock.deref_mut();
             let tx = db.begin_transaction()?;
+            let stored_usage = Self::stored_usage(db, &tree_owner);
+            let groups = Self::groups_of(&db.groups, &requester);
 
             let mut tree = ServerTree::new(
                 requester,
@@ -253,6 +368,7 @@ where
                 &mut db.file_children,
                 &mut db.metas,
             )?
+            .with_groups(&groups)
             .to_lazy();
 
             if tree.calculate_deleted(&id)? {
@@ -270,50 +386,97 @@ where
                 }
             }
 
-            let old_usage = tree.calculate_usage(tree_owner)?;
+            let old_usage = tree.calculate_usage(tree_owner)? + stored_usage;
             let mut tree = tree.stage(vec![new_meta]);
-            let new_usage = tree.calculate_usage(tree_owner)?;
-            tree.validate(requester)?;
+            let new_usage = tree.calculate_usage(tree_owner)? + stored_usage;
+            tree.validate_in_groups(requester, &groups)?;
             if new_usage > usage_cap && new_usage >= old_usage {
                 warn!("user over cap");
                 return Err(ClientError(UsageIsOverDataCap));
             }
             tree.promote()?;
-
-            if let Some(old_hmac) = diff.old.and_then(|old| old.document_hmac().copied()) {
-                db.scheduled_file_cleanups
-                    .insert((id, old_hmac), get_time().0)?;
+            let update = MetadataUpdate::new(db, std::slice::from_ref(&diff), session);
+            update.record(db)?;
+
+            if let Some(old) = diff.old {
+                if let Some(old_hmac) = old.document_hmac().copied() {
+                    let version = DocumentVersion {
+                        hmac: old_hmac,
+                        size_bytes: old.doc_size().unwrap_or_default() as u64,
+                        replaced_at: get_time().0 as u64,
+                    };
+                    Self::retain_version(db, id, tree_owner, version, hmac_bytes)?;
+                }
             }
 
+            // the blob for this hmac was just replaced, so it's a delta only if this upload was
+            match delta_base {
+                Some(base) => db.doc_deltas.insert((id, hmac_bytes), base)?,
+                None => db.doc_deltas.remove(&(id, hmac_bytes))?,
+            };
+
             tx.drop_safely()?;
             drop(lock);
-            Ok(())
+            Ok(update)
         };
 
         let result = result.await;
 
         if result.is_err() {
-            // Cleanup the NEW file created if, for some reason, the tx failed
-            self.document_service.delete(&id, &hmac_bytes).await?;
-            debug!(?id, ?hmac, "Cleaned up new document contents after failed metadata update");
+            // a restored version has the same hmac as the version it was restored from, in which
+            // case the blob isn't ours to clean up
+            let is_version = self
+                .index_db
+                .lock()
+                .await
+                .document_versions
+                .get()
+                .get(&id)
+                .map(|versions| versions.iter().any(|version| version.hmac == hmac_bytes))
+                .unwrap_or_default();
+            if !is_version {
+                // Cleanup the NEW file created if, for some reason, the tx failed
+                self.document_service.delete(&id, &hmac_bytes).await?;
+                debug!(?id, ?hmac, "Cleaned up new document contents after failed metadata update");
+            }
         }
 
-        result?;
+        let update = result?;
+        self.notify(update);
 
         Ok(())
     }
 
+    /// Content stored as a delta or a chunk manifest is refused, because clients that fetch
+    /// documents this way can't read either.
     pub async fn get_document(
         &self, context: RequestContext<GetDocRequest>,
     ) -> Result<GetDocumentResponse, ServerError<GetDocumentError>> {
-        let request = &context.request;
-        let requester = Owner(context.public_key);
-        {
+        let GetDocRequest { id, hmac } = context.request;
+        let GetDocumentResponseV2 { content, .. } = self
+            .get_document_content(Owner(context.public_key), id, hmac, false)
+            .await?;
+        Ok(GetDocumentResponse { content })
+    }
+
+    pub async fn get_document_v2(
+        &self, context: RequestContext<GetDocRequestV2>,
+    ) -> Result<GetDocumentResponseV2, ServerError<GetDocumentError>> {
+        let GetDocRequestV2 { id, hmac } = context.request;
+        self.get_document_content(Owner(context.public_key), id, hmac, true)
+            .await
+    }
+
+    async fn get_document_content(
+        &self, requester: Owner, id: Uuid, hmac: DocumentHmac, formats_readable: bool,
+    ) -> Result<GetDocumentResponseV2, ServerError<GetDocumentError>> {
+        let format = {
             let mut lock = self.index_db.lock().await;
             let db = lock.deref_mut();
             let tx = db.begin_transaction()?;
 
-            let meta_exists = db.metas.get().get(&request.id).is_some();
+            let meta_exists = db.metas.get().get(&id).is_some();
+            let groups = Self::groups_of(&db.groups, &requester);
 
             let mut tree = ServerTree::new(
                 requester,
@@ -322,9 +485,10 @@ where
                 &mut db.file_children,
                 &mut db.metas,
             )?
+            .with_groups(&groups)
             .to_lazy();
 
-            if tree.maybe_find(&request.id).is_none() {
+            if tree.maybe_find(&id).is_none() {
                 return Err(if meta_exists {
                     ClientError(GetDocumentError::NotPermissioned)
                 } else {
@@ -332,18 +496,32 @@ where
                 });
             }
 
-            if tree.calculate_deleted(&request.id)? {
+            if tree.calculate_deleted(&id)? {
                 return Err(ClientError(GetDocumentError::DocumentNotFound));
             }
 
+            let format = if db
+                .chunk_manifests
+                .get()
+                .get(&id)
+                .is_some_and(|manifests| manifests.contains_key(&hmac))
+            {
+                ContentFormat::Manifest
+            } else if db.doc_deltas.get().contains_key(&(id, hmac)) {
+                ContentFormat::Delta
+            } else {
+                ContentFormat::Document
+            };
+
+            if !formats_readable && format != ContentFormat::Document {
+                return Err(ServerError::ClientUpdateRequired);
+            }
+
             tx.drop_safely()?;
+            format
         };
 
-        let Some(content) = self
-            .document_service
-            .maybe_get(&request.id, &request.hmac)
-            .await?
-        else {
+        let Some(content) = self.document_service.maybe_get(&id, &hmac).await? else {
             return Err(ClientError(GetDocumentError::DocumentNotFound));
         };
 
@@ -351,41 +529,195 @@ where
         let db = lock.deref_mut();
         let tx = db.begin_transaction()?;
 
-        if self.config.features.bandwidth_controls {
-            let mut server_wide = db.server_egress.get().cloned().unwrap_or_default();
-            let mut account_bandwidth = db
-                .egress_by_owner
-                .get()
-                .get(&requester)
-                .cloned()
-                .unwrap_or_default();
-            let account_bandwidth_cap = db
-                .accounts
-                .get()
-                .get(&requester)
-                .map(|account| account.billing_info.bandwidth_cap())
-                .unwrap_or_default();
+        if !self.record_egress(db, requester, content.value.len())? {
+            return Err(ClientError(GetDocumentError::BandwidthExceeded));
+        }
 
-            let doc_size = content.value.len();
+        tx.drop_safely()?;
 
-            if doc_size + server_wide.current_bandwidth() > SERVER_BANDWIDTH_CAP {
-                error!("Bandwidth caps are now being enforced");
-                if doc_size + account_bandwidth.current_bandwidth() > account_bandwidth_cap {
-                    error!("User bandwidth cap exceeded");
-                    return Err(ClientError(GetDocumentError::BandwidthExceeded));
-                }
+        Ok(GetDocumentResponseV2 { content, format })
+    }
+
+    /// Counts `size` bytes of egress against the server and `requester`. Returns false, without
+    /// counting anything, if bandwidth caps are being enforced and `requester` is over theirs.
+    pub fn record_egress(
+        &self, db: &mut ServerDb, requester: Owner, size: usize,
+    ) -> Result<bool, DbError> {
+        if !self.config.features.bandwidth_controls {
+            return Ok(true);
+        }
+
+        let mut server_wide = db.server_egress.get().cloned().unwrap_or_default();
+        let mut account_bandwidth = db
+            .egress_by_owner
+            .get()
+            .get(&requester)
+            .cloned()
+            .unwrap_or_default();
+        let account_bandwidth_cap = db
+            .accounts
+            .get()
+            .get(&requester)
+            .map(|account| account.billing_info.bandwidth_cap())
+            .unwrap_or_default();
+
+        if size + server_wide.current_bandwidth() > SERVER_BANDWIDTH_CAP {
+            error!("Bandwidth caps are now being enforced");
+            if size + account_bandwidth.current_bandwidth() > account_bandwidth_cap {
+                error!("User bandwidth cap exceeded");
+                return Ok(false);
             }
+        }
+
+        server_wide.increase_by(size);
+        account_bandwidth.increase_by(size);
+
+        db.server_egress.insert(server_wide)?;
+        db.egress_by_owner.insert(requester, account_bandwidth)?;
+
+        Ok(true)
+    }
+
+    pub async fn get_document_versions(
+        &self, context: RequestContext<GetDocVersionsRequest>,
+    ) -> Result<GetDocVersionsResponse, ServerError<GetDocVersionsError>> {
+        let request = &context.request;
+        let requester = Owner(context.public_key);
+
+        let mut lock = self.index_db.lock().await;
+        let db = lock.deref_mut();
+
+        let meta_exists = db.metas.get().get(&request.id).is_some();
+        let groups = Self::groups_of(&db.groups, &requester);
+
+        let mut tree = ServerTree::new(
+            requester,
+            &mut db.owned_files,
+            &mut db.shared_files,
+            &mut db.file_children,
+            &mut db.metas,
+        )?
+        .with_groups(&groups)
+        .to_lazy();
 
-            server_wide.increase_by(doc_size);
-            account_bandwidth.increase_by(doc_size);
+        if tree.maybe_find(&request.id).is_none() {
+            return Err(if meta_exists {
+                ClientError(GetDocVersionsError::NotPermissioned)
+            } else {
+                ClientError(GetDocVersionsError::DocumentNotFound)
+            });
+        }
 
-            db.server_egress.insert(server_wide)?;
-            db.egress_by_owner.insert(requester, account_bandwidth)?;
+        if tree.calculate_deleted(&request.id)? {
+            return Err(ClientError(GetDocVersionsError::DocumentNotFound));
         }
 
-        tx.drop_safely()?;
+        let versions = db
+            .document_versions
+            .get()
+            .get(&request.id)
+            .cloned()
+            .unwrap_or_default();
 
-        Ok(GetDocumentResponse { content })
+        Ok(GetDocVersionsResponse { versions })
+    }
+
+    /// Records `version` as the newest prior version of `id` and expires whatever the owner's
+    /// retention policy no longer covers. `current` is dropped from the history since the
+    /// document now points at it, which happens when a version is restored.
+    fn retain_version(
+        db: &mut ServerDb, id: Uuid, owner: Owner, version: DocumentVersion, current: DocumentHmac,
+    ) -> Result<(), DbError> {
+        let retention = db
+            .accounts
+            .get()
+            .get(&owner)
+            .map(|account| account.billing_info.version_retention())
+            .unwrap_or_else(|| SubscriptionProfile::default().version_retention());
+
+        let mut versions = db
+            .document_versions
+            .get()
+            .get(&id)
+            .cloned()
+            .unwrap_or_default();
+        versions.retain(|prior| prior.hmac != current && prior.hmac != version.hmac);
+        versions.insert(0, version);
+
+        let (keep, expired) = retention.split(versions, get_time().0 as u64);
+        for version in expired {
+            db.scheduled_file_cleanups
+                .insert((id, version.hmac), get_time().0)?;
+        }
+        db.document_versions.insert(id, keep)?;
+
+        Ok(())
+    }
+
+    /// how many deltas are stacked on top of the complete content `hmac` is materialized from
+    fn delta_chain_len(db: &ServerDb, id: Uuid, hmac: DocumentHmac) -> usize {
+        let mut len = 0;
+        let mut next = db.doc_deltas.get().get(&(id, hmac));
+        while let Some(base) = next {
+            len += 1;
+            next = db.doc_deltas.get().get(&(id, base.hmac));
+        }
+        len
+    }
+
+    /// bytes `owner` is charged for beyond the current content of their files
+    pub fn stored_usage(db: &ServerDb, owner: &Owner) -> u64 {
+        Self::version_usage(db, owner)
+            + Self::delta_base_usage(db, owner)
+            + Self::chunk_usage(db, owner)
+            + Self::public_link_usage(db, owner)
+    }
+
+    /// bytes held by contents of documents owned by `owner` that are kept only because a delta
+    /// applies to them
+    pub fn delta_base_usage(db: &ServerDb, owner: &Owner) -> u64 {
+        let Some(owned) = db.owned_files.get().get(owner) else {
+            return 0;
+        };
+        Self::delta_bases(db)
+            .into_iter()
+            .filter(|((id, _), _)| owned.contains(id))
+            .filter(|((id, hmac), _)| {
+                // the current content and retained versions are already charged for
+                let current =
+                    db.metas.get().get(id).and_then(|meta| meta.document_hmac()) == Some(hmac);
+                let retained =
+                    db.document_versions.get().get(id).is_some_and(|versions| {
+                        versions.iter().any(|version| version.hmac == *hmac)
+                    }) && !db.scheduled_file_cleanups.get().contains_key(&(*id, *hmac));
+                !current && !retained
+            })
+            .map(|(_, size)| size)
+            .sum()
+    }
+
+    /// bytes held by retained versions of documents owned by `owner`
+    pub fn version_usage(db: &ServerDb, owner: &Owner) -> u64 {
+        let Some(owned) = db.owned_files.get().get(owner) else {
+            return 0;
+        };
+        // versions of trashed documents are already on their way out and aren't charged for
+        owned
+            .iter()
+            .filter_map(|id| {
+                db.document_versions
+                    .get()
+                    .get(id)
+                    .map(|versions| (id, versions))
+            })
+            .flat_map(|(id, versions)| versions.iter().map(move |version| (id, version)))
+            .filter(|(id, version)| {
+                !db.scheduled_file_cleanups
+                    .get()
+                    .contains_key(&(**id, version.hmac))
+            })
+            .map(|(_, version)| version.size_bytes)
+            .sum()
     }
 
     pub async fn get_file_ids(
@@ -394,6 +726,7 @@ where
         let owner = Owner(context.public_key);
         let mut db = self.index_db.lock().await;
         let db = db.deref_mut();
+        let groups = Self::groups_of(&db.groups, &owner);
 
         Ok(GetFileIdsResponse {
             ids: ServerTree::new(
@@ -403,6 +736,7 @@ where
                 &mut db.file_children,
                 &mut db.metas,
             )?
+            .with_groups(&groups)
             .ids()
             .into_iter()
             .collect(),
@@ -417,6 +751,10 @@ where
 
         let mut db = self.index_db.lock().await;
         let db = db.deref_mut();
+        let groups = Self::groups_of(&db.groups, &owner);
+        // files shared with a group before the requester joined it are new to them
+        let joined_groups =
+            Self::groups_joined_since(&db.groups, &owner, request.since_metadata_version);
         let mut tree = ServerTree::new(
             owner,
             &mut db.owned_files,
@@ -424,18 +762,24 @@ where
             &mut db.file_children,
             &mut db.metas,
         )?
+        .with_groups(&groups)
         .to_lazy();
 
         let mut result_ids = HashSet::new();
         for id in tree.ids() {
             let file = tree.find(&id)?;
-            if file.version >= request.since_metadata_version {
+            let shared_with_joined_group = file
+                .user_access_keys()
+                .iter()
+                .any(|k| !k.deleted && joined_groups.contains(&Owner(k.encrypted_for)));
+            if file.version >= request.since_metadata_version || shared_with_joined_group {
                 result_ids.insert(id);
                 if file.owner() != owner
-                    && file
-                        .user_access_keys()
-                        .iter()
-                        .any(|k| !k.deleted && k.encrypted_for == context.public_key)
+                    && file.user_access_keys().iter().any(|k| {
+                        !k.deleted
+                            && (k.encrypted_for == context.public_key
+                                || groups.contains(&Owner(k.encrypted_for)))
+                    })
                 {
                     result_ids.insert(id);
                     result_ids.extend(tree.descendants(&id)?);
@@ -514,6 +858,10 @@ where
                     .remove(&id)?
                     .ok_or(ClientError(AdminDisappearFileError::FileNonexistent))?;
 
+                if let Some(versions) = db.document_versions.remove(&id)? {
+                    docs_to_delete.extend(versions.into_iter().map(|version| (id, version.hmac)));
+                }
+
                 // maintain index: owned_files
                 let owner = meta.owner();
 
@@ -585,13 +933,20 @@ where
             .get(&request.username)
             .ok_or(ClientError(AdminValidateAccountError::UserNotFound))?;
 
-        Ok(self.validate_account_helper(&mut db, owner)?)
+        let (mut result, documents) = Self::validate_account_helper(&mut db, owner)?;
+        drop(db);
+
+        result.documents_missing_content = self.missing_contents(documents).await;
+        Ok(result)
     }
 
+    /// Validates `owner`'s tree. The documents whose content should be stored are returned
+    /// rather than checked, so that they can be checked without holding the db.
     pub fn validate_account_helper(
-        &self, db: &mut ServerDb, owner: Owner,
-    ) -> LbResult<AdminValidateAccount> {
+        db: &mut ServerDb, owner: Owner,
+    ) -> LbResult<(AdminValidateAccount, Vec<(Uuid, DocumentHmac)>)> {
         let mut result = AdminValidateAccount::default();
+        let mut documents = vec![];
 
         let mut tree = ServerTree::new(
             owner,
@@ -609,13 +964,7 @@ where
                     if file.file.timestamped_value.value.doc_size().is_none() {
                         result.documents_missing_size.push(id);
                     }
-
-                    if !self
-                        .document_service
-                        .exists(&id, file.document_hmac().unwrap())
-                    {
-                        result.documents_missing_content.push(id);
-                    }
+                    documents.push((id, *file.document_hmac().unwrap()));
                 }
             }
         }
@@ -633,14 +982,25 @@ where
             },
         }
 
-        Ok(result)
+        Ok((result, documents))
+    }
+
+    /// the documents whose content isn't stored
+    async fn missing_contents(&self, documents: Vec<(Uuid, DocumentHmac)>) -> Vec<Uuid> {
+        let mut missing = vec![];
+        for (id, hmac) in documents {
+            if !self.document_service.exists(&id, &hmac).await {
+                missing.push(id);
+            }
+        }
+        missing
     }
 
     pub async fn admin_validate_server(
         &self, context: RequestContext<AdminValidateServerRequest>,
     ) -> Result<AdminValidateServer, ServerError<AdminValidateServerError>> {
-        let mut db = self.index_db.lock().await;
-        let db = db.deref_mut();
+        let mut lock = self.index_db.lock().await;
+        let db = lock.deref_mut();
 
         if !Self::is_admin::<AdminValidateServerError>(
             db,
@@ -668,14 +1028,11 @@ where
             }
         }
 
-        // validate accounts
+        // validate accounts; their documents' contents are checked once the db is released
+        let mut account_validations = vec![];
         for (owner, account) in db.accounts.get().clone() {
-            let validation = self.validate_account_helper(db, owner)?;
-            if !validation.is_empty() {
-                result
-                    .users_with_validation_failures
-                    .insert(account.username, validation);
-            }
+            let (validation, documents) = Self::validate_account_helper(db, owner)?;
+            account_validations.push((account.username, validation, documents));
         }
 
         // validate index: usernames
@@ -815,15 +1172,29 @@ where
             }
         }
 
-        // validate presence of documents
-        for (id, meta) in db.metas.get().clone() {
-            if let Some(hmac) = meta.document_hmac() {
-                if !deleted_ids.contains(&id) && !self.document_service.exists(&id, hmac) {
-                    result.files_with_hmacs_and_no_contents.insert(id);
-                }
+        let documents: Vec<(Uuid, DocumentHmac)> = db
+            .metas
+            .get()
+            .iter()
+            .filter(|(id, _)| !deleted_ids.contains(id))
+            .filter_map(|(id, meta)| Some((*id, *meta.document_hmac()?)))
+            .collect();
+        drop(lock);
+
+        for (username, mut validation, documents) in account_validations {
+            validation.documents_missing_content = self.missing_contents(documents).await;
+            if !validation.is_empty() {
+                result
+                    .users_with_validation_failures
+                    .insert(username, validation);
             }
         }
 
+        // validate presence of documents
+        result
+            .files_with_hmacs_and_no_contents
+            .extend(self.missing_contents(documents).await);
+
         Ok(result)
     }
 
@@ -893,6 +1264,11 @@ where
                 for owner in db.accounts.get().clone().keys() {
                     db.shared_files.create_key(*owner)?;
                 }
+                for group in db.groups.get().clone().values() {
+                    for public_key in group.public_keys() {
+                        db.shared_files.create_key(Owner(public_key))?;
+                    }
+                }
                 for (id, file) in db.metas.get().clone() {
                     // check for implicit deletion (can't use server tree which depends on index)
                     let mut deleted = false;
@@ -939,6 +1315,82 @@ where
         }
         Ok(())
     }
+
+    /// Copies the document contents in `FILES_PATH` to the configured object storage, skipping
+    /// those already there, so that a server can move off of disk without losing anything.
+    pub async fn admin_migrate_documents(
+        &self, context: RequestContext<AdminMigrateDocumentsRequest>,
+    ) -> Result<AdminMigrateDocumentsResponse, ServerError<AdminMigrateDocumentsError>> {
+        {
+            let db = self.index_db.lock().await;
+            if !Self::is_admin::<AdminMigrateDocumentsError>(
+                &db,
+                &context.public_key,
+                &self.config.admin.admins,
+            )? {
+                return Err(ClientError(AdminMigrateDocumentsError::NotPermissioned));
+            }
+        }
+        let DocumentBackend::S3(s3) = &self.config.files.backend else {
+            return Err(ClientError(AdminMigrateDocumentsError::NotUsingObjectStorage));
+        };
+        // the bucket alone, without reading through to what's being copied
+        let bucket = S3Documents::from(s3);
+        let referenced = Self::referenced_contents(self.index_db.lock().await.deref_mut());
+
+        let mut result = AdminMigrateDocumentsResponse::default();
+        let mut entries = tokio::fs::read_dir(&self.config.files.path).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name();
+            let Some((id, hmac)) = name.to_str().and_then(OnDiskDocuments::parse_file_name) else {
+                warn!(?name, "skipping a file that isn't a document");
+                continue;
+            };
+
+            if !referenced.contains(&(id, hmac)) {
+                result.unreferenced += 1;
+                continue;
+            }
+            if bucket.exists(&id, &hmac).await {
+                result.already_present += 1;
+                continue;
+            }
+            bucket.copy_file(&id, &hmac, &entry.path()).await?;
+            result.copied += 1;
+        }
+
+        Ok(result)
+    }
+
+    /// every stored content something refers to: documents and their retained versions, the
+    /// contents deltas apply to, chunks and public link snapshots
+    fn referenced_contents(db: &ServerDb) -> HashSet<(Uuid, DocumentHmac)> {
+        let mut referenced = HashSet::new();
+        for (id, meta) in db.metas.get() {
+            if let Some(hmac) = meta.document_hmac() {
+                referenced.insert((*id, *hmac));
+            }
+        }
+        for (id, versions) in db.document_versions.get() {
+            referenced.extend(versions.iter().map(|version| (*id, version.hmac)));
+        }
+        for ((id, _), base) in db.doc_deltas.get() {
+            referenced.insert((*id, base.hmac));
+        }
+        for (id, chunks) in db.chunks.get() {
+            referenced.extend(chunks.keys().map(|chunk| (*id, *chunk)));
+        }
+        for (id, manifests) in db.chunk_manifests.get() {
+            for (manifest, chunks) in manifests {
+                referenced.insert((*id, *manifest));
+                referenced.extend(chunks.iter().map(|chunk| (*id, *chunk)));
+            }
+        }
+        for id in db.public_links.get().keys() {
+            referenced.insert((*id, SNAPSHOT_HMAC));
+        }
+        referenced
+    }
 }
 
 fn insert<K: Hash + Eq, V: Hash + Eq>(map: &mut HashMap<K, HashSet<V>>, k: K, v: V) {
@@ -1,15 +1,22 @@
+use std::collections::HashMap;
+use std::ops::DerefMut;
 use std::time::Duration;
 use tracing::{debug, error, info};
 
+use db_rs::DbError;
 use lb_rs::model::clock::get_time;
+use lb_rs::model::file_like::FileLike;
+use lb_rs::model::file_metadata::DocumentHmac;
+use uuid::Uuid;
 
 use crate::{
     ServerState,
     billing::{
-        app_store_client::AppStoreClient, google_play_client::GooglePlayClient,
-        stripe_client::StripeClient,
+        app_store_client::AppStoreClient, billing_model::SubscriptionProfile,
+        google_play_client::GooglePlayClient, stripe_client::StripeClient,
     },
     document_service::DocumentService,
+    schema::ServerDb,
 };
 
 impl<S, A, G, D> ServerState<S, A, G, D>
@@ -34,12 +41,25 @@ where
 
     pub async fn garbage_collect(&self) {
         let mut db = self.index_db.lock().await;
+        if let Err(e) = Self::purge_expired_trash(db.deref_mut(), self.config.files.trash_period) {
+            error!("failed to purge expired trash {e:?}");
+        }
+        if let Err(e) = Self::expire_versions(db.deref_mut()) {
+            error!("failed to expire document versions {e:?}");
+        }
+        if let Err(e) = Self::release_chunks(db.deref_mut()) {
+            error!("failed to release document chunks {e:?}");
+        }
+        if let Err(e) = Self::release_public_links(db.deref_mut()) {
+            error!("failed to release public links {e:?}");
+        }
+        let delta_bases = Self::delta_bases(db.deref_mut());
         let files = db.scheduled_file_cleanups.get();
         let mut cleaned = 0;
         let mut skipped = 0;
         let mut remove = vec![];
         for ((id, hmac), time) in files {
-            if get_time().0 - time > 1000 * 60 * 5 {
+            if get_time().0 - time > 1000 * 60 * 5 && !delta_bases.contains_key(&(*id, *hmac)) {
                 cleaned += 1;
                 if let Err(e) = self.document_service.delete::<()>(id, hmac).await {
                     error!(
@@ -57,8 +77,68 @@ where
 
         for (id, hmac) in remove {
             db.scheduled_file_cleanups.remove(&(id, hmac)).unwrap();
+            if let Err(e) = db.doc_deltas.remove(&(id, hmac)) {
+                error!("failed to forget the delta base of {id} {e:?}");
+            }
         }
 
         info!("cleaned {cleaned}, skipped {skipped}");
     }
+
+    /// Contents that deltas are applied to, with their sizes, which are kept while any delta that
+    /// isn't being cleaned up refers to them, even if their own cleanup is due.
+    pub fn delta_bases(db: &ServerDb) -> HashMap<(Uuid, DocumentHmac), u64> {
+        let now = get_time().0;
+        let mut bases = HashMap::new();
+        for ((id, hmac), base) in db.doc_deltas.get() {
+            let cleanup_due = db
+                .scheduled_file_cleanups
+                .get()
+                .get(&(*id, *hmac))
+                .is_some_and(|time| now - time > 1000 * 60 * 5);
+            if cleanup_due {
+                continue;
+            }
+
+            let mut next = Some(*base);
+            while let Some(base) = next {
+                if bases.insert((*id, base.hmac), base.size_bytes).is_some() {
+                    break;
+                }
+                next = db.doc_deltas.get().get(&(*id, base.hmac)).copied();
+            }
+        }
+        bases
+    }
+
+    /// Schedules cleanup for document versions that have aged out of their owner's retention
+    /// policy. Limits on the number of versions are applied when a version is created.
+    fn expire_versions(db: &mut ServerDb) -> Result<(), DbError> {
+        let now = get_time().0;
+        let mut updates = vec![];
+        for (id, versions) in db.document_versions.get() {
+            let Some(meta) = db.metas.get().get(id) else {
+                continue;
+            };
+            let retention = db
+                .accounts
+                .get()
+                .get(&meta.owner())
+                .map(|account| account.billing_info.version_retention())
+                .unwrap_or_else(|| SubscriptionProfile::default().version_retention());
+            let (keep, expired) = retention.split(versions.clone(), now as u64);
+            if !expired.is_empty() {
+                updates.push((*id, keep, expired));
+            }
+        }
+
+        for (id, keep, expired) in updates {
+            for version in expired {
+                db.scheduled_file_cleanups.insert((id, version.hmac), now)?;
+            }
+            db.document_versions.insert(id, keep)?;
+        }
+
+        Ok(())
+    }
 }
@@ -0,0 +1,218 @@
+use crate::ServerError::ClientError;
+use crate::billing::app_store_client::AppStoreClient;
+use crate::billing::google_play_client::GooglePlayClient;
+use crate::billing::stripe_client::StripeClient;
+use crate::document_service::DocumentService;
+use crate::notification_service::MetadataUpdate;
+use crate::{RequestContext, ServerError, ServerState};
+use db_rs::{Db, LookupTable};
+use lb_rs::model::api::*;
+use lb_rs::model::clock::get_time;
+use lb_rs::model::file_metadata::Owner;
+use std::collections::HashSet;
+use std::mem;
+use std::ops::DerefMut;
+use uuid::Uuid;
+
+impl<S, A, G, D> ServerState<S, A, G, D>
+where
+    S: StripeClient,
+    A: AppStoreClient,
+    G: GooglePlayClient,
+    D: DocumentService,
+{
+    pub async fn create_group(
+        &self, context: RequestContext<CreateGroupRequest>,
+    ) -> Result<(), ServerError<CreateGroupError>> {
+        let CreateGroupRequest { id, name, public_key, encrypted_private_key } = context.request;
+        let requester = Owner(context.public_key);
+
+        let mut lock = self.index_db.lock().await;
+        let db = lock.deref_mut();
+        let tx = db.begin_transaction()?;
+
+        let group_owner = Owner(public_key);
+        if db.groups.get().contains_key(&id)
+            || db.accounts.get().contains_key(&group_owner)
+            || db.shared_files.get().contains_key(&group_owner)
+        {
+            return Err(ClientError(CreateGroupError::GroupExists));
+        }
+
+        let group = GroupInfo {
+            id,
+            name,
+            public_key,
+            previous_public_keys: vec![],
+            owner: requester,
+            members: vec![GroupMember {
+                public_key: requester.0,
+                encrypted_private_key,
+                previous_private_keys: vec![],
+                added_at: get_time().0 as u64,
+            }],
+        };
+        db.groups.insert(id, group)?;
+        db.shared_files.create_key(group_owner)?;
+
+        tx.drop_safely()?;
+        Ok(())
+    }
+
+    pub async fn add_group_member(
+        &self, context: RequestContext<AddGroupMemberRequest>,
+    ) -> Result<(), ServerError<AddGroupMemberError>> {
+        use AddGroupMemberError::*;
+        let AddGroupMemberRequest {
+            group_id,
+            member,
+            encrypted_private_key,
+            previous_private_keys,
+        } = context.request;
+        let requester = Owner(context.public_key);
+
+        let mut lock = self.index_db.lock().await;
+        let db = lock.deref_mut();
+        let tx = db.begin_transaction()?;
+
+        let mut group = db
+            .groups
+            .get()
+            .get(&group_id)
+            .cloned()
+            .ok_or(ClientError(GroupNotFound))?;
+        if group.owner != requester {
+            return Err(ClientError(NotPermissioned));
+        }
+        if !db.accounts.get().contains_key(&Owner(member)) {
+            return Err(ClientError(UserNotFound));
+        }
+        if group.members.iter().any(|m| m.public_key == member) {
+            return Err(ClientError(AlreadyMember));
+        }
+        if previous_private_keys.len() != group.previous_public_keys.len() {
+            return Err(ClientError(KeysOutdated));
+        }
+
+        group.members.push(GroupMember {
+            public_key: member,
+            encrypted_private_key,
+            previous_private_keys,
+            added_at: get_time().0 as u64,
+        });
+        db.groups.insert(group_id, group)?;
+
+        tx.drop_safely()?;
+        drop(lock);
+        // the group's files are new to them
+        self.notify(MetadataUpdate { owners: [Owner(member)].into(), ..Default::default() });
+        Ok(())
+    }
+
+    /// Removes a member from a group, after which they're no longer sent the group's files, and
+    /// replaces the group's keypair with one only the remaining members can decrypt, so files
+    /// shared with the group from now on are shared with a key the removed member never had. Like
+    /// any other share, this doesn't take back the keys they had; to make sure they can't read
+    /// what's written to files already shared with the group, their owners should rotate their
+    /// keys.
+    pub async fn remove_group_member(
+        &self, context: RequestContext<RemoveGroupMemberRequest>,
+    ) -> Result<(), ServerError<RemoveGroupMemberError>> {
+        use RemoveGroupMemberError::*;
+        let RemoveGroupMemberRequest { group_id, member, public_key, encrypted_private_keys } =
+            context.request;
+        let requester = Owner(context.public_key);
+
+        let mut lock = self.index_db.lock().await;
+        let db = lock.deref_mut();
+        let tx = db.begin_transaction()?;
+
+        let mut group = db
+            .groups
+            .get()
+            .get(&group_id)
+            .cloned()
+            .ok_or(ClientError(GroupNotFound))?;
+        if group.owner != requester {
+            return Err(ClientError(NotPermissioned));
+        }
+        if group.owner.0 == member {
+            return Err(ClientError(CannotRemoveOwner));
+        }
+        if !group.members.iter().any(|m| m.public_key == member) {
+            return Err(ClientError(NotMember));
+        }
+
+        group.members.retain(|m| m.public_key != member);
+        // fail closed: every remaining member gets the new key, or nothing changes
+        if encrypted_private_keys.len() != group.members.len()
+            || db.accounts.get().contains_key(&Owner(public_key))
+            || db.shared_files.get().contains_key(&Owner(public_key))
+        {
+            return Err(ClientError(KeysOutdated));
+        }
+
+        for m in &mut group.members {
+            let (_, encrypted_private_key) = encrypted_private_keys
+                .iter()
+                .find(|(pk, _)| *pk == m.public_key)
+                .cloned()
+                .ok_or(ClientError(KeysOutdated))?;
+            let previous = mem::replace(&mut m.encrypted_private_key, encrypted_private_key);
+            m.previous_private_keys.push(previous);
+        }
+        group.previous_public_keys.push(group.public_key);
+        group.public_key = public_key;
+        let mut owners: HashSet<Owner> =
+            group.members.iter().map(|m| Owner(m.public_key)).collect();
+        owners.insert(Owner(member));
+        db.groups.insert(group_id, group)?;
+        db.shared_files.create_key(Owner(public_key))?;
+
+        tx.drop_safely()?;
+        drop(lock);
+        // the removed member loses the group's files, and the rest have a new key for it
+        self.notify(MetadataUpdate { owners, ..Default::default() });
+        Ok(())
+    }
+
+    pub async fn get_groups(
+        &self, context: RequestContext<GetGroupsRequest>,
+    ) -> Result<GetGroupsResponse, ServerError<GetGroupsError>> {
+        let requester = context.public_key;
+        let db = self.index_db.lock().await;
+
+        let groups = db
+            .groups
+            .get()
+            .values()
+            .filter(|group| group.members.iter().any(|m| m.public_key == requester))
+            .cloned()
+            .collect();
+
+        Ok(GetGroupsResponse { groups })
+    }
+
+    /// the public keys of the groups `member` is in, whose shared files they have access to
+    pub fn groups_of(groups: &LookupTable<Uuid, GroupInfo>, member: &Owner) -> Vec<Owner> {
+        Self::groups_joined_since(groups, member, 0)
+    }
+
+    /// the public keys, current and previous, of the groups `member` was added to at or after
+    /// `since`
+    pub fn groups_joined_since(
+        groups: &LookupTable<Uuid, GroupInfo>, member: &Owner, since: u64,
+    ) -> Vec<Owner> {
+        groups
+            .get()
+            .values()
+            .filter(|group| {
+                group
+                    .members
+                    .iter()
+                    .any(|m| m.public_key == member.0 && m.added_at >= since)
+            })
+            .flat_map(|group| group.public_keys().map(Owner))
+            .collect()
+    }
+}
@@ -0,0 +1,97 @@
+use crate::ServerError::ClientError;
+use crate::billing::app_store_client::AppStoreClient;
+use crate::billing::google_play_client::GooglePlayClient;
+use crate::billing::stripe_client::StripeClient;
+use crate::document_service::DocumentService;
+use crate::schema::ServerDb;
+use crate::{RequestContext, ServerError, ServerState};
+use lb_rs::model::api::*;
+use lb_rs::model::clock::get_time;
+use lb_rs::model::file_metadata::Owner;
+use uuid::Uuid;
+
+/// long enough not to be guessed, short enough to be read out to someone
+const INVITE_CODE_LEN: usize = 16;
+
+impl<S, A, G, D> ServerState<S, A, G, D>
+where
+    S: StripeClient,
+    A: AppStoreClient,
+    G: GooglePlayClient,
+    D: DocumentService,
+{
+    pub async fn admin_create_invite(
+        &self, context: RequestContext<AdminCreateInviteRequest>,
+    ) -> Result<AdminCreateInviteResponse, ServerError<AdminCreateInviteError>> {
+        let request = context.request;
+        let mut db = self.index_db.lock().await;
+        if !Self::is_admin::<AdminCreateInviteError>(
+            &db,
+            &context.public_key,
+            &self.config.admin.admins,
+        )? {
+            return Err(ClientError(AdminCreateInviteError::NotPermissioned));
+        }
+
+        let mut code = Uuid::new_v4().simple().to_string();
+        code.truncate(INVITE_CODE_LEN);
+        let invite = InviteInfo {
+            code,
+            created_by: Owner(context.public_key),
+            created_at: get_time().0 as u64,
+            expires_at: request.expires_at,
+            max_uses: request.max_uses,
+            uses: 0,
+        };
+        db.invites.insert(invite.code.clone(), invite.clone())?;
+
+        Ok(AdminCreateInviteResponse { invite })
+    }
+
+    pub async fn admin_revoke_invite(
+        &self, context: RequestContext<AdminRevokeInviteRequest>,
+    ) -> Result<(), ServerError<AdminRevokeInviteError>> {
+        let mut db = self.index_db.lock().await;
+        if !Self::is_admin::<AdminRevokeInviteError>(
+            &db,
+            &context.public_key,
+            &self.config.admin.admins,
+        )? {
+            return Err(ClientError(AdminRevokeInviteError::NotPermissioned));
+        }
+
+        // accounts already created with the code keep their invited-by
+        db.invites
+            .remove(&context.request.code)?
+            .ok_or(ClientError(AdminRevokeInviteError::InviteNotFound))?;
+
+        Ok(())
+    }
+
+    pub async fn admin_list_invites(
+        &self, context: RequestContext<AdminListInvitesRequest>,
+    ) -> Result<AdminListInvitesResponse, ServerError<AdminListInvitesError>> {
+        let db = self.index_db.lock().await;
+        if !Self::is_admin::<AdminListInvitesError>(
+            &db,
+            &context.public_key,
+            &self.config.admin.admins,
+        )? {
+            return Err(ClientError(AdminListInvitesError::NotPermissioned));
+        }
+
+        let mut invites: Vec<InviteInfo> = db.invites.get().values().cloned().collect();
+        invites.sort_by_key(|invite| invite.created_at);
+
+        Ok(AdminListInvitesResponse { invites })
+    }
+
+    /// The invite with this code, if it can still be used to create an account.
+    pub fn usable_invite(db: &ServerDb, code: &str, now: u64) -> Option<InviteInfo> {
+        db.invites
+            .get()
+            .get(code)
+            .filter(|invite| invite.is_usable(now))
+            .cloned()
+    }
+}
@@ -1,10 +1,11 @@
 use billing::app_store_client::AppStoreClient;
 use billing::google_play_client::GooglePlayClient;
 use billing::stripe_client::StripeClient;
-use defense::IpData;
+use defense::{IpData, RateLimiter};
 use document_service::DocumentService;
 use lb_rs::model::clock;
 use lb_rs::model::errors::LbResult;
+use notification_service::Updates;
 use schema::ServerDb;
 use std::collections::VecDeque;
 use std::env;
@@ -12,6 +13,7 @@ use std::fmt::Debug;
 use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio::sync::Mutex;
+use uuid::Uuid;
 
 use lb_rs::model::api::{ErrorWrapper, Request, RequestWrapper};
 use lb_rs::model::pubkey;
@@ -44,6 +46,8 @@ where
     pub document_service: D,
     pub discord_client: reqwest::Client,
     pub recent_new_account_ips: Arc<Mutex<VecDeque<IpData>>>,
+    pub rate_limiter: Arc<Mutex<RateLimiter>>,
+    pub updates: Updates,
 }
 
 #[derive(Clone)]
@@ -51,12 +55,16 @@ pub struct RequestContext<TRequest> {
     pub request: TRequest,
     pub public_key: PublicKey,
     pub ip: Option<SocketAddr>,
+    /// the running client the request came from, if it said
+    pub session: Option<Uuid>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum ServerError<U: Debug> {
     ClientError(U),
     InternalError(String),
+    /// the response would be content the requesting client can't read
+    ClientUpdateRequired,
 }
 
 #[macro_export]
@@ -102,6 +110,7 @@ where
 
 pub mod account_service;
 pub mod billing;
+pub mod chunk_service;
 pub mod config;
 pub mod debug_info;
 pub mod defense;
@@ -109,9 +118,14 @@ pub mod document_service;
 pub mod error_handler;
 pub mod file_service;
 pub mod garbage_worker;
+pub mod group_service;
+pub mod invite_service;
 pub mod loggers;
 pub mod metrics;
+pub mod notification_service;
+pub mod public_link_service;
 pub mod router_service;
 pub mod schema;
 pub mod static_files;
+pub mod trash_service;
 pub mod utils;
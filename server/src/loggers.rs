@@ -1,7 +1,7 @@
 use std::backtrace::Backtrace;
 use std::fmt::{Debug, Write};
+use std::panic;
 use std::time::SystemTime;
-use std::{env, panic};
 
 use serde::Serialize;
 
@@ -23,12 +23,12 @@ use pagerduty_rs::eventsv2async::EventsV2;
 use pagerduty_rs::types::{AlertTrigger, AlertTriggerPayload, Event as PagerEvent, Severity};
 
 use crate::CARGO_PKG_VERSION;
-use crate::config::Config;
+use crate::config::{self, Config};
 
 static LOG_FILE: &str = "lockbook_server.log";
 
 pub fn init(config: &Config) {
-    let log_level = env::var("LOG_LEVEL")
+    let log_level = config::var("LOG_LEVEL")
         .ok()
         .and_then(|s| s.as_str().parse().ok())
         .unwrap_or(LevelFilter::DEBUG);
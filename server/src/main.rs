@@ -1,16 +1,20 @@
-#![recursion_limit = "256"]
+#![recursion_limit = "512"]
 
 use db_rs::Db;
-use lockbook_server_lib::billing::google_play_client::get_google_play_client;
-use lockbook_server_lib::config::Config;
-use lockbook_server_lib::document_service::OnDiskDocuments;
+use lockbook_server_lib::billing::app_store_client::AppStoreClient;
+use lockbook_server_lib::billing::google_play_client::{GooglePlayClient, get_google_play_client};
+use lockbook_server_lib::billing::nop_client::Nop;
+use lockbook_server_lib::billing::stripe_client::StripeClient;
+use lockbook_server_lib::config::{Config, DocumentBackend};
+use lockbook_server_lib::document_service::{DocumentService, OnDiskDocuments, S3Documents};
 use lockbook_server_lib::router_service::{
     app_store_notification_webhooks, build_info, core_routes, get_metrics,
-    google_play_notification_webhooks, stripe_webhooks,
+    google_play_notification_webhooks, public_link_snapshot, stripe_webhooks,
 };
 use lockbook_server_lib::schema::{ServerDb, ServerV5};
 use lockbook_server_lib::*;
 use static_files::static_routes;
+use std::env;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use tracing::*;
@@ -18,16 +22,13 @@ use warp::Filter;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let cfg = Config::from_env_vars();
+    // self-hosted servers are typically configured with a file, rather than the environment
+    let cfg = match env::args().nth(1) {
+        Some(path) => Config::from_file(path),
+        None => Config::from_env_vars(),
+    };
     loggers::init(&cfg);
 
-    let config = cfg.clone();
-
-    let stripe_client = stripe::Client::new(&cfg.billing.stripe.stripe_secret);
-    let google_play_client = get_google_play_client(&cfg.billing.google.service_account_key).await;
-    let app_store_client = reqwest::Client::new();
-    let discord_client = reqwest::Client::new();
-
     let index_db = ServerV5::init(db_rs::Config::in_folder(&cfg.index_db.db_location))
         .expect("Failed to load index_db");
     if index_db.incomplete_write().unwrap() {
@@ -36,7 +37,42 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let index_db = Arc::new(Mutex::new(index_db));
     spawn_compacter(&cfg, &index_db);
 
-    let document_service = OnDiskDocuments::from(&config);
+    match &cfg.files.backend {
+        DocumentBackend::Disk => serve(cfg.clone(), index_db, OnDiskDocuments::from(&cfg)).await,
+        DocumentBackend::S3(s3) => {
+            // contents that haven't been migrated yet are still on disk
+            let documents = S3Documents::from(s3).reading_through(OnDiskDocuments::from(&cfg));
+            serve(cfg.clone(), index_db, documents).await
+        }
+    }
+
+    Ok(())
+}
+
+async fn serve<D: DocumentService>(
+    cfg: Config, index_db: Arc<Mutex<ServerDb>>, document_service: D,
+) {
+    if cfg.server.self_hosted {
+        return run(cfg, index_db, Nop {}, Nop {}, Nop {}, document_service).await;
+    }
+
+    let stripe_client = stripe::Client::new(&cfg.billing.stripe.stripe_secret);
+    let google_play_client = get_google_play_client(&cfg.billing.google.service_account_key).await;
+    let app_store_client = reqwest::Client::new();
+    run(cfg, index_db, stripe_client, app_store_client, google_play_client, document_service).await
+}
+
+async fn run<S, A, G, D>(
+    cfg: Config, index_db: Arc<Mutex<ServerDb>>, stripe_client: S, app_store_client: A,
+    google_play_client: G, document_service: D,
+) where
+    S: StripeClient,
+    A: AppStoreClient,
+    G: GooglePlayClient,
+    D: DocumentService,
+{
+    let config = cfg.clone();
+    let discord_client = reqwest::Client::new();
 
     let server_state = Arc::new(ServerState {
         config,
@@ -47,6 +83,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         document_service,
         discord_client,
         recent_new_account_ips: Default::default(),
+        rate_limiter: Default::default(),
+        updates: notification_service::updates(),
     });
 
     let routes = core_routes(&server_state)
@@ -54,6 +92,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         .or(stripe_webhooks(&server_state))
         .or(google_play_notification_webhooks(&server_state))
         .or(app_store_notification_webhooks(&server_state))
+        .or(public_link_snapshot(&server_state))
         .or(static_routes());
 
     let server = warp::serve(routes);
@@ -62,6 +101,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
 
     server_state.start_metrics_worker();
     server_state.start_garbage_worker();
+    server_state.start_rate_limit_pruner();
 
     // metrics endpoint to be served anauthenticated, locally, only
     tokio::spawn(warp::serve(get_metrics()).run(([127, 0, 0, 1], 8080)));
@@ -85,8 +125,6 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
             server.run(([0, 0, 0, 0], cfg.server.port)).await
         }
     };
-
-    Ok(())
 }
 
 fn spawn_compacter(cfg: &Config, db: &Arc<Mutex<ServerDb>>) {
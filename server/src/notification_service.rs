@@ -0,0 +1,175 @@
+use crate::ServerError::ClientError;
+use crate::billing::app_store_client::AppStoreClient;
+use crate::billing::google_play_client::GooglePlayClient;
+use crate::billing::stripe_client::StripeClient;
+use crate::document_service::DocumentService;
+use crate::schema::ServerDb;
+use crate::{RequestContext, ServerError, ServerState};
+use db_rs::DbError;
+use lb_rs::model::api::{
+    UPDATES_WAIT_SECS, WaitForUpdatesError, WaitForUpdatesRequest, WaitForUpdatesResponse,
+};
+use lb_rs::model::file_like::FileLike;
+use lb_rs::model::file_metadata::{FileDiff, Owner};
+use lb_rs::model::signed_meta::SignedMeta;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use uuid::Uuid;
+
+/// Changes to metadata, sent to the requests waiting on them.
+pub type Updates = broadcast::Sender<Arc<MetadataUpdate>>;
+
+pub fn updates() -> Updates {
+    broadcast::channel(1024).0
+}
+
+/// Files whose metadata changed, and everyone who can see them: the owners and sharees of the
+/// files and of the folders they're in, before and after the change. They're found once, when the
+/// change is made, so that waiting requests don't each have to look.
+#[derive(Debug, Default)]
+pub struct MetadataUpdate {
+    pub ids: HashSet<Uuid>,
+    pub owners: HashSet<Owner>,
+    /// the newest metadata version the change made
+    pub version: u64,
+    /// the client that made the change, which already knows about it
+    pub session: Option<Uuid>,
+}
+
+impl MetadataUpdate {
+    /// `db` should already have the change
+    pub fn new(db: &ServerDb, diffs: &[FileDiff<SignedMeta>], session: Option<Uuid>) -> Self {
+        let mut update = Self { session, ..Default::default() };
+        for diff in diffs {
+            let id = *diff.new.id();
+            update.ids.insert(id);
+            if let Some(meta) = db.metas.get().get(&id) {
+                update.version = update.version.max(meta.version);
+            }
+            for meta in diff.old.iter().chain([&diff.new]) {
+                update.owners.insert(meta.owner());
+                update.owners.extend(
+                    meta.user_access_keys()
+                        .iter()
+                        .map(|k| Owner(k.encrypted_for)),
+                );
+                update.add_ancestor_sharees(db, *meta.parent());
+            }
+        }
+        update
+    }
+
+    /// Notes the change as the last one everyone it's visible to has seen, so that a request that
+    /// starts waiting later can tell it's missed it without looking through their files.
+    pub fn record(&self, db: &mut ServerDb) -> Result<(), DbError> {
+        for owner in &self.owners {
+            if db
+                .last_changed
+                .get()
+                .get(owner)
+                .is_none_or(|last| *last < self.version)
+            {
+                db.last_changed.insert(*owner, self.version)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// a file in a shared folder isn't shared with the folder's sharees itself
+    fn add_ancestor_sharees(&mut self, db: &ServerDb, mut id: Uuid) {
+        let mut seen = HashSet::new();
+        while seen.insert(id) {
+            let Some(meta) = db.metas.get().get(&id) else {
+                break;
+            };
+            self.owners.extend(
+                meta.user_access_keys()
+                    .iter()
+                    .filter(|k| !k.deleted)
+                    .map(|k| Owner(k.encrypted_for)),
+            );
+            id = *meta.parent();
+        }
+    }
+
+    fn visible_to(&self, owner: &Owner, groups: &[Owner]) -> bool {
+        self.owners.contains(owner) || groups.iter().any(|g| self.owners.contains(g))
+    }
+}
+
+impl<S, A, G, D> ServerState<S, A, G, D>
+where
+    S: StripeClient,
+    A: AppStoreClient,
+    G: GooglePlayClient,
+    D: DocumentService,
+{
+    pub fn notify(&self, update: MetadataUpdate) {
+        // nobody is waiting
+        let _ = self.updates.send(Arc::new(update));
+    }
+
+    pub async fn wait_for_updates(
+        &self, context: RequestContext<WaitForUpdatesRequest>,
+    ) -> Result<WaitForUpdatesResponse, ServerError<WaitForUpdatesError>> {
+        let owner = Owner(context.public_key);
+        let session = context.session;
+        let mut as_of = context.request.since_metadata_version;
+
+        // subscribed before looking, so that nothing changed in between is missed
+        let mut updates = self.updates.subscribe();
+        if self.updated_since(owner, as_of).await? {
+            return Ok(WaitForUpdatesResponse { updated: true, as_of });
+        }
+        let groups = Self::groups_of(&self.index_db.lock().await.groups, &owner);
+
+        let timeout = tokio::time::sleep(Duration::from_secs(UPDATES_WAIT_SECS));
+        tokio::pin!(timeout);
+        loop {
+            tokio::select! {
+                update = updates.recv() => match update {
+                    Ok(update) => {
+                        if session.is_some() && update.session == session {
+                            // the next wait starts past it, since versions at `since` count
+                            as_of = as_of.max(update.version + 1);
+                        } else if update.visible_to(&owner, &groups) {
+                            return Ok(WaitForUpdatesResponse { updated: true, as_of });
+                        }
+                    }
+                    // too many changes to keep up with; the client can find out which ones
+                    Err(RecvError::Lagged(_)) => {
+                        return Ok(WaitForUpdatesResponse { updated: true, as_of });
+                    }
+                    Err(RecvError::Closed) => {
+                        return Ok(WaitForUpdatesResponse { updated: false, as_of });
+                    }
+                },
+                _ = &mut timeout => return Ok(WaitForUpdatesResponse { updated: false, as_of }),
+            }
+        }
+    }
+
+    async fn updated_since(
+        &self, owner: Owner, since: u64,
+    ) -> Result<bool, ServerError<WaitForUpdatesError>> {
+        let db = self.index_db.lock().await;
+        if !db.accounts.get().contains_key(&owner) {
+            return Err(ClientError(WaitForUpdatesError::UserNotFound));
+        }
+        if !Self::groups_joined_since(&db.groups, &owner, since).is_empty() {
+            return Ok(true);
+        }
+
+        let mut owners = Self::groups_of(&db.groups, &owner);
+        owners.push(owner);
+        Ok(owners.iter().any(|owner| {
+            db.last_changed
+                .get()
+                .get(owner)
+                .is_some_and(|last| *last >= since)
+        }))
+    }
+}
@@ -0,0 +1,261 @@
+use crate::ServerError::ClientError;
+use crate::billing::app_store_client::AppStoreClient;
+use crate::billing::google_play_client::GooglePlayClient;
+use crate::billing::stripe_client::StripeClient;
+use crate::document_service::DocumentService;
+use crate::schema::ServerDb;
+use crate::{RequestContext, ServerError, ServerState};
+use db_rs::{Db, DbError};
+use lb_rs::model::access_info::UserAccessMode;
+use lb_rs::model::api::*;
+use lb_rs::model::clock::get_time;
+use lb_rs::model::crypto::EncryptedDocument;
+use lb_rs::model::file_like::FileLike;
+use lb_rs::model::file_metadata::{DocumentHmac, Owner};
+use lb_rs::model::server_tree::ServerTree;
+use lb_rs::model::tree_like::TreeLike;
+use std::ops::DerefMut;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+/// a link has a single snapshot, stored under the link's id
+pub const SNAPSHOT_HMAC: DocumentHmac = [0; 32];
+
+#[derive(Debug)]
+pub enum GetPublicSnapshotError {
+    NotFound,
+    BandwidthExceeded,
+}
+
+impl<S, A, G, D> ServerState<S, A, G, D>
+where
+    S: StripeClient,
+    A: AppStoreClient,
+    G: GooglePlayClient,
+    D: DocumentService,
+{
+    pub async fn create_public_link(
+        &self, context: RequestContext<CreatePublicLinkRequest>,
+    ) -> Result<(), ServerError<CreatePublicLinkError>> {
+        let CreatePublicLinkRequest { id, file_id, encrypted_key, snapshot } = context.request;
+        let requester = Owner(context.public_key);
+        let size_bytes = snapshot.value.len() as u64;
+
+        // phase 1: validate request before io
+        {
+            let mut lock = self.index_db.lock().await;
+            Self::check_public_link(lock.deref_mut(), requester, id, file_id, size_bytes)?;
+        }
+
+        self.document_service
+            .insert(&id, &SNAPSHOT_HMAC, &snapshot)
+            .await?;
+
+        // phase 2: access, the requester's cap, or the link's id may have been taken during io
+        let result = async {
+            let mut lock = self.index_db.lock().await;
+            let db = lock.deref_mut();
+            let tx = db.begin_transaction()?;
+
+            Self::check_public_link(db, requester, id, file_id, size_bytes)?;
+            let info = PublicLinkInfo {
+                id,
+                file_id,
+                owner: requester,
+                encrypted_key,
+                size_bytes,
+                created_at: get_time().0 as u64,
+            };
+            db.public_links.insert(id, info)?;
+
+            tx.drop_safely()?;
+            Ok::<_, ServerError<CreatePublicLinkError>>(())
+        }
+        .await;
+
+        match result {
+            // the snapshot belongs to the link that took the id
+            Err(ClientError(CreatePublicLinkError::LinkExists)) | Ok(()) => {}
+            Err(_) => {
+                self.document_service.delete(&id, &SNAPSHOT_HMAC).await?;
+                debug!(?id, "cleaned up snapshot after failed public link creation");
+            }
+        }
+
+        result
+    }
+
+    /// Checks that `requester` can share `file_id` publicly, which takes the same access as
+    /// editing it, and that the snapshot keeps them under their cap.
+    fn check_public_link(
+        db: &mut ServerDb, requester: Owner, id: Uuid, file_id: Uuid, size_bytes: u64,
+    ) -> Result<(), ServerError<CreatePublicLinkError>> {
+        use CreatePublicLinkError::*;
+
+        if db.public_links.get().contains_key(&id) {
+            return Err(ClientError(LinkExists));
+        }
+
+        let meta_exists = db.metas.get().get(&file_id).is_some();
+        let usage_cap = Self::get_cap(db, &requester.0).map_err(|err| internal!("{:?}", err))?;
+        let stored_usage = Self::stored_usage(db, &requester);
+        let groups = Self::groups_of(&db.groups, &requester);
+
+        let mut tree = ServerTree::new(
+            requester,
+            &mut db.owned_files,
+            &mut db.shared_files,
+            &mut db.file_children,
+            &mut db.metas,
+        )?
+        .with_groups(&groups)
+        .to_lazy();
+
+        if tree.maybe_find(&file_id).is_none() {
+            return Err(ClientError(if meta_exists { NotPermissioned } else { FileNotFound }));
+        }
+        if tree.calculate_deleted(&file_id)? {
+            return Err(ClientError(FileNotFound));
+        }
+        if tree.access_mode_in_groups(requester, &groups, &file_id)? < Some(UserAccessMode::Write) {
+            return Err(ClientError(NotPermissioned));
+        }
+
+        // links are charged to whoever created them, whoever owns the file
+        let usage = tree.calculate_usage(requester)? + stored_usage;
+        debug!(?usage, ?size_bytes, ?usage_cap, "usage caps on create public link");
+        if usage + size_bytes > usage_cap {
+            warn!(?requester, "user over cap");
+            return Err(ClientError(UsageIsOverDataCap));
+        }
+
+        Ok(())
+    }
+
+    pub async fn list_public_links(
+        &self, context: RequestContext<ListPublicLinksRequest>,
+    ) -> Result<ListPublicLinksResponse, ServerError<ListPublicLinksError>> {
+        let requester = Owner(context.public_key);
+        let db = self.index_db.lock().await;
+
+        let links = db
+            .public_links
+            .get()
+            .values()
+            .filter(|link| link.owner == requester)
+            .cloned()
+            .collect();
+
+        Ok(ListPublicLinksResponse { links })
+    }
+
+    pub async fn revoke_public_link(
+        &self, context: RequestContext<RevokePublicLinkRequest>,
+    ) -> Result<(), ServerError<RevokePublicLinkError>> {
+        use RevokePublicLinkError::*;
+        let RevokePublicLinkRequest { id } = context.request;
+        let requester = Owner(context.public_key);
+
+        let mut lock = self.index_db.lock().await;
+        let db = lock.deref_mut();
+        let tx = db.begin_transaction()?;
+
+        let link = db
+            .public_links
+            .get()
+            .get(&id)
+            .ok_or(ClientError(LinkNotFound))?;
+        if link.owner != requester {
+            return Err(ClientError(NotPermissioned));
+        }
+
+        db.public_links.remove(&id)?;
+        db.scheduled_file_cleanups
+            .insert((id, SNAPSHOT_HMAC), get_time().0)?;
+
+        tx.drop_safely()?;
+        Ok(())
+    }
+
+    /// Serves a link's encrypted snapshot to anyone who asks; what they can read is up to whether
+    /// they have the key from the link. Links stop working once the file they share is deleted.
+    pub async fn get_public_snapshot(
+        &self, id: Uuid,
+    ) -> Result<EncryptedDocument, ServerError<GetPublicSnapshotError>> {
+        use GetPublicSnapshotError::*;
+
+        let owner = {
+            let mut lock = self.index_db.lock().await;
+            let db = lock.deref_mut();
+
+            let Some(link) = db.public_links.get().get(&id).cloned() else {
+                return Err(ClientError(NotFound));
+            };
+            let Some(file_owner) = db.metas.get().get(&link.file_id).map(|meta| meta.owner())
+            else {
+                return Err(ClientError(NotFound));
+            };
+
+            let mut tree = ServerTree::new(
+                file_owner,
+                &mut db.owned_files,
+                &mut db.shared_files,
+                &mut db.file_children,
+                &mut db.metas,
+            )
+            .map_err(|err| internal!("{:?}", err))?
+            .to_lazy();
+            if tree
+                .calculate_deleted(&link.file_id)
+                .map_err(|err| internal!("{:?}", err))?
+            {
+                return Err(ClientError(NotFound));
+            }
+
+            link.owner
+        };
+
+        let Some(snapshot) = self.document_service.maybe_get(&id, &SNAPSHOT_HMAC).await? else {
+            return Err(ClientError(NotFound));
+        };
+
+        let mut lock = self.index_db.lock().await;
+        let db = lock.deref_mut();
+        let tx = db.begin_transaction()?;
+        if !self.record_egress(db, owner, snapshot.value.len())? {
+            return Err(ClientError(BandwidthExceeded));
+        }
+        tx.drop_safely()?;
+
+        Ok(snapshot)
+    }
+
+    /// bytes held by the snapshots of public links `owner` created
+    pub fn public_link_usage(db: &ServerDb, owner: &Owner) -> u64 {
+        db.public_links
+            .get()
+            .values()
+            .filter(|link| &link.owner == owner)
+            .map(|link| link.size_bytes)
+            .sum()
+    }
+
+    /// Removes links to files that no longer exist, scheduling their snapshots for cleanup.
+    pub fn release_public_links(db: &mut ServerDb) -> Result<(), DbError> {
+        let orphaned: Vec<Uuid> = db
+            .public_links
+            .get()
+            .iter()
+            .filter(|(_, link)| db.metas.get().get(&link.file_id).is_none())
+            .map(|(id, _)| *id)
+            .collect();
+
+        for id in orphaned {
+            db.public_links.remove(&id)?;
+            db.scheduled_file_cleanups
+                .insert((id, SNAPSHOT_HMAC), get_time().0)?;
+        }
+
+        Ok(())
+    }
+}
@@ -4,6 +4,7 @@ use crate::billing::google_play_client::GooglePlayClient;
 use crate::billing::stripe_client::StripeClient;
 use crate::config::Config;
 use crate::document_service::DocumentService;
+use crate::public_link_service::GetPublicSnapshotError;
 use crate::utils::get_build_info;
 use crate::{ServerError, ServerState, handle_version_header, router_service, verify_auth};
 use lazy_static::lazy_static;
@@ -18,6 +19,7 @@ use serde::de::DeserializeOwned;
 use std::collections::HashMap;
 use std::sync::Arc;
 use tracing::*;
+use uuid::Uuid;
 use warp::http::{HeaderValue, Method, StatusCode};
 use warp::hyper::body::Bytes;
 use warp::{Filter, Rejection, reject};
@@ -77,7 +79,9 @@ macro_rules! core_req {
     ($Req: ty, $handler: path, $state: ident) => {{
         use lb_rs::model::api::{ErrorWrapper, Request};
         use lb_rs::model::file_metadata::Owner;
-        use lb_rs::model::wire::{CLIENT_HEADER, OS_HEADER, WIRE_FORMAT_HEADER, WireFormat};
+        use lb_rs::model::wire::{
+            CLIENT_HEADER, OS_HEADER, SESSION_HEADER, WIRE_FORMAT_HEADER, WireFormat,
+        };
         use std::net::SocketAddr;
         use tracing::*;
         use $crate::router_service::{self, build_response, deserialize_and_check, method};
@@ -94,6 +98,7 @@ macro_rules! core_req {
             .and(warp::header::optional::<String>(WIRE_FORMAT_HEADER))
             .and(warp::header::optional::<String>(OS_HEADER))
             .and(warp::header::optional::<String>(CLIENT_HEADER))
+            .and(warp::header::optional::<String>(SESSION_HEADER))
             .and(warp::filters::addr::remote())
             .then(
                 |state: Arc<ServerState<S, A, G, D>>,
@@ -103,6 +108,7 @@ macro_rules! core_req {
                  wire_format_header: Option<String>,
                  os: Option<String>,
                  client_type: Option<String>,
+                 session: Option<String>,
                  ip: Option<SocketAddr>| {
                     if ip.is_none() {
                         tracing::error!("ip not present in request");
@@ -138,6 +144,17 @@ macro_rules! core_req {
                             .with_label_values(&[<$Req>::ROUTE])
                             .start_timer();
 
+                        if let Some(ip) = ip {
+                            if let Err(retry_after) =
+                                state.rate_limit_ip(<$Req>::ROUTE, ip.ip()).await
+                            {
+                                return router_service::rate_limited::<$Req>(
+                                    wire_format,
+                                    retry_after,
+                                );
+                            }
+                        }
+
                         let request: RequestWrapper<$Req> = match deserialize_and_check(
                             &state.config,
                             request,
@@ -173,6 +190,12 @@ macro_rules! core_req {
                         };
 
                         let req_pk = request.signed_request.public_key;
+                        if let Err(retry_after) =
+                            state.rate_limit_account(<$Req>::ROUTE, Owner(req_pk)).await
+                        {
+                            return router_service::rate_limited::<$Req>(wire_format, retry_after);
+                        }
+
                         let username = {
                             let db = state.index_db.lock().await;
                             match db
@@ -200,6 +223,7 @@ macro_rules! core_req {
                             request: request.signed_request.timestamped_value.value,
                             public_key: request.signed_request.public_key,
                             ip,
+                            session: session.and_then(|session| session.parse().ok()),
                         };
 
                         async move {
@@ -221,6 +245,11 @@ macro_rules! core_req {
                                     tracing::error!("internal: {e}");
                                     Err(ErrorWrapper::InternalError)
                                 }
+                                Err(ServerError::ClientUpdateRequired) => {
+                                    status = warp::http::StatusCode::BAD_REQUEST;
+                                    level = tracing::Level::WARN;
+                                    Err(ErrorWrapper::ClientUpdateRequired)
+                                }
                             };
                             let body = match wire_format.serialize(&to_serialize) {
                                 Ok(body) => body,
@@ -284,6 +313,22 @@ const RESPONSE_STREAM_CHUNK_BYTES: usize = 4 * 1024 * 1024;
 
 const RESPONSE_STREAM_THRESHOLD: usize = 1024 * 1024 * 1024;
 
+/// The response to a request that was rate limited, in the wire format it was made in.
+pub fn rate_limited<Req: Request>(
+    wire_format: WireFormat, retry_after: u64,
+) -> warp::http::Response<warp::hyper::Body>
+where
+    Req::Response: Serialize,
+    Req::Error: Serialize,
+{
+    let body = wire_format
+        .serialize::<Result<Req::Response, ErrorWrapper<Req::Error>>>(&Err(
+            ErrorWrapper::RateLimited { retry_after },
+        ))
+        .unwrap_or_default();
+    build_response(body, StatusCode::TOO_MANY_REQUESTS)
+}
+
 pub fn build_response(
     body: Vec<u8>, status: StatusCode,
 ) -> warp::http::Response<warp::hyper::Body> {
@@ -316,14 +361,30 @@ where
     D: DocumentService,
 {
     core_req!(NewAccountRequestV2, ServerState::new_account_v2, server_state)
+        .or(core_req!(NewAccountRequestV3, ServerState::new_account_v3, server_state))
         .or(core_req!(ChangeDocRequestV2, ServerState::change_doc_v2, server_state))
+        .or(core_req!(ChangeDocDeltaRequest, ServerState::change_doc_delta, server_state))
         .or(core_req!(UpsertRequestV2, ServerState::upsert_file_metadata_v2, server_state))
         .or(core_req!(GetDocRequest, ServerState::get_document, server_state))
+        .or(core_req!(GetDocRequestV2, ServerState::get_document_v2, server_state))
+        .or(core_req!(GetDocVersionsRequest, ServerState::get_document_versions, server_state))
+        .or(core_req!(EmptyTrashRequest, ServerState::empty_trash, server_state))
+        .or(core_req!(DeclareChunksRequest, ServerState::declare_chunks, server_state))
+        .or(core_req!(UpsertChunkRequest, ServerState::upsert_chunk, server_state))
+        .or(core_req!(GetChunkRequest, ServerState::get_chunk, server_state))
+        .or(core_req!(CreatePublicLinkRequest, ServerState::create_public_link, server_state))
+        .or(core_req!(ListPublicLinksRequest, ServerState::list_public_links, server_state))
+        .or(core_req!(RevokePublicLinkRequest, ServerState::revoke_public_link, server_state))
+        .or(core_req!(CreateGroupRequest, ServerState::create_group, server_state))
+        .or(core_req!(AddGroupMemberRequest, ServerState::add_group_member, server_state))
+        .or(core_req!(RemoveGroupMemberRequest, ServerState::remove_group_member, server_state))
+        .or(core_req!(GetGroupsRequest, ServerState::get_groups, server_state))
         .or(core_req!(GetPublicKeyRequest, ServerState::get_public_key, server_state))
         .or(core_req!(GetUsernameRequest, ServerState::get_username, server_state))
         .or(core_req!(GetUsageRequest, ServerState::get_usage, server_state))
         .or(core_req!(GetFileIdsRequest, ServerState::get_file_ids, server_state))
         .or(core_req!(GetUpdatesRequestV2, ServerState::get_updates_v2, server_state))
+        .or(core_req!(WaitForUpdatesRequest, ServerState::wait_for_updates, server_state))
         .or(core_req!(
             UpgradeAccountGooglePlayRequest,
             ServerState::upgrade_account_google_play,
@@ -368,6 +429,14 @@ where
         .or(core_req!(AdminValidateServerRequest, ServerState::admin_validate_server, server_state))
         .or(core_req!(AdminFileInfoRequest, ServerState::admin_file_info, server_state))
         .or(core_req!(AdminRebuildIndexRequest, ServerState::admin_rebuild_index, server_state))
+        .or(core_req!(
+            AdminMigrateDocumentsRequest,
+            ServerState::admin_migrate_documents,
+            server_state
+        ))
+        .or(core_req!(AdminCreateInviteRequest, ServerState::admin_create_invite, server_state))
+        .or(core_req!(AdminRevokeInviteRequest, ServerState::admin_revoke_invite, server_state))
+        .or(core_req!(AdminListInvitesRequest, ServerState::admin_list_invites, server_state))
         .or(core_req!(AdminSetUserTierRequest, ServerState::admin_set_user_tier, server_state))
 }
 
@@ -457,7 +526,9 @@ where
                             | ServerError::ClientError(StripeWebhookError::ParseError(_)) => {
                                 StatusCode::BAD_REQUEST
                             }
-                            ServerError::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                            ServerError::InternalError(_) | ServerError::ClientUpdateRequired => {
+                                StatusCode::INTERNAL_SERVER_ERROR
+                            }
                         };
 
                         warp::reply::with_status("".to_string(), status_code)
@@ -467,6 +538,60 @@ where
         )
 }
 
+/// The encrypted snapshot behind a public link, as the nonce followed by the ciphertext, which is
+/// the layout the viewer hands to the browser to decrypt. Unauthenticated by design.
+pub fn public_link_snapshot<S, A, G, D>(
+    server_state: &Arc<ServerState<S, A, G, D>>,
+) -> impl Filter<Extract = (impl warp::Reply + use<S, A, G, D>,), Error = warp::Rejection>
++ Clone
++ use<S, A, G, D>
+where
+    S: StripeClient,
+    A: AppStoreClient,
+    G: GooglePlayClient,
+    D: DocumentService,
+{
+    let cloned_state = server_state.clone();
+
+    warp::get()
+        .and(warp::path("public"))
+        .and(warp::path::param::<Uuid>())
+        .and(warp::path("snapshot"))
+        .and(warp::path::end())
+        .and(warp::any().map(move || cloned_state.clone()))
+        .then(|id: Uuid, state: Arc<ServerState<S, A, G, D>>| async move {
+            let span =
+                span!(Level::INFO, "matched_request", method = "GET", route = "/public/snapshot");
+            let _enter = span.enter();
+            let response = span.in_scope(|| state.get_public_snapshot(id)).await;
+
+            let (body, status) = match response {
+                Ok(snapshot) => {
+                    let mut body = snapshot.nonce;
+                    body.extend(snapshot.value);
+                    (body, StatusCode::OK)
+                }
+                Err(ServerError::ClientError(GetPublicSnapshotError::NotFound)) => {
+                    (vec![], StatusCode::NOT_FOUND)
+                }
+                Err(ServerError::ClientError(GetPublicSnapshotError::BandwidthExceeded)) => {
+                    (vec![], StatusCode::TOO_MANY_REQUESTS)
+                }
+                Err(e @ (ServerError::InternalError(_) | ServerError::ClientUpdateRequired)) => {
+                    error!("{:?}", e);
+                    (vec![], StatusCode::INTERNAL_SERVER_ERROR)
+                }
+            };
+
+            warp::http::Response::builder()
+                .status(status)
+                .header("Content-Type", "application/octet-stream")
+                .header("Cache-Control", "no-store")
+                .body(body)
+                .unwrap()
+        })
+}
+
 static PLAY_WEBHOOK_ROUTE: &str = "google_play_notification_webhook";
 
 pub fn google_play_notification_webhooks<S, A, G, D>(
@@ -522,7 +647,10 @@ where
                                 GooglePlayWebhookError::CannotRetrievePublicKey,
                             )
                             | ServerError::ClientError(GooglePlayWebhookError::CannotParseTime)
-                            | ServerError::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                            | ServerError::InternalError(_)
+                            | ServerError::ClientUpdateRequired => {
+                                StatusCode::INTERNAL_SERVER_ERROR
+                            }
                         };
 
                         warp::reply::with_status("".to_string(), status_code)
@@ -572,7 +700,9 @@ where
                         ServerError::ClientError(AppStoreNotificationError::InvalidJWS) => {
                             StatusCode::BAD_REQUEST
                         }
-                        ServerError::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                        ServerError::InternalError(_) | ServerError::ClientUpdateRequired => {
+                            StatusCode::INTERNAL_SERVER_ERROR
+                        }
                     };
 
                     warp::reply::with_status("".to_string(), status_code)
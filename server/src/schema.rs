@@ -1,10 +1,12 @@
 use db_rs::{LookupMap, LookupSet, LookupTable, Single};
 use db_rs_derive::Schema;
+use lb_rs::model::api::{DocumentVersion, GroupInfo, InviteInfo, PublicLinkInfo};
 use lb_rs::model::file_metadata::{DocumentHmac, Owner};
 use lb_rs::model::server_meta::ServerMeta;
 use lb_rs::service::debug::DebugInfo;
 use lb_rs::service::lb_id::LbID;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use uuid::Uuid;
 
 use crate::{billing::billing_model::SubscriptionProfile, defense::BandwidthReport};
@@ -12,6 +14,12 @@ use crate::{billing::billing_model::SubscriptionProfile, defense::BandwidthRepor
 #[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub struct OneKey;
 
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct DeltaBase {
+    pub hmac: DocumentHmac,
+    pub size_bytes: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Account {
     pub username: String,
@@ -37,4 +45,28 @@ pub struct ServerV5 {
     pub egress_by_owner: LookupTable<Owner, BandwidthReport>,
     pub scheduled_file_cleanups: LookupTable<(Uuid, DocumentHmac), i64>,
     pub debug_info: LookupMap<Owner, LbID, DebugInfo>,
+    /// prior versions of a document, newest first; the current version is never in here
+    pub document_versions: LookupTable<Uuid, Vec<DocumentVersion>>,
+    /// explicitly deleted files that can still be restored, with when they were deleted
+    pub trash: LookupTable<Uuid, u64>,
+    /// chunks stored for each chunked document, with their sizes
+    pub chunks: LookupTable<Uuid, HashMap<DocumentHmac, u64>>,
+    /// the chunks each manifest of a chunked document refers to, by the manifest's hmac
+    pub chunk_manifests: LookupTable<Uuid, HashMap<DocumentHmac, Vec<DocumentHmac>>>,
+    /// document contents stored as a delta, with the content the delta applies to
+    pub doc_deltas: LookupTable<(Uuid, DocumentHmac), DeltaBase>,
+    /// public links, whose snapshots are stored under the link's id
+    pub public_links: LookupTable<Uuid, PublicLinkInfo>,
+    /// groups files can be shared with; files shared with a group are in its `shared_files`
+    pub groups: LookupTable<Uuid, GroupInfo>,
+    /// invite codes made by admins, by code; revoked codes are removed
+    pub invites: LookupTable<String, InviteInfo>,
+    /// for accounts created with an invite code made by an admin, that admin
+    pub invited_by: LookupTable<Owner, Owner>,
+    /// when each manifest of a chunked document was declared, until it becomes the document's
+    /// content or a retained version of it
+    pub chunk_declarations: LookupTable<(Uuid, DocumentHmac), i64>,
+    /// the newest metadata version that changed a file each user or group can see, or could see
+    /// before the change
+    pub last_changed: LookupTable<Owner, u64>,
 }
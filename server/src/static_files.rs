@@ -4,7 +4,7 @@ use warp::{Filter, http::Response, hyper::Body};
 const APPLE_APP_SITE_ASSOCIATION: &str = include_str!("../static/apple-app-site-association");
 
 pub fn static_routes() -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
-    open_route().or(well_known_route())
+    open_route().or(public_link_route()).or(well_known_route())
 }
 
 fn open_route() -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
@@ -21,6 +21,21 @@ fn open_route() -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejecti
         })
 }
 
+fn public_link_route() -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::get()
+        .and(warp::path("public"))
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .map(|id: String| {
+            let span = span!(Level::INFO, "matched_request", method = "GET", route = "/public");
+            let _enter = span.enter();
+
+            info!("public link routed");
+
+            warp::reply::html(get_public_link_html(&id))
+        })
+}
+
 fn well_known_route() -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
     warp::path(".well-known")
         .and(warp::path("apple-app-site-association"))
@@ -62,3 +77,99 @@ pub fn get_files_preview_html(uuid: &str) -> String {
         uuid = uuid
     )
 }
+
+/// The key is in the url's fragment, which never reaches the server, so the snapshot is fetched
+/// and decrypted here in the browser.
+pub fn get_public_link_html(id: &str) -> String {
+    let id = id
+        .chars()
+        .filter(|c| c.is_ascii_hexdigit() || *c == '-')
+        .collect::<String>();
+    format!(
+        r#"
+<!doctype html>
+<html lang="en">
+    <head>
+        <meta charset="UTF-8" />
+        <meta name="viewport" content="width=device-width, initial-scale=1.0" />
+        <meta name="referrer" content="no-referrer" />
+        <title>Shared with Lockbook</title>
+        <style>
+            body {{ font-family: sans-serif; max-width: 60em; margin: 2em auto; padding: 0 1em; }}
+            pre {{ white-space: pre-wrap; word-wrap: break-word; background: #f4f4f4; padding: 1em; }}
+            li {{ cursor: pointer; }}
+        </style>
+        <script>
+            function fromBase64Url(text) {{
+                const base64 = text.replace(/-/g, "+").replace(/_/g, "/");
+                return Uint8Array.from(atob(base64), c => c.charCodeAt(0));
+            }}
+
+            function show(file) {{
+                const bytes = Uint8Array.from(atob(file.content), c => c.charCodeAt(0));
+                const view = document.getElementById("content");
+                view.replaceChildren();
+                document.getElementById("path").textContent = file.path;
+                try {{
+                    const pre = document.createElement("pre");
+                    pre.textContent = new TextDecoder("utf-8", {{ fatal: true }}).decode(bytes);
+                    view.appendChild(pre);
+                }} catch (e) {{
+                    const link = document.createElement("a");
+                    link.href = URL.createObjectURL(new Blob([bytes]));
+                    link.download = file.path.split("/").pop();
+                    link.textContent = "Download";
+                    view.appendChild(link);
+                }}
+            }}
+
+            window.onload = async function() {{
+                const status = document.getElementById("status");
+                try {{
+                    const response = await fetch("/public/{id}/snapshot");
+                    if (!response.ok) {{
+                        status.textContent = response.status == 404
+                            ? "This link doesn't exist or has been revoked."
+                            : "This link can't be viewed right now.";
+                        return;
+                    }}
+                    const body = new Uint8Array(await response.arrayBuffer());
+                    const key = await crypto.subtle.importKey(
+                        "raw", fromBase64Url(window.location.hash.slice(1)), "AES-GCM", false, ["decrypt"]
+                    );
+                    const plaintext = await crypto.subtle.decrypt(
+                        {{ name: "AES-GCM", iv: body.slice(0, 12) }}, key, body.slice(12)
+                    );
+                    const snapshot = JSON.parse(new TextDecoder().decode(plaintext));
+
+                    document.getElementById("name").textContent = snapshot.name;
+                    status.textContent = "";
+                    const files = document.getElementById("files");
+                    for (const file of snapshot.files) {{
+                        const item = document.createElement("li");
+                        item.textContent = file.path;
+                        item.onclick = () => show(file);
+                        files.appendChild(item);
+                    }}
+                    if (snapshot.files.length == 1) {{
+                        files.hidden = true;
+                        show(snapshot.files[0]);
+                    }}
+                }} catch (e) {{
+                    status.textContent = "This link is missing its key or is damaged.";
+                }}
+            }};
+        </script>
+    </head>
+    <body>
+        <h1 id="name">Shared with Lockbook</h1>
+        <p id="status">Decrypting...</p>
+        <ul id="files"></ul>
+        <h3 id="path"></h3>
+        <div id="content"></div>
+    </body>
+</html>
+"#,
+        id = id
+    )
+}
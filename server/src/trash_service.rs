@@ -0,0 +1,134 @@
+use crate::ServerError::ClientError;
+use crate::billing::app_store_client::AppStoreClient;
+use crate::billing::google_play_client::GooglePlayClient;
+use crate::billing::stripe_client::StripeClient;
+use crate::document_service::DocumentService;
+use crate::schema::ServerDb;
+use crate::{RequestContext, ServerError, ServerState};
+use db_rs::Db;
+use lb_rs::model::api::{EmptyTrashError, EmptyTrashRequest};
+use lb_rs::model::clock::get_time;
+use lb_rs::model::errors::LbResult;
+use lb_rs::model::file_like::FileLike;
+use lb_rs::model::file_metadata::Owner;
+use std::ops::DerefMut;
+use std::time::Duration;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+impl<S, A, G, D> ServerState<S, A, G, D>
+where
+    S: StripeClient,
+    A: AppStoreClient,
+    G: GooglePlayClient,
+    D: DocumentService,
+{
+    pub async fn empty_trash(
+        &self, context: RequestContext<EmptyTrashRequest>,
+    ) -> Result<(), ServerError<EmptyTrashError>> {
+        let owner = Owner(context.public_key);
+
+        let mut lock = self.index_db.lock().await;
+        let db = lock.deref_mut();
+        let tx = db.begin_transaction()?;
+
+        if !db.accounts.get().contains_key(&owner) {
+            return Err(ClientError(EmptyTrashError::UserNotFound));
+        }
+
+        let trashed: Vec<Uuid> = db
+            .trash
+            .get()
+            .keys()
+            .filter(|id| {
+                db.metas
+                    .get()
+                    .get(id)
+                    .is_some_and(|meta| meta.owner() == owner)
+            })
+            .copied()
+            .collect();
+        for id in trashed {
+            Self::purge(db, id)?;
+        }
+
+        tx.drop_safely()?;
+        Ok(())
+    }
+
+    /// Purges files that have been in the trash for longer than `trash_period`. Files deleted before
+    /// the trash existed were never put in it, so they're left as they were.
+    pub fn purge_expired_trash(db: &mut ServerDb, trash_period: Duration) -> LbResult<()> {
+        let now = get_time().0 as u64;
+        let trash_period = trash_period.as_millis() as u64;
+
+        let expired: Vec<Uuid> = db
+            .trash
+            .get()
+            .iter()
+            .filter(|(_, deleted_at)| now.saturating_sub(**deleted_at) > trash_period)
+            .map(|(id, _)| *id)
+            .collect();
+
+        if !expired.is_empty() {
+            info!("purging {} files from the trash", expired.len());
+        }
+        for id in expired {
+            Self::purge(db, id)?;
+        }
+
+        Ok(())
+    }
+
+    /// Removes a trashed file and its descendants from the index. Their contents, including
+    /// retained versions, are scheduled for cleanup right away. Clients drop the files on their
+    /// next sync because the server no longer reports their ids.
+    fn purge(db: &mut ServerDb, id: Uuid) -> LbResult<()> {
+        db.trash.remove(&id)?;
+
+        match db.metas.get().get(&id) {
+            Some(meta) if meta.explicitly_deleted() => {}
+            _ => return Ok(()),
+        }
+
+        let mut to_purge = vec![id];
+        let mut i = 0;
+        while i < to_purge.len() {
+            if let Some(children) = db.file_children.get().get(&to_purge[i]) {
+                to_purge.extend(children.iter().copied());
+            }
+            i += 1;
+        }
+
+        let now = get_time().0;
+        for id in to_purge {
+            let Some(meta) = db.metas.remove(&id)? else {
+                continue;
+            };
+
+            if let Some(hmac) = meta.document_hmac() {
+                db.scheduled_file_cleanups.insert((id, *hmac), now)?;
+            }
+            if let Some(versions) = db.document_versions.remove(&id)? {
+                for version in versions {
+                    db.scheduled_file_cleanups.insert((id, version.hmac), now)?;
+                }
+            }
+
+            // maintain indexes
+            let owner = meta.owner();
+            db.owned_files.remove(&owner, &id)?;
+            db.trash.remove(&id)?;
+            for user_access_key in meta.user_access_keys() {
+                db.shared_files
+                    .remove(&Owner(user_access_key.encrypted_for), &id)?;
+            }
+            if !db.file_children.remove(meta.parent(), &id)? {
+                warn!(?id, "purged a file its parent didn't have as a child");
+            }
+            db.file_children.clear_key(&id)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,54 @@
+//! Runs against a real bucket, like one in a local MinIO, and only when one is configured. The
+//! bucket must already exist:
+//!
+//! ```sh
+//! S3_ENDPOINT=http://localhost:9000 S3_BUCKET=lockbook-test \
+//!     S3_ACCESS_KEY_ID=minioadmin S3_SECRET_ACCESS_KEY=minioadmin \
+//!     cargo test -p lockbook-server --test s3_documents
+//! ```
+
+use lb_rs::model::crypto::AESEncrypted;
+use lockbook_server_lib::config::S3Config;
+use lockbook_server_lib::document_service::{DocumentService, S3Documents};
+use std::env;
+use uuid::Uuid;
+
+fn bucket() -> Option<S3Documents> {
+    env::var("S3_ENDPOINT").ok()?;
+    let mut config = S3Config::from_env_vars();
+    // runs don't see each other's leftovers
+    config.prefix = format!("{}{}/", config.prefix, Uuid::new_v4());
+    Some(S3Documents::from(&config))
+}
+
+#[tokio::test]
+async fn round_trip() {
+    let Some(bucket) = bucket() else { return };
+    let (id, hmac) = (Uuid::new_v4(), [1; 32]);
+    let content = AESEncrypted::new(b"content".to_vec(), b"nonce".to_vec());
+
+    assert!(!bucket.exists(&id, &hmac).await);
+    assert_eq!(bucket.maybe_get::<()>(&id, &hmac).await.unwrap(), None);
+
+    bucket.insert::<()>(&id, &hmac, &content).await.unwrap();
+    assert!(bucket.exists(&id, &hmac).await);
+    assert_eq!(bucket.get::<()>(&id, &hmac).await.unwrap(), content);
+
+    bucket.delete::<()>(&id, &hmac).await.unwrap();
+    assert!(!bucket.exists(&id, &hmac).await);
+    // deleting what isn't there succeeds
+    bucket.delete::<()>(&id, &hmac).await.unwrap();
+}
+
+#[tokio::test]
+async fn large_contents_round_trip_in_parts() {
+    let Some(bucket) = bucket() else { return };
+    let (id, hmac) = (Uuid::new_v4(), [2; 32]);
+    let value: Vec<u8> = (0..40 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+    let content = AESEncrypted::new(value, b"nonce".to_vec());
+
+    bucket.insert::<()>(&id, &hmac, &content).await.unwrap();
+    assert_eq!(bucket.get::<()>(&id, &hmac).await.unwrap(), content);
+
+    bucket.delete::<()>(&id, &hmac).await.unwrap();
+}